@@ -0,0 +1,24 @@
+// Cache key cost per message: the SHA-256 keys used before against the
+// BLAKE3 ones behind api::fast_hash. Run with `cargo bench --bench cache_keys`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sha2::{Digest, Sha256};
+use aiogram_rust::api;
+
+fn cache_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache_key");
+    // A chat message, a long post and a document
+    for size in [64usize, 1024, 64 * 1024] {
+        let message = "hello world ".repeat(size / 12 + 1)[..size].to_string();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("sha256", size), &message, |b, message| {
+            b.iter(|| format!("text_{:x}", Sha256::digest(message.as_bytes())))
+        });
+        group.bench_with_input(BenchmarkId::new("blake3", size), &message, |b, message| {
+            b.iter(|| format!("text_{}", api::fast_hash(message.as_bytes())))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cache_keys);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let header_path = PathBuf::from(&crate_dir).join("include").join("aiogram_rust.h");
+    
+    #[cfg(feature = "server")]
+    compile_protos();
+    
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    
+    let config = match cbindgen::Config::from_file(PathBuf::from(&crate_dir).join("cbindgen.toml")) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("cargo:warning=Failed to read cbindgen.toml: {}", e);
+            return;
+        }
+    };
+    
+    // Header generation failures should not block building the library itself
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(header_path);
+        },
+        Err(e) => println!("cargo:warning=Failed to generate C header: {}", e),
+    }
+}
+
+#[cfg(feature = "server")]
+fn compile_protos() {
+    println!("cargo:rerun-if-changed=proto/aiogram.proto");
+    
+    // Fall back to the vendored protoc so the server builds without a system
+    // protobuf install
+    if env::var_os("PROTOC").is_none() {
+        if let Ok(protoc) = protoc_bin_vendored::protoc_bin_path() {
+            env::set_var("PROTOC", protoc);
+        }
+    }
+    
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/aiogram.proto"], &["proto"])
+        .expect("Failed to compile proto/aiogram.proto");
+}
\ No newline at end of file
@@ -0,0 +1,480 @@
+/* Check library_abi_version() == AIOGRAM_ABI_VERSION after loading the library. */
+
+#ifndef AIOGRAM_RUST_H
+#define AIOGRAM_RUST_H
+
+/* Generated by cbindgen from src/lib.rs. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define AIOGRAM_ABI_VERSION 1
+
+#define FFI_OK 0
+
+#define FFI_ERR_NULL_POINTER -1
+
+#define FFI_ERR_INVALID_UTF8 -2
+
+#define FFI_ERR_OPERATION_FAILED -3
+
+#define FFI_ERR_PANIC -4
+
+#define MAX_PAYLOAD_LEN 64
+
+#define MAX_COMPARED_DOCUMENTS 500
+
+#define MAX_DOCUMENT_BYTES ((20 * 1024) * 1024)
+
+#define TELEGRAM_MESSAGE_LIMIT 4096
+
+#define TELEGRAM_CAPTION_LIMIT 1024
+
+#define SPAM_THRESHOLD 0.5
+
+#define GAZETTEER_CONFIDENCE 0.9
+
+#define MAX_PATTERNS 256
+
+#define MAX_TRANSCRIPT_WORDS 10000
+
+#define MAX_RETENTION_SECONDS (30 * DAY_SECONDS)
+
+#define COHORT_RETENTION_SECONDS (90 * DAY_SECONDS)
+
+#define MIN_TEXT_CHARS 20
+
+#define MAX_VARIANTS 100
+
+typedef struct Kdf Kdf;
+
+typedef struct Option_AnalyzerCallback Option_AnalyzerCallback;
+
+typedef struct Option_EmbedderCallback Option_EmbedderCallback;
+
+typedef struct Option_OcrCallback Option_OcrCallback;
+
+typedef struct Option_OcrReleaseCallback Option_OcrReleaseCallback;
+
+typedef struct Option_ReleaseCallback Option_ReleaseCallback;
+
+typedef struct ProfanitySeverity ProfanitySeverity;
+
+
+
+
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+char *analyze_text(const char *text);
+
+size_t analyze_text_into(const char *text, char *buf, size_t buf_len);
+
+char *analyze_text_with_options(const char *text, const char *options_json);
+
+char *extract_document_text(const uint8_t *data, size_t len);
+
+char *analyze_document(const uint8_t *data, size_t len, const char *options_json);
+
+char *get_script_stats(const char *text);
+
+char *frequency_report(const char *text, size_t limit);
+
+char *diff_texts(const char *old_text, const char *new_text);
+
+char *compare_documents(const char *texts_json);
+
+char *analyze_text_batch(const char *texts_json, const char *options_json);
+
+char *analyze_image(const uint8_t *data, size_t len, const char *options_json);
+
+char *analyze_transcript(const char *transcript_json, const char *options_json);
+
+uint64_t analyze_text_begin(const char *options_json);
+
+int32_t analyze_text_push(uint64_t handle, const char *chunk);
+
+char *analyze_text_finish(uint64_t handle);
+
+bool analyze_text_abort(uint64_t handle);
+
+char *summarize_text(const char *text, const char *options_json);
+
+char *truncate_for_telegram(const char *text,
+                            const char *entities_json,
+                            size_t limit,
+                            const char *options_json);
+
+char *normalize_text(const char *text, const char *options_json);
+
+char *moderate_message(int64_t chat_id, const char *text);
+
+char *moderate_image(int64_t chat_id, const uint8_t *data, size_t len);
+
+bool set_chat_moderation_policy(int64_t chat_id, const char *policy_json);
+
+bool clear_chat_moderation_policy(int64_t chat_id);
+
+bool open_moderation_store(const char *path);
+
+bool register_analyzer(const char *name,
+                       struct Option_AnalyzerCallback analyze,
+                       struct Option_ReleaseCallback release);
+
+bool unregister_analyzer(const char *name);
+
+bool load_gazetteer(const char *name, const char *path, const char *default_type);
+
+bool remove_gazetteer(const char *name);
+
+bool add_entity_pattern(const char *pattern_json);
+
+bool remove_entity_pattern(const char *name);
+
+char *list_entity_patterns(void);
+
+char *transliterate_text(const char *text, const char *direction);
+
+char *expand_abbreviations(const char *text);
+
+char *detect_languages_segmented(const char *text);
+
+char *pos_tag_text(const char *text, const char *language);
+
+char *extract_noun_phrases(const char *text, const char *language);
+
+bool load_pos_model(const char *language, const char *path);
+
+char *extract_entities(const char *text);
+
+char *get_readability(const char *text, const char *language);
+
+char *stem_word(const char *word, const char *language);
+
+char *get_emoji_stats(const char *text);
+
+char *find_pii(const char *text, const char *options_json);
+
+char *redact_pii(const char *text, const char *options_json);
+
+char *get_tag_stats(const char *text);
+
+char *get_structure_stats(const char *text);
+
+char *get_spam_score(const char *text);
+
+bool register_embedder(struct Option_EmbedderCallback embed, size_t dimensions);
+
+bool register_ocr_provider(struct Option_OcrCallback recognize,
+                           struct Option_OcrReleaseCallback release);
+
+bool set_ocr_command(const char *program, const char *args_json, uint64_t timeout_ms);
+
+char *ocr_image(const uint8_t *data, size_t len);
+
+char *classify_link(const char *url);
+
+char *scan_links(const char *text);
+
+char *decode_qr_payload(const char *payload);
+
+char *generate_qr(const char *text, const char *options_json);
+
+bool set_reply_set(const char *name, const char *replies_json);
+
+int64_t add_reply(const char *name, const char *reply);
+
+bool remove_reply(const char *name, size_t id);
+
+int64_t save_reply_set(const char *name, const char *path);
+
+int64_t load_reply_set(const char *name, const char *path);
+
+char *suggest_replies(const char *name, const char *message, size_t limit);
+
+char *summarize_conversation(const char *messages_json, size_t max_tokens);
+
+bool add_keyword_corpus_document(const char *text);
+
+void clear_keyword_corpus(void);
+
+char *match_command(const char *input, const char *commands_json, const char *options_json);
+
+char *text_similarity(const char *a, const char *b);
+
+bool add_duplicate_corpus_document(const char *id, const char *text);
+
+char *find_duplicates(const char *text);
+
+void clear_duplicate_corpus(void);
+
+bool configure_topic_model(const char *config_json);
+
+bool add_topic_corpus_document(const char *text);
+
+char *train_topic_model(void);
+
+char *get_topic_model_topics(void);
+
+void clear_topic_model(void);
+
+bool save_topic_model(const char *path);
+
+bool load_topic_model(const char *path);
+
+bool load_sentiment_lexicon(const char *path, bool extend_default);
+
+bool load_shadow_sentiment_lexicon(const char *path, bool extend_default);
+
+char *get_shadow_report(void);
+
+bool reset_shadow_report(void);
+
+bool record_chat_message(const char *message_json);
+
+bool record_chat_member_update(const char *update_json);
+
+char *get_member_report(int64_t chat_id, int64_t window_seconds);
+
+char *get_sticker_report(int64_t chat_id, int64_t window_seconds, size_t limit);
+
+bool record_reaction_count(const char *update_json);
+
+void record_post_metrics(int64_t chat_id, int64_t message_id, uint64_t views, uint64_t forwards);
+
+void record_chat_member_count(int64_t chat_id, uint64_t count);
+
+char *get_trending_posts(int64_t chat_id, size_t limit);
+
+char *recommend_slowmode(int64_t chat_id);
+
+char *get_chat_leaderboard(int64_t chat_id, const char *options_json);
+
+bool export_records(const char *name,
+                    const char *records_json,
+                    const char *format,
+                    const char *path);
+
+void record_sentiment(int64_t chat_id, int64_t user_id, int64_t date, double score);
+
+char *get_sentiment_trend(int64_t chat_id, int64_t user_id, int64_t window_seconds);
+
+char *create_deep_link_payload(const char *campaign, const char *data, const char *key);
+
+char *track_deep_link_start(const char *payload, int64_t user_id, const char *key);
+
+bool record_campaign_impressions(const char *campaign, uint64_t impressions);
+
+char *get_campaign_report(int64_t window_seconds);
+
+int64_t incr_counter(const char *name, int64_t by);
+
+int64_t get_counter(const char *name);
+
+bool reset_counter(const char *name);
+
+char *list_counters(const char *prefix);
+
+char *counter_rollup(const char *name, int64_t window_seconds, int64_t step_seconds);
+
+int64_t save_counters(const char *path);
+
+int64_t load_counters(const char *path);
+
+char *select_variant(const char *experiment, const char *context, const char *variants_json);
+
+bool record_variant_reward(const char *experiment,
+                           const char *context,
+                           const char *variant,
+                           double reward);
+
+char *get_variant_stats(const char *experiment, const char *context);
+
+int64_t save_experiments(const char *path);
+
+int64_t load_experiments(const char *path);
+
+bool open_fingerprint_store(const char *path);
+
+bool register_text_fingerprint(const char *id, const char *text);
+
+bool register_image_fingerprint(const char *id, const uint8_t *luma, size_t width, size_t height);
+
+char *find_text_matches(const char *text, uint32_t max_distance);
+
+char *find_image_matches(const uint8_t *luma, size_t width, size_t height, uint32_t max_distance);
+
+char *render_template(const char *template_, const char *vars_json, const char *lang);
+
+char *encrypt_message(const char *message, const char *key);
+
+char *encrypt_message_with(const char *message, const char *key, const char *options_json);
+
+char *decrypt_message(const char *encrypted_message, const char *key);
+
+char *encrypt_batch(const char *messages_json, const char *key);
+
+char *decrypt_batch(const char *encrypted_json, const char *key);
+
+uint64_t hash_begin(const char *algorithm);
+
+uint64_t hmac_begin(const char *algorithm, const uint8_t *key, size_t key_len);
+
+int32_t hash_update(uint64_t handle, const uint8_t *data, size_t data_len);
+
+char *hash_finish(uint64_t handle);
+
+bool hash_abort(uint64_t handle);
+
+char *process_realtime(const char *data);
+
+char *analyze_data(const char *data);
+
+char *analyze_poll(const char *poll_json);
+
+char *analyze_data_f64(const double *values, size_t len);
+
+char *analyze_metric_f64(const char *metric, const double *values, size_t len);
+
+bool set_current_tenant(const char *tenant_id, const char *user_id, const char *chat_id);
+
+char *analyze_text_for_tenant(const char *text, const char *tenant_id, const char *user_id);
+
+char *analyze_data_for_tenant(const char *data, const char *tenant_id, const char *user_id);
+
+char *process_realtime_for_tenant(const char *data, const char *tenant_id, const char *user_id);
+
+uint64_t submit_analysis(const char *kind, const char *input);
+
+uint64_t submit_analysis_for_tenant(const char *kind,
+                                    const char *input,
+                                    const char *tenant_id,
+                                    const char *user_id);
+
+char *poll_result(uint64_t job_id);
+
+bool cancel_job(uint64_t job_id);
+
+uint64_t enqueue_request(const char *request);
+
+char *drain_completions(void);
+
+void set_call_deadline(uint64_t timeout_ms);
+
+char *get_concurrency_stats(void);
+
+void set_progress_callback(void (*callback)(double progress, const char *stage));
+
+void clear_progress_callback(void);
+
+char *validate_input_json(const char *data, const char *schema_name);
+
+bool register_validation_schema(const char *schema_name, const char *rules_json);
+
+bool set_worker_threads(size_t threads);
+
+bool log_message(const char *level,
+                 const char *module,
+                 const char *message,
+                 const char *metadata_json);
+
+void set_alert_callback(void (*callback)(const char *alert_json));
+
+char *get_recent_alerts(size_t limit);
+
+char *get_performance_profiles_ffi(void);
+
+char *get_optimization_suggestions_ffi(void);
+
+char *get_tenant_metrics(const char *tenant_id);
+
+char *get_cost_report(const char *tenant_id);
+
+char *get_cost_lines(void);
+
+bool reset_costs(const char *tenant_id);
+
+char *dry_run_tenant_quota(const char *tenant_id, const char *user_id);
+
+char *simulate_rate_limit(const char *config_json, const char *trace_json);
+
+bool set_fault_injection(const char *config_json);
+
+char *get_fault_injection_stats(void);
+
+char *get_webhook_stats(void);
+
+bool pin_config_public_key(const char *public_key);
+
+bool apply_config_bundle(const char *bundle);
+
+bool patch_config(const char *patch);
+
+char *dump_effective_config(bool redact_secrets);
+
+int32_t analyze_text_buf(const uint8_t *text, size_t text_len, uint8_t **out_ptr, size_t *out_len);
+
+int32_t encrypt_message_buf(const uint8_t *message,
+                            size_t message_len,
+                            const uint8_t *key,
+                            size_t key_len,
+                            uint8_t **out_ptr,
+                            size_t *out_len);
+
+int32_t decrypt_message_buf(const uint8_t *encrypted_message,
+                            size_t encrypted_len,
+                            const uint8_t *key,
+                            size_t key_len,
+                            uint8_t **out_ptr,
+                            size_t *out_len);
+
+int32_t process_realtime_buf(const uint8_t *data,
+                             size_t data_len,
+                             uint8_t **out_ptr,
+                             size_t *out_len);
+
+int32_t analyze_data_buf(const uint8_t *data, size_t data_len, uint8_t **out_ptr, size_t *out_len);
+
+int32_t submit_analysis_buf(const uint8_t *kind,
+                            size_t kind_len,
+                            const uint8_t *input,
+                            size_t input_len,
+                            uint64_t *out_job_id);
+
+int32_t poll_result_buf(uint64_t job_id, uint8_t **out_ptr, size_t *out_len);
+
+int32_t export_records_buf(const char *name,
+                           const uint8_t *records_json,
+                           size_t records_len,
+                           const char *format,
+                           uint8_t **out_ptr,
+                           size_t *out_len);
+
+void free_buffer(uint8_t *ptr, size_t len);
+
+bool init_library(const char *config_json);
+
+char *get_init_report(void);
+
+char *warm_up(const char *options_json);
+
+char *fast_hash(const uint8_t *data, size_t data_len);
+
+bool save_cache_snapshot(const char *path, const char *key);
+
+void shutdown_library(void);
+
+uint32_t library_abi_version(void);
+
+void free_string(char *ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* AIOGRAM_RUST_H */
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+use crate::normalize::unify_arabic;
+
+// Chat shorthand spelled out before analysis. "imo" carries no sentiment and
+// "tbh" makes a poor keyword, but their expansions read like the rest of the
+// text to every stage. Abbreviations match whole words, ignoring case,
+// Arabic or Persian yeh and kaf, and non-joiners, so "س.ع", "سلام‌علیکم"
+// and "سلامن عليكم" typed on an Arabic keyboard all find their entry. The
+// active dictionary is the built-ins with analysis.abbreviations from the
+// config over them, rebuilt on every config reload.
+
+const ZWNJ: char = '\u{200C}';
+
+// (abbreviation, expansion)
+const BUILTIN_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("afaik", "as far as I know"),
+    ("asap", "as soon as possible"),
+    ("btw", "by the way"),
+    ("fyi", "for your information"),
+    ("idk", "I don't know"),
+    ("imho", "in my humble opinion"),
+    ("imo", "in my opinion"),
+    ("irl", "in real life"),
+    ("np", "no problem"),
+    ("omg", "oh my god"),
+    ("pls", "please"),
+    ("plz", "please"),
+    ("smh", "shaking my head"),
+    ("tbh", "to be honest"),
+    ("thx", "thanks"),
+    ("ty", "thank you"),
+    ("س.ع", "سلام علیکم"),
+    ("س ع", "سلام علیکم"),
+    ("سلام‌علیکم", "سلام علیکم"),
+    ("سلامن علیکم", "سلام علیکم"),
+    ("مچکرم", "متشکرم"),
+    ("مچکر", "متشکر"),
+    ("slm", "سلام"),
+    ("mrc", "مرسی"),
+];
+
+// Lowercased, with Persian yeh and kaf and without non-joiners
+fn fold(word: &str) -> String {
+    word.chars().filter(|&c| c != ZWNJ).flat_map(char::to_lowercase).map(unify_arabic).collect()
+}
+
+struct Dictionary {
+    // First folded word -> (all folded words, expansion), most words first
+    entries: HashMap<String, Vec<(Vec<String>, String)>>,
+}
+
+impl Dictionary {
+    fn from_config(config: &AppConfig) -> Self {
+        let mut expansions: HashMap<Vec<String>, String> = HashMap::new();
+        let custom = config.analysis.abbreviations.iter().map(|(abbreviation, expansion)| (abbreviation.as_str(), expansion.as_str()));
+        for (abbreviation, expansion) in BUILTIN_ABBREVIATIONS.iter().copied().chain(custom) {
+            let words: Vec<String> = abbreviation.unicode_words().map(fold).collect();
+            if words.is_empty() {
+                continue;
+            }
+            if expansion.trim().is_empty() {
+                expansions.remove(&words);
+            } else {
+                expansions.insert(words, expansion.trim().to_string());
+            }
+        }
+        
+        let mut entries: HashMap<String, Vec<(Vec<String>, String)>> = HashMap::new();
+        for (words, expansion) in expansions {
+            entries.entry(words[0].clone()).or_default().push((words, expansion));
+        }
+        for candidates in entries.values_mut() {
+            candidates.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        }
+        Self { entries }
+    }
+    
+    fn expand(&self, text: &str) -> String {
+        let words: Vec<(usize, &str)> = text.unicode_word_indices().collect();
+        let folded: Vec<String> = words.iter().map(|(_, word)| fold(word)).collect();
+        let mut expanded = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut i = 0;
+        while i < words.len() {
+            let matched = self.entries.get(&folded[i]).and_then(|candidates| {
+                candidates.iter().find(|(key, _)| matches_at(text, &words[i..], &folded[i..], key))
+            });
+            match matched {
+                Some((key, expansion)) => {
+                    let (last_start, last) = words[i + key.len() - 1];
+                    expanded.push_str(&text[copied..words[i].0]);
+                    expanded.push_str(expansion);
+                    copied = last_start + last.len();
+                    i += key.len();
+                }
+                None => i += 1,
+            }
+        }
+        expanded.push_str(&text[copied..]);
+        expanded
+    }
+}
+
+// Words of a phrase are apart by spaces only, and hashtags, mentions and
+// link paths are left alone
+fn matches_at(text: &str, words: &[(usize, &str)], folded: &[String], key: &[String]) -> bool {
+    if words.len() < key.len() || folded[..key.len()] != *key {
+        return false;
+    }
+    let start = words[0].0;
+    let end = words[key.len() - 1].0 + words[key.len() - 1].1.len();
+    let spaced = words[..key.len()].windows(2).all(|pair| {
+        text[pair[0].0 + pair[0].1.len()..pair[1].0].chars().all(|c| c.is_whitespace() && c != '\n')
+    });
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    spaced && !matches!(before, Some('#' | '@' | '/')) && !matches!(after, Some('/' | '@'))
+}
+
+lazy_static! {
+    static ref ACTIVE: RwLock<Arc<Dictionary>> = RwLock::new(Arc::new(Dictionary::from_config(&AppConfig::get())));
+}
+
+pub fn reload_config() {
+    *ACTIVE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(Dictionary::from_config(&AppConfig::get()));
+}
+
+pub fn expand(text: &str) -> String {
+    let dictionary = ACTIVE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    dictionary.expand(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_expansion() {
+        let mut config = AppConfig::default();
+        config.analysis.abbreviations.insert("LGTM".to_string(), "looks good to me".to_string());
+        config.analysis.abbreviations.insert("np".to_string(), String::new());
+        let dictionary = Dictionary::from_config(&config);
+        
+        assert_eq!(dictionary.expand("IMO this is great, tbh. lgtm"), "in my opinion this is great, to be honest. looks good to me");
+        // Dropped by the config
+        assert_eq!(dictionary.expand("np!"), "np!");
+        // Whole words only, and not in hashtags or links
+        assert_eq!(dictionary.expand("imodium #imo t.me/imo"), "imodium #imo t.me/imo");
+        
+        // Arabic spellings, a non-joiner and the two-word form
+        assert_eq!(dictionary.expand("سلام عليكم، س ع"), "سلام عليكم، سلام علیکم");
+        assert_eq!(dictionary.expand("سلام\u{200C}علیکم دوستان"), "سلام علیکم دوستان");
+        assert_eq!(dictionary.expand("سلامن عليكم"), "سلام علیکم");
+        // Phrases do not span lines
+        assert_eq!(dictionary.expand("س\nع"), "س\nع");
+    }
+}
\ No newline at end of file
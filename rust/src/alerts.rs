@@ -0,0 +1,490 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex, RwLock};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use crate::config::{AnomalyAlertRule, LogAlertRule};
+use crate::logging::{self, LogEntry, LogLevel};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub source: String,
+    pub message: String,
+    pub count: usize,
+    pub window_seconds: u64,
+    pub timestamp: DateTime<Utc>,
+    pub labels: HashMap<String, String>,
+}
+
+// Receives every fired alert; delivery mechanisms such as the host callback
+// register themselves here
+pub type AlertSubscriber = Arc<dyn Fn(&AlertEvent) + Send + Sync>;
+
+// Host callback receiving each alert as a JSON document
+pub type AlertCallback = extern "C" fn(alert_json: *const c_char);
+
+struct CompiledRule {
+    rule: LogAlertRule,
+    min_level: LogLevel,
+    hits: VecDeque<DateTime<Utc>>,
+}
+
+impl CompiledRule {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        if entry.level.severity() < self.min_level.severity() {
+            return false;
+        }
+        if let Some(ref module) = self.rule.module {
+            if !logging::module_matches(&entry.module, module) {
+                return false;
+            }
+        }
+        if let Some(ref needle) = self.rule.message_contains {
+            if !entry.message.contains(needle.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+    
+    // Records a hit and reports whether the threshold was exceeded. The
+    // window restarts after firing so a sustained burst alerts once per window.
+    fn record(&mut self, at: DateTime<Utc>) -> Option<usize> {
+        let window_start = at - Duration::seconds(self.rule.window_seconds as i64);
+        while self.hits.front().map(|hit| *hit < window_start).unwrap_or(false) {
+            self.hits.pop_front();
+        }
+        self.hits.push_back(at);
+        
+        if self.hits.len() > self.rule.threshold {
+            let count = self.hits.len();
+            self.hits.clear();
+            Some(count)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AnomalyDirection {
+    HIGH,
+    LOW,
+}
+
+// Sent as the "state" label of anomaly alerts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertState {
+    TRIGGERED,
+    ACTIVE,
+    RESOLVED,
+}
+
+#[derive(Debug, Clone)]
+struct Anomaly {
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    last_notified: DateTime<Utc>,
+    observations: usize,
+    // False for a relapse inside the cool-down until a reminder is due
+    announced: bool,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+// One state per metric and direction, so a metric that keeps reading high
+// is announced once instead of on every evaluation
+#[derive(Default)]
+struct AnomalyTracker {
+    anomalies: HashMap<(String, AnomalyDirection), Anomaly>,
+}
+
+impl AnomalyTracker {
+    // anomaly is None for a normal observation of the metric
+    fn observe(&mut self, rule: &AnomalyAlertRule, metric: &str, anomaly: Option<AnomalyDirection>, value: f64, at: DateTime<Utc>) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        let cooldown = Duration::seconds(rule.cooldown_seconds);
+        
+        if let Some(direction) = anomaly {
+            let key = (metric.to_string(), direction);
+            match self.anomalies.get_mut(&key) {
+                Some(state) if state.resolved_at.is_none() || at - state.resolved_at.unwrap_or(at) < cooldown => {
+                    state.resolved_at = None;
+                    state.last_seen = at;
+                    state.observations += 1;
+                    if rule.reminder_seconds > 0 && at - state.last_notified >= Duration::seconds(rule.reminder_seconds) {
+                        state.last_notified = at;
+                        state.announced = true;
+                        events.push(anomaly_event(metric, direction, AlertState::ACTIVE, value, state));
+                    }
+                }
+                _ => {
+                    let state = Anomaly { first_seen: at, last_seen: at, last_notified: at, observations: 1, announced: true, resolved_at: None };
+                    events.push(anomaly_event(metric, direction, AlertState::TRIGGERED, value, &state));
+                    self.anomalies.insert(key, state);
+                }
+            }
+        }
+        
+        let resolve_after = Duration::seconds(rule.resolve_after_seconds);
+        for ((name, direction), state) in self.anomalies.iter_mut() {
+            if name != metric || Some(*direction) == anomaly || state.resolved_at.is_some() || at - state.last_seen < resolve_after {
+                continue;
+            }
+            state.resolved_at = Some(at);
+            if state.announced {
+                events.push(anomaly_event(metric, *direction, AlertState::RESOLVED, value, state));
+            }
+            // A relapse stays quiet until a reminder is due
+            state.announced = false;
+        }
+        self.anomalies.retain(|_, state| state.resolved_at.map(|resolved| at - resolved < cooldown).unwrap_or(true));
+        events
+    }
+}
+
+fn anomaly_event(metric: &str, direction: AnomalyDirection, state: AlertState, value: f64, anomaly: &Anomaly) -> AlertEvent {
+    let side = if direction == AnomalyDirection::HIGH { "high" } else { "low" };
+    let message = match state {
+        AlertState::TRIGGERED => format!("{} is anomalously {} ({:.2})", metric, side, value),
+        AlertState::ACTIVE => format!("{} is still anomalously {} ({:.2}, {} anomalous observations)", metric, side, value, anomaly.observations),
+        AlertState::RESOLVED => format!("{} is back to normal ({:.2}) after {} anomalous observations", metric, value, anomaly.observations),
+    };
+    let at = anomaly.resolved_at.unwrap_or(anomaly.last_seen);
+    
+    let mut labels = HashMap::new();
+    labels.insert("metric".to_string(), metric.to_string());
+    labels.insert("direction".to_string(), side.to_string());
+    labels.insert("state".to_string(), format!("{:?}", state).to_lowercase());
+    if state == AlertState::RESOLVED {
+        // Low webhook priority
+        labels.insert("level".to_string(), "info".to_string());
+    }
+    AlertEvent {
+        rule: "anomaly".to_string(),
+        source: "anomaly".to_string(),
+        message,
+        count: anomaly.observations,
+        window_seconds: (at - anomaly.first_seen).num_seconds().max(0) as u64,
+        timestamp: at,
+        labels,
+    }
+}
+
+pub struct AlertManager {
+    rules: Mutex<Vec<CompiledRule>>,
+    anomalies: Mutex<AnomalyTracker>,
+    subscribers: RwLock<Vec<AlertSubscriber>>,
+    recent: Mutex<VecDeque<AlertEvent>>,
+    max_recent: usize,
+}
+
+impl AlertManager {
+    pub fn new() -> Self {
+        Self {
+            rules: Mutex::new(Vec::new()),
+            anomalies: Mutex::new(AnomalyTracker::default()),
+            subscribers: RwLock::new(Vec::new()),
+            recent: Mutex::new(VecDeque::new()),
+            max_recent: 1000,
+        }
+    }
+    
+    pub fn set_log_rules(&self, rules: &[LogAlertRule]) {
+        let compiled = rules.iter()
+            .filter_map(|rule| {
+                LogLevel::parse(&rule.level).map(|min_level| CompiledRule {
+                    rule: rule.clone(),
+                    min_level,
+                    hits: VecDeque::new(),
+                })
+            })
+            .collect();
+        
+        if let Ok(mut current) = self.rules.lock() {
+            *current = compiled;
+        }
+    }
+    
+    // Whether some rule counts entries at this level, so the logger builds
+    // them even when its own level or enabled setting drops them
+    pub fn watches(&self, level: &LogLevel) -> bool {
+        match self.rules.lock() {
+            Ok(rules) => rules.iter().any(|rule| level.severity() >= rule.min_level.severity()),
+            Err(_) => false,
+        }
+    }
+    
+    pub fn observe_log(&self, entry: &LogEntry) {
+        let fired: Vec<AlertEvent> = match self.rules.lock() {
+            Ok(mut rules) => rules.iter_mut()
+                .filter(|rule| rule.matches(entry))
+                .filter_map(|rule| {
+                    rule.record(entry.timestamp).map(|count| {
+                        let mut labels = HashMap::new();
+                        labels.insert("level".to_string(), rule.rule.level.clone());
+                        if let Some(ref module) = rule.rule.module {
+                            labels.insert("module".to_string(), module.clone());
+                        }
+                        
+                        AlertEvent {
+                            rule: rule.rule.name.clone(),
+                            source: "log".to_string(),
+                            message: format!(
+                                "{} matching log entries in the last {}s (threshold {})",
+                                count, rule.rule.window_seconds, rule.rule.threshold
+                            ),
+                            count,
+                            window_seconds: rule.rule.window_seconds,
+                            timestamp: entry.timestamp,
+                            labels,
+                        }
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        
+        for event in fired {
+            self.emit(event);
+        }
+    }
+    
+    // Emits only state changes and due reminders of the metric's anomalies
+    pub fn observe_metric(&self, rule: &AnomalyAlertRule, metric: &str, anomaly: Option<AnomalyDirection>, value: f64, at: DateTime<Utc>) {
+        let events = self.anomalies.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).observe(rule, metric, anomaly, value, at);
+        for event in events {
+            self.emit(event);
+        }
+    }
+    
+    pub fn subscribe(&self, subscriber: AlertSubscriber) {
+        if let Ok(mut subscribers) = self.subscribers.write() {
+            subscribers.push(subscriber);
+        }
+    }
+    
+    pub fn emit(&self, event: AlertEvent) {
+        if let Ok(mut recent) = self.recent.lock() {
+            recent.push_back(event.clone());
+            while recent.len() > self.max_recent {
+                recent.pop_front();
+            }
+        }
+        
+        let subscribers: Vec<AlertSubscriber> = match self.subscribers.read() {
+            Ok(subscribers) => subscribers.clone(),
+            Err(_) => Vec::new(),
+        };
+        for subscriber in subscribers {
+            subscriber(&event);
+        }
+    }
+    
+    pub fn get_recent_alerts(&self, limit: usize) -> Vec<AlertEvent> {
+        match self.recent.lock() {
+            Ok(recent) => recent.iter().rev().take(limit).cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Global alert manager
+lazy_static! {
+    static ref ALERT_MANAGER: AlertManager = {
+        let manager = AlertManager::new();
+        manager.set_log_rules(&crate::config::AppConfig::get().alerts.log_rules);
+        manager.subscribe(Arc::new(notify_host));
+        manager
+    };
+    static ref ALERT_CALLBACK: RwLock<Option<AlertCallback>> = RwLock::new(None);
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&ALERT_MANAGER);
+    lazy_static::initialize(&ALERT_CALLBACK);
+    Ok(())
+}
+
+fn notify_host(event: &AlertEvent) {
+    let callback = match ALERT_CALLBACK.read() {
+        Ok(cb) => *cb,
+        Err(_) => None,
+    };
+    
+    if let Some(callback) = callback {
+        if let Ok(json) = serde_json::to_string(event) {
+            if let Ok(json_c) = CString::new(json) {
+                callback(json_c.as_ptr());
+            }
+        }
+    }
+}
+
+// Public alert functions
+pub fn observe_log(entry: &LogEntry) {
+    ALERT_MANAGER.observe_log(entry);
+}
+
+pub fn watches(level: &LogLevel) -> bool {
+    ALERT_MANAGER.watches(level)
+}
+
+// Raised by subsystems other than the log rules, e.g. security events
+pub fn emit(event: AlertEvent) {
+    ALERT_MANAGER.emit(event);
+}
+
+// Shared by the batch (analyze_metric) and realtime anomaly detectors;
+// anomaly is None when the value looked normal
+pub fn observe_metric(metric: &str, anomaly: Option<AnomalyDirection>, value: f64) {
+    let rule = crate::config::AppConfig::get().alerts.anomalies;
+    if rule.enabled {
+        ALERT_MANAGER.observe_metric(&rule, metric, anomaly, value, Utc::now());
+    }
+}
+
+pub fn subscribe(subscriber: AlertSubscriber) {
+    ALERT_MANAGER.subscribe(subscriber);
+}
+
+pub fn get_recent_alerts(limit: usize) -> Vec<AlertEvent> {
+    ALERT_MANAGER.get_recent_alerts(limit)
+}
+
+pub fn set_callback(callback: Option<AlertCallback>) {
+    if let Ok(mut current) = ALERT_CALLBACK.write() {
+        *current = callback;
+    }
+}
+
+// Re-reads the alert rules after a config change
+pub fn reload_config() {
+    ALERT_MANAGER.set_log_rules(&crate::config::AppConfig::get().alerts.log_rules);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn entry(level: LogLevel, module: &str, at: DateTime<Utc>) -> LogEntry {
+        LogEntry {
+            timestamp: at,
+            level,
+            message: "Decryption failed".to_string(),
+            module: module.to_string(),
+            function: "test".to_string(),
+            line: 1,
+            user_id: None,
+            request_id: None,
+            duration_ms: None,
+            metadata: HashMap::new(),
+        }
+    }
+    
+    fn crypto_rule() -> LogAlertRule {
+        LogAlertRule {
+            name: "crypto_errors".to_string(),
+            level: "error".to_string(),
+            module: Some("crypto".to_string()),
+            message_contains: None,
+            threshold: 3,
+            window_seconds: 300,
+        }
+    }
+    
+    #[test]
+    fn test_rule_fires_above_threshold() {
+        let manager = AlertManager::new();
+        manager.set_log_rules(&[crypto_rule()]);
+        let now = Utc::now();
+        
+        for _ in 0..3 {
+            manager.observe_log(&entry(LogLevel::ERROR, "aiogram_rust::crypto", now));
+        }
+        // Other modules and lower levels are not counted
+        manager.observe_log(&entry(LogLevel::ERROR, "cache", now));
+        manager.observe_log(&entry(LogLevel::WARN, "crypto", now));
+        assert!(manager.get_recent_alerts(10).is_empty());
+        
+        manager.observe_log(&entry(LogLevel::CRITICAL, "crypto", now));
+        let alerts = manager.get_recent_alerts(10);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule, "crypto_errors");
+        assert_eq!(alerts[0].count, 4);
+    }
+    
+    #[test]
+    fn test_watches_levels_of_its_rules() {
+        let manager = AlertManager::new();
+        assert!(!manager.watches(&LogLevel::CRITICAL));
+        manager.set_log_rules(&[crypto_rule()]);
+        assert!(manager.watches(&LogLevel::ERROR));
+        assert!(!manager.watches(&LogLevel::WARN));
+    }
+    
+    #[test]
+    fn test_old_hits_leave_window() {
+        let manager = AlertManager::new();
+        manager.set_log_rules(&[crypto_rule()]);
+        let start = Utc::now();
+        
+        for i in 0..6 {
+            let at = start + Duration::seconds(i * 200);
+            manager.observe_log(&entry(LogLevel::ERROR, "crypto", at));
+        }
+        assert!(manager.get_recent_alerts(10).is_empty());
+    }
+    
+    #[test]
+    fn test_anomaly_lifecycle() {
+        let manager = AlertManager::new();
+        let rule = AnomalyAlertRule { reminder_seconds: 600, resolve_after_seconds: 120, cooldown_seconds: 300, ..Default::default() };
+        let start = Utc::now();
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+        let states = |manager: &AlertManager| manager.get_recent_alerts(100).iter().rev().map(|event| event.labels["state"].clone()).collect::<Vec<_>>();
+        
+        // Announced once, then quiet until the reminder is due
+        for seconds in [0, 60, 120, 660] {
+            manager.observe_metric(&rule, "messages_per_minute", Some(AnomalyDirection::HIGH), 900.0, at(seconds));
+        }
+        assert_eq!(states(&manager), vec!["triggered", "active"]);
+        
+        // Resolves once normal for resolve_after_seconds
+        manager.observe_metric(&rule, "messages_per_minute", None, 40.0, at(700));
+        manager.observe_metric(&rule, "messages_per_minute", None, 40.0, at(800));
+        assert_eq!(states(&manager).last().unwrap(), "resolved");
+        
+        // A relapse inside the cool-down is not a new anomaly; the other
+        // direction is
+        manager.observe_metric(&rule, "messages_per_minute", Some(AnomalyDirection::HIGH), 900.0, at(900));
+        manager.observe_metric(&rule, "messages_per_minute", Some(AnomalyDirection::LOW), 1.0, at(1000));
+        let alerts = manager.get_recent_alerts(1);
+        assert_eq!(states(&manager).len(), 4);
+        assert_eq!((alerts[0].labels["direction"].as_str(), alerts[0].labels["state"].as_str()), ("low", "triggered"));
+    }
+    
+    #[test]
+    fn test_subscribers_receive_alerts() {
+        let manager = AlertManager::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        manager.subscribe(Arc::new(move |event: &AlertEvent| {
+            sink.lock().unwrap().push(event.rule.clone());
+        }));
+        
+        manager.emit(AlertEvent {
+            rule: "manual".to_string(),
+            source: "test".to_string(),
+            message: "test alert".to_string(),
+            count: 1,
+            window_seconds: 0,
+            timestamp: Utc::now(),
+            labels: HashMap::new(),
+        });
+        
+        assert_eq!(*received.lock().unwrap(), vec!["manual".to_string()]);
+    }
+}
\ No newline at end of file
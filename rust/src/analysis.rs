@@ -1,13 +1,53 @@
+#[cfg(feature = "nlp")]
 use regex::Regex;
+#[cfg(feature = "nlp")]
 use unicode_segmentation::UnicodeSegmentation;
+#[cfg(feature = "nlp")]
 use whatlang::Lang;
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "nlp")]
 use std::collections::HashMap;
+#[cfg(feature = "stats")]
 use ndarray::{Array1, Array2};
+#[cfg(feature = "stats")]
 use statrs::statistics::Statistics;
 use chrono::{DateTime, Utc};
 use std::collections::HashSet;
+#[cfg(feature = "nlp")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "nlp")]
+use std::collections::BTreeMap;
+#[cfg(feature = "nlp")]
+use lazy_static::lazy_static;
+#[cfg(feature = "nlp")]
+use std::sync::RwLock;
+#[cfg(feature = "nlp")]
+use crate::config::{AppConfig, KeywordAlgorithm};
+use crate::{costs, deadline, jobs, progress};
+#[cfg(feature = "stats")]
+use crate::{alerts, parsers};
+#[cfg(feature = "nlp")]
+use crate::{abbreviations, emoji, entity_patterns, gazetteers, keywords, language, plugins, readability, sentiment, shadow, spam, structure, summary, tags};
+#[cfg(feature = "nlp")]
+use crate::language::{LanguageSpan, ScriptStats};
+#[cfg(feature = "nlp")]
+use crate::normalize::TextNormalizer;
+#[cfg(feature = "nlp")]
+use crate::readability::ReadabilityReport;
+#[cfg(feature = "nlp")]
+use crate::emoji::EmojiStats;
+#[cfg(feature = "nlp")]
+use crate::spam::SpamScore;
+#[cfg(feature = "nlp")]
+use crate::structure::StructureStats;
+#[cfg(feature = "nlp")]
+use crate::tags::TagStats;
+#[cfg(feature = "nlp")]
+pub use crate::stemming::stem;
+#[cfg(feature = "nlp")]
+pub use crate::transliterate::{transliterate, TransliterationDirection};
 
+#[cfg(feature = "nlp")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TextAnalysisResult {
     pub char_count: usize,
@@ -15,24 +55,60 @@ pub struct TextAnalysisResult {
     pub sentence_count: usize,
     pub language: String,
     pub language_confidence: f64,
+    // Per segment, for messages that switch languages; keywords, summary
+    // and topics pick stop words and stems from these
+    #[serde(default)]
+    pub language_spans: Vec<LanguageSpan>,
+    // Characters per script, plus emoji, digits and symbols
+    #[serde(default)]
+    pub script_stats: ScriptStats,
     pub sentiment: String,
     pub sentiment_score: f64,
     pub keywords: Vec<String>,
+    #[serde(default)]
+    pub emoji_stats: EmojiStats,
+    // #hashtags, @mentions and $CASHTAGS, kept apart from the keywords
+    #[serde(default)]
+    pub tags: TagStats,
+    // Only with AnalysisOptions::spam_score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spam: Option<SpamScore>,
+    // Only with AnalysisOptions::structure; of the text as passed in, code
+    // included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structure: Option<StructureStats>,
     pub entities: Vec<Entity>,
     pub summary: String,
-    pub readability_score: f64,
+    // Formula picked for the language, its score and the counts behind it
+    pub readability_score: ReadabilityReport,
     pub topics: Vec<Topic>,
     pub plagiarism_score: f64,
     pub processing_time: u64,
+    // Milliseconds per stage that ran, keyed "<stage>_ms" by the stage names
+    // progress callbacks see, e.g. "ner_ms" or "topic_modeling_ms"
+    #[serde(default)]
+    pub stage_timings: BTreeMap<String, f64>,
+    // Output of analyzers registered through plugins.rs, keyed by name
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub plugins: serde_json::Map<String, serde_json::Value>,
+    // The call's deadline passed and later stages were skipped; their fields
+    // keep empty defaults
+    #[serde(default)]
+    pub timed_out: bool,
 }
 
+#[cfg(feature = "nlp")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Entity {
     pub name: String,
     pub entity_type: String,
     pub confidence: f64,
+    // Byte offsets of name in the analyzed text
+    pub start: usize,
+    pub end: usize,
 }
 
+#[cfg(feature = "nlp")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Topic {
     pub name: String,
@@ -40,6 +116,7 @@ pub struct Topic {
     pub keywords: Vec<String>,
 }
 
+#[cfg(feature = "stats")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DataAnalysisResult {
     pub record_count: usize,
@@ -57,6 +134,7 @@ pub struct DataAnalysisResult {
     pub visualization_data: VisualizationData,
 }
 
+#[cfg(feature = "stats")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VisualizationData {
     pub histogram: Vec<(f64, usize)>,
@@ -64,46 +142,303 @@ pub struct VisualizationData {
     pub correlation_matrix: Vec<Vec<f64>>,
 }
 
+// Per-call tuning of the text pipeline; unset fields keep the defaults
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalysisOptions {
+    pub skip_ner: bool,
+    pub skip_topics: bool,
+    pub skip_plagiarism: bool,
+    // 0 disables the summary stage
+    pub summary_sentences: usize,
+    // Budget of the summary in UTF-16 code units; 0 for none
+    pub summary_max_chars: usize,
+    // Trusted as the text language instead of running detection
+    pub language_hint: Option<String>,
+    // Leaves out the analyzers registered through register_analyzer
+    pub skip_plugins: bool,
+    // Counts words and extracts keywords with emoji and emoticons removed
+    pub strip_emoji: bool,
+    // Adds the spam / scam heuristic score
+    pub spam_score: bool,
+    // Adds headings, lists, quotes and code found in the markup
+    pub structure: bool,
+    // Blanks out code blocks and inline code before every stage, so code
+    // does not count toward words, keywords, sentiment or readability;
+    // offsets still refer to the text as passed in
+    pub skip_code: bool,
+    // Normalizes the text before every stage; counts and entity offsets then
+    // refer to the normalized text
+    pub normalize: Option<TextNormalizer>,
+    // Groups inflections ("run", "running", "ran") in keywords and topics
+    pub stem: bool,
+    // Reads Latin-script words as Finglish and writes them in Persian
+    // script before every stage, after normalize
+    pub finglish: bool,
+    // Spells out chat abbreviations ("imo", "س.ع") before every stage, after
+    // normalize and before finglish
+    pub expand_abbreviations: bool,
+    // Takes keywords only from noun phrases, so verbs and adverbs drop out
+    pub noun_phrase_keywords: bool,
+}
+
+#[cfg(feature = "nlp")]
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            skip_ner: false,
+            skip_topics: false,
+            skip_plagiarism: false,
+            summary_sentences: 2,
+            summary_max_chars: summary::TELEGRAM_MESSAGE_LIMIT,
+            language_hint: None,
+            skip_plugins: false,
+            strip_emoji: false,
+            spam_score: false,
+            structure: false,
+            skip_code: false,
+            normalize: None,
+            stem: true,
+            finglish: false,
+            expand_abbreviations: false,
+            noun_phrase_keywords: false,
+        }
+    }
+}
+
+#[cfg(feature = "nlp")]
+impl AnalysisOptions {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid analysis options: {}", e))
+    }
+}
+
+// Compiled once instead of on every call
+#[cfg(feature = "nlp")]
+lazy_static! {
+    static ref SENTENCE_REGEX: Regex = Regex::new(r"[.!?]+").unwrap();
+    // AnalysisConfig::keyword_algorithm, read once per config change
+    static ref KEYWORD_ALGORITHM: RwLock<Option<KeywordAlgorithm>> = RwLock::new(None);
+}
+
+#[cfg(feature = "nlp")]
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&SENTENCE_REGEX);
+    reload_config();
+    Ok(())
+}
+
+#[cfg(feature = "nlp")]
+pub fn reload_config() {
+    *KEYWORD_ALGORITHM.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = AppConfig::get_analysis_config().keyword_algorithm;
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageShare {
+    pub language: String,
+    pub messages: usize,
+    // Of the batch, 0-1
+    pub share: f64,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchAnalysisResult {
+    // In input order
+    pub results: Vec<TextAnalysisResult>,
+    // Messages per detected language, most first; ties by name
+    pub languages: Vec<LanguageShare>,
+    // Characters per script over every message
+    pub script_stats: ScriptStats,
+    // Some message ran out of time; see TextAnalysisResult::timed_out
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+// Analyzes each message and sums up which languages and scripts a chat
+// writes in, e.g. to pick the language pipelines it needs
+#[cfg(feature = "nlp")]
+pub fn analyze_batch(texts: &[String], options: &AnalysisOptions) -> BatchAnalysisResult {
+    #[cfg(feature = "realtime")]
+    let results: Vec<TextAnalysisResult> = {
+        use rayon::prelude::*;
+        // Pool threads do not see the caller's deadline or tenant unless
+        // handed them
+        let deadline = deadline::current();
+        let context = crate::tenant::current();
+        crate::pool::install(|| texts.par_iter().map(|text| {
+            crate::tenant::with_tenant(context.clone(), || deadline::with_deadline(deadline, || analyze_text_with_options(text, options)))
+        }).collect())
+    };
+    #[cfg(not(feature = "realtime"))]
+    let results: Vec<TextAnalysisResult> = texts.iter().map(|text| analyze_text_with_options(text, options)).collect();
+    
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for result in &results {
+        *counts.entry(result.language.as_str()).or_insert(0) += 1;
+    }
+    let mut languages: Vec<LanguageShare> = counts.into_iter().map(|(language, messages)| LanguageShare {
+        language: language.to_string(),
+        messages,
+        share: messages as f64 / results.len() as f64,
+    }).collect();
+    languages.sort_by(|a, b| b.messages.cmp(&a.messages).then_with(|| a.language.cmp(&b.language)));
+    
+    BatchAnalysisResult {
+        timed_out: results.iter().any(|result| result.timed_out),
+        script_stats: ScriptStats::combine(results.iter().map(|result| &result.script_stats)),
+        languages,
+        results,
+    }
+}
+
+#[cfg(feature = "nlp")]
 pub fn analyze_text(text: &str) -> TextAnalysisResult {
-    let start_time = std::time::Instant::now();
+    analyze_text_with_options(text, &AnalysisOptions::default())
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_text_with_options(text: &str, options: &AnalysisOptions) -> TextAnalysisResult {
+    let stopwatch = Stopwatch::start();
+    // Stages past the deadline are skipped like those of a cancelled job,
+    // and the result says so
+    let timed_out = Cell::new(false);
+    // A stage is timed until the next one starts, so its time includes the
+    // bookkeeping that follows it
+    let stage_timings = RefCell::new(BTreeMap::new());
+    let open_stage: Cell<Option<(&str, f64)>> = Cell::new(None);
+    let close_stage = || {
+        if let Some((name, started)) = open_stage.take() {
+            stage_timings.borrow_mut().insert(format!("{}_ms", name), stopwatch.elapsed_ms() - started);
+        }
+    };
+    let stage = |fraction: f64, name: &'static str| {
+        close_stage();
+        let run = checkpoint(fraction, name);
+        if !run && deadline::expired() {
+            timed_out.set(true);
+        }
+        if run {
+            open_stage.set(Some((name, stopwatch.elapsed_ms())));
+        }
+        run
+    };
+    
+    // Sampled texts are also run through the candidates of shadow.rs; only
+    // the primary results below are returned
+    let shadow = shadow::sample(text);
+    let input_bytes = text.len();
+    
+    let structure = options.structure.then(|| structure::analyze(text));
+    let blanked = options.skip_code.then(|| structure::blank_code(text));
+    let text = blanked.as_deref().unwrap_or(text);
+    let normalized = options.normalize.as_ref().map(|normalizer| normalizer.normalize(text));
+    let text = normalized.as_deref().unwrap_or(text);
+    let expanded = options.expand_abbreviations.then(|| abbreviations::expand(text));
+    let text = expanded.as_deref().unwrap_or(text);
+    let transliterated = options.finglish.then(|| transliterate(text, TransliterationDirection::ToPersian));
+    let text = transliterated.as_deref().unwrap_or(text);
     
     // Character count
     let char_count = text.chars().count();
+    let script_stats = language::script_stats(text);
+    
+    let emoji_stats = emoji::stats(text);
+    let tags = tags::stats(text);
+    let stripped = if options.strip_emoji { emoji::strip(text) } else { String::new() };
+    let word_text = if options.strip_emoji { stripped.as_str() } else { text };
     
     // Word count using Unicode segmentation
-    let words: Vec<&str> = text.unicode_words().collect();
+    let words: Vec<&str> = word_text.unicode_words().collect();
     let word_count = words.len();
     
     // Sentence count using regex
-    let sentence_regex = Regex::new(r"[.!?]+").unwrap();
-    let sentences: Vec<&str> = sentence_regex.split(text).collect();
+    let sentences: Vec<&str> = SENTENCE_REGEX.split(text).collect();
     let sentence_count = sentences.len().max(1);
     
     // Enhanced language detection with confidence
-    let (language, language_confidence) = detect_language_with_confidence(text);
+    let (language, language_confidence) = if !stage(0.1, "language_detection") {
+        ("unknown".to_string(), 0.0)
+    } else if let Some(hint) = &options.language_hint {
+        (hint.clone(), 1.0)
+    } else {
+        detect_language_with_confidence(text)
+    };
+    let segment = |t: &str| if language == "unknown" || options.language_hint.is_some() {
+        language::whole(t, &language, language_confidence)
+    } else {
+        language::detect_languages_segmented(t)
+    };
+    let language_spans = segment(text);
+    // Emoji stripping moves the offsets keywords are looked up by
+    let word_spans = if options.strip_emoji { segment(word_text) } else { language_spans.clone() };
     
     // Advanced sentiment analysis with score
-    let (sentiment, sentiment_score) = analyze_sentiment_advanced(text);
+    let (sentiment, sentiment_score) = if stage(0.2, "sentiment") {
+        let (label, score) = analyze_sentiment_advanced(text, &language);
+        if let Some(shadow) = &shadow {
+            shadow.compare_sentiment(text, &language, &label, score);
+        }
+        (label, score)
+    } else {
+        ("neutral".to_string(), 0.0)
+    };
     
     // Keyword extraction
-    let keywords = extract_keywords(text);
+    let keywords = if stage(0.3, "keywords") {
+        let keywords = extract_keywords(word_text, &word_spans, options.stem, options.noun_phrase_keywords);
+        if let Some(shadow) = &shadow {
+            if let Some(algorithm) = shadow.keyword_algorithm() {
+                let candidate = extract_keywords_with(Some(algorithm), word_text, &word_spans, options.stem, options.noun_phrase_keywords);
+                shadow.compare_keywords(&language, &keywords, &candidate);
+            }
+        }
+        keywords
+    } else {
+        Vec::new()
+    };
+    
+    // Spam heuristics
+    let spam = if options.spam_score && stage(0.35, "spam") { Some(spam::score(text)) } else { None };
     
     // Named entity recognition
-    let entities = extract_entities(text);
+    let entities = if !options.skip_ner && stage(0.4, "ner") { extract_entities(text) } else { Vec::new() };
     
     // Text summarization
-    let summary = generate_summary(text);
+    let summary = if options.summary_sentences > 0 && stage(0.55, "summary") {
+        let summary_options = summary::SummaryOptions {
+            max_sentences: options.summary_sentences,
+            max_chars: options.summary_max_chars,
+            preserve_entities: true,
+        };
+        summary::summarize_in(text, &language_spans, &summary_options)
+    } else {
+        String::new()
+    };
     
     // Readability scoring
-    let readability_score = calculate_readability(text);
+    let readability = if stage(0.7, "readability") { readability::report(text, &language) } else { ReadabilityReport::default() };
     
     // Topic modeling
-    let topics = extract_topics(text);
+    let topics = if !options.skip_topics && stage(0.8, "topic_modeling") { extract_topics(text, &language_spans, options.stem) } else { Vec::new() };
     
     // Plagiarism detection
-    let plagiarism_score = detect_plagiarism(text);
+    let plagiarism_score = if !options.skip_plagiarism && stage(0.9, "plagiarism") { detect_plagiarism(text) } else { 0.0 };
+    
+    // Host-registered analyzers
+    let plugins = if !options.skip_plugins && stage(0.95, "plugins") { plugins::run_all(text) } else { serde_json::Map::new() };
+    
+    close_stage();
+    checkpoint(1.0, "done");
+    // Charged for the stages that ran
+    costs::record("analyze_text", input_bytes, stage_timings.borrow().keys().map(|stage| stage.trim_end_matches("_ms")));
     
-    let processing_time = start_time.elapsed().as_millis();
+    let processing_time = stopwatch.elapsed_millis();
     
     TextAnalysisResult {
         char_count,
@@ -111,210 +446,153 @@ pub fn analyze_text(text: &str) -> TextAnalysisResult {
         sentence_count,
         language,
         language_confidence,
+        language_spans,
+        script_stats,
         sentiment,
         sentiment_score,
         keywords,
+        emoji_stats,
+        tags,
+        spam,
+        structure,
         entities,
         summary,
-        readability_score,
+        readability_score: readability,
         topics,
         plagiarism_score,
         processing_time,
+        stage_timings: stage_timings.into_inner(),
+        plugins,
+        timed_out: timed_out.get(),
     }
 }
 
-fn detect_language_with_confidence(text: &str) -> (String, f64) {
-    match whatlang::detect(text) {
-        Some(info) => {
-            let confidence = info.confidence();
-            (info.lang().to_string(), confidence)
-        },
-        None => ("unknown".to_string(), 0.0),
+// Reports stage progress and tells the pipeline whether to keep going; a
+// cancelled job, or a call past its deadline, skips every remaining stage
+fn checkpoint(fraction: f64, stage: &str) -> bool {
+    if jobs::is_cancelled() || deadline::expired() {
+        return false;
     }
+    progress::report(fraction, stage);
+    true
 }
 
-fn analyze_sentiment_advanced(text: &str) -> (String, f64) {
-    let positive_words = vec![
-        "خوب", "عالی", "عالیه", "ممتاز", "عالی", "خوب", "عالی", "عالیه", "ممتاز",
-        "good", "great", "excellent", "amazing", "wonderful", "fantastic", "perfect",
-        "beautiful", "nice", "lovely", "happy", "joy", "love", "like", "enjoy",
-        "brilliant", "outstanding", "superb", "magnificent", "delightful", "pleased"
-    ];
-    
-    let negative_words = vec![
-        "بد", "بدی", "بدیه", "بدی", "بد", "بدی", "بدیه", "بدی", "بد",
-        "bad", "terrible", "awful", "horrible", "disgusting", "hate", "dislike",
-        "sad", "angry", "furious", "upset", "disappointed", "worried", "scared",
-        "dreadful", "atrocious", "abysmal", "appalling", "repulsive", "revolting"
-    ];
-    
-    let text_lower = text.to_lowercase();
-    let words: Vec<&str> = text_lower.unicode_words().collect();
-    
-    let positive_count = words.iter()
-        .filter(|word| positive_words.contains(word))
-        .count();
-    
-    let negative_count = words.iter()
-        .filter(|word| negative_words.contains(word))
-        .count();
-    
-    let total_sentiment_words = positive_count + negative_count;
-    let sentiment_score = if total_sentiment_words > 0 {
-        (positive_count as f64 - negative_count as f64) / total_sentiment_words as f64
-    } else {
-        0.0
-    };
-    
-    let sentiment = match sentiment_score {
-        s if s > 0.2 => "positive".to_string(),
-        s if s < -0.2 => "negative".to_string(),
-        _ => "neutral".to_string(),
-    };
-    
-    (sentiment, sentiment_score)
-}
+// std::time::Instant panics on wasm32-unknown-unknown, so browser builds time
+// the pipeline with the JS clock instead
+#[cfg(all(feature = "nlp", not(target_arch = "wasm32")))]
+struct Stopwatch(std::time::Instant);
 
-fn extract_entities(text: &str) -> Vec<Entity> {
-    let mut entities = Vec::new();
-    
-    // Simple named entity recognition patterns
-    let name_pattern = Regex::new(r"\b[A-Z][a-z]+ [A-Z][a-z]+\b").unwrap();
-    let email_pattern = Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b").unwrap();
-    let url_pattern = Regex::new(r"https?://[^\s]+").unwrap();
-    let phone_pattern = Regex::new(r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b").unwrap();
-    
-    // Extract names
-    for cap in name_pattern.find_iter(text) {
-        entities.push(Entity {
-            name: cap.as_str().to_string(),
-            entity_type: "PERSON".to_string(),
-            confidence: 0.8,
-        });
+#[cfg(all(feature = "nlp", not(target_arch = "wasm32")))]
+impl Stopwatch {
+    fn start() -> Self {
+        Stopwatch(std::time::Instant::now())
     }
     
-    // Extract emails
-    for cap in email_pattern.find_iter(text) {
-        entities.push(Entity {
-            name: cap.as_str().to_string(),
-            entity_type: "EMAIL".to_string(),
-            confidence: 0.95,
-        });
+    fn elapsed_millis(&self) -> u64 {
+        self.0.elapsed().as_millis() as u64
     }
     
-    // Extract URLs
-    for cap in url_pattern.find_iter(text) {
-        entities.push(Entity {
-            name: cap.as_str().to_string(),
-            entity_type: "URL".to_string(),
-            confidence: 0.9,
-        });
+    fn elapsed_ms(&self) -> f64 {
+        self.0.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+#[cfg(all(feature = "nlp", target_arch = "wasm32"))]
+struct Stopwatch(f64);
+
+#[cfg(all(feature = "nlp", target_arch = "wasm32"))]
+impl Stopwatch {
+    fn start() -> Self {
+        Stopwatch(js_sys::Date::now())
     }
     
-    // Extract phone numbers
-    for cap in phone_pattern.find_iter(text) {
-        entities.push(Entity {
-            name: cap.as_str().to_string(),
-            entity_type: "PHONE".to_string(),
-            confidence: 0.85,
-        });
+    fn elapsed_millis(&self) -> u64 {
+        self.elapsed_ms() as u64
     }
     
-    entities
+    fn elapsed_ms(&self) -> f64 {
+        (js_sys::Date::now() - self.0).max(0.0)
+    }
 }
 
-fn generate_summary(text: &str) -> String {
-    let sentences: Vec<&str> = text.split(|c| c == '.' || c == '!' || c == '?').collect();
-    let words: Vec<&str> = text.unicode_words().collect();
-    
-    if sentences.len() <= 2 {
-        return text.to_string();
+#[cfg(feature = "nlp")]
+fn detect_language_with_confidence(text: &str) -> (String, f64) {
+    match whatlang::detect(text) {
+        Some(info) => {
+            let confidence = info.confidence();
+            (info.lang().to_string(), confidence)
+        },
+        None => ("unknown".to_string(), 0.0),
     }
-    
-    // Simple extractive summarization
-    let mut sentence_scores: Vec<(usize, f64)> = sentences.iter().enumerate()
-        .map(|(i, sentence)| {
-            let word_count = sentence.split_whitespace().count();
-            let score = word_count as f64 * 0.5; // Simple scoring based on length
-            (i, score)
-        })
-        .collect();
-    
-    sentence_scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    
-    let summary_sentences: Vec<&str> = sentence_scores.iter()
-        .take(2.min(sentences.len()))
-        .map(|(i, _)| sentences[*i])
-        .collect();
-    
-    summary_sentences.join(". ")
 }
 
-fn calculate_readability(text: &str) -> f64 {
-    let sentences: Vec<&str> = text.split(|c| c == '.' || c == '!' || c == '?').collect();
-    let words: Vec<&str> = text.unicode_words().collect();
-    let syllables = count_syllables(text);
-    
-    if sentences.is_empty() || words.is_empty() {
-        return 0.0;
+#[cfg(feature = "nlp")]
+fn analyze_sentiment_advanced(text: &str, language: &str) -> (String, f64) {
+    let score = sentiment::active().analyze(text).compound;
+    (sentiment::label_in(score, language).to_string(), score)
+}
+
+#[cfg(feature = "nlp")]
+fn span_entity(text: &str, start: usize, end: usize, entity_type: &str, confidence: f64) -> Entity {
+    Entity {
+        name: text[start..end].to_string(),
+        entity_type: entity_type.to_string(),
+        confidence,
+        start,
+        end,
     }
-    
-    // Flesch Reading Ease formula
-    let avg_sentence_length = words.len() as f64 / sentences.len() as f64;
-    let avg_syllables_per_word = syllables as f64 / words.len() as f64;
-    
-    206.835 - (1.015 * avg_sentence_length) - (84.6 * avg_syllables_per_word)
 }
 
-fn count_syllables(text: &str) -> usize {
-    let vowels = ['a', 'e', 'i', 'o', 'u', 'y'];
-    let words: Vec<&str> = text.unicode_words().collect();
-    
-    words.iter().map(|word| {
-        let word_lower = word.to_lowercase();
-        let mut syllable_count = 0;
-        let mut prev_vowel = false;
-        
-        for ch in word_lower.chars() {
-            let is_vowel = vowels.contains(&ch);
-            if is_vowel && !prev_vowel {
-                syllable_count += 1;
-            }
-            prev_vowel = is_vowel;
+// In text order. Where matches overlap, the first kind found wins:
+// gazetteer names, then the entity pattern registry in its order (patterns
+// added through config or at runtime, then dates, times, amounts and
+// percentages, then the other built-ins).
+#[cfg(feature = "nlp")]
+pub(crate) fn extract_entities(text: &str) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = gazetteers::find(text).into_iter()
+        .map(|m| span_entity(text, m.start, m.end, &m.entity_type, gazetteers::GAZETTEER_CONFIDENCE))
+        .collect();
+    for m in entity_patterns::find(text) {
+        if !entities.iter().any(|e| e.start < m.end && m.start < e.end) {
+            entities.push(span_entity(text, m.start, m.end, &m.entity_type, m.confidence));
         }
-        
-        syllable_count.max(1)
-    }).sum()
+    }
+    
+    entities.sort_by_key(|e| (e.start, e.end));
+    entities
 }
 
-fn extract_topics(text: &str) -> Vec<Topic> {
-    let words: Vec<&str> = text.unicode_words().collect();
-    let mut word_freq: HashMap<&str, usize> = HashMap::new();
-    
-    for word in words.iter() {
-        if word.len() > 3 {
-            *word_freq.entry(word).or_insert(0) += 1;
-        }
+#[cfg(feature = "nlp")]
+fn extract_topics(text: &str, spans: &[LanguageSpan], stemmed: bool) -> Vec<Topic> {
+    // A trained topic model, when the host feeds one, beats word counts
+    if let Some(topics) = crate::topics::infer(text) {
+        return topics;
     }
     
+    let words = language::words(text, spans);
+    let groups = group_words(words.iter().copied().filter(|(word, _)| word.len() > 3), stemmed);
+    
     // Simple topic extraction based on frequency
     let mut topics = Vec::new();
-    let mut sorted_words: Vec<(&str, &usize)> = word_freq.iter().collect();
-    sorted_words.sort_by(|a, b| b.1.cmp(a.1));
-    
-    for (word, freq) in sorted_words.iter().take(3) {
+    for group in groups.iter().take(3) {
         topics.push(Topic {
-            name: word.to_string(),
-            weight: **freq as f64 / words.len() as f64,
-            keywords: vec![word.to_string()],
+            name: group.forms[0].clone(),
+            weight: group.count as f64 / words.len() as f64,
+            keywords: group.forms.clone(),
         });
     }
     
     topics
 }
 
+#[cfg(feature = "nlp")]
 fn detect_plagiarism(text: &str) -> f64 {
-    // Simple plagiarism detection based on common phrases
+    // Closest recent message, when the host keeps a duplicate corpus
+    if let Some(score) = crate::similarity::corpus_similarity(text) {
+        return score;
+    }
+    
+    // Otherwise simple plagiarism detection based on common phrases
     let common_phrases = vec![
         "in conclusion", "as a result", "it is important", "this shows",
         "according to", "research shows", "studies indicate", "it can be seen",
@@ -333,43 +611,824 @@ fn detect_plagiarism(text: &str) -> f64 {
     plagiarism_score.min(1.0)
 }
 
-fn extract_keywords(text: &str) -> Vec<String> {
-    let stop_words = vec![
-        "the", "a", "an", "and", "or", "but", "in", "on", "at", "to", "for", "of", "with",
-        "این", "آن", "که", "را", "در", "به", "از", "با", "برای", "تا", "یا", "و", "اما"
-    ];
+// Words sharing a stem, or identical words without stemming
+#[cfg(feature = "nlp")]
+struct WordGroup {
+    // Surface forms in order of first use
+    forms: Vec<String>,
+    count: usize,
+}
+
+#[cfg(feature = "nlp")]
+// Most frequent first; equal counts keep first-occurrence order. Each word
+// comes with the language it is stemmed in.
+fn group_words<'a>(words: impl Iterator<Item = (&'a str, &'a str)>, stemmed: bool) -> Vec<WordGroup> {
+    let mut groups: Vec<WordGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (word, language) in words {
+        let key = if stemmed { stem(word, language) } else { word.to_string() };
+        let i = *index.entry(key).or_insert_with(|| {
+            groups.push(WordGroup { forms: Vec::new(), count: 0 });
+            groups.len() - 1
+        });
+        let group = &mut groups[i];
+        group.count += 1;
+        if !group.forms.iter().any(|form| form == word) {
+            group.forms.push(word.to_string());
+        }
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.count));
+    groups
+}
+
+#[cfg(feature = "nlp")]
+fn extract_keywords(text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool) -> Vec<String> {
+    let configured = *KEYWORD_ALGORITHM.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    extract_keywords_with(configured, text, spans, stemmed, noun_phrases)
+}
+
+#[cfg(feature = "nlp")]
+// Unconfigured, a background corpus the host supplied means TF-IDF
+fn extract_keywords_with(configured: Option<KeywordAlgorithm>, text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool) -> Vec<String> {
+    let background = keywords::background();
+    let algorithm = configured.unwrap_or(if background.is_some() { KeywordAlgorithm::TFIDF } else { KeywordAlgorithm::FREQUENCY });
+    match (algorithm, background) {
+        (KeywordAlgorithm::TFIDF, Some(extractor)) => {
+            let extractor = extractor.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+            extractor.extract_in(text, spans, noun_phrases, 5).into_iter().map(|keyword| keyword.term).collect()
+        }
+        (KeywordAlgorithm::RAKE, _) => rake_keywords(text, spans, stemmed, noun_phrases, 5),
+        (KeywordAlgorithm::YAKE, _) => yake_keywords(text, spans, stemmed, noun_phrases, 5),
+        // TF-IDF without a corpus has nothing to weigh words against
+        _ => frequency_keywords(text, spans, stemmed, noun_phrases, 5),
+    }
+}
+
+#[cfg(feature = "nlp")]
+fn frequency_keywords(text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool, limit: usize) -> Vec<String> {
+    let terms = if noun_phrases { keywords::noun_phrase_terms(text, spans) } else { keywords::tagged_terms(text, spans) };
+    
+    // Most frequent first, each shown in the form it first appeared in
+    group_words(terms.iter().map(|(word, language)| (word.as_str(), *language)), stemmed)
+        .into_iter()
+        .take(limit)
+        .map(|group| group.forms[0].clone())
+        .collect()
+}
+
+// Longest keyword phrase; chat messages often have no stop words to end one
+#[cfg(feature = "nlp")]
+const MAX_KEYWORD_WORDS: usize = 3;
+
+// A word of the text as RAKE and YAKE see it
+#[cfg(feature = "nlp")]
+struct KeywordToken {
+    word: String,
+    // What occurrences are counted by: the word, or its stem
+    key: String,
+    sentence: usize,
+    // Can be part of a keyword: not a stop word, number or short word
+    content: bool,
+    // Punctuation or a sentence end separates it from the previous word
+    after_break: bool,
+    capitalized: bool,
+    acronym: bool,
+}
+
+#[cfg(feature = "nlp")]
+fn keyword_tokens(text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool) -> Vec<KeywordToken> {
+    let phrases = if noun_phrases { crate::pos::noun_phrases_in(text, spans) } else { Vec::new() };
+    let mut tokens = Vec::new();
+    let mut sentence = 0;
+    let mut previous_end = 0;
+    for (i, word) in text.unicode_word_indices() {
+        let gap = &text[previous_end..i];
+        let sentence_end = gap.contains(['.', '!', '?', '…', '؟', '\n']);
+        if sentence_end && !tokens.is_empty() {
+            sentence += 1;
+        }
+        previous_end = i + word.len();
+        
+        let language = language::language_at(spans, i);
+        let lower = word.to_lowercase();
+        let in_phrase = || {
+            let k = phrases.partition_point(|phrase| phrase.end <= i);
+            phrases.get(k).is_some_and(|phrase| phrase.start <= i)
+        };
+        let content = lower.len() > 2
+            && !lower.chars().all(|c| c.is_numeric())
+            && !crate::stopwords::is_stop_word(language, &lower)
+            && (!noun_phrases || in_phrase());
+        let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+        tokens.push(KeywordToken {
+            key: if stemmed { stem(&lower, language) } else { lower.clone() },
+            word: lower,
+            sentence,
+            content,
+            after_break: sentence_end || gap.chars().any(|c| !c.is_whitespace() && c != '-'),
+            capitalized: word.chars().next().is_some_and(char::is_uppercase),
+            acronym: letters > 1 && word.chars().all(|c| !c.is_alphabetic() || c.is_uppercase()),
+        });
+    }
+    tokens
+}
+
+// Runs of adjacent content words, as index ranges into the tokens
+#[cfg(feature = "nlp")]
+fn keyword_runs(tokens: &[KeywordToken]) -> Vec<std::ops::Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match start {
+            Some(from) if !token.content || token.after_break => {
+                runs.push(from..i);
+                start = token.content.then_some(i);
+            }
+            None if token.content => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        runs.push(from..tokens.len());
+    }
+    runs
+}
+
+// RAKE (Rose et al., 2010): candidates are the phrases between stop words
+// and punctuation, split into pieces of at most MAX_KEYWORD_WORDS. A word
+// scores its degree (the length of the phrases it appears in, summed) over
+// its frequency, so words that start longer phrases beat words that only
+// repeat; a phrase scores the sum of its words.
+#[cfg(feature = "nlp")]
+fn rake_keywords(text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool, limit: usize) -> Vec<String> {
+    let tokens = keyword_tokens(text, spans, stemmed, noun_phrases);
+    let phrases: Vec<&[KeywordToken]> = keyword_runs(&tokens).into_iter()
+        .flat_map(|run| tokens[run].chunks(MAX_KEYWORD_WORDS).collect::<Vec<_>>())
+        .collect();
     
-    let text_lower = text.to_lowercase();
-    let words: Vec<&str> = text_lower.unicode_words().collect();
+    // Key -> (frequency, degree)
+    let mut words: HashMap<&str, (usize, usize)> = HashMap::new();
+    for phrase in &phrases {
+        for token in phrase.iter() {
+            let entry = words.entry(token.key.as_str()).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += phrase.len();
+        }
+    }
+    
+    let mut seen = HashSet::new();
+    let mut ranked: Vec<(String, f64)> = phrases.iter()
+        .filter(|phrase| seen.insert(phrase.iter().map(|token| token.key.as_str()).collect::<Vec<_>>()))
+        .map(|phrase| {
+            let score = phrase.iter().map(|token| {
+                let (frequency, degree) = words[token.key.as_str()];
+                degree as f64 / frequency as f64
+            }).sum();
+            (phrase.iter().map(|token| token.word.as_str()).collect::<Vec<_>>().join(" "), score)
+        })
+        .collect();
+    // Stable, so equal scores keep the order of first appearance
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.into_iter().take(limit).map(|(phrase, _)| phrase).collect()
+}
+
+// YAKE (Campos et al., 2020), which needs no corpus and holds up on short
+// texts. Each word is scored from its casing, how early it appears, its
+// frequency, how many different words surround it and how many sentences it
+// spreads over; lower is better. Candidates are the n-grams of up to
+// MAX_KEYWORD_WORDS content words, and overlapping ones give way to the
+// better scored.
+#[cfg(feature = "nlp")]
+fn yake_keywords(text: &str, spans: &[LanguageSpan], stemmed: bool, noun_phrases: bool, limit: usize) -> Vec<String> {
+    #[derive(Default)]
+    struct WordStats<'t> {
+        count: usize,
+        cased: usize,
+        acronyms: usize,
+        sentences: Vec<usize>,
+        left: Vec<&'t str>,
+        right: Vec<&'t str>,
+    }
     
-    // Count word frequencies
-    let mut word_freq: HashMap<&str, usize> = HashMap::new();
-    for word in words.iter() {
-        if !stop_words.contains(word) && word.len() > 2 {
-            *word_freq.entry(word).or_insert(0) += 1;
+    let tokens = keyword_tokens(text, spans, stemmed, noun_phrases);
+    let sentence_count = tokens.last().map_or(0, |token| token.sentence + 1);
+    let mut stats: HashMap<&str, WordStats> = HashMap::new();
+    for (i, token) in tokens.iter().enumerate().filter(|(_, token)| token.content) {
+        let entry = stats.entry(token.key.as_str()).or_default();
+        entry.count += 1;
+        // Capitalized at the start of a sentence says nothing
+        let sentence_start = i == 0 || tokens[i - 1].sentence != token.sentence;
+        entry.cased += usize::from(token.capitalized && !sentence_start && !token.acronym);
+        entry.acronyms += usize::from(token.acronym);
+        entry.sentences.push(token.sentence);
+        if let Some(left) = i.checked_sub(1).map(|j| &tokens[j]).filter(|left| left.sentence == token.sentence) {
+            entry.left.push(left.key.as_str());
         }
+        if let Some(right) = tokens.get(i + 1).filter(|right| right.sentence == token.sentence) {
+            entry.right.push(right.key.as_str());
+        }
+    }
+    if stats.is_empty() {
+        return Vec::new();
     }
     
-    // Get top 5 keywords
-    let mut keywords: Vec<(&&str, &usize)> = word_freq.iter().collect();
-    keywords.sort_by(|a, b| b.1.cmp(a.1));
+    let counts: Vec<f64> = stats.values().map(|word| word.count as f64).collect();
+    let mean = counts.iter().sum::<f64>() / counts.len() as f64;
+    let deviation = (counts.iter().map(|count| (count - mean).powi(2)).sum::<f64>() / counts.len() as f64).sqrt();
+    let max_count = counts.iter().copied().fold(0.0, f64::max);
+    let dispersion = |neighbours: &[&str]| if neighbours.is_empty() {
+        0.0
+    } else {
+        neighbours.iter().collect::<HashSet<_>>().len() as f64 / neighbours.len() as f64
+    };
+    let word_scores: HashMap<&str, f64> = stats.iter().map(|(&key, word)| {
+        let count = word.count as f64;
+        let casing = word.cased.max(word.acronyms) as f64 / (1.0 + count.ln());
+        let median_sentence = word.sentences[word.sentences.len() / 2] as f64;
+        let position = (3.0 + median_sentence).ln().ln();
+        let frequency = count / (mean + deviation);
+        let relatedness = 1.0 + (dispersion(&word.left) + dispersion(&word.right)) * count / max_count;
+        let spread = word.sentences.iter().collect::<HashSet<_>>().len() as f64 / sentence_count as f64;
+        (key, relatedness * position / (casing + frequency / relatedness + spread / relatedness))
+    }).collect();
+    
+    // Key sequence -> (surface form, occurrences, first position)
+    let mut candidates: HashMap<Vec<&str>, (String, usize, usize)> = HashMap::new();
+    for run in keyword_runs(&tokens) {
+        for start in run.clone() {
+            for end in start + 1..=(start + MAX_KEYWORD_WORDS).min(run.end) {
+                let words = &tokens[start..end];
+                let entry = candidates.entry(words.iter().map(|token| token.key.as_str()).collect()).or_insert_with(|| {
+                    (words.iter().map(|token| token.word.as_str()).collect::<Vec<_>>().join(" "), 0, start)
+                });
+                entry.1 += 1;
+            }
+        }
+    }
+    let mut ranked: Vec<(Vec<&str>, String, f64, usize)> = candidates.into_iter().map(|(keys, (phrase, count, first))| {
+        let product: f64 = keys.iter().map(|key| word_scores[key]).product();
+        let sum: f64 = keys.iter().map(|key| word_scores[key]).sum();
+        let score = product / (count as f64 * (1.0 + sum));
+        (keys, phrase, score, first)
+    }).collect();
+    ranked.sort_by(|a, b| a.2.total_cmp(&b.2).then(a.3.cmp(&b.3)));
+    
+    let mut chosen: Vec<(Vec<&str>, String)> = Vec::new();
+    for (keys, phrase, _, _) in ranked {
+        if chosen.len() == limit {
+            break;
+        }
+        if !chosen.iter().any(|(taken, _)| taken.iter().any(|key| keys.contains(key))) {
+            chosen.push((keys, phrase));
+        }
+    }
+    chosen.into_iter().map(|(_, phrase)| phrase).collect()
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CharacterClass {
+    LETTER,
+    DIGIT,
+    WHITESPACE,
+    PUNCTUATION,
+    EMOJI,
+    // Currency, math and other symbols, and control characters
+    SYMBOL,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenCount {
+    pub token: String,
+    pub count: usize,
+    // Of all tokens, 0-1
+    pub share: f64,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterShare {
+    pub class: CharacterClass,
+    // Grapheme clusters, so an emoji with a skin tone counts once
+    pub count: usize,
+    pub share: f64,
+}
+
+// Least-squares line through log frequency over log rank; natural language
+// has an exponent near 1 and an r_squared near 1
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZipfFit {
+    pub exponent: f64,
+    pub r_squared: f64,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrequencyReport {
+    // Words, lowercased
+    pub tokens: usize,
+    // Distinct tokens
+    pub types: usize,
+    // Tokens used exactly once
+    pub hapax_legomena: usize,
+    // types / tokens; falls as texts get longer, so compare texts of
+    // similar length
+    pub type_token_ratio: f64,
+    // Most used first, ties by first use
+    pub top_tokens: Vec<TokenCount>,
+    // Like top_tokens without stop words and words of one or two bytes
+    pub top_content_words: Vec<TokenCount>,
+    // Most first
+    pub characters: Vec<CharacterShare>,
+    // None for fewer than three types
+    pub zipf: Option<ZipfFit>,
+}
+
+#[cfg(feature = "nlp")]
+fn character_class(grapheme: &str) -> CharacterClass {
+    let c = grapheme.chars().next().unwrap_or(' ');
+    if emoji::is_emoji(grapheme) {
+        CharacterClass::EMOJI
+    } else if c.is_alphabetic() {
+        CharacterClass::LETTER
+    } else if c.is_numeric() {
+        CharacterClass::DIGIT
+    } else if c.is_whitespace() {
+        CharacterClass::WHITESPACE
+    } else if (c.is_ascii_punctuation() && !"$+<=>^`|~".contains(c)) || matches!(c, '،' | '؛' | '؟' | '«' | '»' | '…' | '–' | '—' | '‘' | '’' | '“' | '”') {
+        CharacterClass::PUNCTUATION
+    } else {
+        CharacterClass::SYMBOL
+    }
+}
+
+#[cfg(feature = "nlp")]
+fn zipf_fit(counts: &[usize]) -> Option<ZipfFit> {
+    if counts.len() < 3 {
+        return None;
+    }
+    let points: Vec<(f64, f64)> = counts.iter().enumerate().map(|(rank, &count)| (((rank + 1) as f64).ln(), (count as f64).ln())).collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let syy: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+    let slope = sxy / sxx;
+    // Every type used equally often lies on a flat line
+    let r_squared = if syy == 0.0 { 0.0 } else { sxy * sxy / (sxx * syy) };
+    Some(ZipfFit { exponent: -slope, r_squared })
+}
+
+// Word and character statistics of a text, e.g. for a group's stats
+// command; top_tokens and top_content_words hold up to limit entries each
+#[cfg(feature = "nlp")]
+pub fn frequency_report(text: &str, limit: usize) -> FrequencyReport {
+    let spans = language::detect_languages_segmented(text);
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut tokens = 0;
+    for word in text.unicode_words() {
+        let first = counts.len();
+        counts.entry(word.to_lowercase()).or_insert((0, first)).0 += 1;
+        tokens += 1;
+    }
+    let content: HashMap<String, usize> = keywords::tagged_terms(text, &spans).into_iter()
+        .fold(HashMap::new(), |mut content, (term, _)| {
+            *content.entry(term).or_insert(0) += 1;
+            content
+        });
     
-    keywords.into_iter()
-        .take(5)
-        .map(|(word, _)| word.to_string())
-        .collect()
+    let mut ranked: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.1.1.cmp(&b.1.1)));
+    let token_count = |(token, (count, _)): &(String, (usize, usize))| TokenCount { token: token.clone(), count: *count, share: *count as f64 / tokens as f64 };
+    let top_tokens = ranked.iter().take(limit).map(token_count).collect();
+    let top_content_words = ranked.iter().filter(|(token, _)| content.contains_key(token)).take(limit).map(token_count).collect();
+    
+    let mut classes: HashMap<CharacterClass, usize> = HashMap::new();
+    let mut graphemes = 0;
+    for grapheme in text.graphemes(true) {
+        *classes.entry(character_class(grapheme)).or_insert(0) += 1;
+        graphemes += 1;
+    }
+    let mut characters: Vec<CharacterShare> = classes.into_iter()
+        .map(|(class, count)| CharacterShare { class, count, share: count as f64 / graphemes as f64 })
+        .collect();
+    characters.sort_by(|a, b| b.count.cmp(&a.count).then(a.class.cmp(&b.class)));
+    
+    FrequencyReport {
+        tokens,
+        types: ranked.len(),
+        hapax_legomena: ranked.iter().filter(|(_, (count, _))| *count == 1).count(),
+        type_token_ratio: if tokens == 0 { 0.0 } else { ranked.len() as f64 / tokens as f64 },
+        zipf: zipf_fit(&ranked.iter().map(|(_, (count, _))| *count).collect::<Vec<_>>()),
+        top_tokens,
+        top_content_words,
+        characters,
+    }
 }
 
-pub fn analyze_data(data: &str) -> DataAnalysisResult {
-    let start_time = std::time::Instant::now();
+// Documents compare_documents takes at once; the matrix grows with the square
+#[cfg(feature = "nlp")]
+pub const MAX_COMPARED_DOCUMENTS: usize = 500;
+// Similarity at which two documents are linked into a cluster
+#[cfg(feature = "nlp")]
+const CLUSTER_SIMILARITY: f64 = 0.6;
+#[cfg(feature = "nlp")]
+const SHARED_TERMS: usize = 20;
+#[cfg(feature = "nlp")]
+const DISTINGUISHING_TERMS: usize = 5;
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedTerm {
+    // In the form it first appeared in; forms with one stem count as one term
+    pub term: String,
+    // Indices of the documents using it, in order
+    pub documents: Vec<usize>,
+    // Occurrences over all of them
+    pub count: usize,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentComparison {
+    // similarity[i][j]: cosine of the TF-IDF vectors of documents i and j,
+    // 0-1, with 1 on the diagonal
+    pub similarity: Vec<Vec<f64>>,
+    // Terms in two or more documents, those in the most documents first
+    pub shared_terms: Vec<SharedTerm>,
+    // Per document, in input order: the terms that set it apart from the
+    // others, highest TF-IDF first; none are in every document
+    pub distinguishing_terms: Vec<Vec<String>>,
+    // Documents linked by a similarity of 0.6 or more, directly or through
+    // others, in groups of at least two: candidates for one message posted
+    // with small rewordings
+    pub clusters: Vec<Vec<usize>>,
+}
+
+// Content words of several documents side by side, e.g. messages from
+// different chats suspected of belonging to one spam campaign. Each
+// document's stop words go by the languages detected in it, and forms with
+// one stem count as one term. The matrix compares word use, not wording;
+// similarity() tells rewordings and edits apart for a single pair.
+#[cfg(feature = "nlp")]
+pub fn compare_documents(texts: &[&str]) -> DocumentComparison {
+    // Term key -> (form first seen, index of first use)
+    let mut terms: HashMap<String, (String, usize)> = HashMap::new();
+    let counts: Vec<HashMap<String, usize>> = texts.iter().map(|text| {
+        let spans = language::detect_languages_segmented(text);
+        let mut counts = HashMap::new();
+        for (word, language) in keywords::tagged_terms(text, &spans) {
+            let key = stem(&word, language);
+            let first = terms.len();
+            terms.entry(key.clone()).or_insert((word, first));
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        counts
+    }).collect();
     
-    // Parse data as numbers (comma-separated or space-separated)
-    let numbers: Vec<f64> = data
-        .split(|c| c == ',' || c == ' ' || c == '\n' || c == '\t')
-        .filter_map(|s| s.trim().parse::<f64>().ok())
+    let n = texts.len();
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for key in counts.iter().flat_map(|counts| counts.keys()) {
+        *frequencies.entry(key.as_str()).or_insert(0) += 1;
+    }
+    // Smoothed for the vectors, so terms in every document still count
+    // toward similarity; plain for distinguishing terms, so they do not
+    let weight = |count: usize, frequency: usize| count as f64 * (((1 + n) as f64 / (1 + frequency) as f64).ln() + 1.0);
+    let vectors: Vec<HashMap<&str, f64>> = counts.iter()
+        .map(|counts| counts.iter().map(|(key, &count)| (key.as_str(), weight(count, frequencies[key.as_str()]))).collect())
         .collect();
+    let norms: Vec<f64> = vectors.iter().map(|vector| vector.values().map(|x| x * x).sum::<f64>().sqrt()).collect();
+    let mut similarity = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        similarity[i][i] = 1.0;
+        for j in i + 1..n {
+            if norms[i] == 0.0 || norms[j] == 0.0 {
+                continue;
+            }
+            let dot: f64 = vectors[i].iter().filter_map(|(key, x)| vectors[j].get(key).map(|y| x * y)).sum();
+            let cosine = (dot / (norms[i] * norms[j])).min(1.0);
+            similarity[i][j] = cosine;
+            similarity[j][i] = cosine;
+        }
+    }
     
+    let mut shared_terms: Vec<SharedTerm> = frequencies.iter().filter(|(_, &frequency)| frequency > 1).map(|(&key, _)| SharedTerm {
+        term: terms[key].0.clone(),
+        documents: (0..n).filter(|&i| counts[i].contains_key(key)).collect(),
+        count: counts.iter().filter_map(|counts| counts.get(key)).sum(),
+    }).collect();
+    shared_terms.sort_by(|a, b| b.documents.len().cmp(&a.documents.len()).then(b.count.cmp(&a.count)).then_with(|| a.term.cmp(&b.term)));
+    shared_terms.truncate(SHARED_TERMS);
+    
+    let distinguishing_terms = counts.iter().map(|counts| {
+        let mut ranked: Vec<(&String, f64)> = counts.iter()
+            .filter(|(key, _)| frequencies[key.as_str()] < n)
+            .map(|(key, &count)| (key, count as f64 * (n as f64 / frequencies[key.as_str()] as f64).ln()))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(terms[a.0].1.cmp(&terms[b.0].1)));
+        ranked.into_iter().take(DISTINGUISHING_TERMS).map(|(key, _)| terms[key].0.clone()).collect()
+    }).collect();
+    
+    // Connected components of the pairs at or above CLUSTER_SIMILARITY
+    let mut cluster_of: Vec<Option<usize>> = vec![None; n];
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for start in 0..n {
+        if cluster_of[start].is_some() {
+            continue;
+        }
+        let mut members = vec![start];
+        cluster_of[start] = Some(clusters.len());
+        let mut k = 0;
+        while k < members.len() {
+            let i = members[k];
+            for j in 0..n {
+                if cluster_of[j].is_none() && similarity[i][j] >= CLUSTER_SIMILARITY {
+                    cluster_of[j] = Some(clusters.len());
+                    members.push(j);
+                }
+            }
+            k += 1;
+        }
+        members.sort_unstable();
+        clusters.push(members);
+    }
+    clusters.retain(|members| members.len() > 1);
+    
+    DocumentComparison { similarity, shared_terms, distinguishing_terms, clusters }
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffOp {
+    EQUAL,
+    INSERT,
+    DELETE,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffSegment {
+    pub op: DiffOp,
+    pub text: String,
+    // Byte offsets of text in the old text for DELETE, in the new one
+    // otherwise
+    pub start: usize,
+    pub end: usize,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    INSERTED,
+    DELETED,
+    REPLACED,
+}
+
+// One edited stretch of the text, without surrounding whitespace
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextChange {
+    pub kind: ChangeKind,
+    // "" for INSERTED
+    pub old: String,
+    // "" for DELETED
+    pub new: String,
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextDiff {
+    // Cover both texts in order: EQUAL and INSERT spell the new text, EQUAL
+    // and DELETE the old one
+    pub segments: Vec<DiffSegment>,
+    pub changes: Vec<TextChange>,
+    pub words_inserted: usize,
+    pub words_deleted: usize,
+    // 2 * words kept / words in both texts; 1 for two texts without words
+    pub similarity: f64,
+    // e.g. `Replaced "10:00" with "11:30"; added "sharp"`
+    pub summary: String,
+}
+
+// Token pairs compared before the middle of a long edit is reported as one
+// replacement, about 16 MB of table
+#[cfg(feature = "nlp")]
+const MAX_DIFF_CELLS: usize = 4_000_000;
+// Changes spelled out in the summary
+#[cfg(feature = "nlp")]
+const SUMMARY_CHANGES: usize = 3;
+#[cfg(feature = "nlp")]
+const SUMMARY_QUOTE_CHARS: usize = 40;
+
+#[cfg(feature = "nlp")]
+fn is_word_token(token: &str) -> bool {
+    token.chars().any(char::is_alphanumeric)
+}
+
+// Longest common subsequence of tokens, as ops in text order
+#[cfg(feature = "nlp")]
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    
+    let mut ops = vec![DiffOp::EQUAL; prefix];
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if n * m > MAX_DIFF_CELLS {
+        ops.resize(ops.len() + n, DiffOp::DELETE);
+        ops.resize(ops.len() + m, DiffOp::INSERT);
+    } else {
+        // lengths[i][j]: LCS of old_middle[i..] and new_middle[j..]
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if old_middle[i] == new_middle[j] {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                ops.push(DiffOp::EQUAL);
+                i += 1;
+                j += 1;
+            } else if j < m && (i == n || lengths[i * (m + 1) + j + 1] >= lengths[(i + 1) * (m + 1) + j]) {
+                ops.push(DiffOp::INSERT);
+                j += 1;
+            } else {
+                ops.push(DiffOp::DELETE);
+                i += 1;
+            }
+        }
+    }
+    ops.resize(ops.len() + suffix, DiffOp::EQUAL);
+    ops
+}
+
+#[cfg(feature = "nlp")]
+fn quote(text: &str) -> String {
+    if text.chars().count() <= SUMMARY_QUOTE_CHARS {
+        format!("\"{}\"", text)
+    } else {
+        format!("\"{}…\"", text.chars().take(SUMMARY_QUOTE_CHARS - 1).collect::<String>())
+    }
+}
+
+#[cfg(feature = "nlp")]
+fn change_summary(old: &str, new: &str, changes: &[TextChange], words_inserted: usize, words_deleted: usize) -> String {
+    if changes.is_empty() {
+        return "No changes".to_string();
+    }
+    if old.to_lowercase() == new.to_lowercase() {
+        return "Changed letter case only".to_string();
+    }
+    if words_inserted == 0 && words_deleted == 0 {
+        return "Changed punctuation or spacing only".to_string();
+    }
+    // Spacing edits next to word edits are not worth a mention
+    let visible: Vec<&TextChange> = changes.iter().filter(|change| !change.old.is_empty() || !change.new.is_empty()).collect();
+    let mut parts: Vec<String> = visible.iter().take(SUMMARY_CHANGES).map(|change| match change.kind {
+        ChangeKind::INSERTED => format!("added {}", quote(&change.new)),
+        ChangeKind::DELETED => format!("removed {}", quote(&change.old)),
+        ChangeKind::REPLACED => format!("replaced {} with {}", quote(&change.old), quote(&change.new)),
+    }).collect();
+    if visible.len() > SUMMARY_CHANGES {
+        let more = visible.len() - SUMMARY_CHANGES;
+        parts.push(format!("and {} more change{} ({} words added, {} removed in all)", more, if more == 1 { "" } else { "s" }, words_inserted, words_deleted));
+    }
+    let summary = parts.join("; ");
+    let mut chars = summary.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+// Word-level differences between two versions of a message, e.g. an edited
+// Telegram message against the original. Whitespace and punctuation between
+// two edits are folded into them, so "10 am" -> "11 pm" is one replacement.
+#[cfg(feature = "nlp")]
+pub fn diff(old: &str, new: &str) -> TextDiff {
+    let old_tokens: Vec<(usize, &str)> = old.split_word_bound_indices().collect();
+    let new_tokens: Vec<(usize, &str)> = new.split_word_bound_indices().collect();
+    let old_words: Vec<&str> = old_tokens.iter().map(|(_, token)| *token).collect();
+    let new_words: Vec<&str> = new_tokens.iter().map(|(_, token)| *token).collect();
+    let mut ops = diff_ops(&old_words, &new_words);
+    
+    // Unchanged runs without words between two edits become part of both
+    let mut k = 0;
+    while k < ops.len() {
+        if ops[k] != DiffOp::EQUAL {
+            k += 1;
+            continue;
+        }
+        let run_end = ops[k..].iter().position(|op| *op != DiffOp::EQUAL).map_or(ops.len(), |len| k + len);
+        let new_index = ops[..k].iter().filter(|op| **op != DiffOp::DELETE).count();
+        let wordless = new_words[new_index..new_index + (run_end - k)].iter().all(|token| !is_word_token(token));
+        if k > 0 && run_end < ops.len() && wordless {
+            let replaced: Vec<DiffOp> = (k..run_end).map(|_| DiffOp::DELETE).chain((k..run_end).map(|_| DiffOp::INSERT)).collect();
+            ops.splice(k..run_end, replaced);
+            k += 2 * (run_end - k);
+        } else {
+            k = run_end;
+        }
+    }
+    
+    let mut segments = Vec::new();
+    let mut changes = Vec::new();
+    let (mut words_inserted, mut words_deleted, mut words_kept) = (0, 0, 0);
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    let end_of = |tokens: &[(usize, &str)], index: usize, text: &str| tokens.get(index).map_or(text.len(), |(offset, _)| *offset);
+    while k < ops.len() {
+        if ops[k] == DiffOp::EQUAL {
+            let start = j;
+            while k < ops.len() && ops[k] == DiffOp::EQUAL {
+                words_kept += is_word_token(new_words[j]) as usize;
+                i += 1;
+                j += 1;
+                k += 1;
+            }
+            let (from, to) = (new_tokens[start].0, end_of(&new_tokens, j, new));
+            segments.push(DiffSegment { op: DiffOp::EQUAL, text: new[from..to].to_string(), start: from, end: to });
+            continue;
+        }
+        // Deletions before insertions within one edit
+        let (old_start, new_start) = (i, j);
+        while k < ops.len() && ops[k] != DiffOp::EQUAL {
+            if ops[k] == DiffOp::DELETE {
+                words_deleted += is_word_token(old_words[i]) as usize;
+                i += 1;
+            } else {
+                words_inserted += is_word_token(new_words[j]) as usize;
+                j += 1;
+            }
+            k += 1;
+        }
+        let removed = old_tokens.get(old_start).map_or(old.len(), |(offset, _)| *offset)..end_of(&old_tokens, i, old);
+        let added = new_tokens.get(new_start).map_or(new.len(), |(offset, _)| *offset)..end_of(&new_tokens, j, new);
+        if !removed.is_empty() {
+            segments.push(DiffSegment { op: DiffOp::DELETE, text: old[removed.clone()].to_string(), start: removed.start, end: removed.end });
+        }
+        if !added.is_empty() {
+            segments.push(DiffSegment { op: DiffOp::INSERT, text: new[added.clone()].to_string(), start: added.start, end: added.end });
+        }
+        let kind = match (removed.is_empty(), added.is_empty()) {
+            (true, _) => ChangeKind::INSERTED,
+            (_, true) => ChangeKind::DELETED,
+            _ => ChangeKind::REPLACED,
+        };
+        changes.push(TextChange { kind, old: old[removed].trim().to_string(), new: new[added].trim().to_string() });
+    }
+    
+    let total_words = 2 * words_kept + words_inserted + words_deleted;
+    TextDiff {
+        summary: change_summary(old, new, &changes, words_inserted, words_deleted),
+        similarity: if total_words == 0 { 1.0 } else { 2.0 * words_kept as f64 / total_words as f64 },
+        segments,
+        changes,
+        words_inserted,
+        words_deleted,
+    }
+}
+
+#[cfg(feature = "stats")]
+pub fn analyze_data(data: &str) -> DataAnalysisResult {
+    // Parse data as numbers (comma-separated or space-separated)
+    checkpoint(0.0, "parsing");
+    let numbers = parsers::parse_numbers(data);
+    
+    analyze_numbers(&numbers)
+}
+
+// For a metric's recent history, oldest first: analyzes it like
+// analyze_numbers and reports whether the latest value is an anomaly (same
+// two standard deviations) to the alert tracker, which announces the metric
+// once and not on every evaluation
+#[cfg(feature = "stats")]
+pub fn analyze_metric(metric: &str, numbers: &[f64]) -> DataAnalysisResult {
+    let result = analyze_numbers(numbers);
+    if let Some(&latest) = numbers.last() {
+        let anomaly = if result.anomalies.contains(&latest) {
+            Some(if latest > result.mean { alerts::AnomalyDirection::HIGH } else { alerts::AnomalyDirection::LOW })
+        } else {
+            None
+        };
+        alerts::observe_metric(metric, anomaly, latest);
+    }
+    result
+}
+
+// Entry point for callers that already hold the values, skipping text parsing
+#[cfg(feature = "stats")]
+pub fn analyze_numbers(numbers: &[f64]) -> DataAnalysisResult {
+    // 8 bytes per value rather than the length of the text the values were
+    // parsed from, so a series costs the same as JSON, CSV or raw f64s
+    costs::record("analyze_data", std::mem::size_of_val(numbers), []);
     if numbers.is_empty() {
+        checkpoint(1.0, "done");
         return DataAnalysisResult {
             record_count: 0,
             mean: 0.0,
@@ -391,6 +1450,7 @@ pub fn analyze_data(data: &str) -> DataAnalysisResult {
         };
     }
     
+    checkpoint(0.2, "statistics");
     let record_count = numbers.len();
     let mean = numbers.iter().sum::<f64>() / record_count as f64;
     
@@ -404,7 +1464,7 @@ pub fn analyze_data(data: &str) -> DataAnalysisResult {
     let max = numbers.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
     
     // Detect patterns
-    let patterns = detect_patterns(&numbers);
+    let patterns = if checkpoint(0.4, "patterns") { detect_patterns(numbers) } else { Vec::new() };
     
     // Detect anomalies (values more than 2 standard deviations from mean)
     let anomalies: Vec<f64> = numbers.iter()
@@ -413,18 +1473,27 @@ pub fn analyze_data(data: &str) -> DataAnalysisResult {
         .collect();
     
     // Advanced forecasting
-    let forecast = generate_forecast(&numbers);
-    let confidence_interval = calculate_confidence_interval(&numbers, mean, std_dev);
-    let seasonality_detected = detect_seasonality(&numbers);
-    let trend_strength = calculate_trend_strength(&numbers);
+    let forecasting = checkpoint(0.6, "forecasting");
+    let forecast = if forecasting { generate_forecast(numbers) } else { Vec::new() };
+    let confidence_interval = calculate_confidence_interval(numbers, mean, std_dev);
+    let seasonality_detected = forecasting && detect_seasonality(numbers);
+    let trend_strength = if forecasting { calculate_trend_strength(numbers) } else { 0.0 };
     
     // Generate visualization data
-    let visualization_data = generate_visualization_data(&numbers);
+    let visualization_data = if checkpoint(0.8, "visualization") {
+        generate_visualization_data(numbers)
+    } else {
+        VisualizationData {
+            histogram: vec![],
+            box_plot: (0.0, 0.0, 0.0, 0.0, 0.0),
+            correlation_matrix: vec![],
+        }
+    };
     
     // Simple prediction (linear trend)
     let prediction = if numbers.len() > 1 {
         let x_values: Vec<f64> = (0..numbers.len()).map(|i| i as f64).collect();
-        let slope = calculate_slope(&x_values, &numbers);
+        let slope = calculate_slope(&x_values, numbers);
         let last_value = numbers.last().unwrap();
         let next_x = numbers.len() as f64;
         last_value + slope * (next_x - (numbers.len() - 1) as f64)
@@ -432,6 +1501,8 @@ pub fn analyze_data(data: &str) -> DataAnalysisResult {
         mean
     };
     
+    checkpoint(1.0, "done");
+    
     DataAnalysisResult {
         record_count,
         mean,
@@ -449,6 +1520,7 @@ pub fn analyze_data(data: &str) -> DataAnalysisResult {
     }
 }
 
+#[cfg(feature = "stats")]
 fn generate_forecast(numbers: &[f64]) -> Vec<f64> {
     if numbers.len() < 3 {
         return vec![];
@@ -469,6 +1541,7 @@ fn generate_forecast(numbers: &[f64]) -> Vec<f64> {
     forecast
 }
 
+#[cfg(feature = "stats")]
 fn calculate_confidence_interval(numbers: &[f64], mean: f64, std_dev: f64) -> (f64, f64) {
     let n = numbers.len() as f64;
     let standard_error = std_dev / n.sqrt();
@@ -477,6 +1550,7 @@ fn calculate_confidence_interval(numbers: &[f64], mean: f64, std_dev: f64) -> (f
     (mean - margin_of_error, mean + margin_of_error)
 }
 
+#[cfg(feature = "stats")]
 fn detect_seasonality(numbers: &[f64]) -> bool {
     if numbers.len() < 8 {
         return false;
@@ -487,6 +1561,7 @@ fn detect_seasonality(numbers: &[f64]) -> bool {
     autocorr > 0.6
 }
 
+#[cfg(feature = "stats")]
 fn calculate_trend_strength(numbers: &[f64]) -> f64 {
     if numbers.len() < 2 {
         return 0.0;
@@ -500,6 +1575,7 @@ fn calculate_trend_strength(numbers: &[f64]) -> f64 {
     (slope / max_possible_slope).abs()
 }
 
+#[cfg(feature = "stats")]
 fn generate_visualization_data(numbers: &[f64]) -> VisualizationData {
     // Generate histogram data
     let min = numbers.iter().fold(f64::INFINITY, |a, &b| a.min(b));
@@ -553,6 +1629,7 @@ fn generate_visualization_data(numbers: &[f64]) -> VisualizationData {
     }
 }
 
+#[cfg(feature = "stats")]
 fn detect_patterns(numbers: &[f64]) -> Vec<String> {
     let mut patterns = Vec::new();
     
@@ -602,7 +1679,8 @@ fn detect_patterns(numbers: &[f64]) -> Vec<String> {
     patterns
 }
 
-fn calculate_slope(x_values: &[f64], y_values: &[f64]) -> f64 {
+#[cfg(feature = "stats")]
+pub(crate) fn calculate_slope(x_values: &[f64], y_values: &[f64]) -> f64 {
     let n = x_values.len() as f64;
     let sum_x: f64 = x_values.iter().sum();
     let sum_y: f64 = y_values.iter().sum();
@@ -614,6 +1692,7 @@ fn calculate_slope(x_values: &[f64], y_values: &[f64]) -> f64 {
     (n * sum_xy - sum_x * sum_y) / (n * sum_x2 - sum_x * sum_x)
 }
 
+#[cfg(feature = "stats")]
 fn calculate_autocorrelation(numbers: &[f64]) -> f64 {
     if numbers.len() < 2 {
         return 0.0;
@@ -643,6 +1722,7 @@ mod tests {
     use super::*;
     
     #[test]
+    #[cfg(feature = "nlp")]
     fn test_text_analysis() {
         let text = "This is a test message. It contains multiple sentences. Hello world!";
         let result = analyze_text(text);
@@ -657,13 +1737,174 @@ mod tests {
         assert!(!result.keywords.is_empty());
         assert!(!result.entities.is_empty());
         assert!(!result.summary.is_empty());
-        assert!(result.readability_score >= 0.0 && result.readability_score <= 100.0);
+        assert!(result.readability_score.score >= 0.0 && result.readability_score.score <= 100.0);
+        assert_eq!(result.readability_score.formula, crate::readability::ReadabilityFormula::FleschReadingEase);
         assert!(!result.topics.is_empty());
         assert!(result.plagiarism_score >= 0.0 && result.plagiarism_score <= 1.0);
         assert!(result.processing_time > 0);
     }
     
     #[test]
+    #[cfg(feature = "nlp")]
+    fn test_diff() {
+        let edit = diff("Meeting at 10 am tomorrow", "Meeting at 11 pm tomorrow");
+        let ops: Vec<(DiffOp, &str)> = edit.segments.iter().map(|s| (s.op, s.text.as_str())).collect();
+        assert_eq!(ops, vec![(DiffOp::EQUAL, "Meeting at "), (DiffOp::DELETE, "10 am"), (DiffOp::INSERT, "11 pm"), (DiffOp::EQUAL, " tomorrow")]);
+        assert_eq!(edit.summary, "Replaced \"10 am\" with \"11 pm\"");
+        assert_eq!((edit.words_inserted, edit.words_deleted, edit.similarity), (2, 2, 0.6));
+        assert_eq!((edit.segments[1].start, edit.segments[2].start), (11, 11));
+        
+        let added = diff("Buy now", "Buy it now");
+        assert_eq!(added.changes, vec![TextChange { kind: ChangeKind::INSERTED, old: String::new(), new: "it".to_string() }]);
+        assert_eq!(added.summary, "Added \"it\"");
+        let rebuilt: String = added.segments.iter().filter(|s| s.op != DiffOp::DELETE).map(|s| s.text.as_str()).collect();
+        assert_eq!(rebuilt, "Buy it now");
+        
+        assert_eq!(diff("same text", "same text").summary, "No changes");
+        assert_eq!(diff("same text", "same text").similarity, 1.0);
+        assert_eq!(diff("hello World", "Hello world").summary, "Changed letter case only");
+        assert_eq!(diff("Hi there", "Hi, there!").summary, "Changed punctuation or spacing only");
+        let many = diff("a b c d e f g h", "a x c y e z g w");
+        assert!(many.summary.ends_with("and 1 more change (4 words added, 4 removed in all)"), "{}", many.summary);
+        assert_eq!(diff("", "سلام دنیا").summary, "Added \"سلام دنیا\"");
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_frequency_report() {
+        let report = frequency_report("The cat and the dog. The cat sleeps! 42 🐈‍⬛", 2);
+        assert_eq!((report.tokens, report.types, report.hapax_legomena), (9, 6, 4));
+        assert!((report.type_token_ratio - 6.0 / 9.0).abs() < 1e-9);
+        let top: Vec<(&str, usize)> = report.top_tokens.iter().map(|t| (t.token.as_str(), t.count)).collect();
+        assert_eq!(top, vec![("the", 3), ("cat", 2)]);
+        assert_eq!(report.top_content_words[0].token, "cat");
+        assert_eq!(report.characters[0].class, CharacterClass::LETTER);
+        let emoji = report.characters.iter().find(|c| c.class == CharacterClass::EMOJI).unwrap();
+        assert_eq!(emoji.count, 1);
+        assert!(report.zipf.unwrap().exponent > 0.0);
+        
+        let empty = frequency_report("", 10);
+        assert_eq!((empty.tokens, empty.type_token_ratio, empty.zipf), (0, 0.0, None));
+        assert!(empty.characters.is_empty());
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_compare_documents() {
+        let comparison = compare_documents(&[
+            "Claim your free crypto bonus now, limited giveaway for new wallets",
+            "Limited giveaway: claim a free crypto bonus for your wallet today",
+            "The meetup moves to Thursday evening at the library",
+            "Free crypto bonus giveaway, claim now before the wallets close",
+        ]);
+        let similarity = &comparison.similarity;
+        assert_eq!(similarity.len(), 4);
+        assert_eq!((similarity[2][2], similarity[0][2]), (1.0, 0.0));
+        assert!(similarity[0][1] > 0.6 && similarity[0][1] == similarity[1][0]);
+        assert_eq!(comparison.clusters, vec![vec![0, 1, 3]]);
+        
+        let bonus = comparison.shared_terms.iter().find(|term| term.term == "bonus").unwrap();
+        assert_eq!((bonus.documents.clone(), bonus.count), (vec![0, 1, 3], 3));
+        // "wallets" and "wallet" share a stem
+        assert_eq!(comparison.shared_terms.iter().find(|term| term.term == "wallets").unwrap().documents, vec![0, 1, 3]);
+        assert!(comparison.distinguishing_terms[2].contains(&"meetup".to_string()));
+        assert!(comparison.distinguishing_terms[1].contains(&"today".to_string()));
+        
+        let empty = compare_documents(&[]);
+        assert!(empty.similarity.is_empty() && empty.clusters.is_empty());
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_date_time_money_entities() {
+        let found = |text: &str| -> Vec<(String, String)> {
+            extract_entities(text).into_iter().map(|e| (e.entity_type, e.name)).collect()
+        };
+        let pair = |entity_type: &str, name: &str| (entity_type.to_string(), name.to_string());
+        
+        assert_eq!(found("Deadline 2024-05-01 at 17:30, budget $12.50 (up 12%)"), vec![
+            pair("DATE", "2024-05-01"), pair("TIME", "17:30"), pair("MONEY", "$12.50"), pair("PERCENT", "12%"),
+        ]);
+        assert_eq!(found("تحویل ۵ خرداد ساعت ۱۰ با تخفیف ۱۲٪ و مبلغ ۲۵۰٬۰۰۰ تومان"), vec![
+            pair("DATE", "۵ خرداد"), pair("TIME", "ساعت ۱۰"), pair("PERCENT", "۱۲٪"), pair("MONEY", "۲۵۰٬۰۰۰ تومان"),
+        ]);
+        assert_eq!(found("Meet May 3rd, 2024 at 9am or 1403/02/14; costs 40 EUR"), vec![
+            pair("DATE", "May 3rd, 2024"), pair("TIME", "9am"), pair("DATE", "1403/02/14"), pair("MONEY", "40 EUR"),
+        ]);
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_analysis_options() {
+        let options = AnalysisOptions::from_json(r#"{"skip_ner": true, "summary_sentences": 0, "language_hint": "fa"}"#).unwrap();
+        let result = analyze_text_with_options("Alice met Bob in Paris. They talked. It was sunny.", &options);
+        
+        assert!(result.entities.is_empty());
+        assert!(result.summary.is_empty());
+        assert_eq!(result.language, "fa");
+        assert_eq!(result.language_confidence, 1.0);
+        
+        assert!(AnalysisOptions::from_json(r#"{"skip_nre": true}"#).is_err());
+        assert_eq!(AnalysisOptions::from_json("").unwrap().summary_sentences, 2);
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_batch_language_share() {
+        let texts: Vec<String> = [
+            "سلام به همه، امروز هوا خیلی خوب است و ما به پارک می‌رویم",
+            "فردا جلسه ساعت ده صبح در دفتر مرکزی برگزار می‌شود",
+            "The release is scheduled for next week after the final review",
+        ].iter().map(|text| text.to_string()).collect();
+        let options = AnalysisOptions { summary_sentences: 0, ..Default::default() };
+        let batch = analyze_batch(&texts, &options);
+        
+        assert_eq!(batch.results.len(), 3);
+        assert_eq!(batch.languages[0].language, batch.results[0].language);
+        assert_eq!(batch.languages[0].messages, 2);
+        assert!((batch.languages[0].share - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(batch.script_stats.scripts[0].script, crate::language::Script::ARABIC);
+        assert_eq!(batch.script_stats.characters, batch.results.iter().map(|r| r.script_stats.characters).sum::<usize>());
+    }
+    
+    #[test]
+    fn test_deadline_skips_stages() {
+        let text = "The release is scheduled for next week after the final review.";
+        let expired = Some(deadline::Deadline::after_millis(0));
+        let result = deadline::with_deadline(expired, || analyze_text(text));
+        assert!(result.timed_out);
+        assert!(result.keywords.is_empty() && result.summary.is_empty());
+        // Counting happens before the first stage
+        assert_eq!(result.word_count, 11);
+        
+        let batch = deadline::with_deadline(expired, || analyze_batch(&[text.to_string()], &AnalysisOptions::default()));
+        assert!(batch.timed_out && batch.results[0].timed_out);
+        assert!(result.stage_timings.is_empty());
+        
+        let full = analyze_text(text);
+        assert!(!full.timed_out);
+        for stage in ["language_detection_ms", "sentiment_ms", "keywords_ms", "ner_ms", "topic_modeling_ms"] {
+            assert!(full.stage_timings[stage] >= 0.0);
+        }
+        // Skipped by default
+        assert!(!full.stage_timings.contains_key("spam_ms"));
+    }
+    
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_numbers_match_parsed_data() {
+        let values: Vec<f64> = (1..=10).map(|i| i as f64).collect();
+        let from_values = analyze_numbers(&values);
+        let from_text = analyze_data("1,2,3,4,5,6,7,8,9,10");
+        
+        assert_eq!(from_values.record_count, from_text.record_count);
+        assert_eq!(from_values.mean, from_text.mean);
+        assert_eq!(from_values.forecast, from_text.forecast);
+        assert_eq!(analyze_numbers(&[]).record_count, 0);
+    }
+    
+    #[test]
+    #[cfg(feature = "stats")]
     fn test_data_analysis() {
         let data = "1,2,3,4,5,6,7,8,9,10";
         let result = analyze_data(data);
@@ -682,9 +1923,72 @@ mod tests {
     }
     
     #[test]
+    #[cfg(feature = "nlp")]
+    fn test_keywords_use_language_stop_words() {
+        let keywords_in = |text: &str, lang: &str, stemmed: bool| extract_keywords(text, &language::whole(text, lang, 1.0), stemmed, false);
+        let keywords = keywords_in("und und und Katze Katze oder Hund", "de", false);
+        assert_eq!(keywords[0], "katze");
+        assert!(!keywords.contains(&"und".to_string()));
+        assert!(!keywords.contains(&"oder".to_string()));
+        
+        // Unknown language: every built-in list applies
+        let keywords = keywords_in("this این bot bot", "unknown", false);
+        assert_eq!(keywords, vec!["bot".to_string()]);
+        
+        // Inflections share one keyword, shown as first written
+        let keywords = keywords_in("Running late, he runs, ran, run again. کتاب‌ها و کتاب", "en", true);
+        assert_eq!(keywords[..2], ["running".to_string(), "کتاب‌ها".to_string()]);
+        
+        // From noun phrases only, verbs and adverbs drop out
+        let text = "We really need to fix the slow checkout page before testing the checkout flow again.";
+        let keywords = extract_keywords(text, &language::whole(text, "en", 1.0), false, true);
+        assert_eq!(keywords[0], "checkout");
+        assert!(!keywords.contains(&"fix".to_string()) && !keywords.contains(&"need".to_string()));
+        
+        // Arabic and Persian spellings of the same words
+        let text = "کتاب كتاب کتاب مي\u{200C}روم می\u{200C}روم";
+        let options = AnalysisOptions { normalize: Some(TextNormalizer::default()), ..Default::default() };
+        let keywords = analyze_text_with_options(text, &options).keywords;
+        assert!(keywords.contains(&"کتاب".to_string()));
+        assert!(!keywords.contains(&"كتاب".to_string()));
+        
+        // Mostly Persian, so a whole-message guess would not stem the
+        // English sentence
+        let text = "امروز بعد از ظهر جلسه مهمی با تیم فروش و بازاریابی داریم. Meetings help when each meeting has a goal and the meeting ends on time.";
+        let result = analyze_text(text);
+        assert_eq!(result.language_spans.len(), 2);
+        assert_eq!(result.keywords[0], "meetings");
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_rake_and_yake_keywords() {
+        let text = "The payment gateway is timing out. Customers report the payment gateway fails at checkout, and support is overwhelmed.";
+        let spans = language::whole(text, "en", 1.0);
+        
+        // Phrases between stop words, longest co-occurring first
+        let rake = rake_keywords(text, &spans, false, false, 5);
+        assert_eq!(rake, ["payment gateway fails", "payment gateway", "customers report", "timing", "checkout"]);
+        assert!(rake.contains(&"payment gateway".to_string()));
+        assert!(rake.iter().all(|phrase| !phrase.split(' ').any(|word| ["the", "and", "is", "at"].contains(&word))));
+        
+        // Repeated phrases win, and no word appears in two keywords
+        let yake = yake_keywords(text, &spans, false, false, 5);
+        assert_eq!(yake[0], "payment gateway");
+        let words: Vec<&str> = yake.iter().flat_map(|phrase| phrase.split(' ')).collect();
+        assert_eq!(words.len(), words.iter().collect::<HashSet<_>>().len());
+        
+        // Punctuation ends a phrase, and numbers are never keywords
+        let text = "Order 42, shipped tomorrow";
+        assert_eq!(rake_keywords(text, &language::whole(text, "en", 1.0), false, false, 5), vec!["shipped tomorrow".to_string(), "order".to_string()]);
+        assert!(yake_keywords("", &spans, false, false, 5).is_empty());
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
     fn test_sentiment_analysis() {
-        assert_eq!(analyze_sentiment_advanced("I love this! It's amazing!").0, "positive");
-        assert_eq!(analyze_sentiment_advanced("I hate this! It's terrible!").0, "negative");
-        assert_eq!(analyze_sentiment_advanced("This is normal.").0, "neutral");
+        assert_eq!(analyze_sentiment_advanced("I love this! It's amazing!", "en").0, "positive");
+        assert_eq!(analyze_sentiment_advanced("I hate this! It's terrible!", "en").0, "negative");
+        assert_eq!(analyze_sentiment_advanced("This is normal.", "en").0, "neutral");
     }
 } 
\ No newline at end of file
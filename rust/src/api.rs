@@ -0,0 +1,1430 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::{alerts, config, i18n, jobs, lifecycle, logging, performance, templates, tenant, validation};
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+use crate::queue;
+
+// Rust entry points for services that depend on the crate directly; the C
+// exports in lib.rs are thin wrappers over these. Everything reachable from
+// this module follows semver. Items only reachable through the C ABI are
+// internal and may change in any release.
+
+#[cfg(feature = "nlp")]
+pub use crate::analysis::{AnalysisOptions, BatchAnalysisResult, ChangeKind, CharacterClass, CharacterShare, DiffOp, DiffSegment, DocumentComparison, Entity, FrequencyReport, LanguageShare, SharedTerm, TextAnalysisResult, TextChange, TextDiff, TokenCount, Topic, ZipfFit, MAX_COMPARED_DOCUMENTS};
+#[cfg(feature = "nlp")]
+pub use crate::incremental::IncrementalTextAnalyzer;
+#[cfg(feature = "nlp")]
+pub use crate::commands::{CommandMatch, CommandMatchOptions};
+#[cfg(feature = "nlp")]
+pub use crate::conversation::{ActionItem, ConversationDigest, ConversationMessage, DigestPoint, Participant};
+#[cfg(feature = "nlp")]
+pub use crate::emoji::{EmojiCount, EmojiStats};
+#[cfg(feature = "nlp")]
+pub use crate::transcripts::{SpeakerStats, SpeechStats, Transcript, TranscriptAnalysis, TranscriptWord};
+#[cfg(feature = "nlp")]
+pub use crate::entity_patterns::{EntityPattern, EntityPatternRegistry, PatternMatch};
+#[cfg(feature = "nlp")]
+pub use crate::tags::{TagCount, TagStats};
+#[cfg(feature = "nlp")]
+pub use crate::spam::{SpamScore, SpamSignal};
+#[cfg(feature = "nlp")]
+pub use crate::structure::StructureStats;
+#[cfg(feature = "nlp")]
+pub use crate::shadow::{Divergence, ShadowReport, StageComparison};
+#[cfg(feature = "nlp")]
+pub use crate::keywords::{Keyword, KeywordExtractor};
+#[cfg(feature = "nlp")]
+pub use crate::language::{LanguageSpan, Script, ScriptShare, ScriptStats};
+#[cfg(feature = "nlp")]
+pub use crate::pos::{NounPhrase, PerceptronTagger, PosTag, TaggedToken};
+#[cfg(feature = "nlp")]
+pub use crate::transliterate::TransliterationDirection;
+#[cfg(feature = "nlp")]
+pub use crate::moderation::{ChatModerationPolicy, ModerationAction, ModerationResult, ProfanityMatch, ProfanitySeverity};
+#[cfg(feature = "nlp")]
+pub use crate::normalize::{TextNormalizer, UnicodeForm, ZwnjHandling};
+#[cfg(feature = "nlp")]
+pub use crate::plugins::Analyzer;
+#[cfg(feature = "nlp")]
+pub use crate::readability::{ReadabilityFormula, ReadabilityReport};
+#[cfg(feature = "nlp")]
+pub use crate::replies::{Embedder, MatchMethod, ReplyRanker, ReplySuggestion};
+#[cfg(feature = "nlp")]
+pub use crate::similarity::{DuplicateDetector, DuplicateDetectorConfig, DuplicateMatch, Similarity};
+#[cfg(feature = "nlp")]
+pub use crate::topics::{ModelTopic, TopicKeyword, TopicModel, TopicModelConfig};
+#[cfg(feature = "nlp")]
+pub use crate::sentiment::{SentimentAnalyzer, SentimentScore};
+#[cfg(feature = "nlp")]
+pub use crate::summary::SummaryOptions;
+#[cfg(feature = "nlp")]
+pub use crate::truncation::{TextEntity, TruncateOptions, TruncatedText, TELEGRAM_CAPTION_LIMIT, TELEGRAM_MESSAGE_LIMIT};
+#[cfg(feature = "nlp")]
+pub use crate::stopwords::{BuiltinStopWords, StopWordProvider};
+#[cfg(feature = "stats")]
+pub use crate::analysis::{DataAnalysisResult, VisualizationData};
+#[cfg(feature = "stats")]
+pub use crate::polls::{OptionShare, OptionTrend, Poll, PollOption, PollReport, PollSummary};
+#[cfg(feature = "realtime")]
+pub use crate::realtime::{RealtimeResult, StageOutcome, StageTiming};
+pub use crate::alerts::{AlertEvent, AlertState, AnomalyDirection};
+#[cfg(feature = "chaos")]
+pub use crate::chaos::{Fault, FaultConfig, FaultStats};
+#[cfg(feature = "crypto")]
+pub use crate::crypto::{BatchResult, CiphertextFormat, EnvelopeOptions, Kdf};
+#[cfg(feature = "crypto")]
+pub use crate::hashing::HashAlgorithm;
+#[cfg(feature = "crypto")]
+pub use crate::deeplinks::DeepLink;
+pub use crate::campaigns::{CampaignReport, CampaignStats};
+pub use crate::chats::{ActiveUser, ActivityStreak, ChatMemberUpdate, ChatMessage, CohortRetention, Leaderboard, LeaderboardOptions, MemberReport, MessageRef, PostEngagement, ReactedMessage, ReactionCount, ReactionCountUpdate, SentimentPoint, SentimentTrend, StickerReport, StickerUsage, UsageCount};
+pub use crate::slowmode::{SlowmodeRecommendation, UserCooldown};
+pub use crate::concurrency::ConcurrencyStats;
+#[cfg(feature = "documents")]
+pub use crate::documents::{DocumentFormat, ExtractedDocument, MAX_DOCUMENT_BYTES};
+pub use crate::counters::{CounterRollup, CounterValue, RollupPoint};
+pub use crate::bandit::ArmStats;
+pub use crate::export::{Cell, ExportFormat, Table};
+pub use crate::fingerprints::{Fingerprint, FingerprintKind, FingerprintMatch, FingerprintStore};
+pub use crate::i18n::{PluralCategory, PluralRule};
+pub use crate::jobs::{JobSnapshot, JobStatus};
+pub use crate::lifecycle::{InitStep, WarmUpOptions};
+pub use crate::links::{LinkReport, LinkVerdict};
+pub use crate::logging::LogLevel;
+pub use crate::ocr::OcrProvider;
+#[cfg(feature = "ocr")]
+pub use crate::ocr::CommandOcr;
+pub use crate::pii::{PiiKind, PiiMatch, PiiRedactor, RedactionStyle};
+pub use crate::performance::{OptimizationPriority, OptimizationSuggestion, PerformanceProfile};
+pub use crate::qr::{QrPayload, QrPayloadKind};
+#[cfg(feature = "qr")]
+pub use crate::qr::{QrErrorCorrection, QrImage, QrOptions};
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+pub use crate::queue::Completion;
+pub use crate::secrets::Secret;
+pub use crate::templates::Template;
+pub use crate::security::{Clock, RateLimitDecision, SecurityConfig, SecurityManager, SimulatedClock, SimulatedRequest};
+pub use crate::tenant::{TenantContext, TenantMetrics};
+pub use crate::costs::{CostLine, CostReport, CostTotals};
+#[cfg(feature = "webhooks")]
+pub use crate::webhooks::{WebhookPriority, WebhookStats};
+pub use crate::validation::{ValidationError, ValidationResult, ValidationRule, ValidationRuleType, ValidationSeverity, ValidationWarning};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Input(String),
+    Config(String),
+    Crypto(String),
+    Job(String),
+    Lifecycle(String),
+    RateLimited(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Input(message) => write!(f, "Invalid input: {}", message),
+            Error::Config(message) => write!(f, "Config error: {}", message),
+            Error::Crypto(message) => write!(f, "Crypto error: {}", message),
+            Error::Job(message) => write!(f, "Job error: {}", message),
+            Error::Lifecycle(message) => write!(f, "Lifecycle error: {}", message),
+            Error::RateLimited(message) => write!(f, "Rate limited: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Text pipeline with fixed per-call options; cheap to build and reuse
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Default)]
+pub struct TextAnalyzer {
+    options: AnalysisOptions,
+}
+
+#[cfg(feature = "nlp")]
+impl TextAnalyzer {
+    pub fn builder() -> TextAnalyzerBuilder {
+        TextAnalyzerBuilder::default()
+    }
+    
+    // Options as accepted by analyze_text_with_options; empty means defaults
+    pub fn from_json(options_json: &str) -> Result<Self> {
+        AnalysisOptions::from_json(options_json).map(Self::from).map_err(Error::Input)
+    }
+    
+    pub fn options(&self) -> &AnalysisOptions {
+        &self.options
+    }
+    
+    pub fn analyze(&self, text: &str) -> TextAnalysisResult {
+        crate::analysis::analyze_text_with_options(text, &self.options)
+    }
+    
+    // For text that arrives in chunks or is too long for one call; see
+    // IncrementalTextAnalyzer
+    pub fn incremental(&self) -> IncrementalTextAnalyzer {
+        IncrementalTextAnalyzer::new(self.options.clone())
+    }
+    
+    // The text the OCR provider reads in the image, analyzed like a message
+    pub fn analyze_image(&self, image: &[u8]) -> Result<TextAnalysisResult> {
+        Ok(self.analyze(&ocr_text(image)?))
+    }
+    
+    // The text of a PDF or DOCX file, analyzed like a message
+    #[cfg(feature = "documents")]
+    pub fn analyze_document(&self, bytes: &[u8]) -> Result<TextAnalysisResult> {
+        Ok(self.analyze(&extract_document_text(bytes)?.text))
+    }
+    
+    // Transcribed voice notes: the joined words analyzed like a message,
+    // plus speaking rate, silence and per-speaker sentiment
+    pub fn analyze_transcript(&self, transcript: &Transcript) -> TranscriptAnalysis {
+        crate::transcripts::analyze(transcript, &self.options)
+    }
+}
+
+#[cfg(feature = "nlp")]
+impl From<AnalysisOptions> for TextAnalyzer {
+    fn from(options: AnalysisOptions) -> Self {
+        Self { options }
+    }
+}
+
+#[cfg(feature = "nlp")]
+#[derive(Debug, Clone, Default)]
+pub struct TextAnalyzerBuilder {
+    options: AnalysisOptions,
+}
+
+#[cfg(feature = "nlp")]
+impl TextAnalyzerBuilder {
+    pub fn skip_ner(mut self, skip: bool) -> Self {
+        self.options.skip_ner = skip;
+        self
+    }
+    
+    pub fn skip_topics(mut self, skip: bool) -> Self {
+        self.options.skip_topics = skip;
+        self
+    }
+    
+    pub fn skip_plagiarism(mut self, skip: bool) -> Self {
+        self.options.skip_plagiarism = skip;
+        self
+    }
+    
+    pub fn skip_plugins(mut self, skip: bool) -> Self {
+        self.options.skip_plugins = skip;
+        self
+    }
+    
+    // 0 disables the summary stage
+    pub fn summary_sentences(mut self, sentences: usize) -> Self {
+        self.options.summary_sentences = sentences;
+        self
+    }
+    
+    // Summary budget in UTF-16 code units, by default Telegram's 4096; 0 for
+    // none
+    pub fn summary_max_chars(mut self, max_chars: usize) -> Self {
+        self.options.summary_max_chars = max_chars;
+        self
+    }
+    
+    // Counts words and extracts keywords with emoji and emoticons removed
+    pub fn strip_emoji(mut self, strip: bool) -> Self {
+        self.options.strip_emoji = strip;
+        self
+    }
+    
+    // Adds TextAnalysisResult::spam
+    pub fn spam_score(mut self, enabled: bool) -> Self {
+        self.options.spam_score = enabled;
+        self
+    }
+    
+    // Adds TextAnalysisResult::structure
+    pub fn structure(mut self, enabled: bool) -> Self {
+        self.options.structure = enabled;
+        self
+    }
+    
+    // Leaves code blocks and inline code out of every stage
+    pub fn skip_code(mut self, enabled: bool) -> Self {
+        self.options.skip_code = enabled;
+        self
+    }
+    
+    // Groups inflections in keywords and topics; on by default
+    pub fn stem(mut self, enabled: bool) -> Self {
+        self.options.stem = enabled;
+        self
+    }
+    
+    // Runs every stage on the normalized text
+    pub fn normalize(mut self, normalizer: TextNormalizer) -> Self {
+        self.options.normalize = Some(normalizer);
+        self
+    }
+    
+    // Spells out chat abbreviations before every stage
+    pub fn expand_abbreviations(mut self, enabled: bool) -> Self {
+        self.options.expand_abbreviations = enabled;
+        self
+    }
+    
+    // Transliterates Finglish to Persian script before every stage
+    pub fn finglish(mut self, enabled: bool) -> Self {
+        self.options.finglish = enabled;
+        self
+    }
+    
+    // Takes keywords only from noun phrases
+    pub fn noun_phrase_keywords(mut self, enabled: bool) -> Self {
+        self.options.noun_phrase_keywords = enabled;
+        self
+    }
+    
+    pub fn language_hint(mut self, language: impl Into<String>) -> Self {
+        self.options.language_hint = Some(language.into());
+        self
+    }
+    
+    pub fn build(self) -> TextAnalyzer {
+        TextAnalyzer { options: self.options }
+    }
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_text(text: &str) -> TextAnalysisResult {
+    TextAnalyzer::default().analyze(text)
+}
+
+#[cfg(all(feature = "nlp", feature = "documents"))]
+pub fn analyze_document(bytes: &[u8]) -> Result<TextAnalysisResult> {
+    TextAnalyzer::default().analyze_document(bytes)
+}
+
+// Transcript JSON: {"words": [{"word", "start", "end", "speaker"}, ...],
+// "duration"}, times in seconds
+#[cfg(feature = "nlp")]
+pub fn analyze_transcript(transcript_json: &str) -> Result<TranscriptAnalysis> {
+    let transcript = Transcript::from_json(transcript_json).map_err(Error::Input)?;
+    Ok(TextAnalyzer::default().analyze_transcript(&transcript))
+}
+
+// Plain text of a PDF or DOCX file, told apart by content; up to 20 MB, the
+// most a bot can download
+#[cfg(feature = "documents")]
+pub fn extract_document_text(bytes: &[u8]) -> Result<ExtractedDocument> {
+    crate::documents::extract(bytes).map_err(Error::Input)
+}
+
+// Registering another provider, or None, drops cached OCR results
+pub fn set_ocr_provider(provider: Option<std::sync::Arc<dyn OcrProvider>>) {
+    crate::ocr::set_provider(provider)
+}
+
+// Text of an image, read by the registered OCR provider and cached by the
+// image's hash; "" for an image without text
+pub fn ocr_text(image: &[u8]) -> Result<String> {
+    crate::ocr::recognize(image).map_err(Error::Input)
+}
+
+// Risk of a single link from the link alone; nothing is fetched
+pub fn classify_link(url: &str) -> LinkReport {
+    crate::links::classify(url)
+}
+
+// Every link in a message; unsafe ones are recorded as UNSAFE_LINK security
+// events and dangerous ones alert
+pub fn scan_links(text: &str) -> Vec<LinkReport> {
+    crate::links::scan(text, "message")
+}
+
+// The text a scanner read out of a QR code, parsed by kind with its links
+// classified and unsafe ones recorded like scan_links does
+pub fn decode_qr_payload(payload: &str) -> QrPayload {
+    crate::qr::decode(payload)
+}
+
+#[cfg(feature = "qr")]
+pub fn generate_qr(text: &str, options: &QrOptions) -> Result<QrImage> {
+    crate::qr::generate(text, options).map_err(Error::Input)
+}
+
+// Handle-based IncrementalTextAnalyzer for hosts that cannot keep one: begin,
+// any number of pushes, then finish for the result
+#[cfg(feature = "nlp")]
+pub fn analyze_text_begin(analyzer: &TextAnalyzer) -> Result<u64> {
+    crate::incremental::begin(analyzer.options.clone()).map_err(Error::RateLimited)
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_text_push(handle: u64, chunk: &str) -> Result<()> {
+    crate::incremental::push(handle, chunk).map_err(Error::Input)
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_text_finish(handle: u64) -> Result<TextAnalysisResult> {
+    crate::incremental::finish(handle).map_err(Error::Input)
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_text_abort(handle: u64) -> bool {
+    crate::incremental::abort(handle)
+}
+
+// Every message analyzed, plus each detected language's share of the
+// messages and the script distribution over all of them
+#[cfg(feature = "nlp")]
+pub fn analyze_batch(texts: &[String], options: &AnalysisOptions) -> BatchAnalysisResult {
+    crate::analysis::analyze_batch(texts, options)
+}
+
+// Token counts, hapax legomena, type-token ratio, character classes and the
+// Zipf exponent of a text; up to limit top tokens and content words
+#[cfg(feature = "nlp")]
+pub fn frequency_report(text: &str, limit: usize) -> FrequencyReport {
+    crate::analysis::frequency_report(text, limit)
+}
+
+// Pairwise similarity, shared terms, distinguishing terms and clusters of
+// similar documents, e.g. to spot one spam message posted across chats
+#[cfg(feature = "nlp")]
+pub fn compare_documents(texts: &[&str]) -> Result<DocumentComparison> {
+    if texts.len() > MAX_COMPARED_DOCUMENTS {
+        return Err(Error::Input(format!("{} documents exceed the limit of {}", texts.len(), MAX_COMPARED_DOCUMENTS)));
+    }
+    Ok(crate::analysis::compare_documents(texts))
+}
+
+// Word-level insertions and deletions between two versions of a message,
+// with a one-line summary such as `Replaced "10 am" with "11 pm"`
+#[cfg(feature = "nlp")]
+pub fn diff_texts(old: &str, new: &str) -> TextDiff {
+    crate::analysis::diff(old, new)
+}
+
+// Characters per script (Latin, Arabic, Cyrillic, other), emoji, digits and
+// symbols
+#[cfg(feature = "nlp")]
+pub fn script_stats(text: &str) -> ScriptStats {
+    crate::language::script_stats(text)
+}
+
+// Adds an analyzer to the text pipeline, or swaps the one registered under
+// the same name; its output appears under result.plugins[name]
+#[cfg(feature = "nlp")]
+pub fn register_analyzer(name: &str, analyzer: impl Analyzer + 'static) -> Result<()> {
+    crate::plugins::register(name, std::sync::Arc::new(analyzer)).map_err(Error::Input)
+}
+
+#[cfg(feature = "nlp")]
+pub fn unregister_analyzer(name: &str) -> bool {
+    crate::plugins::unregister(name)
+}
+
+#[cfg(feature = "nlp")]
+pub fn registered_analyzers() -> Vec<String> {
+    crate::plugins::registered()
+}
+
+// Extractive summary of text on its own; the default options fit one
+// Telegram message
+#[cfg(feature = "nlp")]
+pub fn summarize(text: &str, options: &SummaryOptions) -> String {
+    crate::summary::summarize(text, "unknown", options)
+}
+
+// Fits text and its entities into limit UTF-16 code units, e.g.
+// TELEGRAM_MESSAGE_LIMIT or TELEGRAM_CAPTION_LIMIT, ending in an ellipsis
+#[cfg(feature = "nlp")]
+pub fn truncate_for_telegram(text: &str, entities: &[TextEntity], limit: usize) -> Result<TruncatedText> {
+    truncate_for_telegram_with_options(text, entities, limit, &TruncateOptions::default())
+}
+
+#[cfg(feature = "nlp")]
+pub fn truncate_for_telegram_with_options(text: &str, entities: &[TextEntity], limit: usize, options: &TruncateOptions) -> Result<TruncatedText> {
+    crate::truncation::truncate_for_telegram(text, entities, limit, options).map_err(Error::Input)
+}
+
+// Finglish ("salam khoobi") to Persian script, or Persian to Finglish, e.g.
+// to match either spelling in search
+#[cfg(feature = "nlp")]
+pub fn transliterate(text: &str, direction: TransliterationDirection) -> String {
+    crate::transliterate::transliterate(text, direction)
+}
+
+// Part-of-speech tags of every word and punctuation mark; None detects the
+// language of each segment
+#[cfg(feature = "nlp")]
+pub fn pos_tag(text: &str, language: Option<&str>) -> Vec<TaggedToken> {
+    match language {
+        Some(language) => crate::pos::tag(text, language),
+        None => crate::pos::tag_in(text, &crate::language::detect_languages_segmented(text)),
+    }
+}
+
+#[cfg(feature = "nlp")]
+pub fn noun_phrases(text: &str, language: Option<&str>) -> Vec<NounPhrase> {
+    match language {
+        Some(language) => crate::pos::noun_phrases(text, language),
+        None => crate::pos::noun_phrases_in(text, &crate::language::detect_languages_segmented(text)),
+    }
+}
+
+// Tags the language with a trained PerceptronTagger instead of the built-in
+// rules; None restores the rules
+#[cfg(feature = "nlp")]
+pub fn set_pos_model(language: &str, model: Option<PerceptronTagger>) -> Result<()> {
+    crate::pos::set_model(language, model).map_err(Error::Config)
+}
+
+// A PerceptronTagger saved as JSON; relative paths resolve under the data
+// directory. Returns the model's feature count
+#[cfg(feature = "nlp")]
+pub fn load_pos_model(language: &str, path: &str) -> Result<usize> {
+    crate::pos::load_model(language, path).map_err(Error::Config)
+}
+
+// Language per segment for messages mixing e.g. Persian and English; the
+// spans cover text in order
+#[cfg(feature = "nlp")]
+pub fn detect_languages_segmented(text: &str) -> Vec<LanguageSpan> {
+    crate::language::detect_languages_segmented(text)
+}
+
+// Named entities with byte offsets, including names from loaded gazetteers
+#[cfg(feature = "nlp")]
+pub fn extract_entities(text: &str) -> Vec<Entity> {
+    crate::analysis::extract_entities(text)
+}
+
+// Loads a JSON or CSV name list under `name`, replacing a gazetteer of that
+// name; default_type covers CSV lines without a type. Returns the number of
+// names.
+#[cfg(feature = "nlp")]
+pub fn load_gazetteer(name: &str, path: &str, default_type: Option<&str>) -> Result<usize> {
+    crate::gazetteers::load(name, path, default_type).map_err(Error::Input)
+}
+
+// (name, entity type) pairs
+#[cfg(feature = "nlp")]
+pub fn add_gazetteer(name: &str, entries: Vec<(String, String)>) -> Result<usize> {
+    crate::gazetteers::register(name, entries).map_err(Error::Input)
+}
+
+#[cfg(feature = "nlp")]
+pub fn remove_gazetteer(name: &str) -> bool {
+    crate::gazetteers::remove(name)
+}
+
+// Adds or replaces a named entity pattern; added patterns are tried before
+// the built-in ones and survive config reloads
+#[cfg(feature = "nlp")]
+pub fn add_entity_pattern(pattern: EntityPattern) -> Result<()> {
+    crate::entity_patterns::add(pattern).map_err(Error::Input)
+}
+
+// Also removes built-ins ("date", "time", "money", "percent", "person",
+// "email", "url", "phone") and patterns from the config
+#[cfg(feature = "nlp")]
+pub fn remove_entity_pattern(name: &str) -> bool {
+    crate::entity_patterns::remove(name)
+}
+
+// The active patterns in the order they are tried
+#[cfg(feature = "nlp")]
+pub fn entity_patterns() -> Vec<EntityPattern> {
+    crate::entity_patterns::list()
+}
+
+// Embedding model for reply suggestions; None ranks by keyword overlap
+#[cfg(feature = "nlp")]
+pub fn set_embedder(embedder: Option<std::sync::Arc<dyn Embedder>>) {
+    crate::replies::set_embedder(embedder)
+}
+
+// Prepares canned replies once under a name; build a ReplyRanker directly to
+// hold them yourself
+#[cfg(feature = "nlp")]
+pub fn set_reply_set<S: AsRef<str>>(name: &str, replies: &[S]) {
+    crate::replies::set_reply_set(name, replies)
+}
+
+#[cfg(feature = "nlp")]
+pub fn remove_reply_set(name: &str) -> bool {
+    crate::replies::remove_reply_set(name)
+}
+
+// Adds one reply to a named set and returns its id, which suggestions report
+// as their index
+#[cfg(feature = "nlp")]
+pub fn add_reply(name: &str, reply: &str) -> Result<usize> {
+    crate::replies::add_reply(name, reply).ok_or_else(|| Error::Input(format!("No reply set named {}", name)))
+}
+
+#[cfg(feature = "nlp")]
+pub fn remove_reply(name: &str, id: usize) -> bool {
+    crate::replies::remove_reply(name, id)
+}
+
+// Relative paths resolve under the data directory; both return how many
+// replies the set holds. Embeddings are stored with the replies, so loading
+// does not call the embedder for them
+#[cfg(feature = "nlp")]
+pub fn save_reply_set(name: &str, path: &str) -> Result<usize> {
+    crate::replies::save_reply_set(name, path).map_err(Error::Config)
+}
+
+#[cfg(feature = "nlp")]
+pub fn load_reply_set(name: &str, path: &str) -> Result<usize> {
+    crate::replies::load_reply_set(name, path).map_err(Error::Config)
+}
+
+#[cfg(feature = "nlp")]
+pub fn suggest_replies(name: &str, message: &str, limit: usize) -> Result<Vec<ReplySuggestion>> {
+    crate::replies::suggest(name, message, limit).ok_or_else(|| Error::Input(format!("No reply set named {}", name)))
+}
+
+// Participants, key points, decisions and action items of a JSON array of
+// messages; max_tokens bounds the words in the digest, 0 for no bound
+#[cfg(feature = "nlp")]
+pub fn summarize_conversation(messages_json: &str, max_tokens: usize) -> Result<ConversationDigest> {
+    crate::conversation::summarize_json(messages_json, max_tokens).map_err(Error::Input)
+}
+
+#[cfg(feature = "nlp")]
+pub fn summarize_messages(messages: &[ConversationMessage], max_tokens: usize) -> ConversationDigest {
+    crate::conversation::summarize(messages, max_tokens)
+}
+
+// Profanity check of a message under its chat's policy
+#[cfg(feature = "nlp")]
+pub fn moderate_message(chat_id: i64, text: &str) -> ModerationResult {
+    crate::moderation::check(chat_id, text)
+}
+
+// Profanity check of the text in an image under the chat's policy
+#[cfg(feature = "nlp")]
+pub fn moderate_image(chat_id: i64, image: &[u8]) -> Result<ModerationResult> {
+    Ok(moderate_message(chat_id, &ocr_text(image)?))
+}
+
+// Replaces the chat's overrides; saved when a moderation store is open
+#[cfg(feature = "nlp")]
+pub fn set_chat_moderation_policy(chat_id: i64, policy: ChatModerationPolicy) -> Result<()> {
+    crate::moderation::set_chat_policy(chat_id, policy).map_err(Error::Config)
+}
+
+#[cfg(feature = "nlp")]
+pub fn clear_chat_moderation_policy(chat_id: i64) -> Result<()> {
+    crate::moderation::clear_chat_policy(chat_id).map_err(Error::Config)
+}
+
+// The chat's overrides; empty when it has none
+#[cfg(feature = "nlp")]
+pub fn chat_moderation_policy(chat_id: i64) -> ChatModerationPolicy {
+    crate::moderation::chat_policy(chat_id)
+}
+
+// Loads and from now on saves chat overrides at path; returns how many
+// chats it already held
+#[cfg(feature = "nlp")]
+pub fn open_moderation_store(path: &str) -> Result<usize> {
+    crate::moderation::open_store(path).map_err(Error::Config)
+}
+
+// Lexicon used for TextAnalysisResult::sentiment from now on
+#[cfg(feature = "nlp")]
+pub fn set_sentiment_analyzer(analyzer: SentimentAnalyzer) {
+    crate::sentiment::set_active(analyzer)
+}
+
+// Lexicon compared with the active one on the share of texts set by
+// shadow.sample_rate; its scores are never returned. None stops comparing.
+#[cfg(feature = "nlp")]
+pub fn set_shadow_sentiment_analyzer(analyzer: Option<SentimentAnalyzer>) {
+    crate::shadow::set_sentiment_analyzer(analyzer)
+}
+
+// How often and how far the shadow candidates disagreed with the primary
+// results, for the current tenant
+#[cfg(feature = "nlp")]
+pub fn shadow_report() -> ShadowReport {
+    crate::shadow::report()
+}
+
+#[cfg(feature = "nlp")]
+pub fn reset_shadow_report() -> bool {
+    crate::shadow::reset()
+}
+
+// Readability indices with the formula picked for the language; None detects
+// it from the text
+#[cfg(feature = "nlp")]
+pub fn readability(text: &str, language: Option<&str>) -> ReadabilityReport {
+    crate::readability::report(text, language.unwrap_or("unknown"))
+}
+
+// Lowercased stem used to group keywords and topics; language is an ISO
+// 639-1 code or a name the detector reports
+#[cfg(feature = "nlp")]
+pub fn stem(word: &str, language: &str) -> String {
+    crate::analysis::stem(word, language)
+}
+
+// Chat abbreviations ("imo", "tbh", "س.ع") spelled out with the built-in
+// dictionary and analysis.abbreviations
+#[cfg(feature = "nlp")]
+pub fn expand_abbreviations(text: &str) -> String {
+    crate::abbreviations::expand(text)
+}
+
+// Unicode form, Arabic / Persian letters, non-joiners, diacritics and digits
+// as configured
+#[cfg(feature = "nlp")]
+pub fn normalize_text(text: &str, normalizer: &TextNormalizer) -> String {
+    normalizer.normalize(text)
+}
+
+#[cfg(feature = "nlp")]
+pub fn emoji_stats(text: &str) -> EmojiStats {
+    crate::emoji::stats(text)
+}
+
+// E-mail addresses, phone numbers, IBANs, national IDs and card numbers in
+// text order
+pub fn find_pii(text: &str, redactor: &PiiRedactor) -> Vec<PiiMatch> {
+    redactor.find(text)
+}
+
+// The text with every match masked or labelled, e.g. before it is logged
+pub fn redact_pii(text: &str, redactor: &PiiRedactor) -> String {
+    redactor.redact(text)
+}
+
+// Hashtags, mentions and cashtags with their counts
+#[cfg(feature = "nlp")]
+pub fn tag_stats(text: &str) -> TagStats {
+    crate::tags::stats(text)
+}
+
+// Headings, lists, quotes, code and emphasis in Markdown or MarkdownV2 markup
+#[cfg(feature = "nlp")]
+pub fn structure_stats(text: &str) -> StructureStats {
+    crate::structure::analyze(text)
+}
+
+// Code blocks and inline code replaced by spaces of the same byte length
+#[cfg(feature = "nlp")]
+pub fn blank_code(text: &str) -> String {
+    crate::structure::blank_code(text)
+}
+
+// Spam / scam score of a single message with the per-signal breakdown
+#[cfg(feature = "nlp")]
+pub fn spam_score(text: &str) -> SpamScore {
+    crate::spam::score(text)
+}
+
+#[cfg(feature = "nlp")]
+pub fn analyze_sentiment(text: &str) -> SentimentScore {
+    crate::sentiment::active().analyze(text)
+}
+
+// Background corpus for TextAnalysisResult::keywords; None goes back to
+// ranking by raw frequency
+#[cfg(feature = "nlp")]
+pub fn set_keyword_corpus(extractor: Option<KeywordExtractor>) {
+    crate::keywords::set_background(extractor)
+}
+
+#[cfg(feature = "nlp")]
+pub fn add_keyword_corpus_document(text: &str) {
+    crate::keywords::add_background_document(text)
+}
+
+// Closest registered commands to a mistyped one, for "did you mean" replies
+#[cfg(feature = "nlp")]
+pub fn match_command<S: AsRef<str>>(input: &str, commands: &[S], options: &CommandMatchOptions) -> Vec<CommandMatch> {
+    crate::commands::match_command(input, commands, options)
+}
+
+// Cosine over word counts, Jaccard over character shingles and normalized
+// Levenshtein, each 0-1
+#[cfg(feature = "nlp")]
+pub fn similarity(a: &str, b: &str) -> Similarity {
+    crate::similarity::similarity(a, b)
+}
+
+// Recent messages TextAnalysisResult::plagiarism_score is measured against;
+// None goes back to the common-phrase heuristic
+#[cfg(feature = "nlp")]
+pub fn set_duplicate_corpus(detector: Option<DuplicateDetector>) {
+    crate::similarity::set_corpus(detector)
+}
+
+// False when the text is too short to track
+#[cfg(feature = "nlp")]
+pub fn add_duplicate_corpus_document(id: &str, text: &str) -> bool {
+    crate::similarity::add_corpus_document(id, text)
+}
+
+// Corpus messages text reposts, most similar first
+#[cfg(feature = "nlp")]
+pub fn find_duplicates(text: &str) -> Vec<DuplicateMatch> {
+    crate::similarity::find_duplicates(text)
+}
+
+// Topic model behind TextAnalysisResult::topics. Reconfiguring keeps the
+// messages already in the window and retrains on them.
+#[cfg(feature = "nlp")]
+pub fn configure_topic_model(config: TopicModelConfig) -> Result<()> {
+    crate::topics::configure(config).map_err(Error::Config)
+}
+
+// Slides the window; retrains every retrain_every messages
+#[cfg(feature = "nlp")]
+pub fn add_topic_corpus_document(text: &str) {
+    crate::topics::add_document(text)
+}
+
+#[cfg(feature = "nlp")]
+pub fn train_topic_model() -> Result<Vec<ModelTopic>> {
+    crate::topics::train().map_err(Error::Input)
+}
+
+// Empty until the model has been trained
+#[cfg(feature = "nlp")]
+pub fn topic_model_topics() -> Vec<ModelTopic> {
+    crate::topics::topics()
+}
+
+// Back to frequency topics
+#[cfg(feature = "nlp")]
+pub fn clear_topic_model() {
+    crate::topics::clear()
+}
+
+// Relative paths resolve under the data directory; both return how many
+// messages the model's window holds
+#[cfg(feature = "nlp")]
+pub fn save_topic_model(path: &str) -> Result<usize> {
+    crate::topics::save(path).map_err(Error::Config)
+}
+
+#[cfg(feature = "nlp")]
+pub fn load_topic_model(path: &str) -> Result<usize> {
+    crate::topics::load(path).map_err(Error::Config)
+}
+
+// Replaces the built-in stop word lists used by keyword extraction;
+// analysis.custom_stop_words from the config still apply on top
+#[cfg(feature = "nlp")]
+pub fn set_stop_word_provider(provider: impl StopWordProvider + 'static) {
+    crate::stopwords::set_provider(std::sync::Arc::new(provider))
+}
+
+#[cfg(feature = "stats")]
+pub fn analyze_data(data: &str) -> DataAnalysisResult {
+    crate::analysis::analyze_data(data)
+}
+
+#[cfg(feature = "stats")]
+pub fn analyze_numbers(values: &[f64]) -> DataAnalysisResult {
+    crate::analysis::analyze_numbers(values)
+}
+
+// analyze_numbers over a metric's recent history, oldest first; an anomalous
+// latest value goes to the alert tracker, which announces it once, reminds
+// while it lasts and reports when it resolves (alerts.anomalies)
+#[cfg(feature = "stats")]
+pub fn analyze_metric(metric: &str, values: &[f64]) -> DataAnalysisResult {
+    crate::analysis::analyze_metric(metric, values)
+}
+
+// One Telegram Poll object or an array of them; repeated polls with the
+// same question also get per-option trends
+#[cfg(feature = "stats")]
+pub fn analyze_poll(poll_json: &str) -> Result<PollReport> {
+    crate::polls::analyze_poll_json(poll_json).map_err(Error::Input)
+}
+
+#[cfg(feature = "stats")]
+pub fn analyze_polls(polls: &[Poll]) -> PollReport {
+    crate::polls::analyze_polls(polls)
+}
+
+#[cfg(feature = "realtime")]
+pub fn process_realtime(data_json: &str) -> RealtimeResult {
+    crate::realtime::process_realtime_data(data_json)
+}
+
+#[cfg(feature = "crypto")]
+pub fn encrypt(message: &str, key: &str) -> Result<String> {
+    crate::crypto::encrypt(message, key).map_err(|e| Error::Crypto(e.to_string()))
+}
+
+#[cfg(feature = "crypto")]
+pub fn decrypt(encrypted_message: &str, key: &str) -> Result<String> {
+    crate::crypto::decrypt(encrypted_message, key).map_err(|e| Error::Crypto(e.to_string()))
+}
+
+// Like encrypt / decrypt for messages and keys that are not text
+#[cfg(feature = "crypto")]
+pub fn encrypt_bytes(message: &[u8], key: &[u8]) -> Result<String> {
+    crate::crypto::encrypt_bytes(message, key).map_err(|e| Error::Crypto(e.to_string()))
+}
+
+#[cfg(feature = "crypto")]
+pub fn decrypt_bytes(encrypted_message: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    crate::crypto::decrypt_bytes(encrypted_message, key).map_err(|e| Error::Crypto(e.to_string()))
+}
+
+// Each message encrypted on the worker pool; results keep the input order
+#[cfg(feature = "crypto")]
+pub fn encrypt_batch(messages: &[String], key: &str) -> Vec<BatchResult> {
+    crate::crypto::encrypt_batch(messages, key)
+}
+
+#[cfg(feature = "crypto")]
+pub fn decrypt_batch(encrypted_messages: &[String], key: &str) -> Vec<BatchResult> {
+    crate::crypto::decrypt_batch(encrypted_messages, key)
+}
+
+// Incremental digests for inputs too large for one call: begin, any number
+// of updates, then finish for the lowercase hex digest
+#[cfg(feature = "crypto")]
+pub fn hash_begin(algorithm: HashAlgorithm) -> Result<u64> {
+    crate::hashing::begin(algorithm).map_err(Error::Crypto)
+}
+
+#[cfg(feature = "crypto")]
+pub fn hmac_begin(algorithm: HashAlgorithm, key: &[u8]) -> Result<u64> {
+    crate::hashing::begin_hmac(algorithm, key).map_err(Error::Crypto)
+}
+
+#[cfg(feature = "crypto")]
+pub fn hash_update(handle: u64, data: &[u8]) -> Result<()> {
+    crate::hashing::update(handle, data).map_err(Error::Input)
+}
+
+#[cfg(feature = "crypto")]
+pub fn hash_finish(handle: u64) -> Result<String> {
+    crate::hashing::finish(handle).map_err(Error::Input)
+}
+
+#[cfg(feature = "crypto")]
+pub fn hash_abort(handle: u64) -> bool {
+    crate::hashing::abort(handle)
+}
+
+// Versioned envelope in base64 with the chosen KDF; decrypt() opens it
+#[cfg(feature = "crypto")]
+pub fn encrypt_with(message: &str, key: &str, options: &EnvelopeOptions) -> Result<String> {
+    crate::crypto::encrypt_with(message, key, options).map_err(|e| Error::Crypto(e.to_string()))
+}
+
+// Which layout an encrypt / seal output uses, from its header; None when it
+// cannot be ciphertext of this crate
+#[cfg(feature = "crypto")]
+pub fn ciphertext_format(ciphertext: &[u8]) -> Option<CiphertextFormat> {
+    crate::crypto::detect_format(ciphertext)
+}
+
+pub fn validate(data: &str, schema_name: &str) -> ValidationResult {
+    validation::validate_with_schema(data, schema_name)
+}
+
+pub fn register_validation_schema(schema_name: &str, rules: Vec<ValidationRule>) {
+    validation::register_schema(schema_name, rules);
+}
+
+// Runs on the background job workers; poll with poll_result
+pub fn submit_analysis(kind: &str, input: &str) -> Result<u64> {
+    jobs::submit_analysis(kind, input).map_err(Error::Job)
+}
+
+// Finished jobs are returned once and then forgotten
+pub fn poll_result(job_id: u64) -> Option<JobSnapshot> {
+    jobs::poll_result(job_id)
+}
+
+pub fn cancel_job(job_id: u64) -> bool {
+    jobs::cancel_job(job_id)
+}
+
+// request_json is {"kind": "text" | "data" | "realtime", "input": "..."}
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+pub fn enqueue_request(request_json: &str) -> Result<u64> {
+    queue::enqueue_request(request_json).map_err(Error::Job)
+}
+
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+pub fn drain_completions() -> Vec<Completion> {
+    queue::drain_completions()
+}
+
+#[cfg(feature = "realtime")]
+pub fn set_worker_threads(threads: usize) -> Result<()> {
+    crate::pool::set_worker_threads(threads).map_err(Error::Config)
+}
+
+pub fn patch_config(patch_json: &str) -> Result<()> {
+    config::apply_runtime_patch(patch_json).map_err(Error::Config)
+}
+
+// A full config document, or a signed bundle checked against the pinned key
+pub fn apply_config_bundle(bundle_json: &str) -> Result<()> {
+    config::AppConfig::load_from_str(bundle_json).map_err(|e| Error::Config(e.to_string()))
+}
+
+pub fn pin_config_public_key(public_key_b64: &str) -> Result<()> {
+    config::pin_public_key(public_key_b64).map_err(Error::Config)
+}
+
+pub fn dump_effective_config(redact_secrets: bool) -> serde_json::Value {
+    config::dump_effective_config(redact_secrets)
+}
+
+pub fn log(level: LogLevel, module: &str, message: &str, metadata: HashMap<String, String>) {
+    logging::log_message(level, module, message, metadata);
+}
+
+pub fn recent_alerts(limit: usize) -> Vec<AlertEvent> {
+    alerts::get_recent_alerts(limit)
+}
+
+// One profile per operation name recorded since startup
+pub fn performance_profiles() -> Vec<PerformanceProfile> {
+    performance::get_performance_profiles()
+}
+
+pub fn optimization_suggestions() -> Vec<OptimizationSuggestion> {
+    performance::get_optimization_suggestions()
+}
+
+// Queues a payload for every webhook endpoint subscribed to `source`;
+// returns how many deliveries were queued
+#[cfg(feature = "webhooks")]
+pub fn dispatch_webhook(source: &str, priority: WebhookPriority, payload: &serde_json::Value) -> usize {
+    crate::webhooks::dispatch(source, priority, payload)
+}
+
+// Starts injecting faults with the given probabilities; the stats restart
+// from zero
+#[cfg(feature = "chaos")]
+pub fn set_fault_injection(config: FaultConfig) -> Result<()> {
+    crate::chaos::configure(config).map_err(Error::Config)
+}
+
+#[cfg(feature = "chaos")]
+pub fn disable_fault_injection() {
+    crate::chaos::disable()
+}
+
+#[cfg(feature = "chaos")]
+pub fn fault_injection_stats() -> FaultStats {
+    crate::chaos::stats()
+}
+
+#[cfg(feature = "webhooks")]
+pub fn webhook_stats() -> WebhookStats {
+    crate::webhooks::get_stats()
+}
+
+// Runs body with a budget of timeout_ms: analysis skips the stages it has no
+// time for and reports timed_out, and jobs submitted inside inherit the
+// deadline. A shorter deadline already in force still applies.
+pub fn with_deadline<R, F: FnOnce() -> R>(timeout_ms: u64, body: F) -> R {
+    crate::deadline::with_deadline(Some(crate::deadline::Deadline::after_millis(timeout_ms)), body)
+}
+
+// Admission counters of the concurrency limit on FFI analysis calls
+pub fn concurrency_stats() -> ConcurrencyStats {
+    crate::concurrency::stats()
+}
+
+// Runs body on behalf of a tenant: its quota is checked first, and cache
+// keys, log entries, metrics and any jobs submitted inside are scoped to it
+pub fn for_tenant<R, F: FnOnce() -> R>(context: &TenantContext, body: F) -> Result<R> {
+    tenant::with_tenant(context.clone(), || {
+        tenant::admit().map_err(Error::RateLimited)?;
+        Ok(body())
+    })
+}
+
+// Whether the tenant's next call would be over quota; nothing is counted.
+// None for a context without a tenant, which has no quota.
+pub fn dry_run_tenant_quota(context: &TenantContext) -> Option<RateLimitDecision> {
+    tenant::with_tenant(context.clone(), tenant::dry_run_admit)
+}
+
+// Whether a request for identifier would be rate limited now; nothing is
+// counted
+pub fn dry_run_rate_limit(identifier: &str) -> RateLimitDecision {
+    crate::security::dry_run_rate_limit(identifier)
+}
+
+// Replays a trace against a candidate policy on a simulated clock, one
+// decision per request; the live limiter is not touched
+pub fn simulate_rate_limit(config: SecurityConfig, requests: &[SimulatedRequest]) -> Result<Vec<RateLimitDecision>> {
+    crate::security::simulate(config, requests).map_err(Error::Input)
+}
+
+pub fn tenant_metrics(tenant_id: &str) -> Option<TenantMetrics> {
+    tenant::metrics(tenant_id)
+}
+
+pub fn all_tenant_metrics() -> HashMap<String, TenantMetrics> {
+    tenant::all_metrics()
+}
+
+// Compute cost charged to the tenant, in total and by operation, user and
+// chat, since its last reset
+pub fn cost_report(tenant_id: &str) -> Option<CostReport> {
+    crate::costs::report(tenant_id)
+}
+
+pub fn all_cost_reports() -> HashMap<String, CostReport> {
+    crate::costs::all_reports()
+}
+
+// Every tenant's costs as flat rows, e.g. for export_records
+pub fn cost_lines() -> Vec<CostLine> {
+    crate::costs::lines()
+}
+
+// Starts the tenant's costs over, or every tenant's for None; quota windows
+// are not reset
+pub fn reset_costs(tenant_id: Option<&str>) -> bool {
+    crate::costs::reset(tenant_id)
+}
+
+// Per-chat aggregations take Bot API Message objects as they arrive; chats
+// are scoped by the current tenant
+pub fn record_chat_message(message_json: &str) -> Result<()> {
+    crate::chats::record_message_json(message_json).map_err(Error::Input)
+}
+
+pub fn record_chat_messages(messages: &[ChatMessage]) {
+    messages.iter().for_each(crate::chats::record_message)
+}
+
+// Joins and leaves also arrive as new_chat_members / left_chat_member on
+// messages passed to record_chat_message
+pub fn record_chat_member_update(update_json: &str) -> Result<()> {
+    crate::chats::record_member_update_json(update_json).map_err(Error::Input)
+}
+
+pub fn member_report(chat_id: i64, window_seconds: i64) -> MemberReport {
+    crate::chats::member_report(chat_id, window_seconds)
+}
+
+pub fn sticker_report(chat_id: i64, window_seconds: i64, limit: usize) -> StickerReport {
+    crate::chats::sticker_report(chat_id, window_seconds, limit)
+}
+
+// Reaction totals of a message, from a Bot API MessageReactionCountUpdated
+pub fn record_reaction_count(update_json: &str) -> Result<()> {
+    crate::chats::record_reaction_count_json(update_json).map_err(Error::Input)
+}
+
+// Views and forwards of a post as the host reads them, e.g. from channel
+// statistics; replies and reactions are counted from updates
+pub fn record_post_metrics(chat_id: i64, message_id: i64, views: u64, forwards: u64) {
+    crate::chats::record_post_metrics(chat_id, message_id, views, forwards)
+}
+
+// The chat's size for engagement indexes, e.g. from getChatMemberCount
+pub fn record_chat_member_count(chat_id: i64, count: u64) {
+    crate::chats::record_member_count(chat_id, count)
+}
+
+// Most active members, most reacted-to and most engaging messages and
+// longest daily streaks, each in rank order, e.g. for a weekly community
+// stats post
+pub fn leaderboard(chat_id: i64, options: &LeaderboardOptions) -> Leaderboard {
+    crate::chats::leaderboard(chat_id, options)
+}
+
+// Posts of the last day engaging far more than the chat's usual ones
+pub fn trending_posts(chat_id: i64, limit: usize) -> Vec<PostEngagement> {
+    crate::chats::trending_posts(chat_id, limit)
+}
+
+// Slowmode delay and per-member cooldowns that keep the chat under the
+// slowmode config's target rate; changed says whether it differs from the
+// last recommendation for the chat
+pub fn recommend_slowmode(chat_id: i64) -> SlowmodeRecommendation {
+    crate::slowmode::recommend_slowmode(chat_id)
+}
+
+// For hosts scoring messages with their own model; text messages passed to
+// record_chat_message are scored already when the nlp feature is on
+pub fn record_sentiment(chat_id: i64, user_id: Option<i64>, date: i64, score: f64) {
+    crate::chats::record_sentiment(chat_id, user_id, date, score)
+}
+
+// The chat's sentiment, or one member's with user_id, over the last
+// window_seconds; sharp drops also raise "sentiment_drop" alerts
+pub fn sentiment_trend(chat_id: i64, user_id: Option<i64>, window_seconds: i64) -> SentimentTrend {
+    crate::chats::sentiment_trend(chat_id, user_id, window_seconds)
+}
+
+// Spreadsheet of analysis results or reports, e.g. a batch of
+// TextAnalysisResult or one MemberReport per chat. CSV holds one row per
+// record; XLSX adds a worksheet per list of objects in them.
+pub fn export_records<T: serde::Serialize>(name: &str, records: &[T], format: ExportFormat) -> Result<Vec<u8>> {
+    let tables = crate::export::tables(name, records).map_err(Error::Input)?;
+    crate::export::render(&tables, format).map_err(Error::Config)
+}
+
+// Returns where the file was written
+pub fn export_records_to_file<T: serde::Serialize>(name: &str, records: &[T], format: ExportFormat, path: &str) -> Result<std::path::PathBuf> {
+    crate::export::write(path, &export_records(name, records, format)?).map_err(Error::Config)
+}
+
+// A JSON array of records, as returned by the FFI analysis and report calls
+pub fn export_json(name: &str, records_json: &str, format: ExportFormat) -> Result<Vec<u8>> {
+    let tables = crate::export::tables_from_json(name, records_json).map_err(Error::Input)?;
+    crate::export::render(&tables, format).map_err(Error::Config)
+}
+
+pub fn export_json_to_file(name: &str, records_json: &str, format: ExportFormat, path: &str) -> Result<std::path::PathBuf> {
+    crate::export::write(path, &export_json(name, records_json, format)?).map_err(Error::Config)
+}
+
+pub fn clear_chat_stats(chat_id: i64) -> bool {
+    crate::chats::clear_chat(chat_id)
+}
+
+// Signed /start payload attributing a user to a campaign; use it as
+// https://t.me/<bot>?start=<payload>
+#[cfg(feature = "crypto")]
+pub fn encode_deep_link(campaign: &str, data: Option<&str>, key: &str) -> Result<String> {
+    let link = DeepLink { campaign: campaign.to_string(), data: data.map(str::to_string) };
+    crate::deeplinks::encode(&link, key).map_err(Error::Input)
+}
+
+#[cfg(feature = "crypto")]
+pub fn decode_deep_link(payload: &str, key: &str) -> Result<DeepLink> {
+    crate::deeplinks::decode(payload, key).map_err(Error::Crypto)
+}
+
+// decode_deep_link plus a start counted for the campaign
+#[cfg(feature = "crypto")]
+pub fn track_deep_link_start(payload: &str, user_id: Option<i64>, key: &str) -> Result<DeepLink> {
+    crate::deeplinks::track_start(payload, user_id, key).map_err(Error::Crypto)
+}
+
+pub fn record_campaign_impressions(campaign: &str, impressions: u64) {
+    crate::campaigns::record_campaign_impressions(campaign, impressions)
+}
+
+pub fn campaign_report(window_seconds: i64) -> CampaignReport {
+    crate::campaigns::campaign_report(window_seconds)
+}
+
+// Adds by to the named counter of the current tenant and returns the new
+// total, which never drops below 0
+pub fn incr_counter(name: &str, by: i64) -> i64 {
+    crate::counters::incr(name, by)
+}
+
+pub fn get_counter(name: &str) -> i64 {
+    crate::counters::get(name)
+}
+
+pub fn reset_counter(name: &str) -> bool {
+    crate::counters::reset(name)
+}
+
+pub fn list_counters(prefix: &str) -> Vec<CounterValue> {
+    crate::counters::list(prefix)
+}
+
+// Counts per step over the last window_seconds, up to 30 days; steps are
+// whole minutes for windows up to a day and whole hours beyond
+pub fn counter_rollup(name: &str, window_seconds: i64, step_seconds: i64) -> Option<CounterRollup> {
+    crate::counters::rollup(name, window_seconds, step_seconds)
+}
+
+// Relative paths resolve under the data directory; both return how many
+// counters the file holds
+pub fn save_counters(path: &str) -> Result<usize> {
+    crate::counters::save(path).map_err(Error::Config)
+}
+
+pub fn load_counters(path: &str) -> Result<usize> {
+    crate::counters::load(path).map_err(Error::Config)
+}
+
+// Chooses one of variants for the experiment and context ("" for none) with
+// the configured bandit strategy, counting it as shown
+pub fn select_variant(experiment: &str, context: &str, variants: &[String]) -> Result<String> {
+    crate::bandit::select(experiment, context, variants).map_err(Error::Input)
+}
+
+// Reward from 0 to 1 for a variant select_variant returned; false when it
+// was never selected in that context
+pub fn record_variant_reward(experiment: &str, context: &str, variant: &str, reward: f64) -> bool {
+    crate::bandit::record_reward(experiment, context, variant, reward)
+}
+
+pub fn variant_stats(experiment: &str, context: &str) -> Vec<ArmStats> {
+    crate::bandit::stats(experiment, context)
+}
+
+pub fn reset_experiment(experiment: &str) -> bool {
+    crate::bandit::reset(experiment)
+}
+
+// Relative paths resolve under the data directory; both return how many
+// experiments the file holds
+pub fn save_experiments(path: &str) -> Result<usize> {
+    crate::bandit::save(path).map_err(Error::Config)
+}
+
+pub fn load_experiments(path: &str) -> Result<usize> {
+    crate::bandit::load(path).map_err(Error::Config)
+}
+
+// SimHash of a post's text for repost detection
+pub fn fingerprint_text(text: &str) -> Result<Fingerprint> {
+    Fingerprint::text(text).map_err(Error::Input)
+}
+
+// Difference hash of a decoded image given as 8-bit grayscale rows
+pub fn fingerprint_image(width: usize, height: usize, luma: &[u8]) -> Result<Fingerprint> {
+    Fingerprint::image(width, height, luma).map_err(Error::Input)
+}
+
+// Persists registered fingerprints to path from now on; returns how many it
+// already held
+pub fn open_fingerprint_store(path: &str) -> Result<usize> {
+    crate::fingerprints::open_store(path).map_err(Error::Config)
+}
+
+pub fn register_fingerprint(id: &str, fingerprint: Fingerprint) -> Result<()> {
+    crate::fingerprints::register(id, fingerprint).map_err(Error::Config)
+}
+
+pub fn find_fingerprint_matches(fingerprint: &Fingerprint, max_distance: u32) -> Vec<FingerprintMatch> {
+    crate::fingerprints::find_matches(fingerprint, max_distance)
+}
+
+// Renders a reply template to MarkdownV2 with every interpolated value
+// escaped; compile a Template once instead when rendering it repeatedly
+pub fn render_template(source: &str, vars: &serde_json::Value, lang: &str) -> Result<String> {
+    templates::render(source, vars, lang).map_err(Error::Input)
+}
+
+pub fn escape_markdown_v2(text: &str) -> String {
+    templates::escape_markdown_v2(text)
+}
+
+// Plural rule used by templates for `lang`; en, fa, ru and uk are built in
+pub fn register_plural_rule(lang: &str, rule: PluralRule) {
+    i18n::register_plural_rule(lang, rule)
+}
+
+// Owns an init_library / shutdown_library pair; dropping it shuts the
+// library down
+#[derive(Debug)]
+pub struct Library {
+    _private: (),
+}
+
+impl Library {
+    pub fn builder() -> LibraryBuilder {
+        LibraryBuilder::default()
+    }
+    
+    pub fn init_report(&self) -> Vec<InitStep> {
+        lifecycle::init_report()
+    }
+    
+    pub fn warm_up(&self, options: &WarmUpOptions) -> Vec<InitStep> {
+        lifecycle::warm_up(options)
+    }
+}
+
+// Precompiles regexes, loads the language tables and optionally restores a
+// cache snapshot, so the first request after a deploy is not slow; one
+// InitStep per stage
+pub fn warm_up(options: &WarmUpOptions) -> Vec<InitStep> {
+    lifecycle::warm_up(options)
+}
+
+// BLAKE3 of bytes as 64 hex chars, the hash behind the cache keys; for
+// host-side keys and deduplication, not for secrets (use hmac_begin)
+pub fn fast_hash(bytes: &[u8]) -> String {
+    crate::cache::fast_hash(bytes)
+}
+
+// Writes the live cache entries to path (relative paths resolve under the
+// data directory) for WarmUpOptions::cache_snapshot, sealed when a key is
+// given; returns how many were written
+pub fn save_cache_snapshot(path: &str, key: Option<&str>) -> Result<usize> {
+    crate::cache::save_snapshot(path, key).map_err(Error::Config)
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        lifecycle::shutdown_library();
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LibraryBuilder {
+    config_json: Option<String>,
+}
+
+impl LibraryBuilder {
+    // A full config document or signed bundle; without one CONFIG_PATH or the
+    // platform config file is read
+    pub fn config_json(mut self, config_json: impl Into<String>) -> Self {
+        self.config_json = Some(config_json.into());
+        self
+    }
+    
+    pub fn init(self) -> Result<Library> {
+        lifecycle::init_library(self.config_json.as_deref()).map_err(Error::Lifecycle)?;
+        Ok(Library { _private: () })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_text_analyzer_builder() {
+        let analyzer = TextAnalyzer::builder()
+            .skip_ner(true)
+            .summary_sentences(0)
+            .language_hint("en")
+            .build();
+        
+        let result = analyzer.analyze("Alice met Bob in Paris. They talked for hours.");
+        assert!(result.entities.is_empty());
+        assert!(result.summary.is_empty());
+        assert_eq!(result.language, "en");
+        
+        assert!(matches!(TextAnalyzer::from_json(r#"{"unknown": 1}"#), Err(Error::Input(_))));
+    }
+    
+    #[test]
+    fn test_errors_are_typed() {
+        assert!(matches!(submit_analysis("video", "payload"), Err(Error::Job(_))));
+        assert!(matches!(patch_config("not json"), Err(Error::Config(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::config::{AppConfig, BanditConfig, BanditStrategy};
+use crate::paths;
+use crate::tenant;
+
+// Picks one of several variants of a reply (a welcome message, a call to
+// action) and learns from how users respond. Each selection counts as a pull
+// of that variant; engagement the host observes afterwards is recorded as a
+// reward between 0 and 1, so a pull never rewarded counts as a miss. Arms are
+// kept per experiment and context (a language, a chat type, "" for none), so
+// the copy that works for one audience does not decide for another. Like
+// counters, arms are scoped by the current tenant and can be written to a
+// file and loaded back after a restart.
+
+const BANDIT_FORMAT_VERSION: u32 = 1;
+pub const MAX_VARIANTS: usize = 100;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Arm {
+    pulls: u64,
+    // Sum of rewards, never above pulls
+    rewards: f64,
+}
+
+impl Arm {
+    fn mean(&self) -> f64 {
+        if self.pulls == 0 { 0.0 } else { self.rewards / self.pulls as f64 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArmStats {
+    pub variant: String,
+    pub pulls: u64,
+    pub rewards: f64,
+    // rewards / pulls
+    pub mean: f64,
+}
+
+// Context, then variant
+type Experiment = HashMap<String, HashMap<String, Arm>>;
+
+#[derive(Serialize, Deserialize)]
+struct SavedArms {
+    format_version: u32,
+    experiments: HashMap<String, Experiment>,
+}
+
+lazy_static! {
+    // Tenant scope, then experiment name
+    static ref EXPERIMENTS: Mutex<HashMap<String, HashMap<String, Experiment>>> = Mutex::new(HashMap::new());
+}
+
+fn experiments() -> MutexGuard<'static, HashMap<String, HashMap<String, Experiment>>> {
+    EXPERIMENTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // Box-Muller; 1 - u keeps the logarithm finite
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+// Marsaglia and Tsang; shape is at least 1 for every arm
+fn gamma<R: Rng>(rng: &mut R, shape: f64) -> f64 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = 1.0 - rng.gen::<f64>();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+// A draw from Beta(1 + rewards, 1 + misses)
+fn sample_arm<R: Rng>(rng: &mut R, arm: &Arm) -> f64 {
+    let hits = gamma(rng, 1.0 + arm.rewards);
+    let misses = gamma(rng, 1.0 + (arm.pulls as f64 - arm.rewards).max(0.0));
+    hits / (hits + misses)
+}
+
+fn validate_variants(variants: &[String]) -> Result<(), String> {
+    if variants.is_empty() {
+        return Err("No variants to choose from".to_string());
+    }
+    if variants.len() > MAX_VARIANTS {
+        return Err(format!("{} variants exceed the limit of {}", variants.len(), MAX_VARIANTS));
+    }
+    if variants.iter().any(|variant| variant.trim().is_empty()) {
+        return Err("Variant names must not be empty".to_string());
+    }
+    Ok(())
+}
+
+// Index of the variant to show; variants never pulled in this context come
+// first under either strategy
+fn choose<R: Rng>(rng: &mut R, config: &BanditConfig, arms: &HashMap<String, Arm>, variants: &[String]) -> usize {
+    let unseen: Vec<usize> = (0..variants.len()).filter(|&i| arms.get(&variants[i]).is_none_or(|arm| arm.pulls == 0)).collect();
+    if !unseen.is_empty() {
+        return unseen[rng.gen_range(0..unseen.len())];
+    }
+    let scores: Vec<f64> = match config.strategy {
+        BanditStrategy::EpsilonGreedy => {
+            if rng.gen::<f64>() < config.epsilon {
+                return rng.gen_range(0..variants.len());
+            }
+            variants.iter().map(|variant| arms[variant].mean()).collect()
+        }
+        BanditStrategy::Thompson => variants.iter().map(|variant| sample_arm(rng, &arms[variant])).collect(),
+    };
+    // Ties go to a random one of the best
+    let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let tied: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] == best).collect();
+    tied[rng.gen_range(0..tied.len())]
+}
+
+// Chooses one of variants for the context and counts it as pulled
+pub fn select(experiment: &str, context: &str, variants: &[String]) -> Result<String, String> {
+    select_with(&mut rand::thread_rng(), &AppConfig::get().bandit, experiment, context, variants)
+}
+
+fn select_with<R: Rng>(rng: &mut R, config: &BanditConfig, experiment: &str, context: &str, variants: &[String]) -> Result<String, String> {
+    validate_variants(variants)?;
+    let mut experiments = experiments();
+    let arms = experiments.entry(tenant::scope_key("")).or_default()
+        .entry(experiment.to_string()).or_default()
+        .entry(context.to_string()).or_default();
+    let variant = variants[choose(rng, config, arms, variants)].clone();
+    arms.entry(variant.clone()).or_default().pulls += 1;
+    Ok(variant)
+}
+
+// Adds reward, clamped to 0..1, to a variant select returned for the
+// context; false for a variant never selected there. A variant's rewards
+// stop at its pulls, so a late duplicate cannot push its mean above 1.
+pub fn record_reward(experiment: &str, context: &str, variant: &str, reward: f64) -> bool {
+    let mut experiments = experiments();
+    let arm = experiments.get_mut(&tenant::scope_key(""))
+        .and_then(|scope| scope.get_mut(experiment))
+        .and_then(|contexts| contexts.get_mut(context))
+        .and_then(|arms| arms.get_mut(variant));
+    match arm {
+        Some(arm) if arm.pulls > 0 => {
+            let reward = if reward.is_finite() { reward.clamp(0.0, 1.0) } else { 0.0 };
+            arm.rewards = (arm.rewards + reward).min(arm.pulls as f64);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Best mean first, then by name
+pub fn stats(experiment: &str, context: &str) -> Vec<ArmStats> {
+    let experiments = experiments();
+    let mut stats: Vec<ArmStats> = experiments.get(&tenant::scope_key(""))
+        .and_then(|scope| scope.get(experiment))
+        .and_then(|contexts| contexts.get(context))
+        .into_iter()
+        .flat_map(|arms| arms.iter())
+        .map(|(variant, arm)| ArmStats { variant: variant.clone(), pulls: arm.pulls, rewards: arm.rewards, mean: arm.mean() })
+        .collect();
+    stats.sort_by(|a, b| b.mean.total_cmp(&a.mean).then_with(|| a.variant.cmp(&b.variant)));
+    stats
+}
+
+// Forgets every context of the experiment
+pub fn reset(experiment: &str) -> bool {
+    experiments().get_mut(&tenant::scope_key("")).and_then(|scope| scope.remove(experiment)).is_some()
+}
+
+// Writes the current tenant's experiments; returns how many
+pub fn save(path: &str) -> Result<usize, String> {
+    let saved = SavedArms {
+        format_version: BANDIT_FORMAT_VERSION,
+        experiments: experiments().get(&tenant::scope_key("")).cloned().unwrap_or_default(),
+    };
+    paths::atomic_write_json(path, "experiments", &saved)?;
+    Ok(saved.experiments.len())
+}
+
+// Replaces the current tenant's experiments with those written by save
+pub fn load(path: &str) -> Result<usize, String> {
+    let saved = paths::read_versioned_json(path, "experiments", BANDIT_FORMAT_VERSION, |saved: &SavedArms| saved.format_version)?;
+    let arms = saved.experiments.values().flat_map(|contexts| contexts.values()).flat_map(|arms| arms.values());
+    for arm in arms {
+        if !arm.rewards.is_finite() || arm.rewards < 0.0 || arm.rewards > arm.pulls as f64 {
+            return Err(format!("Invalid experiments file {}: rewards out of range", path));
+        }
+    }
+    
+    let count = saved.experiments.len();
+    experiments().insert(tenant::scope_key(""), saved.experiments);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::tenant::TenantContext;
+    
+    #[test]
+    fn test_bandit_learns_and_persists() {
+        let variants: Vec<String> = vec!["short".to_string(), "friendly".to_string(), "formal".to_string()];
+        let path = std::env::temp_dir().join(format!("aiogram_bandit_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        tenant::with_tenant(TenantContext::new(Some("bandit_test"), None), || {
+            let mut rng = StdRng::seed_from_u64(7);
+            for strategy in [BanditStrategy::Thompson, BanditStrategy::EpsilonGreedy] {
+                let config = BanditConfig { strategy, epsilon: 0.1 };
+                let context = format!("{:?}", strategy);
+                // "friendly" is engaged with 60% of the time, the others 10%
+                let mut shown = HashMap::new();
+                for i in 0..600 {
+                    let variant = select_with(&mut rng, &config, "welcome", &context, &variants).unwrap();
+                    let rate = if variant == "friendly" { 6 } else { 1 };
+                    assert!(record_reward("welcome", &context, &variant, if i % 10 < rate { 1.0 } else { 0.0 }));
+                    *shown.entry(variant).or_insert(0) += 1;
+                }
+                assert!(shown["friendly"] > 400, "{:?}: {:?}", strategy, shown);
+                assert_eq!(stats("welcome", &context)[0].variant, "friendly");
+            }
+            
+            assert!(select_with(&mut rng, &BanditConfig::default(), "welcome", "", &[]).is_err());
+            assert!(!record_reward("welcome", "Thompson", "unknown", 1.0));
+            
+            assert_eq!(save(path), Ok(1));
+            assert!(reset("welcome"));
+            assert!(stats("welcome", "Thompson").is_empty());
+            assert_eq!(load(path), Ok(1));
+            assert_eq!(stats("welcome", "Thompson").iter().map(|arm| arm.pulls).sum::<u64>(), 600);
+        });
+        // Other tenants do not see them
+        assert!(stats("welcome", "Thompson").is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+// Standalone gRPC front end for services that cannot link the shared library.
+// Usage: aiogram-server [address], or AIOGRAM_GRPC_ADDRESS; config comes from
+// CONFIG_PATH or the platform config directory.
+fn main() {
+    let address = std::env::args().nth(1)
+        .or_else(|| std::env::var("AIOGRAM_GRPC_ADDRESS").ok())
+        .unwrap_or_else(|| aiogram_rust::DEFAULT_SERVER_ADDRESS.to_string());
+    
+    println!("Serving aiogram gRPC on {}", address);
+    if let Err(e) = aiogram_rust::run_server(&address, None) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
\ No newline at end of file
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use lazy_static::lazy_static;
-use dashmap::DashMap;
+use crate::paths;
+use crate::tenant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry<T> {
@@ -24,7 +25,7 @@ pub struct CacheStats {
 }
 
 pub struct Cache<T> {
-    data: DashMap<String, CacheEntry<T>>,
+    data: Mutex<HashMap<String, CacheEntry<T>>>,
     max_size: usize,
     ttl_seconds: Option<u64>,
     stats: Arc<Mutex<CacheStats>>,
@@ -36,7 +37,7 @@ where
 {
     pub fn new(max_size: usize, ttl_seconds: Option<u64>) -> Self {
         Self {
-            data: DashMap::new(),
+            data: Mutex::new(HashMap::new()),
             max_size,
             ttl_seconds,
             stats: Arc::new(Mutex::new(CacheStats {
@@ -50,89 +51,91 @@ where
         }
     }
     
+    // A poisoned lock only means a panic mid-update; the map itself is
+    // still usable, so keep serving from it
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, CacheEntry<T>>> {
+        self.data.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    fn is_expired(&self, entry: &CacheEntry<T>) -> bool {
+        self.ttl_seconds.map(|ttl| entry.created_at.elapsed().as_secs() > ttl).unwrap_or(false)
+    }
+    
     pub fn get(&self, key: &str) -> Option<T> {
-        if let Some(entry) = self.data.get(key) {
-            // Check if entry has expired
-            if let Some(ttl) = self.ttl_seconds {
-                if entry.created_at.elapsed().as_secs() > ttl {
-                    self.data.remove(key);
-                    self.update_stats(false);
-                    return None;
-                }
+        let value = {
+            let mut data = self.entries();
+            let expired = data.get(key).map(|entry| self.is_expired(entry));
+            match expired {
+                Some(true) => {
+                    data.remove(key);
+                    None
+                },
+                // Update access statistics
+                Some(false) => data.get_mut(key).map(|entry| {
+                    entry.accessed_at = Instant::now();
+                    entry.access_count += 1;
+                    entry.value.clone()
+                }),
+                None => None,
             }
-            
-            // Update access statistics
-            let mut entry = entry.clone();
-            entry.accessed_at = Instant::now();
-            entry.access_count += 1;
-            self.data.insert(key.to_string(), entry);
-            
-            self.update_stats(true);
-            Some(entry.value)
-        } else {
-            self.update_stats(false);
-            None
-        }
+        };
+        
+        self.update_stats(value.is_some());
+        value
     }
     
     pub fn set(&self, key: &str, value: T) {
-        // Check if we need to evict entries
-        if self.data.len() >= self.max_size {
-            self.evict_lru();
+        {
+            let mut data = self.entries();
+            
+            // Check if we need to evict entries
+            if data.len() >= self.max_size && !data.contains_key(key) {
+                self.evict_lru(&mut data);
+            }
+            
+            let entry = CacheEntry {
+                value,
+                created_at: Instant::now(),
+                accessed_at: Instant::now(),
+                access_count: 1,
+            };
+            
+            data.insert(key.to_string(), entry);
         }
-        
-        let entry = CacheEntry {
-            value,
-            created_at: Instant::now(),
-            accessed_at: Instant::now(),
-            access_count: 1,
-        };
-        
-        self.data.insert(key.to_string(), entry);
         self.update_stats(false);
     }
     
     pub fn remove(&self, key: &str) -> Option<T> {
-        if let Some((_, entry)) = self.data.remove(key) {
-            Some(entry.value)
-        } else {
-            None
-        }
+        self.entries().remove(key).map(|entry| entry.value)
     }
     
     pub fn clear(&self) {
-        self.data.clear();
+        self.entries().clear();
         self.update_stats(false);
     }
     
     pub fn contains_key(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+        self.entries().contains_key(key)
     }
     
     pub fn size(&self) -> usize {
-        self.data.len()
+        self.entries().len()
     }
     
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.entries().is_empty()
     }
     
-    fn evict_lru(&self) {
-        let mut entries: Vec<(String, CacheEntry<T>)> = self.data
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
-            .collect();
-        
-        // Sort by access time and count (LRU)
-        entries.sort_by(|a, b| {
-            let a_score = a.1.access_count as f64 / a.1.accessed_at.elapsed().as_secs().max(1) as f64;
-            let b_score = b.1.access_count as f64 / b.1.accessed_at.elapsed().as_secs().max(1) as f64;
-            a_score.partial_cmp(&b_score).unwrap_or(std::cmp::Ordering::Equal)
-        });
+    fn evict_lru(&self, data: &mut HashMap<String, CacheEntry<T>>) {
+        // Score by access count over idle time (LRU)
+        let score = |entry: &CacheEntry<T>| entry.access_count as f64 / entry.accessed_at.elapsed().as_secs().max(1) as f64;
+        let least_used = data.iter()
+            .min_by(|a, b| score(a.1).partial_cmp(&score(b.1)).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(key, _)| key.clone());
         
         // Remove the least recently used entry
-        if let Some((key, _)) = entries.first() {
-            self.data.remove(key);
+        if let Some(key) = least_used {
+            data.remove(&key);
             if let Ok(mut stats) = self.stats.lock() {
                 stats.evictions += 1;
             }
@@ -154,7 +157,7 @@ where
                 0.0
             };
             
-            stats.size = self.data.len();
+            stats.size = self.size();
         }
     }
     
@@ -174,63 +177,148 @@ where
     }
     
     pub fn cleanup_expired(&self) -> usize {
-        let mut removed_count = 0;
-        
-        if let Some(ttl) = self.ttl_seconds {
-            let now = Instant::now();
-            let expired_keys: Vec<String> = self.data
-                .iter()
-                .filter(|entry| entry.created_at.elapsed().as_secs() > ttl)
-                .map(|entry| entry.key().clone())
-                .collect();
-            
-            for key in expired_keys {
-                if self.data.remove(&key).is_some() {
-                    removed_count += 1;
-                }
+        let mut data = self.entries();
+        let before = data.len();
+        data.retain(|_, entry| !self.is_expired(entry));
+        before - data.len()
+    }
+    
+    fn snapshot(&self) -> Vec<SnapshotEntry<T>> {
+        self.entries().iter()
+            .filter(|(_, entry)| !self.is_expired(entry))
+            .map(|(key, entry)| SnapshotEntry {
+                key: key.clone(),
+                value: entry.value.clone(),
+                age_seconds: entry.created_at.elapsed().as_secs(),
+            })
+            .collect()
+    }
+    
+    // Entries keep their age, so they still expire on schedule; returns how
+    // many were restored
+    fn restore(&self, entries: Vec<SnapshotEntry<T>>) -> usize {
+        let mut data = self.entries();
+        let mut restored = 0;
+        for entry in entries {
+            if self.ttl_seconds.is_some_and(|ttl| entry.age_seconds > ttl) {
+                continue;
+            }
+            if data.len() >= self.max_size && !data.contains_key(&entry.key) {
+                self.evict_lru(&mut data);
             }
+            let now = Instant::now();
+            data.insert(entry.key, CacheEntry {
+                value: entry.value,
+                created_at: now.checked_sub(Duration::from_secs(entry.age_seconds)).unwrap_or(now),
+                accessed_at: now,
+                access_count: 1,
+            });
+            restored += 1;
         }
-        
-        removed_count
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.size = data.len();
+        }
+        restored
     }
 }
 
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry<T> {
+    key: String,
+    value: T,
+    age_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    text: Vec<SnapshotEntry<String>>,
+    data: Vec<SnapshotEntry<Vec<f64>>>,
+    result: Vec<SnapshotEntry<String>>,
+}
+
 // Global cache instances
 lazy_static! {
     static ref TEXT_CACHE: Arc<Cache<String>> = Arc::new(Cache::new(1000, Some(3600)));
     static ref DATA_CACHE: Arc<Cache<Vec<f64>>> = Arc::new(Cache::new(500, Some(1800)));
     static ref RESULT_CACHE: Arc<Cache<String>> = Arc::new(Cache::new(2000, Some(7200)));
+    // Text of images by image hash; not in snapshots, since a restart may
+    // come with another OCR provider
+    static ref OCR_CACHE: Arc<Cache<String>> = Arc::new(Cache::new(1000, Some(86_400)));
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&TEXT_CACHE);
+    lazy_static::initialize(&DATA_CACHE);
+    lazy_static::initialize(&RESULT_CACHE);
+    lazy_static::initialize(&OCR_CACHE);
+    Ok(())
+}
+
+// Keys are scoped to the calling tenant, if any, so bots sharing the library
+// never see each other's entries
+fn lookup<T: Clone + Send + Sync + 'static>(cache: &Cache<T>, key: &str) -> Option<T> {
+    #[cfg(feature = "chaos")]
+    if crate::chaos::inject(crate::chaos::Fault::CacheError) {
+        tenant::record_cache_lookup(false);
+        return None;
+    }
+    let value = cache.get(&tenant::scope_key(key));
+    tenant::record_cache_lookup(value.is_some());
+    value
+}
+
+fn store<T: Clone + Send + Sync + 'static>(cache: &Cache<T>, key: &str, value: T) {
+    // An injected cache error loses the write
+    #[cfg(feature = "chaos")]
+    if crate::chaos::inject(crate::chaos::Fault::CacheError) {
+        return;
+    }
+    cache.set(&tenant::scope_key(key), value);
 }
 
 // Public cache functions
 pub fn get_cached_text(key: &str) -> Option<String> {
-    TEXT_CACHE.get(key)
+    lookup(&TEXT_CACHE, key)
 }
 
 pub fn set_cached_text(key: &str, value: String) {
-    TEXT_CACHE.set(key, value);
+    store(&TEXT_CACHE, key, value);
 }
 
 pub fn get_cached_data(key: &str) -> Option<Vec<f64>> {
-    DATA_CACHE.get(key)
+    lookup(&DATA_CACHE, key)
 }
 
 pub fn set_cached_data(key: &str, value: Vec<f64>) {
-    DATA_CACHE.set(key, value);
+    store(&DATA_CACHE, key, value);
 }
 
 pub fn get_cached_result(key: &str) -> Option<String> {
-    RESULT_CACHE.get(key)
+    lookup(&RESULT_CACHE, key)
 }
 
 pub fn set_cached_result(key: &str, value: String) {
-    RESULT_CACHE.set(key, value);
+    store(&RESULT_CACHE, key, value);
+}
+
+// Shared by every tenant: an image's text depends only on its bytes
+pub fn get_cached_ocr(image_hash: &str) -> Option<String> {
+    OCR_CACHE.get(image_hash)
+}
+
+pub fn set_cached_ocr(image_hash: &str, text: String) {
+    OCR_CACHE.set(image_hash, text);
+}
+
+pub fn clear_cached_ocr() {
+    OCR_CACHE.clear();
 }
 
 pub fn clear_all_caches() {
     TEXT_CACHE.clear();
     DATA_CACHE.clear();
     RESULT_CACHE.clear();
+    OCR_CACHE.clear();
 }
 
 pub fn get_cache_stats() -> HashMap<String, CacheStats> {
@@ -238,6 +326,7 @@ pub fn get_cache_stats() -> HashMap<String, CacheStats> {
     stats.insert("text_cache".to_string(), TEXT_CACHE.get_stats());
     stats.insert("data_cache".to_string(), DATA_CACHE.get_stats());
     stats.insert("result_cache".to_string(), RESULT_CACHE.get_stats());
+    stats.insert("ocr_cache".to_string(), OCR_CACHE.get_stats());
     stats
 }
 
@@ -246,30 +335,75 @@ pub fn cleanup_all_caches() -> HashMap<String, usize> {
     cleanup_stats.insert("text_cache".to_string(), TEXT_CACHE.cleanup_expired());
     cleanup_stats.insert("data_cache".to_string(), DATA_CACHE.cleanup_expired());
     cleanup_stats.insert("result_cache".to_string(), RESULT_CACHE.cleanup_expired());
+    cleanup_stats.insert("ocr_cache".to_string(), OCR_CACHE.cleanup_expired());
     cleanup_stats
 }
 
-// Cache key generation utilities
+// Writes every live entry to path (relative paths resolve under the data
+// directory), sealed with key when one is given (needs the crypto feature);
+// returns the number of entries written
+pub fn save_snapshot(path: &str, key: Option<&str>) -> Result<usize, String> {
+    let snapshot = Snapshot { text: TEXT_CACHE.snapshot(), data: DATA_CACHE.snapshot(), result: RESULT_CACHE.snapshot() };
+    let count = snapshot.text.len() + snapshot.data.len() + snapshot.result.len();
+    let json = serde_json::to_vec(&snapshot).map_err(|e| e.to_string())?;
+    let contents = match key {
+        #[cfg(feature = "crypto")]
+        Some(key) => crate::crypto::seal(&json, key).map_err(|e| format!("Cannot seal cache snapshot: {}", e))?,
+        #[cfg(not(feature = "crypto"))]
+        Some(_) => return Err("Sealed cache snapshots need the crypto feature".to_string()),
+        None => json,
+    };
+    paths::atomic_write(path, "cache snapshot", &contents)?;
+    Ok(count)
+}
+
+// Adds the entries of a snapshot written by save_snapshot to the caches,
+// skipping those that have expired since; returns how many were restored
+pub fn load_snapshot(path: &str, key: Option<&str>) -> Result<usize, String> {
+    let contents = paths::read_data(path, "cache snapshot")?;
+    let json = match key {
+        #[cfg(feature = "crypto")]
+        Some(key) => crate::crypto::open(&contents, key).map_err(|e| format!("Cannot open cache snapshot: {}", e))?,
+        #[cfg(not(feature = "crypto"))]
+        Some(_) => return Err("Sealed cache snapshots need the crypto feature".to_string()),
+        None => contents,
+    };
+    let snapshot: Snapshot = serde_json::from_slice(&json).map_err(|e| format!("Invalid cache snapshot: {}", e))?;
+    Ok(TEXT_CACHE.restore(snapshot.text) + DATA_CACHE.restore(snapshot.data) + RESULT_CACHE.restore(snapshot.result))
+}
+
+// Cache key generation utilities. Keys only need to be collision-free, not
+// secret; BLAKE3 is about four times faster than SHA-256 on long texts and
+// on par for chat-sized ones on CPUs with SHA extensions (cargo bench
+// --bench cache_keys). Entries of snapshots taken with the old SHA-256 keys
+// simply never hit.
+pub fn fast_hash(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+// One allocation instead of format!'s two
+fn cache_key(prefix: &str, hash: blake3::Hash) -> String {
+    let mut key = String::with_capacity(prefix.len() + 64);
+    key.push_str(prefix);
+    key.push_str(&hash.to_hex());
+    key
+}
+
 pub fn generate_text_cache_key(text: &str) -> String {
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(text.as_bytes());
-    format!("text_{:x}", hasher.finalize())
+    cache_key("text_", blake3::hash(text.as_bytes()))
 }
 
 pub fn generate_data_cache_key(data: &str) -> String {
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(data.as_bytes());
-    format!("data_{:x}", hasher.finalize())
+    cache_key("data_", blake3::hash(data.as_bytes()))
 }
 
 pub fn generate_result_cache_key(operation: &str, input: &str) -> String {
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
+    let mut hasher = blake3::Hasher::new();
+    // Length-prefixed so ("ab", "c") and ("a", "bc") differ
+    hasher.update(&(operation.len() as u64).to_le_bytes());
     hasher.update(operation.as_bytes());
     hasher.update(input.as_bytes());
-    format!("result_{:x}", hasher.finalize())
+    cache_key("result_", hasher.finalize())
 }
 
 #[cfg(test)]
@@ -331,5 +465,28 @@ mod tests {
         
         assert_eq!(key1, key2);
         assert_ne!(key1, key3);
+        assert_eq!(key1.len(), "text_".len() + 64);
+        assert_ne!(generate_result_cache_key("ab", "c"), generate_result_cache_key("a", "bc"));
+    }
+    
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let path = std::env::temp_dir().join(format!("aiogram_cache_snapshot_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        set_cached_result("snapshot_test_key", "cached".to_string());
+        assert!(save_snapshot(path, None).unwrap() >= 1);
+        
+        RESULT_CACHE.remove("snapshot_test_key");
+        assert!(load_snapshot(path, None).unwrap() >= 1);
+        assert_eq!(get_cached_result("snapshot_test_key"), Some("cached".to_string()));
+        
+        #[cfg(feature = "crypto")]
+        {
+            save_snapshot(path, Some("snapshot_key")).unwrap();
+            assert!(load_snapshot(path, None).is_err());
+            assert!(load_snapshot(path, Some("wrong_key")).is_err());
+            assert!(load_snapshot(path, Some("snapshot_key")).is_ok());
+        }
+        let _ = std::fs::remove_file(path);
     }
-} 
\ No newline at end of file
+}
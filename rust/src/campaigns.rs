@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::chats::{HourlyCounter, BUCKET_SECONDS, MAX_RETENTION_SECONDS};
+use crate::tenant;
+
+// Deep-link campaign counters. They are kept per tenant rather than per chat,
+// since starts arrive in private chats, one per user, in the same hourly
+// buckets as the chat stats. deeplinks.rs counts verified starts here; hosts
+// report impressions themselves.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignStats {
+    pub campaign: String,
+    // Times the host reported showing the link
+    pub impressions: u64,
+    pub starts: u64,
+    pub unique_users: u64,
+    // starts / impressions, when impressions were reported
+    pub click_through_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignReport {
+    pub window_seconds: i64,
+    // Most starts first
+    pub campaigns: Vec<CampaignStats>,
+    // Start payloads that failed signature checks
+    pub rejected_starts: u64,
+}
+
+#[derive(Debug, Default)]
+struct CampaignCounters {
+    impressions: HourlyCounter,
+    starts: HourlyCounter,
+    // Keyed campaign + '\u{1f}' + user id; distinct keys are unique users
+    users: HourlyCounter,
+    rejected: HourlyCounter,
+}
+
+impl CampaignCounters {
+    fn prune(&mut self, cutoff: i64) {
+        self.impressions.prune(cutoff);
+        self.starts.prune(cutoff);
+        self.users.prune(cutoff);
+        self.rejected.prune(cutoff);
+    }
+}
+
+const REJECTED: &str = "rejected";
+
+lazy_static! {
+    static ref CAMPAIGNS: Mutex<HashMap<String, CampaignCounters>> = Mutex::new(HashMap::new());
+}
+
+fn with_campaigns<R>(now: i64, body: impl FnOnce(&mut CampaignCounters) -> R) -> R {
+    let mut campaigns = CAMPAIGNS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let counters = campaigns.entry(tenant::scope_key("campaigns")).or_default();
+    counters.prune(now - MAX_RETENTION_SECONDS);
+    body(counters)
+}
+
+pub fn record_campaign_start(campaign: &str, user_id: Option<i64>) {
+    record_campaign_start_at(campaign, user_id, Utc::now().timestamp());
+}
+
+fn record_campaign_start_at(campaign: &str, user_id: Option<i64>, now: i64) {
+    with_campaigns(now, |counters| {
+        counters.starts.add(now, campaign);
+        if let Some(user_id) = user_id {
+            counters.users.add(now, &format!("{}\u{1f}{}", campaign, user_id));
+        }
+    });
+}
+
+pub fn record_campaign_impressions(campaign: &str, impressions: u64) {
+    let now = Utc::now().timestamp();
+    with_campaigns(now, |counters| counters.impressions.add_count(now, campaign, impressions));
+}
+
+pub fn record_rejected_start() {
+    let now = Utc::now().timestamp();
+    with_campaigns(now, |counters| counters.rejected.add(now, REJECTED));
+}
+
+pub fn campaign_report(window_seconds: i64) -> CampaignReport {
+    campaign_report_at(window_seconds, Utc::now().timestamp())
+}
+
+fn campaign_report_at(window_seconds: i64, now: i64) -> CampaignReport {
+    let window_seconds = window_seconds.clamp(BUCKET_SECONDS, MAX_RETENTION_SECONDS);
+    let since = now - window_seconds;
+    with_campaigns(now, |counters| {
+        let impressions = counters.impressions.totals(since);
+        let starts = counters.starts.totals(since);
+        let mut unique_users: HashMap<String, u64> = HashMap::new();
+        for key in counters.users.totals(since).into_keys() {
+            if let Some((campaign, _)) = key.rsplit_once('\u{1f}') {
+                *unique_users.entry(campaign.to_string()).or_insert(0) += 1;
+            }
+        }
+        
+        let mut names: Vec<&String> = impressions.keys().chain(starts.keys()).collect();
+        names.sort();
+        names.dedup();
+        let mut campaigns: Vec<CampaignStats> = names.into_iter().map(|campaign| {
+            let impressions = impressions.get(campaign).copied().unwrap_or(0);
+            let starts = starts.get(campaign).copied().unwrap_or(0);
+            CampaignStats {
+                campaign: campaign.clone(),
+                impressions,
+                starts,
+                unique_users: unique_users.get(campaign).copied().unwrap_or(0),
+                click_through_rate: if impressions > 0 { Some(starts as f64 / impressions as f64) } else { None },
+            }
+        }).collect();
+        campaigns.sort_by_key(|c| std::cmp::Reverse(c.starts));
+        
+        CampaignReport {
+            window_seconds,
+            campaigns,
+            rejected_starts: counters.rejected.totals(since).values().sum(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantContext;
+    
+    #[test]
+    fn test_campaign_report_window() {
+        let context = TenantContext::new(Some("campaigns_test_bot"), None);
+        tenant::with_tenant(context, || {
+            let now = Utc::now().timestamp();
+            with_campaigns(now, |counters| counters.impressions.add_count(now - 3 * BUCKET_SECONDS, "old-post", 50));
+            record_campaign_impressions("new-post", 4);
+            record_campaign_start_at("new-post", Some(7), now);
+            record_campaign_start_at("old-post", None, now - 3 * BUCKET_SECONDS);
+            
+            let recent = campaign_report_at(BUCKET_SECONDS, now);
+            assert_eq!(recent.campaigns.len(), 1);
+            assert_eq!((recent.campaigns[0].starts, recent.campaigns[0].unique_users), (1, 1));
+            assert_eq!(recent.campaigns[0].click_through_rate, Some(0.25));
+            
+            let day = campaign_report_at(86_400, now);
+            assert_eq!(day.campaigns.iter().map(|c| c.campaign.as_str()).collect::<Vec<_>>(), ["new-post", "old-post"]);
+            assert_eq!(day.campaigns[1].click_through_rate, Some(0.02));
+        });
+    }
+}
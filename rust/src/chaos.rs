@@ -0,0 +1,163 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Fault injection for resilience testing in staging. Each fault fires with
+// its configured probability: cache lookups miss and writes are dropped,
+// exports are delayed, encrypt / decrypt / seal / open fail, and realtime
+// items are dropped before processing. Nothing fires until configure() is
+// called, and the whole module only exists with the chaos feature.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Fault {
+    CacheError,
+    SlowResponse,
+    CryptoFailure,
+    DroppedRealtime,
+}
+
+// Probabilities are 0-1; unset fields are 0
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    pub cache_error: f64,
+    pub slow_response: f64,
+    pub slow_response_ms: u64,
+    pub crypto_failure: f64,
+    pub dropped_realtime: f64,
+    // Makes the sequence of injected faults repeatable
+    pub seed: Option<u64>,
+}
+
+impl FaultConfig {
+    fn probability(&self, fault: Fault) -> f64 {
+        match fault {
+            Fault::CacheError => self.cache_error,
+            Fault::SlowResponse => self.slow_response,
+            Fault::CryptoFailure => self.crypto_failure,
+            Fault::DroppedRealtime => self.dropped_realtime,
+        }
+    }
+    
+    pub fn validate(&self) -> Result<(), String> {
+        let probabilities = [
+            ("cache_error", self.cache_error),
+            ("slow_response", self.slow_response),
+            ("crypto_failure", self.crypto_failure),
+            ("dropped_realtime", self.dropped_realtime),
+        ];
+        for (name, probability) in probabilities {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!("Fault probability {} must be between 0 and 1, got {}", name, probability));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Faults injected since the last configure()
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultStats {
+    pub cache_errors: u64,
+    pub slow_responses: u64,
+    pub crypto_failures: u64,
+    pub dropped_realtime: u64,
+}
+
+struct Injector {
+    config: FaultConfig,
+    rng: StdRng,
+    stats: FaultStats,
+}
+
+impl Injector {
+    fn new(config: FaultConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng, stats: FaultStats::default() }
+    }
+    
+    fn roll(&mut self, fault: Fault) -> bool {
+        let probability = self.config.probability(fault);
+        if probability <= 0.0 || !self.rng.gen_bool(probability) {
+            return false;
+        }
+        let counter = match fault {
+            Fault::CacheError => &mut self.stats.cache_errors,
+            Fault::SlowResponse => &mut self.stats.slow_responses,
+            Fault::CryptoFailure => &mut self.stats.crypto_failures,
+            Fault::DroppedRealtime => &mut self.stats.dropped_realtime,
+        };
+        *counter += 1;
+        true
+    }
+}
+
+lazy_static! {
+    static ref INJECTOR: Mutex<Option<Injector>> = Mutex::new(None);
+}
+
+fn injector() -> MutexGuard<'static, Option<Injector>> {
+    INJECTOR.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Replaces the fault configuration and resets the stats
+pub fn configure(config: FaultConfig) -> Result<(), String> {
+    config.validate()?;
+    *injector() = Some(Injector::new(config));
+    Ok(())
+}
+
+pub fn disable() {
+    *injector() = None;
+}
+
+pub fn stats() -> FaultStats {
+    injector().as_ref().map(|injector| injector.stats.clone()).unwrap_or_default()
+}
+
+// Whether fault should fire for this call
+pub fn inject(fault: Fault) -> bool {
+    injector().as_mut().map(|injector| injector.roll(fault)).unwrap_or(false)
+}
+
+// Sleeps for slow_response_ms when a slow response is injected
+pub fn maybe_delay() {
+    let delay = injector().as_mut().and_then(|injector| injector.roll(Fault::SlowResponse).then_some(injector.config.slow_response_ms));
+    // Outside the lock, so other calls keep rolling meanwhile
+    if let Some(delay) = delay {
+        std::thread::sleep(Duration::from_millis(delay));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_seeded_injection() {
+        let config = FaultConfig { cache_error: 0.5, crypto_failure: 1.0, seed: Some(7), ..Default::default() };
+        let rolls = |config: &FaultConfig| {
+            let mut injector = Injector::new(config.clone());
+            let rolls: Vec<bool> = (0..64).map(|_| injector.roll(Fault::CacheError)).collect();
+            (rolls, injector)
+        };
+        let (first, mut injector) = rolls(&config);
+        assert_eq!(first, rolls(&config).0);
+        assert!(first.iter().any(|&fired| fired) && first.iter().any(|&fired| !fired));
+        assert_eq!(injector.stats.cache_errors, first.iter().filter(|&&fired| fired).count() as u64);
+        
+        assert!(injector.roll(Fault::CryptoFailure));
+        assert!(!injector.roll(Fault::DroppedRealtime));
+        assert_eq!((injector.stats.crypto_failures, injector.stats.dropped_realtime), (1, 0));
+        
+        assert!(FaultConfig { slow_response: 1.5, ..Default::default() }.validate().is_err());
+        assert!(serde_json::from_str::<FaultConfig>(r#"{"cache_eror": 0.1}"#).is_err());
+    }
+}
\ No newline at end of file
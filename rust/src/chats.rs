@@ -0,0 +1,1293 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::alerts::{self, AlertEvent};
+use crate::config::{AppConfig, EngagementConfig};
+use crate::tenant;
+
+// Per-chat aggregation of Telegram updates. Messages are recorded as they
+// arrive and folded into hourly buckets per chat; reports sum the buckets
+// inside the requested window. Buckets older than MAX_RETENTION_SECONDS are
+// dropped, so memory stays bounded by chats x hours x distinct items.
+// Membership is kept per member instead, for COHORT_RETENTION_SECONDS so
+// that D30 retention can still be computed for older join cohorts.
+// Chats are scoped by the current tenant, like cache keys. Sentiment is kept
+// as hourly sums and counts per chat and per member, which is enough for
+// averages over any window and for spotting a sharp drop.
+// Leaderboards read per-author hourly message counts, which also give the
+// UTC days each member posted on, and the latest reaction totals of recent
+// messages.
+// Engagement weighs a post's views, forwards, replies and reactions by the
+// engagement config and divides by the chat's size, so an index compares a
+// small group with a big channel. Trending posts are recent ones far above
+// their chat's own average.
+
+pub(crate) const BUCKET_SECONDS: i64 = 3600;
+const DAY_SECONDS: i64 = 86_400;
+pub const MAX_RETENTION_SECONDS: i64 = 30 * DAY_SECONDS;
+pub const COHORT_RETENTION_SECONDS: i64 = 90 * DAY_SECONDS;
+// Posts this recent can trend, against the average of this week's posts
+const TRENDING_SECONDS: i64 = DAY_SECONDS;
+const TRENDING_BASELINE_SECONDS: i64 = 7 * DAY_SECONDS;
+
+// The parts of a Bot API Message the aggregations read
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMessage {
+    #[serde(default)]
+    pub message_id: i64,
+    pub chat: ChatRef,
+    #[serde(default)]
+    pub from: Option<UserRef>,
+    pub date: i64,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub caption: Option<String>,
+    #[serde(default)]
+    pub sticker: Option<Sticker>,
+    #[serde(default)]
+    pub entities: Vec<MessageEntity>,
+    #[serde(default)]
+    pub caption_entities: Vec<MessageEntity>,
+    #[serde(default)]
+    pub new_chat_members: Vec<UserRef>,
+    #[serde(default)]
+    pub left_chat_member: Option<UserRef>,
+    // Counts as a reply to that post when the stats saw it
+    #[serde(default)]
+    pub reply_to_message: Option<MessageRef>,
+}
+
+// A Bot API ChatMemberUpdated, sent for joins and leaves when the bot asks
+// for chat_member updates; more reliable than service messages, which big
+// groups often hide
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMemberUpdate {
+    pub chat: ChatRef,
+    pub date: i64,
+    pub old_chat_member: ChatMember,
+    pub new_chat_member: ChatMember,
+}
+
+// A Bot API MessageReactionCountUpdated, sent to bots that administer the
+// chat and ask for message_reaction_count updates. Totals include anonymous
+// reactions and replace those of an earlier update.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReactionCountUpdate {
+    pub chat: ChatRef,
+    pub message_id: i64,
+    pub date: i64,
+    #[serde(default)]
+    pub reactions: Vec<ReactionCount>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReactionCount {
+    // ReactionType object; any kind counts the same
+    #[serde(rename = "type")]
+    pub reaction_type: serde_json::Value,
+    pub total_count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMember {
+    pub status: String,
+    pub user: UserRef,
+    // Only sent for "restricted" members
+    #[serde(default)]
+    pub is_member: Option<bool>,
+}
+
+impl ChatMember {
+    fn is_present(&self) -> bool {
+        match self.status.as_str() {
+            "creator" | "administrator" | "member" => true,
+            "restricted" => self.is_member.unwrap_or(true),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRef {
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserRef {
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageRef {
+    pub message_id: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Sticker {
+    pub file_unique_id: String,
+    #[serde(default)]
+    pub set_name: Option<String>,
+    #[serde(default)]
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub custom_emoji_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub entity_type: String,
+    #[serde(default)]
+    pub custom_emoji_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StickerUsage {
+    pub file_unique_id: String,
+    pub set_name: Option<String>,
+    pub emoji: Option<String>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageCount {
+    pub id: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CohortRetention {
+    // UTC join date, YYYY-MM-DD
+    pub cohort: String,
+    pub joined: u64,
+    // None until every member of the cohort is old enough to be measured
+    pub d1: Option<f64>,
+    pub d7: Option<f64>,
+    pub d30: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberReport {
+    pub chat_id: i64,
+    pub window_seconds: i64,
+    pub joins: u64,
+    pub leaves: u64,
+    pub net_growth: i64,
+    // Tracked members present at any point of the window who left within it
+    pub churn_rate: f64,
+    pub d1_retention: Option<f64>,
+    pub d7_retention: Option<f64>,
+    pub d30_retention: Option<f64>,
+    pub cohorts: Vec<CohortRetention>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentPoint {
+    // UTC date, YYYY-MM-DD
+    pub day: String,
+    pub average: f64,
+    pub messages: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentTrend {
+    pub chat_id: i64,
+    // Set for a single member's trend
+    pub user_id: Option<i64>,
+    pub window_seconds: i64,
+    // Averages of compound scores from -1 to 1; None without scored messages
+    pub average: Option<f64>,
+    pub messages: u64,
+    pub last_day: Option<f64>,
+    // The baseline_days before the last day
+    pub baseline: Option<f64>,
+    // last_day - baseline; negative when the mood got worse
+    pub change: Option<f64>,
+    // Days of the window with scored messages, oldest first
+    pub daily: Vec<SentimentPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StickerReport {
+    pub chat_id: i64,
+    pub window_seconds: i64,
+    pub total_stickers: u64,
+    pub top_stickers: Vec<StickerUsage>,
+    pub top_sticker_sets: Vec<UsageCount>,
+    pub top_custom_emoji: Vec<UsageCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeaderboardOptions {
+    // Length of the window ending at until, one hour to 30 days
+    pub window_seconds: i64,
+    // Unix time the window ends, e.g. the start of this week's post; None
+    // for now
+    pub until: Option<i64>,
+    // Entries per list
+    pub limit: usize,
+}
+
+impl Default for LeaderboardOptions {
+    fn default() -> Self {
+        Self {
+            window_seconds: 7 * DAY_SECONDS,
+            until: None,
+            limit: 10,
+        }
+    }
+}
+
+impl LeaderboardOptions {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid leaderboard options: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveUser {
+    // 1-based position; ties are broken, so ranks never repeat
+    pub rank: usize,
+    pub user_id: i64,
+    pub messages: u64,
+    // UTC days of the window the user posted on
+    pub active_days: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactedMessage {
+    pub rank: usize,
+    pub message_id: i64,
+    // None for channel posts and messages the chat stats never saw
+    pub user_id: Option<i64>,
+    pub date: i64,
+    pub reactions: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEngagement {
+    pub rank: usize,
+    pub message_id: i64,
+    pub user_id: Option<i64>,
+    pub date: i64,
+    pub views: u64,
+    pub forwards: u64,
+    pub replies: u64,
+    pub reactions: u64,
+    // Interactions weighted by the engagement config
+    pub score: f64,
+    // Score per 100 members, comparable between chats of any size
+    pub index: f64,
+    // Score over the average of the chat's posts; above 1 is more than usual
+    pub relative: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityStreak {
+    pub rank: usize,
+    pub user_id: i64,
+    // Consecutive UTC days with at least one message
+    pub days: u64,
+    // UTC dates, YYYY-MM-DD
+    pub start: String,
+    pub end: String,
+    // Ends on the window's last day or the day before, so it can go on
+    pub ongoing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub chat_id: i64,
+    // Unix times of the window
+    pub since: i64,
+    pub until: i64,
+    pub most_active: Vec<ActiveUser>,
+    pub most_reacted: Vec<ReactedMessage>,
+    pub longest_streaks: Vec<ActivityStreak>,
+    // Posts with any engagement, highest score first
+    pub most_engaged: Vec<PostEngagement>,
+}
+
+// Counts per item per hour
+#[derive(Debug, Default)]
+pub(crate) struct HourlyCounter {
+    buckets: BTreeMap<i64, HashMap<String, u64>>,
+}
+
+impl HourlyCounter {
+    pub(crate) fn add(&mut self, timestamp: i64, item: &str) {
+        self.add_count(timestamp, item, 1);
+    }
+    
+    pub(crate) fn add_count(&mut self, timestamp: i64, item: &str, count: u64) {
+        let bucket = timestamp.div_euclid(BUCKET_SECONDS);
+        *self.buckets.entry(bucket).or_default().entry(item.to_string()).or_insert(0) += count;
+    }
+    
+    // Reports whether any bucket was dropped
+    pub(crate) fn prune(&mut self, cutoff: i64) -> bool {
+        let first_kept = cutoff.div_euclid(BUCKET_SECONDS);
+        let before = self.buckets.len();
+        self.buckets = self.buckets.split_off(&first_kept);
+        self.buckets.len() != before
+    }
+    
+    pub(crate) fn totals(&self, since: i64) -> HashMap<String, u64> {
+        let mut totals = HashMap::new();
+        for counts in self.buckets.range(since.div_euclid(BUCKET_SECONDS)..).map(|(_, counts)| counts) {
+            for (item, count) in counts {
+                *totals.entry(item.clone()).or_insert(0) += count;
+            }
+        }
+        totals
+    }
+    
+    // Buckets of the hours from since through until
+    fn between(&self, since: i64, until: i64) -> impl Iterator<Item = (&i64, &HashMap<String, u64>)> {
+        self.buckets.range(since.div_euclid(BUCKET_SECONDS)..=until.div_euclid(BUCKET_SECONDS))
+    }
+}
+
+// Sum and count of sentiment scores per hour
+#[derive(Debug, Default)]
+struct SentimentSeries {
+    buckets: BTreeMap<i64, (f64, u64)>,
+}
+
+impl SentimentSeries {
+    fn add(&mut self, timestamp: i64, score: f64) {
+        let bucket = self.buckets.entry(timestamp.div_euclid(BUCKET_SECONDS)).or_insert((0.0, 0));
+        bucket.0 += score;
+        bucket.1 += 1;
+    }
+    
+    fn prune(&mut self, cutoff: i64) {
+        self.buckets = self.buckets.split_off(&cutoff.div_euclid(BUCKET_SECONDS));
+    }
+    
+    // Sum and count of the hours from since up to, not including, until
+    fn total(&self, since: i64, until: i64) -> (f64, u64) {
+        let (first, last) = (since.div_euclid(BUCKET_SECONDS), until.div_euclid(BUCKET_SECONDS));
+        if first >= last {
+            return (0.0, 0);
+        }
+        self.buckets.range(first..last).fold((0.0, 0), |(sum, count), (_, (s, c))| (sum + s, count + c))
+    }
+    
+    fn daily(&self, since: i64) -> Vec<SentimentPoint> {
+        let mut days: BTreeMap<i64, (f64, u64)> = BTreeMap::new();
+        for (bucket, (sum, count)) in self.buckets.range(since.div_euclid(BUCKET_SECONDS)..) {
+            let day = days.entry((bucket * BUCKET_SECONDS).div_euclid(DAY_SECONDS)).or_insert((0.0, 0));
+            day.0 += sum;
+            day.1 += count;
+        }
+        days.into_iter().map(|(day, (sum, count))| SentimentPoint {
+            day: format_day(day),
+            average: sum / count as f64,
+            messages: count,
+        }).collect()
+    }
+}
+
+fn format_day(day: i64) -> String {
+    chrono::DateTime::from_timestamp(day * DAY_SECONDS, 0).map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default()
+}
+
+fn mean((sum, count): (f64, u64)) -> Option<f64> {
+    if count == 0 { None } else { Some(sum / count as f64) }
+}
+
+// Latest stint of one member; joined_at is None when they joined before
+// tracking started
+#[derive(Debug, Clone, Copy, Default)]
+struct Membership {
+    joined_at: Option<i64>,
+    left_at: Option<i64>,
+}
+
+impl Membership {
+    // Whether the member was still in the chat `days` after joining, or None
+    // if that point is still in the future
+    fn retained(&self, days: i64, now: i64) -> Option<bool> {
+        let checkpoint = self.joined_at? + days * DAY_SECONDS;
+        if checkpoint > now {
+            return None;
+        }
+        Some(self.left_at.map(|left| left >= checkpoint).unwrap_or(true))
+    }
+}
+
+// A recent message and its latest reaction total; views and forwards are
+// whatever the host last reported
+#[derive(Debug, Clone, Copy, Default)]
+struct Post {
+    date: i64,
+    user_id: Option<i64>,
+    reactions: u64,
+    views: u64,
+    forwards: u64,
+    replies: u64,
+}
+
+impl Post {
+    fn score(&self, weights: &EngagementConfig) -> f64 {
+        self.views as f64 * weights.view_weight
+            + self.forwards as f64 * weights.forward_weight
+            + self.replies as f64 * weights.reply_weight
+            + self.reactions as f64 * weights.reaction_weight
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChatStats {
+    members: HashMap<i64, Membership>,
+    stickers: HourlyCounter,
+    sticker_sets: HourlyCounter,
+    custom_emoji: HourlyCounter,
+    // Latest set and emoji seen for each sticker
+    sticker_info: HashMap<String, (Option<String>, Option<String>)>,
+    sentiment: SentimentSeries,
+    member_sentiment: HashMap<i64, SentimentSeries>,
+    // Last sentiment drop alert, so a bad day alerts once
+    sentiment_alerted_at: Option<i64>,
+    // Messages per author id
+    user_messages: HourlyCounter,
+    // By message id
+    posts: HashMap<i64, Post>,
+    // As last reported by the host, e.g. from getChatMemberCount
+    member_count: Option<u64>,
+}
+
+lazy_static! {
+    static ref CHATS: Mutex<HashMap<String, ChatStats>> = Mutex::new(HashMap::new());
+}
+
+fn chats() -> MutexGuard<'static, HashMap<String, ChatStats>> {
+    CHATS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn chat_key(chat_id: i64) -> String {
+    tenant::scope_key(&chat_id.to_string())
+}
+
+fn top_counts(totals: HashMap<String, u64>, limit: usize) -> Vec<UsageCount> {
+    let mut counts: Vec<UsageCount> = totals.into_iter().map(|(id, count)| UsageCount { id, count }).collect();
+    // Ties break by id so reports are stable
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.id.cmp(&b.id)));
+    counts.truncate(limit);
+    counts
+}
+
+pub fn record_message(message: &ChatMessage) {
+    record_message_at(message, Utc::now().timestamp());
+    #[cfg(feature = "nlp")]
+    record_message_sentiment(message);
+}
+
+// Scores text and captions with the active sentiment analyzer; commands are
+// not conversation
+#[cfg(feature = "nlp")]
+fn record_message_sentiment(message: &ChatMessage) {
+    let text = match message.text.as_deref().or(message.caption.as_deref()).map(str::trim) {
+        Some(text) if !text.is_empty() && !text.starts_with('/') => text,
+        _ => return,
+    };
+    let score = crate::sentiment::active().analyze(text).compound;
+    record_sentiment(message.chat.id, message.from.as_ref().map(|user| user.id), message.date, score);
+}
+
+impl ChatStats {
+    // Events can arrive out of order; an older event never overrides a newer one
+    fn member_joined(&mut self, user_id: i64, at: i64) {
+        let membership = self.members.entry(user_id).or_default();
+        let latest = membership.joined_at.into_iter().chain(membership.left_at).max();
+        if latest.map(|latest| at >= latest).unwrap_or(true) {
+            *membership = Membership { joined_at: Some(at), left_at: None };
+        }
+    }
+    
+    fn member_left(&mut self, user_id: i64, at: i64) {
+        let membership = self.members.entry(user_id).or_default();
+        if membership.joined_at.map(|joined| at >= joined).unwrap_or(true) && membership.left_at.map(|left| at > left).unwrap_or(true) {
+            membership.left_at = Some(at);
+        }
+    }
+    
+    fn prune_members(&mut self, now: i64) {
+        let cutoff = now - COHORT_RETENTION_SECONDS;
+        self.members.retain(|_, m| m.joined_at.into_iter().chain(m.left_at).max().map(|latest| latest >= cutoff).unwrap_or(false));
+    }
+    
+    // The reported member count, or else the members who posted within the
+    // retention window
+    fn audience(&self) -> u64 {
+        self.member_count.unwrap_or_else(|| self.user_messages.totals(i64::MIN).len() as u64).max(1)
+    }
+    
+    // Posts dated from since through until, highest score first; relative
+    // compares each with the average of the same posts
+    fn engagement(&self, weights: &EngagementConfig, since: i64, until: i64) -> Vec<PostEngagement> {
+        let posts: Vec<(i64, &Post)> = self.posts.iter()
+            .filter(|(_, post)| post.date >= since && post.date <= until)
+            .map(|(&message_id, post)| (message_id, post))
+            .collect();
+        let average = posts.iter().map(|(_, post)| post.score(weights)).sum::<f64>() / posts.len().max(1) as f64;
+        let audience = self.audience() as f64;
+        let mut engagement: Vec<PostEngagement> = posts.into_iter().map(|(message_id, post)| {
+            let score = post.score(weights);
+            PostEngagement {
+                rank: 0,
+                message_id,
+                user_id: post.user_id,
+                date: post.date,
+                views: post.views,
+                forwards: post.forwards,
+                replies: post.replies,
+                reactions: post.reactions,
+                score,
+                index: score * 100.0 / audience,
+                relative: if average > 0.0 { score / average } else { 0.0 },
+            }
+        }).collect();
+        // Ties go to the earlier post, then the lower id
+        engagement.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.date.cmp(&b.date)).then(a.message_id.cmp(&b.message_id)));
+        engagement
+    }
+}
+
+pub(crate) fn record_message_at(message: &ChatMessage, now: i64) {
+    if message.date < now - COHORT_RETENTION_SECONDS {
+        return;
+    }
+    
+    let mut chats = chats();
+    let stats = chats.entry(chat_key(message.chat.id)).or_default();
+    
+    for member in &message.new_chat_members {
+        stats.member_joined(member.id, message.date);
+    }
+    if let Some(ref member) = message.left_chat_member {
+        stats.member_left(member.id, message.date);
+    }
+    stats.prune_members(now);
+    if message.date < now - MAX_RETENTION_SECONDS {
+        return;
+    }
+    
+    if let Some(ref sticker) = message.sticker {
+        stats.stickers.add(message.date, &sticker.file_unique_id);
+        if let Some(ref set_name) = sticker.set_name {
+            stats.sticker_sets.add(message.date, set_name);
+        }
+        if let Some(ref custom_emoji_id) = sticker.custom_emoji_id {
+            stats.custom_emoji.add(message.date, custom_emoji_id);
+        }
+        stats.sticker_info.insert(sticker.file_unique_id.clone(), (sticker.set_name.clone(), sticker.emoji.clone()));
+    }
+    
+    let custom_emoji = message.entities.iter().chain(message.caption_entities.iter())
+        .filter(|entity| entity.entity_type == "custom_emoji")
+        .filter_map(|entity| entity.custom_emoji_id.as_deref());
+    for custom_emoji_id in custom_emoji {
+        stats.custom_emoji.add(message.date, custom_emoji_id);
+    }
+    
+    // Service messages are not activity
+    if message.new_chat_members.is_empty() && message.left_chat_member.is_none() {
+        let author = message.from.as_ref().map(|user| user.id);
+        if let Some(user_id) = author {
+            stats.user_messages.add(message.date, &user_id.to_string());
+        }
+        if message.message_id != 0 {
+            // Reactions may have been counted before the message arrived
+            let post = stats.posts.entry(message.message_id).or_default();
+            post.date = message.date;
+            post.user_id = author;
+        }
+        if let Some(parent) = message.reply_to_message.as_ref().and_then(|reply| stats.posts.get_mut(&reply.message_id)) {
+            parent.replies += 1;
+        }
+    }
+    
+    let cutoff = now - MAX_RETENTION_SECONDS;
+    stats.user_messages.prune(cutoff);
+    stats.posts.retain(|_, post| post.date >= cutoff);
+    stats.sticker_sets.prune(cutoff);
+    stats.custom_emoji.prune(cutoff);
+    if stats.stickers.prune(cutoff) {
+        let live = stats.stickers.totals(cutoff);
+        stats.sticker_info.retain(|id, _| live.contains_key(id));
+    }
+}
+
+// Accepts one Bot API Message object
+pub fn record_message_json(message_json: &str) -> Result<(), String> {
+    let message: ChatMessage = serde_json::from_str(message_json).map_err(|e| format!("Invalid message: {}", e))?;
+    record_message(&message);
+    Ok(())
+}
+
+pub fn record_member_update(update: &ChatMemberUpdate) {
+    record_member_update_at(update, Utc::now().timestamp());
+}
+
+fn record_member_update_at(update: &ChatMemberUpdate, now: i64) {
+    if update.date < now - COHORT_RETENTION_SECONDS {
+        return;
+    }
+    
+    let mut chats = chats();
+    let stats = chats.entry(chat_key(update.chat.id)).or_default();
+    let user_id = update.new_chat_member.user.id;
+    match (update.old_chat_member.is_present(), update.new_chat_member.is_present()) {
+        (false, true) => stats.member_joined(user_id, update.date),
+        (true, false) => stats.member_left(user_id, update.date),
+        // Promotions, restrictions and the like
+        _ => {}
+    }
+    stats.prune_members(now);
+}
+
+pub fn record_member_update_json(update_json: &str) -> Result<(), String> {
+    let update: ChatMemberUpdate = serde_json::from_str(update_json).map_err(|e| format!("Invalid chat member update: {}", e))?;
+    record_member_update(&update);
+    Ok(())
+}
+
+pub fn record_reaction_count(update: &ReactionCountUpdate) {
+    record_reaction_count_at(update, Utc::now().timestamp());
+}
+
+fn record_reaction_count_at(update: &ReactionCountUpdate, now: i64) {
+    if update.date < now - MAX_RETENTION_SECONDS {
+        return;
+    }
+    let mut chats = chats();
+    let stats = chats.entry(chat_key(update.chat.id)).or_default();
+    // Dated by the update until the message itself is recorded
+    let post = stats.posts.entry(update.message_id).or_insert(Post { date: update.date, ..Default::default() });
+    post.reactions = update.reactions.iter().map(|reaction| reaction.total_count).sum();
+}
+
+pub fn record_reaction_count_json(update_json: &str) -> Result<(), String> {
+    let update: ReactionCountUpdate = serde_json::from_str(update_json).map_err(|e| format!("Invalid reaction count update: {}", e))?;
+    record_reaction_count(&update);
+    Ok(())
+}
+
+// Views and forwards of a post, which the Bot API does not send; totals
+// replace earlier ones
+pub fn record_post_metrics(chat_id: i64, message_id: i64, views: u64, forwards: u64) {
+    record_post_metrics_at(chat_id, message_id, views, forwards, Utc::now().timestamp());
+}
+
+fn record_post_metrics_at(chat_id: i64, message_id: i64, views: u64, forwards: u64, now: i64) {
+    let mut chats = chats();
+    let stats = chats.entry(chat_key(chat_id)).or_default();
+    // Dated now until the message itself is recorded
+    let post = stats.posts.entry(message_id).or_insert(Post { date: now, ..Default::default() });
+    post.views = views;
+    post.forwards = forwards;
+}
+
+// Members of the chat, which engagement indexes are per 100 of; 0 goes back
+// to counting the members who posted
+pub fn record_member_count(chat_id: i64, count: u64) {
+    chats().entry(chat_key(chat_id)).or_default().member_count = (count > 0).then_some(count);
+}
+
+fn retention_rate<'a>(members: impl Iterator<Item = &'a Membership>, days: i64, now: i64) -> Option<f64> {
+    let (mut eligible, mut retained) = (0u64, 0u64);
+    for outcome in members.map(|m| m.retained(days, now)) {
+        match outcome {
+            Some(kept) => {
+                eligible += 1;
+                retained += kept as u64;
+            }
+            None => return None,
+        }
+    }
+    if eligible == 0 { None } else { Some(retained as f64 / eligible as f64) }
+}
+
+pub fn member_report(chat_id: i64, window_seconds: i64) -> MemberReport {
+    member_report_at(chat_id, window_seconds, Utc::now().timestamp())
+}
+
+fn member_report_at(chat_id: i64, window_seconds: i64, now: i64) -> MemberReport {
+    let window_seconds = window_seconds.clamp(DAY_SECONDS, COHORT_RETENTION_SECONDS);
+    let since = now - window_seconds;
+    let chats = chats();
+    let members: Vec<Membership> = chats.get(&chat_key(chat_id)).map(|s| s.members.values().copied().collect()).unwrap_or_default();
+    
+    let in_window = |at: Option<i64>| at.map(|at| at >= since && at <= now).unwrap_or(false);
+    let joins = members.iter().filter(|m| in_window(m.joined_at)).count() as u64;
+    let leaves = members.iter().filter(|m| in_window(m.left_at)).count() as u64;
+    let exposed = members.iter()
+        .filter(|m| m.joined_at.map(|j| j <= now).unwrap_or(true) && m.left_at.map(|l| l >= since).unwrap_or(true))
+        .count();
+    
+    // Overall rates only count members old enough for each checkpoint
+    let joined: Vec<&Membership> = members.iter().filter(|m| in_window(m.joined_at)).collect();
+    let overall = |days: i64| retention_rate(joined.iter().copied().filter(|m| m.retained(days, now).is_some()), days, now);
+    
+    let mut by_day: BTreeMap<i64, Vec<&Membership>> = BTreeMap::new();
+    for membership in &joined {
+        if let Some(joined_at) = membership.joined_at {
+            by_day.entry(joined_at.div_euclid(DAY_SECONDS)).or_default().push(membership);
+        }
+    }
+    let cohorts = by_day.into_iter().map(|(day, members)| CohortRetention {
+        cohort: format_day(day),
+        joined: members.len() as u64,
+        d1: retention_rate(members.iter().copied(), 1, now),
+        d7: retention_rate(members.iter().copied(), 7, now),
+        d30: retention_rate(members.iter().copied(), 30, now),
+    }).collect();
+    
+    MemberReport {
+        chat_id,
+        window_seconds,
+        joins,
+        leaves,
+        net_growth: joins as i64 - leaves as i64,
+        churn_rate: if exposed > 0 { leaves as f64 / exposed as f64 } else { 0.0 },
+        d1_retention: overall(1),
+        d7_retention: overall(7),
+        d30_retention: overall(30),
+        cohorts,
+    }
+}
+
+pub fn sticker_report(chat_id: i64, window_seconds: i64, limit: usize) -> StickerReport {
+    sticker_report_at(chat_id, window_seconds, limit, Utc::now().timestamp())
+}
+
+fn sticker_report_at(chat_id: i64, window_seconds: i64, limit: usize, now: i64) -> StickerReport {
+    let window_seconds = window_seconds.clamp(BUCKET_SECONDS, MAX_RETENTION_SECONDS);
+    let since = now - window_seconds;
+    let chats = chats();
+    let stats = chats.get(&chat_key(chat_id));
+    
+    let totals = |counter: fn(&ChatStats) -> &HourlyCounter| stats.map(|s| counter(s).totals(since)).unwrap_or_default();
+    let sticker_totals = totals(|s| &s.stickers);
+    let total_stickers = sticker_totals.values().sum();
+    let top_stickers = top_counts(sticker_totals, limit).into_iter().map(|usage| {
+        let (set_name, emoji) = stats.and_then(|s| s.sticker_info.get(&usage.id)).cloned().unwrap_or_default();
+        StickerUsage { file_unique_id: usage.id, set_name, emoji, count: usage.count }
+    }).collect();
+    
+    StickerReport {
+        chat_id,
+        window_seconds,
+        total_stickers,
+        top_stickers,
+        top_sticker_sets: top_counts(totals(|s| &s.sticker_sets), limit),
+        top_custom_emoji: top_counts(totals(|s| &s.custom_emoji), limit),
+    }
+}
+
+// First and last day of the longest run of consecutive days; of equally
+// long runs, the latest
+fn longest_run(days: &BTreeSet<i64>) -> Option<(i64, i64)> {
+    let mut best: Option<(i64, i64)> = None;
+    let mut run_start = 0;
+    let mut previous: Option<i64> = None;
+    for &day in days {
+        if previous != Some(day - 1) {
+            run_start = day;
+        }
+        previous = Some(day);
+        if best.map(|(start, end)| day - run_start >= end - start).unwrap_or(true) {
+            best = Some((run_start, day));
+        }
+    }
+    best
+}
+
+pub fn leaderboard(chat_id: i64, options: &LeaderboardOptions) -> Leaderboard {
+    leaderboard_at(chat_id, options, Utc::now().timestamp())
+}
+
+fn leaderboard_at(chat_id: i64, options: &LeaderboardOptions, now: i64) -> Leaderboard {
+    let oldest = now - MAX_RETENTION_SECONDS;
+    let until = options.until.unwrap_or(now).clamp(oldest, now);
+    let since = (until - options.window_seconds.clamp(BUCKET_SECONDS, MAX_RETENTION_SECONDS)).max(oldest);
+    let chats = chats();
+    let stats = chats.get(&chat_key(chat_id));
+    
+    // Messages and UTC days posted on, per author
+    let mut authors: HashMap<i64, (u64, BTreeSet<i64>)> = HashMap::new();
+    for (bucket, counts) in stats.into_iter().flat_map(|s| s.user_messages.between(since, until)) {
+        let day = (bucket * BUCKET_SECONDS).div_euclid(DAY_SECONDS);
+        for (user, count) in counts {
+            if let Ok(user_id) = user.parse::<i64>() {
+                let author = authors.entry(user_id).or_default();
+                author.0 += count;
+                author.1.insert(day);
+            }
+        }
+    }
+    
+    let mut most_active: Vec<ActiveUser> = authors.iter().map(|(&user_id, (messages, days))| ActiveUser {
+        rank: 0,
+        user_id,
+        messages: *messages,
+        active_days: days.len() as u64,
+    }).collect();
+    // Ties go to the member active on more days, then the lower id
+    most_active.sort_by(|a, b| b.messages.cmp(&a.messages).then(b.active_days.cmp(&a.active_days)).then(a.user_id.cmp(&b.user_id)));
+    most_active.truncate(options.limit);
+    most_active.iter_mut().enumerate().for_each(|(i, user)| user.rank = i + 1);
+    
+    let mut most_reacted: Vec<ReactedMessage> = stats.into_iter()
+        .flat_map(|s| s.posts.iter())
+        .filter(|(_, post)| post.reactions > 0 && post.date >= since && post.date <= until)
+        .map(|(&message_id, post)| ReactedMessage { rank: 0, message_id, user_id: post.user_id, date: post.date, reactions: post.reactions })
+        .collect();
+    // Ties go to the earlier message, then the lower id
+    most_reacted.sort_by(|a, b| b.reactions.cmp(&a.reactions).then(a.date.cmp(&b.date)).then(a.message_id.cmp(&b.message_id)));
+    most_reacted.truncate(options.limit);
+    most_reacted.iter_mut().enumerate().for_each(|(i, message)| message.rank = i + 1);
+    
+    let last_day = until.div_euclid(DAY_SECONDS);
+    let mut longest_streaks: Vec<ActivityStreak> = authors.iter().filter_map(|(&user_id, (_, days))| {
+        let (start, end) = longest_run(days)?;
+        Some(ActivityStreak {
+            rank: 0,
+            user_id,
+            days: (end - start + 1) as u64,
+            start: format_day(start),
+            end: format_day(end),
+            ongoing: end >= last_day - 1,
+        })
+    }).collect();
+    // Ties go to the more recent streak, then the lower id
+    longest_streaks.sort_by(|a, b| b.days.cmp(&a.days).then(b.end.cmp(&a.end)).then(a.user_id.cmp(&b.user_id)));
+    longest_streaks.truncate(options.limit);
+    longest_streaks.iter_mut().enumerate().for_each(|(i, streak)| streak.rank = i + 1);
+    
+    let mut most_engaged = stats.map(|s| s.engagement(&AppConfig::get().engagement, since, until)).unwrap_or_default();
+    most_engaged.retain(|post| post.score > 0.0);
+    most_engaged.truncate(options.limit);
+    most_engaged.iter_mut().enumerate().for_each(|(i, post)| post.rank = i + 1);
+    
+    Leaderboard {
+        chat_id,
+        since,
+        until,
+        most_active,
+        most_reacted,
+        longest_streaks,
+        most_engaged,
+    }
+}
+
+// Posts of the last day scoring trending_factor times the average of the
+// week's posts or more, furthest above it first
+pub fn trending_posts(chat_id: i64, limit: usize) -> Vec<PostEngagement> {
+    trending_posts_at(chat_id, limit, Utc::now().timestamp())
+}
+
+fn trending_posts_at(chat_id: i64, limit: usize, now: i64) -> Vec<PostEngagement> {
+    let weights = AppConfig::get().engagement;
+    let chats = chats();
+    let mut trending: Vec<PostEngagement> = chats.get(&chat_key(chat_id))
+        .map(|s| s.engagement(&weights, now - TRENDING_BASELINE_SECONDS, now))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|post| post.date >= now - TRENDING_SECONDS && post.relative >= weights.trending_factor)
+        .filter(|post| post.forwards + post.replies + post.reactions >= weights.trending_min_interactions)
+        .collect();
+    trending.sort_by(|a, b| b.relative.total_cmp(&a.relative).then(a.message_id.cmp(&b.message_id)));
+    trending.truncate(limit);
+    trending.iter_mut().enumerate().for_each(|(i, post)| post.rank = i + 1);
+    trending
+}
+
+// Adds a compound sentiment score (-1 to 1) for a message, e.g. from the
+// host's own model; record_chat_message does this for text messages itself
+pub fn record_sentiment(chat_id: i64, user_id: Option<i64>, date: i64, score: f64) {
+    // Subscribers run outside the chats lock
+    if let Some(alert) = record_sentiment_at(chat_id, user_id, date, score, Utc::now().timestamp()) {
+        alerts::emit(alert);
+    }
+}
+
+fn record_sentiment_at(chat_id: i64, user_id: Option<i64>, date: i64, score: f64, now: i64) -> Option<AlertEvent> {
+    if date < now - MAX_RETENTION_SECONDS || !score.is_finite() {
+        return None;
+    }
+    let score = score.clamp(-1.0, 1.0);
+    let cutoff = now - MAX_RETENTION_SECONDS;
+    
+    let mut chats = chats();
+    let stats = chats.entry(chat_key(chat_id)).or_default();
+    stats.sentiment.add(date, score);
+    stats.sentiment.prune(cutoff);
+    if let Some(user_id) = user_id {
+        stats.member_sentiment.entry(user_id).or_default().add(date, score);
+    }
+    stats.member_sentiment.retain(|_, series| {
+        series.prune(cutoff);
+        !series.buckets.is_empty()
+    });
+    
+    let rule = AppConfig::get().alerts.sentiment_drop;
+    if !rule.enabled || stats.sentiment_alerted_at.map(|at| now - at < DAY_SECONDS).unwrap_or(false) {
+        return None;
+    }
+    // Hour buckets: the last day includes the current hour
+    let day_start = now - DAY_SECONDS + BUCKET_SECONDS;
+    let until = now + BUCKET_SECONDS;
+    let last_day = stats.sentiment.total(day_start, until);
+    let baseline = stats.sentiment.total(day_start - rule.baseline_days * DAY_SECONDS, day_start);
+    if last_day.1 < rule.min_messages || baseline.1 < rule.min_messages {
+        return None;
+    }
+    let (last_day_average, baseline_average) = (mean(last_day)?, mean(baseline)?);
+    if baseline_average - last_day_average < rule.min_drop {
+        return None;
+    }
+    stats.sentiment_alerted_at = Some(now);
+    
+    let mut labels = HashMap::new();
+    labels.insert("chat_id".to_string(), chat_id.to_string());
+    if let Some(tenant_id) = tenant::current().tenant_id {
+        labels.insert("tenant_id".to_string(), tenant_id);
+    }
+    Some(AlertEvent {
+        rule: "sentiment_drop".to_string(),
+        source: "sentiment".to_string(),
+        message: format!(
+            "Sentiment in chat {} dropped sharply today: {:.2} over the last day against {:.2} over the previous {} days",
+            chat_id, last_day_average, baseline_average, rule.baseline_days
+        ),
+        count: last_day.1 as usize,
+        window_seconds: DAY_SECONDS as u64,
+        timestamp: chrono::DateTime::from_timestamp(now, 0).unwrap_or_else(Utc::now),
+        labels,
+    })
+}
+
+pub fn sentiment_trend(chat_id: i64, user_id: Option<i64>, window_seconds: i64) -> SentimentTrend {
+    sentiment_trend_at(chat_id, user_id, window_seconds, Utc::now().timestamp())
+}
+
+fn sentiment_trend_at(chat_id: i64, user_id: Option<i64>, window_seconds: i64, now: i64) -> SentimentTrend {
+    let window_seconds = window_seconds.clamp(DAY_SECONDS, MAX_RETENTION_SECONDS);
+    let baseline_days = AppConfig::get().alerts.sentiment_drop.baseline_days;
+    let chats = chats();
+    let stats = chats.get(&chat_key(chat_id));
+    let series = match user_id {
+        Some(user_id) => stats.and_then(|s| s.member_sentiment.get(&user_id)),
+        None => stats.map(|s| &s.sentiment),
+    };
+    
+    let empty = SentimentSeries::default();
+    let series = series.unwrap_or(&empty);
+    let until = now + BUCKET_SECONDS;
+    let day_start = now - DAY_SECONDS + BUCKET_SECONDS;
+    let window = series.total(now - window_seconds + BUCKET_SECONDS, until);
+    let last_day = mean(series.total(day_start, until));
+    let baseline = mean(series.total(day_start - baseline_days * DAY_SECONDS, day_start));
+    SentimentTrend {
+        chat_id,
+        user_id,
+        window_seconds,
+        average: mean(window),
+        messages: window.1,
+        last_day,
+        baseline,
+        change: last_day.zip(baseline).map(|(last_day, baseline)| last_day - baseline),
+        daily: series.daily(now - window_seconds + BUCKET_SECONDS),
+    }
+}
+
+// Authors of the chat's messages dated after since and up to until, None
+// for those without one, such as channel posts
+pub(crate) fn recent_authors(chat_id: i64, since: i64, until: i64) -> Vec<Option<i64>> {
+    chats().get(&chat_key(chat_id))
+        .map(|stats| stats.posts.values().filter(|post| post.date > since && post.date <= until).map(|post| post.user_id).collect())
+        .unwrap_or_default()
+}
+
+// Forgets everything recorded for a chat, e.g. when the bot leaves it
+pub fn clear_chat(chat_id: i64) -> bool {
+    crate::slowmode::clear(chat_id);
+    chats().remove(&chat_key(chat_id)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn sticker_message(chat_id: i64, date: i64, id: &str, set_name: &str) -> ChatMessage {
+        ChatMessage {
+            chat: ChatRef { id: chat_id },
+            date,
+            sticker: Some(Sticker {
+                file_unique_id: id.to_string(),
+                set_name: Some(set_name.to_string()),
+                emoji: Some("😂".to_string()),
+                custom_emoji_id: None,
+            }),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn test_sticker_windows() {
+        let now = 1_700_000_000;
+        let chat = -100_101;
+        for _ in 0..3 {
+            record_message_at(&sticker_message(chat, now - 60, "laugh", "memes"), now);
+        }
+        record_message_at(&sticker_message(chat, now - 60, "cry", "memes"), now);
+        record_message_at(&sticker_message(chat, now - 3 * 86_400, "old", "classics"), now);
+        record_message_at(&sticker_message(chat, now - 40 * 86_400, "ancient", "classics"), now);
+        
+        let day = sticker_report_at(chat, 86_400, 10, now);
+        assert_eq!(day.total_stickers, 4);
+        assert_eq!(day.top_stickers[0].file_unique_id, "laugh");
+        assert_eq!(day.top_stickers[0].count, 3);
+        assert_eq!(day.top_stickers[0].set_name.as_deref(), Some("memes"));
+        assert_eq!(day.top_sticker_sets.len(), 1);
+        
+        let week = sticker_report_at(chat, 7 * 86_400, 1, now);
+        assert_eq!(week.total_stickers, 5);
+        assert_eq!(week.top_stickers.len(), 1);
+        assert_eq!(week.top_sticker_sets[0].id, "memes");
+        
+        let other_tenant = tenant::TenantContext::new(Some("chats_test_bot"), None);
+        assert_eq!(tenant::with_tenant(other_tenant, || sticker_report_at(chat, 86_400, 10, now)).total_stickers, 0);
+        assert!(clear_chat(chat));
+    }
+    
+    #[test]
+    fn test_custom_emoji_entities() {
+        record_message_json(&format!(r#"{{"chat": {{"id": -100102}}, "date": {}, "text": "hi",
+            "entities": [{{"type": "custom_emoji", "offset": 0, "length": 2, "custom_emoji_id": "5368324170671202286"}},
+                         {{"type": "bold", "offset": 0, "length": 2}}]}}"#, Utc::now().timestamp())).unwrap();
+        
+        let report = sticker_report(-100_102, 3600, 5);
+        assert_eq!(report.top_custom_emoji[0].id, "5368324170671202286");
+        assert_eq!(report.total_stickers, 0);
+        assert!(record_message_json(r#"{"date": 1}"#).is_err());
+        clear_chat(-100_102);
+    }
+    
+    #[test]
+    fn test_member_retention() {
+        let now = 1_700_000_000 - 1_700_000_000 % DAY_SECONDS + 12 * 3600;
+        let chat = -100_103;
+        let join = |user: i64, at: i64| record_message_at(&ChatMessage {
+            chat: ChatRef { id: chat },
+            date: at,
+            new_chat_members: vec![UserRef { id: user }],
+            ..Default::default()
+        }, now);
+        let leave = |user: i64, at: i64| record_member_update_at(&ChatMemberUpdate {
+            chat: ChatRef { id: chat },
+            date: at,
+            old_chat_member: ChatMember { status: "member".to_string(), user: UserRef { id: user }, is_member: None },
+            new_chat_member: ChatMember { status: "left".to_string(), user: UserRef { id: user }, is_member: None },
+        }, now);
+        
+        // Cohort 40 days ago: one stays, one leaves after 3 days, one after 10
+        let old = now - 40 * DAY_SECONDS;
+        join(1, old);
+        join(2, old);
+        join(3, old);
+        leave(2, old + 3 * DAY_SECONDS);
+        leave(3, old + 10 * DAY_SECONDS);
+        // Yesterday's cohort can't have a D7 figure yet
+        join(4, now - DAY_SECONDS - 60);
+        // Left before tracking saw them join
+        leave(5, now - 3600);
+        
+        let report = member_report_at(chat, 60 * DAY_SECONDS, now);
+        assert_eq!(report.joins, 4);
+        assert_eq!(report.leaves, 3);
+        assert_eq!(report.net_growth, 1);
+        assert_eq!(report.cohorts.len(), 2);
+        
+        let first = &report.cohorts[0];
+        assert_eq!(first.joined, 3);
+        assert_eq!(first.d1, Some(1.0));
+        assert!((first.d7.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert!((first.d30.unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.cohorts[1].d1, Some(1.0));
+        assert_eq!(report.cohorts[1].d7, None);
+        assert_eq!(report.d1_retention, Some(1.0));
+        assert!((report.d7_retention.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        
+        let week = member_report_at(chat, 7 * DAY_SECONDS, now);
+        assert_eq!((week.joins, week.leaves), (1, 1));
+        // Members 1, 4 and 5 were present during the week; 5 left
+        assert!((week.churn_rate - 1.0 / 3.0).abs() < 1e-9);
+        
+        // A stale join never reopens a newer departure
+        join(2, old + DAY_SECONDS);
+        assert_eq!(member_report_at(chat, 60 * DAY_SECONDS, now).leaves, 3);
+        clear_chat(chat);
+    }
+    
+    #[test]
+    fn test_sentiment_drop_alerts_once() {
+        let now = 1_700_000_000;
+        let chat = -100_104;
+        let mut alerts = 0;
+        // A cheerful week, then a bad day
+        for day in 1..=7 {
+            for i in 0..5 {
+                alerts += record_sentiment_at(chat, Some(i), now - day * DAY_SECONDS - 3 * 3600, 0.5, now).is_some() as usize;
+            }
+        }
+        for i in 0..25 {
+            alerts += record_sentiment_at(chat, Some(i % 5), now - 60 * i, -0.3, now).is_some() as usize;
+        }
+        assert_eq!(alerts, 1);
+        
+        let trend = sentiment_trend_at(chat, None, 7 * DAY_SECONDS, now);
+        assert_eq!(trend.messages, 25 + 30);
+        assert!((trend.last_day.unwrap() + 0.3).abs() < 1e-9);
+        assert!((trend.baseline.unwrap() - 0.5).abs() < 1e-9);
+        assert!((trend.change.unwrap() + 0.8).abs() < 1e-9);
+        assert_eq!(trend.daily.len(), 7);
+        
+        let member = sentiment_trend_at(chat, Some(0), 30 * DAY_SECONDS, now);
+        assert_eq!(member.messages, 7 + 5);
+        assert_eq!(sentiment_trend_at(chat, Some(99), DAY_SECONDS, now).average, None);
+        clear_chat(chat);
+    }
+    
+    #[test]
+    fn test_leaderboard() {
+        let now = 1_700_000_000 - 1_700_000_000 % DAY_SECONDS + 12 * 3600;
+        let chat = -100_105;
+        let post = |message_id: i64, user: i64, at: i64| record_message_at(&ChatMessage {
+            message_id,
+            chat: ChatRef { id: chat },
+            from: Some(UserRef { id: user }),
+            date: at,
+            text: Some("hi".to_string()),
+            ..Default::default()
+        }, now);
+        
+        // User 1 posts daily for the last 5 days, user 2 three times today
+        // and on two days last week, user 3 as often as user 2 on one day
+        for day in 0..5 {
+            post(100 + day, 1, now - day * DAY_SECONDS);
+        }
+        for i in 0..3 {
+            post(200 + i, 2, now - 60 * (i + 1));
+        }
+        post(210, 2, now - 9 * DAY_SECONDS);
+        post(211, 2, now - 10 * DAY_SECONDS);
+        for i in 0..5 {
+            post(300 + i, 3, now - 2 * DAY_SECONDS - 60 * i);
+        }
+        
+        let reactions = |message_id: i64, counts: &[u64]| record_reaction_count_at(&ReactionCountUpdate {
+            chat: ChatRef { id: chat },
+            message_id,
+            date: now,
+            reactions: counts.iter().map(|&total_count| ReactionCount { reaction_type: serde_json::json!({"type": "emoji", "emoji": "👍"}), total_count }).collect(),
+        }, now);
+        reactions(200, &[4, 1]);
+        reactions(101, &[5]);
+        // Replaced by the later total
+        reactions(300, &[9]);
+        reactions(300, &[2]);
+        
+        let week = leaderboard_at(chat, &LeaderboardOptions::default(), now);
+        let active: Vec<(i64, u64)> = week.most_active.iter().map(|u| (u.user_id, u.messages)).collect();
+        // Users 1 and 3 tie on messages; user 1 was active on more days
+        assert_eq!(active, vec![(1, 5), (3, 5), (2, 3)]);
+        assert_eq!(week.most_active[2].rank, 3);
+        
+        // 101 and 200 tie on 5 reactions; 101 was posted first
+        let reacted: Vec<(i64, u64)> = week.most_reacted.iter().map(|m| (m.message_id, m.reactions)).collect();
+        assert_eq!(reacted, vec![(101, 5), (200, 5), (300, 2)]);
+        assert_eq!(week.most_reacted[0].user_id, Some(1));
+        
+        assert_eq!(week.longest_streaks[0].user_id, 1);
+        assert_eq!(week.longest_streaks[0].days, 5);
+        assert!(week.longest_streaks[0].ongoing);
+        // One-day streaks: user 2's today beats user 3's two days ago
+        assert_eq!(week.longest_streaks[1].user_id, 2);
+        assert!(!week.longest_streaks[2].ongoing);
+        
+        // Last week's board, and a limit
+        let options = LeaderboardOptions::from_json(r#"{"limit": 1}"#).unwrap();
+        let earlier = leaderboard_at(chat, &LeaderboardOptions { until: Some(now - 7 * DAY_SECONDS), ..options }, now);
+        assert_eq!(earlier.most_active.len(), 1);
+        assert_eq!((earlier.most_active[0].user_id, earlier.most_active[0].messages), (2, 2));
+        assert_eq!(earlier.longest_streaks[0].days, 2);
+        assert!(LeaderboardOptions::from_json(r#"{"top": 3}"#).is_err());
+        clear_chat(chat);
+    }
+    
+    #[test]
+    fn test_engagement_and_trending() {
+        let now = 1_700_000_000;
+        let chat = -100_106;
+        let message = |message_id: i64, user: i64, at: i64, reply_to: Option<i64>| record_message_at(&ChatMessage {
+            message_id,
+            chat: ChatRef { id: chat },
+            from: Some(UserRef { id: user }),
+            date: at,
+            text: Some("hi".to_string()),
+            reply_to_message: reply_to.map(|message_id| MessageRef { message_id }),
+            ..Default::default()
+        }, now);
+        let reactions = |message_id: i64, total_count: u64| record_reaction_count_at(&ReactionCountUpdate {
+            chat: ChatRef { id: chat },
+            message_id,
+            date: now,
+            reactions: vec![ReactionCount { reaction_type: serde_json::json!({"type": "emoji", "emoji": "🔥"}), total_count }],
+        }, now);
+        
+        // A quiet week of one-reaction posts, then a post everyone answers
+        for day in 1..=6 {
+            message(day, 1, now - day * DAY_SECONDS, None);
+            reactions(day, 1);
+        }
+        message(100, 2, now - 3600, None);
+        for i in 0..3 {
+            message(101 + i, 3 + i, now - 600, Some(100));
+        }
+        reactions(100, 4);
+        record_post_metrics_at(chat, 100, 500, 2, now);
+        // Replies to messages the stats never saw count nowhere
+        message(200, 3, now - 60, Some(99));
+        
+        // 500 views x 0.02 + 2 forwards x 3 + 3 replies x 2 + 4 reactions
+        let board = leaderboard_at(chat, &LeaderboardOptions::default(), now);
+        let top = &board.most_engaged[0];
+        assert_eq!((top.message_id, top.views, top.forwards, top.replies, top.reactions), (100, 500, 2, 3, 4));
+        assert!((top.score - 26.0).abs() < 1e-9);
+        // Five members have posted
+        assert!((top.index - 26.0 * 100.0 / 5.0).abs() < 1e-9);
+        assert_eq!(board.most_engaged.len(), 7);
+        
+        // Against an average of (26 + 6) / 11 posts
+        let trending = trending_posts_at(chat, 5, now);
+        assert_eq!(trending.len(), 1);
+        assert_eq!((trending[0].rank, trending[0].message_id), (1, 100));
+        assert!((trending[0].relative - 26.0 / (32.0 / 11.0)).abs() < 1e-9);
+        
+        // A channel of 10,000 subscribers makes the same post a small one
+        record_member_count(chat, 10_000);
+        assert!((leaderboard_at(chat, &LeaderboardOptions::default(), now).most_engaged[0].index - 0.26).abs() < 1e-9);
+        clear_chat(chat);
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Serialize, Deserialize};
+use crate::similarity::levenshtein_distance;
+
+// "Did you mean /start?" for mistyped bot commands. The input and the
+// registered commands are compared without the leading slash, a trailing
+// @BotName and any arguments, case-insensitively; confidence is one minus
+// the edit distance over the longer name, so a single typo in a short
+// command still scores well above a wrong command.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandMatchOptions {
+    // Matches below this are left out, 0-1
+    pub min_confidence: f64,
+    pub limit: usize,
+}
+
+impl Default for CommandMatchOptions {
+    fn default() -> Self {
+        Self { min_confidence: 0.6, limit: 3 }
+    }
+}
+
+impl CommandMatchOptions {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid command match options: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandMatch {
+    // As registered
+    pub command: String,
+    pub distance: usize,
+    pub confidence: f64,
+}
+
+// "/Start@MyBot now" -> "start"
+fn command_name(text: &str) -> String {
+    let token = text.split_whitespace().next().unwrap_or("");
+    let token = token.strip_prefix('/').unwrap_or(token);
+    token.split('@').next().unwrap_or("").to_lowercase()
+}
+
+// Best first: fewest edits, then the order of commands
+pub fn match_command<S: AsRef<str>>(input: &str, commands: &[S], options: &CommandMatchOptions) -> Vec<CommandMatch> {
+    let typed: Vec<char> = command_name(input).chars().collect();
+    if typed.is_empty() {
+        return Vec::new();
+    }
+    let mut matches: Vec<CommandMatch> = commands.iter()
+        .filter_map(|command| {
+            let name: Vec<char> = command_name(command.as_ref()).chars().collect();
+            if name.is_empty() {
+                return None;
+            }
+            let distance = levenshtein_distance(&typed, &name);
+            let confidence = 1.0 - distance as f64 / typed.len().max(name.len()) as f64;
+            (confidence >= options.min_confidence).then(|| CommandMatch { command: command.as_ref().to_string(), distance, confidence })
+        })
+        .collect();
+    // Stable, so equal distances keep the registration order
+    matches.sort_by(|a, b| a.distance.cmp(&b.distance).then(b.confidence.total_cmp(&a.confidence)));
+    matches.truncate(options.limit);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_did_you_mean() {
+        let commands = ["/start", "/stop", "/settings", "/help"];
+        let options = CommandMatchOptions::default();
+        let matches = match_command("/staft", &commands, &options);
+        assert_eq!(matches[0], CommandMatch { command: "/start".to_string(), distance: 1, confidence: 0.8 });
+        assert_eq!(matches.len(), 1);
+        
+        assert_eq!(match_command("/HELP@MyBot please", &commands, &options)[0].distance, 0);
+        assert_eq!(match_command("/stp", &commands, &options)[0].command, "/stop");
+        assert!(match_command("/weather", &commands, &options).is_empty());
+        assert!(match_command("/", &commands, &options).is_empty());
+        
+        let loose = CommandMatchOptions { min_confidence: 0.0, limit: 2 };
+        let names: Vec<String> = match_command("/sto", &commands, &loose).into_iter().map(|m| m.command).collect();
+        assert_eq!(names, vec!["/stop", "/start"]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,166 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+use crate::deadline;
+
+// Admission control for the analysis exports. Host threads calling in faster
+// than the library can work would otherwise all pile into the rayon pool and
+// thrash it. Past performance.max_concurrent_requests calls in flight, a
+// caller waits in a short bounded queue or is turned away with an
+// "overloaded" response it can retry later.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConcurrencyStats {
+    // performance.max_concurrent_requests; 0 when the limiter is off
+    pub limit: usize,
+    pub active: usize,
+    // Callers waiting for a slot
+    pub queued: usize,
+    pub peak_active: usize,
+    pub peak_queued: usize,
+    pub admitted: u64,
+    pub rejected: u64,
+}
+
+// Most calls in flight and how long a caller may wait for one to finish
+struct Policy {
+    limit: usize,
+    max_queued: usize,
+    wait: Duration,
+}
+
+struct Limiter {
+    state: Mutex<ConcurrencyStats>,
+    released: Condvar,
+}
+
+lazy_static! {
+    static ref LIMITER: Limiter = Limiter::new();
+}
+
+// Held for the length of an admitted call
+struct Permit<'a> {
+    limiter: Option<&'a Limiter>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter {
+            let mut state = limiter.state();
+            state.active = state.active.saturating_sub(1);
+            drop(state);
+            limiter.released.notify_one();
+        }
+    }
+}
+
+impl Limiter {
+    fn new() -> Self {
+        Self { state: Mutex::new(ConcurrencyStats::default()), released: Condvar::new() }
+    }
+    
+    fn state(&self) -> MutexGuard<'_, ConcurrencyStats> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    fn admit(&self, state: &mut ConcurrencyStats) -> Permit<'_> {
+        state.active += 1;
+        state.admitted += 1;
+        state.peak_active = state.peak_active.max(state.active);
+        Permit { limiter: Some(self) }
+    }
+    
+    // On rejection, the counters as the caller found them
+    fn acquire(&self, policy: &Policy) -> Result<Permit<'_>, ConcurrencyStats> {
+        if policy.limit == 0 {
+            return Ok(Permit { limiter: None });
+        }
+        let mut state = self.state();
+        if state.active < policy.limit {
+            return Ok(self.admit(&mut state));
+        }
+        
+        let reject = |state: &mut ConcurrencyStats| {
+            state.rejected += 1;
+            ConcurrencyStats { limit: policy.limit, ..state.clone() }
+        };
+        if state.queued >= policy.max_queued || policy.wait.is_zero() {
+            return Err(reject(&mut state));
+        }
+        state.queued += 1;
+        state.peak_queued = state.peak_queued.max(state.queued);
+        let until = Instant::now() + policy.wait;
+        loop {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            state = self.released.wait_timeout(state, until - now).unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+            if state.active < policy.limit {
+                state.queued -= 1;
+                return Ok(self.admit(&mut state));
+            }
+        }
+        state.queued -= 1;
+        Err(reject(&mut state))
+    }
+}
+
+// Waiting ends at the queue timeout or the call's deadline, whichever comes
+// first
+fn policy() -> Policy {
+    let config = AppConfig::get_performance_config();
+    let mut wait = Duration::from_millis(config.queue_timeout_ms);
+    if let Some(deadline) = deadline::current() {
+        wait = wait.min(Duration::from_millis(deadline.remaining_millis()));
+    }
+    Policy { limit: config.max_concurrent_requests, max_queued: config.max_queued_requests, wait }
+}
+
+pub fn limited<T, F: FnOnce() -> T>(body: F) -> Result<T, ConcurrencyStats> {
+    let _permit = LIMITER.acquire(&policy())?;
+    Ok(body())
+}
+
+pub fn stats() -> ConcurrencyStats {
+    let limit = AppConfig::get_performance_config().max_concurrent_requests;
+    ConcurrencyStats { limit, ..LIMITER.state().clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    
+    #[test]
+    fn test_limit_queue_and_rejection() {
+        let limiter = Arc::new(Limiter::new());
+        let policy = |max_queued, wait_ms| Policy { limit: 1, max_queued, wait: Duration::from_millis(wait_ms) };
+        
+        let permit = limiter.acquire(&policy(0, 1000)).unwrap();
+        // Full, and no queue to wait in
+        let rejected = limiter.acquire(&policy(0, 1000)).err().unwrap();
+        assert_eq!((rejected.limit, rejected.active, rejected.queued, rejected.rejected), (1, 1, 0, 1));
+        // Queued, but the slot is not freed in time
+        assert!(limiter.acquire(&policy(1, 20)).is_err());
+        
+        // Queued until the holder finishes
+        let waiter = {
+            let limiter = Arc::clone(&limiter);
+            thread::spawn(move || limiter.acquire(&policy(1, 5000)).map(|_| ()).is_ok())
+        };
+        while limiter.state().queued == 0 {
+            thread::yield_now();
+        }
+        drop(permit);
+        assert!(waiter.join().unwrap());
+        
+        let state = limiter.state().clone();
+        assert_eq!((state.active, state.queued, state.admitted, state.rejected), (0, 0, 2, 2));
+        assert_eq!((state.peak_active, state.peak_queued), (1, 1));
+        assert!(Limiter::new().acquire(&Policy { limit: 0, max_queued: 0, wait: Duration::ZERO }).is_ok());
+    }
+}
\ No newline at end of file
@@ -1,14 +1,21 @@
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use crate::paths;
+use crate::secrets::{self, ApiKeyEntry, Secret};
+use crate::security::{self, SecuritySeverity};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisConfig {
     pub max_text_length: usize,
     pub max_data_points: usize,
+    // Compound scores above it are positive, below minus it negative
     pub sentiment_threshold: f64,
     pub language_confidence_threshold: f64,
     pub plagiarism_threshold: f64,
@@ -21,7 +28,88 @@ pub struct AnalysisConfig {
     pub security_enabled: bool,
     pub allowed_languages: Vec<String>,
     pub custom_stop_words: Vec<String>,
-    pub api_keys: HashMap<String, String>,
+    pub api_keys: HashMap<String, ApiKeyEntry>,
+    // FFI response schema: 1 is the legacy compact JSON, 2 the full result
+    #[serde(default = "default_response_version")]
+    pub response_version: u32,
+    // Extra entity kinds, e.g. order numbers, tried before the built-in ones
+    #[serde(default)]
+    pub entity_patterns: Vec<EntityPattern>,
+    // Built-in entity patterns to leave out: "date", "time", "money",
+    // "percent", "person", "email", "url" or "phone"
+    #[serde(default)]
+    pub disabled_entity_patterns: Vec<String>,
+    // How the pipeline ranks keywords; unset uses TF-IDF while a keyword
+    // corpus is set and frequency otherwise
+    #[serde(default)]
+    pub keyword_algorithm: Option<KeywordAlgorithm>,
+    // Replaces the band sentiment_threshold sets, e.g. to call mild
+    // complaints negative sooner than mild praise positive
+    #[serde(default)]
+    pub sentiment_neutral_band: Option<NeutralBand>,
+    // By language code ("fa", "en"), over both of the above; lexicons cover
+    // some languages less well, which keeps their scores nearer 0
+    #[serde(default)]
+    pub language_sentiment_bands: HashMap<String, NeutralBand>,
+    // Chat abbreviations and their expansions ("imo": "in my opinion"), over
+    // the built-in ones; an empty expansion drops a built-in
+    #[serde(default)]
+    pub abbreviations: HashMap<String, String>,
+}
+
+// Compound scores from negative through positive are labeled neutral
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NeutralBand {
+    pub negative: f64,
+    pub positive: f64,
+}
+
+impl NeutralBand {
+    fn is_valid(&self) -> bool {
+        (-1.0..=0.0).contains(&self.negative) && (0.0..=1.0).contains(&self.positive)
+    }
+}
+
+fn default_response_version() -> u32 {
+    1
+}
+
+// A regex recognizing one kind of entity; `group` picks the capture group
+// holding it, 0 for the whole match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityPattern {
+    pub name: String,
+    pub entity_type: String,
+    pub pattern: String,
+    #[serde(default = "default_entity_confidence")]
+    pub confidence: f64,
+    #[serde(default)]
+    pub group: usize,
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+fn default_entity_confidence() -> f64 {
+    0.9
+}
+
+// Written in config files as "frequency", "rake", "yake" or "tfidf"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeywordAlgorithm {
+    // Most frequent words
+    #[serde(alias = "frequency")]
+    FREQUENCY,
+    // Phrases between stop words, scored by word co-occurrence
+    #[serde(alias = "rake")]
+    RAKE,
+    // Words and phrases scored by position, casing and spread over sentences
+    #[serde(alias = "yake")]
+    YAKE,
+    // Against the keyword corpus; frequency while none is set
+    #[serde(alias = "tfidf")]
+    TFIDF,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,12 +124,411 @@ pub struct SecurityConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceConfig {
+    // Analysis calls in flight at once across FFI entry points; 0 for no limit
     pub max_concurrent_requests: usize,
     pub worker_threads: usize,
     pub memory_limit_mb: usize,
     pub timeout_seconds: u64,
     pub enable_profiling: bool,
     pub cache_size_mb: usize,
+    // Background maintenance started by init_library
+    #[serde(default = "default_cache_cleanup_interval")]
+    pub cache_cleanup_interval_seconds: u64,
+    #[serde(default = "default_metrics_flush_interval")]
+    pub metrics_flush_interval_seconds: u64,
+    // Callers that wait for a slot once the limit is reached; 0 turns them
+    // away at once
+    #[serde(default)]
+    pub max_queued_requests: usize,
+    #[serde(default = "default_queue_timeout")]
+    pub queue_timeout_ms: u64,
+}
+
+fn default_cache_cleanup_interval() -> u64 {
+    300
+}
+
+fn default_metrics_flush_interval() -> u64 {
+    60
+}
+
+fn default_queue_timeout() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    // Global level; falls back to analysis.log_level when unset
+    pub level: Option<String>,
+    // Per-module overrides keyed by module path prefix, e.g. "analysis"
+    pub module_levels: HashMap<String, String>,
+    // Enabled sinks ("memory", "console", "syslog", "journald", "otlp");
+    // empty selects the defaults
+    pub sinks: Vec<String>,
+    // Fraction of entries kept per level, e.g. {"debug": 0.1}
+    pub sampling_rates: HashMap<String, f64>,
+    // Syslog destination: a unix socket path or "host:port" for UDP;
+    // defaults to /dev/log
+    pub syslog_address: Option<String>,
+    // APP-NAME / SYSLOG_IDENTIFIER reported to syslog and journald
+    pub app_name: Option<String>,
+    // Extra regexes redacted from log messages and metadata
+    pub scrub_patterns: Vec<String>,
+    // Turns off the built-in token, email and phone number redaction
+    pub disable_builtin_scrubbing: bool,
+    // Target of the "file" sink; relative paths live in the logs directory
+    pub file_path: Option<String>,
+}
+
+// Fires when more than `threshold` matching log entries arrive within
+// `window_seconds`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogAlertRule {
+    pub name: String,
+    // Minimum level counted by the rule
+    pub level: String,
+    #[serde(default)]
+    pub module: Option<String>,
+    #[serde(default)]
+    pub message_contains: Option<String>,
+    pub threshold: usize,
+    pub window_seconds: u64,
+}
+
+// Alert when a chat's sentiment over the last day falls this far below its
+// average over the preceding baseline_days (compound scores, -1 to 1)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SentimentDropRule {
+    pub enabled: bool,
+    pub min_drop: f64,
+    // Scored messages needed on each side of the comparison
+    pub min_messages: u64,
+    pub baseline_days: i64,
+}
+
+impl Default for SentimentDropRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_drop: 0.3,
+            min_messages: 20,
+            baseline_days: 7,
+        }
+    }
+}
+
+// Lifecycle of anomaly alerts from analyze_metric and the realtime stream:
+// an anomaly is announced once, reminded about while it lasts and reported
+// again when it resolves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyAlertRule {
+    pub enabled: bool,
+    // Gap between "still active" reminders; 0 never reminds
+    pub reminder_seconds: i64,
+    // Normal observations for this long after the last anomalous one resolve it
+    pub resolve_after_seconds: i64,
+    // A relapse this soon after resolving continues quietly instead of
+    // triggering again
+    pub cooldown_seconds: i64,
+    // Realtime numeric streams: deviation from the running mean, in
+    // standard deviations, and observations needed before judging
+    pub stream_z_threshold: f64,
+    pub stream_warmup: u64,
+}
+
+impl Default for AnomalyAlertRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reminder_seconds: 3600,
+            resolve_after_seconds: 300,
+            cooldown_seconds: 900,
+            stream_z_threshold: 3.0,
+            stream_warmup: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    pub log_rules: Vec<LogAlertRule>,
+    #[serde(default)]
+    pub sentiment_drop: SentimentDropRule,
+    #[serde(default)]
+    pub anomalies: AnomalyAlertRule,
+}
+
+// How a post's interactions add up to its engagement score. A forward or
+// reply takes more effort than a reaction, and a view almost none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngagementConfig {
+    pub view_weight: f64,
+    pub forward_weight: f64,
+    pub reply_weight: f64,
+    pub reaction_weight: f64,
+    // A recent post trends once its score reaches this multiple of the
+    // chat's average
+    pub trending_factor: f64,
+    // Interactions of any kind a post needs before it can trend
+    pub trending_min_interactions: u64,
+}
+
+impl Default for EngagementConfig {
+    fn default() -> Self {
+        Self {
+            view_weight: 0.02,
+            forward_weight: 3.0,
+            reply_weight: 2.0,
+            reaction_weight: 1.0,
+            trending_factor: 3.0,
+            trending_min_interactions: 5,
+        }
+    }
+}
+
+// Slowmode and cooldown recommendations: the chat rate to stay under, the
+// per-member rate that earns a cooldown, and the margin a rate must fall
+// below before a recommendation is relaxed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlowmodeConfig {
+    pub target_messages_per_minute: f64,
+    pub user_messages_per_minute: f64,
+    // Rates are measured over this many recent seconds
+    pub window_seconds: i64,
+    // A delay is lowered only once the lower one keeps the rate this share
+    // below target, e.g. 0.25 for 25%
+    pub hysteresis: f64,
+    // And only after the current delay has held this long
+    pub min_hold_seconds: i64,
+}
+
+impl Default for SlowmodeConfig {
+    fn default() -> Self {
+        Self {
+            target_messages_per_minute: 20.0,
+            user_messages_per_minute: 4.0,
+            window_seconds: 300,
+            hysteresis: 0.25,
+            min_hold_seconds: 600,
+        }
+    }
+}
+
+// How reply variants are chosen; see bandit.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BanditConfig {
+    pub strategy: BanditStrategy,
+    // Share of epsilon-greedy selections that explore a random variant
+    pub epsilon: f64,
+}
+
+impl Default for BanditConfig {
+    fn default() -> Self {
+        Self { strategy: BanditStrategy::Thompson, epsilon: 0.1 }
+    }
+}
+
+// Written in config files as "epsilon_greedy" or "thompson"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BanditStrategy {
+    // The best variant so far, or a random one epsilon of the time
+    #[serde(rename = "EPSILON_GREEDY", alias = "epsilon_greedy")]
+    EpsilonGreedy,
+    // A draw from each variant's Beta posterior, highest wins
+    #[serde(rename = "THOMPSON", alias = "thompson")]
+    Thompson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    // Collector base URL, e.g. "http://localhost:4318"; /v1/logs and
+    // /v1/metrics are appended
+    pub otlp_endpoint: Option<String>,
+    // Extra request headers, e.g. authentication tokens
+    pub otlp_headers: HashMap<String, Secret>,
+    pub service_name: String,
+    pub export_interval_seconds: u64,
+    pub export_metrics: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            otlp_headers: HashMap::new(),
+            service_name: "aiogram_rust".to_string(),
+            export_interval_seconds: 10,
+            export_metrics: true,
+        }
+    }
+}
+
+// Outbound webhook receiving alerts; see webhooks.rs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub name: String,
+    pub url: String,
+    // HMAC-SHA256 key for the X-Aiogram-Signature header; unsigned when absent
+    #[serde(default)]
+    pub secret: Option<Secret>,
+    // Alert sources delivered here ("log", "security", ...); empty means all
+    #[serde(default)]
+    pub sources: Vec<String>,
+    // Lowest priority delivered: "low", "normal", "high" or "critical"
+    #[serde(default = "default_webhook_min_priority")]
+    pub min_priority: String,
+    #[serde(default = "default_webhook_max_per_minute")]
+    pub max_per_minute: u32,
+    #[serde(default)]
+    pub headers: HashMap<String, Secret>,
+}
+
+fn default_webhook_min_priority() -> String {
+    "low".to_string()
+}
+
+fn default_webhook_max_per_minute() -> u32 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    // Deliveries are retried with exponential backoff up to max_attempts
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // Deliveries beyond this are dropped, lowest priority first
+    pub queue_capacity: usize,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 60_000,
+            queue_capacity: 1000,
+        }
+    }
+}
+
+// API keys stored encrypted at rest. Each key is a base64 crypto::seal
+// payload opened with the master key read from `master_key_env`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    pub master_key_env: String,
+    pub api_keys: HashMap<String, ApiKeyEntry>,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            master_key_env: "AIOGRAM_SECRETS_KEY".to_string(),
+            api_keys: HashMap::new(),
+        }
+    }
+}
+
+// Shadow analysis: a share of requests is also run through alternative
+// implementations and compared with the primary result; see shadow.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+    // Share of analyzed texts compared, 0 to turn shadowing off
+    pub sample_rate: f64,
+    // Keyword algorithm to compare with the configured one
+    pub keyword_algorithm: Option<KeywordAlgorithm>,
+    // Sentiment scores further apart than this diverge even with the same
+    // label
+    pub score_tolerance: f64,
+    // Keyword lists sharing less than this share of their keywords diverge
+    pub keyword_overlap: f64,
+    // Divergences kept as examples, newest first
+    pub max_examples: usize,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.0,
+            keyword_algorithm: None,
+            score_tolerance: 0.1,
+            keyword_overlap: 0.5,
+            max_examples: 20,
+        }
+    }
+}
+
+// Compute cost charged to tenants per operation, and the quota on it; see
+// costs.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostsConfig {
+    // Per call of an operation without an entry in operation_costs
+    pub base_cost: f64,
+    // By operation: "analyze_text", "analyze_data" or "process_realtime"
+    pub operation_costs: HashMap<String, f64>,
+    pub cost_per_kilobyte: f64,
+    // Added for each text pipeline stage that ran, by stage name
+    pub stage_costs: HashMap<String, f64>,
+    // Cost a tenant, or a user within it, may spend per window; unset for
+    // no quota
+    pub quota: Option<f64>,
+    pub quota_window_seconds: i64,
+}
+
+impl Default for CostsConfig {
+    fn default() -> Self {
+        let stage_costs = [
+            ("language_detection", 0.2),
+            ("sentiment", 0.5),
+            ("keywords", 0.5),
+            ("spam", 0.3),
+            ("ner", 1.0),
+            ("summary", 1.0),
+            ("readability", 0.3),
+            ("topic_modeling", 1.5),
+            ("plagiarism", 1.0),
+            ("plugins", 0.5),
+        ];
+        Self {
+            base_cost: 1.0,
+            operation_costs: HashMap::new(),
+            cost_per_kilobyte: 0.1,
+            stage_costs: stage_costs.iter().map(|&(stage, cost)| (stage.to_string(), cost)).collect(),
+            quota: None,
+            quota_window_seconds: 3600,
+        }
+    }
+}
+
+// Cache contents kept across restarts; see cache.rs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    // Saved by shutdown_library and restored by init_library; relative paths
+    // resolve under the data directory. Unset keeps the caches in memory only.
+    pub snapshot_path: Option<String>,
+    // Seals the snapshot; needs the crypto feature
+    pub snapshot_key: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathsConfig {
+    // Replaces the platform config/data/log directories with
+    // <base_dir>/{config,data,logs}
+    pub base_dir: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,12 +536,267 @@ pub struct AppConfig {
     pub analysis: AnalysisConfig,
     pub security: SecurityConfig,
     pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub paths: PathsConfig,
+    #[serde(default)]
+    pub engagement: EngagementConfig,
+    #[serde(default)]
+    pub slowmode: SlowmodeConfig,
+    #[serde(default)]
+    pub bandit: BanditConfig,
+    #[serde(default)]
+    pub shadow: ShadowConfig,
+    #[serde(default)]
+    pub costs: CostsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
     pub environment: String,
     pub version: String,
 }
 
+// Signed configuration bundle. The payload is the base64-encoded AppConfig
+// JSON and the signature covers "<version>.<payload>", so neither the config
+// nor its version can be altered without invalidating the bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: u64,
+    pub payload: String,
+    pub signature: String,
+}
+
+impl ConfigBundle {
+    fn signed_message(&self) -> Vec<u8> {
+        format!("{}.{}", self.version, self.payload).into_bytes()
+    }
+    
+    pub fn verify(&self, public_key: &VerifyingKey) -> Result<AppConfig, String> {
+        let signature_bytes = general_purpose::STANDARD.decode(&self.signature)
+            .map_err(|e| format!("Invalid signature encoding: {}", e))?;
+        let signature = Signature::from_slice(&signature_bytes)
+            .map_err(|e| format!("Invalid signature: {}", e))?;
+        
+        public_key.verify(&self.signed_message(), &signature)
+            .map_err(|_| "Config bundle signature verification failed".to_string())?;
+        
+        let payload = general_purpose::STANDARD.decode(&self.payload)
+            .map_err(|e| format!("Invalid payload encoding: {}", e))?;
+        serde_json::from_slice(&payload).map_err(|e| format!("Invalid config payload: {}", e))
+    }
+}
+
+// Layer an effective config value was last set by
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConfigSource {
+    DEFAULT,
+    FILE,
+    ENV,
+    RUNTIME,
+}
+
+// Environment variables layered over the file config, mapped to config paths
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("LOG_LEVEL", "analysis.log_level"),
+    ("CACHE_ENABLED", "analysis.cache_enabled"),
+    ("CACHE_TTL", "analysis.cache_ttl_seconds"),
+    ("RATE_LIMIT_ENABLED", "security.rate_limit_enabled"),
+    ("MAX_REQUESTS_PER_MINUTE", "analysis.rate_limit_requests_per_minute"),
+];
+
 lazy_static! {
     static ref CONFIG: RwLock<AppConfig> = RwLock::new(AppConfig::default());
+    static ref PINNED_PUBLIC_KEY: RwLock<Option<VerifyingKey>> = RwLock::new(None);
+    // Paths without an entry still hold their default value
+    static ref PROVENANCE: RwLock<HashMap<String, ConfigSource>> = RwLock::new(HashMap::new());
+}
+
+// Version of the last applied bundle, used to reject rollbacks
+static APPLIED_BUNDLE_VERSION: AtomicU64 = AtomicU64::new(0);
+
+// Pins the Ed25519 key that config bundles must be signed with. The key can
+// only be pinned once per process.
+pub fn pin_public_key(public_key_b64: &str) -> Result<(), String> {
+    let key_bytes = general_purpose::STANDARD.decode(public_key_b64)
+        .map_err(|e| format!("Invalid public key encoding: {}", e))?;
+    let key_bytes: [u8; 32] = key_bytes.try_into()
+        .map_err(|_| "Public key must be 32 bytes".to_string())?;
+    let public_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    
+    let mut pinned = PINNED_PUBLIC_KEY.write().unwrap();
+    match *pinned {
+        Some(existing) if existing == public_key => Ok(()),
+        Some(_) => Err("A different config public key is already pinned".to_string()),
+        None => {
+            *pinned = Some(public_key);
+            Ok(())
+        }
+    }
+}
+
+fn pinned_public_key() -> Option<VerifyingKey> {
+    *PINNED_PUBLIC_KEY.read().unwrap()
+}
+
+fn merge_values(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(target_map), serde_json::Value::Object(patch_map)) => {
+            for (key, patch_value) in patch_map {
+                match target_map.get_mut(key) {
+                    Some(target_value) => merge_values(target_value, patch_value),
+                    None => {
+                        target_map.insert(key.clone(), patch_value.clone());
+                    }
+                }
+            }
+        },
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+// Flattens a JSON document into dotted leaf paths such as "analysis.log_level"
+fn leaf_paths(value: &serde_json::Value, prefix: &str, out: &mut Vec<(String, serde_json::Value)>) {
+    match value {
+        serde_json::Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                leaf_paths(child, &path, out);
+            }
+        },
+        _ => out.push((prefix.to_string(), value.clone())),
+    }
+}
+
+fn record_sources(value: &serde_json::Value, source: ConfigSource) {
+    let mut leaves = Vec::new();
+    leaf_paths(value, "", &mut leaves);
+    
+    let mut provenance = PROVENANCE.write().unwrap();
+    for (path, _) in leaves {
+        provenance.insert(path, source);
+    }
+}
+
+// Merges a partial config document into the effective config and records the
+// touched paths as coming from the given source
+fn apply_layer(patch: &serde_json::Value, source: ConfigSource) -> Result<(), String> {
+    if pinned_public_key().is_some() {
+        return Err(reject_bundle("Config changes outside signed bundles are rejected while a public key is pinned".to_string()).to_string());
+    }
+    
+    {
+        let mut config = CONFIG.write().unwrap();
+        let mut merged = secrets::with_secrets_exposed(|| serde_json::to_value(&*config)).map_err(|e| e.to_string())?;
+        merge_values(&mut merged, patch);
+        
+        let updated: AppConfig = serde_json::from_value(merged)
+            .map_err(|e| format!("Invalid config patch: {}", e))?;
+        updated.validate_config().map_err(|errors| errors.join(", "))?;
+        *config = updated;
+    }
+    
+    record_sources(patch, source);
+    config_changed();
+    Ok(())
+}
+
+pub fn apply_env_overrides() -> Result<(), String> {
+    let current = serde_json::to_value(AppConfig::get()).map_err(|e| e.to_string())?;
+    
+    for (var, path) in ENV_OVERRIDES {
+        let raw = match std::env::var(var) {
+            Ok(raw) => raw,
+            Err(_) => continue,
+        };
+        
+        // Strings are taken verbatim; numbers and booleans are parsed as JSON
+        let pointer = format!("/{}", path.replace('.', "/"));
+        let value = match current.pointer(&pointer) {
+            Some(serde_json::Value::String(_)) => serde_json::Value::String(raw),
+            _ => serde_json::from_str(&raw).map_err(|e| format!("Invalid value for {}: {}", var, e))?,
+        };
+        
+        let patch = path.rsplit('.').fold(value, |inner, key| {
+            let mut map = serde_json::Map::new();
+            map.insert(key.to_string(), inner);
+            serde_json::Value::Object(map)
+        });
+        apply_layer(&patch, ConfigSource::ENV)?;
+    }
+    
+    Ok(())
+}
+
+pub fn apply_runtime_patch(patch_json: &str) -> Result<(), String> {
+    let patch: serde_json::Value = serde_json::from_str(patch_json)
+        .map_err(|e| format!("Invalid config patch: {}", e))?;
+    apply_layer(&patch, ConfigSource::RUNTIME)
+}
+
+// Every effective config value together with the layer it came from
+// Secret values serialize redacted unless explicitly exposed
+pub fn dump_effective_config(redact_secrets: bool) -> serde_json::Value {
+    let config = AppConfig::get();
+    let config = if redact_secrets {
+        serde_json::to_value(&config)
+    } else {
+        secrets::with_secrets_exposed(|| serde_json::to_value(&config))
+    }.unwrap_or(serde_json::Value::Null);
+    let mut leaves = Vec::new();
+    leaf_paths(&config, "", &mut leaves);
+    
+    let provenance = PROVENANCE.read().unwrap();
+    let entries: BTreeMap<String, serde_json::Value> = leaves.into_iter()
+        .map(|(path, value)| {
+            let source = provenance.get(&path).copied().unwrap_or(ConfigSource::DEFAULT);
+            (path, serde_json::json!({ "value": value, "source": source }))
+        })
+        .collect();
+    
+    serde_json::json!(entries)
+}
+
+// Hot reload hook for components that cache config values
+fn config_changed() {
+    crate::logging::reload_config();
+    crate::alerts::reload_config();
+    crate::costs::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::stopwords::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::entity_patterns::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::analysis::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::sentiment::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::abbreviations::reload_config();
+    #[cfg(feature = "nlp")]
+    crate::shadow::reload_config();
+    #[cfg(feature = "realtime")]
+    crate::pool::reload_config();
+    #[cfg(feature = "webhooks")]
+    crate::webhooks::reload_config();
+}
+
+fn reject_bundle(reason: String) -> Box<dyn std::error::Error> {
+    security::record_security_event(
+        "config_bundle_rejected",
+        None,
+        None,
+        reason.clone(),
+        SecuritySeverity::CRITICAL,
+    );
+    reason.into()
 }
 
 impl Default for AppConfig {
@@ -81,6 +823,13 @@ impl Default for AppConfig {
                 ],
                 custom_stop_words: vec![],
                 api_keys: HashMap::new(),
+                response_version: default_response_version(),
+                entity_patterns: vec![],
+                disabled_entity_patterns: vec![],
+                keyword_algorithm: None,
+                sentiment_neutral_band: None,
+                language_sentiment_bands: HashMap::new(),
+                abbreviations: HashMap::new(),
             },
             security: SecurityConfig {
                 encryption_enabled: true,
@@ -97,7 +846,23 @@ impl Default for AppConfig {
                 timeout_seconds: 30,
                 enable_profiling: false,
                 cache_size_mb: 100,
+                cache_cleanup_interval_seconds: default_cache_cleanup_interval(),
+                metrics_flush_interval_seconds: default_metrics_flush_interval(),
+                max_queued_requests: 0,
+                queue_timeout_ms: default_queue_timeout(),
             },
+            logging: LoggingConfig::default(),
+            alerts: AlertsConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            secrets: SecretsConfig::default(),
+            paths: PathsConfig::default(),
+            engagement: EngagementConfig::default(),
+            slowmode: SlowmodeConfig::default(),
+            bandit: BanditConfig::default(),
+            shadow: ShadowConfig::default(),
+            costs: CostsConfig::default(),
+            cache: CacheConfig::default(),
             environment: "development".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
         }
@@ -105,23 +870,85 @@ impl Default for AppConfig {
 }
 
 impl AppConfig {
-    pub fn load_from_file(path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        if Path::new(path).exists() {
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        if path.exists() {
             let config_content = fs::read_to_string(path)?;
-            let config: AppConfig = serde_json::from_str(&config_content)?;
-            let mut global_config = CONFIG.write().unwrap();
-            *global_config = config;
+            Self::load_from_str(&config_content)?;
         }
         Ok(())
     }
     
+    // Accepts either a plain AppConfig document or a signed ConfigBundle.
+    // Once a public key is pinned, only bundles signed with it are applied.
+    pub fn load_from_str(config_content: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let value: serde_json::Value = serde_json::from_str(config_content)?;
+        
+        let config = if value.get("signature").is_some() {
+            let bundle: ConfigBundle = serde_json::from_value(value)?;
+            let public_key = match pinned_public_key() {
+                Some(key) => key,
+                None => return Err(reject_bundle("Signed config bundle received but no public key is pinned".to_string())),
+            };
+            
+            let config = bundle.verify(&public_key).map_err(reject_bundle)?;
+            
+            let applied = APPLIED_BUNDLE_VERSION.load(Ordering::SeqCst);
+            if bundle.version <= applied {
+                return Err(reject_bundle(format!(
+                    "Config bundle version {} is not newer than applied version {}",
+                    bundle.version, applied
+                )));
+            }
+            APPLIED_BUNDLE_VERSION.store(bundle.version, Ordering::SeqCst);
+            config
+        } else {
+            if pinned_public_key().is_some() {
+                return Err(reject_bundle("Unsigned config rejected because a public key is pinned".to_string()));
+            }
+            serde_json::from_value(value)?
+        };
+        
+        // A full document replaces every earlier layer
+        let config_value = serde_json::to_value(&config)?;
+        let mut global_config = CONFIG.write().unwrap();
+        *global_config = config;
+        drop(global_config);
+        
+        PROVENANCE.write().unwrap().clear();
+        record_sources(&config_value, ConfigSource::FILE);
+        config_changed();
+        Ok(())
+    }
+    
     pub fn get() -> AppConfig {
         CONFIG.read().unwrap().clone()
     }
     
     pub fn update(updates: AppConfig) {
         let mut config = CONFIG.write().unwrap();
+        let (before, after) = secrets::with_secrets_exposed(|| (
+            serde_json::to_value(&*config).unwrap_or(serde_json::Value::Null),
+            serde_json::to_value(&updates).unwrap_or(serde_json::Value::Null),
+        ));
         *config = updates;
+        drop(config);
+        
+        let mut old_leaves = Vec::new();
+        let mut new_leaves = Vec::new();
+        leaf_paths(&before, "", &mut old_leaves);
+        leaf_paths(&after, "", &mut new_leaves);
+        
+        let old_leaves: HashMap<String, serde_json::Value> = old_leaves.into_iter().collect();
+        let mut provenance = PROVENANCE.write().unwrap();
+        for (path, value) in new_leaves {
+            if old_leaves.get(&path) != Some(&value) {
+                provenance.insert(path, ConfigSource::RUNTIME);
+            }
+        }
+        drop(provenance);
+        
+        config_changed();
     }
     
     pub fn get_analysis_config() -> AnalysisConfig {
@@ -136,6 +963,19 @@ impl AppConfig {
         CONFIG.read().unwrap().performance.clone()
     }
     
+    // Plain keys from analysis.api_keys win over the encrypted secrets
+    // section; expired keys are treated as missing
+    pub fn get_api_key(service: &str) -> Option<Secret> {
+        let config = CONFIG.read().unwrap();
+        if let Some(entry) = config.analysis.api_keys.get(service) {
+            return (!entry.is_expired()).then(|| entry.key.clone());
+        }
+        
+        let entry = config.secrets.api_keys.get(service).filter(|entry| !entry.is_expired())?;
+        let master_key = std::env::var(&config.secrets.master_key_env).ok()?;
+        secrets::open_sealed(entry.key.expose(), &master_key).ok()
+    }
+    
     pub fn is_production() -> bool {
         CONFIG.read().unwrap().environment == "production"
     }
@@ -151,6 +991,23 @@ impl AppConfig {
             errors.push("max_data_points must be greater than 0".to_string());
         }
         
+        if !(0.0..1.0).contains(&self.analysis.sentiment_threshold) {
+            errors.push("sentiment_threshold must be from 0 up to 1".to_string());
+        }
+        
+        let mut bands = self.analysis.sentiment_neutral_band.iter().chain(self.analysis.language_sentiment_bands.values());
+        if bands.any(|band| !band.is_valid()) {
+            errors.push("sentiment neutral bands need negative from -1 to 0 and positive from 0 to 1".to_string());
+        }
+        
+        if self.analysis.abbreviations.keys().any(|abbreviation| !abbreviation.chars().any(char::is_alphanumeric)) {
+            errors.push("abbreviations must contain a letter or digit".to_string());
+        }
+        
+        if !(1..=2).contains(&self.analysis.response_version) {
+            errors.push("response_version must be 1 or 2".to_string());
+        }
+        
         if self.performance.worker_threads == 0 {
             errors.push("worker_threads must be greater than 0".to_string());
         }
@@ -159,6 +1016,80 @@ impl AppConfig {
             errors.push("memory_limit_mb must be greater than 0".to_string());
         }
         
+        if self.telemetry.export_interval_seconds == 0 {
+            errors.push("telemetry export_interval_seconds must be greater than 0".to_string());
+        }
+        
+        for pattern in &self.logging.scrub_patterns {
+            if regex::Regex::new(pattern).is_err() {
+                errors.push(format!("invalid logging scrub pattern: {}", pattern));
+            }
+        }
+        
+        #[cfg(feature = "nlp")]
+        for pattern in &self.analysis.entity_patterns {
+            if let Err(e) = crate::entity_patterns::compile(pattern) {
+                errors.push(e);
+            }
+        }
+        
+        for rule in &self.alerts.log_rules {
+            if rule.threshold == 0 || rule.window_seconds == 0 {
+                errors.push(format!("alert rule '{}' needs a positive threshold and window", rule.name));
+            }
+        }
+        
+        let drop = &self.alerts.sentiment_drop;
+        if drop.min_drop <= 0.0 || drop.min_messages == 0 || !(1..=29).contains(&drop.baseline_days) {
+            errors.push("sentiment_drop needs a positive min_drop and min_messages and baseline_days from 1 to 29".to_string());
+        }
+        
+        let engagement = &self.engagement;
+        let weights = [engagement.view_weight, engagement.forward_weight, engagement.reply_weight, engagement.reaction_weight];
+        if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.0) || weights.iter().all(|weight| *weight == 0.0) {
+            errors.push("engagement weights must be non-negative and not all 0".to_string());
+        }
+        if !(engagement.trending_factor > 1.0 && engagement.trending_factor.is_finite()) {
+            errors.push("engagement trending_factor must be greater than 1".to_string());
+        }
+        let slowmode = &self.slowmode;
+        if !(slowmode.target_messages_per_minute > 0.0 && slowmode.user_messages_per_minute > 0.0) {
+            errors.push("slowmode target and user rates must be positive".to_string());
+        }
+        if !(60..=3600).contains(&slowmode.window_seconds) || slowmode.min_hold_seconds < 0 {
+            errors.push("slowmode window_seconds must be from 60 to 3600 and min_hold_seconds not negative".to_string());
+        }
+        if !(0.0..1.0).contains(&slowmode.hysteresis) {
+            errors.push("slowmode hysteresis must be from 0 up to 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.bandit.epsilon) {
+            errors.push("bandit epsilon must be between 0 and 1".to_string());
+        }
+        let shadow = &self.shadow;
+        if !(0.0..=1.0).contains(&shadow.sample_rate) || !(0.0..=1.0).contains(&shadow.keyword_overlap) {
+            errors.push("shadow sample_rate and keyword_overlap must be between 0 and 1".to_string());
+        }
+        if !(0.0..=2.0).contains(&shadow.score_tolerance) || shadow.max_examples > 1000 {
+            errors.push("shadow score_tolerance must be between 0 and 2 and max_examples at most 1000".to_string());
+        }
+        let costs = &self.costs;
+        let mut all_costs = [costs.base_cost, costs.cost_per_kilobyte].into_iter()
+            .chain(costs.operation_costs.values().copied())
+            .chain(costs.stage_costs.values().copied());
+        if !all_costs.all(|cost| cost.is_finite() && cost >= 0.0) {
+            errors.push("costs must be finite and not negative".to_string());
+        }
+        if costs.quota.is_some_and(|quota| !(quota.is_finite() && quota > 0.0)) || !(1..=2_592_000).contains(&costs.quota_window_seconds) {
+            errors.push("costs quota must be positive and quota_window_seconds from 1 to 2592000".to_string());
+        }
+        
+        for rate in self.logging.sampling_rates.values() {
+            if !(0.0..=1.0).contains(rate) {
+                errors.push("logging sampling rates must be between 0 and 1".to_string());
+                break;
+            }
+        }
+        
         if errors.is_empty() {
             Ok(())
         } else {
@@ -168,8 +1099,26 @@ impl AppConfig {
 }
 
 pub fn initialize_config(config_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(path) = config_path {
-        AppConfig::load_from_file(path)?;
+    // Without an explicit path, fall back to config.json in the platform
+    // config directory (XDG_CONFIG_HOME, %APPDATA%, or AIOGRAM_BASE_DIR)
+    let env_path = std::env::var("CONFIG_PATH").ok();
+    let path = match config_path.or(env_path.as_deref()) {
+        Some(path) => paths::normalize(&paths::expand_home(path)),
+        None => paths::default_config_file(),
+    };
+    AppConfig::load_from_file(path)?;
+    
+    finish_initialization()
+}
+
+pub fn initialize_config_from_str(config_content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    AppConfig::load_from_str(config_content)?;
+    finish_initialization()
+}
+
+fn finish_initialization() -> Result<(), Box<dyn std::error::Error>> {
+    if pinned_public_key().is_none() {
+        apply_env_overrides()?;
     }
     
     let config = AppConfig::get();
@@ -185,6 +1134,18 @@ pub fn initialize_config(config_path: Option<&str>) -> Result<(), Box<dyn std::e
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use crate::secrets::REDACTED;
+    
+    lazy_static! {
+        // Held by tests that change the global config or the environment, so
+        // they never run into each other's changes
+        static ref GLOBAL_STATE: Mutex<()> = Mutex::new(());
+    }
+    
+    fn global_state() -> MutexGuard<'static, ()> {
+        GLOBAL_STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
     
     #[test]
     fn test_default_config() {
@@ -201,9 +1162,93 @@ mod tests {
         assert!(config.validate_config().is_err());
     }
     
+    #[test]
+    fn test_response_version_defaults_to_legacy() {
+        let mut value = serde_json::to_value(AppConfig::default()).unwrap();
+        value["analysis"].as_object_mut().unwrap().remove("response_version");
+        
+        let config: AppConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.analysis.response_version, 1);
+    }
+    
+    #[test]
+    fn test_signed_bundle_verification() {
+        use ed25519_dalek::{Signer, SigningKey};
+        
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let payload = general_purpose::STANDARD.encode(serde_json::to_vec(&AppConfig::default()).unwrap());
+        let mut bundle = ConfigBundle { version: 1, payload, signature: String::new() };
+        bundle.signature = general_purpose::STANDARD.encode(signing_key.sign(&bundle.signed_message()).to_bytes());
+        
+        assert!(bundle.verify(&signing_key.verifying_key()).is_ok());
+        
+        // A bumped version invalidates the signature
+        let mut tampered = bundle.clone();
+        tampered.version = 2;
+        assert!(tampered.verify(&signing_key.verifying_key()).is_err());
+        
+        let other_key = SigningKey::from_bytes(&[9u8; 32]);
+        assert!(bundle.verify(&other_key.verifying_key()).is_err());
+    }
+    
+    #[test]
+    fn test_config_provenance() {
+        let _global = global_state();
+        apply_runtime_patch(r#"{"analysis": {"plagiarism_threshold": 0.8, "api_keys": {"provenance_test": "s3cret"}}}"#).unwrap();
+        
+        let dump = dump_effective_config(true);
+        assert_eq!(dump["analysis.plagiarism_threshold"]["source"], "RUNTIME");
+        assert_eq!(dump["analysis.max_text_length"]["source"], "DEFAULT");
+        assert_eq!(dump["analysis.api_keys.provenance_test"]["value"], REDACTED);
+        
+        let dump = dump_effective_config(false);
+        assert_eq!(dump["analysis.api_keys.provenance_test"]["value"], "s3cret");
+        
+        // Invalid patches leave the config untouched
+        assert!(apply_runtime_patch(r#"{"performance": {"worker_threads": 0}}"#).is_err());
+        assert!(AppConfig::get().performance.worker_threads > 0);
+    }
+    
+    #[test]
+    fn test_otlp_headers_are_redacted() {
+        let mut config = AppConfig::default();
+        config.telemetry.otlp_headers.insert("authorization".to_string(), Secret::new("Bearer otlp-token"));
+        
+        let dumped = serde_json::to_value(&config).unwrap();
+        assert_eq!(dumped["telemetry"]["otlp_headers"]["authorization"], REDACTED);
+        assert!(!format!("{:?}", config.telemetry).contains("otlp-token"));
+        let exposed = crate::secrets::with_secrets_exposed(|| serde_json::to_value(&config).unwrap());
+        assert_eq!(exposed["telemetry"]["otlp_headers"]["authorization"], "Bearer otlp-token");
+    }
+    
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_get_api_key() {
+        let _global = global_state();
+        let sealed = general_purpose::STANDARD.encode(crate::crypto::seal(b"sealed-token", "test-master-key").unwrap());
+        std::env::set_var("AIOGRAM_TEST_SECRETS_KEY", "test-master-key");
+        
+        let mut config = AppConfig::get();
+        config.analysis.api_keys.insert("api_key_test_plain".to_string(), ApiKeyEntry { key: Secret::new("plain-token"), expires_at: None });
+        config.analysis.api_keys.insert("api_key_test_expired".to_string(), ApiKeyEntry {
+            key: Secret::new("old-token"),
+            expires_at: Some(chrono::Utc::now() - chrono::Duration::days(1)),
+        });
+        config.secrets.master_key_env = "AIOGRAM_TEST_SECRETS_KEY".to_string();
+        config.secrets.api_keys.insert("api_key_test_sealed".to_string(), ApiKeyEntry { key: Secret::new(sealed), expires_at: None });
+        AppConfig::update(config);
+        
+        assert_eq!(AppConfig::get_api_key("api_key_test_plain").unwrap().expose(), "plain-token");
+        assert!(AppConfig::get_api_key("api_key_test_expired").is_none());
+        assert_eq!(AppConfig::get_api_key("api_key_test_sealed").unwrap().expose(), "sealed-token");
+        assert!(AppConfig::get_api_key("api_key_test_missing").is_none());
+        assert!(!format!("{:?}", AppConfig::get().analysis.api_keys).contains("plain-token"));
+        std::env::remove_var("AIOGRAM_TEST_SECRETS_KEY");
+    }
+    
     #[test]
     fn test_environment_detection() {
-        let config = AppConfig::default();
+        let _global = global_state();
         assert!(!AppConfig::is_production());
     }
 } 
\ No newline at end of file
@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use regex::Regex;
+use lazy_static::lazy_static;
+use crate::{analysis, summary};
+
+// Digests of a group conversation. Messages are grouped into threads (reply
+// chains, or runs of messages without a long pause), consecutive messages of
+// one author are merged into a turn and repeated messages are dropped. Key
+// points are the TextRank-best sentences, taken thread by thread so a busy
+// thread cannot crowd out the others; decisions and action items are
+// sentences with the cue phrases people use for them.
+
+// A pause this long starts a new thread unless the message is a reply
+const THREAD_GAP_SECONDS: i64 = 30 * 60;
+// Consecutive messages of one author this close together form one turn
+const TURN_GAP_SECONDS: i64 = 2 * 60;
+// Shorter sentences ("ok", "+1", "thanks!") never become key points
+const MIN_POINT_WORDS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub author: String,
+    // Unix seconds
+    pub date: i64,
+    pub text: String,
+    // Id of the message this one replies to
+    #[serde(default)]
+    pub reply_to: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Participant {
+    pub author: String,
+    pub messages: usize,
+    pub first_at: i64,
+    pub last_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigestPoint {
+    pub author: String,
+    pub date: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionItem {
+    pub author: String,
+    pub date: i64,
+    pub text: String,
+    // @mentioned user, or the author for "I'll ..."
+    pub assignee: Option<String>,
+    // First DATE or TIME entity of the sentence, as written
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationDigest {
+    pub message_count: usize,
+    pub thread_count: usize,
+    pub period_start: Option<i64>,
+    pub period_end: Option<i64>,
+    // Most active first
+    pub participants: Vec<Participant>,
+    pub key_points: Vec<DigestPoint>,
+    pub decisions: Vec<DigestPoint>,
+    pub action_items: Vec<ActionItem>,
+    // Words in key points, decisions and action items
+    pub tokens: usize,
+}
+
+lazy_static! {
+    static ref DECISION_CUES: Regex = Regex::new(
+        r"(?i)\b(?:we(?:'ve| have)? (?:decided|agreed)|decided to|decision:|agreed(?: on| to)?|let'?s go with|going with|final(?:ly)?:)|تصمیم گرفت|قرار شد|توافق کرد|نهایی شد"
+    ).unwrap();
+    static ref ACTION_CUES: Regex = Regex::new(
+        r"(?i)\b(?:todo|to-do|action item|i'?ll|i will|i'm going to|can you|could you|please|need to|needs to|should|assign(?:ed)? to)\b|@\w+|باید|لطفا|لطفاً|انجام می‌?دم|بررسی می‌?کنم|میشه"
+    ).unwrap();
+    static ref SELF_ASSIGN: Regex = Regex::new(r"(?i)\b(?:i'?ll|i will|i'm going to)\b|انجام می‌?دم|بررسی می‌?کنم").unwrap();
+    static ref MENTION: Regex = Regex::new(r"@(\w+)").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&DECISION_CUES);
+    lazy_static::initialize(&ACTION_CUES);
+    lazy_static::initialize(&SELF_ASSIGN);
+    lazy_static::initialize(&MENTION);
+    Ok(())
+}
+
+// Word count, the unit of max_tokens
+fn token_count(text: &str) -> usize {
+    text.unicode_words().count()
+}
+
+fn dedup_key(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+struct Turn {
+    thread: usize,
+    author: String,
+    date: i64,
+    text: String,
+}
+
+// Threads by reply chain or pause, then turns within them, in time order
+fn build_turns(messages: &[ConversationMessage]) -> (Vec<Turn>, usize) {
+    let mut ordered: Vec<&ConversationMessage> = messages.iter().collect();
+    ordered.sort_by_key(|m| m.date);
+    
+    let mut thread_of: HashMap<i64, usize> = HashMap::new();
+    let mut last_in_thread: Vec<i64> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut turns: Vec<Turn> = Vec::new();
+    for message in ordered {
+        let text = message.text.trim();
+        let thread = match message.reply_to.and_then(|id| thread_of.get(&id).copied()) {
+            Some(thread) => thread,
+            None => match last_in_thread.iter().rposition(|&last| message.date - last <= THREAD_GAP_SECONDS) {
+                Some(thread) if message.reply_to.is_none() => thread,
+                _ => {
+                    last_in_thread.push(message.date);
+                    last_in_thread.len() - 1
+                }
+            },
+        };
+        last_in_thread[thread] = message.date;
+        if let Some(id) = message.id {
+            thread_of.insert(id, thread);
+        }
+        
+        // Forwards and copy-pastes repeat verbatim
+        let key = dedup_key(text);
+        if key.is_empty() || !seen.insert(key) {
+            continue;
+        }
+        match turns.last_mut() {
+            Some(turn) if turn.thread == thread && turn.author == message.author && message.date - turn.date <= TURN_GAP_SECONDS => {
+                turn.text.push('\n');
+                turn.text.push_str(text);
+                turn.date = message.date;
+            }
+            _ => turns.push(Turn { thread, author: message.author.clone(), date: message.date, text: text.to_string() }),
+        }
+    }
+    (turns, last_in_thread.len())
+}
+
+fn participants(messages: &[ConversationMessage]) -> Vec<Participant> {
+    let mut by_author: BTreeMap<&str, Participant> = BTreeMap::new();
+    for message in messages {
+        let entry = by_author.entry(&message.author).or_insert_with(|| Participant {
+            author: message.author.clone(),
+            messages: 0,
+            first_at: message.date,
+            last_at: message.date,
+        });
+        entry.messages += 1;
+        entry.first_at = entry.first_at.min(message.date);
+        entry.last_at = entry.last_at.max(message.date);
+    }
+    let mut participants: Vec<Participant> = by_author.into_values().collect();
+    participants.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.first_at.cmp(&b.first_at)));
+    participants
+}
+
+fn action_item(author: &str, date: i64, sentence: &str) -> ActionItem {
+    let assignee = MENTION.captures(sentence).map(|c| c[1].to_string())
+        .or_else(|| SELF_ASSIGN.is_match(sentence).then(|| author.to_string()));
+    let due = analysis::extract_entities(sentence).into_iter()
+        .find(|e| e.entity_type == "DATE" || e.entity_type == "TIME")
+        .map(|e| e.name);
+    ActionItem { author: author.to_string(), date, text: sentence.to_string(), assignee, due }
+}
+
+// max_tokens bounds the words across key points, decisions and action items
+// (0 for no bound); decisions and action items are kept first
+pub fn summarize(messages: &[ConversationMessage], max_tokens: usize) -> ConversationDigest {
+    let (turns, thread_count) = build_turns(messages);
+    let mut digest = ConversationDigest {
+        message_count: messages.len(),
+        thread_count,
+        period_start: messages.iter().map(|m| m.date).min(),
+        period_end: messages.iter().map(|m| m.date).max(),
+        participants: participants(messages),
+        ..Default::default()
+    };
+    let mut budget = if max_tokens == 0 { usize::MAX } else { max_tokens };
+    let mut spend = |text: &str| {
+        let tokens = token_count(text);
+        if tokens > budget {
+            return false;
+        }
+        budget -= tokens;
+        true
+    };
+    
+    // (thread, turn index, sentence)
+    let mut sentences: Vec<(usize, usize, &str)> = Vec::new();
+    for (index, turn) in turns.iter().enumerate() {
+        for sentence in summary::split_sentences(&turn.text) {
+            sentences.push((turn.thread, index, sentence));
+        }
+    }
+    
+    let mut used: HashSet<usize> = HashSet::new();
+    for (i, &(_, turn, sentence)) in sentences.iter().enumerate() {
+        let (author, date) = (&turns[turn].author, turns[turn].date);
+        if DECISION_CUES.is_match(sentence) {
+            if spend(sentence) {
+                digest.decisions.push(DigestPoint { author: author.clone(), date, text: sentence.to_string() });
+            }
+            used.insert(i);
+        } else if ACTION_CUES.is_match(sentence) && token_count(sentence) >= MIN_POINT_WORDS - 1 {
+            if spend(sentence) {
+                digest.action_items.push(action_item(author, date, sentence));
+            }
+            used.insert(i);
+        }
+    }
+    
+    let texts: Vec<&str> = sentences.iter().map(|&(_, _, sentence)| sentence).collect();
+    let scores = summary::rank_sentences(&texts, "unknown");
+    let mut by_thread: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, &(thread, _, sentence)) in sentences.iter().enumerate() {
+        if !used.contains(&i) && token_count(sentence) >= MIN_POINT_WORDS {
+            by_thread.entry(thread).or_default().push(i);
+        }
+    }
+    for candidates in by_thread.values_mut() {
+        candidates.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b)));
+    }
+    
+    // Round-robin over threads: each thread's best sentence, then the second
+    // best, and so on until the budget runs out
+    let mut picked: Vec<usize> = Vec::new();
+    let mut round = 0;
+    let mut exhausted = false;
+    while !exhausted {
+        exhausted = true;
+        for candidates in by_thread.values() {
+            if let Some(&i) = candidates.get(round) {
+                exhausted = false;
+                if spend(sentences[i].2) {
+                    picked.push(i);
+                }
+            }
+        }
+        round += 1;
+    }
+    picked.sort_unstable();
+    digest.key_points = picked.into_iter().map(|i| {
+        let turn = &turns[sentences[i].1];
+        DigestPoint { author: turn.author.clone(), date: turn.date, text: sentences[i].2.to_string() }
+    }).collect();
+    
+    digest.tokens = digest.key_points.iter().map(|p| token_count(&p.text)).sum::<usize>()
+        + digest.decisions.iter().map(|p| token_count(&p.text)).sum::<usize>()
+        + digest.action_items.iter().map(|a| token_count(&a.text)).sum::<usize>();
+    digest
+}
+
+pub fn summarize_json(messages_json: &str, max_tokens: usize) -> Result<ConversationDigest, String> {
+    let messages: Vec<ConversationMessage> = serde_json::from_str(messages_json).map_err(|e| format!("Invalid messages: {}", e))?;
+    Ok(summarize(&messages, max_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn message(id: i64, author: &str, date: i64, text: &str, reply_to: Option<i64>) -> ConversationMessage {
+        ConversationMessage { id: Some(id), author: author.to_string(), date, text: text.to_string(), reply_to }
+    }
+    
+    #[test]
+    fn test_digest_structure() {
+        let messages = vec![
+            message(1, "sara", 0, "The mobile release is blocked by the login crash on older Android phones.", None),
+            message(2, "reza", 30, "The login crash comes from the new token refresh on Android.", Some(1)),
+            message(3, "reza", 60, "I'll patch the token refresh by Friday.", None),
+            message(4, "sara", 120, "ok", None),
+            message(5, "sara", 180, "We decided to ship the release without the new onboarding.", None),
+            message(6, "omid", 200, "@sara can you update the changelog before 2024-05-01?", None),
+            message(7, "omid", 210, "@sara can you update the changelog before 2024-05-01?", None),
+            message(8, "mina", 7200, "Separate topic: the design review of the new settings screen went really well.", None),
+            message(9, "mina", 7230, "The settings screen design now uses the shared component library.", None),
+        ];
+        let digest = summarize(&messages, 0);
+        
+        assert_eq!(digest.message_count, 9);
+        assert_eq!(digest.thread_count, 2);
+        assert_eq!((digest.period_start, digest.period_end), (Some(0), Some(7230)));
+        assert_eq!(digest.participants[0].author, "sara");
+        assert_eq!(digest.participants[0].messages, 3);
+        
+        assert_eq!(digest.decisions.len(), 1);
+        assert_eq!(digest.decisions[0].author, "sara");
+        assert_eq!(digest.action_items.len(), 2);
+        assert_eq!(digest.action_items[0].assignee.as_deref(), Some("reza"));
+        assert_eq!(digest.action_items[1].assignee.as_deref(), Some("sara"));
+        assert_eq!(digest.action_items[1].due.as_deref(), Some("2024-05-01"));
+        
+        // Both threads contribute a key point, in time order
+        assert!(digest.key_points.iter().any(|p| p.author == "mina"));
+        assert!(digest.key_points.iter().any(|p| p.author != "mina"));
+        assert!(digest.key_points.windows(2).all(|w| w[0].date <= w[1].date));
+        
+        let small = summarize(&messages, 25);
+        assert!(small.tokens <= 25);
+        assert_eq!(small.decisions.len(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, RwLock};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::config::{AppConfig, CostsConfig};
+use crate::tenant::{self, TenantContext};
+
+// Compute cost of the work done for each tenant, for billing heavy users of
+// a hosted bot. An operation costs its base cost, plus a share per kilobyte
+// of input, plus the cost of each text pipeline stage that actually ran, so
+// a call that skips NER or runs out of time is charged less; cached results
+// cost nothing. Costs add up per tenant, and within it per operation, user
+// and chat. With costs.quota set, admission refuses a tenant (or a user
+// within it, as for the rate limit) once its spending in the current window
+// reaches the quota; the call that crosses it still completes. Calls without
+// a tenant are not charged.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostTotals {
+    pub operations: u64,
+    pub input_bytes: u64,
+    pub cost: f64,
+}
+
+impl CostTotals {
+    fn add(&mut self, input_bytes: usize, cost: f64) {
+        self.operations += 1;
+        self.input_bytes += input_bytes as u64;
+        self.cost += cost;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostReport {
+    pub total: CostTotals,
+    // By operation: "analyze_text", "analyze_data" or "process_realtime"
+    pub operations: HashMap<String, CostTotals>,
+    // Only calls made with a user id, or a chat id, count here
+    pub users: HashMap<String, CostTotals>,
+    pub chats: HashMap<String, CostTotals>,
+}
+
+// One row of a tenant's report, flat for export_records
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostLine {
+    pub tenant_id: String,
+    // "total", "operation", "user" or "chat"
+    pub dimension: String,
+    // "" for the total
+    pub key: String,
+    pub operations: u64,
+    pub input_bytes: u64,
+    pub cost: f64,
+}
+
+struct QuotaWindow {
+    started: i64,
+    spent: f64,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<CostsConfig> = RwLock::new(AppConfig::get().costs);
+    // By tenant id
+    static ref REPORTS: Mutex<HashMap<String, CostReport>> = Mutex::new(HashMap::new());
+    // By TenantContext::rate_limit_key
+    static ref WINDOWS: Mutex<HashMap<String, QuotaWindow>> = Mutex::new(HashMap::new());
+}
+
+fn reports() -> MutexGuard<'static, HashMap<String, CostReport>> {
+    REPORTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn windows() -> MutexGuard<'static, HashMap<String, QuotaWindow>> {
+    WINDOWS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn config() -> CostsConfig {
+    CONFIG.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+pub fn reload_config() {
+    *CONFIG.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = AppConfig::get().costs;
+}
+
+fn cost_of<'a>(config: &CostsConfig, operation: &str, input_bytes: usize, stages: impl IntoIterator<Item = &'a str>) -> f64 {
+    let base = config.operation_costs.get(operation).copied().unwrap_or(config.base_cost);
+    let stages: f64 = stages.into_iter().filter_map(|stage| config.stage_costs.get(stage)).sum();
+    base + config.cost_per_kilobyte * input_bytes as f64 / 1024.0 + stages
+}
+
+// Charges the current tenant for an operation over input_bytes of input
+// that ran the given pipeline stages
+pub fn record<'a>(operation: &str, input_bytes: usize, stages: impl IntoIterator<Item = &'a str>) {
+    let context = tenant::current();
+    if context.tenant_id.is_none() {
+        return;
+    }
+    let config = config();
+    let cost = cost_of(&config, operation, input_bytes, stages);
+    charge(&context, &config, chrono::Utc::now().timestamp(), operation, input_bytes, cost);
+}
+
+fn charge(context: &TenantContext, config: &CostsConfig, now: i64, operation: &str, input_bytes: usize, cost: f64) {
+    let Some(tenant_id) = &context.tenant_id else {
+        return;
+    };
+    {
+        let mut reports = reports();
+        let report = reports.entry(tenant_id.clone()).or_default();
+        report.total.add(input_bytes, cost);
+        report.operations.entry(operation.to_string()).or_default().add(input_bytes, cost);
+        if let Some(user_id) = &context.user_id {
+            report.users.entry(user_id.clone()).or_default().add(input_bytes, cost);
+        }
+        if let Some(chat_id) = &context.chat_id {
+            report.chats.entry(chat_id.clone()).or_default().add(input_bytes, cost);
+        }
+    }
+    if let (Some(_), Some(key)) = (config.quota, context.rate_limit_key()) {
+        let mut windows = windows();
+        let window = windows.entry(key).or_insert(QuotaWindow { started: now, spent: 0.0 });
+        if now - window.started >= config.quota_window_seconds {
+            *window = QuotaWindow { started: now, spent: 0.0 };
+        }
+        window.spent += cost;
+    }
+}
+
+// Seconds until the context may spend again when it has used up its quota
+// for the current window; None when it is within quota or has none
+pub fn exhausted(context: &TenantContext) -> Option<u64> {
+    exhausted_at(context, &config(), chrono::Utc::now().timestamp())
+}
+
+fn exhausted_at(context: &TenantContext, config: &CostsConfig, now: i64) -> Option<u64> {
+    let quota = config.quota?;
+    let key = context.rate_limit_key()?;
+    let windows = windows();
+    let window = windows.get(&key)?;
+    let ends = window.started + config.quota_window_seconds;
+    (now < ends && window.spent >= quota).then(|| (ends - now) as u64)
+}
+
+pub fn report(tenant_id: &str) -> Option<CostReport> {
+    reports().get(tenant_id).cloned()
+}
+
+pub fn all_reports() -> HashMap<String, CostReport> {
+    reports().clone()
+}
+
+// Every tenant's report as rows: its total, then operations, users and
+// chats, each by key
+pub fn lines() -> Vec<CostLine> {
+    let reports = reports();
+    let mut tenant_ids: Vec<&String> = reports.keys().collect();
+    tenant_ids.sort();
+    let mut lines = Vec::new();
+    for tenant_id in tenant_ids {
+        let report = &reports[tenant_id];
+        let line = |dimension: &str, key: &str, totals: &CostTotals| CostLine {
+            tenant_id: tenant_id.clone(),
+            dimension: dimension.to_string(),
+            key: key.to_string(),
+            operations: totals.operations,
+            input_bytes: totals.input_bytes,
+            cost: totals.cost,
+        };
+        lines.push(line("total", "", &report.total));
+        for (dimension, totals) in [("operation", &report.operations), ("user", &report.users), ("chat", &report.chats)] {
+            let mut keys: Vec<&String> = totals.keys().collect();
+            keys.sort();
+            lines.extend(keys.into_iter().map(|key| line(dimension, key, &totals[key])));
+        }
+    }
+    lines
+}
+
+// Starts a tenant's report over, or every tenant's for None, e.g. after
+// billing a period; quota windows are kept
+pub fn reset(tenant_id: Option<&str>) -> bool {
+    let mut reports = reports();
+    match tenant_id {
+        Some(tenant_id) => reports.remove(tenant_id).is_some(),
+        None => {
+            let any = !reports.is_empty();
+            reports.clear();
+            any
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_costs_and_quota() {
+        let config = CostsConfig { quota: Some(10.0), quota_window_seconds: 60, ..Default::default() };
+        // 1 + 0.1 for 1 KiB + sentiment and NER
+        let cost = cost_of(&config, "analyze_text", 1024, ["sentiment", "ner", "unknown"]);
+        assert!((cost - 2.6).abs() < 1e-9);
+        let priced = CostsConfig { operation_costs: HashMap::from([("process_realtime".to_string(), 0.25)]), ..config.clone() };
+        assert_eq!(cost_of(&priced, "process_realtime", 0, []), 0.25);
+        
+        let context = TenantContext::new(Some("costs_test"), Some("42")).with_chat(Some("-100123"));
+        charge(&context, &config, 1000, "analyze_text", 1024, 6.0);
+        assert_eq!(exhausted_at(&context, &config, 1010), None);
+        charge(&context, &config, 1010, "analyze_data", 80, 4.5);
+        // Over quota until the window started at 1000 ends
+        assert_eq!(exhausted_at(&context, &config, 1020), Some(40));
+        assert_eq!(exhausted_at(&context, &config, 1060), None);
+        // Another user of the tenant has a quota of their own
+        assert_eq!(exhausted_at(&TenantContext::new(Some("costs_test"), Some("7")), &config, 1020), None);
+        
+        let tenant_report = report("costs_test").unwrap();
+        assert_eq!((tenant_report.total.operations, tenant_report.total.input_bytes, tenant_report.total.cost), (2, 1104, 10.5));
+        assert_eq!(tenant_report.operations["analyze_data"].cost, 4.5);
+        assert_eq!((tenant_report.users["42"].operations, tenant_report.chats["-100123"].operations), (2, 2));
+        let lines: Vec<(String, String)> = lines().into_iter()
+            .filter(|line| line.tenant_id == "costs_test")
+            .map(|line| (line.dimension, line.key))
+            .collect();
+        assert_eq!(lines[..3], [("total".to_string(), String::new()), ("operation".to_string(), "analyze_data".to_string()), ("operation".to_string(), "analyze_text".to_string())]);
+        
+        assert!(reset(Some("costs_test")));
+        assert!(report("costs_test").is_none());
+        // Calls without a tenant are free
+        charge(&TenantContext::default(), &config, 1000, "analyze_text", 10, 1.0);
+        assert!(report("").is_none());
+    }
+}
\ No newline at end of file
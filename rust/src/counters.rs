@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::paths;
+use crate::tenant;
+
+// Named counters for things like command usage and referral tallies. Each
+// counter keeps a running total plus per-minute counts for the last day and
+// per-hour counts for the last 30 days, which rollups sum into windows.
+// Counters are scoped by the current tenant, like cache keys, and a tenant's
+// counters can be written to a file and loaded back after a restart.
+
+const MINUTE_SECONDS: i64 = 60;
+const HOUR_SECONDS: i64 = 3600;
+const MINUTE_RETENTION_SECONDS: i64 = 86_400;
+pub const MAX_RETENTION_SECONDS: i64 = 30 * 86_400;
+const COUNTERS_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+    // Keyed by minute and hour since the epoch
+    minutes: BTreeMap<i64, i64>,
+    hours: BTreeMap<i64, i64>,
+}
+
+impl Counter {
+    // Totals never drop below zero; the buckets record what was applied
+    fn add(&mut self, by: i64, now: i64) -> i64 {
+        let total = self.total.saturating_add(by).max(0);
+        let applied = total - self.total;
+        self.total = total;
+        if applied != 0 {
+            *self.minutes.entry(now.div_euclid(MINUTE_SECONDS)).or_insert(0) += applied;
+            *self.hours.entry(now.div_euclid(HOUR_SECONDS)).or_insert(0) += applied;
+        }
+        self.prune(now);
+        total
+    }
+    
+    fn prune(&mut self, now: i64) {
+        self.minutes = self.minutes.split_off(&(now - MINUTE_RETENTION_SECONDS).div_euclid(MINUTE_SECONDS));
+        self.hours = self.hours.split_off(&(now - MAX_RETENTION_SECONDS).div_euclid(HOUR_SECONDS));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CounterValue {
+    pub name: String,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollupPoint {
+    // Unix seconds
+    pub start: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CounterRollup {
+    pub name: String,
+    pub total: i64,
+    // Sum of the points
+    pub window_total: i64,
+    // Rounded up to whole minutes, or whole hours for windows over a day
+    pub step_seconds: i64,
+    // Oldest first, including empty steps
+    pub points: Vec<RollupPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedCounters {
+    format_version: u32,
+    counters: HashMap<String, Counter>,
+}
+
+lazy_static! {
+    // Tenant scope, then counter name
+    static ref COUNTERS: Mutex<HashMap<String, HashMap<String, Counter>>> = Mutex::new(HashMap::new());
+}
+
+fn counters() -> MutexGuard<'static, HashMap<String, HashMap<String, Counter>>> {
+    COUNTERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Returns the new total
+pub fn incr(name: &str, by: i64) -> i64 {
+    incr_at(name, by, Utc::now().timestamp())
+}
+
+pub fn incr_at(name: &str, by: i64, now: i64) -> i64 {
+    let mut counters = counters();
+    counters.entry(tenant::scope_key("")).or_default().entry(name.to_string()).or_default().add(by, now)
+}
+
+// 0 for a counter that was never incremented
+pub fn get(name: &str) -> i64 {
+    counters().get(&tenant::scope_key("")).and_then(|scope| scope.get(name)).map(|counter| counter.total).unwrap_or(0)
+}
+
+pub fn reset(name: &str) -> bool {
+    counters().get_mut(&tenant::scope_key("")).and_then(|scope| scope.remove(name)).is_some()
+}
+
+// Counters whose name starts with prefix, by name
+pub fn list(prefix: &str) -> Vec<CounterValue> {
+    let counters = counters();
+    let mut values: Vec<CounterValue> = counters.get(&tenant::scope_key("")).into_iter()
+        .flat_map(|scope| scope.iter())
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, counter)| CounterValue { name: name.clone(), total: counter.total })
+        .collect();
+    values.sort_by(|a, b| a.name.cmp(&b.name));
+    values
+}
+
+pub fn rollup(name: &str, window_seconds: i64, step_seconds: i64) -> Option<CounterRollup> {
+    rollup_at(name, window_seconds, step_seconds, Utc::now().timestamp())
+}
+
+// The window ends with the current minute or hour and is capped at
+// MAX_RETENTION_SECONDS
+pub fn rollup_at(name: &str, window_seconds: i64, step_seconds: i64, now: i64) -> Option<CounterRollup> {
+    let counters = counters();
+    let counter = counters.get(&tenant::scope_key(""))?.get(name)?;
+    
+    let window = window_seconds.clamp(MINUTE_SECONDS, MAX_RETENTION_SECONDS);
+    let (resolution, buckets) = if window <= MINUTE_RETENTION_SECONDS {
+        (MINUTE_SECONDS, &counter.minutes)
+    } else {
+        (HOUR_SECONDS, &counter.hours)
+    };
+    let per_step = (step_seconds.max(resolution) + resolution - 1) / resolution;
+    let bucket_count = (window + resolution - 1) / resolution;
+    let first = now.div_euclid(resolution) - bucket_count + 1;
+    
+    let mut points: Vec<RollupPoint> = (0..(bucket_count + per_step - 1) / per_step)
+        .map(|i| RollupPoint { start: (first + i * per_step) * resolution, count: 0 })
+        .collect();
+    for (bucket, count) in buckets.range(first..first + bucket_count) {
+        points[((bucket - first) / per_step) as usize].count += count;
+    }
+    Some(CounterRollup {
+        name: name.to_string(),
+        total: counter.total,
+        window_total: points.iter().map(|point| point.count).sum(),
+        step_seconds: per_step * resolution,
+        points,
+    })
+}
+
+// Writes the current tenant's counters; returns how many
+pub fn save(path: &str) -> Result<usize, String> {
+    let saved = SavedCounters {
+        format_version: COUNTERS_FORMAT_VERSION,
+        counters: counters().get(&tenant::scope_key("")).cloned().unwrap_or_default(),
+    };
+    paths::atomic_write_json(path, "counters", &saved)?;
+    Ok(saved.counters.len())
+}
+
+// Replaces the current tenant's counters with those written by save
+pub fn load(path: &str) -> Result<usize, String> {
+    let saved = paths::read_versioned_json(path, "counters", COUNTERS_FORMAT_VERSION, |saved: &SavedCounters| saved.format_version)?;
+    
+    let now = Utc::now().timestamp();
+    let mut loaded = saved.counters;
+    for counter in loaded.values_mut() {
+        counter.prune(now);
+    }
+    let count = loaded.len();
+    counters().insert(tenant::scope_key(""), loaded);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantContext;
+    
+    #[test]
+    fn test_counts_and_rollups() {
+        tenant::with_tenant(TenantContext::new(Some("counters_test"), None), || {
+            let now = 1_700_000_000 - 1_700_000_000 % 3600 + 1800;
+            assert_eq!(incr_at("cmd:start", 1, now - 7200), 1);
+            assert_eq!(incr_at("cmd:start", 2, now - 90), 3);
+            assert_eq!(incr_at("cmd:start", 4, now), 7);
+            // Totals stop at zero
+            assert_eq!(incr_at("cmd:help", -5, now), 0);
+            assert_eq!(get("cmd:start"), 7);
+            assert_eq!(get("cmd:unknown"), 0);
+            
+            let last_five_minutes = rollup_at("cmd:start", 300, 60, now).unwrap();
+            assert_eq!(last_five_minutes.points.len(), 5);
+            assert_eq!(last_five_minutes.window_total, 6);
+            assert_eq!(last_five_minutes.points[4].count, 4);
+            assert_eq!(last_five_minutes.points[4].start, now);
+            // Steps end with the current minute, so the first hour holds now - 7200
+            let last_three_hours = rollup_at("cmd:start", 3 * 3600, 3600, now).unwrap();
+            assert_eq!(last_three_hours.points.iter().map(|p| p.count).collect::<Vec<_>>(), vec![1, 0, 6]);
+            let last_week = rollup_at("cmd:start", 7 * 86_400, 86_400, now).unwrap();
+            assert_eq!((last_week.step_seconds, last_week.window_total), (86_400, 7));
+            assert!(rollup_at("cmd:unknown", 60, 60, now).is_none());
+            
+            let names: Vec<String> = list("cmd:").into_iter().map(|value| value.name).collect();
+            assert_eq!(names, vec!["cmd:help", "cmd:start"]);
+            assert!(reset("cmd:help"));
+            assert!(!reset("cmd:help"));
+        });
+        // Other tenants do not see them
+        assert_eq!(get("cmd:start"), 0);
+    }
+    
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("aiogram_counters_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        tenant::with_tenant(TenantContext::new(Some("counters_saved"), None), || {
+            incr("referrals:alice", 3);
+            assert_eq!(save(path), Ok(1));
+            incr("referrals:alice", 10);
+            assert_eq!(load(path), Ok(1));
+            assert_eq!(get("referrals:alice"), 3);
+        });
+        let _ = std::fs::remove_file(path);
+    }
+}
\ No newline at end of file
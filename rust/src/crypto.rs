@@ -7,11 +7,81 @@ use aes::cipher::{
 use block_modes::{BlockMode, Cbc};
 use block_modes::block_padding::Pkcs7;
 use sha2::{Sha256, Digest};
+use aes_gcm::{Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, Payload};
 use rand::Rng;
+#[cfg(feature = "realtime")]
+use rayon::prelude::*;
+use base64::{Engine, engine::general_purpose};
+use serde::{Serialize, Deserialize};
 use std::error::Error;
 
 type Aes256Cbc = Cbc<Aes256, Pkcs7>;
 
+// Sealed payload layout, version 1: MAGIC | 1 | nonce (12 bytes) | AES-256-GCM(zstd(data))
+// Version 2 envelopes describe how they were made:
+// MAGIC | 2 | algorithm | flags | KDF | KDF params | nonce (12 bytes) | ciphertext
+// with Argon2id params as memory KiB, iterations, parallelism (u32 LE each) and
+// a 16 byte salt. Either way the header is passed as associated data so it is
+// authenticated as well.
+const SEAL_MAGIC: &[u8; 3] = b"AGS";
+const SEAL_VERSION: u8 = 1;
+const ENVELOPE_VERSION: u8 = 2;
+const SEAL_HEADER_LEN: usize = 4;
+const SEAL_NONCE_LEN: usize = 12;
+const SEAL_COMPRESSION_LEVEL: i32 = 3;
+
+const ALGORITHM_AES_256_GCM: u8 = 1;
+const FLAG_COMPRESSED: u8 = 1;
+const KDF_SHA256: u8 = 1;
+const KDF_ARGON2ID: u8 = 2;
+const ARGON2_SALT_LEN: usize = 16;
+// Envelopes come from storage and untrusted input alike, so the work a header
+// can ask for is capped
+const MAX_ARGON2_MEMORY_KIB: u32 = 262_144;
+const MAX_ARGON2_ITERATIONS: u32 = 8;
+const MAX_ARGON2_PARALLELISM: u32 = 8;
+
+// Every layout the crate has written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CiphertextFormat {
+    // encrypt() output: IV | AES-256-CBC, unauthenticated
+    #[serde(rename = "LEGACY_CBC")]
+    LegacyCbc,
+    #[serde(rename = "SEALED_V1")]
+    SealedV1,
+    #[serde(rename = "ENVELOPE_V2")]
+    EnvelopeV2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Kdf {
+    // SHA-256 of the key; enough for random keys such as generate_key()
+    Sha256,
+    // For passphrases
+    Argon2id { memory_kib: u32, iterations: u32, parallelism: u32 },
+}
+
+impl Kdf {
+    // OWASP's minimum recommendation
+    pub const ARGON2ID_DEFAULT: Kdf = Kdf::Argon2id { memory_kib: 19_456, iterations: 2, parallelism: 1 };
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvelopeOptions {
+    pub kdf: Kdf,
+    // zstd before encrypting; worth it for anything but short messages
+    pub compress: bool,
+}
+
+impl Default for EnvelopeOptions {
+    fn default() -> Self {
+        Self { kdf: Kdf::Sha256, compress: true }
+    }
+}
+
 #[derive(Debug)]
 pub struct CryptoError(String);
 
@@ -23,6 +93,14 @@ impl std::fmt::Display for CryptoError {
 
 impl Error for CryptoError {}
 
+#[cfg(feature = "chaos")]
+fn injected_failure() -> Result<(), Box<dyn Error>> {
+    if crate::chaos::inject(crate::chaos::Fault::CryptoFailure) {
+        return Err(Box::new(CryptoError("Injected fault".to_string())));
+    }
+    Ok(())
+}
+
 impl From<std::io::Error> for CryptoError {
     fn from(err: std::io::Error) -> Self {
         CryptoError(err.to_string())
@@ -30,9 +108,17 @@ impl From<std::io::Error> for CryptoError {
 }
 
 pub fn encrypt(message: &str, key: &str) -> Result<String, Box<dyn Error>> {
+    encrypt_bytes(message.as_bytes(), key.as_bytes())
+}
+
+// Binary-safe encrypt(): any message and key bytes, base64 ciphertext
+pub fn encrypt_bytes(message: &[u8], key: &[u8]) -> Result<String, Box<dyn Error>> {
+    #[cfg(feature = "chaos")]
+    injected_failure()?;
+    
     // Generate a proper key from the input key
     let mut hasher = Sha256::new();
-    hasher.update(key.as_bytes());
+    hasher.update(key);
     let key_bytes = hasher.finalize();
     
     // Generate random IV
@@ -44,7 +130,7 @@ pub fn encrypt(message: &str, key: &str) -> Result<String, Box<dyn Error>> {
         .map_err(|e| CryptoError(format!("Failed to create cipher: {}", e)))?;
     
     // Encrypt the message
-    let ciphertext = cipher.encrypt_vec(message.as_bytes());
+    let ciphertext = cipher.encrypt_vec(message);
     
     // Combine IV and ciphertext
     let mut result = Vec::new();
@@ -56,19 +142,205 @@ pub fn encrypt(message: &str, key: &str) -> Result<String, Box<dyn Error>> {
 }
 
 pub fn decrypt(encrypted_message: &str, key: &str) -> Result<String, Box<dyn Error>> {
-    // Generate the same key from the input key
-    let mut hasher = Sha256::new();
-    hasher.update(key.as_bytes());
-    let key_bytes = hasher.finalize();
+    let plaintext = decrypt_bytes(encrypted_message.as_bytes(), key.as_bytes())?;
+    
+    // Convert to string
+    String::from_utf8(plaintext)
+        .map_err(|e| CryptoError(format!("Invalid UTF-8: {}", e)).into())
+}
+
+// Binary-safe decrypt(): base64 ciphertext in, plaintext bytes out
+pub fn decrypt_bytes(encrypted_message: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[cfg(feature = "chaos")]
+    injected_failure()?;
     
     // Decode from base64
     let encrypted_bytes = base64::decode(encrypted_message)
         .map_err(|e| CryptoError(format!("Invalid base64: {}", e)))?;
     
-    if encrypted_bytes.len() < 16 {
-        return Err(Box::new(CryptoError("Invalid encrypted data length".to_string())));
+    // Whatever version of the crate encrypted it
+    let (_, plaintext) = open_any(&encrypted_bytes, key)?;
+    Ok(plaintext)
+}
+
+// Envelope in base64, for callers that want GCM or Argon2 today; decrypt()
+// reads it like the CBC output of encrypt()
+pub fn encrypt_with(message: &str, key: &str, options: &EnvelopeOptions) -> Result<String, Box<dyn Error>> {
+    Ok(general_purpose::STANDARD.encode(seal_with(message.as_bytes(), key, options)?))
+}
+
+fn derive_key(key: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(key);
+    hasher.finalize().into()
+}
+
+fn derive_envelope_key(key: &[u8], kdf: &Kdf, salt: &[u8]) -> Result<[u8; 32], Box<dyn Error>> {
+    match *kdf {
+        Kdf::Sha256 => Ok(derive_key(key)),
+        Kdf::Argon2id { memory_kib, iterations, parallelism } => {
+            let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(32))
+                .map_err(|e| CryptoError(format!("Invalid Argon2 parameters: {}", e)))?;
+            let mut derived = [0u8; 32];
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                .hash_password_into(key, salt, &mut derived)
+                .map_err(|e| CryptoError(format!("Key derivation failed: {}", e)))?;
+            Ok(derived)
+        }
+    }
+}
+
+// Compress, encrypt and authenticate a payload in one step. Used for anything
+// persisted to disk (cache snapshots, state, exported archives).
+pub fn seal(data: &[u8], key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    seal_with(data, key, &EnvelopeOptions::default())
+}
+
+pub fn seal_with(data: &[u8], key: &str, options: &EnvelopeOptions) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[cfg(feature = "chaos")]
+    injected_failure()?;
+    
+    let compressed;
+    let plaintext = if options.compress {
+        compressed = zstd::encode_all(data, SEAL_COMPRESSION_LEVEL)?;
+        compressed.as_slice()
+    } else {
+        data
+    };
+    
+    let mut nonce_bytes = [0u8; SEAL_NONCE_LEN];
+    rand::thread_rng().fill(&mut nonce_bytes);
+    
+    let mut header = Vec::with_capacity(SEAL_HEADER_LEN + 4 + 12 + ARGON2_SALT_LEN);
+    header.extend_from_slice(SEAL_MAGIC);
+    header.push(ENVELOPE_VERSION);
+    header.push(ALGORITHM_AES_256_GCM);
+    header.push(if options.compress { FLAG_COMPRESSED } else { 0 });
+    let mut salt = Vec::new();
+    match options.kdf {
+        Kdf::Sha256 => header.push(KDF_SHA256),
+        Kdf::Argon2id { memory_kib, iterations, parallelism } => {
+            header.push(KDF_ARGON2ID);
+            for param in [memory_kib, iterations, parallelism] {
+                header.extend_from_slice(&param.to_le_bytes());
+            }
+            salt.resize(ARGON2_SALT_LEN, 0);
+            rand::thread_rng().fill(salt.as_mut_slice());
+            header.extend_from_slice(&salt);
+        }
+    }
+    
+    let cipher = Aes256Gcm::new_from_slice(&derive_envelope_key(key.as_bytes(), &options.kdf, &salt)?)
+        .map_err(|e| CryptoError(format!("Failed to create cipher: {}", e)))?;
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: plaintext, aad: &header })
+        .map_err(|_| CryptoError("Encryption failed".to_string()))?;
+    
+    let mut result = header;
+    result.extend_from_slice(&nonce_bytes);
+    result.extend_from_slice(&ciphertext);
+    Ok(result)
+}
+
+// Opens sealed payloads of every version; never falls back to the
+// unauthenticated CBC layout
+pub fn open(sealed: &[u8], key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[cfg(feature = "chaos")]
+    injected_failure()?;
+    
+    if sealed.len() < SEAL_HEADER_LEN + SEAL_NONCE_LEN || &sealed[..3] != SEAL_MAGIC {
+        return Err(Box::new(CryptoError("Not a sealed payload".to_string())));
+    }
+    
+    let authenticated = OPENERS.iter().filter(|opener| opener.authenticated);
+    match open_registered(sealed, key.as_bytes(), authenticated) {
+        Some(opened) => opened.map(|(_, data)| data),
+        None if sealed[3] == ENVELOPE_VERSION => Err(Box::new(parse_envelope_header(sealed).err().unwrap_or_else(|| CryptoError("Invalid envelope".to_string())))),
+        None => Err(Box::new(CryptoError(format!("Unsupported sealed payload version: {}", sealed[3])))),
+    }
+}
+
+fn open_sealed_v1(sealed: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let header = &sealed[..SEAL_HEADER_LEN];
+    let nonce = &sealed[SEAL_HEADER_LEN..SEAL_HEADER_LEN + SEAL_NONCE_LEN];
+    let ciphertext = &sealed[SEAL_HEADER_LEN + SEAL_NONCE_LEN..];
+    
+    let cipher = Aes256Gcm::new_from_slice(&derive_key(key))
+        .map_err(|e| CryptoError(format!("Failed to create cipher: {}", e)))?;
+    let compressed = cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| CryptoError("Authentication failed".to_string()))?;
+    
+    Ok(zstd::decode_all(compressed.as_slice())?)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+struct EnvelopeHeader<'a> {
+    flags: u8,
+    kdf: Kdf,
+    salt: &'a [u8],
+    len: usize,
+}
+
+// Everything up to the nonce; Argon2 limits are checked when opening, so an
+// over-limit header still counts as an envelope rather than as CBC
+fn parse_envelope_header(envelope: &[u8]) -> Result<EnvelopeHeader<'_>, CryptoError> {
+    let invalid = || CryptoError("Truncated envelope header".to_string());
+    let (algorithm, flags, kdf_id) = match envelope.get(SEAL_HEADER_LEN..SEAL_HEADER_LEN + 3) {
+        Some(fields) => (fields[0], fields[1], fields[2]),
+        None => return Err(invalid()),
+    };
+    if algorithm != ALGORITHM_AES_256_GCM {
+        return Err(CryptoError(format!("Unsupported envelope algorithm: {}", algorithm)));
     }
     
+    let mut len = SEAL_HEADER_LEN + 3;
+    let (kdf, salt) = match kdf_id {
+        KDF_SHA256 => (Kdf::Sha256, &[][..]),
+        KDF_ARGON2ID => {
+            let param = |i: usize| read_u32(envelope, len + 4 * i).ok_or_else(invalid);
+            let (memory_kib, iterations, parallelism) = (param(0)?, param(1)?, param(2)?);
+            len += 12;
+            let salt = envelope.get(len..len + ARGON2_SALT_LEN).ok_or_else(invalid)?;
+            len += ARGON2_SALT_LEN;
+            (Kdf::Argon2id { memory_kib, iterations, parallelism }, salt)
+        }
+        other => return Err(CryptoError(format!("Unsupported envelope KDF: {}", other))),
+    };
+    if envelope.len() < len + SEAL_NONCE_LEN {
+        return Err(invalid());
+    }
+    Ok(EnvelopeHeader { flags, kdf, salt, len })
+}
+
+fn open_envelope(envelope: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let EnvelopeHeader { flags, kdf, salt, len: header_len } = parse_envelope_header(envelope)?;
+    if let Kdf::Argon2id { memory_kib, iterations, parallelism } = kdf {
+        if memory_kib > MAX_ARGON2_MEMORY_KIB || iterations > MAX_ARGON2_ITERATIONS || parallelism > MAX_ARGON2_PARALLELISM {
+            return Err(Box::new(CryptoError("Argon2 parameters above the accepted limits".to_string())));
+        }
+    }
+    
+    let header = &envelope[..header_len];
+    let nonce = &envelope[header_len..header_len + SEAL_NONCE_LEN];
+    let ciphertext = &envelope[header_len + SEAL_NONCE_LEN..];
+    
+    let cipher = Aes256Gcm::new_from_slice(&derive_envelope_key(key, &kdf, salt)?)
+        .map_err(|e| CryptoError(format!("Failed to create cipher: {}", e)))?;
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| CryptoError("Authentication failed".to_string()))?;
+    
+    if flags & FLAG_COMPRESSED != 0 {
+        Ok(zstd::decode_all(plaintext.as_slice())?)
+    } else {
+        Ok(plaintext)
+    }
+}
+
+fn open_legacy_cbc(encrypted_bytes: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key_bytes = derive_key(key);
+    
     // Extract IV and ciphertext
     let iv = &encrypted_bytes[..16];
     let ciphertext = &encrypted_bytes[16..];
@@ -80,10 +352,105 @@ pub fn decrypt(encrypted_message: &str, key: &str) -> Result<String, Box<dyn Err
     // Decrypt the message
     let plaintext = cipher.decrypt_vec(ciphertext)
         .map_err(|e| CryptoError(format!("Decryption failed: {}", e)))?;
+    Ok(plaintext)
+}
+
+type Opened = (CiphertextFormat, Vec<u8>);
+type OpenFn = fn(&[u8], &[u8]) -> Result<Vec<u8>, Box<dyn Error>>;
+
+struct Opener {
+    format: CiphertextFormat,
+    authenticated: bool,
+    matches: fn(&[u8]) -> bool,
+    open: OpenFn,
+}
+
+fn is_sealed(bytes: &[u8], version: u8) -> bool {
+    bytes.len() >= SEAL_HEADER_LEN + SEAL_NONCE_LEN && bytes.starts_with(SEAL_MAGIC) && bytes[3] == version
+}
+
+// Every format the crate has written, newest first. Upgrades add an entry;
+// old entries stay so stored data keeps opening.
+const OPENERS: &[Opener] = &[
+    Opener {
+        format: CiphertextFormat::EnvelopeV2,
+        authenticated: true,
+        matches: |bytes| is_sealed(bytes, ENVELOPE_VERSION) && parse_envelope_header(bytes).is_ok(),
+        open: open_envelope,
+    },
+    Opener {
+        format: CiphertextFormat::SealedV1,
+        authenticated: true,
+        matches: |bytes| is_sealed(bytes, SEAL_VERSION),
+        open: open_sealed_v1,
+    },
+    Opener {
+        format: CiphertextFormat::LegacyCbc,
+        authenticated: false,
+        // IV plus at least one whole AES block
+        matches: |bytes| bytes.len() >= 32 && bytes.len() % 16 == 0,
+        open: open_legacy_cbc,
+    },
+];
+
+// The first format whose header parses decides, and its error is final: once
+// a GCM header matched, a failed tag means a wrong key or tampering, and
+// retrying as CBC would hand back garbage and act as a padding oracle. A CBC
+// IV that happens to start with a valid header is a 2^-32 chance at most.
+// None when no format matches at all.
+fn open_registered<'a>(bytes: &[u8], key: &[u8], mut openers: impl Iterator<Item = &'a Opener>) -> Option<Result<Opened, Box<dyn Error>>> {
+    let opener = openers.find(|opener| (opener.matches)(bytes))?;
+    Some((opener.open)(bytes, key).map(|data| (opener.format, data)))
+}
+
+// The most recent format whose header bytes match; a guess for LegacyCbc,
+// which has no header
+pub fn detect_format(bytes: &[u8]) -> Option<CiphertextFormat> {
+    OPENERS.iter().find(|opener| (opener.matches)(bytes)).map(|opener| opener.format)
+}
+
+// Opens bytes in any format the crate has produced, reporting which
+pub fn open_any(bytes: &[u8], key: &[u8]) -> Result<Opened, Box<dyn Error>> {
+    open_registered(bytes, key, OPENERS.iter())
+        .unwrap_or_else(|| Err(Box::new(CryptoError("Invalid encrypted data length".to_string()))))
+}
+
+// Outcome of one item of a batch; exactly one of value and error is set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    fn from_result(result: Result<String, Box<dyn Error>>) -> Self {
+        match result {
+            Ok(value) => Self { value: Some(value), error: None },
+            Err(e) => Self { value: None, error: Some(e.to_string()) },
+        }
+    }
+}
+
+// Results in input order; a failed item does not stop the others
+fn run_batch<F>(items: &[String], op: F) -> Vec<BatchResult>
+where
+    F: Fn(&str) -> Result<String, Box<dyn Error>> + Sync,
+{
+    #[cfg(feature = "realtime")]
+    return crate::pool::install(|| items.par_iter().map(|item| BatchResult::from_result(op(item))).collect());
     
-    // Convert to string
-    String::from_utf8(plaintext)
-        .map_err(|e| CryptoError(format!("Invalid UTF-8: {}", e)))
+    #[cfg(not(feature = "realtime"))]
+    items.iter().map(|item| BatchResult::from_result(op(item))).collect()
+}
+
+pub fn encrypt_batch(messages: &[String], key: &str) -> Vec<BatchResult> {
+    run_batch(messages, |message| encrypt(message, key))
+}
+
+pub fn decrypt_batch(encrypted_messages: &[String], key: &str) -> Vec<BatchResult> {
+    run_batch(encrypted_messages, |encrypted| decrypt(encrypted, key))
 }
 
 // Additional cryptographic utilities
@@ -114,6 +481,101 @@ mod tests {
         assert_eq!(message, decrypted);
     }
     
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let payload = "cache snapshot ".repeat(100);
+        let sealed = seal(payload.as_bytes(), "snapshot_key").unwrap();
+        
+        assert_eq!(&sealed[..3], b"AGS");
+        assert!(sealed.len() < payload.len());
+        assert_eq!(open(&sealed, "snapshot_key").unwrap(), payload.as_bytes());
+    }
+    
+    #[test]
+    fn test_open_rejects_tampering() {
+        let mut sealed = seal(b"state", "snapshot_key").unwrap();
+        assert!(open(&sealed, "wrong_key").is_err());
+        
+        sealed[3] = 9;
+        assert!(open(&sealed, "snapshot_key").is_err());
+        
+        sealed[3] = ENVELOPE_VERSION;
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0xff;
+        assert!(open(&sealed, "snapshot_key").is_err());
+    }
+    
+    #[test]
+    fn test_registry_opens_every_format() {
+        // Version 1 sealed payload as written before envelopes
+        let mut sealed_v1 = b"AGS\x01".to_vec();
+        let nonce = [7u8; SEAL_NONCE_LEN];
+        let cipher = Aes256Gcm::new_from_slice(&derive_key(b"key")).unwrap();
+        let compressed = zstd::encode_all(&b"old state"[..], SEAL_COMPRESSION_LEVEL).unwrap();
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: &compressed, aad: &sealed_v1 }).unwrap();
+        sealed_v1.extend_from_slice(&nonce);
+        sealed_v1.extend_from_slice(&ciphertext);
+        assert_eq!(detect_format(&sealed_v1), Some(CiphertextFormat::SealedV1));
+        assert_eq!(open(&sealed_v1, "key").unwrap(), b"old state");
+        
+        let legacy = general_purpose::STANDARD.decode(encrypt("hello", "key").unwrap()).unwrap();
+        assert_eq!(open_any(&legacy, b"key").unwrap(), (CiphertextFormat::LegacyCbc, b"hello".to_vec()));
+        assert!(open(&legacy, "key").is_err());
+        
+        let argon2 = EnvelopeOptions { kdf: Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 }, compress: false };
+        let encrypted = encrypt_with("hello", "passphrase", &argon2).unwrap();
+        assert_eq!(detect_format(&general_purpose::STANDARD.decode(&encrypted).unwrap()), Some(CiphertextFormat::EnvelopeV2));
+        assert_eq!(decrypt(&encrypted, "passphrase").unwrap(), "hello");
+        assert!(decrypt(&encrypted, "wrong").is_err());
+        
+        // Headers asking for more work than allowed are refused before deriving
+        let mut expensive = general_purpose::STANDARD.decode(&encrypted).unwrap();
+        expensive[7..11].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(open(&expensive, "passphrase").unwrap_err().to_string().contains("limits"));
+    }
+    
+    #[test]
+    fn test_failed_authentication_never_falls_back_to_cbc() {
+        // Uncompressed, so the ciphertext length is a multiple of 16 and
+        // would also pass as IV | CBC blocks
+        let options = EnvelopeOptions { kdf: Kdf::Sha256, compress: false };
+        let mut message = "x".to_string();
+        while seal_with(message.as_bytes(), "key", &options).unwrap().len() % 16 != 0 {
+            message.push('x');
+        }
+        let sealed = seal_with(message.as_bytes(), "key", &options).unwrap();
+        for key in ["wrong_key", "other_key", "third_key", "fourth_key"] {
+            let error = open_any(&sealed, key.as_bytes()).unwrap_err().to_string();
+            assert!(error.contains("Authentication failed"), "{}", error);
+        }
+    }
+    
+    #[test]
+    fn test_binary_messages_and_keys() {
+        let message = [0u8, 159, 146, 150, 255, 10, 0];
+        let key = [0xffu8, 0xfe, 0, 1];
+        let encrypted = encrypt_bytes(&message, &key).unwrap();
+        assert_eq!(decrypt_bytes(encrypted.as_bytes(), &key).unwrap(), message);
+        assert!(decrypt_bytes(encrypted.as_bytes(), &[0xff, 0xfe, 0, 2]).is_err());
+        // Not text, so only the bytes API can return it
+        assert!(decrypt(&encrypted, "key").is_err());
+    }
+    
+    #[test]
+    fn test_batches_keep_order_and_per_item_errors() {
+        let messages: Vec<String> = (0..50).map(|i| format!("message {}", i)).collect();
+        let encrypted = encrypt_batch(&messages, "key");
+        assert!(encrypted.iter().all(|item| item.error.is_none()));
+        
+        let mut ciphertexts: Vec<String> = encrypted.into_iter().map(|item| item.value.unwrap()).collect();
+        ciphertexts[3] = "not base64!".to_string();
+        let decrypted = decrypt_batch(&ciphertexts, "key");
+        assert_eq!(decrypted.len(), 50);
+        assert!(decrypted[3].value.is_none() && decrypted[3].error.is_some());
+        assert_eq!(decrypted[49].value.as_deref(), Some("message 49"));
+        assert_eq!(serde_json::to_string(&decrypted[0]).unwrap(), r#"{"value":"message 0"}"#);
+    }
+    
     #[test]
     fn test_key_generation() {
         let key1 = generate_key();
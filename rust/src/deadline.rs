@@ -0,0 +1,126 @@
+use std::cell::Cell;
+#[cfg(not(target_arch = "wasm32"))]
+use lazy_static::lazy_static;
+
+// Time budget of the current call. A host that gives up on a call after its
+// own timeout passes a deadline along; analysis stages, batch workers, jobs
+// and file I/O check it and stop early, so the call returns what it has
+// instead of finishing work nobody waits for. Calls without a deadline are
+// unaffected.
+
+// Milliseconds on the clock below
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Deadline(f64);
+
+impl Deadline {
+    pub fn after_millis(timeout_ms: u64) -> Self {
+        Deadline(now() + timeout_ms as f64)
+    }
+    
+    pub fn remaining_millis(&self) -> u64 {
+        (self.0 - now()).max(0.0) as u64
+    }
+    
+    pub fn is_expired(&self) -> bool {
+        now() >= self.0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+lazy_static! {
+    static ref EPOCH: std::time::Instant = std::time::Instant::now();
+}
+
+// std::time::Instant panics on wasm32-unknown-unknown, so browser builds use
+// the JS clock instead
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> f64 {
+    EPOCH.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<Deadline>> = const { Cell::new(None) };
+    // Armed by set_next_call for the next FFI call on this thread
+    static NEXT_CALL: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+pub fn current() -> Option<Deadline> {
+    CURRENT.with(Cell::get)
+}
+
+pub fn expired() -> bool {
+    current().is_some_and(|deadline| deadline.is_expired())
+}
+
+// For work that cannot stop halfway, such as writing a file: fails before it
+// starts once the deadline has passed
+pub fn check(operation: &str) -> Result<(), String> {
+    match current() {
+        Some(deadline) if deadline.is_expired() => Err(format!("Deadline exceeded before {}", operation)),
+        _ => Ok(()),
+    }
+}
+
+// Restores the outer deadline even when the body panics
+struct RestoreDeadline(Option<Deadline>);
+
+impl Drop for RestoreDeadline {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+// Runs body under the deadline; an earlier one already in force still
+// applies, so nested calls cannot extend the caller's budget
+pub fn with_deadline<R, F: FnOnce() -> R>(deadline: Option<Deadline>, body: F) -> R {
+    let previous = current();
+    let effective = match (previous, deadline) {
+        (Some(outer), Some(inner)) => Some(if inner < outer { inner } else { outer }),
+        (outer, inner) => outer.or(inner),
+    };
+    CURRENT.with(|current| current.set(effective));
+    let _restore = RestoreDeadline(previous);
+    body()
+}
+
+// The next FFI call made on this thread gets timeout_ms from now; 0 disarms
+pub fn set_next_call(timeout_ms: u64) {
+    NEXT_CALL.with(|next| next.set((timeout_ms > 0).then(|| Deadline::after_millis(timeout_ms))));
+}
+
+pub fn take_next_call() -> Option<Deadline> {
+    NEXT_CALL.with(Cell::take)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_nested_deadlines() {
+        assert!(!expired());
+        with_deadline(Some(Deadline::after_millis(60_000)), || {
+            assert!(!expired());
+            assert!(check("write").is_ok());
+            // A later inner deadline does not extend the outer one
+            with_deadline(Some(Deadline::after_millis(120_000)), || {
+                assert!(current().unwrap().remaining_millis() <= 60_000);
+            });
+            with_deadline(Some(Deadline::after_millis(0)), || {
+                assert!(expired());
+                assert!(check("write").is_err());
+            });
+            assert!(!expired());
+        });
+        assert_eq!(current(), None);
+        
+        set_next_call(50);
+        assert!(take_next_call().is_some());
+        assert_eq!(take_next_call(), None);
+    }
+}
\ No newline at end of file
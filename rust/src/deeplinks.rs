@@ -0,0 +1,134 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+use crate::campaigns;
+
+// Signed /start payloads for campaign attribution. A payload is the
+// base64url campaign (and optional data) followed by a truncated HMAC, all
+// within Telegram's 64 characters of [A-Za-z0-9_-]. The signature stops
+// users from forging or editing payloads to inflate a campaign.
+
+pub const MAX_PAYLOAD_LEN: usize = 64;
+// 8 bytes of HMAC-SHA256, base64url without padding
+const SIGNATURE_LEN: usize = 11;
+const SEPARATOR: u8 = 0x1f;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeepLink {
+    pub campaign: String,
+    pub data: Option<String>,
+}
+
+fn signature(body: &str, key: &str) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC key");
+    mac.update(body.as_bytes());
+    URL_SAFE_NO_PAD.encode(&mac.finalize().into_bytes()[..8])
+}
+
+pub fn encode(link: &DeepLink, key: &str) -> Result<String, String> {
+    if link.campaign.is_empty() {
+        return Err("Campaign must not be empty".to_string());
+    }
+    if link.campaign.as_bytes().contains(&SEPARATOR) {
+        return Err("Campaign must not contain U+001F".to_string());
+    }
+    
+    let mut raw = link.campaign.as_bytes().to_vec();
+    if let Some(ref data) = link.data {
+        raw.push(SEPARATOR);
+        raw.extend_from_slice(data.as_bytes());
+    }
+    let body = URL_SAFE_NO_PAD.encode(raw);
+    if body.len() + SIGNATURE_LEN > MAX_PAYLOAD_LEN {
+        return Err(format!("Campaign and data are too long for a {}-character start parameter", MAX_PAYLOAD_LEN));
+    }
+    
+    let signature = signature(&body, key);
+    Ok(body + &signature)
+}
+
+// Accepts the bare payload or the full "/start <payload>" message text
+pub fn decode(payload: &str, key: &str) -> Result<DeepLink, String> {
+    let payload = payload.trim();
+    let payload = payload.strip_prefix("/start").map(str::trim_start).unwrap_or(payload);
+    if payload.len() <= SIGNATURE_LEN || payload.len() > MAX_PAYLOAD_LEN || !payload.is_ascii() {
+        return Err("Malformed deep-link payload".to_string());
+    }
+    
+    let (body, signature_part) = payload.split_at(payload.len() - SIGNATURE_LEN);
+    let expected = signature(body, key);
+    // Constant-time comparison
+    let mismatch = expected.bytes().zip(signature_part.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if mismatch != 0 {
+        return Err("Deep-link signature mismatch".to_string());
+    }
+    
+    let raw = URL_SAFE_NO_PAD.decode(body).map_err(|_| "Malformed deep-link payload".to_string())?;
+    let text = String::from_utf8(raw).map_err(|_| "Malformed deep-link payload".to_string())?;
+    Ok(match text.split_once(SEPARATOR as char) {
+        Some((campaign, data)) => DeepLink { campaign: campaign.to_string(), data: Some(data.to_string()) },
+        None => DeepLink { campaign: text, data: None },
+    })
+}
+
+// Decodes a start payload and counts it for its campaign; payloads failing
+// the signature check are counted as rejected and not attributed
+pub fn track_start(payload: &str, user_id: Option<i64>, key: &str) -> Result<DeepLink, String> {
+    match decode(payload, key) {
+        Ok(link) => {
+            campaigns::record_campaign_start(&link.campaign, user_id);
+            Ok(link)
+        }
+        Err(e) => {
+            campaigns::record_rejected_start();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_payload_roundtrip_and_tampering() {
+        let link = DeepLink { campaign: "spring-sale".to_string(), data: Some("ref:42".to_string()) };
+        let payload = encode(&link, "bot-secret").unwrap();
+        assert!(payload.len() <= MAX_PAYLOAD_LEN);
+        assert!(payload.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        
+        assert_eq!(decode(&payload, "bot-secret").unwrap(), link);
+        assert_eq!(decode(&format!("/start {}", payload), "bot-secret").unwrap(), link);
+        assert!(decode(&payload, "other-secret").is_err());
+        
+        let forged = encode(&DeepLink { campaign: "spring-salf".to_string(), data: Some("ref:42".to_string()) }, "x").unwrap();
+        let spliced = format!("{}{}", &forged[..forged.len() - SIGNATURE_LEN], &payload[payload.len() - SIGNATURE_LEN..]);
+        assert!(decode(&spliced, "bot-secret").is_err());
+        
+        assert!(encode(&DeepLink { campaign: "c".repeat(60), data: None }, "k").is_err());
+        assert!(encode(&DeepLink { campaign: String::new(), data: None }, "k").is_err());
+        assert!(decode("short", "k").is_err());
+    }
+    
+    #[test]
+    fn test_track_start_counts_campaigns() {
+        let context = crate::tenant::TenantContext::new(Some("deeplink_test_bot"), None);
+        crate::tenant::with_tenant(context, || {
+            let payload = encode(&DeepLink { campaign: "channel-post".to_string(), data: None }, "k").unwrap();
+            campaigns::record_campaign_impressions("channel-post", 10);
+            track_start(&payload, Some(1), "k").unwrap();
+            track_start(&payload, Some(1), "k").unwrap();
+            track_start(&payload, Some(2), "k").unwrap();
+            assert!(track_start("AAAAAAAAAAAAAAAAAAAA", Some(3), "k").is_err());
+            
+            let report = campaigns::campaign_report(3600);
+            let stats = &report.campaigns[0];
+            assert_eq!(stats.campaign, "channel-post");
+            assert_eq!((stats.impressions, stats.starts, stats.unique_users), (10, 3, 2));
+            assert_eq!(stats.click_through_rate, Some(0.3));
+            assert_eq!(report.rejected_starts, 1);
+        });
+    }
+}
\ No newline at end of file
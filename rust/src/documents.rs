@@ -0,0 +1,188 @@
+use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
+use serde::{Serialize, Deserialize};
+
+// Plain text of PDF and DOCX files users send to the bot, for the same
+// analysis messages get. PDFs go through pdf-extract; a DOCX is a zip
+// archive whose word/document.xml holds the body, read here without an XML
+// parser since only runs of text, tabs, breaks and paragraph ends matter.
+// Headers, footers, footnotes and comments are left out, and so is any text
+// a PDF only has as images.
+
+// Telegram bots cannot download larger files
+pub const MAX_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DocumentFormat {
+    PDF,
+    DOCX,
+}
+
+impl DocumentFormat {
+    // By content rather than file name, which users rename freely
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"%PDF-") {
+            Some(DocumentFormat::PDF)
+        } else if bytes.starts_with(b"PK\x03\x04") {
+            Some(DocumentFormat::DOCX)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractedDocument {
+    pub format: DocumentFormat,
+    // Paragraphs separated by line breaks, at most one blank line in a row
+    pub text: String,
+}
+
+pub fn extract(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(format!("Document of {} bytes exceeds the limit of {}", bytes.len(), MAX_DOCUMENT_BYTES));
+    }
+    let format = DocumentFormat::detect(bytes).ok_or("Not a PDF or DOCX document")?;
+    let text = match format {
+        DocumentFormat::PDF => pdf_text(bytes)?,
+        DocumentFormat::DOCX => docx_text(bytes)?,
+    };
+    Ok(ExtractedDocument { format, text: tidy(&text) })
+}
+
+fn pdf_text(bytes: &[u8]) -> Result<String, String> {
+    // pdf-extract panics on some malformed files instead of failing
+    match panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(bytes))) {
+        Ok(Ok(text)) => Ok(text),
+        Ok(Err(e)) => Err(format!("Cannot read PDF: {}", e)),
+        Err(_) => Err("Cannot read PDF: malformed document".to_string()),
+    }
+}
+
+fn docx_text(bytes: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Cannot read DOCX: {}", e))?;
+    let mut body = archive.by_name("word/document.xml").map_err(|_| "Not a DOCX document: word/document.xml is missing".to_string())?;
+    let mut xml = String::new();
+    body.by_ref().take(MAX_DOCUMENT_BYTES as u64 * 4).read_to_string(&mut xml).map_err(|e| format!("Cannot read DOCX: {}", e))?;
+    Ok(document_xml_text(&xml))
+}
+
+// Text of the <w:t> runs; <w:tab/>, <w:br/> and paragraph ends become tabs
+// and line breaks
+fn document_xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_run_text = false;
+    // <w:tab/> inside <w:tabs> defines a tab stop, not a tab
+    let mut in_tab_stops = false;
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        if in_run_text {
+            text.push_str(&unescape(&rest[..open]));
+        }
+        let close = match rest[open..].find('>') {
+            Some(i) => open + i,
+            None => break,
+        };
+        let tag = &rest[open + 1..close];
+        let closing = tag.starts_with('/');
+        let empty = tag.ends_with('/');
+        let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        match name {
+            "w:t" => in_run_text = !closing && !empty,
+            "w:tabs" => in_tab_stops = !closing && !empty,
+            "w:tab" if !closing && !in_tab_stops => text.push('\t'),
+            "w:br" | "w:cr" if !closing => text.push('\n'),
+            "w:p" if closing || empty => text.push('\n'),
+            _ => {}
+        }
+        rest = &rest[close + 1..];
+    }
+    text
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        let entity_end = rest[amp..].find(';').map(|i| amp + i);
+        let decoded = entity_end.and_then(|end| match &rest[amp + 1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|decimal| decimal.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (decoded, entity_end) {
+            (Some(c), Some(end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[amp + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+// Trailing spaces go, and runs of blank lines shrink to one
+fn tidy(text: &str) -> String {
+    let mut tidied = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !tidied.is_empty() {
+            tidied.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        tidied.push_str(line);
+        blank_lines = 0;
+    }
+    tidied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    
+    #[test]
+    fn test_docx_text() {
+        let xml = r#"<?xml version="1.0"?><w:document><w:body>
+            <w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr>
+            <w:r><w:t>Order</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">#42 &amp; &#x2713; </w:t></w:r></w:p>
+            <w:p/>
+            <w:p><w:r><w:t>سلام</w:t><w:br/><w:t>&lt;done&gt;</w:t></w:r></w:p>
+            </w:body></w:document>"#;
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut buffer);
+            writer.start_file("word/document.xml", SimpleFileOptions::default()).unwrap();
+            writer.write_all(xml.as_bytes()).unwrap();
+            writer.finish().unwrap();
+        }
+        let document = extract(buffer.get_ref()).unwrap();
+        assert_eq!(document.format, DocumentFormat::DOCX);
+        assert_eq!(document.text, "Order\t#42 & ✓\n\nسلام\n<done>");
+        
+        assert!(extract(b"plain text").is_err());
+        assert!(extract(b"%PDF-1.4 truncated").is_err());
+        assert!(extract(b"PK\x03\x04 truncated").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::sentiment;
+
+// Emoji and emoticon counts for a text. Emoji are counted per grapheme
+// cluster, so ZWJ families, flags and keycaps count once; skin tones and
+// variation selectors fold into the base emoji. Emoji sentiment comes from
+// the active sentiment lexicon, emoticons from the table below.
+
+const EMOTICONS: &[(&str, f64)] = &[
+    (":)", 1.3), (":-)", 1.3), ("=)", 1.3), (":]", 1.3), (":D", 2.3), (":-D", 2.3), ("xD", 1.5),
+    ("XD", 1.5), (";)", 1.2), (";-)", 1.2), (":P", 1.0), (":-P", 1.0), (":p", 1.0), (":*", 1.5),
+    ("<3", 2.6), ("^_^", 1.8), ("^^", 1.5), (":(", -1.3), (":-(", -1.3), (":'(", -2.1),
+    (":/", -0.8), (":-/", -0.8), (":|", -0.3), (">:(", -2.3), ("</3", -2.5), ("T_T", -2.1),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmojiCount {
+    pub emoji: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmojiStats {
+    // Emoji and emoticons together
+    pub total: usize,
+    // Most used first
+    pub emoji: Vec<EmojiCount>,
+    pub emoticons: Vec<EmojiCount>,
+    // Mean valence of the emoji and emoticons the lexicon knows, -1 to 1
+    pub sentiment_score: f64,
+    pub sentiment: String,
+}
+
+impl Default for EmojiStats {
+    fn default() -> Self {
+        Self {
+            total: 0,
+            emoji: Vec::new(),
+            emoticons: Vec::new(),
+            sentiment_score: 0.0,
+            sentiment: "neutral".to_string(),
+        }
+    }
+}
+
+impl EmojiStats {
+    // Totals over several texts; the sentiment is averaged weighted by each
+    // text's total
+    pub fn combine<'a>(stats: impl IntoIterator<Item = &'a EmojiStats>) -> Self {
+        let mut emoji: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut emoticons: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut weighted_score = 0.0;
+        for stats in stats {
+            for (counts, found) in [(&mut emoji, &stats.emoji), (&mut emoticons, &stats.emoticons)] {
+                for item in found {
+                    let first = counts.len();
+                    counts.entry(item.emoji.clone()).or_insert((0, first)).0 += item.count;
+                }
+            }
+            weighted_score += stats.sentiment_score * stats.total as f64;
+        }
+        let total: usize = emoji.values().chain(emoticons.values()).map(|(count, _)| count).sum();
+        let sentiment_score = if total == 0 { 0.0 } else { weighted_score / total as f64 };
+        EmojiStats {
+            total,
+            emoji: ranked(emoji),
+            emoticons: ranked(emoticons),
+            sentiment_score,
+            sentiment: sentiment::label(sentiment_score).to_string(),
+        }
+    }
+}
+
+fn is_emoji_char(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2300..=0x23FF | 0x2B00..=0x2BFF)
+}
+
+// Text symbols such as © or digits only count with emoji presentation
+// (U+FE0F) or as keycaps (U+20E3)
+pub(crate) fn is_emoji(grapheme: &str) -> bool {
+    grapheme.chars().any(|c| is_emoji_char(c) || c == '\u{FE0F}' || c == '\u{20E3}')
+}
+
+// Drops variation selectors and skin tone modifiers
+fn emoji_key(grapheme: &str) -> String {
+    let key: String = grapheme.chars().filter(|&c| !matches!(c as u32, 0xFE0E | 0xFE0F | 0x1F3FB..=0x1F3FF)).collect();
+    if key.is_empty() { grapheme.to_string() } else { key }
+}
+
+// The emoticon a whitespace-separated token stands for; ":)))" counts as
+// ":)" and "xDDD" as "xD"
+fn emoticon(token: &str) -> Option<(&'static str, f64)> {
+    let mut chars: Vec<char> = token.trim_end_matches(['.', ',', '!', '?']).chars().collect();
+    while chars.len() > 2 && chars[chars.len() - 1] == chars[chars.len() - 2] {
+        chars.pop();
+    }
+    let key: String = chars.into_iter().collect();
+    EMOTICONS.iter().find(|(emoticon, _)| *emoticon == key).copied()
+}
+
+fn ranked(counts: HashMap<String, (usize, usize)>) -> Vec<EmojiCount> {
+    let mut counts: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    // Ties keep the order of first use
+    counts.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.1.1.cmp(&b.1.1)));
+    counts.into_iter().map(|(emoji, (count, _))| EmojiCount { emoji, count }).collect()
+}
+
+pub fn stats(text: &str) -> EmojiStats {
+    let analyzer = sentiment::active();
+    let mut valences = Vec::new();
+    
+    let mut emoji: HashMap<String, (usize, usize)> = HashMap::new();
+    for grapheme in text.graphemes(true).filter(|g| is_emoji(g)) {
+        let key = emoji_key(grapheme);
+        valences.extend(analyzer.valence(&key));
+        let first = emoji.len();
+        emoji.entry(key).or_insert((0, first)).0 += 1;
+    }
+    
+    let mut emoticons: HashMap<String, (usize, usize)> = HashMap::new();
+    for (found, valence) in text.split_whitespace().filter_map(emoticon) {
+        valences.push(valence);
+        let first = emoticons.len();
+        emoticons.entry(found.to_string()).or_insert((0, first)).0 += 1;
+    }
+    
+    let total = emoji.values().chain(emoticons.values()).map(|(count, _)| count).sum();
+    let sentiment_score = if valences.is_empty() { 0.0 } else { valences.iter().sum::<f64>() / valences.len() as f64 / 4.0 };
+    EmojiStats {
+        total,
+        emoji: ranked(emoji),
+        emoticons: ranked(emoticons),
+        sentiment_score,
+        sentiment: sentiment::label(sentiment_score).to_string(),
+    }
+}
+
+// The text without emoji and emoticons, words separated by single spaces
+pub fn strip(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|token| emoticon(token).is_none())
+        .map(|token| token.graphemes(true).filter(|g| !is_emoji(g)).collect::<String>())
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_counts_and_sentiment() {
+        let counted = stats("Shipped 👍🏽👍 :))) ❤️ 1️⃣ 👨‍👩‍👧 ©, but 😢");
+        assert_eq!(counted.emoji[0], EmojiCount { emoji: "👍".to_string(), count: 2 });
+        let emoji: Vec<&str> = counted.emoji.iter().map(|e| e.emoji.as_str()).collect();
+        assert_eq!(emoji, vec!["👍", "❤", "1\u{20E3}", "👨‍👩‍👧", "😢"]);
+        assert_eq!(counted.emoticons, vec![EmojiCount { emoji: ":)".to_string(), count: 1 }]);
+        assert_eq!(counted.total, 7);
+        assert_eq!(counted.sentiment, "positive");
+        
+        let sad = stats("ok :'( 😭");
+        assert_eq!(sad.sentiment, "negative");
+        assert_eq!(stats("plain text, no emoji"), EmojiStats::default());
+    }
+    
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip("lol xDDD 🔥🔥 nice🎉 release!! :)"), "lol nice release!!");
+        assert_eq!(strip("سلام 🌹 دوستان"), "سلام دوستان");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,304 @@
+use std::sync::{Arc, RwLock};
+use regex::{Regex, RegexBuilder};
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+pub use crate::config::EntityPattern;
+
+// The regexes behind entity extraction, kept in a registry instead of
+// compiled in, so a deployment can teach the analyzer its own IDs (order
+// numbers, ticket IDs, ...) or drop a built-in that misfires on its chats.
+// The active registry is the built-ins, then analysis.disabled_entity_patterns
+// and analysis.entity_patterns from the config, then whatever was added or
+// removed at runtime, in that order; a config reload rebuilds it and keeps
+// the runtime changes. Patterns are tried in registry order and the first
+// to claim a span wins, with patterns added by name ahead of the built-ins.
+
+// Patterns beyond the built-ins, so a runaway caller cannot make every
+// analysis run thousands of regexes
+pub const MAX_PATTERNS: usize = 256;
+// Compiled program size per pattern; the default of the regex crate is 10 MB
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+// (name, entity type, pattern, confidence), highest priority first. URLs
+// and emails are delimited, so they go first: a date or percentage inside a
+// link must not claim part of it and drop the rest.
+const BUILTIN_PATTERNS: &[(&str, &str, &str, f64)] = &[
+    ("url", "URL", r"https?://[^\s]+", 0.9),
+    ("email", "EMAIL", r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b", 0.95),
+    // \d also matches Persian and Arabic-Indic digits
+    ("date", "DATE", concat!(
+        r"(?i)\b(?:",
+        r"\d{4}-\d{1,2}-\d{1,2}(?:T\d{2}:\d{2}(?::\d{2})?Z?)?",
+        r"|\d{4}[/.]\d{1,2}[/.]\d{1,2}",
+        r"|\d{1,2}[/.]\d{1,2}[/.]\d{2,4}",
+        r"|(?:jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.?\s+\d{1,2}(?:st|nd|rd|th)?(?:,?\s+\d{4})?",
+        r"|\d{1,2}(?:st|nd|rd|th)?\s+(?:of\s+)?(?:jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)(?:,?\s+\d{4})?",
+        r"|\d{1,2}\s+(?:فروردین|اردیبهشت|خرداد|تیر|مرداد|شهریور|مهر|آبان|آذر|دی|بهمن|اسفند)(?:\s+(?:ماه\s+)?\d{4})?",
+        r")\b",
+    ), 0.85),
+    ("time", "TIME", r"(?i)\b\d{1,2}:\d{2}(?::\d{2})?(?:\s*(?:[ap]m\b|[ap]\.m\.))?|\b\d{1,2}\s*(?:[ap]m\b|[ap]\.m\.)|ساعت\s+\d{1,2}(?::\d{2})?\b", 0.85),
+    ("money", "MONEY", concat!(
+        r"(?i)[$€£¥₽]\s?\d[\d,]*(?:\.\d+)?(?:\s?[kmb]\b)?",
+        r"|\b(?:usd|eur|gbp)\s?\d[\d,]*(?:\.\d+)?",
+        r"|\b\d[\d,٬]*(?:[.٫]\d+)?\s?(?:usd|eur|gbp|rub|irr|irt|dollars?|euros?|rials?|tomans?|تومان|تومن|ریال|دلار|یورو)\b",
+    ), 0.9),
+    ("percent", "PERCENT", r"(?i)\b\d+(?:[.٫]\d+)?\s?(?:%|٪|percent\b|per cent\b|درصد)", 0.95),
+    ("person", "PERSON", r"\b[A-Z][a-z]+ [A-Z][a-z]+\b", 0.8),
+    ("phone", "PHONE", r"\b\d{3}[-.]?\d{3}[-.]?\d{4}\b", 0.85),
+];
+
+// Byte offsets into the text and what the pattern found there
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternMatch {
+    pub start: usize,
+    pub end: usize,
+    pub entity_type: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledPattern {
+    pattern: EntityPattern,
+    regex: Regex,
+    builtin: bool,
+}
+
+// Checks a pattern the way the registry would use it
+pub fn compile(pattern: &EntityPattern) -> Result<Regex, String> {
+    if pattern.name.trim().is_empty() || pattern.entity_type.trim().is_empty() {
+        return Err("Entity patterns need a name and an entity type".to_string());
+    }
+    if !(0.0..=1.0).contains(&pattern.confidence) {
+        return Err(format!("Confidence of entity pattern '{}' must be 0-1", pattern.name));
+    }
+    let regex = RegexBuilder::new(&pattern.pattern)
+        .case_insensitive(pattern.case_insensitive)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid entity pattern '{}': {}", pattern.name, e))?;
+    if pattern.group >= regex.captures_len() {
+        return Err(format!("Entity pattern '{}' has no capture group {}", pattern.name, pattern.group));
+    }
+    Ok(regex)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EntityPatternRegistry {
+    // Priority order
+    patterns: Vec<CompiledPattern>,
+}
+
+impl EntityPatternRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // url, email, date, time, money, percent, person and phone
+    pub fn builtin() -> Self {
+        let patterns = BUILTIN_PATTERNS.iter().map(|&(name, entity_type, pattern, confidence)| {
+            let pattern = EntityPattern {
+                name: name.to_string(),
+                entity_type: entity_type.to_string(),
+                pattern: pattern.to_string(),
+                confidence,
+                group: 0,
+                case_insensitive: false,
+            };
+            let regex = compile(&pattern).expect("built-in entity pattern");
+            CompiledPattern { pattern, regex, builtin: true }
+        }).collect();
+        Self { patterns }
+    }
+    
+    // Replaces the pattern of the same name where it stands; a new one goes
+    // after earlier additions and ahead of the built-ins
+    pub fn add(&mut self, pattern: EntityPattern) -> Result<(), String> {
+        let regex = compile(&pattern)?;
+        let mut pattern = pattern;
+        pattern.entity_type = pattern.entity_type.trim().to_uppercase();
+        if let Some(existing) = self.patterns.iter_mut().find(|existing| existing.pattern.name == pattern.name) {
+            existing.pattern = pattern;
+            existing.regex = regex;
+            return Ok(());
+        }
+        if self.patterns.iter().filter(|existing| !existing.builtin).count() >= MAX_PATTERNS {
+            return Err(format!("At most {} entity patterns can be added", MAX_PATTERNS));
+        }
+        let position = self.patterns.iter().position(|existing| existing.builtin).unwrap_or(self.patterns.len());
+        self.patterns.insert(position, CompiledPattern { pattern, regex, builtin: false });
+        Ok(())
+    }
+    
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.patterns.len();
+        self.patterns.retain(|existing| existing.pattern.name != name);
+        self.patterns.len() < before
+    }
+    
+    pub fn patterns(&self) -> Vec<EntityPattern> {
+        self.patterns.iter().map(|compiled| compiled.pattern.clone()).collect()
+    }
+    
+    // Every match of every pattern, pattern by pattern in priority order;
+    // overlaps are for the caller to settle
+    pub fn find(&self, text: &str) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        for compiled in &self.patterns {
+            let group = compiled.pattern.group;
+            for captures in compiled.regex.captures_iter(text) {
+                // An optional group may not take part in the match
+                if let Some(found) = captures.get(group).filter(|found| !found.is_empty()) {
+                    matches.push(PatternMatch {
+                        start: found.start(),
+                        end: found.end(),
+                        entity_type: compiled.pattern.entity_type.clone(),
+                        confidence: compiled.pattern.confidence,
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
+
+lazy_static! {
+    // Runtime additions (Some) and removals (None) by name, oldest first
+    static ref RUNTIME_CHANGES: RwLock<Vec<(String, Option<EntityPattern>)>> = RwLock::new(Vec::new());
+    static ref ACTIVE: RwLock<Arc<EntityPatternRegistry>> = RwLock::new(Arc::new(EntityPatternRegistry::builtin()));
+}
+
+fn build(config: &AppConfig, changes: &[(String, Option<EntityPattern>)]) -> Result<EntityPatternRegistry, String> {
+    let mut registry = EntityPatternRegistry::builtin();
+    for name in &config.analysis.disabled_entity_patterns {
+        registry.remove(name);
+    }
+    for pattern in &config.analysis.entity_patterns {
+        registry.add(pattern.clone())?;
+    }
+    for (name, change) in changes {
+        match change {
+            Some(pattern) => registry.add(pattern.clone())?,
+            None => {
+                registry.remove(name);
+            }
+        }
+    }
+    Ok(registry)
+}
+
+fn change(name: &str, pattern: Option<EntityPattern>) -> Result<(), String> {
+    let mut changes = RUNTIME_CHANGES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut updated = changes.clone();
+    updated.retain(|(existing, _)| existing != name);
+    updated.push((name.to_string(), pattern));
+    let registry = build(&AppConfig::get(), &updated)?;
+    *changes = updated;
+    *ACTIVE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(registry);
+    Ok(())
+}
+
+pub fn initialize() -> Result<(), String> {
+    reload_config();
+    Ok(())
+}
+
+// The config was validated before it was applied, so only the runtime
+// changes can fail here, e.g. a runtime pattern past the limit; those are
+// dropped rather than the whole registry
+pub fn reload_config() {
+    let config = AppConfig::get();
+    let mut changes = RUNTIME_CHANGES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let registry = match build(&config, &changes) {
+        Ok(registry) => registry,
+        Err(e) => {
+            crate::logging::warn(&format!("Dropping runtime entity patterns: {}", e), "entity_patterns", "reload_config", line!());
+            changes.clear();
+            build(&config, &[]).unwrap_or_else(|_| EntityPatternRegistry::builtin())
+        }
+    };
+    *ACTIVE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(registry);
+}
+
+pub fn add(pattern: EntityPattern) -> Result<(), String> {
+    let name = pattern.name.clone();
+    change(&name, Some(pattern))
+}
+
+// Built-ins and config patterns can be removed too; false when no pattern
+// of that name is active
+pub fn remove(name: &str) -> bool {
+    let active = ACTIVE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    if !active.patterns.iter().any(|compiled| compiled.pattern.name == name) {
+        return false;
+    }
+    change(name, None).is_ok()
+}
+
+pub fn list() -> Vec<EntityPattern> {
+    ACTIVE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).patterns()
+}
+
+pub fn find(text: &str) -> Vec<PatternMatch> {
+    let active = ACTIVE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    active.find(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn pattern(name: &str, entity_type: &str, pattern: &str, group: usize) -> EntityPattern {
+        EntityPattern {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            pattern: pattern.to_string(),
+            confidence: 0.9,
+            group,
+            case_insensitive: true,
+        }
+    }
+    
+    #[test]
+    fn test_registry_order_and_groups() {
+        let mut registry = EntityPatternRegistry::builtin();
+        // Ahead of the built-ins, so the phone pattern cannot claim the digits
+        registry.add(pattern("ticket", "ticket_id", r"\btck-(\d{10})\b", 1)).unwrap();
+        let types: Vec<(String, usize)> = registry.find("TCK-5551234567").into_iter().map(|m| (m.entity_type, m.start)).collect();
+        assert_eq!(types[0], ("TICKET_ID".to_string(), 4));
+        assert_eq!(registry.patterns()[0].name, "ticket");
+        
+        // Replacing keeps the position; removing works for built-ins too
+        registry.add(pattern("phone", "PHONE", r"\b09\d{9}\b", 0)).unwrap();
+        assert_eq!(registry.patterns().last().unwrap().pattern, r"\b09\d{9}\b");
+        assert!(registry.remove("person"));
+        assert!(!registry.remove("person"));
+        assert!(registry.find("Sara Karimi").is_empty());
+        
+        assert!(registry.add(pattern("bad", "X", r"(", 0)).is_err());
+        assert!(registry.add(pattern("no_group", "X", r"\d+", 1)).is_err());
+        assert!(registry.add(pattern("", "X", r"\d+", 0)).is_err());
+    }
+    
+    #[test]
+    fn test_urls_keep_the_dates_and_percentages_inside_them() {
+        let entities = crate::analysis::extract_entities("see https://news.example.com/2024/05/01/story and https://shop.example.com/sale?off=50% today");
+        let spans: Vec<(&str, &str)> = entities.iter().map(|e| (e.entity_type.as_str(), e.name.as_str())).collect();
+        assert_eq!(spans, [("URL", "https://news.example.com/2024/05/01/story"), ("URL", "https://shop.example.com/sale?off=50%")]);
+    }
+    
+    #[test]
+    fn test_runtime_patterns_reach_entity_extraction() {
+        let order = pattern("order_number_test", "ORDER", r"\bORD-\d{6}\b", 0);
+        add(order).unwrap();
+        let entities = crate::analysis::extract_entities("Where is ORD-123456?");
+        assert_eq!((entities[0].entity_type.as_str(), entities[0].name.as_str()), ("ORDER", "ORD-123456"));
+        assert!(list().iter().any(|p| p.name == "order_number_test"));
+        
+        // A config reload keeps runtime changes
+        reload_config();
+        assert_eq!(find("ORD-654321")[0].entity_type, "ORDER");
+        assert!(remove("order_number_test"));
+        assert!(!remove("order_number_test"));
+        assert!(crate::analysis::extract_entities("Where is ORD-123456?").is_empty());
+    }
+}
\ No newline at end of file
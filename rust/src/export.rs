@@ -0,0 +1,465 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize, Deserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use crate::config::AppConfig;
+use crate::paths::{self, PathKind};
+
+// Spreadsheet export of analysis results and reports. Records of any
+// serializable type are flattened into a table: nested objects become dotted
+// columns ("visualization_data.box_plot"), lists of plain values are joined
+// with "; ", and lists of objects (entities, top stickers, cohorts) are kept
+// as JSON in the cell and, in XLSX, also get a worksheet of their own with a
+// row per item. Columns keep the field order of the records.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    CSV,
+    XLSX,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "csv" => Some(Self::CSV),
+            "xlsx" | "excel" => Some(Self::XLSX),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    EMPTY,
+    BOOL(bool),
+    NUMBER(f64),
+    TEXT(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+// JSON value that keeps object keys in document order; serde_json's Map
+// sorts them
+enum Node {
+    NULL,
+    BOOL(bool),
+    NUMBER(f64),
+    TEXT(String),
+    ARRAY(Vec<Node>),
+    OBJECT(Vec<(String, Node)>),
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+    
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+    
+    fn visit_unit<E>(self) -> Result<Node, E> {
+        Ok(Node::NULL)
+    }
+    
+    fn visit_none<E>(self) -> Result<Node, E> {
+        Ok(Node::NULL)
+    }
+    
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Node, D::Error> {
+        Node::deserialize(deserializer)
+    }
+    
+    fn visit_bool<E>(self, value: bool) -> Result<Node, E> {
+        Ok(Node::BOOL(value))
+    }
+    
+    fn visit_i64<E>(self, value: i64) -> Result<Node, E> {
+        Ok(Node::NUMBER(value as f64))
+    }
+    
+    fn visit_u64<E>(self, value: u64) -> Result<Node, E> {
+        Ok(Node::NUMBER(value as f64))
+    }
+    
+    fn visit_f64<E>(self, value: f64) -> Result<Node, E> {
+        Ok(Node::NUMBER(value))
+    }
+    
+    fn visit_str<E>(self, value: &str) -> Result<Node, E> {
+        Ok(Node::TEXT(value.to_string()))
+    }
+    
+    fn visit_string<E>(self, value: String) -> Result<Node, E> {
+        Ok(Node::TEXT(value))
+    }
+    
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::ARRAY(items))
+    }
+    
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut fields = Vec::new();
+        while let Some((key, value)) = map.next_entry::<String, Node>()? {
+            fields.push((key, value));
+        }
+        Ok(Node::OBJECT(fields))
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+impl Node {
+    fn is_scalar(&self) -> bool {
+        !matches!(self, Node::ARRAY(_) | Node::OBJECT(_))
+    }
+    
+    fn to_json(&self) -> String {
+        match self {
+            Node::NULL => "null".to_string(),
+            Node::BOOL(value) => value.to_string(),
+            Node::NUMBER(value) => number_text(*value),
+            Node::TEXT(value) => serde_json::Value::String(value.clone()).to_string(),
+            Node::ARRAY(items) => format!("[{}]", items.iter().map(Node::to_json).collect::<Vec<_>>().join(",")),
+            Node::OBJECT(fields) => format!("{{{}}}", fields.iter()
+                .map(|(key, value)| format!("{}:{}", serde_json::Value::String(key.clone()), value.to_json()))
+                .collect::<Vec<_>>()
+                .join(",")),
+        }
+    }
+    
+    fn to_text(&self) -> String {
+        match self {
+            Node::NULL => String::new(),
+            Node::TEXT(value) => value.clone(),
+            Node::NUMBER(value) => number_text(*value),
+            other => other.to_json(),
+        }
+    }
+}
+
+// Whole numbers without a trailing ".0"
+fn number_text(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+// Lists of objects found while flattening: column path and items per record
+type Details = Vec<(String, Vec<(usize, Vec<Node>)>)>;
+
+fn flatten(prefix: &str, node: Node, record: usize, cells: &mut Vec<(String, Cell)>, details: Option<&mut Details>) {
+    let column = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    match node {
+        Node::OBJECT(fields) => {
+            let mut details = details;
+            for (key, value) in fields {
+                flatten(&column(&key), value, record, cells, details.as_deref_mut());
+            }
+        }
+        Node::ARRAY(items) if items.iter().all(Node::is_scalar) => {
+            let joined = items.iter().map(Node::to_text).collect::<Vec<_>>().join("; ");
+            cells.push((prefix.to_string(), Cell::TEXT(joined)));
+        }
+        Node::ARRAY(items) => {
+            let json = format!("[{}]", items.iter().map(Node::to_json).collect::<Vec<_>>().join(","));
+            cells.push((prefix.to_string(), Cell::TEXT(json)));
+            if let Some(details) = details {
+                if items.iter().all(|item| matches!(item, Node::OBJECT(_))) {
+                    match details.iter_mut().find(|(path, _)| path == prefix) {
+                        Some((_, records)) => records.push((record, items)),
+                        None => details.push((prefix.to_string(), vec![(record, items)])),
+                    }
+                }
+            }
+        }
+        Node::NULL => cells.push((prefix.to_string(), Cell::EMPTY)),
+        Node::BOOL(value) => cells.push((prefix.to_string(), Cell::BOOL(value))),
+        Node::NUMBER(value) => cells.push((prefix.to_string(), Cell::NUMBER(value))),
+        Node::TEXT(value) => cells.push((prefix.to_string(), Cell::TEXT(value))),
+    }
+}
+
+// Columns in order of first appearance, so records missing a field still
+// line up
+fn build_table(name: String, records: Vec<Vec<(String, Cell)>>) -> Table {
+    let mut columns: Vec<String> = Vec::new();
+    for cells in &records {
+        for (column, _) in cells {
+            if !columns.contains(column) {
+                columns.push(column.clone());
+            }
+        }
+    }
+    let rows = records.into_iter().map(|cells| {
+        let mut row = vec![Cell::EMPTY; columns.len()];
+        for (column, cell) in cells {
+            if let Some(index) = columns.iter().position(|c| *c == column) {
+                row[index] = cell;
+            }
+        }
+        row
+    }).collect();
+    Table { name, columns, rows }
+}
+
+fn tables_from_nodes(name: &str, nodes: Vec<Node>) -> Vec<Table> {
+    let mut details: Details = Vec::new();
+    let mut records = Vec::new();
+    for (index, node) in nodes.into_iter().enumerate() {
+        let mut cells = Vec::new();
+        // Plain values get a single "value" column
+        let prefix = if matches!(node, Node::OBJECT(_)) { "" } else { "value" };
+        flatten(prefix, node, index + 1, &mut cells, Some(&mut details));
+        records.push(cells);
+    }
+    
+    let mut tables = vec![build_table(name.to_string(), records)];
+    for (path, lists) in details {
+        let rows = lists.into_iter()
+            .flat_map(|(record, items)| items.into_iter().map(move |item| (record, item)))
+            .map(|(record, item)| {
+                // The row of the parent record in the main table
+                let mut cells = vec![("row".to_string(), Cell::NUMBER(record as f64))];
+                flatten("", item, record, &mut cells, None);
+                cells
+            })
+            .collect();
+        tables.push(build_table(format!("{}.{}", name, path), rows));
+    }
+    tables
+}
+
+fn parse_nodes(json: &str) -> Result<Vec<Node>, String> {
+    match serde_json::from_str::<Node>(json).map_err(|e| format!("Invalid records: {}", e))? {
+        Node::ARRAY(items) => Ok(items),
+        node => Ok(vec![node]),
+    }
+}
+
+// The first table holds the records, one per row; the rest hold their lists
+// of objects
+pub fn tables<T: Serialize>(name: &str, records: &[T]) -> Result<Vec<Table>, String> {
+    // Serialized and re-read so columns keep the struct field order
+    let json = serde_json::to_string(records).map_err(|e| format!("Cannot serialize records: {}", e))?;
+    Ok(tables_from_nodes(name, parse_nodes(&json)?))
+}
+
+// A JSON array of records, or a single record
+pub fn tables_from_json(name: &str, records_json: &str) -> Result<Vec<Table>, String> {
+    Ok(tables_from_nodes(name, parse_nodes(records_json)?))
+}
+
+// Spreadsheets run text starting with these as a formula
+fn defuse(text: &str) -> String {
+    if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// RFC 4180 with a UTF-8 byte order mark, which Excel needs to read Persian
+// and other non-Latin text correctly
+pub fn to_csv(table: &Table) -> Vec<u8> {
+    let mut csv = String::from('\u{feff}');
+    let header: Vec<String> = table.columns.iter().map(|column| csv_field(column)).collect();
+    csv.push_str(&header.join(","));
+    csv.push_str("\r\n");
+    for row in &table.rows {
+        let fields: Vec<String> = row.iter().map(|cell| match cell {
+            Cell::EMPTY => String::new(),
+            Cell::BOOL(value) => value.to_string(),
+            Cell::NUMBER(value) => number_text(*value),
+            Cell::TEXT(text) => csv_field(&defuse(text)),
+        }).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv.into_bytes()
+}
+
+// Excel limits: 31 characters without []:*?/\ in sheet names, 32767 in a cell
+#[cfg(feature = "xlsx")]
+const MAX_SHEET_NAME: usize = 31;
+#[cfg(feature = "xlsx")]
+const MAX_CELL_CHARS: usize = 32_767;
+
+#[cfg(feature = "xlsx")]
+fn sheet_name(name: &str, taken: &[String]) -> String {
+    let cleaned: String = name.chars()
+        .map(|c| if matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim_matches('\'');
+    let base: String = if cleaned.is_empty() { "Sheet".to_string() } else { cleaned.chars().take(MAX_SHEET_NAME).collect() };
+    let mut candidate = base.clone();
+    let mut n = 2;
+    while taken.iter().any(|t| t.eq_ignore_ascii_case(&candidate)) {
+        let suffix = format!("~{}", n);
+        candidate = format!("{}{}", base.chars().take(MAX_SHEET_NAME - suffix.len()).collect::<String>(), suffix);
+        n += 1;
+    }
+    candidate
+}
+
+// One worksheet per table, with a bold, frozen header row
+#[cfg(feature = "xlsx")]
+pub fn to_xlsx(tables: &[Table]) -> Result<Vec<u8>, String> {
+    use rust_xlsxwriter::{Format, Workbook};
+    
+    let error = |e: rust_xlsxwriter::XlsxError| format!("Cannot write XLSX: {}", e);
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let mut taken: Vec<String> = Vec::new();
+    for table in tables {
+        if table.columns.len() > u16::MAX as usize || table.rows.len() >= 1_048_576 {
+            return Err(format!("Table {} is too large for a worksheet", table.name));
+        }
+        let name = sheet_name(&table.name, &taken);
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(name.as_str()).map_err(error)?;
+        taken.push(name);
+        for (col, column) in table.columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, column.as_str(), &bold).map_err(error)?;
+        }
+        for (index, row) in table.rows.iter().enumerate() {
+            let r = index as u32 + 1;
+            for (col, cell) in row.iter().enumerate() {
+                let col = col as u16;
+                match cell {
+                    Cell::EMPTY => {}
+                    Cell::BOOL(value) => {
+                        sheet.write_boolean(r, col, *value).map_err(error)?;
+                    }
+                    Cell::NUMBER(value) if value.is_finite() => {
+                        sheet.write_number(r, col, *value).map_err(error)?;
+                    }
+                    Cell::NUMBER(value) => {
+                        sheet.write_string(r, col, value.to_string()).map_err(error)?;
+                    }
+                    // Written as strings, so formulas are never evaluated
+                    Cell::TEXT(text) => {
+                        let text: String = text.chars().take(MAX_CELL_CHARS).collect();
+                        sheet.write_string(r, col, text).map_err(error)?;
+                    }
+                }
+            }
+        }
+        sheet.set_freeze_panes(1, 0).map_err(error)?;
+        sheet.autofit();
+    }
+    workbook.save_to_buffer().map_err(error)
+}
+
+// CSV holds the first table only; XLSX holds them all
+pub fn render(tables: &[Table], format: ExportFormat) -> Result<Vec<u8>, String> {
+    match format {
+        ExportFormat::CSV => tables.first().map(to_csv).ok_or_else(|| "Nothing to export".to_string()),
+        #[cfg(feature = "xlsx")]
+        ExportFormat::XLSX => to_xlsx(tables),
+        #[cfg(not(feature = "xlsx"))]
+        ExportFormat::XLSX => Err("XLSX export needs the xlsx feature".to_string()),
+    }
+}
+
+// Relative paths resolve under the data directory; the file is replaced
+// whole, so a download never sees half a report
+pub fn write(path: &str, contents: &[u8]) -> Result<PathBuf, String> {
+    let resolved = paths::resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    write_atomically(&resolved, contents).map_err(|e| format!("Cannot write export {}: {}", resolved.display(), e))?;
+    Ok(resolved)
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[derive(Serialize)]
+    struct Report {
+        chat_id: i64,
+        average: Option<f64>,
+        tags: Vec<String>,
+        window: (i64, i64),
+        top: Vec<Usage>,
+    }
+    
+    #[derive(Serialize)]
+    struct Usage {
+        id: String,
+        count: u64,
+    }
+    
+    #[test]
+    fn test_flattening_keeps_field_order() {
+        let reports = vec![
+            Report { chat_id: -100, average: Some(0.25), tags: vec!["a".to_string(), "b".to_string()], window: (0, 3600), top: vec![Usage { id: "x".to_string(), count: 3 }] },
+            Report { chat_id: -200, average: None, tags: Vec::new(), window: (0, 60), top: Vec::new() },
+        ];
+        let tables = tables("report", &reports).unwrap();
+        assert_eq!(tables[0].columns, vec!["chat_id", "average", "tags", "window", "top"]);
+        assert_eq!(tables[0].rows[0][0], Cell::NUMBER(-100.0));
+        assert_eq!(tables[0].rows[0][2], Cell::TEXT("a; b".to_string()));
+        assert_eq!(tables[0].rows[1][1], Cell::EMPTY);
+        assert_eq!(tables[0].rows[0][4], Cell::TEXT(r#"[{"id":"x","count":3}]"#.to_string()));
+        
+        assert_eq!(tables[1].name, "report.top");
+        assert_eq!(tables[1].columns, vec!["row", "id", "count"]);
+        assert_eq!(tables[1].rows, vec![vec![Cell::NUMBER(1.0), Cell::TEXT("x".to_string()), Cell::NUMBER(3.0)]]);
+        
+        let nested = tables_from_json("data", r#"{"stats": {"mean": 1.5, "max": 3}, "ok": true}"#).unwrap();
+        assert_eq!(nested[0].columns, vec!["stats.mean", "stats.max", "ok"]);
+    }
+    
+    #[test]
+    fn test_csv_quoting_and_formulas() {
+        let tables = tables_from_json("t", r#"[{"text": "say \"hi\", then\nleave", "n": 2}, {"text": "=HYPERLINK(\"x\")", "n": 0.5}]"#).unwrap();
+        let csv = String::from_utf8(to_csv(&tables[0])).unwrap();
+        assert_eq!(csv, "\u{feff}text,n\r\n\"say \"\"hi\"\", then\nleave\",2\r\n\"'=HYPERLINK(\"\"x\"\")\",0.5\r\n");
+        assert!(render(&[], ExportFormat::CSV).is_err());
+        assert_eq!(ExportFormat::parse("Excel"), Some(ExportFormat::XLSX));
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_xlsx_workbook() {
+        let tables = tables_from_json("chat: report/1", r#"[{"id": 1, "top": [{"id": "a"}]}]"#).unwrap();
+        let bytes = to_xlsx(&tables).unwrap();
+        assert!(bytes.starts_with(b"PK"));
+        assert_eq!(sheet_name("chat: report/1", &[]), "chat_ report_1");
+        assert_eq!(sheet_name("a", &["A".to_string()]), "a~2");
+    }
+}
\ No newline at end of file
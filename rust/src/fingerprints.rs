@@ -0,0 +1,360 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+use crate::paths::{self, PathKind};
+use crate::tenant;
+
+// Repost detection. Text gets a 64-bit SimHash over character shingles, so
+// light edits (a changed link, added emoji, reflowed lines) move it by a few
+// bits only. Images get a difference hash of their downscaled luma. Matches
+// are fingerprints of the same kind within a Hamming distance; around 3 bits
+// for text and 10 for images catches reposts without false positives.
+
+// Shorter texts ("thanks!", "ok") repeat everywhere and are not worth matching
+pub const MIN_TEXT_CHARS: usize = 20;
+pub(crate) const SHINGLE_CHARS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FingerprintKind {
+    TEXT,
+    IMAGE,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub kind: FingerprintKind,
+    pub hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct StoredFingerprint {
+    id: String,
+    // Tenant scope the fingerprint was registered under; empty without one
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    scope: String,
+    kind: FingerprintKind,
+    hash: u64,
+    registered_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintMatch {
+    pub id: String,
+    pub kind: FingerprintKind,
+    pub distance: u32,
+    // 1 - distance / 64
+    pub similarity: f64,
+    pub registered_at: i64,
+}
+
+// FNV-1a with a splitmix64 finalizer. SimHash needs every output bit to
+// depend on the whole shingle, and the store needs hashes that stay the same
+// across builds, which rules out std's hasher.
+pub(crate) fn feature_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+// Lowercase letters and digits; everything else collapses to one space
+pub(crate) fn normalize(text: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            chars.push(c);
+        } else if chars.last().is_some_and(|&last| last != ' ') {
+            chars.push(' ');
+        }
+    }
+    if chars.last() == Some(&' ') {
+        chars.pop();
+    }
+    chars
+}
+
+impl Fingerprint {
+    pub fn text(text: &str) -> Result<Self, String> {
+        let chars = normalize(text);
+        if chars.len() < MIN_TEXT_CHARS {
+            return Err(format!("Text needs at least {} letters or digits to fingerprint", MIN_TEXT_CHARS));
+        }
+        
+        let mut weights = [0i64; 64];
+        let mut shingle = String::new();
+        for window in chars.windows(SHINGLE_CHARS) {
+            shingle.clear();
+            shingle.extend(window);
+            let hash = feature_hash(shingle.as_bytes());
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+            }
+        }
+        
+        let hash = weights.iter().enumerate().filter(|(_, &weight)| weight > 0).fold(0u64, |hash, (bit, _)| hash | 1 << bit);
+        Ok(Self { kind: FingerprintKind::TEXT, hash })
+    }
+    
+    // `luma` is the decoded image as 8-bit grayscale, row by row. The image is
+    // averaged down to 9x8 and each bit tells whether a cell is brighter than
+    // its left neighbour, which survives rescaling, recompression and small
+    // colour changes.
+    pub fn image(width: usize, height: usize, luma: &[u8]) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err("Image has no pixels".to_string());
+        }
+        if width.checked_mul(height) != Some(luma.len()) {
+            return Err(format!("Expected {}x{} luma bytes, got {}", width, height, luma.len()));
+        }
+        
+        // Cell edges rounded so every cell covers at least one pixel, also
+        // for images smaller than the grid
+        let cell = |index: usize, cells: usize, size: usize| {
+            let start = (index * size / cells).min(size - 1);
+            (start, ((index + 1) * size / cells).max(start + 1))
+        };
+        let mut grid = [[0u64; 9]; 8];
+        for (row, cells) in grid.iter_mut().enumerate() {
+            let (y0, y1) = cell(row, 8, height);
+            for (column, value) in cells.iter_mut().enumerate() {
+                let (x0, x1) = cell(column, 9, width);
+                let sum: u64 = (y0..y1).flat_map(|y| &luma[y * width + x0..y * width + x1]).map(|&p| p as u64).sum();
+                *value = sum / ((y1 - y0) * (x1 - x0)) as u64;
+            }
+        }
+        
+        let mut hash = 0u64;
+        for (row, cells) in grid.iter().enumerate() {
+            for column in 0..8 {
+                if cells[column + 1] > cells[column] {
+                    hash |= 1 << (row * 8 + column);
+                }
+            }
+        }
+        Ok(Self { kind: FingerprintKind::IMAGE, hash })
+    }
+    
+    // For hosts that already compute a 64-bit perceptual hash of their own;
+    // only compare it against hashes from the same algorithm
+    pub fn image_hash(hash: u64) -> Self {
+        Self { kind: FingerprintKind::IMAGE, hash }
+    }
+    
+    pub fn distance(&self, other: &Fingerprint) -> Option<u32> {
+        (self.kind == other.kind).then(|| (self.hash ^ other.hash).count_ones())
+    }
+}
+
+// Registered fingerprints, appended as JSON lines to a file when the store
+// has one. Lookups scan every fingerprint; popcount keeps that cheap well
+// into hundreds of thousands of posts.
+#[derive(Debug, Default)]
+pub struct FingerprintStore {
+    entries: Vec<StoredFingerprint>,
+    path: Option<PathBuf>,
+}
+
+impl FingerprintStore {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+    
+    // Loads the fingerprints already in the file; it is created on the first
+    // registration. A crash mid-append leaves a partial last line, which is
+    // cut off so the next registration starts on a line of its own.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Cannot read fingerprint store {}: {}", path.display(), e)),
+        };
+        
+        let mut torn_at = None;
+        let mut offset = 0;
+        let mut number = 0;
+        while offset < bytes.len() {
+            let end = bytes[offset..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| offset + i);
+            number += 1;
+            let line = &bytes[offset..end];
+            if !String::from_utf8_lossy(line).trim().is_empty() {
+                match serde_json::from_slice(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(_) if end + 1 >= bytes.len() => torn_at = Some(offset),
+                    Err(e) => return Err(format!("Corrupt fingerprint store {} at line {}: {}", path.display(), number, e)),
+                }
+            }
+            offset = end + 1;
+        }
+        
+        let repaired = match torn_at {
+            Some(at) => OpenOptions::new().write(true).open(&path).and_then(|file| file.set_len(at as u64)),
+            // Complete but unterminated, e.g. after a hand edit
+            None if !bytes.is_empty() && !bytes.ends_with(b"\n") => {
+                OpenOptions::new().append(true).open(&path).and_then(|mut file| file.write_all(b"\n"))
+            }
+            None => Ok(()),
+        };
+        repaired.map_err(|e| format!("Cannot repair fingerprint store {}: {}", path.display(), e))?;
+        Ok(Self { entries, path: Some(path) })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    // One id may carry several fingerprints (a caption and its photo);
+    // registering the same one twice is a no-op
+    pub fn register(&mut self, id: &str, fingerprint: Fingerprint) -> Result<(), String> {
+        let scope = tenant::scope_key("");
+        if self.entries.iter().any(|e| e.id == id && e.scope == scope && e.kind == fingerprint.kind && e.hash == fingerprint.hash) {
+            return Ok(());
+        }
+        
+        let entry = StoredFingerprint {
+            id: id.to_string(),
+            scope,
+            kind: fingerprint.kind,
+            hash: fingerprint.hash,
+            registered_at: Utc::now().timestamp(),
+        };
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+            }
+            let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+            line.push('\n');
+            OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+                .map_err(|e| format!("Cannot write fingerprint store {}: {}", path.display(), e))?;
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+    
+    // Closest first; equally close matches oldest first, so the original
+    // post leads
+    pub fn find_matches(&self, fingerprint: &Fingerprint, max_distance: u32) -> Vec<FingerprintMatch> {
+        let scope = tenant::scope_key("");
+        let mut matches: Vec<FingerprintMatch> = self.entries.iter()
+            .filter(|e| e.scope == scope && e.kind == fingerprint.kind)
+            .filter_map(|e| {
+                let distance = (e.hash ^ fingerprint.hash).count_ones();
+                (distance <= max_distance).then(|| FingerprintMatch {
+                    id: e.id.clone(),
+                    kind: e.kind,
+                    distance,
+                    similarity: 1.0 - distance as f64 / 64.0,
+                    registered_at: e.registered_at,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| (m.distance, m.registered_at));
+        matches
+    }
+}
+
+lazy_static! {
+    static ref STORE: RwLock<FingerprintStore> = RwLock::new(FingerprintStore::in_memory());
+}
+
+// Switches the global store to a file; relative paths resolve under the data
+// directory
+pub fn open_store(path: &str) -> Result<usize, String> {
+    let resolved = paths::resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    let store = FingerprintStore::open(resolved)?;
+    let count = store.len();
+    *STORE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = store;
+    Ok(count)
+}
+
+pub fn register(id: &str, fingerprint: Fingerprint) -> Result<(), String> {
+    STORE.write().unwrap_or_else(|poisoned| poisoned.into_inner()).register(id, fingerprint)
+}
+
+pub fn find_matches(fingerprint: &Fingerprint, max_distance: u32) -> Vec<FingerprintMatch> {
+    STORE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).find_matches(fingerprint, max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_reposts_match_within_distance() {
+        let original = Fingerprint::text("Our new release ships offline mode, faster sync and a redesigned settings screen. Read more on the blog!").unwrap();
+        let repost = Fingerprint::text("our new release ships offline mode, faster sync and a redesigned settings screen 🎉 read more on the blog").unwrap();
+        let unrelated = Fingerprint::text("Meeting moved to Thursday afternoon, please bring the quarterly numbers").unwrap();
+        assert!(original.distance(&repost).unwrap() <= 3);
+        assert!(original.distance(&unrelated).unwrap() > 10);
+        assert!(Fingerprint::text("ok thanks").is_err());
+        
+        let gradient: Vec<u8> = (0..64 * 48).map(|i| ((i % 64) * 4) as u8).collect();
+        let brighter: Vec<u8> = gradient.iter().map(|&p| p.saturating_add(20)).collect();
+        let small: Vec<u8> = (0..32 * 24).map(|i| ((i % 32) * 8) as u8).collect();
+        let image = Fingerprint::image(64, 48, &gradient).unwrap();
+        assert_eq!(image.distance(&Fingerprint::image(64, 48, &brighter).unwrap()), Some(0));
+        assert!(image.distance(&Fingerprint::image(32, 24, &small).unwrap()).unwrap() <= 10);
+        assert_eq!(image.distance(&original), None);
+        assert!(Fingerprint::image(4, 4, &[0; 15]).is_err());
+    }
+    
+    #[test]
+    fn test_store_persists_and_scopes() {
+        let path = std::env::temp_dir().join(format!("aiogram_fingerprints_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let post = Fingerprint::text("Limited offer: two months of premium for the price of one, this week only").unwrap();
+        
+        let mut store = FingerprintStore::open(&path).unwrap();
+        store.register("channel/41", Fingerprint::image_hash(0xff00)).unwrap();
+        store.register("channel/42", post).unwrap();
+        store.register("channel/42", post).unwrap();
+        tenant::with_tenant(tenant::TenantContext::new(Some("other_bot"), None), || {
+            assert!(store.find_matches(&post, 3).is_empty());
+        });
+        
+        let reopened = FingerprintStore::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        let matches = reopened.find_matches(&post, 3);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].id.as_str(), matches[0].distance), ("channel/42", 0));
+        assert_eq!(reopened.find_matches(&Fingerprint::image_hash(0xff01), 1)[0].id, "channel/41");
+        let _ = fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn test_torn_last_line_is_cut_before_appending() {
+        let path = std::env::temp_dir().join(format!("aiogram_fingerprints_torn_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut store = FingerprintStore::open(&path).unwrap();
+        store.register("channel/1", Fingerprint::image_hash(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":"channel/2","sco"#).unwrap();
+        drop(file);
+        
+        let mut store = FingerprintStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        store.register("channel/3", Fingerprint::image_hash(3)).unwrap();
+        
+        let reopened = FingerprintStore::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.find_matches(&Fingerprint::image_hash(3), 0)[0].id, "channel/3");
+        let _ = fs::remove_file(&path);
+    }
+}
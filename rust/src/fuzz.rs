@@ -0,0 +1,99 @@
+use crate::parsers;
+
+// Entry points for the cargo-fuzz targets in fuzz/. Each one feeds arbitrary
+// bytes to a parser and panics if one of its invariants breaks, so any crash
+// libFuzzer reports is either a parser panic or a broken invariant.
+
+const FUZZ_KEY: &str = "fuzz-key";
+
+fn as_text(data: &[u8]) -> &str {
+    std::str::from_utf8(data).unwrap_or("")
+}
+
+pub fn json_structure(data: &[u8]) {
+    let input = as_text(data);
+    
+    match parsers::check_json_structure(input) {
+        Ok(depth) => {
+            assert!(depth <= parsers::MAX_JSON_DEPTH);
+        }
+        Err(parsers::JsonStructureError::Unbalanced) => {
+            // Anything serde accepts as an array or object must pair up
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(input) {
+                assert!(!value.is_array() && !value.is_object(), "valid JSON reported as unbalanced");
+            }
+        }
+        Err(parsers::JsonStructureError::TooDeep) => {}
+    }
+}
+
+pub fn numbers(data: &[u8]) {
+    let input = as_text(data);
+    let numbers = parsers::parse_numbers(input);
+    
+    assert!(numbers.len() <= parsers::number_tokens(input).count());
+    assert!(numbers.iter().all(|n| n.is_finite()));
+}
+
+#[cfg(feature = "realtime")]
+pub fn realtime_json(data: &[u8]) {
+    let result = crate::realtime::process_realtime_data(as_text(data));
+    
+    assert!(result.processing_speed.is_finite());
+    if crate::realtime::parse_realtime_data(as_text(data)).is_err() {
+        assert_eq!(result.status, "error");
+    }
+}
+
+#[cfg(feature = "crypto")]
+pub fn crypto_envelope(data: &[u8]) {
+    // Arbitrary envelopes must be rejected cleanly, never decrypted
+    let _ = crate::crypto::decrypt(as_text(data), FUZZ_KEY);
+    assert!(crate::crypto::open(data, FUZZ_KEY).is_err());
+    
+    if let Ok(message) = std::str::from_utf8(data) {
+        let encrypted = crate::crypto::encrypt(message, FUZZ_KEY).unwrap();
+        assert_eq!(crate::crypto::decrypt(&encrypted, FUZZ_KEY).unwrap(), message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Seeds for the shapes that used to get through: deep nesting, huge
+    // tokens, braces inside strings and non-finite numbers
+    fn adversarial_inputs() -> Vec<Vec<u8>> {
+        vec![
+            "[".repeat(200_000).into_bytes(),
+            ("{\"a\":".repeat(1_000) + "1" + &"}".repeat(1_000)).into_bytes(),
+            "9".repeat(1_000_000).into_bytes(),
+            br#"{"content": "}{][", "x": "\"\\"}"#.to_vec(),
+            b"NaN,inf,-inf,1e309,0x10,,,\t\r\n".to_vec(),
+            br#"{"timestamp": 1e999, "user_id": -1, "data_type": "numeric_data", "content": "1,2"}"#.to_vec(),
+            vec![0xff, 0xfe, b'{', 0x00],
+            b"AGS\x01".to_vec(),
+            Vec::new(),
+        ]
+    }
+    
+    #[test]
+    fn test_adversarial_inputs_keep_invariants() {
+        for input in adversarial_inputs() {
+            json_structure(&input);
+            numbers(&input);
+            #[cfg(feature = "realtime")]
+            realtime_json(&input);
+        }
+    }
+    
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_crypto_envelope_invariants() {
+        use base64::{Engine as _, engine::general_purpose};
+        for input in adversarial_inputs().into_iter().take(4) {
+            crypto_envelope(&input[..input.len().min(4096)]);
+        }
+        crypto_envelope(general_purpose::STANDARD.encode([0u8; 31]).as_bytes());
+    }
+}
\ No newline at end of file
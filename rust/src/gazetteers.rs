@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use regex::{Regex, RegexBuilder};
+use lazy_static::lazy_static;
+
+// Dictionary lookups for entity extraction: company and product names,
+// Persian person names and whatever else the host knows by name. All loaded
+// names compile into one case-insensitive regex (matched as whole words,
+// longest name first), so a lookup is a single pass over the text however
+// many names are loaded.
+
+pub const GAZETTEER_CONFIDENCE: f64 = 0.9;
+
+// Byte offsets into the text and the entity type of the name found there
+#[derive(Debug, Clone, PartialEq)]
+pub struct GazetteerMatch {
+    pub start: usize,
+    pub end: usize,
+    pub entity_type: String,
+}
+
+struct Matcher {
+    pattern: Regex,
+    // Normalized name -> entity type
+    types: HashMap<String, String>,
+}
+
+lazy_static! {
+    // Gazetteer name -> (normalized name -> entity type)
+    static ref GAZETTEERS: RwLock<BTreeMap<String, HashMap<String, String>>> = RwLock::new(BTreeMap::new());
+    static ref MATCHER: RwLock<Option<Arc<Matcher>>> = RwLock::new(None);
+}
+
+// Arabic yeh and kaf to their Persian forms; the replacements keep byte
+// lengths, so offsets found in the normalized text hold for the
+// original
+fn normalize(text: &str) -> String {
+    text.chars().map(|c| match c {
+        'ي' | 'ى' => 'ی',
+        'ك' => 'ک',
+        c => c,
+    }).collect::<String>()
+}
+
+fn name_key(name: &str) -> String {
+    normalize(&name.split_whitespace().collect::<Vec<_>>().join(" ")).to_lowercase()
+}
+
+// JSON {"ORG": ["Acme", ...], "PRODUCT": [...]} or [{"name": ..., "type": ...}],
+// or CSV lines of "name,type" (the last comma separates them, so names may
+// contain commas); default_type covers CSV lines without a type
+pub fn parse(contents: &str, default_type: Option<&str>) -> Result<Vec<(String, String)>, String> {
+    let trimmed = contents.trim_start();
+    if trimmed.starts_with('{') {
+        let by_type: BTreeMap<String, Vec<String>> = serde_json::from_str(contents).map_err(|e| format!("Invalid gazetteer JSON: {}", e))?;
+        return Ok(by_type.into_iter().flat_map(|(entity_type, names)| names.into_iter().map(move |name| (name, entity_type.clone()))).collect());
+    }
+    if trimmed.starts_with('[') {
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            name: String,
+            #[serde(rename = "type")]
+            entity_type: String,
+        }
+        let entries: Vec<Entry> = serde_json::from_str(contents).map_err(|e| format!("Invalid gazetteer JSON: {}", e))?;
+        return Ok(entries.into_iter().map(|entry| (entry.name, entry.entity_type)).collect());
+    }
+    
+    let mut entries = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() || line.starts_with('#') || (number == 0 && line.eq_ignore_ascii_case("name,type")) {
+            continue;
+        }
+        let (name, entity_type) = match (line.rsplit_once(','), default_type) {
+            (Some((name, entity_type)), _) if !entity_type.trim().is_empty() => (name, entity_type.trim()),
+            (_, Some(default_type)) => (line, default_type),
+            _ => return Err(format!("Gazetteer line {} has no entity type: {}", number + 1, line)),
+        };
+        entries.push((name.trim().trim_matches('"').to_string(), entity_type.to_string()));
+    }
+    Ok(entries)
+}
+
+fn rebuild(gazetteers: &BTreeMap<String, HashMap<String, String>>) -> Result<(), String> {
+    // When two gazetteers list the same name, the later one by name wins
+    let types: HashMap<String, String> = gazetteers.values().flat_map(|names| names.clone()).collect();
+    let matcher = if types.is_empty() {
+        None
+    } else {
+        let mut names: Vec<&String> = types.keys().collect();
+        // Longest first, so "Acme Cloud" wins over "Acme"
+        names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let alternation = names.iter()
+            .map(|name| name.split(' ').map(regex::escape).collect::<Vec<_>>().join(r"\s+"))
+            .collect::<Vec<_>>()
+            .join("|");
+        // Large name lists outgrow the default compiled size limit
+        let pattern = RegexBuilder::new(&format!(r"(?i)\b(?:{})\b", alternation))
+            .size_limit(256 << 20)
+            .build()
+            .map_err(|e| format!("Cannot compile gazetteers: {}", e))?;
+        Some(Arc::new(Matcher { pattern, types }))
+    };
+    *MATCHER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = matcher;
+    Ok(())
+}
+
+// Adds or replaces the named gazetteer
+pub fn register(name: &str, entries: Vec<(String, String)>) -> Result<usize, String> {
+    let names: HashMap<String, String> = entries.into_iter()
+        .map(|(entry, entity_type)| (name_key(&entry), entity_type.trim().to_uppercase()))
+        .filter(|(entry, entity_type)| !entry.is_empty() && !entity_type.is_empty())
+        .collect();
+    let count = names.len();
+    
+    let mut gazetteers = GAZETTEERS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let previous = gazetteers.insert(name.to_string(), names);
+    if let Err(e) = rebuild(&gazetteers) {
+        match previous {
+            Some(previous) => gazetteers.insert(name.to_string(), previous),
+            None => gazetteers.remove(name),
+        };
+        return Err(e);
+    }
+    Ok(count)
+}
+
+pub fn load(name: &str, path: impl AsRef<Path>, default_type: Option<&str>) -> Result<usize, String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read gazetteer {}: {}", path.display(), e))?;
+    register(name, parse(&contents, default_type)?)
+}
+
+pub fn remove(name: &str) -> bool {
+    let mut gazetteers = GAZETTEERS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if gazetteers.remove(name).is_none() {
+        return false;
+    }
+    // Fewer names can only make the pattern smaller
+    let _ = rebuild(&gazetteers);
+    true
+}
+
+pub fn find(text: &str) -> Vec<GazetteerMatch> {
+    let matcher = match MATCHER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone() {
+        Some(matcher) => matcher,
+        None => return Vec::new(),
+    };
+    let normalized = normalize(text);
+    matcher.pattern.find_iter(&normalized)
+        .filter_map(|m| {
+            let entity_type = matcher.types.get(&name_key(m.as_str()))?;
+            Some(GazetteerMatch { start: m.start(), end: m.end(), entity_type: entity_type.clone() })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_formats() {
+        let json = parse(r#"{"ORG": ["Acme"], "PRODUCT": ["Acme Cloud"]}"#, None).unwrap();
+        assert_eq!(json, vec![("Acme".to_string(), "ORG".to_string()), ("Acme Cloud".to_string(), "PRODUCT".to_string())]);
+        let list = parse(r#"[{"name": "Snapp", "type": "ORG"}]"#, None).unwrap();
+        assert_eq!(list, vec![("Snapp".to_string(), "ORG".to_string())]);
+        let csv = parse("name,type\n\"Acme, Inc.\",ORG\nعلی رضایی\n", Some("PERSON")).unwrap();
+        assert_eq!(csv[0], ("Acme, Inc.".to_string(), "ORG".to_string()));
+        assert_eq!(csv[1], ("علی رضایی".to_string(), "PERSON".to_string()));
+        assert!(parse("Acme", None).is_err());
+    }
+    
+    #[test]
+    fn test_matches_with_spans() {
+        register("gazetteer_test", vec![
+            ("Zarrin Soft".to_string(), "org".to_string()),
+            ("Zarrin Soft Cloud".to_string(), "PRODUCT".to_string()),
+            ("علی رضایی".to_string(), "PERSON".to_string()),
+        ]).unwrap();
+        
+        let text = "علي رضايي moved from ZARRIN SOFT to the zarrin soft  cloud team; zarrinsoft is unrelated";
+        let matches = find(text);
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].entity_type, "PERSON");
+        assert_eq!(&text[matches[0].start..matches[0].end], "علي رضايي");
+        assert_eq!(&text[matches[1].start..matches[1].end], "ZARRIN SOFT");
+        assert_eq!(matches[1].entity_type, "ORG");
+        assert_eq!(&text[matches[2].start..matches[2].end], "zarrin soft  cloud");
+        assert_eq!(matches[2].entity_type, "PRODUCT");
+        
+        assert!(remove("gazetteer_test"));
+        assert!(find(text).iter().all(|m| m.entity_type != "PRODUCT"));
+    }
+}
\ No newline at end of file
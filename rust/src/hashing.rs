@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Digest};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Incremental hashing for inputs too large to pass in one call, such as
+// multi-GB file downloads: begin returns a handle, update feeds it chunks
+// and finish returns the hex digest and releases it. Each stream has its
+// own lock, so concurrent downloads hash in parallel.
+
+// Streams a host forgot to finish or abort; begin fails past this
+const MAX_OPEN_STREAMS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('-', "").as_str() {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+}
+
+enum State {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    HmacSha256(Hmac<Sha256>),
+}
+
+impl State {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            State::Sha256(hasher) => hasher.update(data),
+            State::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            State::HmacSha256(mac) => mac.update(data),
+        }
+    }
+    
+    fn finish(self) -> String {
+        match self {
+            State::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            State::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            State::HmacSha256(mac) => format!("{:x}", mac.finalize().into_bytes()),
+        }
+    }
+}
+
+lazy_static! {
+    static ref STREAMS: Mutex<HashMap<u64, Arc<Mutex<Option<State>>>>> = Mutex::new(HashMap::new());
+}
+
+// Handles start at 1, so 0 can signal a failed begin over FFI
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+fn open(state: State) -> Result<u64, String> {
+    let mut streams = STREAMS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if streams.len() >= MAX_OPEN_STREAMS {
+        return Err(format!("{} hash streams are open; finish or abort some first", MAX_OPEN_STREAMS));
+    }
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    streams.insert(handle, Arc::new(Mutex::new(Some(state))));
+    Ok(handle)
+}
+
+fn stream(handle: u64) -> Result<Arc<Mutex<Option<State>>>, String> {
+    STREAMS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&handle)
+        .cloned()
+        .ok_or_else(|| format!("Unknown hash stream {}", handle))
+}
+
+pub fn begin(algorithm: HashAlgorithm) -> Result<u64, String> {
+    open(match algorithm {
+        HashAlgorithm::Sha256 => State::Sha256(Sha256::new()),
+        HashAlgorithm::Blake3 => State::Blake3(Box::new(blake3::Hasher::new())),
+    })
+}
+
+// HMAC-SHA256, or BLAKE3's keyed mode, which needs no HMAC construction;
+// BLAKE3 keys that are not 32 bytes are hashed down to 32 first
+pub fn begin_hmac(algorithm: HashAlgorithm, key: &[u8]) -> Result<u64, String> {
+    open(match algorithm {
+        HashAlgorithm::Sha256 => State::HmacSha256(Hmac::<Sha256>::new_from_slice(key).map_err(|e| e.to_string())?),
+        HashAlgorithm::Blake3 => {
+            let key: [u8; 32] = key.try_into().unwrap_or_else(|_| *blake3::hash(key).as_bytes());
+            State::Blake3(Box::new(blake3::Hasher::new_keyed(&key)))
+        }
+    })
+}
+
+pub fn update(handle: u64, data: &[u8]) -> Result<(), String> {
+    let stream = stream(handle)?;
+    let mut state = stream.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // None once a concurrent finish has taken it
+    state.as_mut().ok_or_else(|| format!("Hash stream {} is finished", handle))?.update(data);
+    Ok(())
+}
+
+// Lowercase hex digest; the handle is invalid afterwards
+pub fn finish(handle: u64) -> Result<String, String> {
+    let stream = STREAMS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(&handle)
+        .ok_or_else(|| format!("Unknown hash stream {}", handle))?;
+    let state = stream.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    state.map(State::finish).ok_or_else(|| format!("Hash stream {} is finished", handle))
+}
+
+// Drops a stream without a digest, e.g. for a cancelled download
+pub fn abort(handle: u64) -> bool {
+    STREAMS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&handle).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_chunked_digests_match_one_shot() {
+        let data = vec![7u8; 100_000];
+        let sha = begin(HashAlgorithm::Sha256).unwrap();
+        let blake = begin(HashAlgorithm::Blake3).unwrap();
+        for chunk in data.chunks(4096) {
+            update(sha, chunk).unwrap();
+            update(blake, chunk).unwrap();
+        }
+        assert_eq!(finish(sha).unwrap(), format!("{:x}", Sha256::digest(&data)));
+        assert_eq!(finish(blake).unwrap(), blake3::hash(&data).to_hex().to_string());
+        assert!(update(sha, b"late").is_err());
+        assert!(finish(sha).is_err());
+        
+        // RFC 4231 test case 2
+        let mac = begin_hmac(HashAlgorithm::Sha256, b"Jefe").unwrap();
+        update(mac, b"what do ya want ").unwrap();
+        update(mac, b"for nothing?").unwrap();
+        assert_eq!(finish(mac).unwrap(), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        
+        let keyed = begin_hmac(HashAlgorithm::Blake3, &[1u8; 32]).unwrap();
+        update(keyed, b"file").unwrap();
+        assert_eq!(finish(keyed).unwrap(), blake3::keyed_hash(&[1u8; 32], b"file").to_hex().to_string());
+        
+        let abandoned = begin(HashAlgorithm::Blake3).unwrap();
+        assert!(abort(abandoned));
+        assert!(!abort(abandoned));
+        assert_eq!(HashAlgorithm::from_name("SHA-256"), Some(HashAlgorithm::Sha256));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// CLDR plural categories. Which of them a language uses, and for which
+// counts, is decided by its plural rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PluralCategory {
+    ZERO,
+    ONE,
+    TWO,
+    FEW,
+    MANY,
+    OTHER,
+}
+
+impl PluralCategory {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "zero" => Some(PluralCategory::ZERO),
+            "one" => Some(PluralCategory::ONE),
+            "two" => Some(PluralCategory::TWO),
+            "few" => Some(PluralCategory::FEW),
+            "many" => Some(PluralCategory::MANY),
+            "other" => Some(PluralCategory::OTHER),
+            _ => None,
+        }
+    }
+}
+
+pub type PluralRule = fn(f64) -> PluralCategory;
+
+fn english(n: f64) -> PluralCategory {
+    if n == 1.0 { PluralCategory::ONE } else { PluralCategory::OTHER }
+}
+
+// Persian treats 0 and 1 alike ("0 کتاب" / "1 کتاب")
+fn persian(n: f64) -> PluralCategory {
+    if n.fract() == 0.0 && n.abs() <= 1.0 { PluralCategory::ONE } else { PluralCategory::OTHER }
+}
+
+// Russian and Ukrainian: 1, 21, 31 / 2-4, 22-24 / 5-20, 25-30 / fractions
+fn east_slavic(n: f64) -> PluralCategory {
+    if n.fract() != 0.0 {
+        return PluralCategory::OTHER;
+    }
+    let n = n.abs() as u64;
+    match (n % 10, n % 100) {
+        (1, m) if m != 11 => PluralCategory::ONE,
+        (2..=4, m) if !(12..=14).contains(&m) => PluralCategory::FEW,
+        _ => PluralCategory::MANY,
+    }
+}
+
+lazy_static! {
+    static ref PLURAL_RULES: RwLock<HashMap<String, PluralRule>> = {
+        let mut rules: HashMap<String, PluralRule> = HashMap::new();
+        rules.insert("en".to_string(), english);
+        rules.insert("fa".to_string(), persian);
+        rules.insert("ru".to_string(), east_slavic);
+        rules.insert("uk".to_string(), east_slavic);
+        RwLock::new(rules)
+    };
+}
+
+// "pt-BR" and "pt_br" both resolve to "pt"
+fn base_language(lang: &str) -> String {
+    lang.split(['-', '_']).next().unwrap_or("").trim().to_lowercase()
+}
+
+// Adds or replaces the rule for a language; region suffixes are ignored
+pub fn register_plural_rule(lang: &str, rule: PluralRule) {
+    let mut rules = PLURAL_RULES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    rules.insert(base_language(lang), rule);
+}
+
+// Languages without a registered rule fall back to English
+pub fn plural_category(lang: &str, n: f64) -> PluralCategory {
+    let rules = PLURAL_RULES.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let rule = rules.get(&base_language(lang)).copied().unwrap_or(english);
+    rule(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_plural_categories() {
+        assert_eq!(plural_category("en", 1.0), PluralCategory::ONE);
+        assert_eq!(plural_category("en-GB", 2.0), PluralCategory::OTHER);
+        assert_eq!(plural_category("fa", 0.0), PluralCategory::ONE);
+        assert_eq!(plural_category("ru", 21.0), PluralCategory::ONE);
+        assert_eq!(plural_category("ru_RU", 23.0), PluralCategory::FEW);
+        assert_eq!(plural_category("ru", 12.0), PluralCategory::MANY);
+        assert_eq!(plural_category("ru", 1.5), PluralCategory::OTHER);
+        assert_eq!(plural_category("xx", 1.0), PluralCategory::ONE);
+        
+        register_plural_rule("ja", |_| PluralCategory::OTHER);
+        assert_eq!(plural_category("JA", 1.0), PluralCategory::OTHER);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use lazy_static::lazy_static;
+use crate::analysis::{self, AnalysisOptions, TextAnalysisResult, Topic};
+use crate::emoji::EmojiStats;
+use crate::language::{self, LanguageSpan, ScriptStats};
+use crate::readability::ReadabilityReport;
+use crate::summary::{self, SummaryOptions};
+use crate::tags::TagStats;
+use crate::transliterate::{transliterate, TransliterationDirection};
+use crate::{abbreviations, keywords, sentiment, structure};
+
+// Analysis of texts too long for one call, such as a 2 MB exported chat log,
+// fed in chunks. Chunks are buffered into segments of about SEGMENT_BYTES,
+// ended after a sentence where possible, and each segment goes through the
+// regular pipeline as soon as it is complete; only running totals are kept
+// between segments. Counts, entities, emoji, tags and readability come out
+// as for the whole text. Sentiment is the word-weighted mean of the
+// segments, keywords and topics are ranked by frequency over the whole text
+// (a background TF-IDF corpus or trained topic model is not consulted), and
+// the summary is a summary of the segment summaries. Plugins do not run.
+
+// Pipeline work per segment by default; a chunk larger than this is split
+const SEGMENT_BYTES: usize = 64 * 1024;
+const MIN_SEGMENT_BYTES: usize = 1024;
+// Analyses a host forgot to finish or abort; begin fails past this
+const MAX_OPEN_ANALYSES: usize = 64;
+const KEYWORD_COUNT: usize = 5;
+const TOPIC_COUNT: usize = 3;
+
+// Words sharing a stem, or identical words without stemming, counted over
+// every segment
+#[derive(Default)]
+struct WordCounts {
+    // Surface forms in order of first use, and count
+    groups: Vec<(Vec<String>, usize)>,
+    index: HashMap<String, usize>,
+    words: usize,
+}
+
+impl WordCounts {
+    fn add(&mut self, word: &str, language: &str, stemmed: bool) {
+        let key = if stemmed { analysis::stem(word, language) } else { word.to_string() };
+        let groups = &mut self.groups;
+        let i = *self.index.entry(key).or_insert_with(|| {
+            groups.push((Vec::new(), 0));
+            groups.len() - 1
+        });
+        let (forms, count) = &mut groups[i];
+        *count += 1;
+        if !forms.iter().any(|form| form == word) {
+            forms.push(word.to_string());
+        }
+    }
+    
+    // Most frequent first; equal counts keep first-occurrence order
+    fn top(&self, limit: usize) -> Vec<&(Vec<String>, usize)> {
+        let mut ranked: Vec<&(Vec<String>, usize)> = self.groups.iter().collect();
+        ranked.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        ranked.truncate(limit);
+        ranked
+    }
+}
+
+pub struct IncrementalTextAnalyzer {
+    options: AnalysisOptions,
+    segment_bytes: usize,
+    // Text not yet analyzed, shorter than a segment between pushes
+    pending: String,
+    // A code fence opened in an earlier segment and not closed yet
+    in_code_block: bool,
+    segments: usize,
+    // Bytes of (normalized) text analyzed so far, the offset of the next
+    // segment's entities and language spans
+    offset: usize,
+    char_count: usize,
+    word_count: usize,
+    sentence_count: usize,
+    language_spans: Vec<LanguageSpan>,
+    script_stats: Vec<ScriptStats>,
+    weighted_sentiment: f64,
+    keywords: WordCounts,
+    topic_words: WordCounts,
+    emoji_stats: Vec<EmojiStats>,
+    tags: Vec<TagStats>,
+    spam: Option<crate::spam::SpamScore>,
+    entities: Vec<analysis::Entity>,
+    summaries: Vec<String>,
+    readability: Vec<ReadabilityReport>,
+    plagiarism_score: f64,
+    processing_time: u64,
+    stage_timings: BTreeMap<String, f64>,
+    timed_out: bool,
+}
+
+impl IncrementalTextAnalyzer {
+    pub fn new(options: AnalysisOptions) -> Self {
+        Self {
+            options,
+            segment_bytes: SEGMENT_BYTES,
+            pending: String::new(),
+            in_code_block: false,
+            segments: 0,
+            offset: 0,
+            char_count: 0,
+            word_count: 0,
+            sentence_count: 0,
+            language_spans: Vec::new(),
+            script_stats: Vec::new(),
+            weighted_sentiment: 0.0,
+            keywords: WordCounts::default(),
+            topic_words: WordCounts::default(),
+            emoji_stats: Vec::new(),
+            tags: Vec::new(),
+            spam: None,
+            entities: Vec::new(),
+            summaries: Vec::new(),
+            readability: Vec::new(),
+            plagiarism_score: 0.0,
+            processing_time: 0,
+            stage_timings: BTreeMap::new(),
+            timed_out: false,
+        }
+    }
+    
+    // Smaller segments keep each push short, larger ones summarize better;
+    // at least MIN_SEGMENT_BYTES
+    pub fn with_segment_bytes(mut self, bytes: usize) -> Self {
+        self.segment_bytes = bytes.max(MIN_SEGMENT_BYTES);
+        self
+    }
+    
+    // Chunks are joined as they are, so a word or sentence may continue in
+    // the next one
+    pub fn push(&mut self, chunk: &str) {
+        self.pending.push_str(chunk);
+        while self.pending.len() >= self.segment_bytes {
+            let end = segment_end(&self.pending, self.segment_bytes);
+            let rest = self.pending.split_off(end);
+            let segment = std::mem::replace(&mut self.pending, rest);
+            self.analyze_segment(&segment);
+        }
+    }
+    
+    // Bytes pushed but not analyzed yet
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.len()
+    }
+    
+    pub fn finish(mut self) -> TextAnalysisResult {
+        if !self.pending.is_empty() || self.segments == 0 {
+            let segment = std::mem::take(&mut self.pending);
+            self.analyze_segment(&segment);
+        }
+        
+        let (language, language_confidence) = main_language(&self.language_spans);
+        let sentiment_score = if self.word_count == 0 { 0.0 } else { self.weighted_sentiment / self.word_count as f64 };
+        let sentiment = sentiment::label_in(sentiment_score, &language);
+        let keywords = self.keywords.top(KEYWORD_COUNT).into_iter().map(|(forms, _)| forms[0].clone()).collect();
+        let topics = if self.options.skip_topics {
+            Vec::new()
+        } else {
+            self.topic_words.top(TOPIC_COUNT).into_iter().map(|(forms, count)| Topic {
+                name: forms[0].clone(),
+                weight: *count as f64 / self.topic_words.words as f64,
+                keywords: forms.clone(),
+            }).collect()
+        };
+        let summary = if self.summaries.len() == 1 {
+            self.summaries.remove(0)
+        } else {
+            let summary_options = SummaryOptions {
+                max_sentences: self.options.summary_sentences,
+                max_chars: self.options.summary_max_chars,
+                preserve_entities: true,
+            };
+            summary::summarize(&self.summaries.join(" "), &language, &summary_options)
+        };
+        
+        TextAnalysisResult {
+            char_count: self.char_count,
+            word_count: self.word_count,
+            // Each cut joins the last piece of one segment's sentence split
+            // with the first of the next
+            sentence_count: self.sentence_count - (self.segments - 1),
+            language,
+            language_confidence,
+            language_spans: self.language_spans,
+            script_stats: ScriptStats::combine(&self.script_stats),
+            sentiment: sentiment.to_string(),
+            sentiment_score,
+            keywords,
+            emoji_stats: EmojiStats::combine(&self.emoji_stats),
+            tags: TagStats::combine(&self.tags),
+            spam: self.spam,
+            // Lists and code blocks run across segments, so structure needs
+            // the whole text
+            structure: None,
+            entities: self.entities,
+            summary,
+            readability_score: ReadabilityReport::combine(&self.readability),
+            topics,
+            plagiarism_score: self.plagiarism_score,
+            processing_time: self.processing_time,
+            stage_timings: self.stage_timings,
+            plugins: serde_json::Map::new(),
+            timed_out: self.timed_out,
+        }
+    }
+    
+    fn analyze_segment(&mut self, segment: &str) {
+        // Blanked and normalized here rather than by the pipeline, so fences
+        // carry over and offsets add up
+        let blanked = self.options.skip_code.then(|| structure::blank_code_continuing(segment, &mut self.in_code_block));
+        let segment = blanked.as_deref().unwrap_or(segment);
+        let normalized = self.options.normalize.as_ref().map(|normalizer| normalizer.normalize(segment));
+        let text = normalized.as_deref().unwrap_or(segment);
+        let expanded = self.options.expand_abbreviations.then(|| abbreviations::expand(text));
+        let text = expanded.as_deref().unwrap_or(text);
+        let transliterated = self.options.finglish.then(|| transliterate(text, TransliterationDirection::ToPersian));
+        let text = transliterated.as_deref().unwrap_or(text);
+        
+        let options = AnalysisOptions {
+            normalize: None,
+            structure: false,
+            skip_code: false,
+            expand_abbreviations: false,
+            finglish: false,
+            skip_topics: true,
+            skip_plugins: true,
+            ..self.options.clone()
+        };
+        let result = analysis::analyze_text_with_options(text, &options);
+        
+        // Keywords and topics need counts the result does not keep
+        let spans = &result.language_spans;
+        let terms = if self.options.noun_phrase_keywords { keywords::noun_phrase_terms(text, spans) } else { keywords::tagged_terms(text, spans) };
+        for (term, language) in &terms {
+            self.keywords.add(term, language, self.options.stem);
+        }
+        if !self.options.skip_topics {
+            let words = language::words(text, spans);
+            self.topic_words.words += words.len();
+            for (word, language) in words.into_iter().filter(|(word, _)| word.len() > 3) {
+                self.topic_words.add(word, language, self.options.stem);
+            }
+        }
+        
+        let offset = self.offset;
+        for span in result.language_spans {
+            let span = LanguageSpan { start: span.start + offset, end: span.end + offset, ..span };
+            match self.language_spans.last_mut() {
+                Some(last) if last.language == span.language && last.end == span.start => {
+                    let (a, b) = ((last.end - last.start) as f64, (span.end - span.start) as f64);
+                    last.confidence = (last.confidence * a + span.confidence * b) / (a + b);
+                    last.end = span.end;
+                }
+                _ => self.language_spans.push(span),
+            }
+        }
+        self.entities.extend(result.entities.into_iter().map(|entity| analysis::Entity {
+            start: entity.start + offset,
+            end: entity.end + offset,
+            ..entity
+        }));
+        
+        self.segments += 1;
+        self.offset += text.len();
+        self.char_count += result.char_count;
+        self.word_count += result.word_count;
+        self.sentence_count += result.sentence_count;
+        self.script_stats.push(result.script_stats);
+        self.weighted_sentiment += result.sentiment_score * result.word_count as f64;
+        self.emoji_stats.push(result.emoji_stats);
+        self.tags.push(result.tags);
+        if let Some(spam) = result.spam {
+            if self.spam.as_ref().is_none_or(|worst| spam.score > worst.score) {
+                self.spam = Some(spam);
+            }
+        }
+        if !result.summary.is_empty() {
+            self.summaries.push(result.summary);
+        }
+        self.readability.push(result.readability_score);
+        self.plagiarism_score = self.plagiarism_score.max(result.plagiarism_score);
+        self.processing_time += result.processing_time;
+        for (stage, ms) in result.stage_timings {
+            *self.stage_timings.entry(stage).or_insert(0.0) += ms;
+        }
+        self.timed_out |= result.timed_out;
+    }
+}
+
+// Where the segment at the start of text ends: after the last sentence
+// within segment_bytes, else before the last whitespace, else wherever a
+// character ends
+fn segment_end(text: &str, segment_bytes: usize) -> usize {
+    let mut window = segment_bytes.min(text.len());
+    while !text.is_char_boundary(window) {
+        window -= 1;
+    }
+    let head = &text[..window];
+    let sentence_end = head.char_indices()
+        .zip(head.chars().skip(1))
+        .filter(|((_, c), next)| matches!(c, '.' | '!' | '?') && next.is_whitespace())
+        .map(|((i, _), _)| i + 1)
+        .last();
+    sentence_end
+        .or_else(|| head.rfind(char::is_whitespace).filter(|&i| i > 0))
+        .unwrap_or(window)
+}
+
+// The language most of the text is in, with its length-weighted confidence
+fn main_language(spans: &[LanguageSpan]) -> (String, f64) {
+    let mut lengths: HashMap<&str, (usize, f64)> = HashMap::new();
+    for span in spans {
+        let length = (span.end - span.start) as f64;
+        let entry = lengths.entry(span.language.as_str()).or_insert((0, 0.0));
+        entry.0 += span.end - span.start;
+        entry.1 += span.confidence * length;
+    }
+    // Known languages first, then length, then name for a stable pick
+    lengths.into_iter()
+        .filter(|(_, (length, _))| *length > 0)
+        .max_by(|a, b| (a.0 != "unknown").cmp(&(b.0 != "unknown")).then(a.1.0.cmp(&b.1.0)).then(b.0.cmp(a.0)))
+        .map(|(language, (length, weighted))| (language.to_string(), weighted / length as f64))
+        .unwrap_or_else(|| ("unknown".to_string(), 0.0))
+}
+
+lazy_static! {
+    static ref ANALYSES: Mutex<HashMap<u64, Arc<Mutex<Option<IncrementalTextAnalyzer>>>>> = Mutex::new(HashMap::new());
+}
+
+// Handles start at 1, so 0 can signal a failed begin over FFI
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+// For hosts that cannot hold the analyzer themselves: begin returns a
+// handle that push and finish take
+pub fn begin(options: AnalysisOptions) -> Result<u64, String> {
+    let mut analyses = ANALYSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if analyses.len() >= MAX_OPEN_ANALYSES {
+        return Err(format!("{} incremental analyses are open; finish or abort some first", MAX_OPEN_ANALYSES));
+    }
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    analyses.insert(handle, Arc::new(Mutex::new(Some(IncrementalTextAnalyzer::new(options)))));
+    Ok(handle)
+}
+
+pub fn push(handle: u64, chunk: &str) -> Result<(), String> {
+    let analysis = ANALYSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&handle)
+        .cloned()
+        .ok_or_else(|| format!("Unknown incremental analysis {}", handle))?;
+    let mut analyzer = analysis.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // None once a concurrent finish has taken it
+    analyzer.as_mut().ok_or_else(|| format!("Incremental analysis {} is finished", handle))?.push(chunk);
+    Ok(())
+}
+
+// The handle is invalid afterwards
+pub fn finish(handle: u64) -> Result<TextAnalysisResult, String> {
+    let analysis = ANALYSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(&handle)
+        .ok_or_else(|| format!("Unknown incremental analysis {}", handle))?;
+    let analyzer = analysis.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    analyzer.map(IncrementalTextAnalyzer::finish).ok_or_else(|| format!("Incremental analysis {} is finished", handle))
+}
+
+pub fn abort(handle: u64) -> bool {
+    ANALYSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&handle).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_chunks_add_up_to_the_whole_text() {
+        let paragraph = "Sara shipped the release on 2024-05-01. The release notes are great! Everyone loves the release 👍 #launch\n";
+        let text = paragraph.repeat(60);
+        let options = AnalysisOptions { skip_plagiarism: true, ..AnalysisOptions::default() };
+        let whole = analysis::analyze_text_with_options(&text, &options);
+        
+        let mut incremental = IncrementalTextAnalyzer::new(options).with_segment_bytes(1024);
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            let mut end = rest.len().min(397);
+            while !rest.is_char_boundary(end) {
+                end += 1;
+            }
+            incremental.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        assert!(incremental.pending_bytes() < 1024);
+        let result = incremental.finish();
+        
+        assert!(result.char_count > 0);
+        assert_eq!(result.word_count, whole.word_count);
+        assert_eq!(result.sentence_count, whole.sentence_count);
+        assert_eq!(result.language, whole.language);
+        assert_eq!(result.sentiment, whole.sentiment);
+        assert_eq!(result.keywords[0], whole.keywords[0]);
+        assert_eq!(result.entities.len(), whole.entities.len());
+        assert_eq!(result.entities.last().map(|e| (e.start, e.end)), whole.entities.last().map(|e| (e.start, e.end)));
+        assert_eq!(result.emoji_stats.total, 60);
+        assert_eq!(result.tags.hashtags[0].count, 60);
+        assert_eq!(result.readability_score.words, whole.readability_score.words);
+        assert!(!result.summary.is_empty());
+    }
+    
+    #[test]
+    fn test_handles() {
+        let handle = begin(AnalysisOptions::default()).unwrap();
+        push(handle, "Hello wor").unwrap();
+        push(handle, "ld. Bye.").unwrap();
+        let result = finish(handle).unwrap();
+        assert_eq!((result.word_count, result.sentence_count), (3, 3));
+        assert_eq!(result.summary, "Hello world. Bye.");
+        assert!(push(handle, "more").is_err());
+        assert!(!abort(handle));
+        assert_eq!(IncrementalTextAnalyzer::new(AnalysisOptions::default()).finish().word_count, 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,351 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+use crate::deadline::{self, Deadline};
+use crate::tenant::{self, TenantContext};
+
+// Finished jobs that are never polled are dropped this long after they
+// finish, at the next submit
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JobStatus {
+    PENDING,
+    RUNNING,
+    COMPLETED,
+    CANCELLED,
+    FAILED,
+}
+
+impl JobStatus {
+    fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::COMPLETED | JobStatus::CANCELLED | JobStatus::FAILED)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSnapshot {
+    pub job_id: u64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+struct Job {
+    id: u64,
+    kind: String,
+    input: String,
+    // Tenant of the submitting call; the worker runs the job under it
+    tenant: TenantContext,
+    // Deadline of the submitting call, if any; the job stops at it too
+    deadline: Option<Deadline>,
+    cancelled: Arc<AtomicBool>,
+    state: Mutex<JobState>,
+}
+
+struct JobState {
+    status: JobStatus,
+    result: Option<serde_json::Value>,
+    error: Option<String>,
+    // When the job reached a terminal status
+    finished: Option<Instant>,
+}
+
+impl JobState {
+    fn finish(&mut self, status: JobStatus) {
+        self.status = status;
+        self.finished = Some(Instant::now());
+    }
+}
+
+thread_local! {
+    // Cancellation flag of the job the current worker thread is executing
+    static CURRENT_JOB: RefCell<Option<Arc<AtomicBool>>> = RefCell::new(None);
+}
+
+// Checked by long-running pipelines between stages
+pub fn is_cancelled() -> bool {
+    CURRENT_JOB.with(|current| {
+        current.borrow().as_ref().map(|flag| flag.load(Ordering::SeqCst)).unwrap_or(false)
+    })
+}
+
+pub struct JobManager {
+    jobs: Arc<Mutex<HashMap<u64, Arc<Job>>>>,
+    sender: Sender<Arc<Job>>,
+    // Held so the queue stays open even when no workers are running
+    _receiver: Receiver<Arc<Job>>,
+    next_id: AtomicU64,
+    finished_ttl: Duration,
+}
+
+impl JobManager {
+    pub fn new(worker_count: usize) -> Self {
+        let (sender, receiver) = unbounded::<Arc<Job>>();
+        let jobs = Arc::new(Mutex::new(HashMap::new()));
+        
+        for i in 0..worker_count {
+            let receiver = receiver.clone();
+            let _ = thread::Builder::new()
+                .name(format!("aiogram-job-{}", i))
+                .spawn(move || worker_loop(receiver));
+        }
+        
+        Self {
+            jobs,
+            sender,
+            _receiver: receiver,
+            next_id: AtomicU64::new(1),
+            finished_ttl: FINISHED_JOB_TTL,
+        }
+    }
+    
+    pub fn with_finished_ttl(mut self, ttl: Duration) -> Self {
+        self.finished_ttl = ttl;
+        self
+    }
+    
+    fn job_table(&self) -> MutexGuard<'_, HashMap<u64, Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    pub fn submit(&self, kind: &str, input: &str) -> Result<u64, String> {
+        if !is_supported_kind(kind) {
+            return Err(format!("Unknown analysis kind: {}", kind));
+        }
+        
+        self.sweep_finished();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let job = Arc::new(Job {
+            id,
+            kind: kind.to_string(),
+            input: input.to_string(),
+            tenant: tenant::current(),
+            deadline: deadline::current(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            state: Mutex::new(JobState {
+                status: JobStatus::PENDING,
+                result: None,
+                error: None,
+                finished: None,
+            }),
+        });
+        
+        self.job_table().insert(id, Arc::clone(&job));
+        self.sender.send(job).map_err(|e| format!("Failed to queue job: {}", e))?;
+        Ok(id)
+    }
+    
+    pub fn poll(&self, job_id: u64) -> Option<JobSnapshot> {
+        let job = self.job_table().get(&job_id).cloned()?;
+        let snapshot = {
+            let state = job.state.lock().ok()?;
+            JobSnapshot {
+                job_id: job.id,
+                kind: job.kind.clone(),
+                status: state.status.clone(),
+                result: state.result.clone(),
+                error: state.error.clone(),
+            }
+        };
+        
+        // Finished jobs are handed out once and then forgotten
+        if snapshot.status.is_terminal() {
+            self.job_table().remove(&job_id);
+        }
+        
+        Some(snapshot)
+    }
+    
+    // Forgets finished jobs nobody polled within the TTL, so callers that
+    // never poll do not grow the table forever
+    fn sweep_finished(&self) {
+        let ttl = self.finished_ttl;
+        self.job_table().retain(|_, job| match job.state.lock() {
+            Ok(state) => !state.finished.is_some_and(|finished| finished.elapsed() >= ttl),
+            Err(_) => false,
+        });
+    }
+    
+    pub fn cancel(&self, job_id: u64) -> bool {
+        let job = match self.job_table().get(&job_id) {
+            Some(job) => Arc::clone(job),
+            None => return false,
+        };
+        
+        let mut state = match job.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        
+        if state.status.is_terminal() {
+            return false;
+        }
+        
+        job.cancelled.store(true, Ordering::SeqCst);
+        
+        // Jobs that never started can be finalized right away; running jobs
+        // stop at the next stage boundary
+        if state.status == JobStatus::PENDING {
+            state.finish(JobStatus::CANCELLED);
+        }
+        
+        true
+    }
+}
+
+fn worker_loop(receiver: Receiver<Arc<Job>>) {
+    while let Ok(job) = receiver.recv() {
+        {
+            let mut state = match job.state.lock() {
+                Ok(state) => state,
+                Err(_) => continue,
+            };
+            if job.cancelled.load(Ordering::SeqCst) {
+                state.finish(JobStatus::CANCELLED);
+                continue;
+            }
+            state.status = JobStatus::RUNNING;
+        }
+        
+        CURRENT_JOB.with(|current| *current.borrow_mut() = Some(Arc::clone(&job.cancelled)));
+        // A job still queued at its deadline fails without running
+        let outcome = deadline::with_deadline(job.deadline, || match deadline::check("the job started") {
+            Ok(()) => tenant::with_tenant(job.tenant.clone(), || run_job(&job)),
+            Err(error) => Err(error),
+        });
+        CURRENT_JOB.with(|current| *current.borrow_mut() = None);
+        
+        if let Ok(mut state) = job.state.lock() {
+            if job.cancelled.load(Ordering::SeqCst) {
+                state.finish(JobStatus::CANCELLED);
+            } else {
+                match outcome {
+                    Ok(result) => {
+                        state.result = Some(result);
+                        state.finish(JobStatus::COMPLETED);
+                    },
+                    Err(error) => {
+                        state.error = Some(error);
+                        state.finish(JobStatus::FAILED);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Kinds whose analysis was compiled in; see the nlp and stats features
+fn is_supported_kind(kind: &str) -> bool {
+    match kind {
+        #[cfg(feature = "nlp")]
+        "text" => true,
+        #[cfg(feature = "stats")]
+        "data" => true,
+        _ => false,
+    }
+}
+
+fn run_job(job: &Job) -> Result<serde_json::Value, String> {
+    match job.kind.as_str() {
+        #[cfg(feature = "nlp")]
+        "text" => serde_json::to_value(crate::analysis::analyze_text(&job.input)).map_err(|e| e.to_string()),
+        #[cfg(feature = "stats")]
+        "data" => serde_json::to_value(crate::analysis::analyze_data(&job.input)).map_err(|e| e.to_string()),
+        other => Err(format!("Unknown analysis kind: {}", other)),
+    }
+}
+
+// Global job manager
+lazy_static! {
+    static ref JOB_MANAGER: JobManager = JobManager::new(
+        crate::config::AppConfig::get_performance_config().worker_threads.max(1)
+    );
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&JOB_MANAGER);
+    Ok(())
+}
+
+// Public job functions
+pub fn submit_analysis(kind: &str, input: &str) -> Result<u64, String> {
+    JOB_MANAGER.submit(kind, input)
+}
+
+pub fn poll_result(job_id: u64) -> Option<JobSnapshot> {
+    JOB_MANAGER.poll(job_id)
+}
+
+pub fn cancel_job(job_id: u64) -> bool {
+    JOB_MANAGER.cancel(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_job_completes() {
+        let job_id = submit_analysis("data", "1,2,3,4,5").unwrap();
+        
+        let mut snapshot = None;
+        for _ in 0..500 {
+            let current = poll_result(job_id).unwrap();
+            if current.status.is_terminal() {
+                snapshot = Some(current);
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        
+        let snapshot = snapshot.expect("job did not finish");
+        assert_eq!(snapshot.status, JobStatus::COMPLETED);
+        assert_eq!(snapshot.result.unwrap()["record_count"], 5);
+        
+        // Finished jobs are removed after being polled
+        assert!(poll_result(job_id).is_none());
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_cancel_pending_job() {
+        // No workers, so the job stays queued until cancelled
+        let manager = JobManager::new(0);
+        let job_id = manager.submit("text", "Hello world").unwrap();
+        assert_eq!(manager.poll(job_id).unwrap().status, JobStatus::PENDING);
+        
+        assert!(manager.cancel(job_id));
+        assert_eq!(manager.poll(job_id).unwrap().status, JobStatus::CANCELLED);
+        assert!(!manager.cancel(job_id));
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_unpolled_finished_jobs_expire() {
+        let manager = JobManager::new(0).with_finished_ttl(Duration::ZERO);
+        let cancelled = manager.submit("text", "Hello world").unwrap();
+        let pending = manager.submit("text", "Hello again").unwrap();
+        assert!(manager.cancel(cancelled));
+        
+        // The next submit sweeps the finished job but keeps the pending one
+        manager.submit("text", "And again").unwrap();
+        assert!(manager.poll(cancelled).is_none());
+        assert_eq!(manager.poll(pending).unwrap().status, JobStatus::PENDING);
+    }
+    
+    #[test]
+    fn test_unknown_kind_rejected() {
+        let manager = JobManager::new(0);
+        assert!(manager.submit("video", "payload").is_err());
+        assert!(manager.poll(1).is_none());
+    }
+}
\ No newline at end of file
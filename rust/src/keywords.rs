@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+use crate::{language, pos, stemming, stopwords};
+use crate::language::LanguageSpan;
+
+// TF-IDF keyword ranking against a background corpus, typically earlier
+// messages of the same chat. Words that are frequent everywhere ("really",
+// "message") get a low inverse document frequency and sink below the words
+// that make this text different from the rest.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keyword {
+    pub term: String,
+    pub score: f64,
+    // Occurrences in the analyzed text
+    pub count: usize,
+}
+
+// Serializable so a corpus built from chat history can be saved and loaded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeywordExtractor {
+    document_count: u64,
+    document_frequency: HashMap<String, u64>,
+    // Stop words are picked for this language; None applies every list
+    language: Option<String>,
+    // Counts stems instead of words; off for corpora saved before stemming
+    #[serde(default)]
+    stemmed: bool,
+    // Ranks only words inside noun phrases; the corpus counts every word
+    #[serde(default)]
+    noun_phrases: bool,
+}
+
+// Lowercased words worth ranking: stop words and words of one or two bytes
+// are dropped
+pub fn terms(text: &str, language: &str) -> Vec<String> {
+    text.to_lowercase()
+        .unicode_words()
+        .filter(|word| word.len() > 2 && !stopwords::is_stop_word(language, word))
+        .map(str::to_string)
+        .collect()
+}
+
+// Like terms, with each word's stop words picked by the language of its span
+pub(crate) fn tagged_terms<'s>(text: &str, spans: &'s [LanguageSpan]) -> Vec<(String, &'s str)> {
+    language::words(text, spans)
+        .into_iter()
+        .map(|(word, language)| (word.to_lowercase(), language))
+        .filter(|(word, language)| word.len() > 2 && !stopwords::is_stop_word(language, word))
+        .collect()
+}
+
+// Like tagged_terms, keeping only the words of noun phrases
+pub(crate) fn noun_phrase_terms<'s>(text: &str, spans: &'s [LanguageSpan]) -> Vec<(String, &'s str)> {
+    let phrases = pos::noun_phrases_in(text, spans);
+    text.unicode_word_indices()
+        .filter(|&(i, _)| {
+            let k = phrases.partition_point(|phrase| phrase.end <= i);
+            phrases.get(k).is_some_and(|phrase| phrase.start <= i)
+        })
+        .map(|(i, word)| (word.to_lowercase(), language::language_at(spans, i)))
+        .filter(|(word, language)| word.len() > 2 && !stopwords::is_stop_word(language, word))
+        .collect()
+}
+
+impl KeywordExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+    
+    // Groups inflections; set before adding documents, since the corpus
+    // stores whatever was counted
+    pub fn with_stemming(mut self, stemmed: bool) -> Self {
+        self.stemmed = stemmed;
+        self
+    }
+    
+    pub fn with_noun_phrases(mut self, noun_phrases: bool) -> Self {
+        self.noun_phrases = noun_phrases;
+        self
+    }
+    
+    fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or("unknown")
+    }
+    
+    fn key(&self, term: &str, language: &str) -> String {
+        if self.stemmed { stemming::stem(term, language) } else { term.to_string() }
+    }
+    
+    pub fn add_document(&mut self, text: &str) {
+        let language = self.language();
+        let mut seen: Vec<String> = terms(text, language).iter().map(|term| self.key(term, language)).collect();
+        seen.sort_unstable();
+        seen.dedup();
+        for term in seen {
+            *self.document_frequency.entry(term).or_insert(0) += 1;
+        }
+        self.document_count += 1;
+    }
+    
+    pub fn add_documents<'a>(&mut self, texts: impl IntoIterator<Item = &'a str>) {
+        texts.into_iter().for_each(|text| self.add_document(text));
+    }
+    
+    pub fn document_count(&self) -> u64 {
+        self.document_count
+    }
+    
+    // Drops the rarest terms until at most max_terms are left, those seen
+    // in one document first; to idf they are about as rare as unseen ones
+    pub fn prune(&mut self, max_terms: usize) {
+        let mut floor = 1;
+        while self.document_frequency.len() > max_terms {
+            self.document_frequency.retain(|_, df| *df > floor);
+            floor += 1;
+        }
+    }
+    
+    // Smoothed so terms missing from the corpus get the highest weight
+    // instead of dividing by zero
+    fn idf(&self, term: &str) -> f64 {
+        let df = self.document_frequency.get(term).copied().unwrap_or(0) as f64;
+        ((1.0 + self.document_count as f64) / (1.0 + df)).ln() + 1.0
+    }
+    
+    // Highest scores first; equal scores keep first-occurrence order
+    pub fn extract(&self, text: &str, limit: usize) -> Vec<Keyword> {
+        self.extract_in(text, &language::whole(text, self.language(), 1.0), false, limit)
+    }
+    
+    // For the pipeline: the detected language of each segment picks the
+    // stop words and stems unless the extractor was built for a fixed one;
+    // noun_phrases applies on top of the extractor's own setting
+    pub(crate) fn extract_in(&self, text: &str, detected: &[LanguageSpan], noun_phrases: bool, limit: usize) -> Vec<Keyword> {
+        let fixed;
+        let spans = match &self.language {
+            Some(language) => {
+                fixed = language::whole(text, language, 1.0);
+                &fixed
+            }
+            None => detected,
+        };
+        let terms = if noun_phrases || self.noun_phrases { noun_phrase_terms(text, spans) } else { tagged_terms(text, spans) };
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        
+        // (key, first surface form, count)
+        let mut counts: Vec<(String, &str, usize)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for (term, language) in &terms {
+            let key = self.key(term, language);
+            match index.get(&key) {
+                Some(&i) => counts[i].2 += 1,
+                None => {
+                    index.insert(key.clone(), counts.len());
+                    counts.push((key, term, 1));
+                }
+            }
+        }
+        
+        let total = terms.len() as f64;
+        let mut keywords: Vec<Keyword> = counts.into_iter().map(|(key, term, count)| Keyword {
+            score: count as f64 / total * self.idf(&key),
+            term: term.to_string(),
+            count,
+        }).collect();
+        keywords.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        keywords.truncate(limit);
+        keywords
+    }
+}
+
+// Vocabulary of the corpus add_background_document grows; past it the
+// rarest terms are pruned
+const MAX_BACKGROUND_TERMS: usize = 100_000;
+
+lazy_static! {
+    // The counts have their own lock, so adding a message updates them in
+    // place while pipeline calls that fetched the corpus keep reading it
+    static ref BACKGROUND: RwLock<Option<Arc<RwLock<KeywordExtractor>>>> = RwLock::new(None);
+}
+
+// Corpus used by the text pipeline's keywords; None restores plain
+// frequency ranking
+pub fn set_background(extractor: Option<KeywordExtractor>) {
+    *BACKGROUND.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = extractor.map(|extractor| Arc::new(RwLock::new(extractor)));
+}
+
+pub fn background() -> Option<Arc<RwLock<KeywordExtractor>>> {
+    BACKGROUND.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+// Adds to the pipeline's corpus, starting a stemmed one if none is set
+pub fn add_background_document(text: &str) {
+    let corpus = background().unwrap_or_else(|| {
+        let mut background = BACKGROUND.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        background.get_or_insert_with(|| Arc::new(RwLock::new(KeywordExtractor::new().with_stemming(true)))).clone()
+    });
+    let mut corpus = corpus.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    corpus.add_document(text);
+    if corpus.document_frequency.len() > MAX_BACKGROUND_TERMS {
+        corpus.prune(MAX_BACKGROUND_TERMS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_tfidf_demotes_corpus_filler() {
+        let mut extractor = KeywordExtractor::new().with_language("en");
+        extractor.add_documents([
+            "really nice message, thanks",
+            "another message, really",
+            "this message is really long",
+            "really? message me later",
+        ]);
+        assert_eq!(extractor.document_count(), 4);
+        
+        let keywords = extractor.extract("really really message message deployment failed", 3);
+        assert_eq!(keywords[0].term, "deployment");
+        assert_eq!(keywords[1].term, "failed");
+        assert_eq!(keywords[2].count, 2);
+        assert!(keywords[0].score > keywords[2].score);
+        
+        // Without a corpus it degrades to frequency ranking
+        let plain = KeywordExtractor::new().extract("bot bot deploy", 5);
+        assert_eq!(plain[0].term, "bot");
+        assert!(KeywordExtractor::new().extract("a an the", 5).is_empty());
+        
+        let restored: KeywordExtractor = serde_json::from_str(&serde_json::to_string(&extractor).unwrap()).unwrap();
+        assert_eq!(restored.extract("really deployment", 1)[0].term, "deployment");
+        
+        let mut stemmed = KeywordExtractor::new().with_language("en").with_stemming(true);
+        stemmed.add_documents(["messages everywhere", "messaging again"]);
+        let keywords = stemmed.extract("Messaged twice, the deploys deployed", 3);
+        assert_eq!((keywords[0].term.as_str(), keywords[0].count), ("deploys", 2));
+        // Common in the corpus under other inflections
+        assert_eq!(keywords[2].term, "messaged");
+    }
+    
+    #[test]
+    fn test_prune_drops_the_rarest_terms() {
+        let mut extractor = KeywordExtractor::new().with_language("en");
+        extractor.add_documents(["deploy failed again", "deploy worked", "deploy rollback failed", "lunch"]);
+        extractor.prune(2);
+        let mut kept: Vec<&String> = extractor.document_frequency.keys().collect();
+        kept.sort();
+        assert_eq!(kept, ["deploy", "failed"]);
+        assert_eq!(extractor.document_count(), 4);
+        
+        // Pruned terms weigh like unseen ones
+        assert_eq!(extractor.idf("lunch"), extractor.idf("unseen"));
+        extractor.prune(0);
+        assert!(extractor.document_frequency.is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::emoji;
+
+// Language per segment for code-switched messages, e.g. Persian with English
+// terms mixed in, where one whole-message guess picks the wrong stop words
+// and stemmer for part of the words. The text is cut where the script
+// changes and at sentence ends, so two Latin-script languages can alternate
+// by sentence; each run is detected on its own and neighbours that agree
+// are merged.
+
+// Runs with fewer letters are too short for a reliable guess and take the
+// language of the nearest run in the same script
+const MIN_DETECT_LETTERS: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageSpan {
+    // Byte offsets; the spans are contiguous and cover the whole text
+    pub start: usize,
+    pub end: usize,
+    // As detect_language reports it, or "unknown"
+    pub language: String,
+    pub confidence: f64,
+}
+
+// Letters by script, then the characters of no script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Script {
+    // Persian, Arabic and Urdu letters
+    ARABIC,
+    LATIN,
+    CYRILLIC,
+    // Letters of any other script
+    OTHER,
+    EMOJI,
+    // In any script, e.g. Persian digits
+    DIGITS,
+    // Punctuation and symbols
+    SYMBOLS,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptShare {
+    pub script: Script,
+    // Grapheme clusters, so an emoji or a letter with harakat counts once
+    pub characters: usize,
+    // Of ScriptStats::characters, 0-1
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScriptStats {
+    // Every character except whitespace
+    pub characters: usize,
+    // Most characters first; scripts that do not occur are left out
+    pub scripts: Vec<ScriptShare>,
+}
+
+impl ScriptStats {
+    fn from_counts(counts: HashMap<Script, usize>) -> Self {
+        let characters = counts.values().sum();
+        let mut scripts: Vec<ScriptShare> = counts.into_iter()
+            .filter(|&(_, count)| count > 0)
+            .map(|(script, count)| ScriptShare { script, characters: count, share: count as f64 / characters as f64 })
+            .collect();
+        scripts.sort_by(|a, b| b.characters.cmp(&a.characters).then(a.script.cmp(&b.script)));
+        Self { characters, scripts }
+    }
+    
+    // Totals over several texts, e.g. a batch of messages
+    pub fn combine<'a>(stats: impl IntoIterator<Item = &'a ScriptStats>) -> Self {
+        let mut counts: HashMap<Script, usize> = HashMap::new();
+        for share in stats.into_iter().flat_map(|stats| &stats.scripts) {
+            *counts.entry(share.script).or_insert(0) += share.characters;
+        }
+        Self::from_counts(counts)
+    }
+    
+    pub fn share(&self, script: Script) -> f64 {
+        self.scripts.iter().find(|share| share.script == script).map(|share| share.share).unwrap_or(0.0)
+    }
+}
+
+// None for characters that belong to no script: spaces, digits, punctuation
+// and emoji, which stay with the run before them
+fn script(c: char) -> Option<Script> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    Some(match c {
+        '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' | '\u{FB50}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' => Script::ARABIC,
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Script::LATIN,
+        '\u{0400}'..='\u{04FF}' => Script::CYRILLIC,
+        _ => Script::OTHER,
+    })
+}
+
+struct Run {
+    start: usize,
+    end: usize,
+    script: Option<Script>,
+    letters: usize,
+}
+
+fn runs(text: &str) -> Vec<Run> {
+    let mut runs = vec![Run { start: 0, end: 0, script: None, letters: 0 }];
+    let mut terminated = false;
+    let mut sentence_break = false;
+    for (i, c) in text.char_indices() {
+        if let Some(letter_script) = script(c) {
+            let last = runs.len() - 1;
+            match runs[last].script {
+                None => runs[last].script = Some(letter_script),
+                Some(current) if current != letter_script || sentence_break => {
+                    runs.push(Run { start: i, end: i, script: Some(letter_script), letters: 0 });
+                }
+                _ => {}
+            }
+            let last = runs.len() - 1;
+            runs[last].letters += 1;
+        }
+        let last = runs.len() - 1;
+        runs[last].end = i + c.len_utf8();
+        
+        // A sentence ends at a line break, or at terminal punctuation once
+        // whitespace follows, so "3.5" or "example.com" do not cut
+        match c {
+            '.' | '!' | '?' | '…' | '؟' => terminated = true,
+            '\n' => sentence_break = true,
+            c if c.is_whitespace() => sentence_break |= terminated,
+            c if c.is_alphanumeric() => {
+                terminated = false;
+                sentence_break = false;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+fn detect(text: &str) -> Option<(String, f64)> {
+    whatlang::detect(text).map(|info| (info.lang().to_string(), info.confidence()))
+}
+
+pub fn script_stats(text: &str) -> ScriptStats {
+    let mut counts: HashMap<Script, usize> = HashMap::new();
+    for grapheme in text.graphemes(true) {
+        let first = match grapheme.chars().next() {
+            Some(c) if !c.is_whitespace() => c,
+            _ => continue,
+        };
+        let class = if emoji::is_emoji(grapheme) {
+            Script::EMOJI
+        } else if first.is_numeric() {
+            Script::DIGITS
+        } else {
+            script(first).unwrap_or(Script::SYMBOLS)
+        };
+        *counts.entry(class).or_insert(0) += 1;
+    }
+    ScriptStats::from_counts(counts)
+}
+
+// In text order; an empty text has no spans
+pub fn detect_languages_segmented(text: &str) -> Vec<LanguageSpan> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let runs = runs(text);
+    let mut detected: Vec<Option<(String, f64)>> = runs.iter()
+        .map(|run| if run.letters >= MIN_DETECT_LETTERS || runs.len() == 1 { detect(&text[run.start..run.end]) } else { None })
+        .collect();
+    
+    // Short runs borrow from the nearest detected run in their script,
+    // preferring the one before; with none they stay unknown
+    for i in 0..runs.len() {
+        if detected[i].is_some() || runs[i].letters >= MIN_DETECT_LETTERS {
+            continue;
+        }
+        let distance = |j: &usize| if *j < i { i - j } else { j - i };
+        let neighbour = (0..runs.len())
+            .filter(|&j| j != i && runs[j].script == runs[i].script && runs[j].letters >= MIN_DETECT_LETTERS && detected[j].is_some())
+            .min_by_key(distance);
+        if let Some(j) = neighbour {
+            detected[i] = detected[j].clone();
+        }
+    }
+    
+    let mut spans: Vec<LanguageSpan> = Vec::new();
+    for (run, guess) in runs.iter().zip(detected) {
+        let (language, confidence) = guess.unwrap_or_else(|| ("unknown".to_string(), 0.0));
+        if let Some(last) = spans.last_mut() {
+            if last.language == language {
+                // Weighted by length, so a long confident run dominates
+                let (a, b) = ((last.end - last.start) as f64, (run.end - run.start) as f64);
+                last.confidence = (last.confidence * a + confidence * b) / (a + b);
+                last.end = run.end;
+                continue;
+            }
+        }
+        spans.push(LanguageSpan { start: run.start, end: run.end, language, confidence });
+    }
+    spans
+}
+
+// One span for a language the host already knows
+pub fn whole(text: &str, language: &str, confidence: f64) -> Vec<LanguageSpan> {
+    vec![LanguageSpan { start: 0, end: text.len(), language: language.to_string(), confidence }]
+}
+
+// The spans of text[start..end], with offsets relative to start
+pub fn slice(spans: &[LanguageSpan], start: usize, end: usize) -> Vec<LanguageSpan> {
+    spans.iter()
+        .filter(|span| span.start < end && start < span.end)
+        .map(|span| LanguageSpan {
+            start: span.start.max(start) - start,
+            end: span.end.min(end) - start,
+            language: span.language.clone(),
+            confidence: span.confidence,
+        })
+        .collect()
+}
+
+// Language of the span holding the byte at offset
+pub fn language_at(spans: &[LanguageSpan], offset: usize) -> &str {
+    let i = spans.partition_point(|span| span.end <= offset);
+    spans.get(i).or(spans.last()).map(|span| span.language.as_str()).unwrap_or("unknown")
+}
+
+// Each word with the language of the span it starts in
+pub fn words<'t, 's>(text: &'t str, spans: &'s [LanguageSpan]) -> Vec<(&'t str, &'s str)> {
+    text.unicode_word_indices().map(|(i, word)| (word, language_at(spans, i))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_code_switched_spans() {
+        let text = "سلام به همه دوستان عزیز، امروز جلسه داریم. The meeting will start at five o'clock in the main room.";
+        let spans = detect_languages_segmented(text);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].start, 0);
+        assert_eq!(spans[1].end, text.len());
+        assert_eq!(spans[0].end, spans[1].start);
+        assert_eq!(spans[0].language, "فارسی");
+        assert_eq!(spans[1].language, "English");
+        assert!(text[spans[1].start..].starts_with("The"));
+        
+        let words = words(text, &spans);
+        assert_eq!(words.iter().find(|(word, _)| *word == "meeting").map(|(_, lang)| *lang), Some("English"));
+        assert_eq!(language_at(&spans, 0), "فارسی");
+        
+        let sliced = slice(&spans, spans[1].start, text.len());
+        assert_eq!(sliced, vec![LanguageSpan { start: 0, end: text.len() - spans[1].start, ..spans[1].clone() }]);
+        assert!(detect_languages_segmented("").is_empty());
+        
+        // Too short to detect, and no longer English run to borrow from
+        let mixed = detect_languages_segmented("امروز بعد از ظهر یک meeting مهم با تیم فروش داریم");
+        assert_eq!(mixed.len(), 3);
+        assert_eq!(mixed[1].language, "unknown");
+        assert_eq!(mixed[2].language, "فارسی");
+    }
+    
+    #[test]
+    fn test_script_stats() {
+        let stats = script_stats("سلام hi ۱۲ 👍🏽!");
+        assert_eq!(stats.characters, 10);
+        assert_eq!(stats.scripts[0].script, Script::ARABIC);
+        assert_eq!(stats.scripts[0].characters, 4);
+        assert!((stats.share(Script::LATIN) - 0.2).abs() < 1e-9);
+        assert!((stats.share(Script::DIGITS) - 0.2).abs() < 1e-9);
+        assert!((stats.share(Script::EMOJI) - 0.1).abs() < 1e-9);
+        assert_eq!(stats.share(Script::CYRILLIC), 0.0);
+        
+        let batch = ScriptStats::combine([&stats, &script_stats("привет")]);
+        assert_eq!(batch.characters, 16);
+        assert_eq!(batch.scripts[0].script, Script::CYRILLIC);
+        assert!(script_stats("  ").scripts.is_empty());
+    }
+}
\ No newline at end of file
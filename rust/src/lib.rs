@@ -1,179 +1,4006 @@
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 
+#[cfg(feature = "crypto")]
 mod crypto;
+#[cfg(feature = "crypto")]
+mod deeplinks;
+#[cfg(feature = "crypto")]
+mod hashing;
+#[cfg(any(feature = "nlp", feature = "stats"))]
 mod analysis;
+#[cfg(feature = "stats")]
+mod polls;
+#[cfg(feature = "realtime")]
 mod realtime;
+#[cfg(feature = "nlp")]
+mod plugins;
+#[cfg(feature = "nlp")]
+mod sentiment;
+#[cfg(feature = "nlp")]
+mod stopwords;
+#[cfg(feature = "nlp")]
+mod keywords;
+#[cfg(feature = "nlp")]
+mod stemming;
+#[cfg(feature = "nlp")]
+mod language;
+#[cfg(feature = "nlp")]
+mod pos;
+#[cfg(feature = "nlp")]
+mod transliterate;
+#[cfg(feature = "nlp")]
+mod abbreviations;
+#[cfg(feature = "nlp")]
+mod topics;
+#[cfg(feature = "nlp")]
+mod similarity;
+#[cfg(feature = "nlp")]
+mod commands;
+#[cfg(feature = "nlp")]
+mod incremental;
+#[cfg(feature = "documents")]
+mod documents;
+#[cfg(feature = "nlp")]
+mod summary;
+#[cfg(feature = "nlp")]
+mod truncation;
+#[cfg(feature = "nlp")]
+mod readability;
+#[cfg(feature = "nlp")]
+mod normalize;
+#[cfg(feature = "nlp")]
+mod emoji;
+#[cfg(feature = "nlp")]
+mod tags;
+#[cfg(feature = "nlp")]
+mod structure;
+#[cfg(feature = "nlp")]
+mod shadow;
+#[cfg(feature = "nlp")]
+mod spam;
+#[cfg(feature = "nlp")]
+mod gazetteers;
+#[cfg(feature = "nlp")]
+mod entity_patterns;
+#[cfg(feature = "nlp")]
+mod moderation;
+#[cfg(feature = "nlp")]
+mod replies;
+#[cfg(feature = "nlp")]
+mod conversation;
+#[cfg(feature = "nlp")]
+mod transcripts;
 mod config;
 mod logging;
 mod cache;
 mod security;
+mod secrets;
+mod pii;
+mod paths;
 mod validation;
+mod parsers;
 mod performance;
+mod progress;
+mod deadline;
+mod concurrency;
+mod jobs;
+#[cfg(feature = "realtime")]
+mod pool;
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+mod queue;
+mod alerts;
+mod lifecycle;
+mod tenant;
+mod costs;
+mod chats;
+mod campaigns;
+mod slowmode;
+mod fingerprints;
+mod counters;
+mod bandit;
+mod ocr;
+mod links;
+mod qr;
+mod export;
+mod i18n;
+mod templates;
+#[cfg(feature = "otlp")]
+mod telemetry;
+#[cfg(feature = "webhooks")]
+mod webhooks;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "chaos")]
+mod chaos;
+// Also compiled for unit tests so the invariants run in every cargo test
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
 
+// Stable Rust API; the C exports below wrap it
+pub mod api;
+pub mod prelude;
+
+#[cfg(feature = "server")]
+pub use server::{run_server, DEFAULT_SERVER_ADDRESS};
+
+// Bumped whenever an exported signature or a JSON response shape changes
+// incompatibly; mirrored in the generated header as AIOGRAM_ABI_VERSION
+pub const AIOGRAM_ABI_VERSION: u32 = 1;
+
+// Status codes returned by the length-prefixed buffer API
+pub const FFI_OK: i32 = 0;
+pub const FFI_ERR_NULL_POINTER: i32 = -1;
+pub const FFI_ERR_INVALID_UTF8: i32 = -2;
+pub const FFI_ERR_OPERATION_FAILED: i32 = -3;
+pub const FFI_ERR_PANIC: i32 = -4;
+
+// Response schemas selected by analysis.response_version
+#[cfg(any(feature = "nlp", feature = "stats", feature = "realtime"))]
+const RESPONSE_VERSION_FULL: u32 = 2;
+
+#[cfg(any(feature = "nlp", feature = "stats", feature = "realtime"))]
+fn response_version() -> u32 {
+    config::AppConfig::get_analysis_config().response_version
+}
+
+// Full schema: the complete serde-serialized result tagged with its version
+#[cfg(any(feature = "nlp", feature = "stats", feature = "realtime"))]
+fn full_response<T: serde::Serialize>(result: &T) -> String {
+    let mut value = serde_json::to_value(result).unwrap_or_else(|_| serde_json::json!({}));
+    if let Some(map) = value.as_object_mut() {
+        map.insert("response_version".to_string(), serde_json::json!(RESPONSE_VERSION_FULL));
+    }
+    value.to_string()
+}
+
+#[cfg(feature = "nlp")]
+fn analyze_text_response(text: &str, analyzer: &api::TextAnalyzer) -> String {
+    admitted(|| {
+        let start_time = std::time::Instant::now();
+        
+        let result = analyzer.analyze(text);
+        if response_version() >= RESPONSE_VERSION_FULL {
+            return full_response(&result);
+        }
+        let processing_time = start_time.elapsed().as_millis();
+        
+        let mut response = serde_json::json!({
+            "char_count": result.char_count,
+            "word_count": result.word_count,
+            "sentence_count": result.sentence_count,
+            "language": result.language,
+            "sentiment": result.sentiment,
+            "keywords": result.keywords,
+            "processing_time": processing_time
+        });
+        
+        // Analyzers are opt-in, so their output is added to every schema version
+        if !result.plugins.is_empty() {
+            response["plugins"] = serde_json::Value::Object(result.plugins);
+        }
+        // Likewise only present for calls that ran out of time
+        if result.timed_out {
+            response["timed_out"] = serde_json::json!(true);
+        }
+        
+        response.to_string()
+    })
+}
+
+#[cfg(feature = "realtime")]
+fn process_realtime_response(data: &str) -> String {
+    admitted(|| {
+        let start_time = std::time::Instant::now();
+        
+        let result = api::process_realtime(data);
+        if response_version() >= RESPONSE_VERSION_FULL {
+            return full_response(&result);
+        }
+        let processing_time = start_time.elapsed().as_millis();
+        
+        let response = serde_json::json!({
+            "status": result.status,
+            "processing_speed": result.processing_speed,
+            "latency": processing_time,
+            "quality": result.quality,
+            "timestamp": chrono::Utc::now().timestamp()
+        });
+        
+        response.to_string()
+    })
+}
+
+#[cfg(feature = "stats")]
+fn analyze_data_response(data: &str) -> String {
+    data_analysis_response(|| api::analyze_data(data))
+}
+
+#[cfg(feature = "stats")]
+fn analyze_numbers_response(values: &[f64]) -> String {
+    data_analysis_response(|| api::analyze_numbers(values))
+}
+
+#[cfg(feature = "stats")]
+fn data_analysis_response<F: FnOnce() -> api::DataAnalysisResult>(analyze: F) -> String {
+    admitted(|| {
+        let start_time = std::time::Instant::now();
+        
+        let result = analyze();
+        if response_version() >= RESPONSE_VERSION_FULL {
+            return full_response(&result);
+        }
+        let analysis_time = start_time.elapsed().as_millis();
+        
+        let response = serde_json::json!({
+            "record_count": result.record_count,
+            "mean": result.mean,
+            "std_dev": result.std_dev,
+            "min": result.min,
+            "max": result.max,
+            "patterns": result.patterns,
+            "anomalies": result.anomalies,
+            "prediction": result.prediction,
+            "analysis_time": analysis_time
+        });
+        
+        response.to_string()
+    })
+}
+
+// Runs an analysis call once the concurrency limiter admits it; otherwise
+// {"error": "overloaded", "message": ..., "concurrency": {"limit", "active",
+// "queued", ...}} so the host can back off and retry
+#[cfg(any(feature = "nlp", feature = "stats", feature = "realtime"))]
+fn admitted<F: FnOnce() -> String>(body: F) -> String {
+    match concurrency::limited(body) {
+        Ok(response) => response,
+        Err(stats) => serde_json::json!({
+            "error": "overloaded",
+            "message": "Too many concurrent requests, retry later",
+            "concurrency": stats
+        }).to_string(),
+    }
+}
+
+fn poll_result_response(job_id: u64) -> Option<String> {
+    let snapshot = api::poll_result(job_id)?;
+    serde_json::to_string(&snapshot).ok()
+}
+
+fn to_c_string(response: String) -> *mut c_char {
+    match CString::new(response) {
+        Ok(s) => s.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// What an export returns to the host when its body panicked
+trait PanicValue {
+    fn from_panic(export: &str, message: &str) -> Self;
+}
+
+// String-returning exports hand back an error document instead of null so
+// the host can tell a crash apart from invalid input
+impl PanicValue for *mut c_char {
+    fn from_panic(export: &str, message: &str) -> Self {
+        to_c_string(serde_json::json!({ "error": "panic", "function": export, "message": message }).to_string())
+    }
+}
+
+impl PanicValue for bool {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        false
+    }
+}
+
+impl PanicValue for i32 {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        FFI_ERR_PANIC
+    }
+}
+
+// Job and request ids start at 1, so 0 already means "rejected"
+impl PanicValue for u64 {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        0
+    }
+}
+
+impl PanicValue for u32 {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        0
+    }
+}
+
+// Buffer sizes include the NUL terminator, so 0 is never a valid size
+impl PanicValue for usize {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        0
+    }
+}
+
+// Signed results are counts or ids, so -1 already means failure
+impl PanicValue for i64 {
+    fn from_panic(_export: &str, _message: &str) -> Self {
+        -1
+    }
+}
+
+impl PanicValue for () {
+    fn from_panic(_export: &str, _message: &str) -> Self {}
+}
+
+// Every export runs its body through this: unwinding across extern "C" is
+// undefined behaviour, so a panic is logged and turned into the export's
+// error value instead. A deadline armed with set_call_deadline applies to the
+// body and is used up by it.
+fn ffi_guard<R: PanicValue, F: FnOnce() -> R>(export: &str, body: F) -> R {
+    guarded(export, deadline::take_next_call(), body)
+}
+
+// For bookkeeping exports such as free_string, getters of counters and
+// stats, and the deadline setter itself: hosts call them between arming a
+// deadline and the call it is meant for, so they leave it armed
+fn ffi_guard_untimed<R: PanicValue, F: FnOnce() -> R>(export: &str, body: F) -> R {
+    guarded(export, None, body)
+}
+
+// The body also runs in the tenant set with set_current_tenant; the
+// _for_tenant exports replace it with their own arguments
+fn guarded<R: PanicValue, F: FnOnce() -> R>(export: &str, call_deadline: Option<deadline::Deadline>, body: F) -> R {
+    #[cfg(feature = "chaos")]
+    chaos::maybe_delay();
+    let context = tenant::thread_default();
+    match panic::catch_unwind(AssertUnwindSafe(|| tenant::with_tenant(context, || deadline::with_deadline(call_deadline, body)))) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = lifecycle::panic_message(payload.as_ref());
+            logging::error(&format!("Panic in {}: {}", export, message), "ffi", export, line!());
+            R::from_panic(export, &message)
+        }
+    }
+}
+
+unsafe fn read_bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], i32> {
+    if data.is_null() {
+        return Err(FFI_ERR_NULL_POINTER);
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+// For the buffer exports whose input is text
+unsafe fn read_buf<'a>(data: *const u8, len: usize) -> Result<&'a str, i32> {
+    std::str::from_utf8(read_bytes(data, len)?).map_err(|_| FFI_ERR_INVALID_UTF8)
+}
+
+unsafe fn write_buf(bytes: Vec<u8>, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    if out_ptr.is_null() || out_len.is_null() {
+        return FFI_ERR_NULL_POINTER;
+    }
+    let boxed = bytes.into_boxed_slice();
+    *out_len = boxed.len();
+    *out_ptr = Box::into_raw(boxed) as *mut u8;
+    FFI_OK
+}
+
+// Copies a NUL-terminated response into a caller-owned buffer and returns the
+// size it needs, terminator included. Nothing is written when it does not fit.
+#[cfg(feature = "nlp")]
+unsafe fn copy_into(bytes: &[u8], buf: *mut c_char, buf_len: usize) -> usize {
+    let needed = bytes.len() + 1;
+    if !buf.is_null() && buf_len >= needed {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+        *buf.add(bytes.len()) = 0;
+    }
+    needed
+}
+
+// Tenant arguments are optional: NULL and "" both mean no tenant. Returns
+// None when one is not valid UTF-8.
+unsafe fn read_optional<'a>(ptr: *const c_char) -> Option<Option<&'a str>> {
+    if ptr.is_null() {
+        return Some(None);
+    }
+    CStr::from_ptr(ptr).to_str().ok().map(Some)
+}
+
+// The chat set with set_current_tenant carries over, since these calls take
+// no chat argument of their own
+unsafe fn read_tenant(tenant_id: *const c_char, user_id: *const c_char) -> Option<api::TenantContext> {
+    let context = api::TenantContext::new(read_optional(tenant_id)?, read_optional(user_id)?);
+    Some(context.with_chat(tenant::current().chat_id.as_deref()))
+}
+
+fn rate_limited_response(error: &api::Error) -> *mut c_char {
+    to_c_string(serde_json::json!({ "error": "rate_limited", "message": error.to_string() }).to_string())
+}
+
+#[cfg(feature = "nlp")]
 #[no_mangle]
 pub extern "C" fn analyze_text(text: *const c_char) -> *mut c_char {
-    let start_time = std::time::Instant::now();
-    
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
+    ffi_guard("analyze_text", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(analyze_text_response(text_str, &api::TextAnalyzer::default()))
+    })
+}
+
+#[cfg(feature = "nlp")]
+thread_local! {
+    // The last analyze_text_into response that did not fit, by input text, so
+    // the retry copies it instead of analyzing again; its timings would make
+    // a fresh response a different size
+    static UNSENT_TEXT_RESPONSE: std::cell::RefCell<Option<(String, String)>> = std::cell::RefCell::new(None);
+}
+
+// Like analyze_text, but writes into a buffer the caller reuses across messages
+// instead of returning a string to release with free_string. Returns the buffer
+// size the response needs including the NUL terminator; when buf_len is smaller
+// nothing is written and a retry on the same thread with the same text and a
+// buffer of that size gets the same response. Returns 0 for null or non-UTF-8
+// text.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_into(text: *const c_char, buf: *mut c_char, buf_len: usize) -> usize {
+    ffi_guard("analyze_text_into", || {
+        if text.is_null() {
+            return 0;
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
             Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+            Err(_) => return 0,
+        };
+        
+        let unsent = UNSENT_TEXT_RESPONSE.with(|unsent| unsent.borrow_mut().take());
+        let response = match unsent {
+            Some((input, response)) if input == text_str => response,
+            _ => analyze_text_response(text_str, &api::TextAnalyzer::default()),
+        };
+        let needed = unsafe { copy_into(response.as_bytes(), buf, buf_len) };
+        if buf.is_null() || buf_len < needed {
+            UNSENT_TEXT_RESPONSE.with(|unsent| *unsent.borrow_mut() = Some((text_str.to_string(), response)));
         }
-    };
-    
-    let result = analysis::analyze_text(text_str);
-    let processing_time = start_time.elapsed().as_millis();
-    
-    let response = serde_json::json!({
-        "char_count": result.char_count,
-        "word_count": result.word_count,
-        "sentence_count": result.sentence_count,
-        "language": result.language,
-        "sentiment": result.sentiment,
-        "keywords": result.keywords,
-        "processing_time": processing_time
-    });
-    
-    let response_str = response.to_string();
-    let c_string = match CString::new(response_str) {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    c_string.into_raw()
+        needed
+    })
 }
 
+// options_json may be null or a JSON object such as
+// {"skip_ner": true, "summary_sentences": 3, "language_hint": "fa"};
+// returns null when the options do not parse
+#[cfg(feature = "nlp")]
 #[no_mangle]
-pub extern "C" fn encrypt_message(message: *const c_char, key: *const c_char) -> *mut c_char {
-    let message_str = unsafe {
-        match CStr::from_ptr(message).to_str() {
+pub extern "C" fn analyze_text_with_options(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_text_with_options", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let analyzer = if options_json.is_null() {
+            api::TextAnalyzer::default()
+        } else {
+            let options_str = match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            };
+            match api::TextAnalyzer::from_json(options_str) {
+                Ok(analyzer) => analyzer,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(analyze_text_response(text_str, &analyzer))
+    })
+}
+
+// {"format": "PDF" | "DOCX", "text": ...} for the document in data[..len], or
+// {"error": "invalid_document", "message": ...}
+#[cfg(feature = "documents")]
+#[no_mangle]
+pub extern "C" fn extract_document_text(data: *const u8, len: usize) -> *mut c_char {
+    ffi_guard("extract_document_text", || {
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        let response = match api::extract_document_text(bytes) {
+            Ok(document) => serde_json::json!(document),
+            Err(e) => serde_json::json!({ "error": "invalid_document", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Analyzes the text of a PDF or DOCX file like analyze_text_with_options
+// (options_json may be null) and returns the same response, or
+// {"error": "invalid_document" | "invalid_options", "message": ...}
+#[cfg(all(feature = "nlp", feature = "documents"))]
+#[no_mangle]
+pub extern "C" fn analyze_document(data: *const u8, len: usize, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_document", || {
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let analyzer = match api::TextAnalyzer::from_json(options_str) {
+            Ok(analyzer) => analyzer,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_options", "message": e.to_string() }).to_string()),
+        };
+        match api::extract_document_text(bytes) {
+            Ok(document) => to_c_string(analyze_text_response(&document.text, &analyzer)),
+            Err(e) => to_c_string(serde_json::json!({ "error": "invalid_document", "message": e.to_string() }).to_string()),
+        }
+    })
+}
+
+// {"characters": ..., "scripts": [{"script": "ARABIC", "characters", "share"}]},
+// most characters first
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_script_stats(text: *const c_char) -> *mut c_char {
+    ffi_guard("get_script_stats", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::script_stats(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
         }
-    };
-    
-    let key_str = unsafe {
-        match CStr::from_ptr(key).to_str() {
+    })
+}
+
+// {"tokens", "types", "hapax_legomena", "type_token_ratio",
+// "top_tokens": [{"token", "count", "share"}], "top_content_words": [...],
+// "characters": [{"class": "LETTER", "count", "share"}],
+// "zipf": {"exponent", "r_squared"} | null}, with up to limit top entries
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn frequency_report(text: *const c_char, limit: usize) -> *mut c_char {
+    ffi_guard("frequency_report", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::frequency_report(text_str, limit)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
         }
-    };
-    
-    let encrypted = match crypto::encrypt(message_str, key_str) {
-        Ok(result) => result,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    let c_string = match CString::new(encrypted) {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    c_string.into_raw()
+    })
 }
 
+// {"segments": [{"op": "EQUAL" | "INSERT" | "DELETE", "text", "start",
+// "end"}], "changes": [{"kind": "INSERTED" | "DELETED" | "REPLACED", "old",
+// "new"}], "words_inserted", "words_deleted", "similarity", "summary"};
+// offsets of DELETE segments are in old_text, the others in new_text
+#[cfg(feature = "nlp")]
 #[no_mangle]
-pub extern "C" fn decrypt_message(encrypted_message: *const c_char, key: *const c_char) -> *mut c_char {
-    let encrypted_str = unsafe {
-        match CStr::from_ptr(encrypted_message).to_str() {
+pub extern "C" fn diff_texts(old_text: *const c_char, new_text: *const c_char) -> *mut c_char {
+    ffi_guard("diff_texts", || {
+        if old_text.is_null() || new_text.is_null() {
+            return ptr::null_mut();
+        }
+        let (old_str, new_str) = match (unsafe { CStr::from_ptr(old_text) }.to_str(), unsafe { CStr::from_ptr(new_text) }.to_str()) {
+            (Ok(old), Ok(new)) => (old, new),
+            _ => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::diff_texts(old_str, new_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// texts_json is a JSON array of strings; returns {"similarity": [[...]],
+// "shared_terms": [{"term", "documents", "count"}], "distinguishing_terms":
+// [[...]], "clusters": [[...]]} or {"error": "invalid_input", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn compare_documents(texts_json: *const c_char) -> *mut c_char {
+    ffi_guard("compare_documents", || {
+        if texts_json.is_null() {
+            return ptr::null_mut();
+        }
+        let texts_str = match unsafe { CStr::from_ptr(texts_json) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        
+        let texts: Vec<String> = match serde_json::from_str(texts_str) {
+            Ok(texts) => texts,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": e.to_string() }).to_string()),
+        };
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        to_c_string(admitted(|| match api::compare_documents(&texts) {
+            Ok(comparison) => serde_json::json!(comparison).to_string(),
+            Err(e) => serde_json::json!({ "error": "invalid_input", "message": e.to_string() }).to_string(),
+        }))
+    })
+}
+
+// texts_json is a JSON array of strings and options_json null or the options
+// of analyze_text_with_options; returns the full results with
+// {"languages": [{"language", "messages", "share"}], "script_stats": ...},
+// or {"error": "invalid_input" | "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_batch(texts_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_text_batch", || {
+        if texts_json.is_null() {
+            return ptr::null_mut();
         }
-    };
-    
-    let key_str = unsafe {
-        match CStr::from_ptr(key).to_str() {
+        let texts_str = match unsafe { CStr::from_ptr(texts_json) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let texts: Vec<String> = match serde_json::from_str(texts_str) {
+            Ok(texts) => texts,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": e.to_string() }).to_string()),
+        };
+        let response = match api::AnalysisOptions::from_json(options_str) {
+            Ok(options) => admitted(|| serde_json::json!(api::analyze_batch(&texts, &options)).to_string()),
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }).to_string(),
+        };
+        to_c_string(response)
+    })
+}
+
+// Analyzes the text in an image like analyze_text_with_options (options_json
+// may be null) and returns the same response, or
+// {"error": "ocr_failed" | "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_image(data: *const u8, len: usize, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_image", || {
+        if data.is_null() {
+            return ptr::null_mut();
         }
-    };
-    
-    let decrypted = match crypto::decrypt(encrypted_str, key_str) {
-        Ok(result) => result,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    let c_string = match CString::new(decrypted) {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    c_string.into_raw()
+        let image = unsafe { std::slice::from_raw_parts(data, len) };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let analyzer = match api::TextAnalyzer::from_json(options_str) {
+            Ok(analyzer) => analyzer,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_options", "message": e.to_string() }).to_string()),
+        };
+        match api::ocr_text(image) {
+            Ok(text) => to_c_string(analyze_text_response(&text, &analyzer)),
+            Err(e) => to_c_string(serde_json::json!({ "error": "ocr_failed", "message": e.to_string() }).to_string()),
+        }
+    })
 }
 
+// Analyzes a transcribed voice note: {"words": [{"word", "start", "end",
+// "speaker"}, ...], "duration"}, times in seconds; options_json as for
+// analyze_text_with_options, may be null. Returns {"text", "analysis",
+// "speech", "speakers", "response_version"}, or
+// {"error": "invalid_input" | "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
 #[no_mangle]
-pub extern "C" fn process_realtime(data: *const c_char) -> *mut c_char {
-    let start_time = std::time::Instant::now();
-    
-    let data_str = unsafe {
-        match CStr::from_ptr(data).to_str() {
+pub extern "C" fn analyze_transcript(transcript_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_transcript", || {
+        if transcript_json.is_null() {
+            return ptr::null_mut();
+        }
+        let transcript_str = match unsafe { CStr::from_ptr(transcript_json) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let transcript = match api::Transcript::from_json(transcript_str) {
+            Ok(transcript) => transcript,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": e }).to_string()),
+        };
+        let analyzer = match api::TextAnalyzer::from_json(options_str) {
+            Ok(analyzer) => analyzer,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_options", "message": e.to_string() }).to_string()),
+        };
+        to_c_string(admitted(|| full_response(&analyzer.analyze_transcript(&transcript))))
+    })
+}
+
+// Starts an analysis of a text fed in chunks with analyze_text_push, e.g. an
+// exported chat log; options_json may be null. Returns a handle, or 0 when
+// the options do not parse or too many analyses are open
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_begin(options_json: *const c_char) -> u64 {
+    ffi_guard("analyze_text_begin", || {
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        match api::TextAnalyzer::from_json(options_str) {
+            Ok(analyzer) => api::analyze_text_begin(&analyzer).unwrap_or(0),
+            Err(_) => 0,
         }
-    };
-    
-    let result = realtime::process_realtime_data(data_str);
-    let processing_time = start_time.elapsed().as_millis();
-    
-    let response = serde_json::json!({
-        "status": result.status,
-        "processing_speed": result.processing_speed,
-        "latency": processing_time,
-        "quality": result.quality,
-        "timestamp": chrono::Utc::now().timestamp()
-    });
-    
-    let response_str = response.to_string();
-    let c_string = match CString::new(response_str) {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    c_string.into_raw()
+    })
 }
 
+// Feeds the next chunk; FFI_ERR_OPERATION_FAILED for an unknown handle, or
+// when the concurrency limit is reached, in which case the chunk was not
+// taken and can be pushed again
+#[cfg(feature = "nlp")]
 #[no_mangle]
-pub extern "C" fn analyze_data(data: *const c_char) -> *mut c_char {
-    let start_time = std::time::Instant::now();
-    
-    let data_str = unsafe {
-        match CStr::from_ptr(data).to_str() {
+pub extern "C" fn analyze_text_push(handle: u64, chunk: *const c_char) -> i32 {
+    ffi_guard("analyze_text_push", || {
+        if chunk.is_null() {
+            return FFI_ERR_NULL_POINTER;
+        }
+        let chunk_str = match unsafe { CStr::from_ptr(chunk) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return FFI_ERR_INVALID_UTF8,
+        };
+        match concurrency::limited(|| api::analyze_text_push(handle, chunk_str)) {
+            Ok(Ok(())) => FFI_OK,
+            _ => FFI_ERR_OPERATION_FAILED,
+        }
+    })
+}
+
+// The full analysis of everything pushed, or {"error": "unknown_handle",
+// "message": ...}; releases the handle
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_finish(handle: u64) -> *mut c_char {
+    ffi_guard("analyze_text_finish", || {
+        to_c_string(admitted(|| match api::analyze_text_finish(handle) {
+            Ok(result) => full_response(&result),
+            Err(e) => serde_json::json!({ "error": "unknown_handle", "message": e.to_string() }).to_string(),
+        }))
+    })
+}
+
+// Releases a handle without a result
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_abort(handle: u64) -> bool {
+    ffi_guard_untimed("analyze_text_abort", || api::analyze_text_abort(handle))
+}
+
+// options_json may be null or {"max_sentences": 3, "max_chars": 4096,
+// "preserve_entities": true}; returns {"summary": ...} or
+// {"error": "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn summarize_text(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("summarize_text", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::SummaryOptions::from_json(options_str) {
+            Ok(options) => serde_json::json!({ "summary": api::summarize(text_str, &options) }),
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// entities_json is a Bot API array of message entities and options_json
+// {"ellipsis", "word_boundary"}, both may be null; returns {"text",
+// "entities", "truncated"} or {"error": "invalid_input" | "invalid_options",
+// "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn truncate_for_telegram(text: *const c_char, entities_json: *const c_char, limit: usize, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("truncate_for_telegram", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
             Ok(s) => s,
             Err(_) => return ptr::null_mut(),
+        };
+        let optional = |json: *const c_char| if json.is_null() {
+            Ok("")
+        } else {
+            unsafe { CStr::from_ptr(json) }.to_str()
+        };
+        let (entities_str, options_str) = match (optional(entities_json), optional(options_json)) {
+            (Ok(entities), Ok(options)) => (entities, options),
+            _ => return ptr::null_mut(),
+        };
+        
+        let entities: Vec<api::TextEntity> = if entities_str.trim().is_empty() {
+            Vec::new()
+        } else {
+            match serde_json::from_str(entities_str) {
+                Ok(entities) => entities,
+                Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": format!("Invalid entities: {}", e) }).to_string()),
+            }
+        };
+        let options = match api::TruncateOptions::from_json(options_str) {
+            Ok(options) => options,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_options", "message": e }).to_string()),
+        };
+        
+        let response = match api::truncate_for_telegram_with_options(text_str, &entities, limit, &options) {
+            Ok(truncated) => serde_json::json!(truncated),
+            Err(e) => serde_json::json!({ "error": "invalid_input", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// options_json may be null for the defaults or e.g. {"unicode_form": "NFC",
+// "zwnj": "SPACE", "strip_diacritics": true}; returns {"text": ...} or
+// {"error": "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn normalize_text(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("normalize_text", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::TextNormalizer::from_json(options_str) {
+            Ok(normalizer) => serde_json::json!({ "text": api::normalize_text(text_str, &normalizer) }),
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// {"action": "ALLOW" | "WARN" | "DELETE", "severity": ..., "matches": [...]}
+// under the chat's moderation policy
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn moderate_message(chat_id: i64, text: *const c_char) -> *mut c_char {
+    ffi_guard("moderate_message", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::moderate_message(chat_id, text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
         }
-    };
-    
-    let result = analysis::analyze_data(data_str);
-    let analysis_time = start_time.elapsed().as_millis();
-    
-    let response = serde_json::json!({
-        "record_count": result.record_count,
-        "mean": result.mean,
-        "std_dev": result.std_dev,
-        "min": result.min,
-        "max": result.max,
-        "patterns": result.patterns,
-        "anomalies": result.anomalies,
-        "prediction": result.prediction,
-        "analysis_time": analysis_time
-    });
-    
-    let response_str = response.to_string();
-    let c_string = match CString::new(response_str) {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    c_string.into_raw()
+    })
 }
 
+// Moderation of the text in an image, e.g. a meme caption: the
+// moderate_message response, or {"error": "ocr_failed", "message": ...}
+#[cfg(feature = "nlp")]
 #[no_mangle]
-pub extern "C" fn free_string(ptr: *mut c_char) {
-    if !ptr.is_null() {
-        unsafe {
-            let _ = CString::from_raw(ptr);
+pub extern "C" fn moderate_image(chat_id: i64, data: *const u8, len: usize) -> *mut c_char {
+    ffi_guard("moderate_image", || {
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+        let image = unsafe { std::slice::from_raw_parts(data, len) };
+        let response = match api::moderate_image(chat_id, image) {
+            Ok(result) => serde_json::json!(result),
+            Err(e) => serde_json::json!({ "error": "ocr_failed", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// policy_json: {"banned_words": {"word": "SEVERE"}, "allowed_words": [...],
+// "warn_at": "MILD", "delete_at": "MODERATE", "language": "fa"}, every field
+// optional
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn set_chat_moderation_policy(chat_id: i64, policy_json: *const c_char) -> bool {
+    ffi_guard("set_chat_moderation_policy", || {
+        if policy_json.is_null() {
+            return false;
+        }
+        let policy = match unsafe { CStr::from_ptr(policy_json) }.to_str().ok().and_then(|s| serde_json::from_str(s).ok()) {
+            Some(policy) => policy,
+            None => return false,
+        };
+        
+        match api::set_chat_moderation_policy(chat_id, policy) {
+            Ok(()) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "moderation", "set_chat_moderation_policy", line!());
+                false
+            }
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn clear_chat_moderation_policy(chat_id: i64) -> bool {
+    ffi_guard("clear_chat_moderation_policy", || api::clear_chat_moderation_policy(chat_id).is_ok())
+}
+
+// Relative paths resolve under the data directory
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn open_moderation_store(path: *const c_char) -> bool {
+    ffi_guard("open_moderation_store", || {
+        if path.is_null() {
+            return false;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match api::open_moderation_store(path_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "moderation", "open_moderation_store", line!());
+                false
+            }
+        }
+    })
+}
+
+// Plugs a host analyzer into the text pipeline under `name`, replacing any
+// analyzer already registered under it. The callback gets each message and
+// returns a JSON document (or NULL) that appears under "plugins"."name" in the
+// result. `release` may be NULL; otherwise it receives every non-null result
+// once the library has copied it. The callback can run on any thread.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn register_analyzer(name: *const c_char, analyze: Option<plugins::AnalyzerCallback>, release: Option<plugins::ReleaseCallback>) -> bool {
+    ffi_guard("register_analyzer", || {
+        if name.is_null() {
+            return false;
+        }
+        let name_str = match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match analyze {
+            Some(analyze) => plugins::register_host(name_str, analyze, release).is_ok(),
+            None => false,
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn unregister_analyzer(name: *const c_char) -> bool {
+    ffi_guard("unregister_analyzer", || {
+        if name.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::unregister_analyzer(name_str),
+            Err(_) => false,
+        }
+    })
+}
+
+// Loads a gazetteer file for entity extraction: JSON {"ORG": ["Acme"]},
+// JSON [{"name": "Acme", "type": "ORG"}] or CSV "name,type" lines.
+// default_type (may be NULL) covers CSV lines without a type.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_gazetteer(name: *const c_char, path: *const c_char, default_type: *const c_char) -> bool {
+    ffi_guard("load_gazetteer", || {
+        let (name_str, path_str) = unsafe {
+            match (CStr::from_ptr(name).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(name), Ok(path)) => (name, path),
+                _ => return false,
+            }
+        };
+        
+        let default_type_str = if default_type.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(default_type) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return false,
+            }
+        };
+        
+        match api::load_gazetteer(name_str, path_str, default_type_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "gazetteers", "load_gazetteer", line!());
+                false
+            }
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn remove_gazetteer(name: *const c_char) -> bool {
+    ffi_guard("remove_gazetteer", || {
+        if name.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::remove_gazetteer(name_str),
+            Err(_) => false,
+        }
+    })
+}
+
+// pattern_json is {"name": "order", "entity_type": "ORDER", "pattern":
+// "\\bORD-\\d{6}\\b", "confidence": 0.9, "group": 0, "case_insensitive":
+// false}, the last three optional. A pattern of the same name is replaced.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn add_entity_pattern(pattern_json: *const c_char) -> bool {
+    ffi_guard("add_entity_pattern", || {
+        if pattern_json.is_null() {
+            return false;
         }
+        let pattern = match unsafe { CStr::from_ptr(pattern_json) }.to_str().map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(s).map_err(|e| e.to_string())) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                logging::error(&format!("Invalid entity pattern: {}", e), "entity_patterns", "add_entity_pattern", line!());
+                return false;
+            }
+        };
+        
+        match api::add_entity_pattern(pattern) {
+            Ok(()) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "entity_patterns", "add_entity_pattern", line!());
+                false
+            }
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn remove_entity_pattern(name: *const c_char) -> bool {
+    ffi_guard("remove_entity_pattern", || {
+        if name.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::remove_entity_pattern(name_str),
+            Err(_) => false,
+        }
+    })
+}
+
+// {"patterns": [...]} in the order they are tried
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn list_entity_patterns() -> *mut c_char {
+    ffi_guard_untimed("list_entity_patterns", || {
+        to_c_string(serde_json::json!({ "patterns": api::entity_patterns() }).to_string())
+    })
+}
+
+// Finglish to Persian script with direction "to_persian", or back with
+// "to_latin"; NULL for an unknown direction
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn transliterate_text(text: *const c_char, direction: *const c_char) -> *mut c_char {
+    ffi_guard("transliterate_text", || {
+        if text.is_null() || direction.is_null() {
+            return ptr::null_mut();
+        }
+        let (text_str, direction_str) = match (unsafe { CStr::from_ptr(text) }.to_str(), unsafe { CStr::from_ptr(direction) }.to_str()) {
+            (Ok(text), Ok(direction)) => (text, direction),
+            _ => return ptr::null_mut(),
+        };
+        
+        match api::TransliterationDirection::from_name(direction_str) {
+            Some(direction) => to_c_string(api::transliterate(text_str, direction)),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+// Text with chat abbreviations ("imo", "س.ع") spelled out
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn expand_abbreviations(text: *const c_char) -> *mut c_char {
+    ffi_guard("expand_abbreviations", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(text_str) => to_c_string(api::expand_abbreviations(text_str)),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// JSON array of {"start", "end", "language", "confidence"} covering text in
+// order, with byte offsets into it
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn detect_languages_segmented(text: *const c_char) -> *mut c_char {
+    ffi_guard("detect_languages_segmented", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::detect_languages_segmented(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+fn pos_export(name: &'static str, text: *const c_char, language: *const c_char, run: fn(&str, Option<&str>) -> serde_json::Value) -> *mut c_char {
+    ffi_guard(name, || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        let language_str = if language.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(language) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(run(text_str, language_str).to_string())
+    })
+}
+
+// JSON array of {"text", "start", "end", "tag"} with Universal Dependencies
+// tags ("NOUN", "VERB", ...); language may be NULL to detect it per segment
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn pos_tag_text(text: *const c_char, language: *const c_char) -> *mut c_char {
+    pos_export("pos_tag_text", text, language, |text, language| serde_json::json!(api::pos_tag(text, language)))
+}
+
+// JSON array of {"text", "start", "end", "head"}; language as for pos_tag_text
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn extract_noun_phrases(text: *const c_char, language: *const c_char) -> *mut c_char {
+    pos_export("extract_noun_phrases", text, language, |text, language| serde_json::json!(api::noun_phrases(text, language)))
+}
+
+// Loads a perceptron tagger saved as JSON for the language
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_pos_model(language: *const c_char, path: *const c_char) -> bool {
+    ffi_guard("load_pos_model", || {
+        if language.is_null() || path.is_null() {
+            return false;
+        }
+        let (language_str, path_str) = unsafe {
+            match (CStr::from_ptr(language).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(language), Ok(path)) => (language, path),
+                _ => return false,
+            }
+        };
+        
+        match api::load_pos_model(language_str, path_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "pos", "load_pos_model", line!());
+                false
+            }
+        }
+    })
+}
+
+// JSON array of {"name", "entity_type", "confidence", "start", "end"}, with
+// byte offsets into text
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn extract_entities(text: *const c_char) -> *mut c_char {
+    ffi_guard("extract_entities", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::extract_entities(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// ReadabilityReport of text as JSON; language may be NULL to detect it
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_readability(text: *const c_char, language: *const c_char) -> *mut c_char {
+    ffi_guard("get_readability", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let language_str = if language.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(language) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::readability(text_str, language_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Stem of a single word; language may be NULL for unknown. Returns NULL
+// on invalid input.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn stem_word(word: *const c_char, language: *const c_char) -> *mut c_char {
+    ffi_guard("stem_word", || {
+        let word_str = unsafe {
+            match CStr::from_ptr(word).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let language_str = if language.is_null() {
+            "unknown"
+        } else {
+            match unsafe { CStr::from_ptr(language) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(api::stem(word_str, language_str))
+    })
+}
+
+// EmojiStats of text as JSON
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_emoji_stats(text: *const c_char) -> *mut c_char {
+    ffi_guard("get_emoji_stats", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::emoji_stats(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// options_json may be null for the defaults or e.g. {"kinds": ["EMAIL",
+// "CREDIT_CARD"], "style": "LABEL"}; returns {"matches": [{"kind", "text",
+// "start", "end"}]} or {"error": "invalid_options", "message": ...}
+#[no_mangle]
+pub extern "C" fn find_pii(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("find_pii", || {
+        pii_response(text, options_json, |text, redactor| serde_json::json!({ "matches": api::find_pii(text, redactor) }))
+    })
+}
+
+// Same options as find_pii; returns {"text": ...} with every match masked
+// ("j***@example.com") or labelled ("[REDACTED:EMAIL]")
+#[no_mangle]
+pub extern "C" fn redact_pii(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("redact_pii", || {
+        pii_response(text, options_json, |text, redactor| serde_json::json!({ "text": api::redact_pii(text, redactor) }))
+    })
+}
+
+fn pii_response<F: FnOnce(&str, &api::PiiRedactor) -> serde_json::Value>(text: *const c_char, options_json: *const c_char, respond: F) -> *mut c_char {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+    let options_str = if options_json.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(options_json) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        }
+    };
+    
+    let response = match api::PiiRedactor::from_json(options_str) {
+        Ok(redactor) => respond(text_str, &redactor),
+        Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+    };
+    to_c_string(response.to_string())
+}
+
+// TagStats of text as JSON: {"total", "hashtags", "mentions", "cashtags"},
+// each a list of {"tag", "count"} with the most used first
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_tag_stats(text: *const c_char) -> *mut c_char {
+    ffi_guard("get_tag_stats", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::tag_stats(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// StructureStats of text as JSON
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_structure_stats(text: *const c_char) -> *mut c_char {
+    ffi_guard("get_structure_stats", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::structure_stats(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// SpamScore of text as JSON
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_spam_score(text: *const c_char) -> *mut c_char {
+    ffi_guard("get_spam_score", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::spam_score(text_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Plugs a host embedding model into reply suggestions. The callback writes up
+// to `dimensions` floats for a text and returns how many it wrote, 0 when it
+// cannot embed it. NULL goes back to keyword matching.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn register_embedder(embed: Option<replies::EmbedderCallback>, dimensions: usize) -> bool {
+    ffi_guard("register_embedder", || {
+        match embed {
+            Some(embed) => replies::set_host_embedder(embed, dimensions).is_ok(),
+            None => {
+                replies::set_embedder(None);
+                true
+            }
+        }
+    })
+}
+
+// Sets the OCR provider behind ocr_image, analyze_image and moderate_image:
+// `recognize` receives the image bytes and returns their text, or NULL when
+// it cannot read them. `release` may be NULL; otherwise it receives every
+// non-null text once the library has copied it. NULL `recognize` removes
+// the provider. Either way cached OCR results are dropped.
+#[no_mangle]
+pub extern "C" fn register_ocr_provider(recognize: Option<ocr::OcrCallback>, release: Option<ocr::OcrReleaseCallback>) -> bool {
+    ffi_guard("register_ocr_provider", || {
+        match recognize {
+            Some(recognize) => ocr::set_host_provider(recognize, release),
+            None => ocr::set_provider(None),
+        }
+        true
+    })
+}
+
+// Makes an external program the OCR provider: it gets each image on stdin
+// and writes the text to stdout, e.g. "tesseract" with
+// ["stdin", "stdout", "-l", "eng+fas"]. args_json may be null; timeout_ms 0
+// keeps the default of 30 seconds.
+#[cfg(feature = "ocr")]
+#[no_mangle]
+pub extern "C" fn set_ocr_command(program: *const c_char, args_json: *const c_char, timeout_ms: u64) -> bool {
+    ffi_guard("set_ocr_command", || {
+        if program.is_null() {
+            return false;
+        }
+        let program_str = match unsafe { CStr::from_ptr(program) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        let args: Vec<String> = if args_json.is_null() {
+            Vec::new()
+        } else {
+            match unsafe { CStr::from_ptr(args_json) }.to_str().ok().and_then(|s| serde_json::from_str(s).ok()) {
+                Some(args) => args,
+                None => return false,
+            }
+        };
+        
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let mut command = api::CommandOcr::new(program_str, &args);
+        if timeout_ms > 0 {
+            command = command.with_timeout_ms(timeout_ms);
+        }
+        api::set_ocr_provider(Some(std::sync::Arc::new(command)));
+        true
+    })
+}
+
+// {"text": ...} for the image in data[..len], or
+// {"error": "ocr_failed", "message": ...}
+#[no_mangle]
+pub extern "C" fn ocr_image(data: *const u8, len: usize) -> *mut c_char {
+    ffi_guard("ocr_image", || {
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+        let image = unsafe { std::slice::from_raw_parts(data, len) };
+        let response = match api::ocr_text(image) {
+            Ok(text) => serde_json::json!({ "text": text }),
+            Err(e) => serde_json::json!({ "error": "ocr_failed", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// {"url", "host", "verdict": "SAFE" | "SUSPICIOUS" | "DANGEROUS", "risk",
+// "reasons": [...]} for a single link
+#[no_mangle]
+pub extern "C" fn classify_link(url: *const c_char) -> *mut c_char {
+    ffi_guard("classify_link", || {
+        if url.is_null() {
+            return ptr::null_mut();
+        }
+        let url_str = match unsafe { CStr::from_ptr(url) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::classify_link(url_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// {"links": [...]} with a classify_link report per link in the text; unsafe
+// links are recorded as UNSAFE_LINK security events
+#[no_mangle]
+pub extern "C" fn scan_links(text: *const c_char) -> *mut c_char {
+    ffi_guard("scan_links", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        to_c_string(serde_json::json!({ "links": api::scan_links(text_str) }).to_string())
+    })
+}
+
+// payload is the text the host's scanner read out of a QR code; returns
+// {"kind": "URL" | "WIFI" | "CONTACT" | "EMAIL" | "SMS" | "PHONE" | "GEO" |
+// "OTP" | "TEXT", "fields": {...}, "links": [...], "verdict"}
+#[no_mangle]
+pub extern "C" fn decode_qr_payload(payload: *const c_char) -> *mut c_char {
+    ffi_guard("decode_qr_payload", || {
+        if payload.is_null() {
+            return ptr::null_mut();
+        }
+        let payload_str = match unsafe { CStr::from_ptr(payload) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::decode_qr_payload(payload_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// options_json null or {"error_correction": "LOW" | "MEDIUM" | "QUARTILE" |
+// "HIGH", "border", "module_size", "dark", "light"}; returns {"version",
+// "size", "svg"} or {"error": "invalid_input" | "invalid_options", "message": ...}
+#[cfg(feature = "qr")]
+#[no_mangle]
+pub extern "C" fn generate_qr(text: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("generate_qr", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        let text_str = match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::QrOptions::from_json(options_str) {
+            Ok(options) => match api::generate_qr(text_str, &options) {
+                Ok(image) => serde_json::to_value(image).unwrap_or_default(),
+                Err(e) => serde_json::json!({ "error": "invalid_input", "message": e.to_string() }),
+            },
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Prepares a named set of canned replies from a JSON array of strings,
+// replacing any set of that name
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn set_reply_set(name: *const c_char, replies_json: *const c_char) -> bool {
+    ffi_guard("set_reply_set", || {
+        let (name_str, replies_str) = unsafe {
+            match (CStr::from_ptr(name).to_str(), CStr::from_ptr(replies_json).to_str()) {
+                (Ok(name), Ok(replies)) => (name, replies),
+                _ => return false,
+            }
+        };
+        
+        match serde_json::from_str::<Vec<String>>(replies_str) {
+            Ok(replies) => {
+                api::set_reply_set(name_str, &replies);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+// Adds a reply to a named set; returns its id, or -1 when there is no such set
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn add_reply(name: *const c_char, reply: *const c_char) -> i64 {
+    ffi_guard("add_reply", || {
+        if name.is_null() || reply.is_null() {
+            return -1;
+        }
+        let (name_str, reply_str) = unsafe {
+            match (CStr::from_ptr(name).to_str(), CStr::from_ptr(reply).to_str()) {
+                (Ok(name), Ok(reply)) => (name, reply),
+                _ => return -1,
+            }
+        };
+        
+        match api::add_reply(name_str, reply_str) {
+            Ok(id) => id as i64,
+            Err(_) => -1,
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn remove_reply(name: *const c_char, id: usize) -> bool {
+    ffi_guard("remove_reply", || {
+        if name.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::remove_reply(name_str, id),
+            Err(_) => false,
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+fn reply_set_file(name: &'static str, set: *const c_char, path: *const c_char, run: fn(&str, &str) -> api::Result<usize>) -> i64 {
+    ffi_guard(name, || {
+        if set.is_null() || path.is_null() {
+            return -1;
+        }
+        let (set_str, path_str) = unsafe {
+            match (CStr::from_ptr(set).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(set), Ok(path)) => (set, path),
+                _ => return -1,
+            }
+        };
+        
+        match run(set_str, path_str) {
+            Ok(count) => count as i64,
+            Err(e) => {
+                logging::error(&e.to_string(), "replies", name, line!());
+                -1
+            }
+        }
+    })
+}
+
+// Writes a reply set with its embeddings so a restart can load it instead of
+// embedding every reply again; both return the number of replies, or -1
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn save_reply_set(name: *const c_char, path: *const c_char) -> i64 {
+    reply_set_file("save_reply_set", name, path, api::save_reply_set)
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_reply_set(name: *const c_char, path: *const c_char) -> i64 {
+    reply_set_file("load_reply_set", name, path, api::load_reply_set)
+}
+
+// Best replies of the set for the message: a JSON array of {"index", "reply",
+// "confidence", "method"}, or {"error": "unknown_reply_set"}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn suggest_replies(name: *const c_char, message: *const c_char, limit: usize) -> *mut c_char {
+    ffi_guard("suggest_replies", || {
+        let (name_str, message_str) = unsafe {
+            match (CStr::from_ptr(name).to_str(), CStr::from_ptr(message).to_str()) {
+                (Ok(name), Ok(message)) => (name, message),
+                _ => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::suggest_replies(name_str, message_str, limit) {
+            Ok(suggestions) => serde_json::json!(suggestions),
+            Err(e) => serde_json::json!({ "error": "unknown_reply_set", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Digest of a group conversation from a JSON array of {"author", "date",
+// "text"} messages with optional "id" and "reply_to"; max_tokens bounds the
+// words in the digest (0 for no bound). Returns the digest as JSON, or
+// {"error": "invalid_messages"}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn summarize_conversation(messages_json: *const c_char, max_tokens: usize) -> *mut c_char {
+    ffi_guard("summarize_conversation", || {
+        let messages_str = unsafe {
+            match CStr::from_ptr(messages_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::summarize_conversation(messages_str, max_tokens) {
+            Ok(digest) => serde_json::json!(digest),
+            Err(e) => serde_json::json!({ "error": "invalid_messages", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Adds a message to the background corpus the text pipeline ranks keywords
+// against (TF-IDF); until the first call keywords are ranked by frequency
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn add_keyword_corpus_document(text: *const c_char) -> bool {
+    ffi_guard("add_keyword_corpus_document", || {
+        if text.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(text_str) => {
+                api::add_keyword_corpus_document(text_str);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn clear_keyword_corpus() {
+    ffi_guard("clear_keyword_corpus", || api::set_keyword_corpus(None))
+}
+
+// commands_json is a JSON array of the bot's commands, e.g. ["/start",
+// "/help"], and options_json null or {"min_confidence": 0.6, "limit": 3};
+// returns {"matches": [{"command", "distance", "confidence"}]}, best first,
+// or {"error": "invalid_input" | "invalid_options", "message": ...}
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn match_command(input: *const c_char, commands_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("match_command", || {
+        if input.is_null() || commands_json.is_null() {
+            return ptr::null_mut();
+        }
+        let (input_str, commands_str) = match (unsafe { CStr::from_ptr(input) }.to_str(), unsafe { CStr::from_ptr(commands_json) }.to_str()) {
+            (Ok(input), Ok(commands)) => (input, commands),
+            _ => return ptr::null_mut(),
+        };
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let commands: Vec<String> = match serde_json::from_str(commands_str) {
+            Ok(commands) => commands,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": e.to_string() }).to_string()),
+        };
+        let response = match api::CommandMatchOptions::from_json(options_str) {
+            Ok(options) => serde_json::json!({ "matches": api::match_command(input_str, &commands, &options) }),
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// {"cosine", "jaccard", "levenshtein"}, each 0-1
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn text_similarity(a: *const c_char, b: *const c_char) -> *mut c_char {
+    ffi_guard("text_similarity", || {
+        if a.is_null() || b.is_null() {
+            return ptr::null_mut();
+        }
+        let (a_str, b_str) = match (unsafe { CStr::from_ptr(a) }.to_str(), unsafe { CStr::from_ptr(b) }.to_str()) {
+            (Ok(a), Ok(b)) => (a, b),
+            _ => return ptr::null_mut(),
+        };
+        to_c_string(serde_json::json!(api::similarity(a_str, b_str)).to_string())
+    })
+}
+
+// Adds a message to the recent messages reposts are matched against and
+// plagiarism_score is measured on; false when it is too short to track
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn add_duplicate_corpus_document(id: *const c_char, text: *const c_char) -> bool {
+    ffi_guard("add_duplicate_corpus_document", || {
+        if id.is_null() || text.is_null() {
+            return false;
+        }
+        match (unsafe { CStr::from_ptr(id) }.to_str(), unsafe { CStr::from_ptr(text) }.to_str()) {
+            (Ok(id_str), Ok(text_str)) => api::add_duplicate_corpus_document(id_str, text_str),
+            _ => false,
+        }
+    })
+}
+
+// JSON array of {"id", "similarity"}, most similar first
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn find_duplicates(text: *const c_char) -> *mut c_char {
+    ffi_guard("find_duplicates", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(text_str) => to_c_string(serde_json::json!(api::find_duplicates(text_str)).to_string()),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn clear_duplicate_corpus() {
+    ffi_guard("clear_duplicate_corpus", || api::set_duplicate_corpus(None))
+}
+
+// Sets K, the window and the other TopicModelConfig fields from JSON; NULL
+// or empty uses the defaults
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn configure_topic_model(config_json: *const c_char) -> bool {
+    ffi_guard("configure_topic_model", || {
+        let config_str = if config_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(config_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        let result = api::TopicModelConfig::from_json(config_str)
+            .map_err(api::Error::Config)
+            .and_then(api::configure_topic_model);
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "topics", "configure_topic_model", line!());
+                false
+            }
+        }
+    })
+}
+
+// Adds a message to the window the text pipeline's topic model trains on;
+// until it has been trained topics are picked by frequency
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn add_topic_corpus_document(text: *const c_char) -> bool {
+    ffi_guard("add_topic_corpus_document", || {
+        if text.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(text) }.to_str() {
+            Ok(text_str) => {
+                api::add_topic_corpus_document(text_str);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+// Trains now and returns the topics with their keywords as JSON
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn train_topic_model() -> *mut c_char {
+    ffi_guard("train_topic_model", || {
+        let response = match api::train_topic_model() {
+            Ok(topics) => serde_json::json!(topics),
+            Err(e) => serde_json::json!({ "error": "not_trained", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_topic_model_topics() -> *mut c_char {
+    ffi_guard("get_topic_model_topics", || to_c_string(serde_json::json!(api::topic_model_topics()).to_string()))
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn clear_topic_model() {
+    ffi_guard("clear_topic_model", api::clear_topic_model)
+}
+
+#[cfg(feature = "nlp")]
+fn topic_model_file(name: &'static str, path: *const c_char, run: fn(&str) -> api::Result<usize>) -> bool {
+    ffi_guard(name, || {
+        if path.is_null() {
+            return false;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match run(path_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "topics", name, line!());
+                false
+            }
+        }
+    })
+}
+
+// Persists the model and its window so it survives a restart
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn save_topic_model(path: *const c_char) -> bool {
+    topic_model_file("save_topic_model", path, api::save_topic_model)
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_topic_model(path: *const c_char) -> bool {
+    topic_model_file("load_topic_model", path, api::load_topic_model)
+}
+
+// Replaces the sentiment lexicon of the text pipeline with the file at path
+// (JSON object or "word valence" lines); with extend_default the built-in
+// English/Persian entries are kept and the file's entries added on top
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_sentiment_lexicon(path: *const c_char, extend_default: bool) -> bool {
+    ffi_guard("load_sentiment_lexicon", || {
+        if path.is_null() {
+            return false;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match api::SentimentAnalyzer::from_lexicon(path_str) {
+            Ok(analyzer) if extend_default => {
+                api::set_sentiment_analyzer(api::SentimentAnalyzer::default().extend(&analyzer));
+                true
+            }
+            Ok(analyzer) => {
+                api::set_sentiment_analyzer(analyzer);
+                true
+            }
+            Err(e) => {
+                logging::error(&e, "sentiment", "load_sentiment_lexicon", line!());
+                false
+            }
+        }
+    })
+}
+
+// Sets the lexicon shadow analysis compares with the active one, loaded like
+// load_sentiment_lexicon; a NULL path stops comparing sentiment
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn load_shadow_sentiment_lexicon(path: *const c_char, extend_default: bool) -> bool {
+    ffi_guard("load_shadow_sentiment_lexicon", || {
+        if path.is_null() {
+            api::set_shadow_sentiment_analyzer(None);
+            return true;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match api::SentimentAnalyzer::from_lexicon(path_str) {
+            Ok(analyzer) if extend_default => {
+                api::set_shadow_sentiment_analyzer(Some(api::SentimentAnalyzer::default().extend(&analyzer)));
+                true
+            }
+            Ok(analyzer) => {
+                api::set_shadow_sentiment_analyzer(Some(analyzer));
+                true
+            }
+            Err(e) => {
+                logging::error(&e, "shadow", "load_shadow_sentiment_lexicon", line!());
+                false
+            }
+        }
+    })
+}
+
+// ShadowReport of the current tenant as JSON
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn get_shadow_report() -> *mut c_char {
+    ffi_guard_untimed("get_shadow_report", || match serde_json::to_string(&api::shadow_report()) {
+        Ok(response) => to_c_string(response),
+        Err(_) => ptr::null_mut(),
+    })
+}
+
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn reset_shadow_report() -> bool {
+    ffi_guard("reset_shadow_report", api::reset_shadow_report)
+}
+
+// Feeds one Bot API Message into the per-chat aggregations; returns false if
+// it is not a message object
+#[no_mangle]
+pub extern "C" fn record_chat_message(message_json: *const c_char) -> bool {
+    ffi_guard("record_chat_message", || {
+        if message_json.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(message_json) }.to_str() {
+            Ok(message_str) => api::record_chat_message(message_str).is_ok(),
+            Err(_) => false,
+        }
+    })
+}
+
+// Feeds one Bot API ChatMemberUpdated into the membership cohorts
+#[no_mangle]
+pub extern "C" fn record_chat_member_update(update_json: *const c_char) -> bool {
+    ffi_guard("record_chat_member_update", || {
+        if update_json.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(update_json) }.to_str() {
+            Ok(update_str) => api::record_chat_member_update(update_str).is_ok(),
+            Err(_) => false,
+        }
+    })
+}
+
+// Joins, leaves, churn and D1/D7/D30 retention by join cohort over the last
+// window_seconds (one day to 90 days)
+#[no_mangle]
+pub extern "C" fn get_member_report(chat_id: i64, window_seconds: i64) -> *mut c_char {
+    ffi_guard("get_member_report", || {
+        match serde_json::to_string(&api::member_report(chat_id, window_seconds)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Top stickers, sticker sets and custom emoji of a chat over the last
+// window_seconds (one hour to 30 days)
+#[no_mangle]
+pub extern "C" fn get_sticker_report(chat_id: i64, window_seconds: i64, limit: usize) -> *mut c_char {
+    ffi_guard("get_sticker_report", || {
+        match serde_json::to_string(&api::sticker_report(chat_id, window_seconds, limit)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Feeds one Bot API MessageReactionCountUpdated into the leaderboards
+#[no_mangle]
+pub extern "C" fn record_reaction_count(update_json: *const c_char) -> bool {
+    ffi_guard("record_reaction_count", || {
+        if update_json.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(update_json) }.to_str() {
+            Ok(update_str) => api::record_reaction_count(update_str).is_ok(),
+            Err(_) => false,
+        }
+    })
+}
+
+// Views and forwards of a post, which the Bot API does not send; the latest
+// totals replace earlier ones
+#[no_mangle]
+pub extern "C" fn record_post_metrics(chat_id: i64, message_id: i64, views: u64, forwards: u64) {
+    ffi_guard("record_post_metrics", || api::record_post_metrics(chat_id, message_id, views, forwards))
+}
+
+// Members of the chat, e.g. from getChatMemberCount; engagement indexes are
+// per 100 of them. 0 goes back to counting the members who posted.
+#[no_mangle]
+pub extern "C" fn record_chat_member_count(chat_id: i64, count: u64) {
+    ffi_guard("record_chat_member_count", || api::record_chat_member_count(chat_id, count))
+}
+
+// [{"rank", "message_id", "user_id", "date", "views", "forwards", "replies",
+// "reactions", "score", "index", "relative"}] for the posts of the last day
+// trending in the chat
+#[no_mangle]
+pub extern "C" fn get_trending_posts(chat_id: i64, limit: usize) -> *mut c_char {
+    ffi_guard("get_trending_posts", || {
+        match serde_json::to_string(&api::trending_posts(chat_id, limit)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// {"chat_id", "window_seconds", "messages_per_minute",
+// "target_messages_per_minute", "active_users", "slowmode_seconds",
+// "previous_slowmode_seconds", "changed", "expected_messages_per_minute",
+// "cooldowns": [{"user_id", "messages", "messages_per_minute",
+// "cooldown_seconds"}]}
+#[no_mangle]
+pub extern "C" fn recommend_slowmode(chat_id: i64) -> *mut c_char {
+    ffi_guard("recommend_slowmode", || {
+        match serde_json::to_string(&api::recommend_slowmode(chat_id)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// options_json may be null or {"window_seconds": 604800, "until": null,
+// "limit": 10}; returns the Leaderboard or
+// {"error": "invalid_options", "message": ...}
+#[no_mangle]
+pub extern "C" fn get_chat_leaderboard(chat_id: i64, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("get_chat_leaderboard", || {
+        let options_str = if options_json.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::LeaderboardOptions::from_json(options_str) {
+            Ok(options) => serde_json::json!(api::leaderboard(chat_id, &options)),
+            Err(e) => serde_json::json!({ "error": "invalid_options", "message": e }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Writes a spreadsheet of a JSON array of records to path (relative paths
+// resolve under the data directory); format is "csv" or "xlsx"
+#[no_mangle]
+pub extern "C" fn export_records(name: *const c_char, records_json: *const c_char, format: *const c_char, path: *const c_char) -> bool {
+    ffi_guard("export_records", || {
+        let (name_str, records_str, format_str, path_str) = unsafe {
+            match (CStr::from_ptr(name).to_str(), CStr::from_ptr(records_json).to_str(), CStr::from_ptr(format).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(name), Ok(records), Ok(format), Ok(path)) => (name, records, format, path),
+                _ => return false,
+            }
+        };
+        
+        let format = match api::ExportFormat::parse(format_str) {
+            Some(format) => format,
+            None => return false,
+        };
+        match api::export_json_to_file(name_str, records_str, format, path_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "export", "export_records", line!());
+                false
+            }
+        }
+    })
+}
+
+// Adds a compound sentiment score (-1 to 1) from the host's own model;
+// user_id 0 means the author is unknown
+#[no_mangle]
+pub extern "C" fn record_sentiment(chat_id: i64, user_id: i64, date: i64, score: f64) {
+    ffi_guard("record_sentiment", || {
+        api::record_sentiment(chat_id, (user_id != 0).then_some(user_id), date, score)
+    })
+}
+
+// Average, last day against baseline, and daily averages of a chat's
+// sentiment (or one member's; user_id 0 for the whole chat) over the last
+// window_seconds (one day to 30 days)
+#[no_mangle]
+pub extern "C" fn get_sentiment_trend(chat_id: i64, user_id: i64, window_seconds: i64) -> *mut c_char {
+    ffi_guard("get_sentiment_trend", || {
+        match serde_json::to_string(&api::sentiment_trend(chat_id, (user_id != 0).then_some(user_id), window_seconds)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Signed /start payload for a campaign, at most 64 characters; data may be
+// NULL. Returns NULL if campaign and data do not fit.
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn create_deep_link_payload(campaign: *const c_char, data: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_guard("create_deep_link_payload", || {
+        let campaign_str = unsafe {
+            match CStr::from_ptr(campaign).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let data_str = if data.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(data) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let key_str = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match api::encode_deep_link(campaign_str, data_str, key_str) {
+            Ok(payload) => to_c_string(payload),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Verifies a start payload (or the whole "/start ..." text) and counts it for
+// its campaign. user_id 0 means unknown. Returns {"campaign": ..., "data": ...}
+// or {"error": "invalid_deep_link", "message": ...}.
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn track_deep_link_start(payload: *const c_char, user_id: i64, key: *const c_char) -> *mut c_char {
+    ffi_guard("track_deep_link_start", || {
+        let payload_str = unsafe {
+            match CStr::from_ptr(payload).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let key_str = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let user = if user_id == 0 { None } else { Some(user_id) };
+        let response = match api::track_deep_link_start(payload_str, user, key_str) {
+            Ok(link) => serde_json::json!(link),
+            Err(e) => serde_json::json!({ "error": "invalid_deep_link", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// How many times the host showed a campaign's link, for click-through rates
+#[no_mangle]
+pub extern "C" fn record_campaign_impressions(campaign: *const c_char, impressions: u64) -> bool {
+    ffi_guard("record_campaign_impressions", || {
+        if campaign.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(campaign) }.to_str() {
+            Ok(campaign_str) => {
+                api::record_campaign_impressions(campaign_str, impressions);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn get_campaign_report(window_seconds: i64) -> *mut c_char {
+    ffi_guard("get_campaign_report", || {
+        match serde_json::to_string(&api::campaign_report(window_seconds)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Adds by (which may be negative) to the named counter of the current
+// tenant and returns the new total; totals stop at 0, so -1 means a bad name
+#[no_mangle]
+pub extern "C" fn incr_counter(name: *const c_char, by: i64) -> i64 {
+    ffi_guard("incr_counter", || {
+        if name.is_null() {
+            return -1;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::incr_counter(name_str, by),
+            Err(_) => -1,
+        }
+    })
+}
+
+// 0 for a counter never incremented, -1 for a bad name
+#[no_mangle]
+pub extern "C" fn get_counter(name: *const c_char) -> i64 {
+    ffi_guard_untimed("get_counter", || {
+        if name.is_null() {
+            return -1;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::get_counter(name_str),
+            Err(_) => -1,
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn reset_counter(name: *const c_char) -> bool {
+    ffi_guard("reset_counter", || {
+        if name.is_null() {
+            return false;
+        }
+        match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(name_str) => api::reset_counter(name_str),
+            Err(_) => false,
+        }
+    })
+}
+
+// JSON array of {"name", "total"} for the counters whose name starts with
+// prefix; null or "" lists them all
+#[no_mangle]
+pub extern "C" fn list_counters(prefix: *const c_char) -> *mut c_char {
+    ffi_guard_untimed("list_counters", || {
+        let prefix_str = if prefix.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(prefix) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::list_counters(prefix_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// {"name", "total", "window_total", "step_seconds", "points": [{"start",
+// "count"}]} over the last window_seconds, or {"error": "unknown_counter"}
+#[no_mangle]
+pub extern "C" fn counter_rollup(name: *const c_char, window_seconds: i64, step_seconds: i64) -> *mut c_char {
+    ffi_guard("counter_rollup", || {
+        if name.is_null() {
+            return ptr::null_mut();
+        }
+        let name_str = match unsafe { CStr::from_ptr(name) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        let response = match api::counter_rollup(name_str, window_seconds, step_seconds) {
+            Some(rollup) => serde_json::json!(rollup),
+            None => serde_json::json!({ "error": "unknown_counter" }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Saves or loads a tenant's counters or experiments; failures are logged
+// under module
+fn state_file(module: &str, name: &'static str, path: *const c_char, run: fn(&str) -> api::Result<usize>) -> i64 {
+    ffi_guard(name, || {
+        if path.is_null() {
+            return -1;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return -1,
+        };
+        
+        match run(path_str) {
+            Ok(count) => count as i64,
+            Err(e) => {
+                logging::error(&e.to_string(), module, name, line!());
+                -1
+            }
+        }
+    })
+}
+
+// Writes or replaces the current tenant's counters; both return how many
+// counters the file holds, or -1
+#[no_mangle]
+pub extern "C" fn save_counters(path: *const c_char) -> i64 {
+    state_file("counters", "save_counters", path, api::save_counters)
+}
+
+#[no_mangle]
+pub extern "C" fn load_counters(path: *const c_char) -> i64 {
+    state_file("counters", "load_counters", path, api::load_counters)
+}
+
+// Chooses one of the variants in the JSON array (for example reply template
+// names) for the experiment and context, which may be null for none;
+// {"variant"}, or {"error": "invalid_input", "message": ...}
+#[no_mangle]
+pub extern "C" fn select_variant(experiment: *const c_char, context: *const c_char, variants_json: *const c_char) -> *mut c_char {
+    ffi_guard("select_variant", || {
+        if experiment.is_null() || variants_json.is_null() {
+            return ptr::null_mut();
+        }
+        let experiment_str = match unsafe { CStr::from_ptr(experiment) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        let context_str = if context.is_null() {
+            ""
+        } else {
+            match unsafe { CStr::from_ptr(context) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        let variants_str = match unsafe { CStr::from_ptr(variants_json) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        let response = match serde_json::from_str::<Vec<String>>(variants_str) {
+            Ok(variants) => match api::select_variant(experiment_str, context_str, &variants) {
+                Ok(variant) => serde_json::json!({ "variant": variant }),
+                Err(e) => serde_json::json!({ "error": "invalid_input", "message": e.to_string() }),
+            },
+            Err(e) => serde_json::json!({ "error": "invalid_input", "message": format!("Invalid variants: {}", e) }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+// Engagement with a variant select_variant returned, from 0 to 1; false for
+// a variant never selected in that context
+#[no_mangle]
+pub extern "C" fn record_variant_reward(experiment: *const c_char, context: *const c_char, variant: *const c_char, reward: f64) -> bool {
+    ffi_guard("record_variant_reward", || {
+        if experiment.is_null() || variant.is_null() {
+            return false;
+        }
+        let context_str = if context.is_null() {
+            Ok("")
+        } else {
+            unsafe { CStr::from_ptr(context) }.to_str()
+        };
+        match (unsafe { CStr::from_ptr(experiment) }.to_str(), context_str, unsafe { CStr::from_ptr(variant) }.to_str()) {
+            (Ok(experiment_str), Ok(context_str), Ok(variant_str)) => api::record_variant_reward(experiment_str, context_str, variant_str, reward),
+            _ => false,
+        }
+    })
+}
+
+// JSON array of {"variant", "pulls", "rewards", "mean"}, best mean first
+#[no_mangle]
+pub extern "C" fn get_variant_stats(experiment: *const c_char, context: *const c_char) -> *mut c_char {
+    ffi_guard_untimed("get_variant_stats", || {
+        if experiment.is_null() {
+            return ptr::null_mut();
+        }
+        let context_str = if context.is_null() {
+            Ok("")
+        } else {
+            unsafe { CStr::from_ptr(context) }.to_str()
+        };
+        match (unsafe { CStr::from_ptr(experiment) }.to_str(), context_str) {
+            (Ok(experiment_str), Ok(context_str)) => match serde_json::to_string(&api::variant_stats(experiment_str, context_str)) {
+                Ok(response) => to_c_string(response),
+                Err(_) => ptr::null_mut(),
+            },
+            _ => ptr::null_mut(),
+        }
+    })
+}
+
+// Writes or replaces the current tenant's experiments; both return how many
+// experiments the file holds, or -1
+#[no_mangle]
+pub extern "C" fn save_experiments(path: *const c_char) -> i64 {
+    state_file("bandit", "save_experiments", path, api::save_experiments)
+}
+
+#[no_mangle]
+pub extern "C" fn load_experiments(path: *const c_char) -> i64 {
+    state_file("bandit", "load_experiments", path, api::load_experiments)
+}
+
+// Switches repost detection to the fingerprint file at path (relative paths
+// resolve under the data directory), loading what it already holds
+#[no_mangle]
+pub extern "C" fn open_fingerprint_store(path: *const c_char) -> bool {
+    ffi_guard("open_fingerprint_store", || {
+        if path.is_null() {
+            return false;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        
+        match api::open_fingerprint_store(path_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "fingerprints", "open_fingerprint_store", line!());
+                false
+            }
+        }
+    })
+}
+
+// 8-bit grayscale pixels, row by row
+unsafe fn read_luma<'a>(luma: *const u8, width: usize, height: usize) -> Option<&'a [u8]> {
+    if luma.is_null() {
+        return None;
+    }
+    Some(std::slice::from_raw_parts(luma, width.checked_mul(height)?))
+}
+
+fn fingerprint_matches_response(fingerprint: api::Result<api::Fingerprint>, max_distance: u32) -> String {
+    match fingerprint {
+        Ok(fingerprint) => serde_json::json!(api::find_fingerprint_matches(&fingerprint, max_distance)).to_string(),
+        Err(e) => serde_json::json!({ "error": "invalid_content", "message": e.to_string() }).to_string(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn register_text_fingerprint(id: *const c_char, text: *const c_char) -> bool {
+    ffi_guard("register_text_fingerprint", || {
+        let (id_str, text_str) = unsafe {
+            match (CStr::from_ptr(id).to_str(), CStr::from_ptr(text).to_str()) {
+                (Ok(id), Ok(text)) => (id, text),
+                _ => return false,
+            }
+        };
+        
+        api::fingerprint_text(text_str).and_then(|fingerprint| api::register_fingerprint(id_str, fingerprint)).is_ok()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn register_image_fingerprint(id: *const c_char, luma: *const u8, width: usize, height: usize) -> bool {
+    ffi_guard("register_image_fingerprint", || {
+        let id_str = unsafe {
+            match CStr::from_ptr(id).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        let pixels = match unsafe { read_luma(luma, width, height) } {
+            Some(pixels) => pixels,
+            None => return false,
+        };
+        
+        api::fingerprint_image(width, height, pixels).and_then(|fingerprint| api::register_fingerprint(id_str, fingerprint)).is_ok()
+    })
+}
+
+// Registered posts whose text is within max_distance bits (3 is a good
+// start), as a JSON array of {"id", "kind", "distance", "similarity",
+// "registered_at"}
+#[no_mangle]
+pub extern "C" fn find_text_matches(text: *const c_char, max_distance: u32) -> *mut c_char {
+    ffi_guard("find_text_matches", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(fingerprint_matches_response(api::fingerprint_text(text_str), max_distance))
+    })
+}
+
+// Same for images; around 10 bits catches rescaled and recompressed copies
+#[no_mangle]
+pub extern "C" fn find_image_matches(luma: *const u8, width: usize, height: usize, max_distance: u32) -> *mut c_char {
+    ffi_guard("find_image_matches", || {
+        let pixels = match unsafe { read_luma(luma, width, height) } {
+            Some(pixels) => pixels,
+            None => return ptr::null_mut(),
+        };
+        
+        to_c_string(fingerprint_matches_response(api::fingerprint_image(width, height, pixels), max_distance))
+    })
+}
+
+// Renders a MarkdownV2 reply template. vars_json is an object of template
+// variables; lang selects the plural rules and may be NULL for English.
+// Returns {"text": "..."} or {"error": "invalid_template", "message": "..."}.
+#[no_mangle]
+pub extern "C" fn render_template(template: *const c_char, vars_json: *const c_char, lang: *const c_char) -> *mut c_char {
+    ffi_guard("render_template", || {
+        let template_str = unsafe {
+            match CStr::from_ptr(template).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let vars_str = unsafe {
+            match CStr::from_ptr(vars_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let lang_str = if lang.is_null() {
+            "en"
+        } else {
+            match unsafe { CStr::from_ptr(lang) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let vars: serde_json::Value = match serde_json::from_str(vars_str) {
+            Ok(vars) => vars,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_vars", "message": e.to_string() }).to_string()),
+        };
+        
+        let response = match api::render_template(template_str, &vars, lang_str) {
+            Ok(text) => serde_json::json!({ "text": text }),
+            Err(e) => serde_json::json!({ "error": "invalid_template", "message": e.to_string() }),
+        };
+        to_c_string(response.to_string())
+    })
+}
+
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn encrypt_message(message: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_guard("encrypt_message", || {
+        let message_str = unsafe {
+            match CStr::from_ptr(message).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let key_str = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let encrypted = match api::encrypt(message_str, key_str) {
+            Ok(result) => result,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        let c_string = match CString::new(encrypted) {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        c_string.into_raw()
+    })
+}
+
+// Encrypts into a versioned envelope. options_json may be NULL or e.g.
+// {"kdf": {"ARGON2ID": {"memory_kib": 19456, "iterations": 2, "parallelism": 1}},
+// "compress": false}. decrypt_message opens the result. NULL on failure.
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn encrypt_message_with(message: *const c_char, key: *const c_char, options_json: *const c_char) -> *mut c_char {
+    ffi_guard("encrypt_message_with", || {
+        let message_str = unsafe {
+            match CStr::from_ptr(message).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let key_str = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let options = if options_json.is_null() {
+            api::EnvelopeOptions::default()
+        } else {
+            let parsed = unsafe { CStr::from_ptr(options_json) }.to_str().ok().and_then(|s| serde_json::from_str(s).ok());
+            match parsed {
+                Some(options) => options,
+                None => return ptr::null_mut(),
+            }
+        };
+        
+        match api::encrypt_with(message_str, key_str, &options) {
+            Ok(encrypted) => to_c_string(encrypted),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn decrypt_message(encrypted_message: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_guard("decrypt_message", || {
+        let encrypted_str = unsafe {
+            match CStr::from_ptr(encrypted_message).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let key_str = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let decrypted = match api::decrypt(encrypted_str, key_str) {
+            Ok(result) => result,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        let c_string = match CString::new(decrypted) {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        
+        c_string.into_raw()
+    })
+}
+
+// messages_json is a JSON array of strings. Returns an array in the same
+// order with {"value": ...} or {"error": ...} per item, or
+// {"error": "invalid_input", "message": ...} when the array cannot be read.
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn encrypt_batch(messages_json: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_guard("encrypt_batch", || crypto_batch(messages_json, key, api::encrypt_batch))
+}
+
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn decrypt_batch(encrypted_json: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_guard("decrypt_batch", || crypto_batch(encrypted_json, key, api::decrypt_batch))
+}
+
+#[cfg(feature = "crypto")]
+fn crypto_batch(items_json: *const c_char, key: *const c_char, op: fn(&[String], &str) -> Vec<api::BatchResult>) -> *mut c_char {
+    let (items_str, key_str) = unsafe {
+        match (CStr::from_ptr(items_json).to_str(), CStr::from_ptr(key).to_str()) {
+            (Ok(items), Ok(key)) => (items, key),
+            _ => return ptr::null_mut(),
+        }
+    };
+    
+    let items: Vec<String> = match serde_json::from_str(items_str) {
+        Ok(items) => items,
+        Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_input", "message": e.to_string() }).to_string()),
+    };
+    match serde_json::to_string(&op(&items, key_str)) {
+        Ok(response) => to_c_string(response),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Starts an incremental hash ("sha256", the default for NULL, or "blake3");
+// returns a handle for hash_update / hash_finish, or 0 on failure
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn hash_begin(algorithm: *const c_char) -> u64 {
+    ffi_guard("hash_begin", || {
+        let algorithm = match hash_algorithm(algorithm) {
+            Some(algorithm) => algorithm,
+            None => return 0,
+        };
+        api::hash_begin(algorithm).unwrap_or(0)
+    })
+}
+
+// Same handles as hash_begin; BLAKE3 uses its keyed mode
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn hmac_begin(algorithm: *const c_char, key: *const u8, key_len: usize) -> u64 {
+    ffi_guard("hmac_begin", || {
+        let algorithm = match hash_algorithm(algorithm) {
+            Some(algorithm) => algorithm,
+            None => return 0,
+        };
+        if key.is_null() {
+            return 0;
+        }
+        let key_bytes = unsafe { std::slice::from_raw_parts(key, key_len) };
+        api::hmac_begin(algorithm, key_bytes).unwrap_or(0)
+    })
+}
+
+#[cfg(feature = "crypto")]
+fn hash_algorithm(name: *const c_char) -> Option<api::HashAlgorithm> {
+    if name.is_null() {
+        return Some(api::HashAlgorithm::Sha256);
+    }
+    let name_str = unsafe { CStr::from_ptr(name) }.to_str().ok()?;
+    api::HashAlgorithm::from_name(name_str)
+}
+
+// Feeds a chunk; the data is read in place, not copied
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn hash_update(handle: u64, data: *const u8, data_len: usize) -> i32 {
+    ffi_guard("hash_update", || {
+        if data.is_null() && data_len > 0 {
+            return FFI_ERR_NULL_POINTER;
+        }
+        let bytes = if data_len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(data, data_len) } };
+        match api::hash_update(handle, bytes) {
+            Ok(()) => FFI_OK,
+            Err(_) => FFI_ERR_OPERATION_FAILED,
+        }
+    })
+}
+
+// Lowercase hex digest, or null for an unknown handle; releases the handle
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn hash_finish(handle: u64) -> *mut c_char {
+    ffi_guard("hash_finish", || match api::hash_finish(handle) {
+        Ok(digest) => to_c_string(digest),
+        Err(_) => ptr::null_mut(),
+    })
+}
+
+// Releases a handle without a digest, e.g. when a download is cancelled
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn hash_abort(handle: u64) -> bool {
+    ffi_guard_untimed("hash_abort", || api::hash_abort(handle))
+}
+
+#[cfg(feature = "realtime")]
+#[no_mangle]
+pub extern "C" fn process_realtime(data: *const c_char) -> *mut c_char {
+    ffi_guard("process_realtime", || {
+        let data_str = unsafe {
+            match CStr::from_ptr(data).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(process_realtime_response(data_str))
+    })
+}
+
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_data(data: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_data", || {
+        let data_str = unsafe {
+            match CStr::from_ptr(data).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        to_c_string(analyze_data_response(data_str))
+    })
+}
+
+// poll_json is a Telegram Poll object or an array of them; returns the poll
+// report or {"error": "invalid_poll", "message": "..."}
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_poll(poll_json: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_poll", || {
+        let poll_str = unsafe {
+            match CStr::from_ptr(poll_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let response = match api::analyze_poll(poll_str) {
+            Ok(report) => serde_json::to_string(&report),
+            Err(e) => Ok(serde_json::json!({ "error": "invalid_poll", "message": e.to_string() }).to_string()),
+        };
+        match response {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Zero-copy variant of analyze_data for large datasets: the values are read
+// straight from the caller's buffer instead of being parsed from text
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_data_f64(values: *const f64, len: usize) -> *mut c_char {
+    ffi_guard("analyze_data_f64", || {
+        let values_slice: &[f64] = if len == 0 {
+            &[]
+        } else if values.is_null() {
+            return ptr::null_mut();
+        } else {
+            unsafe { std::slice::from_raw_parts(values, len) }
+        };
+        
+        to_c_string(analyze_numbers_response(values_slice))
+    })
+}
+
+// analyze_data_f64 for a metric's recent history, oldest first; an
+// anomalous latest value raises an anomaly alert once, not on every call
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_metric_f64(metric: *const c_char, values: *const f64, len: usize) -> *mut c_char {
+    ffi_guard("analyze_metric_f64", || {
+        if metric.is_null() || (values.is_null() && len > 0) {
+            return ptr::null_mut();
+        }
+        let metric_str = match unsafe { CStr::from_ptr(metric) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ptr::null_mut(),
+        };
+        let values_slice: &[f64] = if len == 0 { &[] } else { unsafe { std::slice::from_raw_parts(values, len) } };
+        
+        to_c_string(data_analysis_response(|| api::analyze_metric(metric_str, values_slice)))
+    })
+}
+
+// Tenant of every later call on this thread that takes no tenant arguments:
+// counters, bandit experiments, fingerprints, reply sets, chat stats and the
+// caches are scoped by it, and costs are charged to it. Quotas are enforced
+// by the _for_tenant calls only. chat_id only breaks down costs by chat.
+// NULL or "" for all three clears it; returns false for arguments that are
+// not valid UTF-8.
+#[no_mangle]
+pub extern "C" fn set_current_tenant(tenant_id: *const c_char, user_id: *const c_char, chat_id: *const c_char) -> bool {
+    ffi_guard_untimed("set_current_tenant", || {
+        let context = unsafe {
+            match (read_optional(tenant_id), read_optional(user_id), read_optional(chat_id)) {
+                (Some(tenant_id), Some(user_id), Some(chat_id)) => api::TenantContext::new(tenant_id, user_id).with_chat(chat_id),
+                _ => return false,
+            }
+        };
+        tenant::set_thread_default(context);
+        true
+    })
+}
+
+// Tenant-scoped variants of the analysis calls. A call over its tenant's
+// quota gets {"error": "rate_limited", ...} back instead of a result.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_for_tenant(text: *const c_char, tenant_id: *const c_char, user_id: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_text_for_tenant", || {
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        let context = match unsafe { read_tenant(tenant_id, user_id) } {
+            Some(context) => context,
+            None => return ptr::null_mut(),
+        };
+        
+        match api::for_tenant(&context, || analyze_text_response(text_str, &api::TextAnalyzer::default())) {
+            Ok(response) => to_c_string(response),
+            Err(e) => rate_limited_response(&e),
+        }
+    })
+}
+
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_data_for_tenant(data: *const c_char, tenant_id: *const c_char, user_id: *const c_char) -> *mut c_char {
+    ffi_guard("analyze_data_for_tenant", || {
+        let data_str = unsafe {
+            match CStr::from_ptr(data).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        let context = match unsafe { read_tenant(tenant_id, user_id) } {
+            Some(context) => context,
+            None => return ptr::null_mut(),
+        };
+        
+        match api::for_tenant(&context, || analyze_data_response(data_str)) {
+            Ok(response) => to_c_string(response),
+            Err(e) => rate_limited_response(&e),
+        }
+    })
+}
+
+#[cfg(feature = "realtime")]
+#[no_mangle]
+pub extern "C" fn process_realtime_for_tenant(data: *const c_char, tenant_id: *const c_char, user_id: *const c_char) -> *mut c_char {
+    ffi_guard("process_realtime_for_tenant", || {
+        let data_str = unsafe {
+            match CStr::from_ptr(data).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        let context = match unsafe { read_tenant(tenant_id, user_id) } {
+            Some(context) => context,
+            None => return ptr::null_mut(),
+        };
+        
+        match api::for_tenant(&context, || process_realtime_response(data_str)) {
+            Ok(response) => to_c_string(response),
+            Err(e) => rate_limited_response(&e),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn submit_analysis(kind: *const c_char, input: *const c_char) -> u64 {
+    ffi_guard("submit_analysis", || {
+        let kind_str = unsafe {
+            match CStr::from_ptr(kind).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        
+        let input_str = unsafe {
+            match CStr::from_ptr(input).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        
+        // Job ids start at 1, so 0 signals a rejected submission
+        api::submit_analysis(kind_str, input_str).unwrap_or(0)
+    })
+}
+
+// The job runs under the tenant's context; returns 0 when it is over quota
+#[no_mangle]
+pub extern "C" fn submit_analysis_for_tenant(kind: *const c_char, input: *const c_char, tenant_id: *const c_char, user_id: *const c_char) -> u64 {
+    ffi_guard("submit_analysis_for_tenant", || {
+        let kind_str = unsafe {
+            match CStr::from_ptr(kind).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        
+        let input_str = unsafe {
+            match CStr::from_ptr(input).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        let context = match unsafe { read_tenant(tenant_id, user_id) } {
+            Some(context) => context,
+            None => return 0,
+        };
+        
+        match api::for_tenant(&context, || api::submit_analysis(kind_str, input_str)) {
+            Ok(Ok(job_id)) => job_id,
+            _ => 0,
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn poll_result(job_id: u64) -> *mut c_char {
+    ffi_guard_untimed("poll_result", || {
+        match poll_result_response(job_id) {
+            Some(response) => to_c_string(response),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn cancel_job(job_id: u64) -> bool {
+    ffi_guard_untimed("cancel_job", || {
+        api::cancel_job(job_id)
+    })
+}
+
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+#[no_mangle]
+pub extern "C" fn enqueue_request(request: *const c_char) -> u64 {
+    ffi_guard("enqueue_request", || {
+        let request_str = unsafe {
+            match CStr::from_ptr(request).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        
+        // 0 is never a valid request id and signals a rejected request
+        api::enqueue_request(request_str).unwrap_or(0)
+    })
+}
+
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+#[no_mangle]
+pub extern "C" fn drain_completions() -> *mut c_char {
+    ffi_guard_untimed("drain_completions", || {
+        match serde_json::to_string(&api::drain_completions()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Gives the next call made on this thread timeout_ms from now, e.g. the time
+// left of the host handler's own timeout; 0 disarms. Analysis skips the
+// stages it has no time for and reports "timed_out": true, batches and jobs
+// stop the same way, and file writes and loads fail before starting.
+#[no_mangle]
+pub extern "C" fn set_call_deadline(timeout_ms: u64) {
+    ffi_guard_untimed("set_call_deadline", || {
+        deadline::set_next_call(timeout_ms);
+    })
+}
+
+// {"limit", "active", "queued", "peak_active", "peak_queued", "admitted",
+// "rejected"} for the limiter on analysis calls
+#[no_mangle]
+pub extern "C" fn get_concurrency_stats() -> *mut c_char {
+    ffi_guard_untimed("get_concurrency_stats", || {
+        match serde_json::to_string(&api::concurrency_stats()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Spelled out instead of Option<progress::ProgressCallback> so the generated
+// header declares a nullable function pointer
+#[no_mangle]
+pub extern "C" fn set_progress_callback(callback: Option<extern "C" fn(progress: f64, stage: *const c_char)>) {
+    ffi_guard_untimed("set_progress_callback", || {
+        progress::set_callback(callback);
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn clear_progress_callback() {
+    ffi_guard_untimed("clear_progress_callback", || {
+        progress::clear_callback();
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn validate_input_json(data: *const c_char, schema_name: *const c_char) -> *mut c_char {
+    ffi_guard("validate_input_json", || {
+        let data_str = unsafe {
+            match CStr::from_ptr(data).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        let schema_str = unsafe {
+            match CStr::from_ptr(schema_name).to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::validate(data_str, schema_str)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn register_validation_schema(schema_name: *const c_char, rules_json: *const c_char) -> bool {
+    ffi_guard("register_validation_schema", || {
+        let schema_str = unsafe {
+            match CStr::from_ptr(schema_name).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        let rules_str = unsafe {
+            match CStr::from_ptr(rules_json).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        match serde_json::from_str::<Vec<api::ValidationRule>>(rules_str) {
+            Ok(rules) => {
+                api::register_validation_schema(schema_str, rules);
+                true
+            },
+            Err(_) => false,
+        }
+    })
+}
+
+// Resizes the rayon pool used by parallel analysis stages; also stored as
+// performance.worker_threads. Returns false for 0 or a rejected config change.
+#[cfg(feature = "realtime")]
+#[no_mangle]
+pub extern "C" fn set_worker_threads(threads: usize) -> bool {
+    ffi_guard("set_worker_threads", || {
+        api::set_worker_threads(threads).is_ok()
+    })
+}
+
+// Writes a host-application log entry into the shared logging pipeline.
+// metadata_json may be null or a flat JSON object; non-string values are
+// stored in their JSON form.
+#[no_mangle]
+pub extern "C" fn log_message(level: *const c_char, module: *const c_char, message: *const c_char, metadata_json: *const c_char) -> bool {
+    ffi_guard("log_message", || {
+        let (level_str, module_str, message_str) = unsafe {
+            match (CStr::from_ptr(level).to_str(), CStr::from_ptr(module).to_str(), CStr::from_ptr(message).to_str()) {
+                (Ok(level), Ok(module), Ok(message)) => (level, module, message),
+                _ => return false,
+            }
+        };
+        
+        let level = match api::LogLevel::parse(level_str) {
+            Some(level) => level,
+            None => return false,
+        };
+        
+        let mut metadata = HashMap::new();
+        if !metadata_json.is_null() {
+            let metadata_str = match unsafe { CStr::from_ptr(metadata_json) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            };
+            match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(metadata_str) {
+                Ok(map) => {
+                    for (key, value) in map {
+                        let value = match value {
+                            serde_json::Value::String(s) => s,
+                            other => other.to_string(),
+                        };
+                        metadata.insert(key, value);
+                    }
+                },
+                Err(_) => return false,
+            }
+        }
+        
+        api::log(level, module_str, message_str, metadata);
+        true
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn set_alert_callback(callback: Option<extern "C" fn(alert_json: *const c_char)>) {
+    ffi_guard("set_alert_callback", || {
+        alerts::set_callback(callback);
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn get_recent_alerts(limit: usize) -> *mut c_char {
+    ffi_guard_untimed("get_recent_alerts", || {
+        match serde_json::to_string(&api::recent_alerts(limit)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Performance data as JSON arrays for dashboards polling the host process
+#[no_mangle]
+pub extern "C" fn get_performance_profiles_ffi() -> *mut c_char {
+    ffi_guard_untimed("get_performance_profiles_ffi", || {
+        match serde_json::to_string(&api::performance_profiles()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn get_optimization_suggestions_ffi() -> *mut c_char {
+    ffi_guard_untimed("get_optimization_suggestions_ffi", || {
+        match serde_json::to_string(&api::optimization_suggestions()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Request, quota and cache counters for one tenant, or for every tenant keyed
+// by id when tenant_id is NULL
+#[no_mangle]
+pub extern "C" fn get_tenant_metrics(tenant_id: *const c_char) -> *mut c_char {
+    ffi_guard_untimed("get_tenant_metrics", || {
+        let response = if tenant_id.is_null() {
+            serde_json::to_string(&api::all_tenant_metrics())
+        } else {
+            let tenant_str = match unsafe { CStr::from_ptr(tenant_id) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            };
+            serde_json::to_string(&api::tenant_metrics(tenant_str).unwrap_or_default())
+        };
+        
+        match response {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// CostReport for one tenant, or for every tenant keyed by id when tenant_id
+// is NULL
+#[no_mangle]
+pub extern "C" fn get_cost_report(tenant_id: *const c_char) -> *mut c_char {
+    ffi_guard_untimed("get_cost_report", || {
+        let response = if tenant_id.is_null() {
+            serde_json::to_string(&api::all_cost_reports())
+        } else {
+            let tenant_str = match unsafe { CStr::from_ptr(tenant_id) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ptr::null_mut(),
+            };
+            serde_json::to_string(&api::cost_report(tenant_str).unwrap_or_default())
+        };
+        
+        match response {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Every tenant's costs as a JSON array of rows {"tenant_id", "dimension",
+// "key", "operations", "input_bytes", "cost"}, ready for export_records
+#[no_mangle]
+pub extern "C" fn get_cost_lines() -> *mut c_char {
+    ffi_guard_untimed("get_cost_lines", || match serde_json::to_string(&api::cost_lines()) {
+        Ok(response) => to_c_string(response),
+        Err(_) => ptr::null_mut(),
+    })
+}
+
+// Starts one tenant's costs over, or every tenant's when tenant_id is NULL
+#[no_mangle]
+pub extern "C" fn reset_costs(tenant_id: *const c_char) -> bool {
+    ffi_guard("reset_costs", || {
+        if tenant_id.is_null() {
+            return api::reset_costs(None);
+        }
+        match unsafe { CStr::from_ptr(tenant_id) }.to_str() {
+            Ok(tenant_str) => api::reset_costs(Some(tenant_str)),
+            Err(_) => false,
+        }
+    })
+}
+
+// RateLimitDecision for the tenant's next call without counting it, or
+// null as JSON when tenant_id is NULL or empty
+#[no_mangle]
+pub extern "C" fn dry_run_tenant_quota(tenant_id: *const c_char, user_id: *const c_char) -> *mut c_char {
+    ffi_guard("dry_run_tenant_quota", || {
+        let context = match unsafe { read_tenant(tenant_id, user_id) } {
+            Some(context) => context,
+            None => return ptr::null_mut(),
+        };
+        
+        match serde_json::to_string(&api::dry_run_tenant_quota(&context)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Decisions for a JSON trace [{"at_ms", "identifier", "dry_run"}] replayed
+// against config_json on a simulated clock; {"error": ...} for bad input
+#[no_mangle]
+pub extern "C" fn simulate_rate_limit(config_json: *const c_char, trace_json: *const c_char) -> *mut c_char {
+    ffi_guard("simulate_rate_limit", || {
+        let (config_str, trace_str) = unsafe {
+            match (CStr::from_ptr(config_json).to_str(), CStr::from_ptr(trace_json).to_str()) {
+                (Ok(config), Ok(trace)) => (config, trace),
+                _ => return ptr::null_mut(),
+            }
+        };
+        
+        let config: api::SecurityConfig = match serde_json::from_str(if config_str.trim().is_empty() { "{}" } else { config_str }) {
+            Ok(config) => config,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_config", "message": e.to_string() }).to_string()),
+        };
+        let trace: Vec<api::SimulatedRequest> = match serde_json::from_str(trace_str) {
+            Ok(trace) => trace,
+            Err(e) => return to_c_string(serde_json::json!({ "error": "invalid_trace", "message": e.to_string() }).to_string()),
+        };
+        
+        let response = match api::simulate_rate_limit(config, &trace) {
+            Ok(decisions) => serde_json::to_string(&decisions),
+            Err(e) => Ok(serde_json::json!({ "error": "invalid_trace", "message": e.to_string() }).to_string()),
+        };
+        match response {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Fault injection for resilience tests: {"cache_error", "slow_response",
+// "slow_response_ms", "crypto_failure", "dropped_realtime", "seed"}; NULL
+// turns it off
+#[cfg(feature = "chaos")]
+#[no_mangle]
+pub extern "C" fn set_fault_injection(config_json: *const c_char) -> bool {
+    ffi_guard("set_fault_injection", || {
+        if config_json.is_null() {
+            api::disable_fault_injection();
+            return true;
+        }
+        let config = match unsafe { CStr::from_ptr(config_json) }.to_str().ok().and_then(|s| serde_json::from_str(s).ok()) {
+            Some(config) => config,
+            None => return false,
+        };
+        
+        match api::set_fault_injection(config) {
+            Ok(()) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "chaos", "set_fault_injection", line!());
+                false
+            }
+        }
+    })
+}
+
+// Faults injected since the last set_fault_injection
+#[cfg(feature = "chaos")]
+#[no_mangle]
+pub extern "C" fn get_fault_injection_stats() -> *mut c_char {
+    ffi_guard_untimed("get_fault_injection_stats", || {
+        match serde_json::to_string(&api::fault_injection_stats()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Delivery counters of the webhook dispatcher and the current queue length
+#[cfg(feature = "webhooks")]
+#[no_mangle]
+pub extern "C" fn get_webhook_stats() -> *mut c_char {
+    ffi_guard_untimed("get_webhook_stats", || {
+        match serde_json::to_string(&api::webhook_stats()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn pin_config_public_key(public_key: *const c_char) -> bool {
+    ffi_guard("pin_config_public_key", || {
+        let key_str = unsafe {
+            match CStr::from_ptr(public_key).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        api::pin_config_public_key(key_str).is_ok()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn apply_config_bundle(bundle: *const c_char) -> bool {
+    ffi_guard("apply_config_bundle", || {
+        let bundle_str = unsafe {
+            match CStr::from_ptr(bundle).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        api::apply_config_bundle(bundle_str).is_ok()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn patch_config(patch: *const c_char) -> bool {
+    ffi_guard("patch_config", || {
+        let patch_str = unsafe {
+            match CStr::from_ptr(patch).to_str() {
+                Ok(s) => s,
+                Err(_) => return false,
+            }
+        };
+        
+        api::patch_config(patch_str).is_ok()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dump_effective_config(redact_secrets: bool) -> *mut c_char {
+    ffi_guard("dump_effective_config", || {
+        to_c_string(api::dump_effective_config(redact_secrets).to_string())
+    })
+}
+
+// Length-prefixed variants of the exports above. Inputs are (pointer, length)
+// pairs and outputs are written to caller-provided out parameters, so payloads
+// containing NUL bytes cross the boundary intact. Output buffers must be
+// released with free_buffer.
+#[cfg(feature = "nlp")]
+#[no_mangle]
+pub extern "C" fn analyze_text_buf(text: *const u8, text_len: usize, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("analyze_text_buf", || {
+        unsafe {
+            let text_str = match read_buf(text, text_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            write_buf(analyze_text_response(text_str, &api::TextAnalyzer::default()).into_bytes(), out_ptr, out_len)
+        }
+    })
+}
+
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn encrypt_message_buf(message: *const u8, message_len: usize, key: *const u8, key_len: usize, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("encrypt_message_buf", || {
+        unsafe {
+            let message_bytes = match read_bytes(message, message_len) {
+                Ok(bytes) => bytes,
+                Err(code) => return code,
+            };
+            let key_bytes = match read_bytes(key, key_len) {
+                Ok(bytes) => bytes,
+                Err(code) => return code,
+            };
+            match api::encrypt_bytes(message_bytes, key_bytes) {
+                Ok(encrypted) => write_buf(encrypted.into_bytes(), out_ptr, out_len),
+                Err(_) => FFI_ERR_OPERATION_FAILED,
+            }
+        }
+    })
+}
+
+#[cfg(feature = "crypto")]
+#[no_mangle]
+pub extern "C" fn decrypt_message_buf(encrypted_message: *const u8, encrypted_len: usize, key: *const u8, key_len: usize, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("decrypt_message_buf", || {
+        unsafe {
+            let encrypted_bytes = match read_bytes(encrypted_message, encrypted_len) {
+                Ok(bytes) => bytes,
+                Err(code) => return code,
+            };
+            let key_bytes = match read_bytes(key, key_len) {
+                Ok(bytes) => bytes,
+                Err(code) => return code,
+            };
+            match api::decrypt_bytes(encrypted_bytes, key_bytes) {
+                Ok(decrypted) => write_buf(decrypted, out_ptr, out_len),
+                Err(_) => FFI_ERR_OPERATION_FAILED,
+            }
+        }
+    })
+}
+
+#[cfg(feature = "realtime")]
+#[no_mangle]
+pub extern "C" fn process_realtime_buf(data: *const u8, data_len: usize, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("process_realtime_buf", || {
+        unsafe {
+            let data_str = match read_buf(data, data_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            write_buf(process_realtime_response(data_str).into_bytes(), out_ptr, out_len)
+        }
+    })
+}
+
+#[cfg(feature = "stats")]
+#[no_mangle]
+pub extern "C" fn analyze_data_buf(data: *const u8, data_len: usize, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("analyze_data_buf", || {
+        unsafe {
+            let data_str = match read_buf(data, data_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            write_buf(analyze_data_response(data_str).into_bytes(), out_ptr, out_len)
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn submit_analysis_buf(kind: *const u8, kind_len: usize, input: *const u8, input_len: usize, out_job_id: *mut u64) -> i32 {
+    ffi_guard("submit_analysis_buf", || {
+        unsafe {
+            let kind_str = match read_buf(kind, kind_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            let input_str = match read_buf(input, input_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            if out_job_id.is_null() {
+                return FFI_ERR_NULL_POINTER;
+            }
+            match api::submit_analysis(kind_str, input_str) {
+                Ok(job_id) => {
+                    *out_job_id = job_id;
+                    FFI_OK
+                },
+                Err(_) => FFI_ERR_OPERATION_FAILED,
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn poll_result_buf(job_id: u64, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard_untimed("poll_result_buf", || {
+        match poll_result_response(job_id) {
+            Some(response) => unsafe { write_buf(response.into_bytes(), out_ptr, out_len) },
+            None => FFI_ERR_OPERATION_FAILED,
+        }
+    })
+}
+
+// Spreadsheet of a JSON array of records (analysis results, reports) in
+// format "csv" or "xlsx"; name titles the worksheets
+#[no_mangle]
+pub extern "C" fn export_records_buf(name: *const c_char, records_json: *const u8, records_len: usize, format: *const c_char, out_ptr: *mut *mut u8, out_len: *mut usize) -> i32 {
+    ffi_guard("export_records_buf", || {
+        unsafe {
+            if name.is_null() || format.is_null() {
+                return FFI_ERR_NULL_POINTER;
+            }
+            let (name_str, format_str) = match (CStr::from_ptr(name).to_str(), CStr::from_ptr(format).to_str()) {
+                (Ok(name), Ok(format)) => (name, format),
+                _ => return FFI_ERR_INVALID_UTF8,
+            };
+            let records_str = match read_buf(records_json, records_len) {
+                Ok(s) => s,
+                Err(code) => return code,
+            };
+            let format = match api::ExportFormat::parse(format_str) {
+                Some(format) => format,
+                None => return FFI_ERR_OPERATION_FAILED,
+            };
+            match api::export_json(name_str, records_str, format) {
+                Ok(bytes) => write_buf(bytes, out_ptr, out_len),
+                Err(e) => {
+                    logging::error(&e.to_string(), "export", "export_records_buf", line!());
+                    FFI_ERR_OPERATION_FAILED
+                }
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_buffer(ptr: *mut u8, len: usize) {
+    ffi_guard_untimed("free_buffer", || {
+        if !ptr.is_null() {
+            unsafe {
+                let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len));
+            }
+        }
+    })
+}
+
+// Loads config (a full config document or signed bundle; null falls back to
+// CONFIG_PATH) and starts background maintenance. Returns false when the
+// config is rejected or the library is already initialized.
+#[no_mangle]
+pub extern "C" fn init_library(config_json: *const c_char) -> bool {
+    ffi_guard("init_library", || {
+        let config_str = if config_json.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(config_json) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return false,
+            }
+        };
+        
+        lifecycle::init_library(config_str).is_ok()
+    })
+}
+
+// JSON array of {subsystem, duration_ms, error} for each step of the last
+// init_library call, in the order they ran
+#[no_mangle]
+pub extern "C" fn get_init_report() -> *mut c_char {
+    ffi_guard_untimed("get_init_report", || {
+        match serde_json::to_string(&lifecycle::init_report()) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// Runs the warm-up stages ({"cache_snapshot", "snapshot_key"}, or NULL for
+// none) and returns their [{subsystem, duration_ms, error}] report
+#[no_mangle]
+pub extern "C" fn warm_up(options_json: *const c_char) -> *mut c_char {
+    ffi_guard("warm_up", || {
+        let options = if options_json.is_null() {
+            api::WarmUpOptions::default()
+        } else {
+            match unsafe { CStr::from_ptr(options_json) }.to_str().ok().and_then(|s| serde_json::from_str(s).ok()) {
+                Some(options) => options,
+                None => return ptr::null_mut(),
+            }
+        };
+        
+        match serde_json::to_string(&api::warm_up(&options)) {
+            Ok(response) => to_c_string(response),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+// BLAKE3 of data as 64 lowercase hex chars, the hash behind the cache keys;
+// data may be NULL when data_len is 0
+#[no_mangle]
+pub extern "C" fn fast_hash(data: *const u8, data_len: usize) -> *mut c_char {
+    ffi_guard("fast_hash", || {
+        if data.is_null() && data_len > 0 {
+            return ptr::null_mut();
+        }
+        let bytes = if data_len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(data, data_len) } };
+        to_c_string(api::fast_hash(bytes))
+    })
+}
+
+// Snapshot of the caches for warm_up; key may be NULL to skip sealing
+#[no_mangle]
+pub extern "C" fn save_cache_snapshot(path: *const c_char, key: *const c_char) -> bool {
+    ffi_guard("save_cache_snapshot", || {
+        if path.is_null() {
+            return false;
+        }
+        let path_str = match unsafe { CStr::from_ptr(path) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        let key_str = if key.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(key) }.to_str() {
+                Ok(s) => Some(s),
+                Err(_) => return false,
+            }
+        };
+        
+        match api::save_cache_snapshot(path_str, key_str) {
+            Ok(_) => true,
+            Err(e) => {
+                logging::error(&e.to_string(), "cache", "save_cache_snapshot", line!());
+                false
+            }
+        }
+    })
+}
+
+// Stops background threads and flushes log sinks; call before unloading
+#[no_mangle]
+pub extern "C" fn shutdown_library() {
+    ffi_guard("shutdown_library", || {
+        lifecycle::shutdown_library();
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn library_abi_version() -> u32 {
+    ffi_guard_untimed("library_abi_version", || {
+        AIOGRAM_ABI_VERSION
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn free_string(ptr: *mut c_char) {
+    ffi_guard_untimed("free_string", || {
+        if !ptr.is_null() {
+            unsafe {
+                let _ = CString::from_raw(ptr);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_ffi_guard_catches_panics() {
+        assert_eq!(ffi_guard("test_export", || -> i32 { panic!("boom") }), FFI_ERR_PANIC);
+        assert!(!ffi_guard("test_export", || -> bool { panic!("boom") }));
+        
+        let response = ffi_guard("test_export", || -> *mut c_char { panic!("boom") });
+        let json: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        assert_eq!(json["error"], "panic");
+        assert_eq!(json["message"], "boom");
+        free_string(response);
+        
+        assert_eq!(ffi_guard("test_export", || 7), 7);
+    }
+    
+    #[test]
+    fn test_bookkeeping_exports_keep_the_armed_deadline() {
+        let previous = to_c_string("previous result".to_string());
+        set_call_deadline(60_000);
+        free_string(previous);
+        let _ = library_abi_version();
+        
+        let remaining = ffi_guard("test_export", || deadline::current().map_or(0, |d| d.remaining_millis()));
+        assert!(remaining > 0 && remaining <= 60_000);
+        // Used up by that call
+        assert!(!ffi_guard("test_export", || deadline::current().is_some()));
+        
+        set_call_deadline(1);
+        free_string(ptr::null_mut());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(ffi_guard("test_export", deadline::expired));
+    }
+    
+    #[test]
+    fn test_current_tenant_scopes_exports() {
+        let name = CString::new("ffi_tenant_test_counter").unwrap();
+        let tenant = CString::new("ffi_tenant_test_bot").unwrap();
+        assert!(set_current_tenant(tenant.as_ptr(), ptr::null(), ptr::null()));
+        assert_eq!(incr_counter(name.as_ptr(), 3), 3);
+        assert_eq!(get_counter(name.as_ptr()), 3);
+        
+        assert!(set_current_tenant(ptr::null(), ptr::null(), ptr::null()));
+        assert_eq!(get_counter(name.as_ptr()), 0);
+        // Other threads never saw the tenant
+        assert!(set_current_tenant(tenant.as_ptr(), ptr::null(), ptr::null()));
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(get_counter(name.as_ptr()), 0));
+        });
+        assert_eq!(get_counter(name.as_ptr()), 3);
+        assert!(set_current_tenant(ptr::null(), ptr::null(), ptr::null()));
+    }
+    
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_current_tenant_chat_reaches_cost_report() {
+        let tenant = CString::new("ffi_chat_cost_bot").unwrap();
+        let chat = CString::new("-100777").unwrap();
+        assert!(set_current_tenant(tenant.as_ptr(), ptr::null(), chat.as_ptr()));
+        let values = [1.0, 2.0, 3.0, 4.0];
+        free_string(analyze_data_f64(values.as_ptr(), values.len()));
+        let data = CString::new("[5, 6, 7]").unwrap();
+        free_string(analyze_data_for_tenant(data.as_ptr(), tenant.as_ptr(), ptr::null()));
+        assert!(set_current_tenant(ptr::null(), ptr::null(), ptr::null()));
+        
+        let response = get_cost_report(tenant.as_ptr());
+        let report: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        free_string(response);
+        assert_eq!(report["chats"]["-100777"]["operations"], 2);
+    }
+    
+    #[test]
+    #[cfg(feature = "nlp")]
+    fn test_analyze_text_into() {
+        let text = CString::new("Reusable buffers avoid allocator churn.").unwrap();
+        let needed = analyze_text_into(text.as_ptr(), ptr::null_mut(), 0);
+        assert!(needed > 1);
+        
+        // Too small: the buffer is left untouched
+        let mut small = [b'x' as c_char; 4];
+        assert_eq!(analyze_text_into(text.as_ptr(), small.as_mut_ptr(), small.len()), needed);
+        assert!(small.iter().all(|&c| c == b'x' as c_char));
+        
+        // Exactly the size asked for fits, since the retry gets the same response
+        let mut buf = vec![0 as c_char; needed];
+        let written = analyze_text_into(text.as_ptr(), buf.as_mut_ptr(), buf.len());
+        assert_eq!(written, needed);
+        let response = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap();
+        assert_eq!(response.len() + 1, written);
+        assert!(serde_json::from_str::<serde_json::Value>(response).unwrap()["word_count"].as_u64().unwrap() > 0);
+        
+        assert_eq!(analyze_text_into(ptr::null(), buf.as_mut_ptr(), buf.len()), 0);
+    }
+    
+    #[test]
+    fn test_performance_exports() {
+        performance::record_operation_performance("ffi_export_test", 1500, 1.0, 1.0, false, true);
+        
+        let response = get_performance_profiles_ffi();
+        let profiles: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        free_string(response);
+        assert!(profiles.as_array().unwrap().iter().any(|p| p["operation_name"] == "ffi_export_test"));
+        
+        let response = get_optimization_suggestions_ffi();
+        let suggestions: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        free_string(response);
+        assert!(suggestions.as_array().unwrap().iter().any(|s| s["category"] == "Performance"));
+    }
+    
+    #[test]
+    fn test_render_template_export() {
+        let template = CString::new("{{plural n one=\"# file\" other=\"# files\"}} from {{name}}").unwrap();
+        let vars = CString::new(r#"{"n": 3, "name": "a.b"}"#).unwrap();
+        let response = render_template(template.as_ptr(), vars.as_ptr(), ptr::null());
+        let json: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        free_string(response);
+        assert_eq!(json["text"], "3 files from a\\.b");
+        
+        let broken = CString::new("{{#if n}}").unwrap();
+        let response = render_template(broken.as_ptr(), vars.as_ptr(), ptr::null());
+        let json: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(response) }.to_str().unwrap()).unwrap();
+        free_string(response);
+        assert_eq!(json["error"], "invalid_template");
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+use crate::config::{self, AppConfig};
+use crate::logging::{self, LogLevel};
+use crate::{alerts, cache, jobs, links, performance, pii, security, validation};
+#[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+use crate::queue;
+#[cfg(feature = "nlp")]
+use crate::{analysis, conversation, entity_patterns, spam, structure, summary, tags};
+#[cfg(feature = "realtime")]
+use crate::{pool, realtime};
+#[cfg(feature = "webhooks")]
+use crate::webhooks;
+
+// Background maintenance owned by an init_library / shutdown_library pair
+struct Lifecycle {
+    // Dropping the sender wakes every worker and makes it exit
+    stop: Sender<()>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitStep {
+    pub subsystem: String,
+    pub duration_ms: f64,
+    pub error: Option<String>,
+}
+
+type Initializer = fn() -> Result<(), String>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmUpOptions {
+    // Written by save_cache_snapshot; relative paths resolve under the data
+    // directory
+    pub cache_snapshot: Option<String>,
+    // Key the snapshot was sealed with, if any
+    pub snapshot_key: Option<String>,
+}
+
+// Analyzed once by warm_up, so whatlang's tables, the lexicons and the stop
+// word sets are loaded before the first real message
+#[cfg(feature = "nlp")]
+const WARM_UP_TEXTS: &[&str] = &[
+    "Thanks everyone! The release is ready and the new dashboard looks great. Meeting moved to 5pm on March 3rd.",
+    "سلام به همه! نسخه جدید آماده است و داشبورد جدید خیلی خوب شده. جلسه ساعت ۱۷ برگزار می‌شود.",
+];
+
+// Singletons forced by init_library, in dependency order: logging first so
+// later steps can log, then everything that compiles regexes, allocates or
+// spawns threads up front instead of on the first request. Subsystems of
+// disabled features are left out.
+const INIT_ORDER: &[(&str, Initializer)] = &[
+    ("logging", logging::initialize),
+    ("security", security::initialize),
+    ("validation", validation::initialize),
+    #[cfg(feature = "nlp")]
+    ("analysis", analysis::initialize),
+    ("cache", cache::initialize),
+    ("cache_snapshot", restore_cache_snapshot),
+    ("performance", performance::initialize),
+    #[cfg(feature = "realtime")]
+    ("thread_pool", pool::initialize),
+    ("jobs", jobs::initialize),
+    #[cfg(all(feature = "queue", not(target_arch = "wasm32")))]
+    ("queue", queue::initialize),
+    ("alerts", alerts::initialize),
+    #[cfg(feature = "webhooks")]
+    ("webhooks", webhooks::initialize),
+];
+
+lazy_static! {
+    static ref LIFECYCLE: Mutex<Option<Lifecycle>> = Mutex::new(None);
+    static ref INIT_REPORT: Mutex<Vec<InitStep>> = Mutex::new(Vec::new());
+}
+
+pub fn init_library(config_json: Option<&str>) -> Result<(), String> {
+    let mut lifecycle = LIFECYCLE.lock().map_err(|_| "Lifecycle lock poisoned".to_string())?;
+    if lifecycle.is_some() {
+        return Err("Library is already initialized".to_string());
+    }
+    
+    let mut report = Vec::new();
+    let outcome = run_step(&mut report, "config", || {
+        match config_json.map(str::trim).filter(|json| !json.is_empty()) {
+            Some(json) => config::initialize_config_from_str(json),
+            None => config::initialize_config(None),
+        }.map_err(|e| e.to_string())
+    }).and_then(|_| {
+        INIT_ORDER.iter().try_for_each(|(subsystem, initialize)| run_step(&mut report, subsystem, *initialize))
+    });
+    *INIT_REPORT.lock().unwrap() = report;
+    outcome?;
+    
+    let performance = AppConfig::get_performance_config();
+    let (stop, stopped) = bounded::<()>(0);
+    let mut workers = Vec::new();
+    
+    for (name, interval_seconds, task) in [
+        ("aiogram-cache-cleanup", performance.cache_cleanup_interval_seconds, cleanup_caches as fn()),
+        ("aiogram-metrics-flush", performance.metrics_flush_interval_seconds, flush_metrics as fn()),
+    ] {
+        let stopped = stopped.clone();
+        let interval = Duration::from_secs(interval_seconds.max(1));
+        let worker = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || run_periodic(stopped, interval, task))
+            .map_err(|e| format!("Failed to start {}: {}", name, e))?;
+        workers.push(worker);
+    }
+    
+    *lifecycle = Some(Lifecycle { stop, workers });
+    logging::info("Library initialized", "lifecycle", "init_library", line!());
+    Ok(())
+}
+
+// Safe to call without a prior init_library; sinks are still flushed so
+// nothing buffered is lost when the host unloads the library
+pub fn shutdown_library() {
+    let lifecycle = LIFECYCLE.lock().ok().and_then(|mut lifecycle| lifecycle.take());
+    
+    if let Some(Lifecycle { stop, workers }) = lifecycle {
+        drop(stop);
+        for worker in workers {
+            let _ = worker.join();
+        }
+        flush_metrics();
+        #[cfg(feature = "webhooks")]
+        webhooks::shutdown();
+        logging::info("Library shut down", "lifecycle", "shutdown_library", line!());
+    }
+    
+    save_cache_snapshot();
+    logging::shutdown();
+}
+
+// Pays the cold-start costs of the first request up front: compiles every
+// static regex, analyzes sample text, builds the worker pool and the
+// realtime tables, and optionally refills the caches from a snapshot. Every
+// step runs even when an earlier one failed; the report says which did.
+pub fn warm_up(options: &WarmUpOptions) -> Vec<InitStep> {
+    let mut report = Vec::new();
+    let _ = run_step(&mut report, "regexes", || {
+        security::initialize()?;
+        validation::initialize()?;
+        pii::initialize()?;
+        links::initialize()?;
+        #[cfg(feature = "nlp")]
+        {
+            analysis::initialize()?;
+            entity_patterns::initialize()?;
+            summary::initialize()?;
+            conversation::initialize()?;
+            spam::initialize()?;
+            tags::initialize()?;
+            structure::initialize()?;
+        }
+        Ok(())
+    });
+    #[cfg(feature = "nlp")]
+    let _ = run_step(&mut report, "text_analysis", || {
+        let options = analysis::AnalysisOptions { skip_plugins: true, spam_score: true, ..Default::default() };
+        for text in WARM_UP_TEXTS {
+            analysis::analyze_text_with_options(text, &options);
+        }
+        Ok(())
+    });
+    #[cfg(feature = "realtime")]
+    let _ = run_step(&mut report, "realtime", || {
+        pool::initialize()?;
+        realtime::initialize()
+    });
+    let _ = run_step(&mut report, "cache", cache::initialize);
+    if let Some(path) = &options.cache_snapshot {
+        let _ = run_step(&mut report, "cache_snapshot", || {
+            let restored = cache::load_snapshot(path, options.snapshot_key.as_deref())?;
+            logging::info(&format!("Restored {} cache entries from {}", restored, path), "lifecycle", "warm_up", line!());
+            Ok(())
+        });
+    }
+    report
+}
+
+// Timing and outcome of each step of the last init_library call
+pub fn init_report() -> Vec<InitStep> {
+    INIT_REPORT.lock().map(|report| report.clone()).unwrap_or_default()
+}
+
+// A panicking initializer is reported as a failed step instead of surfacing
+// later as a panic in the middle of a request
+fn run_step<F: FnOnce() -> Result<(), String>>(report: &mut Vec<InitStep>, subsystem: &str, step: F) -> Result<(), String> {
+    let started = Instant::now();
+    let outcome = panic::catch_unwind(AssertUnwindSafe(step))
+        .unwrap_or_else(|payload| Err(panic_message(payload.as_ref())));
+    
+    report.push(InitStep {
+        subsystem: subsystem.to_string(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: outcome.as_ref().err().cloned(),
+    });
+    outcome.map_err(|e| format!("Failed to initialize {}: {}", subsystem, e))
+}
+
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn run_periodic(stopped: Receiver<()>, interval: Duration, task: fn()) {
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+        task();
+    }
+}
+
+// A missing or unreadable snapshot only costs a cold cache, so it does not
+// fail init
+fn restore_cache_snapshot() -> Result<(), String> {
+    let config = AppConfig::get().cache;
+    let Some(path) = config.snapshot_path else { return Ok(()) };
+    let key = config.snapshot_key.as_ref().map(|key| key.expose());
+    match cache::load_snapshot(&path, key) {
+        Ok(restored) => logging::info(&format!("Restored {} cache entries from {}", restored, path), "lifecycle", "init_library", line!()),
+        Err(e) => logging::warn(&format!("Cache snapshot not restored: {}", e), "lifecycle", "init_library", line!()),
+    }
+    Ok(())
+}
+
+fn save_cache_snapshot() {
+    let config = AppConfig::get().cache;
+    let Some(path) = config.snapshot_path else { return };
+    let key = config.snapshot_key.as_ref().map(|key| key.expose());
+    match cache::save_snapshot(&path, key) {
+        Ok(saved) => logging::info(&format!("Saved {} cache entries to {}", saved, path), "lifecycle", "shutdown_library", line!()),
+        Err(e) => logging::error(&format!("Cache snapshot not saved: {}", e), "lifecycle", "shutdown_library", line!()),
+    }
+}
+
+fn cleanup_caches() {
+    let removed: usize = crate::cache::cleanup_all_caches().values().sum();
+    if removed > 0 {
+        logging::debug(&format!("Removed {} expired cache entries", removed), "lifecycle", "cleanup_caches", line!());
+    }
+}
+
+// Writes the performance rollup into the log pipeline so every sink gets it
+fn flush_metrics() {
+    let summary = crate::performance::get_performance_summary();
+    let total_operations = summary.get("total_operations").copied().unwrap_or(0.0);
+    if total_operations == 0.0 {
+        return;
+    }
+    
+    let metadata: HashMap<String, String> = summary.into_iter()
+        .map(|(name, value)| (name, value.to_string()))
+        .collect();
+    logging::log_with_metadata(LogLevel::INFO, "Performance metrics", "lifecycle", "flush_metrics", line!(), metadata);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn is_initialized() -> bool {
+        LIFECYCLE.lock().unwrap().is_some()
+    }
+    
+    #[test]
+    fn test_init_and_shutdown() {
+        assert!(init_library(None).is_ok());
+        assert!(is_initialized());
+        
+        let report = init_report();
+        assert_eq!(report[0].subsystem, "config");
+        assert_eq!(report.len(), INIT_ORDER.len() + 1);
+        assert!(report.iter().all(|step| step.error.is_none()));
+        
+        assert!(init_library(None).is_err());
+        
+        shutdown_library();
+        assert!(!is_initialized());
+        
+        // A shut down library can be initialized again
+        assert!(init_library(None).is_ok());
+        shutdown_library();
+        assert!(!is_initialized());
+    }
+    
+    #[test]
+    fn test_warm_up_report() {
+        let report = warm_up(&WarmUpOptions::default());
+        assert!(report.iter().any(|step| step.subsystem == "regexes"));
+        assert!(report.iter().all(|step| step.error.is_none()));
+        
+        let missing = warm_up(&WarmUpOptions { cache_snapshot: Some("/nonexistent/aiogram/cache.json".to_string()), snapshot_key: None });
+        assert!(missing.last().unwrap().error.as_ref().unwrap().contains("Cannot read cache snapshot"));
+    }
+}
@@ -0,0 +1,312 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::security::{self, SecuritySeverity};
+
+// Risk of links in messages, QR codes and deep links, from the link alone:
+// nothing is fetched or resolved. Each heuristic that fires adds its weight
+// and a reason; the sum, capped at 1, decides the verdict. Links that only
+// point at another one (redirect parameters, a URL hidden in a /start
+// payload) also carry the risk of where they lead. Unsafe links found by scan
+// are recorded as UNSAFE_LINK security events, so dangerous ones raise
+// alerts like other threats.
+
+const SUSPICIOUS_RISK: f64 = 0.3;
+const DANGEROUS_RISK: f64 = 0.7;
+// A redirect inside a redirect is not followed further
+const MAX_NESTING: usize = 2;
+
+const SHORTENERS: &[&str] = &[
+    "bit.ly", "tinyurl.com", "t.co", "goo.gl", "is.gd", "cutt.ly", "ow.ly", "rebrand.ly", "shorturl.at", "tiny.cc", "rb.gy", "v.gd",
+];
+const SUSPICIOUS_TLDS: &[&str] = &[
+    "xyz", "top", "click", "link", "zip", "mov", "tk", "ml", "ga", "cf", "gq", "work", "rest", "cam", "icu",
+];
+const TELEGRAM_DOMAINS: &[&str] = &["t.me", "telegram.me", "telegram.org", "telegram.dog", "telesco.pe"];
+const REDIRECT_PARAMETERS: &[&str] = &["url", "u", "redirect", "redirect_uri", "redirect_url", "next", "target", "dest", "destination", "to", "link", "goto"];
+
+lazy_static! {
+    // Links with a scheme, plus bare t.me / www. links as people type them
+    static ref LINK_PATTERN: Regex = Regex::new(r"(?i)\b(?:(?:https?|tg|javascript|data|file|vbscript)://?|www\.|t\.me/|telegram\.me/)[^\s<>\x22']+").unwrap();
+    static ref TELEGRAM_LOOKALIKE: Regex = Regex::new(r"(?i)te[l1][e3][gq]r[a4@]m|t[e3][l1][e3]gr[a4]m|tlgrm").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&LINK_PATTERN);
+    lazy_static::initialize(&TELEGRAM_LOOKALIKE);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LinkVerdict {
+    SAFE,
+    SUSPICIOUS,
+    DANGEROUS,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkReport {
+    // As found, with trailing punctuation removed
+    pub url: String,
+    // Lowercased, without port or credentials; None for schemes without one
+    pub host: Option<String>,
+    pub verdict: LinkVerdict,
+    // 0-1
+    pub risk: f64,
+    pub reasons: Vec<String>,
+}
+
+struct ParsedLink<'a> {
+    scheme: String,
+    userinfo: bool,
+    host: Option<String>,
+    port: Option<&'a str>,
+    path: &'a str,
+    query: &'a str,
+}
+
+// Just enough of RFC 3986 for the checks below; links without a scheme are
+// taken as http
+fn parse(url: &str) -> ParsedLink<'_> {
+    let (scheme, rest) = match url.find(':') {
+        Some(i) if i > 0 && url[..i].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) && !url[i + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+            (url[..i].to_ascii_lowercase(), &url[i + 1..])
+        }
+        _ => ("http".to_string(), url),
+    };
+    let authority_and_path = rest.strip_prefix("//").unwrap_or(rest);
+    let has_authority = rest.starts_with("//") || !url.contains(':') || scheme == "http" || scheme == "https";
+    
+    let (before_query, query) = authority_and_path.split_once('?').unwrap_or((authority_and_path, ""));
+    let query = query.split('#').next().unwrap_or("");
+    let before_query = before_query.split('#').next().unwrap_or("");
+    if !has_authority || matches!(scheme.as_str(), "tg" | "javascript" | "data" | "mailto" | "tel" | "sms") {
+        // tg://resolve?domain=... has no host, the rest no authority at all
+        return ParsedLink { scheme, userinfo: false, host: None, port: None, path: before_query, query };
+    }
+    
+    let (authority, path) = match before_query.find('/') {
+        Some(i) => (&before_query[..i], &before_query[i..]),
+        None => (before_query, ""),
+    };
+    let (userinfo, host_port) = match authority.rfind('@') {
+        Some(i) => (true, &authority[i + 1..]),
+        None => (false, authority),
+    };
+    let (host, port) = if host_port.starts_with('[') {
+        match host_port.find(']') {
+            Some(i) => (&host_port[..=i], host_port[i + 1..].strip_prefix(':')),
+            None => (host_port, None),
+        }
+    } else {
+        match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        }
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    ParsedLink { scheme, userinfo, host: (!host.is_empty()).then_some(host), port, path, query }
+}
+
+fn query_value<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        key.eq_ignore_ascii_case(name).then_some(value)
+    })
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|pair| std::str::from_utf8(pair).ok()).and_then(|pair| u8::from_str_radix(pair, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn is_ip_literal(host: &str) -> bool {
+    host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok()
+        // Decimal and hex forms browsers also accept, e.g. http://3232235777
+        || (!host.is_empty() && host.chars().all(|c| c.is_ascii_digit()))
+        || host.starts_with("0x") && host[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_telegram_host(host: &str) -> bool {
+    TELEGRAM_DOMAINS.iter().any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+}
+
+// The start parameter of t.me/bot?start=... or tg://resolve?...&start=...
+// when it decodes to a link
+fn hidden_start_link(link: &ParsedLink) -> Option<String> {
+    let payload = query_value(link.query, "start").or_else(|| query_value(link.query, "startapp"))?;
+    let decoded = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let text = String::from_utf8(decoded).ok()?;
+    find(&text).map(str::to_string)
+}
+
+fn assess(url: &str, depth: usize) -> (f64, Vec<String>, Option<String>) {
+    let link = parse(url);
+    let mut risk = 0.0;
+    let mut reasons = Vec::new();
+    let mut flag = |weight: f64, reason: &str| {
+        risk += weight;
+        reasons.push(reason.to_string());
+    };
+    
+    match link.scheme.as_str() {
+        "javascript" | "data" | "vbscript" | "file" => flag(1.0, "runs a script or opens local data instead of a page"),
+        // Scanning someone else's login QR signs their device into your account
+        "tg" if link.path.trim_start_matches('/').eq_ignore_ascii_case("login") && query_value(link.query, "token").is_some() => {
+            flag(1.0, "Telegram login token that would sign another device into the account")
+        }
+        "http" if url.to_ascii_lowercase().starts_with("http:") => flag(0.15, "not encrypted"),
+        _ => {}
+    }
+    if link.userinfo {
+        flag(0.5, "text before @ hides the real destination");
+    }
+    if let Some(ref host) = link.host {
+        if is_ip_literal(host) {
+            flag(0.4, "points at a bare IP address");
+        }
+        if host.split('.').any(|label| label.starts_with("xn--")) {
+            flag(0.35, "internationalized domain that may imitate another");
+        }
+        if SHORTENERS.contains(&host.as_str()) {
+            flag(0.25, "link shortener hides the destination");
+        }
+        if SUSPICIOUS_TLDS.iter().any(|tld| host.ends_with(&format!(".{}", tld))) {
+            flag(0.25, "domain ending often used for abuse");
+        }
+        if host.split('.').count() > 4 {
+            flag(0.15, "unusually many subdomains");
+        }
+        if !is_telegram_host(host) && TELEGRAM_LOOKALIKE.is_match(host) {
+            flag(0.6, "imitates a Telegram domain");
+        }
+        if link.port.is_some_and(|port| !port.is_empty() && port != "80" && port != "443") {
+            flag(0.1, "unusual port");
+        }
+        if is_telegram_host(host) && (link.path.starts_with("/+") || link.path.starts_with("/joinchat/")) {
+            flag(0.1, "private invite link");
+        }
+    }
+    
+    // Where the link really leads, judged in its own right
+    let target = REDIRECT_PARAMETERS.iter()
+        .filter_map(|name| query_value(link.query, name))
+        .map(percent_decode)
+        .find(|value| value.contains("://") || value.starts_with("www."))
+        .map(|value| (value, "redirects to another link"))
+        .or_else(|| hidden_start_link(&link).map(|value| (value, "link hidden in the start parameter")));
+    if let Some((target, reason)) = target {
+        flag(0.2, reason);
+        if depth < MAX_NESTING {
+            let (target_risk, target_reasons, _) = assess(&target, depth + 1);
+            if target_risk > 0.0 {
+                risk += target_risk;
+                reasons.extend(target_reasons.into_iter().map(|r| format!("{}: {}", target, r)));
+            }
+        }
+    }
+    (risk.min(1.0), reasons, link.host)
+}
+
+// Unicode and other trailing punctuation is seldom part of a typed link
+fn trim_link(url: &str) -> &str {
+    url.trim_end_matches(|c: char| ".,;:!?)]}»،؛".contains(c))
+}
+
+pub fn classify(url: &str) -> LinkReport {
+    let url = trim_link(url.trim());
+    let (risk, reasons, host) = assess(url, 0);
+    let verdict = if risk >= DANGEROUS_RISK {
+        LinkVerdict::DANGEROUS
+    } else if risk >= SUSPICIOUS_RISK {
+        LinkVerdict::SUSPICIOUS
+    } else {
+        LinkVerdict::SAFE
+    };
+    LinkReport { url: url.to_string(), host, verdict, risk, reasons }
+}
+
+// The first link in text, trailing punctuation removed
+pub fn find(text: &str) -> Option<&str> {
+    LINK_PATTERN.find(text).map(|found| trim_link(found.as_str()))
+}
+
+// Every link in text, in order; source names where the text came from in
+// the security events of unsafe links ("message", "qr", ...)
+pub fn scan(text: &str, source: &str) -> Vec<LinkReport> {
+    let reports: Vec<LinkReport> = LINK_PATTERN.find_iter(text).map(|found| classify(found.as_str())).collect();
+    for report in &reports {
+        record(report, source);
+    }
+    reports
+}
+
+// DANGEROUS links are HIGH severity events and alert; SUSPICIOUS ones MEDIUM
+pub fn record(report: &LinkReport, source: &str) {
+    let severity = match report.verdict {
+        LinkVerdict::SAFE => return,
+        LinkVerdict::SUSPICIOUS => SecuritySeverity::MEDIUM,
+        LinkVerdict::DANGEROUS => SecuritySeverity::HIGH,
+    };
+    let verdict = format!("{:?}", report.verdict).to_lowercase();
+    let details = format!("{} link in {}: {} ({})", verdict, source, report.url, report.reasons.join("; "));
+    security::record_security_event("UNSAFE_LINK", None, None, details, severity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_classify_links() {
+        let safe = classify("https://core.telegram.org/bots/api.");
+        assert_eq!((safe.verdict, safe.host.as_deref(), safe.url.as_str()), (LinkVerdict::SAFE, Some("core.telegram.org"), "https://core.telegram.org/bots/api"));
+        assert!(safe.reasons.is_empty());
+        
+        assert_eq!(classify("http://telegram-premium.xyz/gift").verdict, LinkVerdict::DANGEROUS);
+        assert_eq!(classify("https://paypal.com@192.168.1.20/login").verdict, LinkVerdict::DANGEROUS);
+        assert_eq!(classify("javascript:alert(1)").verdict, LinkVerdict::DANGEROUS);
+        assert_eq!(classify("tg://login?token=AQAB").verdict, LinkVerdict::DANGEROUS);
+        assert_eq!(classify("https://bit.ly/3abc").verdict, LinkVerdict::SAFE);
+        assert_eq!(classify("https://xn--80ak6aa92e.com/").verdict, LinkVerdict::SUSPICIOUS);
+        assert_eq!(classify("t.me/+AbCdEf").host.as_deref(), Some("t.me"));
+        
+        // Redirects and start payloads are judged by where they lead
+        let redirect = classify("https://example.com/out?url=http%3A%2F%2F10.0.0.1%2Fwallet");
+        assert_eq!(redirect.verdict, LinkVerdict::DANGEROUS);
+        assert!(redirect.reasons.iter().any(|r| r.starts_with("http://10.0.0.1/wallet: ")));
+        let payload = URL_SAFE_NO_PAD.encode("https://te1egram.click/verify");
+        let deep_link = classify(&format!("https://t.me/some_bot?start={}", payload));
+        assert_eq!(deep_link.verdict, LinkVerdict::DANGEROUS);
+        assert!(deep_link.reasons.contains(&"link hidden in the start parameter".to_string()));
+    }
+    
+    #[test]
+    fn test_scan_records_unsafe_links() {
+        let reports = scan("Free stars: http://te1egram-gift.top/claim, docs at https://core.telegram.org", "test");
+        let verdicts: Vec<LinkVerdict> = reports.iter().map(|report| report.verdict).collect();
+        assert_eq!(verdicts, vec![LinkVerdict::DANGEROUS, LinkVerdict::SAFE]);
+        let events = security::get_security_events(Some(SecuritySeverity::HIGH), 1000);
+        assert!(events.iter().any(|event| event.event_type == "UNSAFE_LINK" && event.details.contains("te1egram-gift.top")));
+    }
+}
\ No newline at end of file
@@ -4,6 +4,9 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
+use regex::Regex;
+use crate::tenant;
+use crate::pii::{PiiRedactor, RedactionStyle};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LogLevel {
@@ -14,6 +17,39 @@ pub enum LogLevel {
     CRITICAL,
 }
 
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<LogLevel> {
+        match level.to_lowercase().as_str() {
+            "debug" => Some(LogLevel::DEBUG),
+            "info" => Some(LogLevel::INFO),
+            "warn" | "warning" => Some(LogLevel::WARN),
+            "error" => Some(LogLevel::ERROR),
+            "critical" => Some(LogLevel::CRITICAL),
+            _ => None,
+        }
+    }
+    
+    pub fn severity(&self) -> u8 {
+        match self {
+            LogLevel::DEBUG => 0,
+            LogLevel::INFO => 1,
+            LogLevel::WARN => 2,
+            LogLevel::ERROR => 3,
+            LogLevel::CRITICAL => 4,
+        }
+    }
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::DEBUG => "debug",
+            LogLevel::INFO => "info",
+            LogLevel::WARN => "warn",
+            LogLevel::ERROR => "error",
+            LogLevel::CRITICAL => "critical",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub timestamp: DateTime<Utc>,
@@ -57,15 +93,358 @@ pub struct SystemHealth {
 }
 
 lazy_static! {
-    static ref LOGGER: Arc<Mutex<Logger>> = Arc::new(Mutex::new(Logger::new()));
+    // Secrets that never belong in logs; personal data is found by pii.rs
+    static ref BUILTIN_SCRUB_PATTERNS: Vec<(&'static str, Regex)> = vec![
+        ("BOT_TOKEN", Regex::new(r"\b\d{6,12}:[A-Za-z0-9_-]{30,}").unwrap()),
+        ("BEARER_TOKEN", Regex::new(r"(?i)\bbearer\s+[A-Za-z0-9._~+/-]+=*").unwrap()),
+    ];
+}
+
+// Redacts configured patterns from messages and metadata before an entry
+// reaches any sink
+pub struct Scrubber {
+    builtin: bool,
+    pii: PiiRedactor,
+    custom: Vec<Regex>,
+}
+
+impl Scrubber {
+    pub fn new(config: &crate::config::LoggingConfig) -> Self {
+        Self {
+            builtin: !config.disable_builtin_scrubbing,
+            pii: PiiRedactor::default().with_style(RedactionStyle::LABEL),
+            custom: config.scrub_patterns.iter()
+                .filter_map(|pattern| Regex::new(pattern).ok())
+                .collect(),
+        }
+    }
+    
+    pub fn scrub(&self, text: &str) -> String {
+        let mut scrubbed = text.to_string();
+        if self.builtin {
+            for (label, pattern) in BUILTIN_SCRUB_PATTERNS.iter() {
+                scrubbed = pattern.replace_all(&scrubbed, format!("[REDACTED:{}]", label).as_str()).into_owned();
+            }
+            scrubbed = self.pii.redact(&scrubbed);
+        }
+        for pattern in &self.custom {
+            scrubbed = pattern.replace_all(&scrubbed, "[REDACTED]").into_owned();
+        }
+        scrubbed
+    }
+    
+    pub fn scrub_entry(&self, entry: &mut LogEntry) {
+        entry.message = self.scrub(&entry.message);
+        for value in entry.metadata.values_mut() {
+            *value = self.scrub(value);
+        }
+    }
+}
+
+// Destination for log entries besides the in-memory buffer
+pub trait LogSink: Send {
+    fn write(&mut self, entry: &LogEntry);
+    
+    // Called once before the sink is discarded at shutdown
+    fn close(&mut self) {}
+}
+
+pub struct ConsoleSink;
+
+impl LogSink for ConsoleSink {
+    fn write(&mut self, entry: &LogEntry) {
+        println!("[{}] {} - {}:{} - {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            format!("{:?}", entry.level),
+            entry.module,
+            entry.line,
+            entry.message
+        );
+    }
+    
+    fn close(&mut self) {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }
+}
+
+const DEFAULT_APP_NAME: &str = "aiogram_rust";
+const DEFAULT_SYSLOG_SOCKET: &str = "/dev/log";
+const DEFAULT_LOG_FILE: &str = "aiogram.log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+// Private enterprise number reserved for documentation (RFC 5612)
+const SYSLOG_SD_ID: &str = "aiogram@32473";
+
+// Syslog severities; journald uses the same numbering for PRIORITY
+fn syslog_severity(level: &LogLevel) -> u8 {
+    match level {
+        LogLevel::DEBUG => 7,
+        LogLevel::INFO => 6,
+        LogLevel::WARN => 4,
+        LogLevel::ERROR => 3,
+        LogLevel::CRITICAL => 2,
+    }
+}
+
+fn escape_sd_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+// Formats an entry as an RFC 5424 message with facility "user" and the entry
+// context as structured data
+fn format_rfc5424(entry: &LogEntry, hostname: &str, app_name: &str) -> String {
+    let priority = 8 + syslog_severity(&entry.level) as u32;
+    
+    let mut params = vec![
+        format!("module=\"{}\"", escape_sd_value(&entry.module)),
+        format!("function=\"{}\"", escape_sd_value(&entry.function)),
+        format!("line=\"{}\"", entry.line),
+    ];
+    if let Some(ref request_id) = entry.request_id {
+        params.push(format!("request_id=\"{}\"", escape_sd_value(request_id)));
+    }
+    if let Some(ref user_id) = entry.user_id {
+        params.push(format!("user_id=\"{}\"", escape_sd_value(user_id)));
+    }
+    if let Some(duration_ms) = entry.duration_ms {
+        params.push(format!("duration_ms=\"{}\"", duration_ms));
+    }
+    let mut metadata: Vec<(&String, &String)> = entry.metadata.iter().collect();
+    metadata.sort();
+    for (key, value) in metadata {
+        // SD-NAMEs may not contain '=', ' ', ']' or '"'
+        let name: String = key.chars()
+            .filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"'))
+            .take(32)
+            .collect();
+        if !name.is_empty() {
+            params.push(format!("{}=\"{}\"", name, escape_sd_value(value)));
+        }
+    }
+    
+    format!("<{}>1 {} {} {} {} - [{} {}] {}",
+        priority,
+        entry.timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        hostname,
+        app_name,
+        std::process::id(),
+        SYSLOG_SD_ID,
+        params.join(" "),
+        entry.message
+    )
+}
+
+// Encodes an entry in the journald native protocol. Values containing
+// newlines use the length-prefixed binary form.
+fn encode_journald(entry: &LogEntry, app_name: &str) -> Vec<u8> {
+    let mut fields: Vec<(String, String)> = vec![
+        ("MESSAGE".to_string(), entry.message.clone()),
+        ("PRIORITY".to_string(), syslog_severity(&entry.level).to_string()),
+        ("SYSLOG_IDENTIFIER".to_string(), app_name.to_string()),
+        ("CODE_FUNC".to_string(), entry.function.clone()),
+        ("CODE_LINE".to_string(), entry.line.to_string()),
+        ("AIOGRAM_MODULE".to_string(), entry.module.clone()),
+    ];
+    if let Some(ref request_id) = entry.request_id {
+        fields.push(("AIOGRAM_REQUEST_ID".to_string(), request_id.clone()));
+    }
+    if let Some(ref user_id) = entry.user_id {
+        fields.push(("AIOGRAM_USER_ID".to_string(), user_id.clone()));
+    }
+    if let Some(duration_ms) = entry.duration_ms {
+        fields.push(("AIOGRAM_DURATION_MS".to_string(), duration_ms.to_string()));
+    }
+    for (key, value) in &entry.metadata {
+        // Field names are limited to uppercase letters, digits and underscores
+        let name: String = key.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        fields.push((format!("AIOGRAM_{}", name.trim_start_matches('_')), value.clone()));
+    }
+    
+    let mut payload = Vec::new();
+    for (name, value) in fields {
+        payload.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            payload.push(b'\n');
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            payload.push(b'=');
+        }
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(b'\n');
+    }
+    payload
+}
+
+#[cfg(unix)]
+fn local_hostname() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return "-".to_string();
+    }
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    match std::str::from_utf8(&buffer[..len]) {
+        Ok(name) if !name.is_empty() => name.to_string(),
+        _ => "-".to_string(),
+    }
+}
+
+// No gethostname outside unix; Windows sets COMPUTERNAME for every process
+#[cfg(not(unix))]
+fn local_hostname() -> String {
+    ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+enum SyslogTransport {
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixDatagram, String),
+    Udp(std::net::UdpSocket, String),
+}
+
+pub struct SyslogSink {
+    transport: SyslogTransport,
+    hostname: String,
+    app_name: String,
+}
+
+impl SyslogSink {
+    fn connect(address: &str, app_name: &str) -> std::io::Result<Self> {
+        let transport = if address.starts_with('/') {
+            #[cfg(unix)]
+            {
+                SyslogTransport::Unix(std::os::unix::net::UnixDatagram::unbound()?, address.to_string())
+            }
+            #[cfg(not(unix))]
+            {
+                return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "unix sockets are not available"));
+            }
+        } else {
+            SyslogTransport::Udp(std::net::UdpSocket::bind("0.0.0.0:0")?, address.to_string())
+        };
+        
+        Ok(Self {
+            transport,
+            hostname: local_hostname(),
+            app_name: app_name.to_string(),
+        })
+    }
+}
+
+impl LogSink for SyslogSink {
+    fn write(&mut self, entry: &LogEntry) {
+        let message = format_rfc5424(entry, &self.hostname, &self.app_name);
+        // Delivery is best effort; a missing syslog daemon must not break logging
+        let _ = match &self.transport {
+            #[cfg(unix)]
+            SyslogTransport::Unix(socket, path) => socket.send_to(message.as_bytes(), path),
+            SyslogTransport::Udp(socket, address) => socket.send_to(message.as_bytes(), address.as_str()),
+        };
+    }
+}
+
+// Appends one JSON object per line
+pub struct FileSink {
+    file: std::fs::File,
+}
+
+impl FileSink {
+    fn open(path: &std::path::Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, entry: &LogEntry) {
+        if let Ok(mut line) = serde_json::to_vec(entry) {
+            line.push(b'\n');
+            let _ = std::io::Write::write_all(&mut self.file, &line);
+        }
+    }
+    
+    fn close(&mut self) {
+        let _ = self.file.sync_all();
+    }
+}
+
+#[cfg(unix)]
+pub struct JournaldSink {
+    socket: std::os::unix::net::UnixDatagram,
+    app_name: String,
+}
+
+#[cfg(unix)]
+impl LogSink for JournaldSink {
+    fn write(&mut self, entry: &LogEntry) {
+        let _ = self.socket.send_to(&encode_journald(entry, &self.app_name), JOURNALD_SOCKET);
+    }
+}
+
+fn create_sink(name: &str, app_config: &crate::config::AppConfig) -> Option<Box<dyn LogSink>> {
+    let config = &app_config.logging;
+    let app_name = config.app_name.as_deref().unwrap_or(DEFAULT_APP_NAME);
+    
+    match name {
+        "console" => Some(Box::new(ConsoleSink)),
+        "syslog" => {
+            let address = config.syslog_address.as_deref().unwrap_or(DEFAULT_SYSLOG_SOCKET);
+            SyslogSink::connect(address, app_name)
+                .ok()
+                .map(|sink| Box::new(sink) as Box<dyn LogSink>)
+        },
+        "file" => {
+            let path = crate::paths::resolve(
+                config.file_path.as_deref().unwrap_or(DEFAULT_LOG_FILE),
+                crate::paths::PathKind::LOGS,
+                app_config.paths.base_dir.as_deref(),
+            );
+            FileSink::open(&path)
+                .ok()
+                .map(|sink| Box::new(sink) as Box<dyn LogSink>)
+        },
+        #[cfg(unix)]
+        "journald" => std::os::unix::net::UnixDatagram::unbound()
+            .ok()
+            .map(|socket| Box::new(JournaldSink { socket, app_name: app_name.to_string() }) as Box<dyn LogSink>),
+        #[cfg(feature = "otlp")]
+        "otlp" => crate::telemetry::OtlpLogSink::start(&app_config.telemetry)
+            .map(|sink| Box::new(sink) as Box<dyn LogSink>),
+        _ => None,
+    }
+}
+
+lazy_static! {
+    static ref LOGGER: Arc<Mutex<Logger>> = Arc::new(Mutex::new(Logger::from_config(&crate::config::AppConfig::get())));
     static ref METRICS: Arc<Mutex<MetricsCollector>> = Arc::new(Mutex::new(MetricsCollector::new()));
 }
 
+// Forces the singletons during init_library instead of on the first log call
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&BUILTIN_SCRUB_PATTERNS);
+    lazy_static::initialize(&LOGGER);
+    lazy_static::initialize(&METRICS);
+    Ok(())
+}
+
 pub struct Logger {
     entries: Vec<LogEntry>,
     max_entries: usize,
     enabled: bool,
     log_level: LogLevel,
+    // Sorted longest prefix first so the most specific override wins
+    module_levels: Vec<(String, LogLevel)>,
+    sampling_rates: HashMap<String, f64>,
+    memory_enabled: bool,
+    sinks: Vec<Box<dyn LogSink>>,
+    scrubber: Scrubber,
 }
 
 pub struct MetricsCollector {
@@ -82,54 +461,134 @@ impl Logger {
             max_entries: 10000,
             enabled: true,
             log_level: LogLevel::INFO,
+            module_levels: Vec::new(),
+            sampling_rates: HashMap::new(),
+            memory_enabled: true,
+            sinks: Vec::new(),
+            scrubber: Scrubber::new(&crate::config::LoggingConfig::default()),
+        }
+    }
+    
+    fn from_config(config: &crate::config::AppConfig) -> Self {
+        let mut logger = Self::new();
+        logger.apply_config(config);
+        logger
+    }
+    
+    pub fn apply_config(&mut self, config: &crate::config::AppConfig) {
+        let logging = &config.logging;
+        let level = logging.level.as_deref().unwrap_or(&config.analysis.log_level);
+        
+        self.enabled = config.analysis.enable_logging;
+        self.log_level = LogLevel::parse(level).unwrap_or(LogLevel::INFO);
+        
+        self.module_levels = logging.module_levels.iter()
+            .filter_map(|(module, level)| LogLevel::parse(level).map(|level| (module.clone(), level)))
+            .collect();
+        self.module_levels.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        
+        self.sampling_rates = logging.sampling_rates.iter()
+            .map(|(level, rate)| (level.to_lowercase(), rate.clamp(0.0, 1.0)))
+            .collect();
+        
+        // Without an explicit selection, keep entries in memory and echo them
+        // to the console in production
+        let sink_names: Vec<String> = if logging.sinks.is_empty() {
+            let mut defaults = vec!["memory".to_string()];
+            if config.environment == "production" {
+                defaults.push("console".to_string());
+            }
+            defaults
+        } else {
+            logging.sinks.clone()
+        };
+        
+        self.scrubber = Scrubber::new(logging);
+        self.memory_enabled = sink_names.iter().any(|name| name == "memory");
+        self.sinks = sink_names.iter().filter_map(|name| create_sink(name, config)).collect();
+    }
+    
+    // Hands buffered entries to their destinations and detaches all sinks;
+    // the in-memory store keeps working until the next apply_config
+    fn close_sinks(&mut self) {
+        for mut sink in self.sinks.drain(..) {
+            sink.close();
         }
     }
     
-    pub fn log(&mut self, level: LogLevel, message: &str, module: &str, function: &str, line: u32) {
-        if !self.enabled || !self.should_log(&level) {
-            return;
+    // Returns the entry when it passed the level filter, even if sampling
+    // kept it out of the sinks, so alert rules still see it
+    pub fn log(&mut self, level: LogLevel, message: &str, module: &str, function: &str, line: u32) -> Option<LogEntry> {
+        self.log_with_metadata(level, message, module, function, line, HashMap::new())
+    }
+    
+    pub fn log_with_metadata(&mut self, level: LogLevel, message: &str, module: &str, function: &str, line: u32, metadata: HashMap<String, String>) -> Option<LogEntry> {
+        self.record(level, message, module, function, line, metadata, false)
+    }
+    
+    // watched: alert rules count entries at this level, so the entry is
+    // built and returned even when the level or enabled setting keeps it
+    // out of the sinks
+    #[allow(clippy::too_many_arguments)]
+    fn record(&mut self, level: LogLevel, message: &str, module: &str, function: &str, line: u32, mut metadata: HashMap<String, String>, watched: bool) -> Option<LogEntry> {
+        let written = self.enabled && self.should_log(&level, module);
+        if !written && !watched {
+            return None;
         }
         
-        let entry = LogEntry {
+        // Entries logged while serving a tenant carry its ids
+        let context = tenant::current();
+        if let Some(tenant_id) = context.tenant_id {
+            metadata.entry("tenant_id".to_string()).or_insert(tenant_id);
+        }
+        
+        let mut entry = LogEntry {
             timestamp: Utc::now(),
             level,
             message: message.to_string(),
             module: module.to_string(),
             function: function.to_string(),
             line,
-            user_id: None,
+            user_id: context.user_id,
             request_id: None,
             duration_ms: None,
-            metadata: HashMap::new(),
+            metadata,
         };
+        self.scrubber.scrub_entry(&mut entry);
         
-        self.entries.push(entry);
+        if !written || !self.should_sample(&entry.level) {
+            return Some(entry);
+        }
         
-        // Keep only the latest entries
-        if self.entries.len() > self.max_entries {
-            self.entries.drain(0..self.entries.len() - self.max_entries);
+        for sink in self.sinks.iter_mut() {
+            sink.write(&entry);
         }
         
-        // Print to console in development
-        if crate::config::AppConfig::is_production() {
-            println!("[{}] {} - {}:{} - {}", 
-                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                format!("{:?}", entry.level),
-                entry.module,
-                entry.line,
-                entry.message
-            );
+        if self.memory_enabled {
+            self.entries.push(entry.clone());
+            
+            // Keep only the latest entries
+            if self.entries.len() > self.max_entries {
+                self.entries.drain(0..self.entries.len() - self.max_entries);
+            }
         }
+        
+        Some(entry)
     }
     
-    fn should_log(&self, level: &LogLevel) -> bool {
-        match (&self.log_level, level) {
-            (LogLevel::DEBUG, _) => true,
-            (LogLevel::INFO, LogLevel::INFO | LogLevel::WARN | LogLevel::ERROR | LogLevel::CRITICAL) => true,
-            (LogLevel::WARN, LogLevel::WARN | LogLevel::ERROR | LogLevel::CRITICAL) => true,
-            (LogLevel::ERROR, LogLevel::ERROR | LogLevel::CRITICAL) => true,
-            (LogLevel::CRITICAL, LogLevel::CRITICAL) => true,
-            _ => false,
+    fn should_log(&self, level: &LogLevel, module: &str) -> bool {
+        let threshold = self.module_levels.iter()
+            .find(|(prefix, _)| module_matches(module, prefix))
+            .map(|(_, level)| level)
+            .unwrap_or(&self.log_level);
+        
+        level.severity() >= threshold.severity()
+    }
+    
+    fn should_sample(&self, level: &LogLevel) -> bool {
+        match self.sampling_rates.get(level.name()) {
+            Some(rate) if *rate < 1.0 => rand::random::<f64>() < *rate,
+            _ => true,
         }
     }
     
@@ -159,6 +618,15 @@ impl Logger {
     }
 }
 
+// Matches "analysis" against "analysis", "analysis::sentiment" and
+// "aiogram_rust::analysis"
+pub fn module_matches(module: &str, prefix: &str) -> bool {
+    let crate_prefix = concat!(env!("CARGO_CRATE_NAME"), "::");
+    let module = module.strip_prefix(crate_prefix).unwrap_or(module);
+    let prefix = prefix.strip_prefix(crate_prefix).unwrap_or(prefix);
+    module == prefix || module.starts_with(&format!("{}::", prefix))
+}
+
 impl MetricsCollector {
     fn new() -> Self {
         Self {
@@ -258,33 +726,59 @@ impl MetricsCollector {
 }
 
 // Public logging functions
-pub fn debug(message: &str, module: &str, function: &str, line: u32) {
-    if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(LogLevel::DEBUG, message, module, function, line);
+fn log_at(level: LogLevel, message: &str, module: &str, function: &str, line: u32, metadata: HashMap<String, String>) {
+    let watched = crate::alerts::watches(&level);
+    let entry = match LOGGER.lock() {
+        Ok(mut logger) => logger.record(level, message, module, function, line, metadata, watched),
+        Err(_) => None,
+    };
+    
+    // Evaluated after the logger lock is released so alert subscribers can log
+    if let Some(entry) = entry {
+        crate::alerts::observe_log(&entry);
     }
 }
 
+pub fn debug(message: &str, module: &str, function: &str, line: u32) {
+    log_at(LogLevel::DEBUG, message, module, function, line, HashMap::new());
+}
+
 pub fn info(message: &str, module: &str, function: &str, line: u32) {
-    if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(LogLevel::INFO, message, module, function, line);
-    }
+    log_at(LogLevel::INFO, message, module, function, line, HashMap::new());
 }
 
 pub fn warn(message: &str, module: &str, function: &str, line: u32) {
-    if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(LogLevel::WARN, message, module, function, line);
-    }
+    log_at(LogLevel::WARN, message, module, function, line, HashMap::new());
 }
 
 pub fn error(message: &str, module: &str, function: &str, line: u32) {
+    log_at(LogLevel::ERROR, message, module, function, line, HashMap::new());
+}
+
+pub fn critical(message: &str, module: &str, function: &str, line: u32) {
+    log_at(LogLevel::CRITICAL, message, module, function, line, HashMap::new());
+}
+
+pub fn log_with_metadata(level: LogLevel, message: &str, module: &str, function: &str, line: u32, metadata: HashMap<String, String>) {
+    log_at(level, message, module, function, line, metadata);
+}
+
+// Entry point for logs written by the host application
+pub fn log_message(level: LogLevel, module: &str, message: &str, metadata: HashMap<String, String>) {
+    log_at(level, message, module, "host", 0, metadata);
+}
+
+// Re-reads the logging section after a config change
+pub fn reload_config() {
+    let config = crate::config::AppConfig::get();
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(LogLevel::ERROR, message, module, function, line);
+        logger.apply_config(&config);
     }
 }
 
-pub fn critical(message: &str, module: &str, function: &str, line: u32) {
+pub fn shutdown() {
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(LogLevel::CRITICAL, message, module, function, line);
+        logger.close_sinks();
     }
 }
 
@@ -377,6 +871,160 @@ mod tests {
         assert!(!logs.is_empty());
     }
     
+    #[test]
+    fn test_file_sink() {
+        let dir = std::env::temp_dir().join(format!("aiogram_file_sink_{}", std::process::id()));
+        let mut config = crate::config::AppConfig::default();
+        config.paths.base_dir = Some(dir.to_string_lossy().to_string());
+        config.logging.file_path = Some("nested/../bot.log".to_string());
+        
+        let mut sink = create_sink("file", &config).expect("file sink not created");
+        let mut logger = Logger::new();
+        let entry = logger.log(LogLevel::INFO, "File sink entry", "test", "test_file_sink", line!()).unwrap();
+        sink.write(&entry);
+        sink.close();
+        
+        let path = crate::paths::resolve("bot.log", crate::paths::PathKind::LOGS, config.paths.base_dir.as_deref());
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let written: LogEntry = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(written.message, "File sink entry");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_host_log_message() {
+        let mut metadata = HashMap::new();
+        metadata.insert("chat_id".to_string(), "42".to_string());
+        log_message(LogLevel::WARN, "bot.handlers", "Host warning for test_host_log_message", metadata);
+        
+        let logs = get_recent_logs(None, 1000);
+        let entry = logs.iter()
+            .find(|entry| entry.message == "Host warning for test_host_log_message")
+            .expect("host log entry missing");
+        assert_eq!(entry.module, "bot.handlers");
+        assert_eq!(entry.metadata["chat_id"], "42");
+    }
+    
+    #[test]
+    fn test_module_level_overrides() {
+        let mut config = crate::config::AppConfig::default();
+        config.logging.level = Some("warn".to_string());
+        config.logging.module_levels.insert("analysis".to_string(), "debug".to_string());
+        config.logging.sinks = vec!["memory".to_string()];
+        
+        let mut logger = Logger::from_config(&config);
+        logger.log(LogLevel::DEBUG, "kept", "aiogram_rust::analysis", "f", 1);
+        logger.log(LogLevel::INFO, "dropped", "aiogram_rust::cache", "f", 2);
+        logger.log(LogLevel::ERROR, "kept", "aiogram_rust::cache", "f", 3);
+        
+        let entries = logger.get_entries(None, 10);
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.message == "kept"));
+    }
+    
+    #[test]
+    fn test_alert_rules_see_filtered_entries() {
+        let mut config = crate::config::AppConfig::default();
+        config.logging.level = Some("warn".to_string());
+        config.logging.sinks = vec!["memory".to_string()];
+        
+        let mut logger = Logger::from_config(&config);
+        assert!(logger.record(LogLevel::INFO, "unwatched", "test", "f", 1, HashMap::new(), false).is_none());
+        let entry = logger.record(LogLevel::INFO, "watched", "test", "f", 2, HashMap::new(), true).unwrap();
+        assert_eq!(entry.message, "watched");
+        
+        // Disabled logging still feeds the rules
+        config.analysis.enable_logging = false;
+        logger.apply_config(&config);
+        assert!(logger.record(LogLevel::ERROR, "disabled", "test", "f", 3, HashMap::new(), true).is_some());
+        assert!(logger.get_entries(None, 10).is_empty());
+    }
+    
+    #[test]
+    fn test_sampling_rates() {
+        let mut config = crate::config::AppConfig::default();
+        config.logging.sampling_rates.insert("info".to_string(), 0.0);
+        
+        let mut logger = Logger::from_config(&config);
+        for i in 0..10 {
+            logger.log(LogLevel::INFO, "sampled out", "test", "f", i);
+        }
+        logger.log(LogLevel::WARN, "kept", "test", "f", 11);
+        
+        assert_eq!(logger.get_entries(None, 100).len(), 1);
+    }
+    
+    #[test]
+    fn test_scrubbing() {
+        let mut config = crate::config::LoggingConfig::default();
+        config.scrub_patterns = vec![r"chat_\d+".to_string()];
+        let scrubber = Scrubber::new(&config);
+        
+        let scrubbed = scrubber.scrub("token 123456789:AAHdqTcvCH1vGWJxfSeofSAs0K5PALDsaw0 from chat_42");
+        assert_eq!(scrubbed, "token [REDACTED:BOT_TOKEN] from [REDACTED]");
+        
+        let scrubbed = scrubber.scrub("Contact john.doe@example.com or 555-123-4567 at 2024-01-15");
+        assert_eq!(scrubbed, "Contact [REDACTED:EMAIL] or [REDACTED:PHONE] at 2024-01-15");
+        let scrubbed = scrubber.scrub("card 4111 1111 1111 1111");
+        assert_eq!(scrubbed, "card [REDACTED:CREDIT_CARD]");
+        
+        let mut entry = sample_entry();
+        entry.metadata.insert("user".to_string(), "mail me: a@b.io".to_string());
+        scrubber.scrub_entry(&mut entry);
+        assert_eq!(entry.metadata["user"], "mail me: [REDACTED:EMAIL]");
+        
+        config.disable_builtin_scrubbing = true;
+        assert_eq!(Scrubber::new(&config).scrub("a@b.io"), "a@b.io");
+    }
+    
+    fn sample_entry() -> LogEntry {
+        let mut metadata = HashMap::new();
+        metadata.insert("chat id".to_string(), "42".to_string());
+        
+        LogEntry {
+            timestamp: Utc::now(),
+            level: LogLevel::ERROR,
+            message: "Decryption failed".to_string(),
+            module: "crypto".to_string(),
+            function: "decrypt".to_string(),
+            line: 7,
+            user_id: None,
+            request_id: Some("req_\"1]".to_string()),
+            duration_ms: None,
+            metadata,
+        }
+    }
+    
+    #[test]
+    fn test_rfc5424_format() {
+        let message = format_rfc5424(&sample_entry(), "host", "bot");
+        
+        assert!(message.starts_with("<11>1 "));
+        assert!(message.contains(" host bot "));
+        assert!(message.contains("module=\"crypto\""));
+        assert!(message.contains("request_id=\"req_\\\"1\\]\""));
+        assert!(message.contains("chatid=\"42\""));
+        assert!(message.ends_with("] Decryption failed"));
+    }
+    
+    #[test]
+    fn test_journald_encoding() {
+        let mut entry = sample_entry();
+        entry.message = "line one\nline two".to_string();
+        let payload = encode_journald(&entry, "bot");
+        let text = String::from_utf8_lossy(&payload);
+        
+        assert!(text.contains("PRIORITY=3\n"));
+        assert!(text.contains("SYSLOG_IDENTIFIER=bot\n"));
+        assert!(text.contains("AIOGRAM_CHAT_ID=42\n"));
+        
+        // Multi-line values use the binary length-prefixed form
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&(entry.message.len() as u64).to_le_bytes());
+        expected.extend_from_slice(entry.message.as_bytes());
+        assert!(payload.windows(expected.len()).any(|w| w == expected.as_slice()));
+    }
+    
     #[test]
     fn test_performance_monitoring() {
         record_performance("test_operation", 100, true, None);
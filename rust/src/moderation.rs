@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+use crate::paths::{self, PathKind};
+use crate::{stopwords, tenant};
+
+// Profanity moderation. Words are matched per language with a severity, and
+// the severity picks the action through two thresholds. Chats can override
+// the defaults: extra banned words or phrases, whitelisted terms (a city
+// named like a swear word, a community's reclaimed slang) and their own
+// thresholds. Overrides are saved to a JSON file when a store is open.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ProfanitySeverity {
+    MILD,
+    MODERATE,
+    SEVERE,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ModerationAction {
+    ALLOW,
+    WARN,
+    DELETE,
+}
+
+// Unset fields fall back to the defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatModerationPolicy {
+    // Lowercase words or phrases, flagged in every language
+    pub banned_words: BTreeMap<String, ProfanitySeverity>,
+    // Never flagged, including built-in words
+    pub allowed_words: BTreeSet<String>,
+    pub warn_at: Option<ProfanitySeverity>,
+    pub delete_at: Option<ProfanitySeverity>,
+    // ISO 639-1 code of the chat; skips detection, which is unreliable on
+    // short messages
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfanityMatch {
+    // As written in the message
+    pub word: String,
+    // The list entry it matched
+    pub term: String,
+    pub severity: ProfanitySeverity,
+    // Byte offsets into the message
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationResult {
+    pub action: ModerationAction,
+    // Highest severity among the matches
+    pub severity: Option<ProfanitySeverity>,
+    pub matches: Vec<ProfanityMatch>,
+}
+
+pub const DEFAULT_WARN_AT: ProfanitySeverity = ProfanitySeverity::MODERATE;
+pub const DEFAULT_DELETE_AT: ProfanitySeverity = ProfanitySeverity::SEVERE;
+// Longest banned phrase, in words
+const MAX_PHRASE_WORDS: usize = 4;
+
+use ProfanitySeverity::{MILD, MODERATE, SEVERE};
+
+// Deliberately short; chats extend them through banned_words
+const EN: &[(&str, ProfanitySeverity)] = &[
+    ("damn", MILD), ("crap", MILD), ("bloody", MILD), ("piss", MILD), ("hell", MILD),
+    ("shit", MODERATE), ("shitty", MODERATE), ("bullshit", MODERATE), ("bitch", MODERATE),
+    ("bastard", MODERATE), ("asshole", MODERATE), ("dick", MODERATE), ("prick", MODERATE),
+    ("fuck", SEVERE), ("fucking", SEVERE), ("fucked", SEVERE), ("fucker", SEVERE),
+    ("motherfucker", SEVERE), ("cunt", SEVERE),
+];
+
+const FA: &[(&str, ProfanitySeverity)] = &[
+    ("لعنتی", MILD), ("احمق", MILD), ("بیشعور", MILD),
+    ("کثافت", MODERATE), ("عوضی", MODERATE), ("گوه", MODERATE), ("گمشو", MODERATE), ("آشغال", MODERATE),
+    ("کسکش", SEVERE), ("جنده", SEVERE), ("کیر", SEVERE), ("حرومزاده", SEVERE),
+];
+
+const RU: &[(&str, ProfanitySeverity)] = &[
+    ("блин", MILD), ("чёрт", MILD), ("черт", MILD),
+    ("дерьмо", MODERATE), ("сука", MODERATE), ("говно", MODERATE),
+    ("блять", SEVERE), ("бля", SEVERE), ("хуй", SEVERE), ("пиздец", SEVERE),
+];
+
+const ES: &[(&str, ProfanitySeverity)] = &[
+    ("maldito", MILD), ("maldita", MILD),
+    ("mierda", MODERATE), ("cabrón", MODERATE), ("gilipollas", MODERATE),
+    ("puta", SEVERE), ("coño", SEVERE), ("joder", SEVERE),
+];
+
+const DE: &[(&str, ProfanitySeverity)] = &[
+    ("verdammt", MILD),
+    ("scheiße", MODERATE), ("scheisse", MODERATE), ("arschloch", MODERATE),
+    ("wichser", SEVERE), ("fotze", SEVERE),
+];
+
+lazy_static! {
+    static ref BUILTIN: HashMap<&'static str, HashMap<String, ProfanitySeverity>> = {
+        let lists: [(&str, &[(&str, ProfanitySeverity)]); 5] = [("en", EN), ("fa", FA), ("ru", RU), ("es", ES), ("de", DE)];
+        lists.iter().map(|(lang, words)| (*lang, words.iter().map(|(word, severity)| (normalize(word), *severity)).collect())).collect()
+    };
+    static ref POLICIES: RwLock<PolicyStore> = RwLock::new(PolicyStore::default());
+}
+
+// Lowercase, Arabic letters folded to their Persian forms and digits used
+// as letters ("sh1t") read as letters
+fn normalize(word: &str) -> String {
+    let has_letters = word.chars().any(char::is_alphabetic);
+    word.chars().flat_map(char::to_lowercase).map(|c| match c {
+        'ي' | 'ى' => 'ی',
+        'ك' => 'ک',
+        '0' if has_letters => 'o',
+        '1' if has_letters => 'i',
+        '3' if has_letters => 'e',
+        '4' if has_letters => 'a',
+        '5' if has_letters => 's',
+        '7' if has_letters => 't',
+        c => c,
+    }).collect()
+}
+
+// "fuuuuck" -> "fuck"; only applied to words with a letter repeated three
+// times, so "ass"/"as" style collisions stay apart
+fn squeeze(word: &str) -> Option<String> {
+    let chars: Vec<char> = word.chars().collect();
+    if !chars.windows(3).any(|w| w[0] == w[1] && w[1] == w[2]) {
+        return None;
+    }
+    let mut squeezed = String::new();
+    let mut last = None;
+    for c in chars {
+        if Some(c) != last {
+            squeezed.push(c);
+        }
+        last = Some(c);
+    }
+    Some(squeezed)
+}
+
+fn language_of(text: &str, policy: &ChatModerationPolicy) -> Option<&'static str> {
+    match policy.language {
+        Some(ref language) => stopwords::language_code(language),
+        None => whatlang::detect(text).filter(|info| info.is_reliable()).and_then(|info| stopwords::language_code(info.lang().code())),
+    }
+}
+
+fn builtin_severity(language: Option<&str>, term: &str) -> Option<ProfanitySeverity> {
+    match language.and_then(|lang| BUILTIN.get(lang)) {
+        Some(words) => words.get(term).copied(),
+        None => BUILTIN.values().filter_map(|words| words.get(term).copied()).max(),
+    }
+}
+
+pub fn check_with_policy(text: &str, policy: &ChatModerationPolicy) -> ModerationResult {
+    let language = language_of(text, policy);
+    let allowed: BTreeSet<String> = policy.allowed_words.iter().map(|word| normalize(word.trim())).collect();
+    let banned: HashMap<String, ProfanitySeverity> = policy.banned_words.iter()
+        .map(|(word, severity)| (word.split_whitespace().map(normalize).collect::<Vec<_>>().join(" "), *severity))
+        .filter(|(word, _)| !word.is_empty())
+        .collect();
+    let longest_phrase = banned.keys().map(|phrase| phrase.split(' ').count()).max().unwrap_or(1).min(MAX_PHRASE_WORDS);
+    
+    let words: Vec<(usize, &str, String)> = text.unicode_word_indices().map(|(i, word)| (i, word, normalize(word))).collect();
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let mut matched = None;
+        // Longest phrase first so "son of a bitch" wins over "bitch"
+        for len in (1..=longest_phrase.min(words.len() - i)).rev() {
+            let phrase = words[i..i + len].iter().map(|(_, _, word)| word.as_str()).collect::<Vec<_>>().join(" ");
+            if allowed.contains(&phrase) {
+                break;
+            }
+            let squeezed = if len == 1 { squeeze(&phrase) } else { None };
+            let found = [Some(&phrase), squeezed.as_ref()].into_iter().flatten().find_map(|term| {
+                let severity = banned.get(term).copied().or_else(|| if len == 1 { builtin_severity(language, term) } else { None })?;
+                Some((term.clone(), severity))
+            });
+            if let Some((term, severity)) = found {
+                matched = Some((len, term, severity));
+                break;
+            }
+        }
+        
+        match matched {
+            Some((len, term, severity)) => {
+                let start = words[i].0;
+                let (last_start, last_word, _) = &words[i + len - 1];
+                let end = last_start + last_word.len();
+                matches.push(ProfanityMatch { word: text[start..end].to_string(), term, severity, start, end });
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    
+    let severity = matches.iter().map(|m| m.severity).max();
+    let action = match severity {
+        Some(s) if s >= policy.delete_at.unwrap_or(DEFAULT_DELETE_AT) => ModerationAction::DELETE,
+        Some(s) if s >= policy.warn_at.unwrap_or(DEFAULT_WARN_AT) => ModerationAction::WARN,
+        _ => ModerationAction::ALLOW,
+    };
+    ModerationResult { action, severity, matches }
+}
+
+// Overrides per chat, keyed by tenant scope and chat id. With a path, every
+// change rewrites the file through a temporary one so a crash never leaves
+// it half written.
+#[derive(Debug, Default)]
+struct PolicyStore {
+    policies: BTreeMap<String, ChatModerationPolicy>,
+    path: Option<PathBuf>,
+}
+
+impl PolicyStore {
+    fn open(path: PathBuf) -> Result<Self, String> {
+        let policies = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| format!("Corrupt moderation store {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Cannot read moderation store {}: {}", path.display(), e)),
+        };
+        Ok(Self { policies, path: Some(path) })
+    }
+    
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        paths::write_atomically(path, &serde_json::to_vec_pretty(&self.policies).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("Cannot write moderation store {}: {}", path.display(), e))
+    }
+}
+
+fn chat_key(chat_id: i64) -> String {
+    tenant::scope_key(&chat_id.to_string())
+}
+
+// Relative paths resolve under the data directory
+pub fn open_store(path: &str) -> Result<usize, String> {
+    let resolved = paths::resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    let store = PolicyStore::open(resolved)?;
+    let count = store.policies.len();
+    *POLICIES.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = store;
+    Ok(count)
+}
+
+pub fn set_chat_policy(chat_id: i64, policy: ChatModerationPolicy) -> Result<(), String> {
+    let mut store = POLICIES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let key = chat_key(chat_id);
+    let previous = store.policies.insert(key.clone(), policy);
+    let saved = store.save();
+    if saved.is_err() {
+        // Keep memory and file in agreement
+        match previous {
+            Some(previous) => store.policies.insert(key, previous),
+            None => store.policies.remove(&key),
+        };
+    }
+    saved
+}
+
+pub fn clear_chat_policy(chat_id: i64) -> Result<(), String> {
+    let mut store = POLICIES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if store.policies.remove(&chat_key(chat_id)).is_none() {
+        return Ok(());
+    }
+    store.save()
+}
+
+pub fn chat_policy(chat_id: i64) -> ChatModerationPolicy {
+    POLICIES.read().unwrap_or_else(|poisoned| poisoned.into_inner()).policies.get(&chat_key(chat_id)).cloned().unwrap_or_default()
+}
+
+pub fn check(chat_id: i64, text: &str) -> ModerationResult {
+    check_with_policy(text, &chat_policy(chat_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_severity_and_thresholds() {
+        let default = ChatModerationPolicy::default();
+        assert_eq!(check_with_policy("Well damn, that was close", &default).action, ModerationAction::ALLOW);
+        assert_eq!(check_with_policy("This is SHIIIT", &default).action, ModerationAction::WARN);
+        let result = check_with_policy("what the fuck, sh1t happens", &default);
+        assert_eq!(result.action, ModerationAction::DELETE);
+        assert_eq!(result.severity, Some(SEVERE));
+        assert_eq!(result.matches[1].word, "sh1t");
+        assert_eq!(result.matches[1].term, "shit");
+        assert_eq!(check_with_policy("پسره‌ی کثافت", &default).severity, Some(MODERATE));
+        // "as" and "ass" style collisions stay apart
+        assert!(check_with_policy("as soon as possible", &default).matches.is_empty());
+        
+        let strict = ChatModerationPolicy {
+            banned_words: BTreeMap::from([("crypto pump".to_string(), MODERATE), ("scam".to_string(), SEVERE)]),
+            allowed_words: BTreeSet::from(["hell".to_string()]),
+            warn_at: Some(MILD),
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(check_with_policy("damn", &strict).action, ModerationAction::WARN);
+        assert!(check_with_policy("Oh hell, the build broke", &strict).matches.is_empty());
+        let result = check_with_policy("Join our Crypto  Pump, no scam", &strict);
+        assert_eq!(result.matches.iter().map(|m| m.word.as_str()).collect::<Vec<_>>(), vec!["Crypto  Pump", "scam"]);
+        assert_eq!(result.action, ModerationAction::DELETE);
+    }
+    
+    #[test]
+    fn test_policies_persist_per_chat() {
+        let path = std::env::temp_dir().join(format!("aiogram_moderation_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut store = PolicyStore::open(path.clone()).unwrap();
+        let policy = ChatModerationPolicy { delete_at: Some(MODERATE), ..Default::default() };
+        store.policies.insert("42".to_string(), policy.clone());
+        store.save().unwrap();
+        
+        let reopened = PolicyStore::open(path.clone()).unwrap();
+        assert_eq!(reopened.policies.get("42"), Some(&policy));
+        let _ = fs::remove_file(&path);
+    }
+}
\ No newline at end of file
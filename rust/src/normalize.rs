@@ -0,0 +1,169 @@
+use serde::{Serialize, Deserialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+// Text cleanup before analysis. Persian text arrives typed on Arabic
+// keyboards as often as Persian ones, so the same word can be spelled with
+// different code points (ي / ی, ك / ک), with or without a zero-width
+// non-joiner, with harakat or with Persian, Arabic-Indic or ASCII digits;
+// unnormalized, each spelling counts as a different word.
+
+const ZWNJ: char = '\u{200C}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnicodeForm {
+    NONE,
+    NFC,
+    // Also folds compatibility forms: Arabic presentation forms, ligatures,
+    // full-width Latin
+    NFKC,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ZwnjHandling {
+    KEEP,
+    // Drops stray and repeated non-joiners and other invisible separators,
+    // keeping those inside words ("می‌روم")
+    CLEAN,
+    // Joins the parts ("میروم")
+    REMOVE,
+    // Splits the parts ("می روم")
+    SPACE,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextNormalizer {
+    pub unicode_form: UnicodeForm,
+    // Arabic yeh and kaf to their Persian forms
+    pub unify_arabic: bool,
+    pub zwnj: ZwnjHandling,
+    // Harakat, tatweel and Latin accents
+    pub strip_diacritics: bool,
+    // Persian and Arabic-Indic digits to ASCII
+    pub normalize_digits: bool,
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self {
+            unicode_form: UnicodeForm::NFKC,
+            unify_arabic: true,
+            zwnj: ZwnjHandling::CLEAN,
+            strip_diacritics: false,
+            normalize_digits: true,
+        }
+    }
+}
+
+pub(crate) fn unify_arabic(c: char) -> char {
+    match c {
+        'ي' | 'ى' => 'ی',
+        'ك' => 'ک',
+        c => c,
+    }
+}
+
+fn ascii_digit(c: char) -> char {
+    match c {
+        '۰'..='۹' => char::from(b'0' + (c as u32 - '۰' as u32) as u8),
+        '٠'..='٩' => char::from(b'0' + (c as u32 - '٠' as u32) as u8),
+        c => c,
+    }
+}
+
+// Zero-width space, word joiner and byte order mark carry nothing for
+// analysis; ZWJ is left alone since emoji sequences need it
+fn is_invisible_separator(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}')
+}
+
+// A non-joiner only means something between two letters
+fn clean_zwnj(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut cleaned = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != ZWNJ {
+            cleaned.push(c);
+            continue;
+        }
+        let follows_letter = cleaned.chars().next_back().is_some_and(char::is_alphabetic);
+        let precedes_letter = chars[i + 1..].iter().find(|&&next| next != ZWNJ).is_some_and(|next| next.is_alphabetic());
+        if follows_letter && precedes_letter && chars.get(i + 1) != Some(&ZWNJ) {
+            cleaned.push(c);
+        }
+    }
+    cleaned
+}
+
+impl TextNormalizer {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid normalizer options: {}", e))
+    }
+    
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text: String = match self.unicode_form {
+            UnicodeForm::NONE => text.to_string(),
+            UnicodeForm::NFC => text.nfc().collect(),
+            UnicodeForm::NFKC => text.nfkc().collect(),
+        };
+        
+        if self.strip_diacritics {
+            // Decomposed first so accented Latin letters lose only the accent
+            text = text.nfd()
+                .filter(|&c| !is_combining_mark(c) && c != '\u{0640}')
+                .nfc()
+                .collect();
+        }
+        if self.unify_arabic || self.normalize_digits {
+            text = text.chars()
+                .map(|c| if self.unify_arabic { unify_arabic(c) } else { c })
+                .map(|c| if self.normalize_digits { ascii_digit(c) } else { c })
+                .collect();
+        }
+        
+        match self.zwnj {
+            ZwnjHandling::KEEP => text,
+            ZwnjHandling::CLEAN => clean_zwnj(&text.replace(is_invisible_separator, "")),
+            ZwnjHandling::REMOVE => text.replace(|c| c == ZWNJ || is_invisible_separator(c), ""),
+            ZwnjHandling::SPACE => text.replace(is_invisible_separator, "").replace(ZWNJ, " "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_persian_spellings_converge() {
+        let normalizer = TextNormalizer::default();
+        // Arabic yeh and kaf, a doubled and a trailing non-joiner
+        let arabic = normalizer.normalize("كتاب مي\u{200C}\u{200C}خواني\u{200C} ۱۲۳ ٤٥");
+        let persian = normalizer.normalize("کتاب می\u{200C}خوانی 123 45");
+        assert_eq!(arabic, persian);
+        
+        // Presentation form of lam-alef under NFKC
+        assert_eq!(normalizer.normalize("\u{FEFB}"), "لا");
+        assert_eq!(normalizer.normalize("a\u{200B}b\u{FEFF}"), "ab");
+    }
+    
+    #[test]
+    fn test_options() {
+        let stripping = TextNormalizer { strip_diacritics: true, ..Default::default() };
+        assert_eq!(stripping.normalize("کِتابـــ café"), "کتاب cafe");
+        
+        let remove = TextNormalizer { zwnj: ZwnjHandling::REMOVE, ..Default::default() };
+        assert_eq!(remove.normalize("می\u{200C}روم"), "میروم");
+        let space = TextNormalizer { zwnj: ZwnjHandling::SPACE, ..Default::default() };
+        assert_eq!(space.normalize("می\u{200C}روم"), "می روم");
+        
+        let untouched = TextNormalizer { unicode_form: UnicodeForm::NONE, unify_arabic: false, zwnj: ZwnjHandling::KEEP, strip_diacritics: false, normalize_digits: false };
+        assert_eq!(untouched.normalize("كي ۱\u{200C}"), "كي ۱\u{200C}");
+        assert!(TextNormalizer::from_json(r#"{"unicode_form": "NFC", "normalize_digits": false}"#).is_ok());
+        assert!(TextNormalizer::from_json(r#"{"lowercase": true}"#).is_err());
+    }
+}
\ No newline at end of file
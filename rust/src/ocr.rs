@@ -0,0 +1,210 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use crate::{cache, deadline, logging};
+
+// Text in images, so photos, screenshots and meme captions can go through
+// the same analysis and moderation as messages. The library does no OCR of
+// its own: a provider registered by the host (a callback, a Rust closure,
+// or with the ocr feature an external program such as tesseract) reads the
+// image. Results are cached by the BLAKE3 hash of the image bytes, so a
+// photo forwarded to many chats is read once; registering another provider
+// clears the cache.
+
+pub trait OcrProvider: Send + Sync {
+    // The image as Telegram delivers it (JPEG, PNG or WebP)
+    fn recognize(&self, image: &[u8]) -> Result<String, String>;
+}
+
+impl<F> OcrProvider for F
+where
+    F: Fn(&[u8]) -> Result<String, String> + Send + Sync,
+{
+    fn recognize(&self, image: &[u8]) -> Result<String, String> {
+        self(image)
+    }
+}
+
+// Host provider: receives the image bytes and returns its text, or NULL
+// when it cannot read it. The library copies the text and then passes it
+// to the release callback, if one was registered.
+pub type OcrCallback = extern "C" fn(image: *const u8, len: usize) -> *const c_char;
+pub type OcrReleaseCallback = extern "C" fn(text: *const c_char);
+
+struct HostOcr {
+    recognize: OcrCallback,
+    release: Option<OcrReleaseCallback>,
+}
+
+impl OcrProvider for HostOcr {
+    fn recognize(&self, image: &[u8]) -> Result<String, String> {
+        let text = (self.recognize)(image.as_ptr(), image.len());
+        if text.is_null() {
+            return Err("The host OCR provider could not read the image".to_string());
+        }
+        let copied = unsafe { CStr::from_ptr(text) }.to_str().map(str::to_string)
+            .map_err(|_| "The host OCR provider returned invalid UTF-8".to_string());
+        if let Some(release) = self.release {
+            release(text);
+        }
+        copied
+    }
+}
+
+// Runs a program per image with the image on stdin and reads the text from
+// its stdout, e.g. CommandOcr::tesseract("eng+fas")
+#[cfg(feature = "ocr")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOcr {
+    program: String,
+    args: Vec<String>,
+    timeout_ms: u64,
+}
+
+#[cfg(feature = "ocr")]
+impl CommandOcr {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self { program: program.to_string(), args: args.iter().map(|arg| arg.to_string()).collect(), timeout_ms: 30_000 }
+    }
+    
+    // Tesseract with its language packs joined by "+", e.g. "eng+fas"
+    pub fn tesseract(languages: &str) -> Self {
+        Self::new("tesseract", &["stdin", "stdout", "-l", languages])
+    }
+    
+    // The program is killed past this, or past the call's deadline if sooner
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+}
+
+#[cfg(feature = "ocr")]
+impl OcrProvider for CommandOcr {
+    fn recognize(&self, image: &[u8]) -> Result<String, String> {
+        use std::io::{Read, Write};
+        use std::process::{Command, Stdio};
+        use std::time::{Duration, Instant};
+        
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Cannot run {}: {}", self.program, e))?;
+        // Written and read on their own threads, so neither pipe can fill
+        // up while the other is waited on
+        let mut stdin = child.stdin.take().ok_or("No stdin for the OCR program")?;
+        let input = image.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let mut stdout = child.stdout.take().ok_or("No stdout for the OCR program")?;
+        let reader = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+        
+        let timeout = deadline::current().map_or(self.timeout_ms, |call| call.remaining_millis().min(self.timeout_ms));
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if started.elapsed() >= Duration::from_millis(timeout) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} did not finish within {} ms", self.program, timeout));
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(format!("Cannot wait for {}: {}", self.program, e)),
+            }
+        };
+        // A program that exits without reading its input breaks the pipe,
+        // which only matters if it also failed
+        let _ = writer.join();
+        let output = match reader.join() {
+            Ok(Ok(output)) => output,
+            _ => return Err(format!("Cannot read the output of {}", self.program)),
+        };
+        if !status.success() {
+            return Err(format!("{} failed with {}", self.program, status));
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+lazy_static! {
+    static ref PROVIDER: RwLock<Option<Arc<dyn OcrProvider>>> = RwLock::new(None);
+}
+
+// None unregisters the provider
+pub fn set_provider(provider: Option<Arc<dyn OcrProvider>>) {
+    *PROVIDER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = provider;
+    cache::clear_cached_ocr();
+}
+
+pub fn set_host_provider(recognize: OcrCallback, release: Option<OcrReleaseCallback>) {
+    set_provider(Some(Arc::new(HostOcr { recognize, release })));
+}
+
+// The image's text with surrounding whitespace trimmed; "" for an image
+// without text. Failures are not cached, so a retry asks the provider again.
+pub fn recognize(image: &[u8]) -> Result<String, String> {
+    if image.is_empty() {
+        return Err("Image is empty".to_string());
+    }
+    let key = cache::fast_hash(image);
+    if let Some(text) = cache::get_cached_ocr(&key) {
+        return Ok(text);
+    }
+    let provider = PROVIDER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+        .ok_or("No OCR provider is registered")?;
+    deadline::check("recognizing text in an image")?;
+    
+    let text = match panic::catch_unwind(AssertUnwindSafe(|| provider.recognize(image))) {
+        Ok(result) => result?.trim().to_string(),
+        Err(payload) => {
+            let message = crate::lifecycle::panic_message(payload.as_ref());
+            logging::warn(&format!("OCR provider panicked: {}", message), "ocr", "recognize", line!());
+            return Err(format!("OCR provider panicked: {}", message));
+        }
+    };
+    cache::set_cached_ocr(&key, text.clone());
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    
+    #[test]
+    fn test_provider_results_are_cached_by_image() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        set_provider(Some(Arc::new(|image: &[u8]| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            match image {
+                b"broken" => Err("unreadable".to_string()),
+                _ => Ok(format!("  text of {} bytes\n", image.len())),
+            }
+        })));
+        assert_eq!(recognize(b"ocr test image").as_deref(), Ok("text of 14 bytes"));
+        assert_eq!(recognize(b"ocr test image").as_deref(), Ok("text of 14 bytes"));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert!(recognize(b"broken").is_err());
+        assert!(recognize(b"").is_err());
+        
+        set_provider(None);
+        assert!(recognize(b"another ocr test image").is_err());
+    }
+    
+    #[cfg(all(feature = "ocr", unix))]
+    #[test]
+    fn test_command_provider() {
+        assert_eq!(CommandOcr::new("cat", &[]).recognize(b"caption"), Ok("caption".to_string()));
+        assert!(CommandOcr::new("false", &[]).recognize(b"caption").is_err());
+        assert!(CommandOcr::new("sleep", &["5"]).with_timeout_ms(50).recognize(b"").is_err());
+        assert!(CommandOcr::new("aiogram-no-such-program", &[]).recognize(b"caption").is_err());
+    }
+}
\ No newline at end of file
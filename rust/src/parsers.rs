@@ -0,0 +1,139 @@
+use std::fmt;
+
+// Hand-rolled parsers shared by the validation, analysis and realtime paths.
+// Every one of them sees untrusted chat input, so they are bounded in depth and
+// token size and must never panic; see fuzz.rs for the invariants they keep.
+
+// Deeper documents are rejected before any recursive parser sees them
+// (serde_json gives up at 128 on its own)
+pub(crate) const MAX_JSON_DEPTH: usize = 64;
+
+// Longest token the numeric splitter will hand to f64::from_str
+pub(crate) const MAX_NUMBER_TOKEN_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonStructureError {
+    Unbalanced,
+    TooDeep,
+}
+
+impl JsonStructureError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            JsonStructureError::Unbalanced => "UNBALANCED_JSON",
+            JsonStructureError::TooDeep => "JSON_TOO_DEEP",
+        }
+    }
+}
+
+impl fmt::Display for JsonStructureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonStructureError::Unbalanced => write!(f, "Unbalanced JSON structure"),
+            JsonStructureError::TooDeep => write!(f, "JSON nesting exceeds {} levels", MAX_JSON_DEPTH),
+        }
+    }
+}
+
+// Checks that braces and brackets pair up in order and stay within
+// MAX_JSON_DEPTH, ignoring anything inside string literals. Returns the
+// deepest nesting seen. This is a cheap pre-check, not a JSON parser.
+pub fn check_json_structure(input: &str) -> Result<usize, JsonStructureError> {
+    let mut stack = Vec::new();
+    let mut max_depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    
+    for ch in input.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        
+        match ch {
+            '"' => in_string = true,
+            '{' | '[' => {
+                stack.push(ch);
+                if stack.len() > MAX_JSON_DEPTH {
+                    return Err(JsonStructureError::TooDeep);
+                }
+                max_depth = max_depth.max(stack.len());
+            }
+            '}' => {
+                if stack.pop() != Some('{') {
+                    return Err(JsonStructureError::Unbalanced);
+                }
+            }
+            ']' => {
+                if stack.pop() != Some('[') {
+                    return Err(JsonStructureError::Unbalanced);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    if !stack.is_empty() || in_string {
+        return Err(JsonStructureError::Unbalanced);
+    }
+    
+    Ok(max_depth)
+}
+
+fn is_number_separator(c: char) -> bool {
+    c == ',' || c == ' ' || c == '\n' || c == '\t' || c == '\r'
+}
+
+// Raw tokens of a comma/whitespace separated value list, empty ones dropped
+pub fn number_tokens(input: &str) -> impl Iterator<Item = &str> {
+    input.split(is_number_separator).filter(|s| !s.is_empty())
+}
+
+// Finite values only: NaN and infinities would poison every statistic
+// downstream, and oversized tokens are skipped without being parsed
+pub fn parse_number(token: &str) -> Option<f64> {
+    let token = token.trim();
+    if token.is_empty() || token.len() > MAX_NUMBER_TOKEN_LEN {
+        return None;
+    }
+    
+    token.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+pub fn parse_numbers(input: &str) -> Vec<f64> {
+    number_tokens(input).filter_map(parse_number).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_json_structure() {
+        assert_eq!(check_json_structure(r#"{"a": [1, {"b": "}]"}]}"#), Ok(3));
+        assert_eq!(check_json_structure(r#"{"a": "\"{"}"#), Ok(1));
+        assert_eq!(check_json_structure("[}"), Err(JsonStructureError::Unbalanced));
+        assert_eq!(check_json_structure(r#"{"a": "open}"#), Err(JsonStructureError::Unbalanced));
+        
+        let deep = "[".repeat(100_000) + &"]".repeat(100_000);
+        assert_eq!(check_json_structure(&deep), Err(JsonStructureError::TooDeep));
+        let limit = "[".repeat(MAX_JSON_DEPTH) + &"]".repeat(MAX_JSON_DEPTH);
+        assert_eq!(check_json_structure(&limit), Ok(MAX_JSON_DEPTH));
+    }
+    
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse_numbers("1, 2.5\t-3\r\n4e2"), vec![1.0, 2.5, -3.0, 400.0]);
+        assert_eq!(parse_numbers("NaN, inf, -infinity, 1e999, 7"), vec![7.0]);
+        
+        let huge = "9".repeat(1_000_000);
+        assert!(parse_numbers(&format!("{},1", huge)).eq(&[1.0]));
+        assert_eq!(number_tokens(",, ,a,").count(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::config::AppConfig;
+use crate::deadline;
+
+const APP_DIR_NAME: &str = "aiogram";
+const BASE_DIR_ENV: &str = "AIOGRAM_BASE_DIR";
+const CONFIG_FILE_NAME: &str = "config.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathKind {
+    CONFIG,
+    DATA,
+    LOGS,
+}
+
+impl PathKind {
+    fn subdir(&self) -> &'static str {
+        match self {
+            PathKind::CONFIG => "config",
+            PathKind::DATA => "data",
+            PathKind::LOGS => "logs",
+        }
+    }
+}
+
+// Directory for the given kind of file. AIOGRAM_BASE_DIR, then the
+// configured base dir, replace the platform defaults with
+// <base>/{config,data,logs}.
+pub fn app_dir(kind: PathKind, base_dir: Option<&str>) -> PathBuf {
+    let env_base = std::env::var(BASE_DIR_ENV).ok().filter(|dir| !dir.trim().is_empty());
+    match env_base.as_deref().or(base_dir) {
+        Some(base) => normalize(&expand_home(base)).join(kind.subdir()),
+        None => platform_dir(kind),
+    }
+}
+
+// Absolute paths (after ~ expansion) are kept; relative ones are placed under
+// the app directory for their kind
+pub fn resolve(path: &str, kind: PathKind, base_dir: Option<&str>) -> PathBuf {
+    let expanded = expand_home(path);
+    if expanded.is_absolute() {
+        normalize(&expanded)
+    } else {
+        normalize(&app_dir(kind, base_dir).join(expanded))
+    }
+}
+
+pub fn default_config_file() -> PathBuf {
+    app_dir(PathKind::CONFIG, None).join(CONFIG_FILE_NAME)
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => rest,
+        _ => return PathBuf::from(path),
+    };
+    
+    match home_dir() {
+        Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(path),
+    }
+}
+
+// Lexically removes `.` and `..` components without touching the filesystem,
+// so paths to files that do not exist yet normalize too
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                let can_pop = matches!(normalized.components().next_back(), Some(Component::Normal(_)));
+                if can_pop {
+                    normalized.pop();
+                } else if !normalized.has_root() {
+                    normalized.push("..");
+                }
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    
+    if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        normalized
+    }
+}
+
+// Writes through a temporary file next to path so a crash never leaves it
+// half written
+pub fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+// Saves a data file (relative paths resolve under the data directory);
+// what names it in errors, e.g. "topic model"
+pub fn atomic_write(path: &str, what: &str, contents: &[u8]) -> Result<(), String> {
+    let path = resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    deadline::check(&format!("writing the {}", what))?;
+    write_atomically(&path, contents).map_err(|e| format!("Cannot write {} {}: {}", what, path.display(), e))
+}
+
+pub fn atomic_write_json<T: Serialize>(path: &str, what: &str, value: &T) -> Result<(), String> {
+    let json = serde_json::to_vec(value).map_err(|e| format!("Cannot serialize {}: {}", what, e))?;
+    atomic_write(path, what, &json)
+}
+
+// Reads a data file written by atomic_write
+pub fn read_data(path: &str, what: &str) -> Result<Vec<u8>, String> {
+    let path = resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    deadline::check(&format!("reading the {}", what))?;
+    fs::read(&path).map_err(|e| format!("Cannot read {} {}: {}", what, path.display(), e))
+}
+
+// Reads a file written by atomic_write_json whose format_version, taken by
+// version_of, must be between 1 and supported
+pub fn read_versioned_json<T: DeserializeOwned>(path: &str, what: &str, supported: u32, version_of: impl Fn(&T) -> u32) -> Result<T, String> {
+    let contents = read_data(path, what)?;
+    let value: T = serde_json::from_slice(&contents).map_err(|e| format!("Invalid {} file {}: {}", what, path, e))?;
+    let version = version_of(&value);
+    if version == 0 || version > supported {
+        return Err(format!("Unsupported {} format version {}", what, version));
+    }
+    Ok(value)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    env_dir(var)
+}
+
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn platform_dir(kind: PathKind) -> PathBuf {
+    // Roaming %APPDATA% for config; machine-local %LOCALAPPDATA% for the rest
+    let root = match kind {
+        PathKind::CONFIG => env_dir("APPDATA"),
+        PathKind::DATA | PathKind::LOGS => env_dir("LOCALAPPDATA").or_else(|| env_dir("APPDATA")),
+    };
+    let app = root.unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR_NAME);
+    match kind {
+        PathKind::LOGS => app.join("logs"),
+        _ => app,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn platform_dir(kind: PathKind) -> PathBuf {
+    let home = home_dir().unwrap_or_else(|| PathBuf::from("."));
+    match kind {
+        PathKind::LOGS => home.join("Library/Logs").join(APP_DIR_NAME),
+        _ => home.join("Library/Application Support").join(APP_DIR_NAME),
+    }
+}
+
+// XDG base directories on Linux (glibc and musl alike) and other unixes
+#[cfg(not(any(windows, target_os = "macos")))]
+fn platform_dir(kind: PathKind) -> PathBuf {
+    let (var, fallback) = match kind {
+        PathKind::CONFIG => ("XDG_CONFIG_HOME", ".config"),
+        PathKind::DATA => ("XDG_DATA_HOME", ".local/share"),
+        PathKind::LOGS => ("XDG_STATE_HOME", ".local/state"),
+    };
+    
+    // The spec says relative values are invalid and must be ignored
+    let root = env_dir(var)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(fallback)))
+        .unwrap_or_else(|| PathBuf::from("."));
+    
+    match kind {
+        PathKind::LOGS => root.join(APP_DIR_NAME).join("logs"),
+        _ => root.join(APP_DIR_NAME),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("/srv/bot/./logs/../data")), PathBuf::from("/srv/bot/data"));
+        assert_eq!(normalize(Path::new("../a/./b/..")), PathBuf::from("../a"));
+        assert_eq!(normalize(Path::new("/..")), PathBuf::from("/"));
+        assert_eq!(normalize(Path::new("./")), PathBuf::from("."));
+    }
+    
+    #[test]
+    fn test_resolve_against_base_dir() {
+        if std::env::var_os(BASE_DIR_ENV).is_some() {
+            return;
+        }
+        
+        assert_eq!(resolve("bot.log", PathKind::LOGS, Some("/srv/bot")), PathBuf::from("/srv/bot/logs/bot.log"));
+        assert_eq!(resolve("../shared/state.bin", PathKind::DATA, Some("/srv/bot/")), PathBuf::from("/srv/bot/shared/state.bin"));
+        assert_eq!(resolve("/var/log/bot.log", PathKind::LOGS, Some("/srv/bot")), PathBuf::from("/var/log/bot.log"));
+        assert!(app_dir(PathKind::CONFIG, None).ends_with(APP_DIR_NAME));
+    }
+    
+    #[test]
+    fn test_versioned_json_round_trip() {
+        #[derive(Serialize, serde::Deserialize)]
+        struct Saved {
+            format_version: u32,
+            items: Vec<String>,
+        }
+        
+        let path = std::env::temp_dir().join(format!("aiogram_versioned_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        atomic_write_json(path, "test file", &Saved { format_version: 2, items: vec!["a".to_string()] }).unwrap();
+        let saved: Saved = read_versioned_json(path, "test file", 2, |saved: &Saved| saved.format_version).unwrap();
+        assert_eq!(saved.items, ["a"]);
+        let newer = read_versioned_json(path, "test file", 1, |saved: &Saved| saved.format_version);
+        assert_eq!(newer.err().unwrap(), "Unsupported test file format version 2");
+        assert!(!Path::new(path).with_extension("tmp").exists());
+        fs::remove_file(path).unwrap();
+    }
+}
\ No newline at end of file
@@ -3,6 +3,7 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use lazy_static::lazy_static;
+#[cfg(feature = "realtime")]
 use rayon::prelude::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -246,15 +247,16 @@ impl PerformanceOptimizer {
             .collect();
         
         // Use parallel processing for large datasets
+        #[cfg(feature = "realtime")]
         if numbers.len() > 1000 {
-            let processed_numbers: Vec<String> = numbers
+            let processed_numbers: Vec<String> = crate::pool::install(|| numbers
                 .par_iter()
                 .map(|num| num.trim().to_string())
-                .collect();
-            processed_numbers.join(",")
-        } else {
-            numbers.iter().map(|s| s.trim()).collect::<Vec<_>>().join(",")
+                .collect());
+            return processed_numbers.join(",");
         }
+        
+        numbers.iter().map(|s| s.trim()).collect::<Vec<_>>().join(",")
     }
     
     pub fn get_performance_summary(&self) -> HashMap<String, f64> {
@@ -298,6 +300,11 @@ lazy_static! {
     static ref PERFORMANCE_OPTIMIZER: Arc<PerformanceOptimizer> = Arc::new(PerformanceOptimizer::new());
 }
 
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&PERFORMANCE_OPTIMIZER);
+    Ok(())
+}
+
 // Public performance functions
 pub fn record_operation_performance(operation_name: &str, duration_ms: u64, memory_mb: f64, cpu_percent: f64, cache_hit: bool, success: bool) {
     PERFORMANCE_OPTIMIZER.record_operation(operation_name, duration_ms, memory_mb, cpu_percent, cache_hit, success);
@@ -369,8 +376,11 @@ macro_rules! measure_performance_with_cache {
         let result = $block;
         let duration = start_time.elapsed().as_millis() as u64;
         
-        // Cache the result
-        $crate::cache::set_cached_result($cache_key, result.clone());
+        // Cache the result, unless the call ran out of time and it may be
+        // partial
+        if !$crate::deadline::expired() {
+            $crate::cache::set_cached_result($cache_key, result.clone());
+        }
         
         $crate::performance::record_operation_performance(
             $operation_name,
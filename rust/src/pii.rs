@@ -0,0 +1,270 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Personal data in message text: e-mail addresses, phone numbers, IBANs,
+// national IDs and payment card numbers. Number formats with a check digit
+// are validated, so order numbers and timestamps of the right length are
+// left alone: cards by Luhn, IBANs by ISO 7064 mod 97 and Iranian national
+// codes by their own check digit. Persian and Arabic-Indic digits count like
+// ASCII ones.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Iban,
+    // US social security numbers and Iranian national codes (code melli)
+    NationalId,
+    CreditCard,
+}
+
+impl PiiKind {
+    fn label(self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Iban => "IBAN",
+            PiiKind::NationalId => "NATIONAL_ID",
+            PiiKind::CreditCard => "CREDIT_CARD",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RedactionStyle {
+    // Keeps enough to recognize the value: "j***@example.com",
+    // "**** **** **** 1111"
+    MASK,
+    // "[REDACTED:EMAIL]"; the log scrubber redacts with this style
+    LABEL,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiMatch {
+    pub kind: PiiKind,
+    pub text: String,
+    // Byte offsets in the searched text
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PiiRedactor {
+    // Kinds to look for; where matches overlap, the earlier kind in this
+    // list wins
+    pub kinds: Vec<PiiKind>,
+    pub style: RedactionStyle,
+    pub mask_char: char,
+}
+
+impl Default for PiiRedactor {
+    fn default() -> Self {
+        Self {
+            kinds: vec![PiiKind::Email, PiiKind::Iban, PiiKind::CreditCard, PiiKind::NationalId, PiiKind::Phone],
+            style: RedactionStyle::MASK,
+            mask_char: '*',
+        }
+    }
+}
+
+lazy_static! {
+    static ref EMAIL_PATTERN: Regex = Regex::new(r"\b[\w.%+-]+@[\w-]+(?:\.[\w-]+)*\.[A-Za-z]{2,}\b").unwrap();
+    // North American numbers, with or without a country code, and Iranian
+    // mobiles such as 0912 345 6789 or +98 912 345 6789
+    static ref PHONE_PATTERN: Regex = Regex::new(
+        r"(?:\+\d{1,3}[\s.-]?)?\(?\b\d{3}\)?[\s.-]?\d{3}[\s.-]?\d{4}\b|(?:\+98[\s-]?|\b0)9\d{2}[\s.-]?\d{3}[\s.-]?\d{4}\b"
+    ).unwrap();
+    // Country code, check digits, then groups of four, spaced or not
+    static ref IBAN_PATTERN: Regex = Regex::new(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,4})?\b").unwrap();
+    static ref SSN_PATTERN: Regex = Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap();
+    static ref NATIONAL_CODE_PATTERN: Regex = Regex::new(r"\b\d{3}-?\d{6}-?\d\b").unwrap();
+    static ref CARD_PATTERN: Regex = Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&EMAIL_PATTERN);
+    lazy_static::initialize(&PHONE_PATTERN);
+    lazy_static::initialize(&IBAN_PATTERN);
+    lazy_static::initialize(&SSN_PATTERN);
+    lazy_static::initialize(&NATIONAL_CODE_PATTERN);
+    lazy_static::initialize(&CARD_PATTERN);
+    Ok(())
+}
+
+fn digit(c: char) -> Option<u32> {
+    match c {
+        '۰'..='۹' => Some(c as u32 - '۰' as u32),
+        '٠'..='٩' => Some(c as u32 - '٠' as u32),
+        c => c.to_digit(10),
+    }
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(digit).collect()
+}
+
+fn luhn_valid(text: &str) -> bool {
+    let sum: u32 = digits(text).iter().rev().enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn iban_valid(text: &str) -> bool {
+    let compact: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    // The first four characters move to the end and letters count as 10-35
+    let mut remainder = 0u32;
+    for c in compact[4..].iter().chain(&compact[..4]) {
+        remainder = match c.to_digit(36) {
+            Some(value) if value < 10 => (remainder * 10 + value) % 97,
+            Some(value) => (remainder * 100 + value) % 97,
+            None => return false,
+        };
+    }
+    remainder == 1
+}
+
+fn ssn_valid(text: &str) -> bool {
+    let d = digits(text);
+    let area = d[0] * 100 + d[1] * 10 + d[2];
+    area != 0 && area != 666 && area < 900 && d[3..5] != [0, 0] && d[5..] != [0, 0, 0, 0]
+}
+
+fn national_code_valid(text: &str) -> bool {
+    let d = digits(text);
+    if d.len() != 10 || d.iter().all(|&x| x == d[0]) {
+        return false;
+    }
+    let remainder = d[..9].iter().enumerate().map(|(i, &x)| x * (10 - i as u32)).sum::<u32>() % 11;
+    d[9] == if remainder < 2 { remainder } else { 11 - remainder }
+}
+
+type Validator = fn(&str) -> bool;
+
+// The candidates of a kind, with the validator their digits must pass
+fn candidates(kind: PiiKind) -> Vec<(&'static Regex, Validator)> {
+    let any: Validator = |_| true;
+    match kind {
+        PiiKind::Email => vec![(&EMAIL_PATTERN, any)],
+        PiiKind::Phone => vec![(&PHONE_PATTERN, any)],
+        PiiKind::Iban => vec![(&IBAN_PATTERN, iban_valid)],
+        PiiKind::NationalId => vec![(&SSN_PATTERN, ssn_valid), (&NATIONAL_CODE_PATTERN, national_code_valid)],
+        PiiKind::CreditCard => vec![(&CARD_PATTERN, luhn_valid)],
+    }
+}
+
+impl PiiRedactor {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid PII options: {}", e))
+    }
+    
+    pub fn with_kinds(mut self, kinds: &[PiiKind]) -> Self {
+        self.kinds = kinds.to_vec();
+        self
+    }
+    
+    pub fn with_style(mut self, style: RedactionStyle) -> Self {
+        self.style = style;
+        self
+    }
+    
+    // In text order
+    pub fn find(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches: Vec<PiiMatch> = Vec::new();
+        for &kind in &self.kinds {
+            for (pattern, valid) in candidates(kind) {
+                for found in pattern.find_iter(text) {
+                    let claimed = matches.iter().any(|m| m.start < found.end() && found.start() < m.end);
+                    if !claimed && valid(found.as_str()) {
+                        matches.push(PiiMatch { kind, text: found.as_str().to_string(), start: found.start(), end: found.end() });
+                    }
+                }
+            }
+        }
+        matches.sort_by_key(|m| m.start);
+        matches
+    }
+    
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for found in self.find(text) {
+            redacted.push_str(&text[last..found.start]);
+            redacted.push_str(&self.replacement(&found));
+            last = found.end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+    
+    fn replacement(&self, found: &PiiMatch) -> String {
+        if self.style == RedactionStyle::LABEL {
+            return format!("[REDACTED:{}]", found.kind.label());
+        }
+        let mask = self.mask_char;
+        match found.kind {
+            // First letter of the mailbox; the domain stays readable
+            PiiKind::Email => match found.text.split_once('@') {
+                Some((local, domain)) => format!("{}{}{}{}@{}", local.chars().next().unwrap_or(mask), mask, mask, mask, domain),
+                None => mask.to_string().repeat(3),
+            },
+            PiiKind::Iban => self.mask_except(&found.text, 2, 4),
+            PiiKind::CreditCard => self.mask_except(&found.text, 0, 4),
+            PiiKind::Phone | PiiKind::NationalId => self.mask_except(&found.text, 0, 2),
+        }
+    }
+    
+    // Masks letters and digits but the first head and last tail of them;
+    // spaces, dashes and the like keep the shape of the number
+    fn mask_except(&self, text: &str, head: usize, tail: usize) -> String {
+        let total = text.chars().filter(|c| c.is_alphanumeric()).count();
+        let mut seen = 0;
+        text.chars().map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if seen <= head || seen > total.saturating_sub(tail) { c } else { self.mask_char }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_find_validates_check_digits() {
+        let text = "Mail john.doe@example.com, card 4111 1111 1111 1111 (not 4111 1111 1111 1112), IBAN DE89 3704 0044 0532 0130 00, code ۰۰۱۲۳۴۵۶۷۹, SSN 123-45-6789, call 0912 345 6789";
+        let found: Vec<(PiiKind, &str)> = PiiRedactor::default().find(text).iter().map(|m| (m.kind, &text[m.start..m.end])).collect();
+        assert_eq!(found, vec![
+            (PiiKind::Email, "john.doe@example.com"),
+            (PiiKind::CreditCard, "4111 1111 1111 1111"),
+            (PiiKind::Iban, "DE89 3704 0044 0532 0130 00"),
+            (PiiKind::NationalId, "۰۰۱۲۳۴۵۶۷۹"),
+            (PiiKind::NationalId, "123-45-6789"),
+            (PiiKind::Phone, "0912 345 6789"),
+        ]);
+        assert!(PiiRedactor::default().find("order 1234567890123 shipped at 2024-01-15").is_empty());
+    }
+    
+    #[test]
+    fn test_redact() {
+        let text = "from john.doe@example.com: pay to 4111-1111-1111-1111 or DE89370400440532013000";
+        assert_eq!(PiiRedactor::default().redact(text), "from j***@example.com: pay to ****-****-****-1111 or DE****************3000");
+        
+        let labels = PiiRedactor::default().with_kinds(&[PiiKind::Email]).with_style(RedactionStyle::LABEL);
+        assert_eq!(labels.redact(text), "from [REDACTED:EMAIL]: pay to 4111-1111-1111-1111 or DE89370400440532013000");
+        assert!(PiiRedactor::from_json(r#"{"style": "LABEL", "mask_char": "x"}"#).is_ok());
+        assert!(PiiRedactor::from_json(r#"{"kinds": ["ADDRESS"]}"#).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,150 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+use serde_json::{Map, Value};
+use lazy_static::lazy_static;
+use crate::logging;
+
+// Custom per-message analyzers contributed by the host or by Rust callers.
+// Each runs at the end of the text pipeline and its output lands in the
+// result's "plugins" object under the analyzer's name. Registering a name
+// again swaps the analyzer in place, so hosts can reload a model without
+// restarting.
+pub trait Analyzer: Send + Sync {
+    // None contributes nothing for this message
+    fn analyze(&self, text: &str) -> Option<Value>;
+}
+
+impl<F> Analyzer for F
+where
+    F: Fn(&str) -> Option<Value> + Send + Sync,
+{
+    fn analyze(&self, text: &str) -> Option<Value> {
+        self(text)
+    }
+}
+
+// Host analyzer: receives the message text and returns a JSON document, or
+// NULL for no contribution. The library copies the result and then passes it
+// to the release callback, if one was registered.
+pub type AnalyzerCallback = extern "C" fn(text: *const c_char) -> *const c_char;
+pub type ReleaseCallback = extern "C" fn(result: *const c_char);
+
+struct HostAnalyzer {
+    analyze: AnalyzerCallback,
+    release: Option<ReleaseCallback>,
+}
+
+impl Analyzer for HostAnalyzer {
+    fn analyze(&self, text: &str) -> Option<Value> {
+        let text_c = CString::new(text).ok()?;
+        let result = (self.analyze)(text_c.as_ptr());
+        if result.is_null() {
+            return None;
+        }
+        
+        let parsed = unsafe { CStr::from_ptr(result) }.to_str().ok()
+            .and_then(|json| serde_json::from_str(json).ok());
+        if let Some(release) = self.release {
+            release(result);
+        }
+        parsed
+    }
+}
+
+lazy_static! {
+    // Kept in registration order so the output order is stable
+    static ref ANALYZERS: RwLock<Vec<(String, Arc<dyn Analyzer>)>> = RwLock::new(Vec::new());
+}
+
+pub fn register(name: &str, analyzer: Arc<dyn Analyzer>) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Analyzer name must not be empty".to_string());
+    }
+    
+    let mut analyzers = ANALYZERS.write().map_err(|_| "Analyzer registry lock poisoned".to_string())?;
+    match analyzers.iter_mut().find(|(existing, _)| existing == name) {
+        Some(entry) => entry.1 = analyzer,
+        None => analyzers.push((name.to_string(), analyzer)),
+    }
+    Ok(())
+}
+
+pub fn register_host(name: &str, analyze: AnalyzerCallback, release: Option<ReleaseCallback>) -> Result<(), String> {
+    register(name, Arc::new(HostAnalyzer { analyze, release }))
+}
+
+pub fn unregister(name: &str) -> bool {
+    match ANALYZERS.write() {
+        Ok(mut analyzers) => {
+            let before = analyzers.len();
+            analyzers.retain(|(existing, _)| existing != name);
+            analyzers.len() != before
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn registered() -> Vec<String> {
+    ANALYZERS.read().map(|analyzers| analyzers.iter().map(|(name, _)| name.clone()).collect()).unwrap_or_default()
+}
+
+// A panicking or failing analyzer is logged and left out of the result; it
+// never fails the analysis it was plugged into
+pub fn run_all(text: &str) -> Map<String, Value> {
+    // Snapshot so analyzers can (un)register others without deadlocking
+    let analyzers: Vec<(String, Arc<dyn Analyzer>)> = match ANALYZERS.read() {
+        Ok(analyzers) => analyzers.clone(),
+        Err(_) => return Map::new(),
+    };
+    
+    let mut results = Map::new();
+    for (name, analyzer) in analyzers {
+        match panic::catch_unwind(AssertUnwindSafe(|| analyzer.analyze(text))) {
+            Ok(Some(value)) => {
+                results.insert(name, value);
+            }
+            Ok(None) => {}
+            Err(payload) => {
+                let message = crate::lifecycle::panic_message(payload.as_ref());
+                logging::warn(&format!("Analyzer {} panicked: {}", name, message), "plugins", "run_all", line!());
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    extern "C" fn host_length(text: *const c_char) -> *const c_char {
+        let len = unsafe { CStr::from_ptr(text) }.to_bytes().len();
+        CString::new(format!("{{\"bytes\": {}}}", len)).unwrap().into_raw()
+    }
+    
+    extern "C" fn host_release(result: *const c_char) {
+        unsafe { drop(CString::from_raw(result as *mut c_char)) };
+    }
+    
+    #[test]
+    fn test_registry_runs_and_replaces_analyzers() {
+        register("test_plugin_spam", Arc::new(|_: &str| Some(serde_json::json!({ "score": 0.1 })))).unwrap();
+        register("test_plugin_spam", Arc::new(|text: &str| Some(serde_json::json!({ "score": text.len() })))).unwrap();
+        register_host("test_plugin_host", host_length, Some(host_release)).unwrap();
+        register("test_plugin_panics", Arc::new(|_: &str| -> Option<Value> { panic!("model not loaded") })).unwrap();
+        
+        let results = run_all("spam");
+        assert_eq!(results["test_plugin_spam"]["score"], 4);
+        assert_eq!(results["test_plugin_host"]["bytes"], 4);
+        assert!(!results.contains_key("test_plugin_panics"));
+        assert_eq!(registered().iter().filter(|name| *name == "test_plugin_spam").count(), 1);
+        
+        assert!(unregister("test_plugin_spam"));
+        assert!(!unregister("test_plugin_spam"));
+        assert!(register("  ", Arc::new(|_: &str| None)).is_err());
+        unregister("test_plugin_host");
+        unregister("test_plugin_panics");
+    }
+}
\ No newline at end of file
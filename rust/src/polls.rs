@@ -0,0 +1,262 @@
+use serde::{Serialize, Deserialize};
+use statrs::distribution::{ChiSquared, ContinuousCDF, Normal};
+use crate::analysis;
+
+// Results of Telegram polls and quizzes: how votes split between options,
+// whether the leader's margin is more than noise, and how option shares move
+// across repeated runs of the same poll. Input is the Bot API Poll object,
+// or an array of them, optionally with a unix "date" per poll.
+
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollOption {
+    pub text: String,
+    #[serde(default)]
+    pub voter_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Poll {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub question: String,
+    pub options: Vec<PollOption>,
+    #[serde(default, rename = "type")]
+    pub poll_type: Option<String>,
+    #[serde(default)]
+    pub correct_option_id: Option<usize>,
+    // When the poll was sent; orders repeated polls for trends
+    #[serde(default)]
+    pub date: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionShare {
+    pub text: String,
+    pub votes: u64,
+    pub share: f64,
+    // 95% Wilson score interval for the share
+    pub confidence_interval: (f64, f64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollSummary {
+    pub id: String,
+    pub question: String,
+    pub total_votes: u64,
+    pub options: Vec<OptionShare>,
+    pub leader: Option<String>,
+    // Two-sided test of the leader against the runner-up
+    pub leader_p_value: Option<f64>,
+    pub leader_significant: bool,
+    // Goodness of fit against an even split across all options
+    pub chi_square: f64,
+    pub uniform_p_value: Option<f64>,
+    // Share of votes on the correct option, for quizzes
+    pub correct_share: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionTrend {
+    pub question: String,
+    pub option: String,
+    pub shares: Vec<f64>,
+    // Change in share per poll, by least squares
+    pub slope: f64,
+    pub change: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollReport {
+    pub polls: Vec<PollSummary>,
+    pub trends: Vec<OptionTrend>,
+}
+
+fn wilson_interval(successes: u64, total: u64) -> (f64, f64) {
+    if total == 0 {
+        return (0.0, 0.0);
+    }
+    let z = 1.96;
+    let n = total as f64;
+    let p = successes as f64 / n;
+    let denominator = 1.0 + z * z / n;
+    let center = (p + z * z / (2.0 * n)) / denominator;
+    let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+// The two shares come from the same sample, so the variance of their
+// difference includes their covariance
+fn leader_p_value(first: u64, second: u64, total: u64) -> Option<f64> {
+    if total == 0 || first + second == 0 {
+        return None;
+    }
+    let n = total as f64;
+    let (p1, p2) = (first as f64 / n, second as f64 / n);
+    let variance = (p1 + p2 - (p1 - p2).powi(2)) / n;
+    if variance <= 0.0 {
+        return None;
+    }
+    let z = (p1 - p2) / variance.sqrt();
+    let normal = Normal::new(0.0, 1.0).ok()?;
+    Some((2.0 * (1.0 - normal.cdf(z.abs()))).clamp(0.0, 1.0))
+}
+
+fn chi_square_uniform(votes: &[u64], total: u64) -> (f64, Option<f64>) {
+    if votes.len() < 2 || total == 0 {
+        return (0.0, None);
+    }
+    let expected = total as f64 / votes.len() as f64;
+    let chi_square = votes.iter().map(|&v| (v as f64 - expected).powi(2) / expected).sum::<f64>();
+    let p_value = ChiSquared::new((votes.len() - 1) as f64).ok()
+        .map(|distribution| (1.0 - distribution.cdf(chi_square)).clamp(0.0, 1.0));
+    (chi_square, p_value)
+}
+
+fn summarize(poll: &Poll) -> PollSummary {
+    // Shares are of votes cast, which exceeds voters on multiple-answer polls
+    let total_votes: u64 = poll.options.iter().map(|o| o.voter_count).sum();
+    let options: Vec<OptionShare> = poll.options.iter().map(|option| OptionShare {
+        text: option.text.clone(),
+        votes: option.voter_count,
+        share: if total_votes > 0 { option.voter_count as f64 / total_votes as f64 } else { 0.0 },
+        confidence_interval: wilson_interval(option.voter_count, total_votes),
+    }).collect();
+    
+    let mut ranked: Vec<&OptionShare> = options.iter().collect();
+    ranked.sort_by_key(|o| std::cmp::Reverse(o.votes));
+    let leader = ranked.first().filter(|o| o.votes > 0 && ranked.get(1).map(|r| r.votes < o.votes).unwrap_or(true));
+    let p_value = match (ranked.first(), ranked.get(1)) {
+        (Some(first), Some(second)) => leader_p_value(first.votes, second.votes, total_votes),
+        _ => None,
+    };
+    
+    let votes: Vec<u64> = options.iter().map(|o| o.votes).collect();
+    let (chi_square, uniform_p_value) = chi_square_uniform(&votes, total_votes);
+    
+    let is_quiz = poll.poll_type.as_deref() == Some("quiz");
+    let correct_share = poll.correct_option_id
+        .filter(|_| is_quiz)
+        .and_then(|i| options.get(i))
+        .map(|o| o.share);
+    
+    PollSummary {
+        id: poll.id.clone(),
+        question: poll.question.clone(),
+        total_votes,
+        leader: leader.map(|o| o.text.clone()),
+        leader_p_value: p_value,
+        leader_significant: leader.is_some() && p_value.map(|p| p < SIGNIFICANCE_LEVEL).unwrap_or(false),
+        chi_square,
+        uniform_p_value,
+        correct_share,
+        options,
+    }
+}
+
+// Polls with the same question are runs of one poll; each option's share is
+// tracked across the runs it appears in
+fn trends(polls: &[Poll], summaries: &[PollSummary]) -> Vec<OptionTrend> {
+    let mut order: Vec<usize> = (0..polls.len()).collect();
+    order.sort_by_key(|&i| polls[i].date.unwrap_or(i64::MIN));
+    
+    let mut runs: Vec<(String, Vec<usize>)> = Vec::new();
+    for i in order {
+        let question = polls[i].question.trim().to_lowercase();
+        match runs.iter_mut().find(|(q, _)| *q == question) {
+            Some((_, indices)) => indices.push(i),
+            None => runs.push((question, vec![i])),
+        }
+    }
+    
+    let mut trends = Vec::new();
+    for (_, indices) in runs.iter().filter(|(_, indices)| indices.len() > 1) {
+        let mut shares: Vec<(String, Vec<f64>)> = Vec::new();
+        for &i in indices {
+            for option in &summaries[i].options {
+                match shares.iter_mut().find(|(text, _)| *text == option.text) {
+                    Some((_, values)) => values.push(option.share),
+                    None => shares.push((option.text.clone(), vec![option.share])),
+                }
+            }
+        }
+        
+        for (option, values) in shares.into_iter().filter(|(_, values)| values.len() > 1) {
+            let x_values: Vec<f64> = (0..values.len()).map(|i| i as f64).collect();
+            trends.push(OptionTrend {
+                question: summaries[indices[0]].question.clone(),
+                option,
+                slope: analysis::calculate_slope(&x_values, &values),
+                change: values[values.len() - 1] - values[0],
+                shares: values,
+            });
+        }
+    }
+    trends
+}
+
+pub fn analyze_polls(polls: &[Poll]) -> PollReport {
+    let summaries: Vec<PollSummary> = polls.iter().map(summarize).collect();
+    PollReport {
+        trends: trends(polls, &summaries),
+        polls: summaries,
+    }
+}
+
+// Accepts one Poll object or an array of them
+pub fn analyze_poll_json(json: &str) -> Result<PollReport, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Invalid poll JSON: {}", e))?;
+    let polls: Vec<Poll> = if value.is_array() {
+        serde_json::from_value(value)
+    } else {
+        serde_json::from_value(value).map(|poll| vec![poll])
+    }.map_err(|e| format!("Invalid poll: {}", e))?;
+    
+    Ok(analyze_polls(&polls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_poll_significance() {
+        let report = analyze_poll_json(r#"{"id": "1", "question": "Lunch?", "type": "quiz", "correct_option_id": 1,
+            "options": [{"text": "Pizza", "voter_count": 70}, {"text": "Sushi", "voter_count": 25}, {"text": "Salad", "voter_count": 5}]}"#).unwrap();
+        let poll = &report.polls[0];
+        
+        assert_eq!(poll.total_votes, 100);
+        assert_eq!(poll.leader.as_deref(), Some("Pizza"));
+        assert!(poll.leader_significant);
+        assert!(poll.uniform_p_value.unwrap() < 0.001);
+        assert_eq!(poll.correct_share, Some(0.25));
+        let (low, high) = poll.options[0].confidence_interval;
+        assert!(low < 0.7 && 0.7 < high);
+        
+        let close = analyze_poll_json(r#"{"options": [{"text": "A", "voter_count": 11}, {"text": "B", "voter_count": 10}]}"#).unwrap();
+        assert!(!close.polls[0].leader_significant);
+        let tied = analyze_poll_json(r#"{"options": [{"text": "A", "voter_count": 3}, {"text": "B", "voter_count": 3}]}"#).unwrap();
+        assert_eq!(tied.polls[0].leader, None);
+        
+        assert!(analyze_poll_json(r#"{"question": "no options"}"#).is_err());
+    }
+    
+    #[test]
+    fn test_poll_trends() {
+        let report = analyze_poll_json(r#"[
+            {"question": "Weekly mood", "date": 300, "options": [{"text": "Good", "voter_count": 8}, {"text": "Bad", "voter_count": 2}]},
+            {"question": "Weekly mood", "date": 100, "options": [{"text": "Good", "voter_count": 2}, {"text": "Bad", "voter_count": 8}]},
+            {"question": "weekly mood ", "date": 200, "options": [{"text": "Good", "voter_count": 5}, {"text": "Bad", "voter_count": 5}]},
+            {"question": "One-off", "options": [{"text": "Yes", "voter_count": 1}]}
+        ]"#).unwrap();
+        
+        assert_eq!(report.polls.len(), 4);
+        assert_eq!(report.trends.len(), 2);
+        let good = report.trends.iter().find(|t| t.option == "Good").unwrap();
+        assert_eq!(good.shares, vec![0.2, 0.5, 0.8]);
+        assert!((good.slope - 0.3).abs() < 1e-9);
+        assert!((good.change - 0.6).abs() < 1e-9);
+    }
+}
\ No newline at end of file
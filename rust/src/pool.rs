@@ -0,0 +1,83 @@
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::config::{self, AppConfig};
+
+// Rayon pool sized from PerformanceConfig::worker_threads, so parallel stages
+// do not take every core from the host application. Built on first use and
+// rebuilt when the setting changes.
+lazy_static! {
+    static ref POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+}
+
+fn build_pool(threads: usize) -> Option<Arc<ThreadPool>> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .thread_name(|i| format!("aiogram-rayon-{}", i))
+        .build()
+        .ok()
+        .map(Arc::new)
+}
+
+fn current_pool() -> Option<Arc<ThreadPool>> {
+    if let Some(pool) = POOL.read().unwrap().as_ref() {
+        return Some(Arc::clone(pool));
+    }
+    
+    let mut slot = POOL.write().unwrap();
+    if slot.is_none() {
+        *slot = build_pool(AppConfig::get_performance_config().worker_threads);
+    }
+    slot.clone()
+}
+
+pub fn initialize() -> Result<(), String> {
+    current_pool().map(|_| ()).ok_or_else(|| "Failed to build the worker thread pool".to_string())
+}
+
+// Runs `op` inside the configured pool; rayon calls made by `op` stay on it.
+// Falls back to the global pool if the configured one cannot be built.
+pub fn install<R, F>(op: F) -> R
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    match current_pool() {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+pub fn set_worker_threads(threads: usize) -> Result<(), String> {
+    if threads == 0 {
+        return Err("worker_threads must be greater than 0".to_string());
+    }
+    let patch = serde_json::json!({ "performance": { "worker_threads": threads } });
+    config::apply_runtime_patch(&patch.to_string())
+}
+
+// Work already running keeps the old pool until it finishes
+pub fn reload_config() {
+    let threads = AppConfig::get_performance_config().worker_threads.max(1);
+    let mut slot = POOL.write().unwrap();
+    let outdated = slot.as_ref().map(|pool| pool.current_num_threads() != threads).unwrap_or(false);
+    if outdated {
+        *slot = build_pool(threads);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_set_worker_threads() {
+        let original = AppConfig::get_performance_config().worker_threads;
+        
+        set_worker_threads(3).unwrap();
+        assert_eq!(install(rayon::current_num_threads), 3);
+        assert!(set_worker_threads(0).is_err());
+        
+        set_worker_threads(original).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+use crate::config::AppConfig;
+use crate::language::LanguageSpan;
+use crate::paths::{self, PathKind};
+use crate::stopwords;
+
+// Part-of-speech tags and noun-phrase chunks. A perceptron model loaded for a
+// language tags it; without one, a lexicon of function words with suffix
+// rules covers English and Persian, and other languages only tell stop words
+// from content words. Keyword extraction can keep to the words of the
+// chunks, so verbs and adverbs do not become keywords.
+
+// Universal Dependencies tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PosTag {
+    ADJ,
+    ADP,
+    ADV,
+    AUX,
+    CONJ,
+    DET,
+    NOUN,
+    NUM,
+    PART,
+    PRON,
+    PROPN,
+    PUNCT,
+    SYM,
+    VERB,
+    // Anything else, e.g. stop words of a language without rules
+    X,
+}
+
+const TAGS: [PosTag; 15] = [
+    PosTag::ADJ, PosTag::ADP, PosTag::ADV, PosTag::AUX, PosTag::CONJ, PosTag::DET, PosTag::NOUN, PosTag::NUM,
+    PosTag::PART, PosTag::PRON, PosTag::PROPN, PosTag::PUNCT, PosTag::SYM, PosTag::VERB, PosTag::X,
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaggedToken {
+    pub text: String,
+    // Byte offsets into the tagged text
+    pub start: usize,
+    pub end: usize,
+    pub tag: PosTag,
+}
+
+// Adjectives, numbers and nouns in a row with at least one noun; determiners
+// are left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NounPhrase {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    // The last noun, or the first in Persian and Arabic, where adjectives
+    // follow the noun
+    pub head: String,
+}
+
+lazy_static! {
+    static ref ENGLISH: HashMap<&'static str, PosTag> = lexicon(&[
+        (PosTag::DET, &["the", "a", "an", "this", "that", "these", "those", "every", "each", "some", "any", "no", "all",
+            "my", "your", "his", "her", "its", "our", "their", "another", "both", "either", "neither"]),
+        (PosTag::PRON, &["i", "you", "he", "she", "it", "we", "they", "me", "him", "us", "them", "mine", "yours", "ours",
+            "theirs", "myself", "yourself", "himself", "herself", "itself", "ourselves", "themselves", "who", "whom",
+            "what", "which", "someone", "something", "anyone", "anything", "everyone", "everything", "nothing"]),
+        (PosTag::ADP, &["in", "on", "at", "of", "for", "with", "from", "to", "by", "about", "into", "onto", "over", "under",
+            "after", "before", "between", "through", "during", "without", "within", "against", "across", "since",
+            "until", "via", "per", "upon", "behind", "above", "below", "near", "like"]),
+        (PosTag::CONJ, &["and", "or", "but", "nor", "yet", "because", "if", "while", "although", "though", "unless",
+            "whether", "than", "so"]),
+        (PosTag::AUX, &["is", "am", "are", "was", "were", "be", "been", "being", "do", "does", "did", "have", "has", "had",
+            "will", "would", "can", "could", "shall", "should", "may", "might", "must"]),
+        (PosTag::PART, &["not", "n't", "'s", "’s"]),
+        (PosTag::ADV, &["very", "really", "too", "also", "just", "never", "always", "often", "here", "there", "now",
+            "then", "when", "where", "why", "how", "again", "still", "already", "soon", "only", "even", "maybe",
+            "please", "back", "away", "today", "tomorrow", "yesterday"]),
+        (PosTag::VERB, &["get", "got", "go", "went", "make", "made", "take", "took", "see", "saw", "know", "knew",
+            "want", "need", "help", "open", "tap", "click", "send", "sent", "receive", "change", "reset"]),
+    ]);
+    
+    static ref PERSIAN: HashMap<&'static str, PosTag> = lexicon(&[
+        (PosTag::DET, &["این", "آن", "هر", "همه", "چند", "هیچ", "همین", "همان", "چه", "کدام"]),
+        (PosTag::PRON, &["من", "تو", "او", "ما", "شما", "آنها", "آن‌ها", "ایشان", "وی", "خودم", "خودت", "خودش", "خودمان",
+            "خودتان", "خودشان", "کسی", "چیزی"]),
+        (PosTag::ADP, &["از", "به", "با", "در", "برای", "بر", "بی", "درباره", "روی", "زیر", "بین", "مثل", "بدون", "را",
+            "توی", "پیش", "کنار", "طرف"]),
+        (PosTag::CONJ, &["و", "یا", "اما", "ولی", "که", "اگر", "چون", "تا", "زیرا", "پس"]),
+        (PosTag::AUX, &["است", "هست", "نیست", "هستند", "بود", "بودند", "باید", "خواهد", "خواهم", "شد", "شده", "شود",
+            "می‌شود", "بوده"]),
+        (PosTag::ADV, &["خیلی", "هم", "نیز", "فقط", "هنوز", "الان", "بسیار", "دیگر", "حتما", "حتماً", "واقعا", "واقعاً",
+            "همیشه", "هرگز", "کمی", "لطفا", "لطفاً"]),
+        (PosTag::VERB, &["دارم", "داری", "دارد", "داریم", "دارید", "دارند", "کرد", "کردم", "کردیم", "کنم", "کنید", "کنیم",
+            "کن", "کنند", "گفت", "رفت", "آمد", "بکنید", "بدهید", "بده", "بزنید"]),
+    ]);
+}
+
+fn lexicon(entries: &[(PosTag, &[&'static str])]) -> HashMap<&'static str, PosTag> {
+    entries.iter().flat_map(|(tag, words)| words.iter().map(move |word| (*word, *tag))).collect()
+}
+
+const MODALS: [&str; 9] = ["will", "would", "can", "could", "shall", "should", "may", "might", "must"];
+const ADJECTIVE_SUFFIXES: [&str; 9] = ["ous", "ful", "able", "ible", "ive", "less", "ical", "ish", "est"];
+
+// Words and punctuation, without the whitespace between them
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    text.split_word_bound_indices().filter(|(_, token)| !token.trim().is_empty()).collect()
+}
+
+// Tags that need no context: numbers, punctuation and symbols
+fn character_class(token: &str) -> Option<PosTag> {
+    if token.chars().any(char::is_numeric) && token.chars().all(|c| c.is_numeric() || ".,/:٫٬".contains(c)) {
+        return Some(PosTag::NUM);
+    }
+    if token.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    Some(if token.chars().all(|c| c.is_ascii_punctuation() && !"$%&+<=>^|~".contains(c) || "«»،؛؟…“”‘’".contains(c)) {
+        PosTag::PUNCT
+    } else {
+        PosTag::SYM
+    })
+}
+
+fn ends_sentence(token: &str) -> bool {
+    matches!(token, "." | "!" | "?" | "…" | "؟")
+}
+
+fn english(word: &str, lower: &str, prev: Option<(PosTag, &str)>, sentence_start: bool) -> PosTag {
+    if let Some(&tag) = ENGLISH.get(lower) {
+        return tag;
+    }
+    if !sentence_start && word.chars().next().is_some_and(char::is_uppercase) {
+        return PosTag::PROPN;
+    }
+    let prev_tag = prev.map(|(tag, _)| tag);
+    let chars = lower.chars().count();
+    // "the meeting", "of testing" are nouns; "is testing" a verb
+    let nominal = matches!(prev_tag, Some(PosTag::DET | PosTag::ADJ | PosTag::ADP | PosTag::NUM));
+    if lower.ends_with("ing") && chars > 5 {
+        return if nominal { PosTag::NOUN } else { PosTag::VERB };
+    }
+    if lower.ends_with("ed") && chars > 4 {
+        return if prev_tag == Some(PosTag::DET) { PosTag::ADJ } else { PosTag::VERB };
+    }
+    if lower.ends_with("ly") && chars > 4 {
+        return PosTag::ADV;
+    }
+    if chars > 5 && ADJECTIVE_SUFFIXES.iter().any(|suffix| lower.ends_with(suffix)) {
+        return PosTag::ADJ;
+    }
+    // After a subject pronoun, a modal or "to"
+    match prev {
+        Some((PosTag::PRON, _)) => PosTag::VERB,
+        Some((_, prev_word)) if prev_word == "to" || MODALS.contains(&prev_word) => PosTag::VERB,
+        _ => PosTag::NOUN,
+    }
+}
+
+fn persian(word: &str) -> PosTag {
+    if let Some(&tag) = PERSIAN.get(word) {
+        return tag;
+    }
+    if word.starts_with("می\u{200C}") || word.starts_with("نمی\u{200C}") {
+        return PosTag::VERB;
+    }
+    if word.ends_with("ترین") || word.ends_with("\u{200C}تر") {
+        return PosTag::ADJ;
+    }
+    // Past stems end in ت or د, followed by a personal ending
+    if word.chars().count() > 4 && ["تند", "دند", "تیم", "دیم", "تید", "دید"].iter().any(|ending| word.ends_with(ending)) {
+        return PosTag::VERB;
+    }
+    PosTag::NOUN
+}
+
+fn rule_tags(text: &str, tokens: &[(usize, &str)], code: Option<&str>) -> Vec<PosTag> {
+    let mut tags: Vec<PosTag> = Vec::with_capacity(tokens.len());
+    let mut prev_lower = String::new();
+    for (i, &(start, token)) in tokens.iter().enumerate() {
+        let sentence_start = match i.checked_sub(1) {
+            None => true,
+            Some(p) => ends_sentence(tokens[p].1) || text[tokens[p].0 + tokens[p].1.len()..start].contains('\n'),
+        };
+        let lower = token.to_lowercase();
+        let prev = tags.last().map(|&tag| (tag, prev_lower.as_str()));
+        let tag = match character_class(token) {
+            Some(tag) => tag,
+            None => match code {
+                Some("en") => english(token, &lower, prev, sentence_start),
+                Some("fa") => persian(&lower),
+                _ if stopwords::is_stop_word(code.unwrap_or("unknown"), &lower) => PosTag::X,
+                _ if !sentence_start && token.chars().next().is_some_and(char::is_uppercase) => PosTag::PROPN,
+                _ => PosTag::NOUN,
+            },
+        };
+        tags.push(tag);
+        prev_lower = lower;
+    }
+    tags
+}
+
+// Averaged perceptron over the word, its affixes and its neighbours' words
+// and tags, trained with tagged sentences from the host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PerceptronTagger {
+    weights: HashMap<String, HashMap<PosTag, f64>>,
+    // Frequent words that nearly always carry one tag skip the weights
+    tag_dictionary: HashMap<String, PosTag>,
+}
+
+fn normalize(word: &str) -> String {
+    if word.chars().any(char::is_numeric) && word.chars().all(|c| c.is_numeric() || ".,".contains(c)) {
+        return if word.chars().count() == 4 { "!YEAR".to_string() } else { "!DIGITS".to_string() };
+    }
+    word.to_lowercase()
+}
+
+fn suffix(word: &str, length: usize) -> &str {
+    match word.char_indices().rev().nth(length - 1) {
+        Some((i, _)) => &word[i..],
+        None => word,
+    }
+}
+
+fn prefix(word: &str) -> &str {
+    word.chars().next().map(|c| &word[..c.len_utf8()]).unwrap_or("")
+}
+
+// Normalized words padded with two markers at either end
+fn context(words: &[&str]) -> Vec<String> {
+    let mut context = vec!["-START-".to_string(), "-START2-".to_string()];
+    context.extend(words.iter().map(|word| normalize(word)));
+    context.extend(["-END-".to_string(), "-END2-".to_string()]);
+    context
+}
+
+fn features(i: usize, context: &[String], prev: Option<PosTag>, prev2: Option<PosTag>) -> Vec<String> {
+    let tag = |tag: Option<PosTag>| tag.map(|tag| format!("{:?}", tag)).unwrap_or_else(|| "-START-".to_string());
+    let (prev, prev2) = (tag(prev), tag(prev2));
+    let i = i + 2;
+    let word = &context[i];
+    vec![
+        "bias".to_string(),
+        format!("i suffix {}", suffix(word, 3)),
+        format!("i pref1 {}", prefix(word)),
+        format!("i-1 tag {}", prev),
+        format!("i-2 tag {}", prev2),
+        format!("i tag+i-2 tag {} {}", prev, prev2),
+        format!("i word {}", word),
+        format!("i-1 tag+i word {} {}", prev, word),
+        format!("i-1 word {}", context[i - 1]),
+        format!("i-1 suffix {}", suffix(&context[i - 1], 3)),
+        format!("i-2 word {}", context[i - 2]),
+        format!("i+1 word {}", context[i + 1]),
+        format!("i+1 suffix {}", suffix(&context[i + 1], 3)),
+        format!("i+2 word {}", context[i + 2]),
+    ]
+}
+
+impl PerceptronTagger {
+    // Sentences of (word, tag), visited in the given order on every pass
+    pub fn train(sentences: &[Vec<(String, PosTag)>], iterations: usize) -> Self {
+        let mut tagger = Self { weights: HashMap::new(), tag_dictionary: dictionary(sentences) };
+        let mut totals: HashMap<(String, PosTag), f64> = HashMap::new();
+        let mut stamps: HashMap<(String, PosTag), u64> = HashMap::new();
+        let mut instances: u64 = 0;
+        for _ in 0..iterations {
+            for sentence in sentences {
+                let words: Vec<&str> = sentence.iter().map(|(word, _)| word.as_str()).collect();
+                let context = context(&words);
+                let (mut prev, mut prev2) = (None, None);
+                for (i, (word, truth)) in sentence.iter().enumerate() {
+                    instances += 1;
+                    let guess = match tagger.tag_dictionary.get(&normalize(word)) {
+                        Some(&tag) => tag,
+                        None => {
+                            let features = features(i, &context, prev, prev2);
+                            let guess = tagger.predict(&features);
+                            if guess != *truth {
+                                for feature in features {
+                                    for (tag, delta) in [(*truth, 1.0), (guess, -1.0)] {
+                                        let weight = tagger.weights.entry(feature.clone()).or_default().entry(tag).or_insert(0.0);
+                                        let key = (feature.clone(), tag);
+                                        let since = instances - stamps.get(&key).copied().unwrap_or(0);
+                                        *totals.entry(key.clone()).or_insert(0.0) += since as f64 * *weight;
+                                        stamps.insert(key, instances);
+                                        *weight += delta;
+                                    }
+                                }
+                            }
+                            guess
+                        }
+                    };
+                    prev2 = prev;
+                    prev = Some(guess);
+                }
+            }
+        }
+        
+        // Each weight becomes its average over every update step
+        for (feature, weights) in tagger.weights.iter_mut() {
+            for (tag, weight) in weights.iter_mut() {
+                let key = (feature.clone(), *tag);
+                let since = instances - stamps.get(&key).copied().unwrap_or(0);
+                let total = totals.get(&key).copied().unwrap_or(0.0) + since as f64 * *weight;
+                *weight = total / instances.max(1) as f64;
+            }
+            weights.retain(|_, weight| *weight != 0.0);
+        }
+        tagger.weights.retain(|_, weights| !weights.is_empty());
+        tagger
+    }
+    
+    // Number of features with weights, 0 for an untrained tagger
+    pub fn feature_count(&self) -> usize {
+        self.weights.len()
+    }
+    
+    // Ties go to the first tag in PosTag order
+    fn predict(&self, features: &[String]) -> PosTag {
+        let mut scores: HashMap<PosTag, f64> = HashMap::new();
+        for weights in features.iter().filter_map(|feature| self.weights.get(feature)) {
+            for (tag, weight) in weights {
+                *scores.entry(*tag).or_insert(0.0) += weight;
+            }
+        }
+        let mut best = TAGS[0];
+        for tag in TAGS {
+            if scores.get(&tag).copied().unwrap_or(0.0) > scores.get(&best).copied().unwrap_or(0.0) {
+                best = tag;
+            }
+        }
+        best
+    }
+    
+    pub fn tag(&self, words: &[&str]) -> Vec<PosTag> {
+        let context = context(words);
+        let (mut prev, mut prev2) = (None, None);
+        let mut tags = Vec::with_capacity(words.len());
+        for (i, word) in words.iter().enumerate() {
+            let tag = match self.tag_dictionary.get(&normalize(word)) {
+                Some(&tag) => tag,
+                None => self.predict(&features(i, &context, prev, prev2)),
+            };
+            prev2 = prev;
+            prev = Some(tag);
+            tags.push(tag);
+        }
+        tags
+    }
+}
+
+// Words seen at least 20 times with one tag 97% of the time or more
+fn dictionary(sentences: &[Vec<(String, PosTag)>]) -> HashMap<String, PosTag> {
+    let mut counts: HashMap<String, HashMap<PosTag, usize>> = HashMap::new();
+    for (word, tag) in sentences.iter().flatten() {
+        *counts.entry(normalize(word)).or_default().entry(*tag).or_insert(0) += 1;
+    }
+    counts.into_iter()
+        .filter_map(|(word, tags)| {
+            let total: usize = tags.values().sum();
+            let (&tag, &count) = tags.iter().max_by_key(|&(tag, count)| (*count, std::cmp::Reverse(*tag)))?;
+            (total >= 20 && count as f64 / total as f64 >= 0.97).then_some((word, tag))
+        })
+        .collect()
+}
+
+lazy_static! {
+    // By ISO 639-1 code
+    static ref MODELS: RwLock<HashMap<&'static str, Arc<PerceptronTagger>>> = RwLock::new(HashMap::new());
+}
+
+// None goes back to the built-in rules for the language
+pub fn set_model(language: &str, model: Option<PerceptronTagger>) -> Result<(), String> {
+    let code = stopwords::language_code(language).ok_or_else(|| format!("Unsupported language: {}", language))?;
+    let mut models = MODELS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match model {
+        Some(model) => models.insert(code, Arc::new(model)),
+        None => models.remove(code),
+    };
+    Ok(())
+}
+
+// A tagger written as JSON, e.g. serde_json::to_string of a trained one;
+// returns its feature count
+pub fn load_model(language: &str, path: &str) -> Result<usize, String> {
+    let path = paths::resolve(path, PathKind::DATA, AppConfig::get().paths.base_dir.as_deref());
+    let contents = fs::read(&path).map_err(|e| format!("Cannot read tagger model {}: {}", path.display(), e))?;
+    let model: PerceptronTagger = serde_json::from_slice(&contents).map_err(|e| format!("Invalid tagger model: {}", e))?;
+    let count = model.feature_count();
+    set_model(language, Some(model))?;
+    Ok(count)
+}
+
+// language is an ISO 639-1 code, a name the detector reports, or "unknown"
+pub fn tag(text: &str, language: &str) -> Vec<TaggedToken> {
+    let code = stopwords::language_code(language);
+    let tokens = tokens(text);
+    let model = code.and_then(|code| MODELS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(code).cloned());
+    let tags = match model {
+        Some(model) => model.tag(&tokens.iter().map(|(_, token)| *token).collect::<Vec<_>>()),
+        None => rule_tags(text, &tokens, code),
+    };
+    tokens.into_iter().zip(tags)
+        .map(|((start, token), tag)| TaggedToken { text: token.to_string(), start, end: start + token.len(), tag })
+        .collect()
+}
+
+// Each span tagged in its own language, offsets into the whole text
+pub fn tag_in(text: &str, spans: &[LanguageSpan]) -> Vec<TaggedToken> {
+    spans.iter()
+        .flat_map(|span| {
+            tag(&text[span.start..span.end], &span.language).into_iter().map(move |token| TaggedToken {
+                start: token.start + span.start,
+                end: token.end + span.start,
+                ..token
+            })
+        })
+        .collect()
+}
+
+fn chunk(text: &str, tokens: &[TaggedToken], head_first: bool) -> Vec<NounPhrase> {
+    let nominal = |token: &TaggedToken| matches!(token.tag, PosTag::ADJ | PosTag::NUM | PosTag::NOUN | PosTag::PROPN);
+    let mut phrases = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if !nominal(&tokens[i]) {
+            i += 1;
+            continue;
+        }
+        // A line break ends a phrase even without punctuation
+        let mut j = i + 1;
+        while j < tokens.len() && nominal(&tokens[j]) && !text[tokens[j - 1].end..tokens[j].start].contains('\n') {
+            j += 1;
+        }
+        let run = &tokens[i..j];
+        let mut nouns = run.iter().filter(|token| matches!(token.tag, PosTag::NOUN | PosTag::PROPN));
+        let head = if head_first { nouns.next() } else { nouns.next_back() };
+        if let Some(head) = head {
+            let (start, end) = (run[0].start, run[run.len() - 1].end);
+            phrases.push(NounPhrase { text: text[start..end].to_string(), start, end, head: head.text.clone() });
+        }
+        i = j;
+    }
+    phrases
+}
+
+pub fn noun_phrases(text: &str, language: &str) -> Vec<NounPhrase> {
+    let head_first = matches!(stopwords::language_code(language), Some("fa" | "ar"));
+    chunk(text, &tag(text, language), head_first)
+}
+
+// In text order, with offsets into the whole text
+pub fn noun_phrases_in(text: &str, spans: &[LanguageSpan]) -> Vec<NounPhrase> {
+    spans.iter()
+        .flat_map(|span| {
+            noun_phrases(&text[span.start..span.end], &span.language).into_iter().map(move |phrase| NounPhrase {
+                start: phrase.start + span.start,
+                end: phrase.end + span.start,
+                ..phrase
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn tags(text: &str, language: &str) -> Vec<(String, PosTag)> {
+        tag(text, language).into_iter().map(|token| (token.text, token.tag)).collect()
+    }
+    
+    #[test]
+    fn test_rule_tags_and_chunks() {
+        let tagged = tags("I reset the old router in the server room yesterday.", "en");
+        let tag_of = |word: &str| tagged.iter().find(|(text, _)| text == word).map(|(_, tag)| *tag);
+        assert_eq!(tag_of("reset"), Some(PosTag::VERB));
+        assert_eq!(tag_of("the"), Some(PosTag::DET));
+        assert_eq!(tag_of("router"), Some(PosTag::NOUN));
+        assert_eq!(tag_of("."), Some(PosTag::PUNCT));
+        
+        let phrases = noun_phrases("We discussed 3 critical bugs in the payment service with Sarah.", "en");
+        let texts: Vec<&str> = phrases.iter().map(|phrase| phrase.text.as_str()).collect();
+        assert_eq!(texts, ["3 critical bugs", "payment service", "Sarah"]);
+        assert_eq!(phrases[0].head, "bugs");
+        assert_eq!(tags("We discussed it", "en")[1].1, PosTag::VERB);
+        
+        // Persian puts adjectives after the noun
+        let phrases = noun_phrases("سرور جدید شرکت از امروز فعال است", "fa");
+        assert_eq!((phrases[0].text.as_str(), phrases[0].head.as_str()), ("سرور جدید شرکت", "سرور"));
+        assert!(tags("می‌روم", "fa").iter().all(|(_, tag)| *tag == PosTag::VERB));
+    }
+    
+    #[test]
+    fn test_perceptron_learns_tags() {
+        let sentence = |words: &[(&str, PosTag)]| words.iter().map(|(word, tag)| (word.to_string(), *tag)).collect::<Vec<_>>();
+        let corpus = vec![
+            sentence(&[("dogs", PosTag::NOUN), ("bark", PosTag::VERB), ("loudly", PosTag::ADV)]),
+            sentence(&[("cats", PosTag::NOUN), ("sleep", PosTag::VERB), ("quietly", PosTag::ADV)]),
+            sentence(&[("birds", PosTag::NOUN), ("sing", PosTag::VERB), ("sweetly", PosTag::ADV)]),
+        ];
+        let tagger = PerceptronTagger::train(&corpus, 5);
+        assert!(tagger.feature_count() > 0);
+        assert_eq!(tagger.tag(&["dogs", "sleep", "loudly"]), vec![PosTag::NOUN, PosTag::VERB, PosTag::ADV]);
+        
+        let restored: PerceptronTagger = serde_json::from_str(&serde_json::to_string(&tagger).unwrap()).unwrap();
+        assert_eq!(restored.tag(&["cats", "sing", "sweetly"]), tagger.tag(&["cats", "sing", "sweetly"]));
+        assert_eq!(restored.tag(&["birds", "bark", "quietly"]), vec![PosTag::NOUN, PosTag::VERB, PosTag::ADV]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+// use aiogram_rust::prelude::*; brings in the types most callers need. The
+// api::Result alias is left out so it does not shadow std's Result.
+pub use crate::api::{self, Error, JobSnapshot, JobStatus, Library, LibraryBuilder, LogLevel, Secret, ValidationResult, ValidationRule};
+#[cfg(feature = "nlp")]
+pub use crate::api::{AnalysisOptions, TextAnalysisResult, TextAnalyzer, TextAnalyzerBuilder};
+#[cfg(feature = "stats")]
+pub use crate::api::DataAnalysisResult;
+#[cfg(feature = "realtime")]
+pub use crate::api::RealtimeResult;
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+
+// Signature of the host-supplied callback: progress in [0.0, 1.0] and the
+// name of the pipeline stage that is about to run.
+pub type ProgressCallback = extern "C" fn(progress: f64, stage: *const c_char);
+
+lazy_static! {
+    static ref PROGRESS_CALLBACK: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+}
+
+pub fn set_callback(callback: Option<ProgressCallback>) {
+    if let Ok(mut current) = PROGRESS_CALLBACK.write() {
+        *current = callback;
+    }
+}
+
+pub fn clear_callback() {
+    set_callback(None);
+}
+
+pub fn has_callback() -> bool {
+    PROGRESS_CALLBACK.read().map(|cb| cb.is_some()).unwrap_or(false)
+}
+
+pub fn report(progress: f64, stage: &str) {
+    let callback = match PROGRESS_CALLBACK.read() {
+        Ok(cb) => *cb,
+        Err(_) => None,
+    };
+    
+    if let Some(callback) = callback {
+        // Stage names are internal constants, so they never contain NUL bytes
+        if let Ok(stage_c) = CString::new(stage) {
+            callback(progress.clamp(0.0, 1.0), stage_c.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+    
+    lazy_static! {
+        static ref RECORDED: Mutex<Vec<(f64, String)>> = Mutex::new(Vec::new());
+    }
+    
+    extern "C" fn record(progress: f64, stage: *const c_char) {
+        let stage = unsafe { CStr::from_ptr(stage) }.to_string_lossy().into_owned();
+        RECORDED.lock().unwrap().push((progress, stage));
+    }
+    
+    #[test]
+    fn test_progress_reporting() {
+        set_callback(Some(record));
+        assert!(has_callback());
+        
+        report(0.25, "test_stage");
+        report(1.5, "test_stage_clamped");
+        clear_callback();
+        report(0.5, "test_stage_ignored");
+        
+        let recorded = RECORDED.lock().unwrap();
+        assert!(recorded.contains(&(0.25, "test_stage".to_string())));
+        assert!(recorded.contains(&(1.0, "test_stage_clamped".to_string())));
+        assert!(!recorded.iter().any(|(_, stage)| stage == "test_stage_ignored"));
+        assert!(!has_callback());
+    }
+}
\ No newline at end of file
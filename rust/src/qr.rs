@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use crate::links::{self, LinkReport, LinkVerdict};
+
+// QR codes users share: the text a scanner reads out of one is parsed into
+// its kind (Wi-Fi login, contact card, link, ...) and every link in it goes
+// through link classification, so a poster with a QR code gets the same
+// scrutiny as a link typed into the chat. Reading the code out of an image is
+// left to the host's scanner, like OCR. With the qr feature the library also
+// draws QR codes as SVG, e.g. for deep links to the bot.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QrPayloadKind {
+    URL,
+    WIFI,
+    CONTACT,
+    EMAIL,
+    SMS,
+    PHONE,
+    GEO,
+    // otpauth:// two-factor secrets
+    OTP,
+    TEXT,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QrPayload {
+    pub kind: QrPayloadKind,
+    // Per kind, e.g. ssid / security / password / hidden for WIFI; secrets
+    // of OTP payloads are left out
+    pub fields: BTreeMap<String, String>,
+    pub links: Vec<LinkReport>,
+    // The worst verdict of the links, SAFE without any
+    pub verdict: LinkVerdict,
+}
+
+// "T:WPA;S:home;P:pa\;ss;;" -> [("T", "WPA"), ("S", "home"), ("P", "pa;ss")],
+// the key-value layout of WIFI:, MECARD: and MATMSG: payloads
+fn escaped_fields(body: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            ';' => {
+                if let Some((key, value)) = current.split_once(':') {
+                    fields.push((key.trim().to_uppercase(), value.to_string()));
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    if let Some((key, value)) = current.split_once(':') {
+        fields.push((key.trim().to_uppercase(), value.to_string()));
+    }
+    fields
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &text[prefix.len()..])
+}
+
+fn percent_decoded_query(query: &str) -> Vec<(String, String)> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_lowercase(), links::percent_decode(value)))
+        .collect()
+}
+
+// Fields named by the payload mapped to ours, the first of repeated ones kept
+fn collect(pairs: Vec<(String, String)>, names: &[(&str, &str)]) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    for (key, value) in pairs {
+        if let Some((_, name)) = names.iter().find(|(from, _)| *from == key) {
+            if !value.is_empty() {
+                fields.entry(name.to_string()).or_insert(value);
+            }
+        }
+    }
+    fields
+}
+
+fn parse_kind(payload: &str) -> (QrPayloadKind, BTreeMap<String, String>) {
+    if let Some(body) = strip_prefix_ignore_case(payload, "WIFI:") {
+        let mut fields = collect(escaped_fields(body), &[("S", "ssid"), ("T", "security"), ("P", "password"), ("H", "hidden")]);
+        fields.entry("security".to_string()).or_insert_with(|| "nopass".to_string());
+        return (QrPayloadKind::WIFI, fields);
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "MECARD:") {
+        let names = [("N", "name"), ("TEL", "phone"), ("EMAIL", "email"), ("URL", "url"), ("ADR", "address"), ("NOTE", "note"), ("ORG", "organization")];
+        return (QrPayloadKind::CONTACT, collect(escaped_fields(body), &names));
+    }
+    if strip_prefix_ignore_case(payload, "BEGIN:VCARD").is_some() {
+        // "TEL;TYPE=CELL:+98..." -> ("TEL", "+98...")
+        let pairs = payload.lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.split(';').next().unwrap_or("").trim().to_uppercase(), value.trim().to_string()))
+            .collect();
+        let names = [("FN", "name"), ("TEL", "phone"), ("EMAIL", "email"), ("URL", "url"), ("ADR", "address"), ("NOTE", "note"), ("ORG", "organization")];
+        return (QrPayloadKind::CONTACT, collect(pairs, &names));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "MATMSG:") {
+        return (QrPayloadKind::EMAIL, collect(escaped_fields(body), &[("TO", "to"), ("SUB", "subject"), ("BODY", "body")]));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "mailto:") {
+        let (to, query) = body.split_once('?').unwrap_or((body, ""));
+        let mut pairs = vec![("to".to_string(), links::percent_decode(to))];
+        pairs.extend(percent_decoded_query(query));
+        return (QrPayloadKind::EMAIL, collect(pairs, &[("to", "to"), ("subject", "subject"), ("body", "body")]));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "SMSTO:") {
+        let (number, message) = body.split_once(':').unwrap_or((body, ""));
+        let pairs = vec![("number".to_string(), number.to_string()), ("body".to_string(), message.to_string())];
+        return (QrPayloadKind::SMS, collect(pairs, &[("number", "number"), ("body", "body")]));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "sms:") {
+        let (number, query) = body.split_once('?').unwrap_or((body, ""));
+        let mut pairs = vec![("number".to_string(), number.to_string())];
+        pairs.extend(percent_decoded_query(query));
+        return (QrPayloadKind::SMS, collect(pairs, &[("number", "number"), ("body", "body")]));
+    }
+    if let Some(number) = strip_prefix_ignore_case(payload, "tel:") {
+        return (QrPayloadKind::PHONE, collect(vec![("number".to_string(), number.to_string())], &[("number", "number")]));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "geo:") {
+        let (coordinates, query) = body.split_once('?').unwrap_or((body, ""));
+        let mut parts = coordinates.split(',');
+        let mut pairs = vec![
+            ("latitude".to_string(), parts.next().unwrap_or("").to_string()),
+            ("longitude".to_string(), parts.next().unwrap_or("").to_string()),
+        ];
+        pairs.extend(percent_decoded_query(query));
+        return (QrPayloadKind::GEO, collect(pairs, &[("latitude", "latitude"), ("longitude", "longitude"), ("q", "query")]));
+    }
+    if let Some(body) = strip_prefix_ignore_case(payload, "otpauth://") {
+        let (path, query) = body.split_once('?').unwrap_or((body, ""));
+        let (otp_type, label) = path.split_once('/').unwrap_or((path, ""));
+        let mut pairs = vec![("type".to_string(), otp_type.to_lowercase()), ("label".to_string(), links::percent_decode(label))];
+        pairs.extend(percent_decoded_query(query));
+        return (QrPayloadKind::OTP, collect(pairs, &[("type", "type"), ("label", "label"), ("issuer", "issuer")]));
+    }
+    let single_link = !payload.contains(char::is_whitespace) && links::find(payload).is_some_and(|link| link.len() == payload.len());
+    if single_link {
+        return (QrPayloadKind::URL, collect(vec![("url".to_string(), payload.to_string())], &[("url", "url")]));
+    }
+    (QrPayloadKind::TEXT, BTreeMap::new())
+}
+
+// The text a scanner read out of a QR code; unsafe links in it are recorded
+// as security events with source "qr"
+pub fn decode(payload: &str) -> QrPayload {
+    let payload = payload.trim();
+    let (kind, fields) = parse_kind(payload);
+    let links = links::scan(payload, "qr");
+    let verdict = links.iter().map(|link| link.verdict).max().unwrap_or(LinkVerdict::SAFE);
+    QrPayload { kind, fields, links, verdict }
+}
+
+#[cfg(feature = "qr")]
+pub use self::image::{generate, QrErrorCorrection, QrImage, QrOptions};
+
+#[cfg(feature = "qr")]
+mod image {
+    use std::fmt::Write;
+    use qrcodegen::{QrCode, QrCodeEcc};
+    use serde::{Serialize, Deserialize};
+    
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum QrErrorCorrection {
+        // Recovers about 7%, 15%, 25% and 30% of the code
+        LOW,
+        MEDIUM,
+        QUARTILE,
+        HIGH,
+    }
+    
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct QrOptions {
+        // The minimum; raised when the code fits at no extra size
+        pub error_correction: QrErrorCorrection,
+        // Quiet zone in modules; scanners expect 4
+        pub border: u32,
+        // Pixels per module
+        pub module_size: u32,
+        // CSS colors as #rgb or #rrggbb
+        pub dark: String,
+        pub light: String,
+    }
+    
+    impl Default for QrOptions {
+        fn default() -> Self {
+            Self {
+                error_correction: QrErrorCorrection::MEDIUM,
+                border: 4,
+                module_size: 8,
+                dark: "#000000".to_string(),
+                light: "#ffffff".to_string(),
+            }
+        }
+    }
+    
+    impl QrOptions {
+        pub fn from_json(options_json: &str) -> Result<Self, String> {
+            if options_json.trim().is_empty() {
+                return Ok(Self::default());
+            }
+            serde_json::from_str(options_json).map_err(|e| format!("Invalid QR options: {}", e))
+        }
+    }
+    
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct QrImage {
+        // 1-40
+        pub version: u8,
+        // Modules per side, without the border
+        pub size: u32,
+        pub svg: String,
+    }
+    
+    fn is_color(color: &str) -> bool {
+        color.strip_prefix('#').is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+    }
+    
+    pub fn generate(text: &str, options: &QrOptions) -> Result<QrImage, String> {
+        if !is_color(&options.dark) || !is_color(&options.light) {
+            return Err("QR colors must be #rgb or #rrggbb".to_string());
+        }
+        if options.module_size == 0 || options.module_size > 100 || options.border > 100 {
+            return Err("QR module_size must be 1-100 and border at most 100".to_string());
+        }
+        let ecc = match options.error_correction {
+            QrErrorCorrection::LOW => QrCodeEcc::Low,
+            QrErrorCorrection::MEDIUM => QrCodeEcc::Medium,
+            QrErrorCorrection::QUARTILE => QrCodeEcc::Quartile,
+            QrErrorCorrection::HIGH => QrCodeEcc::High,
+        };
+        let code = QrCode::encode_text(text, ecc).map_err(|e| format!("Cannot encode QR code: {}", e))?;
+        
+        let size = code.size();
+        let border = options.border as i32;
+        let side = size + 2 * border;
+        let pixels = side as u32 * options.module_size;
+        let mut path = String::new();
+        for y in 0..size {
+            for x in 0..size {
+                if code.get_module(x, y) {
+                    let _ = write!(path, "M{},{}h1v1h-1z", x + border, y + border);
+                }
+            }
+        }
+        let svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{pixels}\" height=\"{pixels}\" viewBox=\"0 0 {side} {side}\" shape-rendering=\"crispEdges\">\
+             <rect width=\"100%\" height=\"100%\" fill=\"{}\"/><path d=\"{}\" fill=\"{}\"/></svg>",
+            options.light, path, options.dark,
+        );
+        Ok(QrImage { version: code.version().value(), size: size as u32, svg })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_decode_payloads() {
+        let wifi = decode("WIFI:T:WPA;S:Cafe \\; Bar;P:secret;H:false;;");
+        assert_eq!(wifi.kind, QrPayloadKind::WIFI);
+        assert_eq!(wifi.fields["ssid"], "Cafe ; Bar");
+        assert_eq!(wifi.fields["password"], "secret");
+        assert_eq!(wifi.verdict, LinkVerdict::SAFE);
+        
+        let card = decode("BEGIN:VCARD\nVERSION:3.0\nFN:Sara\nTEL;TYPE=CELL:+989120000000\nURL:http://192.168.0.5/pay\nEND:VCARD");
+        assert_eq!((card.kind, card.fields["name"].as_str(), card.fields["phone"].as_str()), (QrPayloadKind::CONTACT, "Sara", "+989120000000"));
+        assert_eq!(card.verdict, LinkVerdict::SUSPICIOUS);
+        
+        let mail = decode("mailto:support@example.com?subject=Order%2042&body=Hi");
+        assert_eq!((mail.kind, mail.fields["subject"].as_str()), (QrPayloadKind::EMAIL, "Order 42"));
+        let otp = decode("otpauth://totp/Bot:sara?secret=JBSWY3DPEHPK3PXP&issuer=Bot");
+        assert_eq!(otp.kind, QrPayloadKind::OTP);
+        assert!(!otp.fields.contains_key("secret"));
+        assert_eq!(decode("geo:35.6892,51.3890").fields["longitude"], "51.3890");
+        assert_eq!(decode("SMSTO:+100:hello").fields["body"], "hello");
+        assert_eq!(decode("just a note").kind, QrPayloadKind::TEXT);
+        
+        // A login QR passed around as a link is the classic account takeover
+        let login = decode("tg://login?token=AQABzxc");
+        assert_eq!((login.kind, login.verdict), (QrPayloadKind::URL, LinkVerdict::DANGEROUS));
+    }
+    
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_generate_svg() {
+        let image = generate("https://t.me/some_bot?start=promo", &QrOptions::default()).unwrap();
+        assert_eq!((image.version, image.size), (3, 29));
+        assert!(image.svg.starts_with("<svg") && image.svg.contains("viewBox=\"0 0 37 37\""));
+        let options = QrOptions { dark: "red\"/><script>".to_string(), ..QrOptions::default() };
+        assert!(generate("x", &options).is_err());
+        assert!(QrOptions::from_json(r#"{"error_correction": "HIGH", "border": 2}"#).is_ok());
+        assert!(QrOptions::from_json(r##"{"colour": "#fff"}"##).is_err());
+    }
+}
\ No newline at end of file
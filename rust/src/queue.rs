@@ -0,0 +1,252 @@
+use serde::{Serialize, Deserialize};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+use lazy_static::lazy_static;
+use crate::tenant::{self, TenantContext};
+
+// Completions that are never drained are dropped this long after they
+// finish, at the next enqueue, as unpolled jobs are
+const COMPLETION_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedRequest {
+    pub kind: String,
+    pub input: String,
+    // Optional tenant context; see tenant.rs
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    // Breaks the tenant's costs down by chat
+    #[serde(default)]
+    pub chat_id: Option<String>,
+}
+
+impl QueuedRequest {
+    fn tenant(&self) -> TenantContext {
+        TenantContext::new(self.tenant_id.as_deref(), self.user_id.as_deref()).with_chat(self.chat_id.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    pub request_id: u64,
+    pub kind: String,
+    // Echoed back so a host serving several bots can route the completion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub success: bool,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+// Requests run as tasks on a shared tokio runtime; the CPU-heavy analysis
+// itself goes to the runtime's bounded blocking pool, so many in-flight
+// requests share a fixed set of OS threads.
+pub struct CompletionQueue {
+    runtime: Runtime,
+    // With the time each finished
+    completions: Arc<Mutex<Vec<(Instant, Completion)>>>,
+    next_id: AtomicU64,
+    completion_ttl: Duration,
+}
+
+impl CompletionQueue {
+    pub fn new(worker_threads: usize) -> Result<Self, String> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(worker_threads.max(1))
+            .max_blocking_threads(worker_threads.max(1))
+            .thread_name("aiogram-async")
+            .build()
+            .map_err(|e| format!("Failed to start async runtime: {}", e))?;
+        
+        Ok(Self {
+            runtime,
+            completions: Arc::new(Mutex::new(Vec::new())),
+            next_id: AtomicU64::new(1),
+            completion_ttl: COMPLETION_TTL,
+        })
+    }
+    
+    pub fn with_completion_ttl(mut self, ttl: Duration) -> Self {
+        self.completion_ttl = ttl;
+        self
+    }
+    
+    // Drops completions nobody drained within the TTL, so a host that stops
+    // draining does not grow the queue forever; returns their request ids
+    fn sweep_expired(&self) -> Vec<u64> {
+        let mut completions = match self.completions.lock() {
+            Ok(completions) => completions,
+            Err(_) => return Vec::new(),
+        };
+        let (expired, kept) = completions.drain(..).partition(|(finished, _)| finished.elapsed() >= self.completion_ttl);
+        *completions = kept;
+        expired.into_iter().map(|(_, completion): (Instant, Completion)| completion.request_id).collect()
+    }
+    
+    pub fn enqueue(&self, request_json: &str) -> Result<u64, String> {
+        let request: QueuedRequest = serde_json::from_str(request_json)
+            .map_err(|e| format!("Invalid request: {}", e))?;
+        if !is_supported_kind(&request.kind) {
+            return Err(format!("Unknown request kind: {}", request.kind));
+        }
+        let context = request.tenant();
+        tenant::with_tenant(context.clone(), tenant::admit)?;
+        
+        let expired = self.sweep_expired();
+        if !expired.is_empty() {
+            crate::logging::warn(
+                &format!("Dropped {} completions nobody drained: requests {:?}", expired.len(), expired),
+                "queue",
+                "enqueue",
+                line!(),
+            );
+        }
+        
+        let request_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let completions = Arc::clone(&self.completions);
+        
+        self.runtime.spawn(async move {
+            let kind = request.kind.clone();
+            let tenant_id = context.tenant_id.clone();
+            let outcome = tokio::task::spawn_blocking(move || tenant::with_tenant(context, || run_request(&request)))
+                .await
+                .unwrap_or_else(|e| Err(format!("Request task failed: {}", e)));
+            
+            let completion = match outcome {
+                Ok(result) => Completion { request_id, kind, tenant_id, success: true, result: Some(result), error: None },
+                Err(error) => Completion { request_id, kind, tenant_id, success: false, result: None, error: Some(error) },
+            };
+            
+            if let Ok(mut completions) = completions.lock() {
+                completions.push((Instant::now(), completion));
+            }
+        });
+        
+        Ok(request_id)
+    }
+    
+    pub fn drain(&self) -> Vec<Completion> {
+        match self.completions.lock() {
+            Ok(mut completions) => completions.drain(..).map(|(_, completion)| completion).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Kinds whose engines were compiled in; see the nlp, stats and realtime
+// features
+fn is_supported_kind(kind: &str) -> bool {
+    match kind {
+        #[cfg(feature = "nlp")]
+        "text" => true,
+        #[cfg(feature = "stats")]
+        "data" => true,
+        #[cfg(feature = "realtime")]
+        "realtime" => true,
+        _ => false,
+    }
+}
+
+fn run_request(request: &QueuedRequest) -> Result<serde_json::Value, String> {
+    match request.kind.as_str() {
+        #[cfg(feature = "nlp")]
+        "text" => serde_json::to_value(crate::analysis::analyze_text(&request.input)).map_err(|e| e.to_string()),
+        #[cfg(feature = "stats")]
+        "data" => serde_json::to_value(crate::analysis::analyze_data(&request.input)).map_err(|e| e.to_string()),
+        #[cfg(feature = "realtime")]
+        "realtime" => serde_json::to_value(crate::realtime::process_realtime_data(&request.input)).map_err(|e| e.to_string()),
+        other => Err(format!("Unknown request kind: {}", other)),
+    }
+}
+
+// Global completion queue
+lazy_static! {
+    static ref COMPLETION_QUEUE: Result<CompletionQueue, String> = CompletionQueue::new(
+        crate::config::AppConfig::get_performance_config().worker_threads
+    );
+}
+
+// Reports a runtime that failed to start instead of leaving it to the first
+// enqueue_request call
+pub fn initialize() -> Result<(), String> {
+    COMPLETION_QUEUE.as_ref().map(|_| ()).map_err(Clone::clone)
+}
+
+// Public queue functions
+pub fn enqueue_request(request_json: &str) -> Result<u64, String> {
+    match &*COMPLETION_QUEUE {
+        Ok(queue) => queue.enqueue(request_json),
+        Err(e) => Err(e.clone()),
+    }
+}
+
+pub fn drain_completions() -> Vec<Completion> {
+    match &*COMPLETION_QUEUE {
+        Ok(queue) => queue.drain(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    #[cfg(all(feature = "nlp", feature = "stats"))]
+    fn test_enqueue_and_drain() {
+        let queue = CompletionQueue::new(2).unwrap();
+        let first = queue.enqueue(r#"{"kind": "data", "input": "1,2,3"}"#).unwrap();
+        let second = queue.enqueue(r#"{"kind": "text", "input": "Hello world"}"#).unwrap();
+        assert_ne!(first, second);
+        
+        let mut completions = Vec::new();
+        for _ in 0..500 {
+            completions.extend(queue.drain());
+            if completions.len() == 2 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        
+        assert_eq!(completions.len(), 2);
+        assert!(completions.iter().all(|c| c.success));
+        let data = completions.iter().find(|c| c.request_id == first).unwrap();
+        assert_eq!(data.result.as_ref().unwrap()["record_count"], 3);
+        
+        // Drained completions are not returned again
+        assert!(queue.drain().is_empty());
+    }
+    
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_undrained_completions_expire() {
+        let queue = CompletionQueue::new(1).unwrap().with_completion_ttl(Duration::ZERO);
+        let wait_for = |count: usize| {
+            for _ in 0..500 {
+                if queue.completions.lock().unwrap().len() == count {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        };
+        queue.enqueue(r#"{"kind": "data", "input": "1,2,3"}"#).unwrap();
+        wait_for(1);
+        
+        // The next enqueue drops the finished, undrained one
+        let second = queue.enqueue(r#"{"kind": "data", "input": "4,5"}"#).unwrap();
+        wait_for(1);
+        let drained: Vec<u64> = queue.drain().into_iter().map(|completion| completion.request_id).collect();
+        assert_eq!(drained, [second]);
+    }
+    
+    #[test]
+    fn test_invalid_requests_rejected() {
+        let queue = CompletionQueue::new(1).unwrap();
+        assert!(queue.enqueue("not json").is_err());
+        assert!(queue.enqueue(r#"{"kind": "video", "input": ""}"#).is_err());
+    }
+}
\ No newline at end of file
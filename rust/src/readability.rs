@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::{stopwords, summary};
+
+// Readability indices with the formula picked by language. The Flesch family
+// is calibrated on English syllable counts and means little elsewhere, so
+// English gets Flesch Reading Ease, Flesch-Kincaid and SMOG, German the
+// Amstad adaptation, Persian Dayani's adaptation, and every other language
+// LIX, which only counts word and sentence lengths. Persian script leaves
+// short vowels unwritten, so Persian syllables are estimated at one per two
+// letters.
+
+// LIX counts words longer than this as long
+const LONG_WORD_CHARS: usize = 6;
+// SMOG counts words of this many syllables or more
+const POLYSYLLABLE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadabilityFormula {
+    #[serde(rename = "FLESCH_READING_EASE")]
+    FleschReadingEase,
+    #[serde(rename = "FLESCH_AMSTAD")]
+    FleschAmstad,
+    #[serde(rename = "FLESCH_DAYANI")]
+    FleschDayani,
+    #[serde(rename = "LIX")]
+    Lix,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadabilityReport {
+    // ISO 639-1 code the formula was picked for, or "unknown"
+    pub language: String,
+    pub formula: ReadabilityFormula,
+    // Value of formula: 0-100, higher is easier, for the Flesch family;
+    // LIX runs the other way, under 30 very easy and over 60 very hard
+    pub score: f64,
+    // Indices that only apply to some languages are None elsewhere
+    pub flesch_reading_ease: Option<f64>,
+    // US school grade
+    pub flesch_kincaid_grade: Option<f64>,
+    pub smog_index: Option<f64>,
+    pub flesch_amstad: Option<f64>,
+    pub flesch_dayani: Option<f64>,
+    pub lix: f64,
+    pub sentences: usize,
+    pub words: usize,
+    pub syllables: usize,
+    pub long_words: usize,
+    pub polysyllables: usize,
+}
+
+impl Default for ReadabilityReport {
+    fn default() -> Self {
+        Self {
+            language: "unknown".to_string(),
+            formula: ReadabilityFormula::Lix,
+            score: 0.0,
+            flesch_reading_ease: None,
+            flesch_kincaid_grade: None,
+            smog_index: None,
+            flesch_amstad: None,
+            flesch_dayani: None,
+            lix: 0.0,
+            sentences: 0,
+            words: 0,
+            syllables: 0,
+            long_words: 0,
+            polysyllables: 0,
+        }
+    }
+}
+
+fn is_arabic_script(c: char) -> bool {
+    matches!(c, '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' | '\u{FB50}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'ä' | 'ö' | 'ü' | 'á' | 'é' | 'í' | 'ó' | 'ú' | 'à' | 'è' | 'ì' | 'ò' | 'ù' | 'â' | 'ê' | 'î' | 'ô' | 'û')
+}
+
+// Vowel groups for alphabetic scripts, with English silent final e; one per
+// two letters for Arabic script
+pub fn count_syllables(word: &str) -> usize {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).flat_map(char::to_lowercase).collect();
+    if letters.is_empty() {
+        return 0;
+    }
+    if letters.iter().any(|&c| is_arabic_script(c)) {
+        return (letters.len() / 2).max(1);
+    }
+    
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for &c in &letters {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    // "make", "code"; not "the" or "free"
+    let n = letters.len();
+    if n > 2 && letters[n - 1] == 'e' && !is_vowel(letters[n - 2]) && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+// The language's ISO 639-1 code; for an unknown language the detected one,
+// or else Persian for text mostly in Arabic script
+fn resolve_language(text: &str, language: &str) -> Option<&'static str> {
+    stopwords::language_code(language).or_else(|| {
+        let detected = whatlang::detect(text).filter(|info| info.is_reliable()).and_then(|info| stopwords::language_code(info.lang().code()));
+        if detected.is_some() {
+            return detected;
+        }
+        let (arabic, letters) = text.chars().filter(|c| c.is_alphabetic())
+            .fold((0, 0), |(arabic, letters), c| (arabic + is_arabic_script(c) as usize, letters + 1));
+        // Most Arabic-script chat the bot sees is Persian
+        (letters > 0 && arabic * 2 > letters).then_some("fa")
+    })
+}
+
+pub fn report(text: &str, language: &str) -> ReadabilityReport {
+    let words: Vec<&str> = text.unicode_words().filter(|w| w.chars().any(char::is_alphabetic)).collect();
+    let code = resolve_language(text, language);
+    let mut report = ReadabilityReport { language: code.unwrap_or("unknown").to_string(), ..Default::default() };
+    if words.is_empty() {
+        return report;
+    }
+    
+    let syllables: Vec<usize> = words.iter().map(|w| count_syllables(w)).collect();
+    report.sentences = summary::split_sentences(text).len().max(1);
+    report.words = words.len();
+    report.syllables = syllables.iter().sum();
+    report.long_words = words.iter().filter(|w| w.chars().count() > LONG_WORD_CHARS).count();
+    report.polysyllables = syllables.iter().filter(|&&s| s >= POLYSYLLABLE).count();
+    scored(report, code)
+}
+
+// The indices of counted words and sentences, for the language of code
+fn scored(mut report: ReadabilityReport, code: Option<&str>) -> ReadabilityReport {
+    let (sentences, words) = (report.sentences as f64, report.words as f64);
+    let words_per_sentence = words / sentences;
+    let syllables_per_word = report.syllables as f64 / words;
+    report.lix = words_per_sentence + 100.0 * report.long_words as f64 / words;
+    
+    let ease = |score: f64| score.clamp(0.0, 100.0);
+    match code {
+        Some("en") => {
+            report.flesch_reading_ease = Some(ease(206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word));
+            report.flesch_kincaid_grade = Some((0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59).max(0.0));
+            report.smog_index = Some(1.043 * (report.polysyllables as f64 * 30.0 / sentences).sqrt() + 3.1291);
+        }
+        Some("de") => report.flesch_amstad = Some(ease(180.0 - words_per_sentence - 58.5 * syllables_per_word)),
+        Some("fa") => report.flesch_dayani = Some(ease(262.835 - 0.846 * syllables_per_word * 100.0 - 1.015 * words_per_sentence)),
+        _ => {}
+    }
+    (report.formula, report.score) = match (report.flesch_reading_ease, report.flesch_amstad, report.flesch_dayani) {
+        (Some(score), _, _) => (ReadabilityFormula::FleschReadingEase, score),
+        (_, Some(score), _) => (ReadabilityFormula::FleschAmstad, score),
+        (_, _, Some(score)) => (ReadabilityFormula::FleschDayani, score),
+        _ => (ReadabilityFormula::Lix, report.lix),
+    };
+    report
+}
+
+impl ReadabilityReport {
+    // One report for consecutive parts of a text, scored by the formula of
+    // the language most of their words are in
+    pub fn combine<'a>(reports: impl IntoIterator<Item = &'a ReadabilityReport>) -> Self {
+        let mut combined = ReadabilityReport::default();
+        let mut words_per_language: HashMap<&str, usize> = HashMap::new();
+        for report in reports {
+            combined.sentences += report.sentences;
+            combined.words += report.words;
+            combined.syllables += report.syllables;
+            combined.long_words += report.long_words;
+            combined.polysyllables += report.polysyllables;
+            *words_per_language.entry(report.language.as_str()).or_insert(0) += report.words;
+        }
+        if let Some((language, _)) = words_per_language.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))) {
+            combined.language = language.to_string();
+        }
+        if combined.words == 0 {
+            return combined;
+        }
+        let code = (combined.language != "unknown").then(|| combined.language.clone());
+        scored(combined, code.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_syllables() {
+        assert_eq!(count_syllables("cat"), 1);
+        assert_eq!(count_syllables("make"), 1);
+        assert_eq!(count_syllables("the"), 1);
+        assert_eq!(count_syllables("readability"), 5);
+        assert_eq!(count_syllables("کتاب"), 2);
+        assert_eq!(count_syllables("دانشگاه"), 3);
+        assert_eq!(count_syllables("42"), 0);
+    }
+    
+    #[test]
+    fn test_formula_per_language() {
+        let english = report("The cat sat on the mat. It was a good cat.", "en");
+        assert_eq!(english.formula, ReadabilityFormula::FleschReadingEase);
+        assert!(english.score > 90.0);
+        assert!(english.flesch_kincaid_grade.unwrap() < 2.0);
+        assert_eq!((english.sentences, english.words), (2, 11));
+        assert!(english.flesch_dayani.is_none());
+        
+        let hard = report("Institutional accountability necessitates comprehensive organizational transparency.", "en");
+        assert!(hard.score < english.score);
+        assert!(hard.lix > english.lix);
+        
+        // Detected from the script when the language is unknown
+        let persian = report("من به دانشگاه رفتم. کتاب خوبی خواندم.", "unknown");
+        assert_eq!(persian.language, "fa");
+        assert_eq!(persian.formula, ReadabilityFormula::FleschDayani);
+        assert!(persian.flesch_reading_ease.is_none());
+        assert!((0.0..=100.0).contains(&persian.score));
+        
+        let french = report("Le chat dort sur le canapé.", "fr");
+        assert_eq!((french.formula, french.score), (ReadabilityFormula::Lix, french.lix));
+        assert_eq!(report("", "en").words, 0);
+        
+        let halves = ReadabilityReport::combine([&report("The cat sat on the mat.", "en"), &report("It was a good cat.", "en")]);
+        assert_eq!(halves, english);
+    }
+}
\ No newline at end of file
@@ -5,6 +5,7 @@ use std::time::{Duration, Instant};
 use rayon::prelude::*;
 use dashmap::DashMap;
 use chrono::{DateTime, Utc};
+use crate::{alerts, parsers, pool};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RealtimeResult {
@@ -12,6 +13,36 @@ pub struct RealtimeResult {
     pub processing_speed: f64,
     pub quality: String,
     pub timestamp: i64,
+    // Pipeline stages in the order they ran; a failed stage is the last one
+    #[serde(default)]
+    pub stages: Vec<StageTiming>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StageOutcome {
+    OK,
+    FAILED,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    // parse, fault_injection, buffer, process or stats
+    pub name: String,
+    pub duration_ms: f64,
+    pub outcome: StageOutcome,
+    // Processing notes of the stage, or why it failed
+    pub details: Vec<String>,
+}
+
+impl StageTiming {
+    fn finish(name: &str, started: Instant, outcome: StageOutcome, details: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+            outcome,
+            details,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,8 +55,18 @@ pub struct RealtimeData {
 
 // Global state for real-time processing
 lazy_static::lazy_static! {
-    static ref PROCESSING_STATS: Arc<DashMap<String, ProcessingStats>> = Arc::new(DashMap::new());
-    static ref DATA_BUFFER: Arc<Mutex<Vec<RealtimeData>>> = Arc::new(Mutex::new(Vec::new()));
+    // Sized up front for the built-in data types plus a few host-defined
+    // ones, and for a full buffer (1000 items plus the one being added)
+    static ref PROCESSING_STATS: Arc<DashMap<String, ProcessingStats>> = Arc::new(DashMap::with_capacity(16));
+    static ref DATA_BUFFER: Arc<Mutex<Vec<RealtimeData>>> = Arc::new(Mutex::new(Vec::with_capacity(1001)));
+    // Running mean and variance of each numeric stream, for anomaly alerts
+    static ref STREAM_BASELINES: DashMap<String, Baseline> = DashMap::new();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&PROCESSING_STATS);
+    lazy_static::initialize(&DATA_BUFFER);
+    Ok(())
 }
 
 #[derive(Debug, Clone)]
@@ -52,23 +93,104 @@ impl ProcessingStats {
     }
 }
 
+// Exponentially weighted, so a stream whose level shifts for good stops
+// alerting once the baseline has caught up
+#[derive(Debug, Clone, Default)]
+struct Baseline {
+    observations: u64,
+    mean: f64,
+    variance: f64,
+}
+
+impl Baseline {
+    const ALPHA: f64 = 0.05;
+    
+    // z-score of value against the baseline so far, then folds value in
+    fn score(&mut self, value: f64) -> f64 {
+        let deviation = value - self.mean;
+        let z = if self.variance > 0.0 { deviation / self.variance.sqrt() } else { 0.0 };
+        if self.observations == 0 {
+            self.mean = value;
+        } else {
+            self.mean += Self::ALPHA * deviation;
+            self.variance = (1.0 - Self::ALPHA) * (self.variance + Self::ALPHA * deviation * deviation);
+        }
+        self.observations += 1;
+        z
+    }
+}
+
+// Streaming side of the anomaly alerts: each numeric_data payload's mean is
+// scored against the running baseline of its data type
+fn observe_stream(data_type: &str, value: f64) {
+    let rule = crate::config::AppConfig::get().alerts.anomalies;
+    if !rule.enabled || !value.is_finite() {
+        return;
+    }
+    let (observations, z) = {
+        let mut baseline = STREAM_BASELINES.entry(data_type.to_string()).or_default();
+        let z = baseline.score(value);
+        (baseline.observations, z)
+    };
+    if observations <= rule.stream_warmup {
+        return;
+    }
+    let anomaly = if z >= rule.stream_z_threshold {
+        Some(alerts::AnomalyDirection::HIGH)
+    } else if z <= -rule.stream_z_threshold {
+        Some(alerts::AnomalyDirection::LOW)
+    } else {
+        None
+    };
+    alerts::observe_metric(&format!("realtime.{}", data_type), anomaly, value);
+}
+
+// Structure is checked first so a hostile payload is rejected in one linear
+// pass instead of reaching serde's recursion limit
+pub fn parse_realtime_data(data_json: &str) -> Result<RealtimeData, String> {
+    parsers::check_json_structure(data_json).map_err(|e| e.to_string())?;
+    serde_json::from_str(data_json).map_err(|e| format!("Invalid realtime payload: {}", e))
+}
+
 pub fn process_realtime_data(data_json: &str) -> RealtimeResult {
     let start_time = Instant::now();
+    // Charged whether or not the data parses
+    crate::costs::record("process_realtime", data_json.len(), []);
+    let mut stages = Vec::new();
     
     // Parse input data
-    let data: RealtimeData = match serde_json::from_str(data_json) {
+    let data = match parse_realtime_data(data_json) {
         Ok(d) => d,
-        Err(_) => {
+        Err(e) => {
+            stages.push(StageTiming::finish("parse", start_time, StageOutcome::FAILED, vec![e]));
             return RealtimeResult {
                 status: "error".to_string(),
                 processing_speed: 0.0,
                 quality: "invalid_data".to_string(),
                 timestamp: Utc::now().timestamp(),
+                stages,
             };
         }
     };
+    stages.push(StageTiming::finish("parse", start_time, StageOutcome::OK, Vec::new()));
+    
+    #[cfg(feature = "chaos")]
+    {
+        let stage_start = Instant::now();
+        if crate::chaos::inject(crate::chaos::Fault::DroppedRealtime) {
+            stages.push(StageTiming::finish("fault_injection", stage_start, StageOutcome::FAILED, vec!["Dropped by fault injection".to_string()]));
+            return RealtimeResult {
+                status: "error".to_string(),
+                processing_speed: 0.0,
+                quality: "dropped".to_string(),
+                timestamp: Utc::now().timestamp(),
+                stages,
+            };
+        }
+    }
     
     // Store data in buffer
+    let stage_start = Instant::now();
     {
         let mut buffer = DATA_BUFFER.lock().unwrap();
         buffer.push(data.clone());
@@ -79,20 +201,27 @@ pub fn process_realtime_data(data_json: &str) -> RealtimeResult {
         }
     }
     
+    stages.push(StageTiming::finish("buffer", stage_start, StageOutcome::OK, Vec::new()));
+    
     // Process data with different algorithms based on type
-    let processing_result = match data.data_type.as_str() {
+    let stage_start = Instant::now();
+    let processing_result = pool::install(|| match data.data_type.as_str() {
         "telegram_message" => process_telegram_message(&data),
         "numeric_data" => process_numeric_data(&data),
         "text_data" => process_text_data(&data),
         _ => process_generic_data(&data),
-    };
+    });
+    let outcome = if processing_result.status == "processed" { StageOutcome::OK } else { StageOutcome::FAILED };
+    stages.push(StageTiming::finish("process", stage_start, outcome, processing_result.processing_notes));
     
     // Update processing statistics
+    let stage_start = Instant::now();
     let processing_time = start_time.elapsed().as_millis() as f64;
-    let mut stats = PROCESSING_STATS
+    PROCESSING_STATS
         .entry(data.data_type.clone())
-        .or_insert_with(ProcessingStats::new);
-    stats.update(processing_time);
+        .or_insert_with(ProcessingStats::new)
+        .update(processing_time);
+    stages.push(StageTiming::finish("stats", stage_start, StageOutcome::OK, Vec::new()));
     
     // Calculate processing speed (operations per second)
     let processing_speed = if processing_time > 0.0 {
@@ -109,6 +238,7 @@ pub fn process_realtime_data(data_json: &str) -> RealtimeResult {
         processing_speed,
         quality,
         timestamp: Utc::now().timestamp(),
+        stages,
     }
 }
 
@@ -141,10 +271,7 @@ fn process_telegram_message(data: &RealtimeData) -> ProcessingResult {
 
 fn process_numeric_data(data: &RealtimeData) -> ProcessingResult {
     // Parse numeric data
-    let numbers: Vec<f64> = data.content
-        .split(|c| c == ',' || c == ' ' || c == '\n')
-        .filter_map(|s| s.trim().parse::<f64>().ok())
-        .collect();
+    let numbers = parsers::parse_numbers(&data.content);
     
     if numbers.is_empty() {
         return ProcessingResult {
@@ -168,6 +295,7 @@ fn process_numeric_data(data: &RealtimeData) -> ProcessingResult {
     );
     
     let complexity = std_dev / mean; // Coefficient of variation
+    observe_stream(&data.data_type, mean);
     
     ProcessingResult {
         status: "processed".to_string(),
@@ -320,6 +448,27 @@ mod tests {
         assert!(result.processing_speed > 0.0);
     }
     
+    #[test]
+    fn test_stages() {
+        let data = RealtimeData {
+            timestamp: 1234567890.0,
+            user_id: 12345,
+            data_type: "numeric_data".to_string(),
+            content: "no numbers here".to_string(),
+        };
+        let result = process_realtime_data(&serde_json::to_string(&data).unwrap());
+        let names: Vec<&str> = result.stages.iter().map(|stage| stage.name.as_str()).collect();
+        assert_eq!(names, vec!["parse", "buffer", "process", "stats"]);
+        assert_eq!(result.stages[2].outcome, StageOutcome::FAILED);
+        assert_eq!(result.stages[2].details, vec!["No valid numeric data found".to_string()]);
+        assert!(result.stages.iter().all(|stage| stage.duration_ms >= 0.0));
+        
+        let invalid = process_realtime_data("{\"user_id\": 1}");
+        assert_eq!(invalid.stages.len(), 1);
+        assert_eq!((invalid.stages[0].name.as_str(), invalid.stages[0].outcome), ("parse", StageOutcome::FAILED));
+        assert!(!invalid.stages[0].details.is_empty());
+    }
+    
     #[test]
     fn test_complexity_calculation() {
         let words = vec!["hello".to_string(), "world".to_string(), "test".to_string()];
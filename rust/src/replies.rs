@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::{Arc, RwLock};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::{keywords, tenant};
+use crate::paths;
+
+// Ranks canned replies (FAQ answers, support macros) against an incoming
+// message. With an embedder registered, candidates are compared by cosine
+// similarity of their embeddings; otherwise, or when embedding fails, by
+// TF-IDF weighted keyword overlap. Reply sets are prepared once so ranking a
+// message only embeds or tokenizes the message itself, can take single
+// replies in and out, and can be saved with their embeddings so a restart
+// does not embed the whole FAQ again.
+
+pub trait Embedder: Send + Sync {
+    // None when the text cannot be embedded; every vector from one embedder
+    // must have the same length
+    fn embed(&self, text: &str) -> Option<Vec<f32>>;
+}
+
+impl<F> Embedder for F
+where
+    F: Fn(&str) -> Option<Vec<f32>> + Send + Sync,
+{
+    fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        self(text)
+    }
+}
+
+// Host embedder: writes up to `capacity` floats for the text into `out` and
+// returns how many it wrote; 0 means no embedding
+pub type EmbedderCallback = extern "C" fn(text: *const c_char, out: *mut f32, capacity: usize) -> usize;
+
+struct HostEmbedder {
+    embed: EmbedderCallback,
+    dimensions: usize,
+}
+
+impl Embedder for HostEmbedder {
+    fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        let text_c = CString::new(text).ok()?;
+        let mut vector = vec![0.0f32; self.dimensions];
+        let written = (self.embed)(text_c.as_ptr(), vector.as_mut_ptr(), vector.len());
+        if written == 0 || written > self.dimensions {
+            return None;
+        }
+        vector.truncate(written);
+        Some(vector)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchMethod {
+    EMBEDDING,
+    KEYWORDS,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplySuggestion {
+    // Id of the reply: its position in the set it was prepared from, or the
+    // id add_reply returned
+    pub index: usize,
+    pub reply: String,
+    // Cosine similarity from 0 to 1
+    pub confidence: f64,
+    pub method: MatchMethod,
+}
+
+type TermVector = HashMap<String, f64>;
+
+// Bumped when the saved layout changes; older files are still read
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone)]
+struct Candidate {
+    id: usize,
+    reply: String,
+    counts: HashMap<String, f64>,
+    terms: TermVector,
+    embedding: Option<Vec<f32>>,
+}
+
+#[derive(Clone)]
+pub struct ReplyRanker {
+    candidates: Vec<Candidate>,
+    // Replies containing each term, kept so adds and removes do not
+    // tokenize the whole set again
+    document_frequency: HashMap<String, f64>,
+    // Smoothed inverse document frequency over the replies
+    idf: HashMap<String, f64>,
+    default_idf: f64,
+    embedder: Option<Arc<dyn Embedder>>,
+    next_id: usize,
+    revision: u64,
+}
+
+// On disk: the replies with their embeddings, so loading skips the embedder
+#[derive(Serialize, Deserialize)]
+struct SavedIndex {
+    format_version: u32,
+    revision: u64,
+    next_id: usize,
+    replies: Vec<SavedReply>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedReply {
+    id: usize,
+    reply: String,
+    #[serde(default)]
+    embedding: Option<Vec<f32>>,
+}
+
+fn term_counts(text: &str) -> HashMap<String, f64> {
+    let mut counts = HashMap::new();
+    for term in keywords::terms(text, "unknown") {
+        *counts.entry(term).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+fn weigh(counts: &HashMap<String, f64>, idf: &HashMap<String, f64>, default_idf: f64) -> TermVector {
+    let mut vector: TermVector = counts.iter()
+        .map(|(term, count)| {
+            let weight = count * idf.get(term).copied().unwrap_or(default_idf);
+            (term.clone(), weight)
+        })
+        .collect();
+    let norm = vector.values().map(|w| w * w).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.values_mut().for_each(|w| *w /= norm);
+    }
+    vector
+}
+
+fn term_cosine(a: &TermVector, b: &TermVector) -> f64 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small.iter().filter_map(|(term, w)| large.get(term).map(|v| w * v)).sum()
+}
+
+fn embedding_cosine(a: &[f32], b: &[f32]) -> Option<f64> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm_a = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some((dot / (norm_a * norm_b)).clamp(0.0, 1.0))
+}
+
+impl ReplyRanker {
+    // Uses the embedder registered at the time of the call
+    pub fn new<S: AsRef<str>>(replies: &[S]) -> Self {
+        Self::with_embedder(replies, embedder())
+    }
+    
+    pub fn with_embedder<S: AsRef<str>>(replies: &[S], embedder: Option<Arc<dyn Embedder>>) -> Self {
+        let entries = replies.iter().enumerate()
+            .map(|(id, reply)| {
+                let embedding = embedder.as_ref().and_then(|embedder| embedder.embed(reply.as_ref()));
+                (id, reply.as_ref().to_string(), embedding)
+            })
+            .collect();
+        Self::from_entries(entries, embedder, replies.len(), 0)
+    }
+    
+    fn from_entries(entries: Vec<(usize, String, Option<Vec<f32>>)>, embedder: Option<Arc<dyn Embedder>>, next_id: usize, revision: u64) -> Self {
+        let mut document_frequency: HashMap<String, f64> = HashMap::new();
+        let candidates = entries.into_iter()
+            .map(|(id, reply, embedding)| {
+                let counts = term_counts(&reply);
+                for term in counts.keys() {
+                    *document_frequency.entry(term.clone()).or_insert(0.0) += 1.0;
+                }
+                Candidate { id, reply, counts, terms: HashMap::new(), embedding }
+            })
+            .collect();
+        let mut ranker = Self { candidates, document_frequency, idf: HashMap::new(), default_idf: 0.0, embedder, next_id, revision };
+        ranker.reweigh();
+        ranker
+    }
+    
+    // IDF depends on every reply, so each change weighs the term vectors
+    // again; only the changed reply is tokenized or embedded
+    fn reweigh(&mut self) {
+        let n = self.candidates.len() as f64;
+        self.idf = self.document_frequency.iter()
+            .map(|(term, df)| (term.clone(), ((1.0 + n) / (1.0 + df)).ln() + 1.0))
+            .collect();
+        self.default_idf = (1.0 + n).ln() + 1.0;
+        for candidate in &mut self.candidates {
+            candidate.terms = weigh(&candidate.counts, &self.idf, self.default_idf);
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+    
+    // Counts adds and removes, including those before the last save
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    
+    pub fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        self.embedder.as_ref().and_then(|embedder| embedder.embed(text))
+    }
+    
+    // Returns the id of the new reply; ids are never reused
+    pub fn add(&mut self, reply: &str) -> usize {
+        let embedding = self.embed(reply);
+        self.insert(reply, embedding)
+    }
+    
+    fn insert(&mut self, reply: &str, embedding: Option<Vec<f32>>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let counts = term_counts(reply);
+        for term in counts.keys() {
+            *self.document_frequency.entry(term.clone()).or_insert(0.0) += 1.0;
+        }
+        self.candidates.push(Candidate { id, reply: reply.to_string(), counts, terms: HashMap::new(), embedding });
+        self.revision += 1;
+        self.reweigh();
+        id
+    }
+    
+    pub fn remove(&mut self, id: usize) -> bool {
+        let position = match self.candidates.iter().position(|candidate| candidate.id == id) {
+            Some(position) => position,
+            None => return false,
+        };
+        let candidate = self.candidates.remove(position);
+        for term in candidate.counts.keys() {
+            if let Some(df) = self.document_frequency.get_mut(term) {
+                *df -= 1.0;
+                if *df <= 0.0 {
+                    self.document_frequency.remove(term);
+                }
+            }
+        }
+        self.revision += 1;
+        self.reweigh();
+        true
+    }
+    
+    pub fn to_json(&self) -> Result<String, String> {
+        let saved = SavedIndex {
+            format_version: INDEX_FORMAT_VERSION,
+            revision: self.revision,
+            next_id: self.next_id,
+            replies: self.candidates.iter()
+                .map(|candidate| SavedReply { id: candidate.id, reply: candidate.reply.clone(), embedding: candidate.embedding.clone() })
+                .collect(),
+        };
+        serde_json::to_string(&saved).map_err(|e| e.to_string())
+    }
+    
+    // Saved embeddings are reused as they are; replies saved without one are
+    // embedded with the given embedder
+    pub fn from_json(json: &str, embedder: Option<Arc<dyn Embedder>>) -> Result<Self, String> {
+        let saved: SavedIndex = serde_json::from_str(json).map_err(|e| format!("Invalid reply index: {}", e))?;
+        if saved.format_version == 0 || saved.format_version > INDEX_FORMAT_VERSION {
+            return Err(format!("Unsupported reply index format {}, expected at most {}", saved.format_version, INDEX_FORMAT_VERSION));
+        }
+        let mut next_id = saved.next_id;
+        let entries = saved.replies.into_iter()
+            .map(|saved| {
+                next_id = next_id.max(saved.id + 1);
+                let embedding = saved.embedding.or_else(|| embedder.as_ref().and_then(|embedder| embedder.embed(&saved.reply)));
+                (saved.id, saved.reply, embedding)
+            })
+            .collect();
+        Ok(Self::from_entries(entries, embedder, next_id, saved.revision))
+    }
+    
+    // Best replies first, leaving out those with nothing in common with the
+    // message
+    pub fn rank(&self, message: &str, limit: usize) -> Vec<ReplySuggestion> {
+        let message_embedding = self.embed(message);
+        let message_terms = weigh(&term_counts(message), &self.idf, self.default_idf);
+        
+        let mut suggestions: Vec<ReplySuggestion> = self.candidates.iter()
+            .filter_map(|candidate| {
+                let embedded = message_embedding.as_deref()
+                    .zip(candidate.embedding.as_deref())
+                    .and_then(|(a, b)| embedding_cosine(a, b));
+                let (confidence, method) = match embedded {
+                    Some(similarity) => (similarity, MatchMethod::EMBEDDING),
+                    None => (term_cosine(&message_terms, &candidate.terms), MatchMethod::KEYWORDS),
+                };
+                (confidence > 0.0).then(|| ReplySuggestion { index: candidate.id, reply: candidate.reply.clone(), confidence, method })
+            })
+            .collect();
+        // Ties keep reply set order
+        suggestions.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal).then(a.index.cmp(&b.index)));
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+lazy_static! {
+    static ref EMBEDDER: RwLock<Option<Arc<dyn Embedder>>> = RwLock::new(None);
+    static ref REPLY_SETS: RwLock<HashMap<String, Arc<ReplyRanker>>> = RwLock::new(HashMap::new());
+}
+
+// Reply sets keep the embedder they were prepared with; set them again after
+// switching embedders
+pub fn set_embedder(embedder: Option<Arc<dyn Embedder>>) {
+    *EMBEDDER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = embedder;
+}
+
+pub fn set_host_embedder(embed: EmbedderCallback, dimensions: usize) -> Result<(), String> {
+    if dimensions == 0 {
+        return Err("Embedding dimensions must be positive".to_string());
+    }
+    set_embedder(Some(Arc::new(HostEmbedder { embed, dimensions })));
+    Ok(())
+}
+
+pub fn embedder() -> Option<Arc<dyn Embedder>> {
+    EMBEDDER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+// Named and scoped to the current tenant, so each bot keeps its own FAQ
+pub fn set_reply_set<S: AsRef<str>>(name: &str, replies: &[S]) {
+    let ranker = Arc::new(ReplyRanker::new(replies));
+    REPLY_SETS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(tenant::scope_key(name), ranker);
+}
+
+pub fn remove_reply_set(name: &str) -> bool {
+    REPLY_SETS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&tenant::scope_key(name)).is_some()
+}
+
+fn reply_set(name: &str) -> Option<Arc<ReplyRanker>> {
+    REPLY_SETS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&tenant::scope_key(name)).cloned()
+}
+
+// None when there is no such set. The reply is embedded before the set is
+// locked, so a slow embedder does not hold up suggestions
+pub fn add_reply(name: &str, reply: &str) -> Option<usize> {
+    let embedding = reply_set(name)?.embed(reply);
+    let mut sets = REPLY_SETS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let ranker = sets.get_mut(&tenant::scope_key(name))?;
+    Some(Arc::make_mut(ranker).insert(reply, embedding))
+}
+
+pub fn remove_reply(name: &str, id: usize) -> bool {
+    let mut sets = REPLY_SETS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match sets.get_mut(&tenant::scope_key(name)) {
+        Some(ranker) => Arc::make_mut(ranker).remove(id),
+        None => false,
+    }
+}
+
+// Returns the number of replies written
+pub fn save_reply_set(name: &str, path: &str) -> Result<usize, String> {
+    let ranker = reply_set(name).ok_or_else(|| format!("Unknown reply set: {}", name))?;
+    paths::atomic_write(path, "reply index", ranker.to_json()?.as_bytes())?;
+    Ok(ranker.len())
+}
+
+// Replaces the named set with one written by save_reply_set, using the
+// embedder registered now for replies saved without an embedding
+pub fn load_reply_set(name: &str, path: &str) -> Result<usize, String> {
+    let contents = paths::read_data(path, "reply index")?;
+    let contents = String::from_utf8(contents).map_err(|e| format!("Invalid reply index: {}", e))?;
+    let ranker = ReplyRanker::from_json(&contents, embedder())?;
+    let count = ranker.len();
+    REPLY_SETS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(tenant::scope_key(name), Arc::new(ranker));
+    Ok(count)
+}
+
+pub fn suggest(name: &str, message: &str, limit: usize) -> Option<Vec<ReplySuggestion>> {
+    Some(reply_set(name)?.rank(message, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const FAQ: [&str; 4] = [
+        "To reset your password, open Settings and tap Forgot password.",
+        "Refunds are processed within 5 business days after we receive the item.",
+        "You can change your delivery address until the order ships.",
+        "Our support team is available every day from 9 to 18.",
+    ];
+    
+    #[test]
+    fn test_keyword_ranking() {
+        let ranker = ReplyRanker::with_embedder(&FAQ, None);
+        let suggestions = ranker.rank("how do I reset my password?", 2);
+        assert_eq!(suggestions[0].index, 0);
+        assert_eq!(suggestions[0].method, MatchMethod::KEYWORDS);
+        assert!(suggestions[0].confidence > 0.0 && suggestions[0].confidence <= 1.0);
+        assert_eq!(suggestions.len(), 1);
+        assert!(ranker.rank("qwerty zxcvb", 3).is_empty());
+    }
+    
+    #[test]
+    fn test_embeddings_take_precedence() {
+        // Toy embedder: "money" and "refunds" land on the same axis
+        let embedder: Arc<dyn Embedder> = Arc::new(|text: &str| {
+            let text = text.to_lowercase();
+            if text.contains("xyz") {
+                return None;
+            }
+            let axis = |words: &[&str]| if words.iter().any(|w| text.contains(w)) { 1.0 } else { 0.0 };
+            Some(vec![axis(&["money", "refund"]), axis(&["password"]), axis(&["address", "deliver"]), 0.1])
+        });
+        let ranker = ReplyRanker::with_embedder(&FAQ, Some(embedder));
+        let suggestions = ranker.rank("when do I get my money back", 1);
+        assert_eq!((suggestions[0].index, suggestions[0].method), (1, MatchMethod::EMBEDDING));
+        // The message failing to embed falls back to keywords
+        assert_eq!(ranker.rank("xyz password reset", 1)[0].method, MatchMethod::KEYWORDS);
+    }
+    
+    #[test]
+    fn test_incremental_updates_and_persistence() {
+        let mut ranker = ReplyRanker::with_embedder(&FAQ[..3], None);
+        let id = ranker.add(FAQ[3]);
+        assert_eq!((id, ranker.len(), ranker.revision()), (3, 4, 1));
+        assert!(ranker.remove(0));
+        assert!(!ranker.remove(0));
+        // Same weights as building the remaining replies from scratch
+        let fresh = ReplyRanker::with_embedder(&FAQ[1..], None);
+        let message = "is support available to change the delivery address?";
+        let (updated, rebuilt) = (ranker.rank(message, 1), fresh.rank(message, 1));
+        assert_eq!(updated[0].index, 2);
+        assert!((updated[0].confidence - rebuilt[0].confidence).abs() < 1e-9);
+        assert_eq!(ranker.add("Gift cards never expire."), 4);
+        
+        // Loading reuses the saved embeddings instead of calling the embedder
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let embedder: Arc<dyn Embedder> = Arc::new(move |text: &str| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Some(vec![text.len() as f32, 1.0])
+        });
+        let embedded = ReplyRanker::with_embedder(&FAQ, Some(embedder.clone()));
+        let loaded = ReplyRanker::from_json(&embedded.to_json().unwrap(), Some(embedder)).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), FAQ.len());
+        assert_eq!(loaded.rank("refunds", 4), embedded.rank("refunds", 4));
+        
+        let restored = ReplyRanker::from_json(&ranker.to_json().unwrap(), None).unwrap();
+        assert_eq!((restored.len(), restored.revision()), (4, 3));
+        assert_eq!(restored.rank(message, 1), ranker.rank(message, 1));
+        let newer = ranker.to_json().unwrap().replace("\"format_version\":1", "\"format_version\":9");
+        assert!(ReplyRanker::from_json(&newer, None).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::cell::Cell;
+use std::fmt;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+pub const REDACTED: &str = "[REDACTED]";
+
+thread_local! {
+    static EXPOSE_SECRETS: Cell<bool> = const { Cell::new(false) };
+}
+
+// Secrets serialize as [REDACTED] except inside this scope, which the config
+// layer uses when it needs the real values to merge or dump layers
+pub fn with_secrets_exposed<T>(f: impl FnOnce() -> T) -> T {
+    let previous = EXPOSE_SECRETS.with(|flag| flag.replace(true));
+    let result = f();
+    EXPOSE_SECRETS.with(|flag| flag.set(previous));
+    result
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+    
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if EXPOSE_SECRETS.with(|flag| flag.get()) {
+            serializer.serialize_str(&self.0)
+        } else {
+            serializer.serialize_str(REDACTED)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+// An API key as written in config: either a bare string or
+// {"key": "...", "expires_at": "2025-01-01T00:00:00Z"}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ApiKeyRepr", into = "ApiKeyRepr")]
+pub struct ApiKeyEntry {
+    pub key: Secret,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyEntry {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| expires_at <= Utc::now()).unwrap_or(false)
+    }
+}
+
+// Keys without expiry keep the bare string form so existing configs and
+// provenance paths stay unchanged
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ApiKeyRepr {
+    Plain(Secret),
+    Detailed {
+        key: Secret,
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl From<ApiKeyRepr> for ApiKeyEntry {
+    fn from(repr: ApiKeyRepr) -> Self {
+        match repr {
+            ApiKeyRepr::Plain(key) => ApiKeyEntry { key, expires_at: None },
+            ApiKeyRepr::Detailed { key, expires_at } => ApiKeyEntry { key, expires_at },
+        }
+    }
+}
+
+impl From<ApiKeyEntry> for ApiKeyRepr {
+    fn from(entry: ApiKeyEntry) -> Self {
+        match entry.expires_at {
+            None => ApiKeyRepr::Plain(entry.key),
+            Some(expires_at) => ApiKeyRepr::Detailed { key: entry.key, expires_at: Some(expires_at) },
+        }
+    }
+}
+
+// Entries of the encrypted secrets section hold base64 crypto::seal payloads
+pub fn open_sealed(sealed_b64: &str, master_key: &str) -> Result<Secret, String> {
+    let sealed = general_purpose::STANDARD.decode(sealed_b64.trim())
+        .map_err(|e| format!("Invalid sealed secret encoding: {}", e))?;
+    let plaintext = open_bytes(&sealed, master_key)?;
+    String::from_utf8(plaintext)
+        .map(Secret)
+        .map_err(|e| format!("Sealed secret is not UTF-8: {}", e))
+}
+
+#[cfg(feature = "crypto")]
+fn open_bytes(sealed: &[u8], master_key: &str) -> Result<Vec<u8>, String> {
+    crate::crypto::open(sealed, master_key).map_err(|e| format!("Failed to open sealed secret: {}", e))
+}
+
+// Plain api_keys keep working; only the encrypted section needs the cipher
+#[cfg(not(feature = "crypto"))]
+fn open_bytes(_sealed: &[u8], _master_key: &str) -> Result<Vec<u8>, String> {
+    Err("Sealed secrets require the crypto feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_secret_redaction() {
+        let secret = Secret::new("s3cret");
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"[REDACTED]\"");
+        assert_eq!(with_secrets_exposed(|| serde_json::to_string(&secret).unwrap()), "\"s3cret\"");
+    }
+    
+    #[test]
+    fn test_api_key_entry_forms() {
+        let plain: ApiKeyEntry = serde_json::from_str("\"abc\"").unwrap();
+        assert_eq!(plain.key.expose(), "abc");
+        assert!(!plain.is_expired());
+        
+        let expired: ApiKeyEntry = serde_json::from_str(r#"{"key": "abc", "expires_at": "2000-01-01T00:00:00Z"}"#).unwrap();
+        assert!(expired.is_expired());
+        
+        // Keys without expiry round-trip to the bare string form
+        assert_eq!(with_secrets_exposed(|| serde_json::to_string(&plain).unwrap()), "\"abc\"");
+    }
+    
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_open_sealed() {
+        let sealed = general_purpose::STANDARD.encode(crate::crypto::seal(b"token-123", "master").unwrap());
+        assert_eq!(open_sealed(&sealed, "master").unwrap().expose(), "token-123");
+        assert!(open_sealed(&sealed, "wrong").is_err());
+    }
+}
\ No newline at end of file
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use lazy_static::lazy_static;
-use dashmap::DashMap;
 use regex::Regex;
+use crate::alerts::{self, AlertEvent};
+use crate::parsers;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RateLimitInfo {
@@ -31,7 +32,96 @@ pub enum SecuritySeverity {
     CRITICAL,
 }
 
+// Requests are counted per identifier over this window
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+// How long an identifier stays limited after going over
+const BLOCK_DURATION: Duration = Duration::from_secs(300);
+
+// What a rate limit check decided, or would decide for a dry run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    // Requests left in the current window after this one
+    pub remaining: u32,
+    // Seconds until the identifier may send again; 0 when allowed
+    pub retry_after_seconds: u64,
+}
+
+impl RateLimitDecision {
+    fn allowed(remaining: u32) -> Self {
+        Self { allowed: true, remaining, retry_after_seconds: 0 }
+    }
+    
+    fn limited(retry_after: Duration) -> Self {
+        Self { allowed: false, remaining: 0, retry_after_seconds: retry_after.as_millis().div_ceil(1000) as u64 }
+    }
+}
+
+// Source of time for rate limits and IP blocks, so policies can be tested
+// against a SimulatedClock instead of waiting out real windows
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Stands still until advanced
+pub struct SimulatedClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl SimulatedClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now(), elapsed: Mutex::new(Duration::ZERO) }
+    }
+    
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += by;
+    }
+    
+    // Time since the clock was created; it can only move forward
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        let mut current = self.elapsed.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *current = (*current).max(elapsed);
+    }
+    
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for SimulatedClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+// One request of a simulate() trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedRequest {
+    // Milliseconds since the start of the trace; never decreasing
+    pub at_ms: u64,
+    pub identifier: String,
+    // Only asks whether the request would be limited
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SecurityConfig {
     pub max_requests_per_minute: u32,
     pub max_request_size_bytes: usize,
@@ -42,16 +132,35 @@ pub struct SecurityConfig {
     pub max_concurrent_connections: usize,
 }
 
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            max_requests_per_minute: 100,
+            max_request_size_bytes: 1024 * 1024, // 1MB
+            enable_input_validation: true,
+            enable_threat_detection: true,
+            blocked_ips: vec![],
+            allowed_origins: vec!["*".to_string()],
+            max_concurrent_connections: 1000,
+        }
+    }
+}
+
 pub struct SecurityManager {
-    rate_limits: DashMap<String, RateLimitInfo>,
+    rate_limits: Mutex<HashMap<String, RateLimitInfo>>,
     security_events: Arc<Mutex<Vec<SecurityEvent>>>,
-    blocked_ips: DashMap<String, Instant>,
+    blocked_ips: Mutex<HashMap<String, Instant>>,
     config: SecurityConfig,
     threat_patterns: Vec<Regex>,
+    clock: Arc<dyn Clock>,
 }
 
 impl SecurityManager {
     pub fn new(config: SecurityConfig) -> Self {
+        Self::with_clock(config, Arc::new(SystemClock))
+    }
+    
+    pub fn with_clock(config: SecurityConfig, clock: Arc<dyn Clock>) -> Self {
         let threat_patterns = vec![
             Regex::new(r"(?i)(script|javascript|vbscript|onload|onerror)").unwrap(),
             Regex::new(r"(?i)(union|select|insert|update|delete|drop|create|alter)").unwrap(),
@@ -60,37 +169,44 @@ impl SecurityManager {
         ];
         
         Self {
-            rate_limits: DashMap::new(),
+            rate_limits: Mutex::new(HashMap::new()),
             security_events: Arc::new(Mutex::new(Vec::new())),
-            blocked_ips: DashMap::new(),
+            blocked_ips: Mutex::new(HashMap::new()),
             config,
             threat_patterns,
+            clock,
         }
     }
     
     pub fn check_rate_limit(&self, identifier: &str) -> bool {
-        let now = Instant::now();
-        let window_duration = Duration::from_secs(60);
+        self.rate_limit(identifier).allowed
+    }
+    
+    // Counts the request and decides whether it may go through
+    pub fn rate_limit(&self, identifier: &str) -> RateLimitDecision {
+        let now = self.clock.now();
+        let max_requests = self.config.max_requests_per_minute;
         
-        if let Some(mut info) = self.rate_limits.get_mut(identifier) {
+        let mut rate_limits = lock_map(&self.rate_limits);
+        if let Some(info) = rate_limits.get_mut(identifier) {
             // Check if still blocked
             if let Some(blocked_until) = info.blocked_until {
                 if now < blocked_until {
-                    return false;
+                    return RateLimitDecision::limited(blocked_until - now);
                 }
             }
             
             // Check if window has expired
-            if now.duration_since(info.window_start) > window_duration {
+            if now.duration_since(info.window_start) > RATE_WINDOW {
                 info.requests = 1;
                 info.window_start = now;
                 info.blocked_until = None;
-                return true;
+                return RateLimitDecision::allowed(max_requests.saturating_sub(1));
             }
             
             // Check if limit exceeded
-            if info.requests >= self.config.max_requests_per_minute {
-                info.blocked_until = Some(now + Duration::from_secs(300)); // 5 minute block
+            if info.requests >= max_requests {
+                info.blocked_until = Some(now + BLOCK_DURATION);
                 self.record_security_event(
                     "RATE_LIMIT_EXCEEDED",
                     Some(identifier.to_string()),
@@ -98,11 +214,11 @@ impl SecurityManager {
                     format!("Rate limit exceeded for {}", identifier),
                     SecuritySeverity::MEDIUM,
                 );
-                return false;
+                return RateLimitDecision::limited(BLOCK_DURATION);
             }
             
             info.requests += 1;
-            true
+            RateLimitDecision::allowed(max_requests.saturating_sub(info.requests))
         } else {
             // First request
             let info = RateLimitInfo {
@@ -110,8 +226,27 @@ impl SecurityManager {
                 window_start: now,
                 blocked_until: None,
             };
-            self.rate_limits.insert(identifier.to_string(), info);
-            true
+            rate_limits.insert(identifier.to_string(), info);
+            RateLimitDecision::allowed(max_requests.saturating_sub(1))
+        }
+    }
+    
+    // What rate_limit would decide now, without counting the request,
+    // blocking the identifier or recording an event
+    pub fn dry_run_rate_limit(&self, identifier: &str) -> RateLimitDecision {
+        let now = self.clock.now();
+        let max_requests = self.config.max_requests_per_minute;
+        
+        let rate_limits = lock_map(&self.rate_limits);
+        let info = match rate_limits.get(identifier) {
+            Some(info) => info,
+            None => return RateLimitDecision::allowed(max_requests.saturating_sub(1)),
+        };
+        match info.blocked_until {
+            Some(blocked_until) if now < blocked_until => RateLimitDecision::limited(blocked_until - now),
+            _ if now.duration_since(info.window_start) > RATE_WINDOW => RateLimitDecision::allowed(max_requests.saturating_sub(1)),
+            _ if info.requests >= max_requests => RateLimitDecision::limited(BLOCK_DURATION),
+            _ => RateLimitDecision::allowed(max_requests.saturating_sub(info.requests + 1)),
         }
     }
     
@@ -179,8 +314,8 @@ impl SecurityManager {
     
     fn validate_data_input(&self, input: &str) -> Result<(), String> {
         // Check if input contains valid numeric data
-        let numbers: Vec<&str> = input.split(|c| c == ',' || c == ' ' || c == '\n' || c == '\t').collect();
-        let valid_numbers = numbers.iter().filter(|s| s.trim().parse::<f64>().is_ok()).count();
+        let numbers: Vec<&str> = parsers::number_tokens(input).collect();
+        let valid_numbers = numbers.iter().filter(|s| parsers::parse_number(s).is_some()).count();
         
         if valid_numbers == 0 {
             return Err("No valid numeric data found".to_string());
@@ -199,45 +334,27 @@ impl SecurityManager {
             return Err("Invalid JSON format".to_string());
         }
         
-        // Check for balanced braces/brackets
-        let mut brace_count = 0;
-        let mut bracket_count = 0;
-        
-        for ch in input.chars() {
-            match ch {
-                '{' => brace_count += 1,
-                '}' => brace_count -= 1,
-                '[' => bracket_count += 1,
-                ']' => bracket_count -= 1,
-                _ => {}
-            }
-            
-            if brace_count < 0 || bracket_count < 0 {
-                return Err("Unbalanced JSON structure".to_string());
-            }
-        }
-        
-        if brace_count != 0 || bracket_count != 0 {
-            return Err("Unbalanced JSON structure".to_string());
-        }
+        // Balanced, string-aware and depth-limited
+        parsers::check_json_structure(input).map_err(|e| e.to_string())?;
         
         Ok(())
     }
     
     pub fn is_ip_blocked(&self, ip: &str) -> bool {
-        if let Some(blocked_until) = self.blocked_ips.get(ip) {
-            if Instant::now() < *blocked_until {
+        let mut blocked_ips = lock_map(&self.blocked_ips);
+        if let Some(blocked_until) = blocked_ips.get(ip) {
+            if self.clock.now() < *blocked_until {
                 return true;
             } else {
-                self.blocked_ips.remove(ip);
+                blocked_ips.remove(ip);
             }
         }
         false
     }
     
     pub fn block_ip(&self, ip: &str, duration_seconds: u64) {
-        let blocked_until = Instant::now() + Duration::from_secs(duration_seconds);
-        self.blocked_ips.insert(ip.to_string(), blocked_until);
+        let blocked_until = self.clock.now() + Duration::from_secs(duration_seconds);
+        lock_map(&self.blocked_ips).insert(ip.to_string(), blocked_until);
         
         self.record_security_event(
             "IP_BLOCKED",
@@ -249,8 +366,12 @@ impl SecurityManager {
     }
     
     pub fn record_security_event(&self, event_type: &str, source_ip: Option<String>, user_id: Option<String>, details: String, severity: SecuritySeverity) {
+        if matches!(severity, SecuritySeverity::HIGH | SecuritySeverity::CRITICAL) {
+            raise_alert(event_type, source_ip.as_deref(), user_id.as_deref(), &details, &severity);
+        }
+        
         let event = SecurityEvent {
-            timestamp: Instant::now(),
+            timestamp: self.clock.now(),
             event_type: event_type.to_string(),
             source_ip,
             user_id,
@@ -293,29 +414,32 @@ impl SecurityManager {
     }
     
     pub fn get_rate_limit_info(&self, identifier: &str) -> Option<RateLimitInfo> {
-        self.rate_limits.get(identifier).map(|info| info.clone())
+        lock_map(&self.rate_limits).get(identifier).cloned()
     }
     
     pub fn clear_rate_limits(&self) {
-        self.rate_limits.clear();
+        lock_map(&self.rate_limits).clear();
     }
     
     pub fn get_blocked_ips(&self) -> Vec<String> {
-        self.blocked_ips.iter().map(|entry| entry.key().clone()).collect()
+        lock_map(&self.blocked_ips).keys().cloned().collect()
     }
 }
 
+// Rate limit and block lists stay usable after a panic elsewhere poisoned
+// their lock
+fn lock_map<V>(map: &Mutex<HashMap<String, V>>) -> MutexGuard<'_, HashMap<String, V>> {
+    map.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 // Global security manager
 lazy_static! {
-    static ref SECURITY_MANAGER: Arc<SecurityManager> = Arc::new(SecurityManager::new(SecurityConfig {
-        max_requests_per_minute: 100,
-        max_request_size_bytes: 1024 * 1024, // 1MB
-        enable_input_validation: true,
-        enable_threat_detection: true,
-        blocked_ips: vec![],
-        allowed_origins: vec!["*".to_string()],
-        max_concurrent_connections: 1000,
-    }));
+    static ref SECURITY_MANAGER: Arc<SecurityManager> = Arc::new(SecurityManager::new(SecurityConfig::default()));
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&SECURITY_MANAGER);
+    Ok(())
 }
 
 // Public security functions
@@ -323,6 +447,30 @@ pub fn check_rate_limit(identifier: &str) -> bool {
     SECURITY_MANAGER.check_rate_limit(identifier)
 }
 
+pub fn dry_run_rate_limit(identifier: &str) -> RateLimitDecision {
+    SECURITY_MANAGER.dry_run_rate_limit(identifier)
+}
+
+// Replays a request trace against config on a simulated clock, one decision
+// per request, without touching the live limiter
+pub fn simulate(config: SecurityConfig, requests: &[SimulatedRequest]) -> Result<Vec<RateLimitDecision>, String> {
+    let clock = Arc::new(SimulatedClock::new());
+    let manager = SecurityManager::with_clock(config, clock.clone());
+    let mut previous = 0;
+    requests.iter().enumerate().map(|(i, request)| {
+        if request.at_ms < previous {
+            return Err(format!("Request {} at {} ms is earlier than the one before it", i, request.at_ms));
+        }
+        previous = request.at_ms;
+        clock.set_elapsed(Duration::from_millis(request.at_ms));
+        Ok(if request.dry_run {
+            manager.dry_run_rate_limit(&request.identifier)
+        } else {
+            manager.rate_limit(&request.identifier)
+        })
+    }).collect()
+}
+
 pub fn validate_input(input: &str, input_type: &str) -> Result<(), String> {
     SECURITY_MANAGER.validate_input(input, input_type)
 }
@@ -351,6 +499,28 @@ pub fn get_blocked_ips() -> Vec<String> {
     SECURITY_MANAGER.get_blocked_ips()
 }
 
+// High and critical events also go out as alerts (host callback, webhooks)
+fn raise_alert(event_type: &str, source_ip: Option<&str>, user_id: Option<&str>, details: &str, severity: &SecuritySeverity) {
+    let mut labels = HashMap::new();
+    labels.insert("severity".to_string(), format!("{:?}", severity).to_lowercase());
+    if let Some(source_ip) = source_ip {
+        labels.insert("source_ip".to_string(), source_ip.to_string());
+    }
+    if let Some(user_id) = user_id {
+        labels.insert("user_id".to_string(), user_id.to_string());
+    }
+    
+    alerts::emit(AlertEvent {
+        rule: event_type.to_string(),
+        source: "security".to_string(),
+        message: details.to_string(),
+        count: 1,
+        window_seconds: 0,
+        timestamp: chrono::Utc::now(),
+        labels,
+    });
+}
+
 // Utility functions
 pub fn sanitize_input(input: &str) -> String {
     // Remove null bytes and control characters
@@ -433,6 +603,44 @@ mod tests {
         assert!(manager.is_ip_blocked("192.168.1.1"));
     }
     
+    #[test]
+    fn test_dry_run_on_simulated_clock() {
+        let clock = Arc::new(SimulatedClock::new());
+        let manager = SecurityManager::with_clock(SecurityConfig { max_requests_per_minute: 2, ..Default::default() }, clock.clone());
+        
+        // Dry runs never use up the window
+        for _ in 0..5 {
+            assert_eq!(manager.dry_run_rate_limit("sim_user"), RateLimitDecision::allowed(1));
+        }
+        assert!(manager.check_rate_limit("sim_user"));
+        assert_eq!(manager.rate_limit("sim_user"), RateLimitDecision::allowed(0));
+        assert_eq!(manager.dry_run_rate_limit("sim_user"), RateLimitDecision::limited(BLOCK_DURATION));
+        assert!(manager.get_rate_limit_info("sim_user").unwrap().blocked_until.is_none());
+        
+        assert!(!manager.check_rate_limit("sim_user"));
+        clock.advance(Duration::from_secs(200));
+        assert_eq!(manager.dry_run_rate_limit("sim_user").retry_after_seconds, 100);
+        clock.advance(Duration::from_secs(100));
+        assert!(manager.check_rate_limit("sim_user"));
+    }
+    
+    #[test]
+    fn test_simulate_trace() {
+        let trace: Vec<SimulatedRequest> = serde_json::from_str(r#"[
+            {"at_ms": 0, "identifier": "a"},
+            {"at_ms": 10, "identifier": "a"},
+            {"at_ms": 20, "identifier": "a", "dry_run": true},
+            {"at_ms": 30, "identifier": "a"},
+            {"at_ms": 61000, "identifier": "b"}
+        ]"#).unwrap();
+        let config: SecurityConfig = serde_json::from_str(r#"{"max_requests_per_minute": 2}"#).unwrap();
+        let allowed: Vec<bool> = simulate(config.clone(), &trace).unwrap().iter().map(|d| d.allowed).collect();
+        assert_eq!(allowed, vec![true, true, false, false, true]);
+        
+        let backwards = vec![trace[1].clone(), trace[0].clone()];
+        assert!(simulate(config, &backwards).is_err());
+    }
+    
     #[test]
     fn test_security_events() {
         let manager = SecurityManager::new(SecurityConfig {
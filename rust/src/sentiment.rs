@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::config::{AnalysisConfig, AppConfig, NeutralBand};
+use crate::stopwords;
+
+// Lexicon-based sentiment in the style of VADER: each known word or emoji has
+// a valence between -4 and 4, adjusted by preceding intensifiers and
+// negations, "but" clauses, capitalised shouting and exclamation marks, then
+// normalised into a compound score between -1 and 1. Labels come from the
+// neutral band in the config, per language where one is set, since a thin
+// lexicon such as the Persian one scores fewer words and stays nearer 0.
+
+// VADER's constants
+const NEGATION_SCALAR: f64 = -0.74;
+const BOOSTER_INCREMENT: f64 = 0.293;
+const CAPS_INCREMENT: f64 = 0.733;
+const EXCLAMATION_INCREMENT: f64 = 0.292;
+const MAX_EXCLAMATIONS: usize = 4;
+const NORMALIZATION_ALPHA: f64 = 15.0;
+
+// Negations and intensifiers reach this many words ahead
+const SCOPE: usize = 3;
+
+const DEFAULT_LEXICON: &[(&str, f64)] = &[
+    ("good", 1.9), ("great", 3.1), ("excellent", 2.7), ("amazing", 2.8), ("wonderful", 2.7),
+    ("fantastic", 2.6), ("perfect", 2.7), ("beautiful", 2.9), ("nice", 1.8), ("lovely", 2.8),
+    ("happy", 2.7), ("joy", 2.8), ("love", 3.2), ("like", 1.5), ("enjoy", 2.2),
+    ("brilliant", 2.8), ("outstanding", 3.0), ("superb", 3.1), ("magnificent", 2.9),
+    ("delightful", 2.9), ("pleased", 1.9), ("thanks", 1.9), ("thank", 1.5), ("awesome", 3.1),
+    ("bad", -2.5), ("terrible", -2.1), ("awful", -2.0), ("horrible", -2.5), ("disgusting", -2.4),
+    ("hate", -2.7), ("dislike", -1.6), ("sad", -2.1), ("angry", -2.3), ("furious", -2.7),
+    ("upset", -1.6), ("disappointed", -1.9), ("worried", -1.2), ("scared", -1.9),
+    ("dreadful", -2.7), ("atrocious", -2.5), ("abysmal", -2.5), ("appalling", -2.7),
+    ("repulsive", -2.2), ("revolting", -2.8), ("broken", -1.5), ("worst", -3.1), ("useless", -1.8),
+    ("خوب", 1.9), ("عالی", 3.0), ("عالیه", 3.0), ("ممتاز", 2.7), ("ممنون", 1.9), ("مرسی", 1.9),
+    ("دوست", 1.5), ("قشنگ", 2.5), ("زیبا", 2.7), ("خوشحال", 2.7), ("عشق", 3.0),
+    ("بد", -2.5), ("بدی", -2.5), ("بدیه", -2.5), ("افتضاح", -3.0), ("متنفرم", -2.9),
+    ("ناراحت", -2.1), ("عصبانی", -2.3), ("غمگین", -2.1), ("خراب", -1.5), ("زشت", -2.2),
+    ("😀", 2.3), ("😃", 2.3), ("😄", 2.3), ("😁", 2.0), ("😊", 2.2), ("🙂", 1.3), ("😍", 2.8),
+    ("🥰", 2.8), ("❤", 2.6), ("👍", 1.9), ("🎉", 2.2), ("😂", 1.5), ("🔥", 1.2),
+    ("😞", -1.9), ("😢", -2.1), ("😭", -2.2), ("😡", -2.6), ("😠", -2.3), ("👎", -1.9),
+    ("💔", -2.5), ("🙁", -1.3), ("☹", -1.5), ("🤬", -2.8), ("😤", -1.6),
+];
+
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "nobody", "nothing", "neither", "nor", "nowhere", "without",
+    "cannot", "cant", "isnt", "arent", "wasnt", "werent", "dont", "doesnt", "didnt", "wont",
+    "wouldnt", "couldnt", "shouldnt", "hasnt", "havent", "hadnt", "aint",
+    "نه", "نیست", "نبود", "هرگز", "بدون", "نمی",
+];
+
+const BOOSTERS: &[(&str, f64)] = &[
+    ("very", BOOSTER_INCREMENT), ("really", BOOSTER_INCREMENT), ("extremely", BOOSTER_INCREMENT),
+    ("so", BOOSTER_INCREMENT), ("too", BOOSTER_INCREMENT), ("incredibly", BOOSTER_INCREMENT),
+    ("totally", BOOSTER_INCREMENT), ("absolutely", BOOSTER_INCREMENT), ("super", BOOSTER_INCREMENT),
+    ("slightly", -BOOSTER_INCREMENT), ("barely", -BOOSTER_INCREMENT), ("somewhat", -BOOSTER_INCREMENT),
+    ("kinda", -BOOSTER_INCREMENT), ("little", -BOOSTER_INCREMENT),
+    ("خیلی", BOOSTER_INCREMENT), ("بسیار", BOOSTER_INCREMENT), ("واقعا", BOOSTER_INCREMENT),
+    ("کمی", -BOOSTER_INCREMENT), ("یکم", -BOOSTER_INCREMENT),
+];
+
+const CONTRASTS: &[&str] = &["but", "however", "اما", "ولی"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentimentScore {
+    pub label: String,
+    pub compound: f64,
+    // Proportions of positive, negative and neutral tokens
+    pub positive: f64,
+    pub negative: f64,
+    pub neutral: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentimentAnalyzer {
+    lexicon: HashMap<String, f64>,
+}
+
+impl Default for SentimentAnalyzer {
+    fn default() -> Self {
+        Self::with_entries(DEFAULT_LEXICON.iter().map(|(word, valence)| (word.to_string(), *valence)))
+    }
+}
+
+struct Token {
+    // Lowercased, apostrophes removed ("don't" -> "dont")
+    key: String,
+    shouted: bool,
+}
+
+fn tokenize(text: &str) -> (Vec<Token>, bool) {
+    let mut tokens = Vec::new();
+    let mut has_lowercase = false;
+    
+    for raw in text.split_whitespace() {
+        let mut word = String::new();
+        let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+            if !word.is_empty() {
+                let shouted = word.chars().count() > 1 && word.chars().any(char::is_uppercase)
+                    && !word.chars().any(char::is_lowercase);
+                tokens.push(Token { key: word.to_lowercase(), shouted });
+                word.clear();
+            }
+        };
+        
+        for c in raw.chars() {
+            if c.is_alphanumeric() || c == '\u{200c}' {
+                has_lowercase |= c.is_lowercase();
+                word.push(c);
+            } else if c == '\'' || c == '’' {
+                continue;
+            } else if is_emoji(c) {
+                flush(&mut word, &mut tokens);
+                tokens.push(Token { key: c.to_string(), shouted: false });
+            } else {
+                flush(&mut word, &mut tokens);
+            }
+        }
+        flush(&mut word, &mut tokens);
+    }
+    (tokens, has_lowercase)
+}
+
+// Neutral bands from the config, language bands keyed by language code
+#[derive(Debug, Clone, PartialEq)]
+struct LabelBands {
+    default: NeutralBand,
+    languages: HashMap<String, NeutralBand>,
+}
+
+impl LabelBands {
+    fn from_config(config: &AnalysisConfig) -> Self {
+        let threshold = config.sentiment_threshold;
+        Self {
+            default: config.sentiment_neutral_band.unwrap_or(NeutralBand { negative: -threshold, positive: threshold }),
+            languages: config.language_sentiment_bands.iter().map(|(language, band)| (language_key(language), *band)).collect(),
+        }
+    }
+    
+    fn label(&self, score: f64, language: Option<&str>) -> &'static str {
+        let band = language.and_then(|language| self.languages.get(&language_key(language))).unwrap_or(&self.default);
+        match score {
+            s if s > band.positive => "positive",
+            s if s < band.negative => "negative",
+            _ => "neutral",
+        }
+    }
+}
+
+// "fa", "fas" and "Persian" alike
+fn language_key(language: &str) -> String {
+    stopwords::language_code(language).map(str::to_string).unwrap_or_else(|| language.trim().to_lowercase())
+}
+
+lazy_static! {
+    static ref BANDS: RwLock<LabelBands> = RwLock::new(LabelBands::from_config(&AppConfig::default().analysis));
+}
+
+pub fn reload_config() {
+    *BANDS.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = LabelBands::from_config(&AppConfig::get_analysis_config());
+}
+
+// Label of a compound score in -1..1
+pub(crate) fn label(score: f64) -> &'static str {
+    BANDS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).label(score, None)
+}
+
+// With the neutral band of the text's language, if it has one
+pub(crate) fn label_in(score: f64, language: &str) -> &'static str {
+    BANDS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).label(score, Some(language))
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F300..=0x1FAFF | 0x2600..=0x27BF)
+}
+
+impl SentimentAnalyzer {
+    fn with_entries(entries: impl IntoIterator<Item = (String, f64)>) -> Self {
+        Self {
+            lexicon: entries.into_iter()
+                .map(|(word, valence)| (word.to_lowercase().replace(['\'', '’'], ""), valence.clamp(-4.0, 4.0)))
+                .collect(),
+        }
+    }
+    
+    // Reads a lexicon file: either a JSON object of word to valence, or one
+    // entry per line as "word<whitespace>valence", which also accepts VADER's
+    // own vader_lexicon.txt. Lines starting with '#' are comments.
+    pub fn from_lexicon<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read lexicon {}: {}", path.display(), e))?;
+        Self::from_lexicon_str(&contents)
+    }
+    
+    pub fn from_lexicon_str(contents: &str) -> Result<Self, String> {
+        if contents.trim_start().starts_with('{') {
+            let entries: HashMap<String, f64> = serde_json::from_str(contents).map_err(|e| format!("Invalid lexicon JSON: {}", e))?;
+            return Ok(Self::with_entries(entries));
+        }
+        
+        let mut entries = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let word = fields.next().unwrap_or_default();
+            let valence = fields.next()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("Invalid lexicon entry on line {}: {}", number + 1, line))?;
+            entries.push((word.to_string(), valence));
+        }
+        if entries.is_empty() {
+            return Err("Lexicon has no entries".to_string());
+        }
+        Ok(Self::with_entries(entries))
+    }
+    
+    // Adds entries on top of this lexicon, replacing existing words
+    pub fn extend(mut self, other: &SentimentAnalyzer) -> Self {
+        self.lexicon.extend(other.lexicon.iter().map(|(word, valence)| (word.clone(), *valence)));
+        self
+    }
+    
+    pub fn len(&self) -> usize {
+        self.lexicon.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.lexicon.is_empty()
+    }
+    
+    // Lexicon valence of a single word or emoji, -4 to 4
+    pub fn valence(&self, word: &str) -> Option<f64> {
+        self.lexicon.get(&word.to_lowercase()).copied()
+    }
+    
+    pub fn analyze(&self, text: &str) -> SentimentScore {
+        let (tokens, has_lowercase) = tokenize(text);
+        let mut valences = vec![0.0; tokens.len()];
+        
+        for (i, token) in tokens.iter().enumerate() {
+            let mut valence = match self.lexicon.get(&token.key) {
+                Some(valence) => *valence,
+                None => continue,
+            };
+            
+            // Caps only count as shouting when the message is not all caps
+            if token.shouted && has_lowercase {
+                valence += CAPS_INCREMENT * valence.signum();
+            }
+            
+            for distance in 1..=SCOPE.min(i) {
+                let previous = &tokens[i - distance].key;
+                // Intensifiers further away matter less
+                let damping = [1.0, 0.95, 0.9][distance - 1];
+                if let Some((_, boost)) = BOOSTERS.iter().find(|(word, _)| *word == previous.as_str()) {
+                    valence += boost * valence.signum() * damping;
+                }
+                if NEGATIONS.contains(&previous.as_str()) {
+                    valence *= NEGATION_SCALAR;
+                }
+            }
+            valences[i] = valence;
+        }
+        
+        // Whatever follows "but" outweighs what came before it
+        if let Some(pivot) = tokens.iter().position(|t| CONTRASTS.contains(&t.key.as_str())) {
+            for (i, valence) in valences.iter_mut().enumerate() {
+                *valence *= if i < pivot { 0.5 } else { 1.5 };
+            }
+        }
+        
+        let mut sum: f64 = valences.iter().sum();
+        if sum != 0.0 {
+            let exclamations = text.matches('!').count().min(MAX_EXCLAMATIONS);
+            sum += EXCLAMATION_INCREMENT * exclamations as f64 * sum.signum();
+        }
+        let compound = sum / (sum * sum + NORMALIZATION_ALPHA).sqrt();
+        
+        let (positive, negative) = valences.iter().fold((0usize, 0usize), |(p, n), v| {
+            if *v > 0.0 { (p + 1, n) } else if *v < 0.0 { (p, n + 1) } else { (p, n) }
+        });
+        let total = tokens.len().max(1) as f64;
+        
+        SentimentScore {
+            label: label(compound).to_string(),
+            compound,
+            positive: positive as f64 / total,
+            negative: negative as f64 / total,
+            neutral: if tokens.is_empty() { 1.0 } else { (tokens.len() - positive - negative) as f64 / total },
+        }
+    }
+}
+
+lazy_static! {
+    static ref ACTIVE: RwLock<Arc<SentimentAnalyzer>> = RwLock::new(Arc::new(SentimentAnalyzer::default()));
+}
+
+// The analyzer used by the text pipeline
+pub fn active() -> Arc<SentimentAnalyzer> {
+    ACTIVE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+pub fn set_active(analyzer: SentimentAnalyzer) {
+    *ACTIVE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(analyzer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_valence_modifiers() {
+        let analyzer = SentimentAnalyzer::default();
+        let plain = analyzer.analyze("the food was good").compound;
+        
+        assert!(analyzer.analyze("the food was very good").compound > plain);
+        assert!(analyzer.analyze("the food was GOOD").compound > plain);
+        assert!(analyzer.analyze("the food was good!!!").compound > plain);
+        assert!(analyzer.analyze("the food was not good").compound < 0.0);
+        assert!(analyzer.analyze("the food wasn't good").compound < 0.0);
+        assert_eq!(analyzer.analyze("the food was good but the service was terrible").label, "negative");
+        assert_eq!(analyzer.analyze("غذا خیلی خوب بود 😍").label, "positive");
+        assert_eq!(analyzer.analyze("😡😡").label, "negative");
+        
+        let neutral = analyzer.analyze("");
+        assert_eq!((neutral.label.as_str(), neutral.compound, neutral.neutral), ("neutral", 0.0, 1.0));
+    }
+    
+    #[test]
+    fn test_neutral_bands() {
+        let mut config = AppConfig::default().analysis;
+        let bands = LabelBands::from_config(&config);
+        assert_eq!((bands.label(0.15, None), bands.label(0.25, None), bands.label(-0.25, Some("fa"))), ("neutral", "positive", "negative"));
+        
+        config.sentiment_threshold = 0.1;
+        assert_eq!(LabelBands::from_config(&config).label(0.15, None), "positive");
+        
+        // A band replaces the threshold, and a language band both
+        config.sentiment_neutral_band = Some(NeutralBand { negative: -0.05, positive: 0.4 });
+        config.language_sentiment_bands.insert("Persian".to_string(), NeutralBand { negative: -0.1, positive: 0.1 });
+        let bands = LabelBands::from_config(&config);
+        assert_eq!((bands.label(-0.08, None), bands.label(0.3, Some("en"))), ("negative", "neutral"));
+        assert_eq!((bands.label(-0.08, Some("fa")), bands.label(0.15, Some("fa"))), ("neutral", "positive"));
+        
+        config.language_sentiment_bands.insert("en".to_string(), NeutralBand { negative: 0.2, positive: 0.5 });
+        let invalid = AppConfig { analysis: config, ..AppConfig::default() };
+        assert!(invalid.validate_config().is_err());
+    }
+    
+    #[test]
+    fn test_custom_lexicon() {
+        let analyzer = SentimentAnalyzer::from_lexicon_str("# bot slang\nbased\t2.5\t0.5\t[2, 3]\ncringe -2.0\n").unwrap();
+        assert_eq!(analyzer.len(), 2);
+        assert_eq!(analyzer.analyze("so based").label, "positive");
+        assert_eq!(analyzer.analyze("cringe").label, "negative");
+        assert_eq!(analyzer.analyze("good").label, "neutral");
+        
+        let json = SentimentAnalyzer::from_lexicon_str(r#"{"Lit": 9.0}"#).unwrap();
+        assert!(json.analyze("lit").compound > 0.0);
+        assert!(SentimentAnalyzer::from_lexicon_str("word notanumber").is_err());
+        assert!(SentimentAnalyzer::from_lexicon("/nonexistent/lexicon.txt").is_err());
+        
+        let merged = SentimentAnalyzer::default().extend(&analyzer);
+        assert_eq!(merged.analyze("good and based").label, "positive");
+    }
+}
\ No newline at end of file
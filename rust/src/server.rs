@@ -0,0 +1,133 @@
+use tonic::{Request, Response, Status};
+use crate::{api, lifecycle};
+
+pub mod proto {
+    tonic::include_proto!("aiogram.v1");
+}
+
+use proto::analysis_service_server::{AnalysisService, AnalysisServiceServer};
+use proto::{
+    AnalyzeDataRequest, AnalyzeTextRequest, DecryptRequest, DecryptResponse, EncryptRequest,
+    EncryptResponse, JsonResponse, ValidateRequest, ValidateResponse,
+};
+
+// Loopback only; the service has no authentication, so listening on other
+// interfaces takes an explicit address
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:50051";
+
+#[derive(Default)]
+pub struct AnalysisServer;
+
+// The engine is CPU bound and synchronous, so every call runs on the blocking
+// pool to keep the gRPC reactor responsive
+async fn run_blocking<T, F>(task: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| Status::internal(format!("Worker failed: {}", e)))
+}
+
+#[tonic::async_trait]
+impl AnalysisService for AnalysisServer {
+    async fn analyze_text(&self, request: Request<AnalyzeTextRequest>) -> Result<Response<JsonResponse>, Status> {
+        let request = request.into_inner();
+        let analyzer = api::TextAnalyzer::from_json(&request.options_json).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        
+        let result_json = run_blocking(move || crate::analyze_text_response(&request.text, &analyzer)).await?;
+        Ok(Response::new(JsonResponse { result_json }))
+    }
+    
+    async fn analyze_data(&self, request: Request<AnalyzeDataRequest>) -> Result<Response<JsonResponse>, Status> {
+        let data = request.into_inner().data;
+        let result_json = run_blocking(move || crate::analyze_data_response(&data)).await?;
+        Ok(Response::new(JsonResponse { result_json }))
+    }
+    
+    async fn encrypt(&self, request: Request<EncryptRequest>) -> Result<Response<EncryptResponse>, Status> {
+        let request = request.into_inner();
+        let ciphertext = run_blocking(move || api::encrypt(&request.message, &request.key).map_err(|e| e.to_string()))
+            .await?
+            .map_err(Status::internal)?;
+        Ok(Response::new(EncryptResponse { ciphertext }))
+    }
+    
+    async fn decrypt(&self, request: Request<DecryptRequest>) -> Result<Response<DecryptResponse>, Status> {
+        let request = request.into_inner();
+        let message = run_blocking(move || api::decrypt(&request.ciphertext, &request.key).map_err(|e| e.to_string()))
+            .await?
+            .map_err(Status::invalid_argument)?;
+        Ok(Response::new(DecryptResponse { message }))
+    }
+    
+    async fn validate(&self, request: Request<ValidateRequest>) -> Result<Response<ValidateResponse>, Status> {
+        let request = request.into_inner();
+        let result = run_blocking(move || api::validate(&request.data, &request.schema_name)).await?;
+        let result_json = serde_json::to_string(&result).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ValidateResponse { is_valid: result.is_valid, result_json }))
+    }
+}
+
+// Runs the gRPC service until Ctrl-C, with the same config loading,
+// background maintenance and shutdown as an embedding host
+pub fn run_server(address: &str, config_json: Option<&str>) -> Result<(), String> {
+    let address = address.parse().map_err(|e| format!("Invalid server address {}: {}", address, e))?;
+    
+    lifecycle::init_library(config_json)?;
+    
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("aiogram-grpc")
+        .build()
+        .map_err(|e| format!("Failed to start runtime: {}", e))?;
+    
+    let result = runtime.block_on(async {
+        tonic::transport::Server::builder()
+            .add_service(AnalysisServiceServer::new(AnalysisServer))
+            .serve_with_shutdown(address, async {
+                let _ = tokio::signal::ctrl_c().await;
+            })
+            .await
+    });
+    
+    lifecycle::shutdown_library();
+    result.map_err(|e| format!("gRPC server failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_service_round_trip() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let server = AnalysisServer;
+        
+        runtime.block_on(async {
+            let encrypted = server.encrypt(Request::new(EncryptRequest {
+                message: "hello".to_string(),
+                key: "grpc-test-key".to_string(),
+            })).await.unwrap().into_inner();
+            
+            let decrypted = server.decrypt(Request::new(DecryptRequest {
+                ciphertext: encrypted.ciphertext,
+                key: "grpc-test-key".to_string(),
+            })).await.unwrap().into_inner();
+            assert_eq!(decrypted.message, "hello");
+            
+            let validated = server.validate(Request::new(ValidateRequest {
+                data: "user@example.com".to_string(),
+                schema_name: "email".to_string(),
+            })).await.unwrap().into_inner();
+            assert!(validated.is_valid);
+            
+            let status = server.analyze_text(Request::new(AnalyzeTextRequest {
+                text: "Hello".to_string(),
+                options_json: r#"{"unknown_option": true}"#.to_string(),
+            })).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        });
+    }
+}
\ No newline at end of file
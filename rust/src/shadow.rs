@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::cache::fast_hash;
+use crate::config::{AppConfig, KeywordAlgorithm, ShadowConfig};
+use crate::sentiment::{self, SentimentAnalyzer};
+use crate::{deadline, logging, tenant};
+
+// Shadow analysis validates an algorithm swap on live traffic before it
+// ships. A sampled share of analyzed texts is also scored by the candidate
+// (another sentiment lexicon, another keyword algorithm) next to the primary
+// implementation; only the primary result is returned, while the two are
+// compared, divergences logged and the differences aggregated per tenant.
+// The text itself is neither logged nor kept, only its hash, and the
+// shadow work is skipped once a call's deadline has passed.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageComparison {
+    pub compared: u64,
+    pub diverged: u64,
+    // diverged / compared
+    pub divergence_rate: f64,
+    // Sentiment: distance between the scores; keywords: share of the
+    // keywords not in both lists
+    pub mean_difference: f64,
+    pub max_difference: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    // "sentiment" or "keywords"
+    pub stage: String,
+    // BLAKE3 of the text as passed in, before code blanking or
+    // normalization, to find it in the host's own records
+    pub text_hash: String,
+    pub language: String,
+    // {"label", "score"} for sentiment, the keyword list for keywords
+    pub primary: serde_json::Value,
+    pub shadow: serde_json::Value,
+    pub difference: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShadowReport {
+    // Texts picked for comparison
+    pub sampled: u64,
+    pub sentiment: StageComparison,
+    pub keywords: StageComparison,
+    // Newest first, at most shadow.max_examples
+    pub examples: Vec<Divergence>,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    compared: u64,
+    diverged: u64,
+    difference_sum: f64,
+    max_difference: f64,
+}
+
+impl Tally {
+    fn add(&mut self, difference: f64, diverged: bool) {
+        self.compared += 1;
+        self.diverged += u64::from(diverged);
+        self.difference_sum += difference;
+        self.max_difference = self.max_difference.max(difference);
+    }
+    
+    fn comparison(&self) -> StageComparison {
+        let per_compared = |value: f64| if self.compared == 0 { 0.0 } else { value / self.compared as f64 };
+        StageComparison {
+            compared: self.compared,
+            diverged: self.diverged,
+            divergence_rate: per_compared(self.diverged as f64),
+            mean_difference: per_compared(self.difference_sum),
+            max_difference: self.max_difference,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Aggregate {
+    sampled: u64,
+    sentiment: Tally,
+    keywords: Tally,
+    examples: VecDeque<Divergence>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<ShadowConfig> = RwLock::new(AppConfig::get().shadow);
+    static ref SENTIMENT: RwLock<Option<Arc<SentimentAnalyzer>>> = RwLock::new(None);
+    // By tenant scope
+    static ref AGGREGATES: Mutex<HashMap<String, Aggregate>> = Mutex::new(HashMap::new());
+}
+
+fn aggregates() -> MutexGuard<'static, HashMap<String, Aggregate>> {
+    AGGREGATES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn reload_config() {
+    *CONFIG.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = AppConfig::get().shadow;
+}
+
+// The lexicon compared with the active one; None stops comparing sentiment
+pub fn set_sentiment_analyzer(analyzer: Option<SentimentAnalyzer>) {
+    *SENTIMENT.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = analyzer.map(Arc::new);
+}
+
+// What one sampled text is compared with, taken once so a config reload
+// halfway through the pipeline does not mix two setups
+pub struct Shadow {
+    config: ShadowConfig,
+    sentiment: Option<Arc<SentimentAnalyzer>>,
+    text_hash: String,
+}
+
+// Some when this text is to be compared: shadowing is on, there is a
+// candidate to compare with and the text falls in the sample
+pub fn sample(text: &str) -> Option<Shadow> {
+    sample_with(&mut rand::thread_rng(), text)
+}
+
+fn sample_with<R: Rng>(rng: &mut R, text: &str) -> Option<Shadow> {
+    let config = CONFIG.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if config.sample_rate <= 0.0 || rng.gen::<f64>() >= config.sample_rate {
+        return None;
+    }
+    let sentiment = SENTIMENT.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    if sentiment.is_none() && config.keyword_algorithm.is_none() {
+        return None;
+    }
+    aggregates().entry(tenant::scope_key("")).or_default().sampled += 1;
+    Some(Shadow { config: config.clone(), sentiment, text_hash: fast_hash(text.as_bytes()) })
+}
+
+// Share of the keywords in both lists, ignoring case; 1 for two empty lists
+fn keyword_overlap(primary: &[String], shadow: &[String]) -> f64 {
+    let primary: HashSet<String> = primary.iter().map(|keyword| keyword.to_lowercase()).collect();
+    let shadow: HashSet<String> = shadow.iter().map(|keyword| keyword.to_lowercase()).collect();
+    let union = primary.union(&shadow).count();
+    if union == 0 {
+        return 1.0;
+    }
+    primary.intersection(&shadow).count() as f64 / union as f64
+}
+
+impl Shadow {
+    // Scores text with the shadow lexicon and compares it with the primary
+    // label and score
+    pub fn compare_sentiment(&self, text: &str, language: &str, label: &str, score: f64) {
+        let Some(analyzer) = &self.sentiment else {
+            return;
+        };
+        if deadline::expired() {
+            return;
+        }
+        let shadow_score = analyzer.analyze(text).compound;
+        let shadow_label = sentiment::label_in(shadow_score, language);
+        let difference = (shadow_score - score).abs();
+        let diverged = shadow_label != label || difference > self.config.score_tolerance;
+        self.record("sentiment", language, difference, diverged, || {
+            (serde_json::json!({ "label": label, "score": score }), serde_json::json!({ "label": shadow_label, "score": shadow_score }))
+        });
+    }
+    
+    // The algorithm to extract the shadow keywords with, if any
+    pub fn keyword_algorithm(&self) -> Option<KeywordAlgorithm> {
+        self.config.keyword_algorithm.filter(|_| !deadline::expired())
+    }
+    
+    pub fn compare_keywords(&self, language: &str, primary: &[String], shadow: &[String]) {
+        let difference = 1.0 - keyword_overlap(primary, shadow);
+        let diverged = 1.0 - difference < self.config.keyword_overlap;
+        self.record("keywords", language, difference, diverged, || (serde_json::json!(primary), serde_json::json!(shadow)));
+    }
+    
+    fn record(&self, stage: &str, language: &str, difference: f64, diverged: bool, values: impl FnOnce() -> (serde_json::Value, serde_json::Value)) {
+        let mut aggregates = aggregates();
+        let aggregate = aggregates.entry(tenant::scope_key("")).or_default();
+        let tally = if stage == "sentiment" { &mut aggregate.sentiment } else { &mut aggregate.keywords };
+        tally.add(difference, diverged);
+        if !diverged {
+            return;
+        }
+        let (primary, shadow) = values();
+        aggregate.examples.push_front(Divergence {
+            stage: stage.to_string(),
+            text_hash: self.text_hash.clone(),
+            language: language.to_string(),
+            primary,
+            shadow,
+            difference,
+            timestamp: Utc::now(),
+        });
+        aggregate.examples.truncate(self.config.max_examples);
+        drop(aggregates);
+        logging::info(&format!("Shadow {} diverged by {:.3} on text {}", stage, difference, self.text_hash), "shadow", "record", line!());
+    }
+}
+
+// The current tenant's comparisons since the last reset
+pub fn report() -> ShadowReport {
+    let aggregates = aggregates();
+    match aggregates.get(&tenant::scope_key("")) {
+        Some(aggregate) => ShadowReport {
+            sampled: aggregate.sampled,
+            sentiment: aggregate.sentiment.comparison(),
+            keywords: aggregate.keywords.comparison(),
+            examples: aggregate.examples.iter().cloned().collect(),
+        },
+        None => ShadowReport::default(),
+    }
+}
+
+// Starts the current tenant's comparisons over, e.g. after changing the
+// candidate
+pub fn reset() -> bool {
+    aggregates().remove(&tenant::scope_key("")).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::tenant::TenantContext;
+    
+    #[test]
+    fn test_shadow_comparisons() {
+        tenant::with_tenant(TenantContext::new(Some("shadow_test"), None), || {
+            // Nothing to compare with, nothing sampled
+            let mut rng = StdRng::seed_from_u64(3);
+            assert!(sample_with(&mut rng, "great").is_none());
+            
+            let config = ShadowConfig { sample_rate: 1.0, keyword_algorithm: Some(KeywordAlgorithm::RAKE), max_examples: 2, ..Default::default() };
+            let shadow = Shadow { config, sentiment: Some(Arc::new(SentimentAnalyzer::from_lexicon_str(r#"{"great": -3.0}"#).unwrap())), text_hash: fast_hash(b"great") };
+            shadow.compare_sentiment("great", "en", "positive", 0.6);
+            shadow.compare_sentiment("hello", "en", "neutral", 0.05);
+            let keywords = |list: &[&str]| list.iter().map(|keyword| keyword.to_string()).collect::<Vec<_>>();
+            shadow.compare_keywords("en", &keywords(&["rust", "Cargo"]), &keywords(&["cargo", "rust"]));
+            shadow.compare_keywords("en", &keywords(&["rust", "cargo", "crate"]), &keywords(&["borrow"]));
+            
+            let shadow_report = report();
+            assert_eq!((shadow_report.sentiment.compared, shadow_report.sentiment.diverged), (2, 1));
+            assert!(shadow_report.sentiment.max_difference > 1.0);
+            assert_eq!((shadow_report.keywords.compared, shadow_report.keywords.diverged), (2, 1));
+            assert_eq!(shadow_report.keywords.divergence_rate, 0.5);
+            // Newest first, capped; the text itself is not kept
+            assert_eq!(shadow_report.examples.len(), 2);
+            assert_eq!(shadow_report.examples[0].stage, "keywords");
+            assert_eq!(shadow_report.examples[1].primary["label"], "positive");
+            assert_eq!(shadow_report.examples[1].text_hash, fast_hash(b"great"));
+            
+            assert!(reset());
+            assert_eq!(report().sentiment.compared, 0);
+        });
+    }
+}
\ No newline at end of file
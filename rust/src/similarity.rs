@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::RwLock;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+use crate::fingerprints::{self, MIN_TEXT_CHARS, SHINGLE_CHARS};
+
+// Pairwise text similarity and near-duplicate detection. Cosine compares
+// word counts, so it ignores word order; Jaccard compares character
+// shingles, so it tolerates small edits inside words; Levenshtein counts
+// character edits and suits short texts. DuplicateDetector keeps MinHash
+// signatures of recent messages, whose agreement estimates the shingle
+// Jaccard, and finds candidates through LSH bands instead of comparing
+// against every message.
+
+const SIGNATURE_HASHES: usize = 128;
+// 32 bands of 4 rows: pairs above ~0.5 Jaccard share a band with high odds
+const BANDS: usize = 32;
+const ROWS: usize = SIGNATURE_HASHES / BANDS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Similarity {
+    // All 0-1, 1 for identical texts
+    pub cosine: f64,
+    pub jaccard: f64,
+    pub levenshtein: f64,
+}
+
+fn shingles(chars: &[char]) -> HashSet<u64> {
+    if chars.len() < SHINGLE_CHARS {
+        // Too short for a full shingle; the whole text is the only one
+        return std::iter::once(chars.iter().collect::<String>()).filter(|s| !s.is_empty())
+            .map(|s| fingerprints::feature_hash(s.as_bytes()))
+            .collect();
+    }
+    let mut shingle = String::new();
+    chars.windows(SHINGLE_CHARS).map(|window| {
+        shingle.clear();
+        shingle.extend(window);
+        fingerprints::feature_hash(shingle.as_bytes())
+    }).collect()
+}
+
+fn cosine(a: &str, b: &str) -> f64 {
+    let counts = |text: &str| {
+        let mut counts: HashMap<String, f64> = HashMap::new();
+        for word in text.unicode_words() {
+            *counts.entry(word.to_lowercase()).or_insert(0.0) += 1.0;
+        }
+        counts
+    };
+    let (a, b) = (counts(a), counts(b));
+    let dot: f64 = a.iter().filter_map(|(word, x)| b.get(word).map(|y| x * y)).sum();
+    let norm = |counts: &HashMap<String, f64>| counts.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(&a) * norm(&b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 { 0.0 } else { a.intersection(b).count() as f64 / union as f64 }
+}
+
+// Edit distance over chars with two rows, O(len(a) * len(b)) time
+pub fn levenshtein_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// Jaccard and Levenshtein run on lowercased letters and digits, so case,
+// punctuation and spacing do not count as differences. Two empty texts
+// score 0 on cosine and Jaccard and 1 on Levenshtein.
+pub fn similarity(a: &str, b: &str) -> Similarity {
+    let (chars_a, chars_b) = (fingerprints::normalize(a), fingerprints::normalize(b));
+    let longest = chars_a.len().max(chars_b.len());
+    let levenshtein = if longest == 0 { 1.0 } else { 1.0 - levenshtein_distance(&chars_a, &chars_b) as f64 / longest as f64 };
+    Similarity {
+        cosine: cosine(a, b),
+        jaccard: jaccard(&shingles(&chars_a), &shingles(&chars_b)),
+        levenshtein,
+    }
+}
+
+// splitmix64, to derive the independent hash functions of a signature from
+// one shingle hash
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// None for texts below fingerprints::MIN_TEXT_CHARS, which repeat too
+// often to mean anything
+fn signature(text: &str) -> Option<Vec<u64>> {
+    let chars = fingerprints::normalize(text);
+    if chars.len() < MIN_TEXT_CHARS {
+        return None;
+    }
+    let mut signature = vec![u64::MAX; SIGNATURE_HASHES];
+    for shingle in shingles(&chars) {
+        for (i, minimum) in signature.iter_mut().enumerate() {
+            *minimum = (*minimum).min(mix(shingle ^ (i as u64).wrapping_mul(0x9e3779b97f4a7c15)));
+        }
+    }
+    Some(signature)
+}
+
+fn band_keys(signature: &[u64]) -> impl Iterator<Item = (usize, u64)> + '_ {
+    signature.chunks(ROWS).enumerate().map(|(band, rows)| (band, rows.iter().fold(0, |key, &row| mix(key ^ row))))
+}
+
+fn estimate(a: &[u64], b: &[u64]) -> f64 {
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / SIGNATURE_HASHES as f64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DuplicateDetectorConfig {
+    // Recent messages kept; the oldest drop out first
+    pub capacity: usize,
+    // Estimated shingle Jaccard at which a message counts as a repost
+    pub threshold: f64,
+}
+
+impl Default for DuplicateDetectorConfig {
+    fn default() -> Self {
+        Self { capacity: 10_000, threshold: 0.8 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    pub id: String,
+    // Estimated Jaccard similarity of the shingles
+    pub similarity: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateDetector {
+    config: DuplicateDetectorConfig,
+    // (sequence number, id, signature), oldest first
+    messages: VecDeque<(u64, String, Vec<u64>)>,
+    // (band, band hash) -> sequence numbers
+    buckets: HashMap<(usize, u64), Vec<u64>>,
+    next_sequence: u64,
+}
+
+impl Default for DuplicateDetector {
+    fn default() -> Self {
+        Self::new(DuplicateDetectorConfig::default())
+    }
+}
+
+impl DuplicateDetector {
+    pub fn new(config: DuplicateDetectorConfig) -> Self {
+        Self { config, messages: VecDeque::new(), buckets: HashMap::new(), next_sequence: 0 }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+    
+    fn matches(&self, signature: &[u64], threshold: f64) -> Vec<DuplicateMatch> {
+        let oldest = match self.messages.front() {
+            Some(&(sequence, _, _)) => sequence,
+            None => return Vec::new(),
+        };
+        let mut candidates: Vec<u64> = band_keys(signature)
+            .filter_map(|key| self.buckets.get(&key))
+            .flatten()
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        
+        let mut matches: Vec<DuplicateMatch> = candidates.into_iter()
+            // Sequence numbers are contiguous, so the offset is the position
+            .filter_map(|sequence| self.messages.get((sequence - oldest) as usize))
+            .map(|(_, id, other)| DuplicateMatch { id: id.clone(), similarity: estimate(signature, other) })
+            .filter(|m| m.similarity >= threshold)
+            .collect();
+        matches.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+        matches
+    }
+    
+    // Recent messages text reposts, most similar first
+    pub fn check(&self, text: &str) -> Vec<DuplicateMatch> {
+        signature(text).map(|signature| self.matches(&signature, self.config.threshold)).unwrap_or_default()
+    }
+    
+    // Highest estimated similarity to any recent message, 0 without a
+    // candidate; for scores rather than a yes/no
+    pub fn best_similarity(&self, text: &str) -> f64 {
+        signature(text)
+            .and_then(|signature| self.matches(&signature, 0.0).first().map(|m| m.similarity))
+            .unwrap_or(0.0)
+    }
+    
+    // False when text is too short to track
+    pub fn add(&mut self, id: &str, text: &str) -> bool {
+        let signature = match signature(text) {
+            Some(signature) => signature,
+            None => return false,
+        };
+        self.insert(id, signature);
+        true
+    }
+    
+    // check, then add: the usual call for each incoming message
+    pub fn check_and_add(&mut self, id: &str, text: &str) -> Vec<DuplicateMatch> {
+        let signature = match signature(text) {
+            Some(signature) => signature,
+            None => return Vec::new(),
+        };
+        let matches = self.matches(&signature, self.config.threshold);
+        self.insert(id, signature);
+        matches
+    }
+    
+    fn insert(&mut self, id: &str, signature: Vec<u64>) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        for key in band_keys(&signature) {
+            self.buckets.entry(key).or_default().push(sequence);
+        }
+        self.messages.push_back((sequence, id.to_string(), signature));
+        
+        while self.messages.len() > self.config.capacity.max(1) {
+            let (evicted, _, signature) = match self.messages.pop_front() {
+                Some(message) => message,
+                None => break,
+            };
+            for key in band_keys(&signature) {
+                if let Some(bucket) = self.buckets.get_mut(&key) {
+                    bucket.retain(|&s| s != evicted);
+                    if bucket.is_empty() {
+                        self.buckets.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref CORPUS: RwLock<Option<DuplicateDetector>> = RwLock::new(None);
+}
+
+// Recent messages the text pipeline scores plagiarism_score against; None
+// goes back to the phrase heuristic
+pub fn set_corpus(detector: Option<DuplicateDetector>) {
+    *CORPUS.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = detector;
+}
+
+// Starts a default detector if none is set
+pub fn add_corpus_document(id: &str, text: &str) -> bool {
+    CORPUS.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_insert_with(DuplicateDetector::default)
+        .add(id, text)
+}
+
+pub fn find_duplicates(text: &str) -> Vec<DuplicateMatch> {
+    CORPUS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref().map(|detector| detector.check(text)).unwrap_or_default()
+}
+
+// None while no corpus has been set
+pub fn corpus_similarity(text: &str) -> Option<f64> {
+    CORPUS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref().map(|detector| detector.best_similarity(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_similarity() {
+        let same = similarity("The deploy failed again", "the deploy FAILED again!");
+        assert!((same.cosine - 1.0).abs() < 1e-9);
+        assert_eq!((same.jaccard, same.levenshtein), (1.0, 1.0));
+        
+        let reordered = similarity("failed deploy again", "again deploy failed");
+        assert!((reordered.cosine - 1.0).abs() < 1e-9);
+        assert!(reordered.levenshtein < 0.5);
+        
+        let unrelated = similarity("lunch at noon", "quarterly revenue report");
+        assert_eq!(unrelated.cosine, 0.0);
+        assert!(unrelated.jaccard < 0.1);
+        assert_eq!(levenshtein_distance(&['k', 'i', 't', 't', 'e', 'n'], &['s', 'i', 't', 't', 'i', 'n', 'g']), 3);
+    }
+    
+    #[test]
+    fn test_detects_reposts() {
+        let mut detector = DuplicateDetector::new(DuplicateDetectorConfig { capacity: 2, threshold: 0.7 });
+        let post = "Join our crypto signals channel for daily 10x gains, link in bio";
+        assert!(detector.check_and_add("1", post).is_empty());
+        assert!(!detector.add("2", "ok thanks"));
+        
+        let repost = detector.check("Join our crypto signals channel for daily 10x gains!! link in bio");
+        assert_eq!(repost.len(), 1);
+        assert_eq!(repost[0].id, "1");
+        assert!(repost[0].similarity > 0.7);
+        assert!(detector.check("Does anyone know when the next meetup is scheduled?").is_empty());
+        
+        // Evicted once capacity is exceeded
+        detector.add("3", "The meetup is on Friday at the usual place downtown");
+        detector.add("4", "Bring your laptops, we will be coding all evening long");
+        assert_eq!(detector.len(), 2);
+        assert!(detector.check(post).is_empty());
+        assert!(detector.best_similarity("Bring your laptops, we will be coding all evening") > 0.5);
+    }
+}
\ No newline at end of file
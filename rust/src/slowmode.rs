@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::chats;
+use crate::config::{AppConfig, SlowmodeConfig};
+use crate::tenant;
+
+// Slowmode recommendations read the dates and authors of the last minutes'
+// messages from the chat stats and keep the last recommended delay per chat,
+// so a rate hovering around the target does not switch the delay on and off.
+
+// The slowmode delays Telegram offers, in seconds
+const SLOWMODE_LEVELS: [u32; 7] = [0, 10, 30, 60, 300, 900, 3600];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserCooldown {
+    pub user_id: i64,
+    // In the measuring window
+    pub messages: u64,
+    pub messages_per_minute: f64,
+    // Seconds to hold back between the member's messages
+    pub cooldown_seconds: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowmodeRecommendation {
+    pub chat_id: i64,
+    pub window_seconds: i64,
+    // Over the window, all senders
+    pub messages_per_minute: f64,
+    pub target_messages_per_minute: f64,
+    // Members who posted in the window
+    pub active_users: usize,
+    // One of 0, 10, 30, 60, 300, 900 or 3600
+    pub slowmode_seconds: u32,
+    // The last recommendation, so hosts can act only on changes
+    pub previous_slowmode_seconds: u32,
+    pub changed: bool,
+    // The rate the window would have had under slowmode_seconds
+    pub expected_messages_per_minute: f64,
+    // Members posting faster than the user rate, busiest first; empty while
+    // the slowmode delay already holds everyone back as long
+    pub cooldowns: Vec<UserCooldown>,
+}
+
+#[derive(Debug, Default)]
+struct SlowmodeState {
+    seconds: u32,
+    // When seconds last changed
+    since: i64,
+    // Members given a cooldown; they keep it until their rate drops below
+    // the hysteresis band
+    cooled: BTreeSet<i64>,
+}
+
+lazy_static! {
+    static ref STATES: Mutex<HashMap<String, SlowmodeState>> = Mutex::new(HashMap::new());
+}
+
+fn states() -> MutexGuard<'static, HashMap<String, SlowmodeState>> {
+    STATES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn chat_key(chat_id: i64) -> String {
+    tenant::scope_key(&chat_id.to_string())
+}
+
+// The slowmode delay and per-member cooldowns that would have kept the last
+// window_seconds under the target rate. A higher delay is recommended as
+// soon as it is needed; a lower one only once it keeps the rate hysteresis
+// below the target and the current delay has held for min_hold_seconds.
+pub fn recommend_slowmode(chat_id: i64) -> SlowmodeRecommendation {
+    recommend_slowmode_at(chat_id, &AppConfig::get().slowmode, Utc::now().timestamp())
+}
+
+fn recommend_slowmode_at(chat_id: i64, config: &SlowmodeConfig, now: i64) -> SlowmodeRecommendation {
+    let window = config.window_seconds;
+    let authors = chats::recent_authors(chat_id, now - window, now);
+    let total = authors.len() as u64;
+    let mut by_user: BTreeMap<i64, u64> = BTreeMap::new();
+    for user_id in authors.into_iter().flatten() {
+        *by_user.entry(user_id).or_insert(0) += 1;
+    }
+    let per_minute = |messages: f64| messages * 60.0 / window as f64;
+    
+    // Slowmode lets each member send one message per delay; posts without
+    // an author, such as channel posts, are not held back
+    let anonymous = (total - by_user.values().sum::<u64>()) as f64;
+    let expected = |seconds: u32| {
+        let allowed = if seconds == 0 { f64::INFINITY } else { (window as f64 / seconds as f64).max(1.0) };
+        per_minute(anonymous + by_user.values().map(|&messages| (messages as f64).min(allowed)).sum::<f64>())
+    };
+    let lowest_under = |rate: f64| SLOWMODE_LEVELS.iter().copied().find(|&seconds| expected(seconds) <= rate).unwrap_or(SLOWMODE_LEVELS[SLOWMODE_LEVELS.len() - 1]);
+    let needed = lowest_under(config.target_messages_per_minute);
+    let relaxed = lowest_under(config.target_messages_per_minute * (1.0 - config.hysteresis));
+    
+    let mut states = states();
+    let state = states.entry(chat_key(chat_id)).or_default();
+    let previous = state.seconds;
+    let seconds = if needed > previous {
+        needed
+    } else if relaxed < previous && now - state.since >= config.min_hold_seconds {
+        relaxed
+    } else {
+        previous
+    };
+    if seconds != previous {
+        state.seconds = seconds;
+        state.since = now;
+    }
+    
+    let user_rate = |user_id: &i64| per_minute(by_user.get(user_id).copied().unwrap_or(0) as f64);
+    state.cooled.retain(|user_id| user_rate(user_id) > config.user_messages_per_minute * (1.0 - config.hysteresis));
+    state.cooled.extend(by_user.keys().filter(|user_id| user_rate(user_id) > config.user_messages_per_minute));
+    let cooldown_seconds = (60.0 / config.user_messages_per_minute).ceil() as u32;
+    let mut cooldowns: Vec<UserCooldown> = if seconds >= cooldown_seconds {
+        Vec::new()
+    } else {
+        state.cooled.iter().map(|&user_id| UserCooldown {
+            user_id,
+            messages: by_user.get(&user_id).copied().unwrap_or(0),
+            messages_per_minute: user_rate(&user_id),
+            cooldown_seconds,
+        }).collect()
+    };
+    cooldowns.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.user_id.cmp(&b.user_id)));
+    
+    SlowmodeRecommendation {
+        chat_id,
+        window_seconds: window,
+        messages_per_minute: per_minute(total as f64),
+        target_messages_per_minute: config.target_messages_per_minute,
+        active_users: by_user.len(),
+        slowmode_seconds: seconds,
+        previous_slowmode_seconds: previous,
+        changed: seconds != previous,
+        expected_messages_per_minute: expected(seconds),
+        cooldowns,
+    }
+}
+
+// Forgets the last recommendation for a chat; chats::clear_chat calls this
+pub fn clear(chat_id: i64) -> bool {
+    states().remove(&chat_key(chat_id)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chats::{ChatMessage, ChatRef, UserRef};
+    
+    #[test]
+    fn test_slowmode_recommendations() {
+        let config = SlowmodeConfig::default();
+        let mut next_id = 0;
+        // messages each from users, spread over the five minutes before now
+        let mut burst = |chat: i64, users: std::ops::Range<i64>, messages: i64, now: i64| {
+            for user in users {
+                for i in 0..messages {
+                    next_id += 1;
+                    chats::record_message_at(&ChatMessage {
+                        message_id: next_id,
+                        chat: ChatRef { id: chat },
+                        from: Some(UserRef { id: user }),
+                        date: now - i * 300 / messages,
+                        text: Some("hi".to_string()),
+                        ..Default::default()
+                    }, now);
+                }
+            }
+        };
+        let chat = -100_107;
+        let now = 1_700_000_000;
+        
+        // 40 a minute from ten members; a 30 s delay lets each send 10
+        burst(chat, 0..10, 20, now);
+        let busy = recommend_slowmode_at(chat, &config, now);
+        assert_eq!((busy.slowmode_seconds, busy.previous_slowmode_seconds, busy.changed), (30, 0, true));
+        assert!((busy.messages_per_minute - 40.0).abs() < 1e-9);
+        assert!((busy.expected_messages_per_minute - 20.0).abs() < 1e-9);
+        assert!(busy.cooldowns.is_empty());
+        assert!(!recommend_slowmode_at(chat, &config, now).changed);
+        
+        // 16 a minute is under the target but not under the band below it
+        burst(chat, 0..10, 8, now + 400);
+        assert_eq!(recommend_slowmode_at(chat, &config, now + 400).slowmode_seconds, 30);
+        // 10 a minute is, once the delay has held ten minutes
+        burst(chat, 0..10, 5, now + 800);
+        let calm = recommend_slowmode_at(chat, &config, now + 800);
+        assert_eq!((calm.slowmode_seconds, calm.changed), (0, true));
+        
+        // One member at 6 a minute gets a cooldown and keeps it at 3.4
+        burst(chat, 20..21, 30, now + 1200);
+        burst(chat, 21..22, 5, now + 1200);
+        let cooldowns = recommend_slowmode_at(chat, &config, now + 1200).cooldowns;
+        assert_eq!(cooldowns.len(), 1);
+        assert_eq!((cooldowns[0].user_id, cooldowns[0].messages, cooldowns[0].cooldown_seconds), (20, 30, 15));
+        burst(chat, 20..21, 17, now + 1600);
+        assert_eq!(recommend_slowmode_at(chat, &config, now + 1600).cooldowns.len(), 1);
+        burst(chat, 20..21, 10, now + 2000);
+        assert!(recommend_slowmode_at(chat, &config, now + 2000).cooldowns.is_empty());
+        chats::clear_chat(chat);
+    }
+}
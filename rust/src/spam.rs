@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+use lazy_static::lazy_static;
+
+// Heuristic spam and scam score for chat messages. Each signal is scaled to
+// 0-1 and the weighted sum goes through a logistic function, so a message
+// needs several signals (or a scam phrase and one more) to pass 0.5. The
+// weights are tuned so ordinary chat stays under 0.1.
+
+pub const SPAM_THRESHOLD: f64 = 0.5;
+
+const BIAS: f64 = -4.0;
+const WEIGHTS: &[(&str, f64)] = &[
+    ("url_density", 3.0),
+    ("repeated_chars", 2.0),
+    ("caps_ratio", 2.0),
+    ("mentions", 1.5),
+    ("scam_phrases", 4.0),
+    ("entropy", 1.5),
+];
+
+// Caps and entropy mean little for messages shorter than this
+const MIN_LETTERS: usize = 8;
+const MIN_ENTROPY_CHARS: usize = 20;
+
+const SCAM_PHRASES: &[&str] = &[
+    "free money", "double your", "guaranteed profit", "guaranteed return", "investment opportunity",
+    "click here", "claim your prize", "claim your reward", "you have won", "you've won", "giveaway",
+    "airdrop", "send usdt", "send btc", "dm me", "act now", "limited offer", "risk free",
+    "work from home", "earn money", "per day", "verify your account", "wallet address",
+    "سود تضمینی", "درآمد روزانه", "کسب درآمد", "درآمد دلاری", "جایزه", "رایگان", "سرمایه گذاری",
+    "سرمایه‌گذاری", "کلیک کنید", "پیام بدید", "ایردراپ",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamSignal {
+    pub name: String,
+    // Signal strength, 0-1
+    pub value: f64,
+    // weight * value, as added to the logit
+    pub contribution: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamScore {
+    // 0-1
+    pub score: f64,
+    pub is_spam: bool,
+    pub signals: Vec<SpamSignal>,
+    pub urls: usize,
+    pub mentions: usize,
+    pub matched_phrases: Vec<String>,
+}
+
+lazy_static! {
+    static ref URL_PATTERN: Regex = Regex::new(r"(?i)(?:https?://|www\.|t\.me/)\S+|\b[a-z0-9-]+\.(?:com|net|org|io|me|xyz|top|click|link|ly|ir)\b(?:/\S*)?").unwrap();
+    static ref MENTION_PATTERN: Regex = Regex::new(r"(?:^|\s)@\w{3,}").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&URL_PATTERN);
+    lazy_static::initialize(&MENTION_PATTERN);
+    Ok(())
+}
+
+// Share of characters in runs of three or more of the same character
+fn repeated_ratio(text: &str) -> f64 {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+    let mut repeated = 0;
+    let mut run = 1;
+    for i in 1..=chars.len() {
+        if i < chars.len() && chars[i] == chars[i - 1] {
+            run += 1;
+            continue;
+        }
+        if run >= 3 {
+            repeated += run;
+        }
+        run = 1;
+    }
+    repeated as f64 / chars.len() as f64
+}
+
+// Shannon entropy in bits per character
+fn entropy(text: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        *counts.entry(c).or_insert(0) += 1;
+        total += 1;
+    }
+    counts.values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+pub fn score(text: &str) -> SpamScore {
+    let lower = text.to_lowercase();
+    let words = text.unicode_words().count().max(1);
+    let urls = URL_PATTERN.find_iter(text).count();
+    let mentions = MENTION_PATTERN.find_iter(text).count();
+    let matched_phrases: Vec<String> = SCAM_PHRASES.iter()
+        .filter(|phrase| lower.contains(*phrase))
+        .map(|phrase| phrase.to_string())
+        .collect();
+    
+    let (upper, cased) = text.chars().filter(|c| c.is_uppercase() || c.is_lowercase())
+        .fold((0, 0), |(upper, cased), c| (upper + c.is_uppercase() as usize, cased + 1));
+    // Some caps is normal; mostly caps is shouting
+    let caps = if cased >= MIN_LETTERS { ((upper as f64 / cased as f64 - 0.3) / 0.7).max(0.0) } else { 0.0 };
+    
+    // Very repetitive text has low entropy, random strings (codes, wallet
+    // addresses) high; chat usually sits between 3 and 5 bits
+    let entropy_signal = if text.chars().filter(|c| !c.is_whitespace()).count() >= MIN_ENTROPY_CHARS {
+        let bits = entropy(text);
+        ((3.0 - bits) / 3.0).max(bits - 5.0).max(0.0)
+    } else {
+        0.0
+    };
+    
+    let values = [
+        // One link per five words is as bad as it gets
+        urls as f64 * 5.0 / words as f64,
+        repeated_ratio(text) * 4.0,
+        caps,
+        mentions as f64 / 5.0,
+        matched_phrases.len() as f64 / 2.0,
+        entropy_signal,
+    ];
+    let signals: Vec<SpamSignal> = WEIGHTS.iter().zip(values)
+        .map(|((name, weight), value)| {
+            let value = value.min(1.0);
+            SpamSignal { name: name.to_string(), value, contribution: weight * value }
+        })
+        .collect();
+    
+    let logit = BIAS + signals.iter().map(|signal| signal.contribution).sum::<f64>();
+    let score = 1.0 / (1.0 + (-logit).exp());
+    SpamScore {
+        score,
+        is_spam: score >= SPAM_THRESHOLD,
+        signals,
+        urls,
+        mentions,
+        matched_phrases,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_spam_scores() {
+        let ham = score("Hi all, the meeting moved to 5pm tomorrow. See you there!");
+        assert!(ham.score < 0.1);
+        assert!(!ham.is_spam);
+        assert_eq!(ham.signals.len(), WEIGHTS.len());
+        
+        let scam = score("CONGRATULATIONS!!! You have WON our crypto giveaway, claim your prize now: http://bit.ly/x1 @alice @bob @carol");
+        assert!(scam.is_spam);
+        assert!(scam.score > 0.8);
+        assert_eq!((scam.urls, scam.mentions), (1, 3));
+        assert!(scam.matched_phrases.contains(&"claim your prize".to_string()));
+        
+        let persian = score("سود تضمینی روزانه! برای کسب درآمد دلاری همین الان عضو شوید t.me/fastmoney");
+        assert!(persian.is_spam);
+    }
+    
+    #[test]
+    fn test_signals() {
+        assert_eq!(repeated_ratio("heyyyy"), 4.0 / 6.0);
+        assert_eq!(repeated_ratio("hello"), 0.0);
+        assert!(entropy("aaaaaaaaaa") < 0.01);
+        let shouting = score("BUY NOW BEST PRICE EVER");
+        let caps = shouting.signals.iter().find(|signal| signal.name == "caps_ratio").unwrap();
+        assert_eq!(caps.value, 1.0);
+    }
+}
\ No newline at end of file
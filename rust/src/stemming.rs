@@ -0,0 +1,144 @@
+use rust_stemmers::{Algorithm, Stemmer};
+use crate::stopwords;
+
+// Reduces words to a shared stem so keyword and topic counts group
+// inflections: Snowball for the European languages and Arabic, a light
+// suffix stripper for Persian, and a table of irregular English verbs
+// Snowball cannot know ("ran", "went"). Stems are lookup keys, not words to
+// show; the pipeline still reports the first surface form it saw.
+
+const ZWNJ: char = '\u{200C}';
+
+// Verb prefixes, only when joined with a non-joiner ("می‌روم")
+const FA_PREFIXES: &[&str] = &["می", "نمی"];
+// Plural, comparative, possessive and copula suffixes after a non-joiner
+const FA_JOINED_SUFFIXES: &[&str] = &[
+    "ها", "های", "هایی", "هایم", "هایت", "هایش", "هایمان", "هایتان", "هایشان", "تر", "ترین",
+    "ام", "ات", "اش", "ای", "ایم", "اید", "اند", "مان", "تان", "شان", "ی",
+];
+// Written without a non-joiner these are only stripped from longer words, so
+// "بهتر" or "تنها" survive
+const FA_ATTACHED_SUFFIXES: &[&str] = &["هایی", "های", "ها", "ترین", "تر"];
+const FA_MIN_STEM_CHARS: usize = 3;
+
+const EN_IRREGULAR: &[(&str, &str)] = &[
+    ("ran", "run"), ("went", "go"), ("gone", "go"), ("was", "be"), ("were", "be"), ("been", "be"),
+    ("had", "have"), ("did", "do"), ("done", "do"), ("made", "make"), ("said", "say"), ("got", "get"),
+    ("gotten", "get"), ("saw", "see"), ("seen", "see"), ("came", "come"), ("took", "take"),
+    ("taken", "take"), ("gave", "give"), ("given", "give"), ("knew", "know"), ("known", "know"),
+    ("thought", "think"), ("told", "tell"), ("found", "find"), ("felt", "feel"),
+    ("kept", "keep"), ("began", "begin"), ("begun", "begin"), ("wrote", "write"), ("written", "write"),
+    ("bought", "buy"), ("brought", "bring"), ("sent", "send"), ("spent", "spend"), ("built", "build"),
+    ("paid", "pay"), ("met", "meet"), ("sold", "sell"), ("won", "win"), ("lost", "lose"),
+    ("broke", "break"), ("broken", "break"), ("chose", "choose"), ("chosen", "choose"),
+    ("spoke", "speak"), ("spoken", "speak"), ("drove", "drive"), ("driven", "drive"), ("ate", "eat"),
+    ("eaten", "eat"), ("fell", "fall"), ("fallen", "fall"), ("flew", "fly"), ("flown", "fly"),
+    ("grew", "grow"), ("grown", "grow"), ("held", "hold"), ("led", "lead"), ("meant", "mean"),
+    ("rose", "rise"), ("risen", "rise"), ("stood", "stand"), ("taught", "teach"), ("understood", "understand"),
+    ("children", "child"), ("men", "man"), ("women", "woman"), ("people", "person"), ("mice", "mouse"),
+    ("feet", "foot"), ("teeth", "tooth"), ("better", "good"), ("best", "good"), ("worse", "bad"),
+    ("worst", "bad"),
+];
+
+fn is_arabic_script(word: &str) -> bool {
+    word.chars().any(|c| matches!(c, '\u{0600}'..='\u{06FF}' | '\u{FB50}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}'))
+}
+
+// Snowball algorithm for an ISO 639-1 code or a name the detector reports
+fn algorithm(language: &str) -> Option<Algorithm> {
+    let base = language.split(['-', '_']).next().unwrap_or("").trim().to_lowercase();
+    let code = stopwords::language_code(language).unwrap_or(base.as_str());
+    Some(match code {
+        "ar" => Algorithm::Arabic,
+        "da" => Algorithm::Danish,
+        "nl" => Algorithm::Dutch,
+        "en" => Algorithm::English,
+        "fi" => Algorithm::Finnish,
+        "fr" => Algorithm::French,
+        "de" => Algorithm::German,
+        "el" => Algorithm::Greek,
+        "hu" => Algorithm::Hungarian,
+        "it" => Algorithm::Italian,
+        "no" | "nb" | "nn" => Algorithm::Norwegian,
+        "pt" => Algorithm::Portuguese,
+        "ro" => Algorithm::Romanian,
+        "ru" => Algorithm::Russian,
+        "es" => Algorithm::Spanish,
+        "sv" => Algorithm::Swedish,
+        "ta" => Algorithm::Tamil,
+        "tr" => Algorithm::Turkish,
+        _ => return None,
+    })
+}
+
+fn strip_persian_suffix(word: &str) -> &str {
+    for suffix in FA_ATTACHED_SUFFIXES {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if stem.chars().count() >= FA_MIN_STEM_CHARS {
+                return stem;
+            }
+        }
+    }
+    word
+}
+
+// "کتاب‌ها", "کتاب‌هایشان" and "کتابها" all become "کتاب"
+fn stem_persian(word: &str) -> String {
+    let mut parts: Vec<&str> = word.split(ZWNJ).filter(|part| !part.is_empty()).collect();
+    if parts.len() > 1 && FA_PREFIXES.contains(&parts[0]) {
+        parts.remove(0);
+    }
+    while parts.len() > 1 && FA_JOINED_SUFFIXES.contains(&parts[parts.len() - 1]) {
+        parts.pop();
+    }
+    let last = parts.len().saturating_sub(1);
+    parts.iter()
+        .enumerate()
+        .map(|(i, part)| if i == last { strip_persian_suffix(part) } else { part })
+        .collect::<Vec<_>>()
+        .join("\u{200C}")
+}
+
+// Lowercased stem of word in language (a code or detector name). Words in
+// Arabic script get the Persian stemmer unless the language is Arabic, so
+// Persian in mixed or misdetected chat still groups; words of languages
+// without a stemmer come back lowercased.
+pub fn stem(word: &str, language: &str) -> String {
+    let word = word.to_lowercase();
+    let algorithm = algorithm(language);
+    
+    if is_arabic_script(&word) && algorithm != Some(Algorithm::Arabic) {
+        return stem_persian(&word);
+    }
+    if algorithm == Some(Algorithm::English) {
+        if let Some((_, lemma)) = EN_IRREGULAR.iter().find(|(form, _)| *form == word) {
+            return lemma.to_string();
+        }
+    }
+    match algorithm {
+        Some(algorithm) => Stemmer::create(algorithm).stem(&word).into_owned(),
+        None => word,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_groups_inflections() {
+        let english: Vec<String> = ["running", "run", "runs", "ran"].iter().map(|w| stem(w, "en")).collect();
+        assert!(english.iter().all(|s| s == "run"));
+        assert_eq!(stem("Connections", "English"), stem("connected", "en"));
+        assert_eq!(stem("Katzen", "de"), stem("Katze", "de"));
+        
+        assert_eq!(stem("کتاب‌ها", "fa"), "کتاب");
+        assert_eq!(stem("کتابها", "unknown"), "کتاب");
+        assert_eq!(stem("کتاب‌هایشان", "fa"), "کتاب");
+        assert_eq!(stem("می‌خوانم", "fa"), "خوانم");
+        assert_eq!(stem("بهتر", "fa"), "بهتر");
+        
+        // No stemmer: lowercased only
+        assert_eq!(stem("Running", "ja"), "running");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use whatlang::Lang;
+use crate::config::AppConfig;
+
+// Stop words dropped by keyword extraction. The list is picked from the
+// detected language; when that is unknown every built-in list applies, so
+// mixed Persian/English chat still loses its filler words. Words from
+// analysis.custom_stop_words in the config are dropped in every language.
+
+// Receives the ISO 639-1 code of the message language, or None when it is
+// unknown, and a lowercased word
+pub trait StopWordProvider: Send + Sync {
+    fn is_stop_word(&self, language: Option<&str>, word: &str) -> bool;
+}
+
+const EN: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "before", "being", "but", "by", "can", "could", "did", "do", "does", "for", "from", "had",
+    "has", "have", "he", "her", "here", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+    "just", "me", "more", "my", "no", "not", "of", "on", "or", "our", "out", "she", "so", "some", "than",
+    "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "to", "too", "up",
+    "us", "very", "was", "we", "were", "what", "when", "where", "which", "who", "why", "will", "with",
+    "would", "you", "your",
+];
+
+const ES: &[&str] = &[
+    "a", "al", "algo", "como", "con", "de", "del", "el", "ella", "ellos", "en", "era", "es", "esa", "ese",
+    "eso", "esta", "este", "esto", "fue", "ha", "hay", "la", "las", "le", "les", "lo", "los", "mas",
+    "más", "me", "mi", "muy", "no", "nos", "o", "para", "pero", "por", "porque", "que", "qué", "se",
+    "si", "sí", "sin", "sobre", "su", "sus", "también", "te", "tu", "un", "una", "uno", "y", "ya", "yo",
+];
+
+const FR: &[&str] = &[
+    "à", "au", "aux", "avec", "ce", "ces", "cette", "dans", "de", "des", "du", "elle", "en", "est", "et",
+    "été", "être", "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "mes", "moi",
+    "mon", "ne", "nous", "on", "ou", "où", "par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses",
+    "son", "sur", "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vous", "y",
+];
+
+const DE: &[&str] = &[
+    "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das", "dass", "dem",
+    "den", "der", "des", "die", "dies", "du", "ein", "eine", "einem", "einen", "einer", "er", "es",
+    "für", "hat", "ich", "ihr", "im", "in", "ist", "ja", "mit", "nach", "nicht", "noch", "nur", "oder",
+    "sich", "sie", "sind", "so", "um", "und", "uns", "von", "vor", "war", "was", "wie", "wir", "zu", "zum",
+    "zur",
+];
+
+const IT: &[&str] = &[
+    "a", "ad", "al", "alla", "anche", "che", "chi", "ci", "come", "con", "da", "dal", "del", "della",
+    "di", "e", "è", "ed", "gli", "ha", "ho", "i", "il", "in", "io", "la", "le", "lo", "ma", "mi", "nel",
+    "nella", "non", "o", "per", "più", "questo", "se", "si", "sono", "su", "sua", "suo", "ti", "tra",
+    "tu", "un", "una", "uno",
+];
+
+const PT: &[&str] = &[
+    "a", "ao", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "é", "ela", "ele", "em", "era",
+    "essa", "esse", "esta", "este", "eu", "foi", "isso", "isto", "já", "lhe", "mais", "mas", "me", "meu",
+    "minha", "na", "não", "nas", "no", "nos", "o", "os", "ou", "para", "pela", "pelo", "por", "que",
+    "se", "sem", "seu", "sua", "também", "um", "uma", "você",
+];
+
+const RU: &[&str] = &[
+    "а", "без", "бы", "был", "была", "были", "было", "в", "вы", "да", "для", "до", "его", "ее", "её",
+    "если", "есть", "же", "за", "и", "из", "или", "им", "их", "к", "как", "когда", "кто", "ли", "мы",
+    "на", "не", "нет", "но", "о", "об", "он", "она", "они", "оно", "от", "по", "при", "с", "так",
+    "также", "то", "только", "ты", "у", "уже", "что", "это", "я",
+];
+
+// Han, kana and hangul segment into single characters or short runs, so
+// these are particles and the most frequent function characters
+const ZH: &[&str] = &[
+    "的", "了", "是", "在", "我", "你", "他", "她", "它", "们", "这", "那", "和", "与", "也", "就",
+    "都", "而", "及", "着", "或", "一", "个", "不", "有", "吗", "呢", "吧", "啊", "很", "把", "被",
+];
+
+const JA: &[&str] = &[
+    "の", "に", "は", "を", "た", "が", "で", "て", "と", "し", "れ", "さ", "も", "な", "だ", "です",
+    "ます", "これ", "それ", "あれ", "この", "その", "から", "まで", "より", "ね", "よ", "か",
+];
+
+const KO: &[&str] = &[
+    "그", "이", "저", "것", "수", "등", "및", "또는", "그리고", "하지만", "그러나", "그래서", "있다",
+    "없다", "하다", "되다", "에서", "으로", "에게", "한", "할", "더", "잘", "좀", "나", "너", "우리",
+];
+
+const AR: &[&str] = &[
+    "في", "من", "على", "إلى", "عن", "مع", "أن", "إن", "ان", "هذا", "هذه", "ذلك", "التي", "الذي",
+    "هو", "هي", "هم", "نحن", "أنا", "انت", "كان", "كانت", "لا", "لم", "لن", "ما", "و", "أو", "ثم",
+    "قد", "كل", "بعد", "قبل", "عند", "حتى", "لكن",
+];
+
+const HI: &[&str] = &[
+    "और", "का", "की", "के", "को", "में", "से", "पर", "है", "हैं", "था", "थी", "थे", "यह", "वह", "ये",
+    "वे", "एक", "भी", "तो", "ही", "ने", "कि", "जो", "नहीं", "हो", "या", "लेकिन", "मैं", "हम", "आप",
+    "तुम", "कर", "रहा", "गया",
+];
+
+const FA: &[&str] = &[
+    "این", "آن", "که", "را", "در", "به", "از", "با", "برای", "تا", "یا", "و", "اما", "هم", "نیز",
+    "است", "بود", "شد", "شود", "هست", "های", "ها", "یک", "من", "تو", "او", "ما", "شما", "آنها", "خود",
+    "هر", "چه", "چون", "اگر", "ولی", "بر", "پس", "دیگر", "کرد", "کند", "می", "باید", "همه",
+];
+
+lazy_static! {
+    static ref BUILTIN: HashMap<&'static str, HashSet<&'static str>> = {
+        let lists: [(&str, &[&str]); 13] = [
+            ("en", EN), ("es", ES), ("fr", FR), ("de", DE), ("it", IT), ("pt", PT), ("ru", RU),
+            ("zh", ZH), ("ja", JA), ("ko", KO), ("ar", AR), ("hi", HI), ("fa", FA),
+        ];
+        lists.iter().map(|(lang, words)| (*lang, words.iter().copied().collect())).collect()
+    };
+    static ref PROVIDER: RwLock<Arc<dyn StopWordProvider>> = RwLock::new(Arc::new(BuiltinStopWords));
+    static ref CUSTOM: RwLock<HashSet<String>> = RwLock::new(custom_words(&AppConfig::get()));
+}
+
+pub struct BuiltinStopWords;
+
+impl StopWordProvider for BuiltinStopWords {
+    fn is_stop_word(&self, language: Option<&str>, word: &str) -> bool {
+        match language.and_then(|lang| BUILTIN.get(lang)) {
+            Some(words) => words.contains(word),
+            None => BUILTIN.values().any(|words| words.contains(word)),
+        }
+    }
+}
+
+// Accepts ISO 639-1 codes ("fa", "pt-BR"), ISO 639-3 codes and the language
+// names the detector reports ("فارسی", "English")
+pub fn language_code(language: &str) -> Option<&'static str> {
+    let base = language.split(['-', '_']).next().unwrap_or("").trim().to_lowercase();
+    if let Some((code, _)) = BUILTIN.get_key_value(base.as_str()) {
+        return Some(code);
+    }
+    
+    let lang = Lang::from_code(base.as_str())
+        .or_else(|| Lang::all().iter().copied().find(|l| l.name().to_lowercase() == base || l.eng_name().to_lowercase() == base))?;
+    match lang {
+        Lang::Eng => Some("en"),
+        Lang::Spa => Some("es"),
+        Lang::Fra => Some("fr"),
+        Lang::Deu => Some("de"),
+        Lang::Ita => Some("it"),
+        Lang::Por => Some("pt"),
+        Lang::Rus => Some("ru"),
+        Lang::Cmn => Some("zh"),
+        Lang::Jpn => Some("ja"),
+        Lang::Kor => Some("ko"),
+        Lang::Ara => Some("ar"),
+        Lang::Hin => Some("hi"),
+        Lang::Pes => Some("fa"),
+        _ => None,
+    }
+}
+
+fn custom_words(config: &AppConfig) -> HashSet<String> {
+    config.analysis.custom_stop_words.iter().map(|word| word.trim().to_lowercase()).filter(|word| !word.is_empty()).collect()
+}
+
+pub fn set_provider(provider: Arc<dyn StopWordProvider>) {
+    *PROVIDER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = provider;
+}
+
+pub fn reload_config() {
+    *CUSTOM.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = custom_words(&AppConfig::get());
+}
+
+// `language` is whatever the pipeline detected or was hinted
+pub fn is_stop_word(language: &str, word: &str) -> bool {
+    if CUSTOM.read().unwrap_or_else(|poisoned| poisoned.into_inner()).contains(word) {
+        return true;
+    }
+    let provider = PROVIDER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    provider.is_stop_word(language_code(language), word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_language_selection() {
+        assert_eq!(language_code("fa"), Some("fa"));
+        assert_eq!(language_code("pt-BR"), Some("pt"));
+        assert_eq!(language_code("فارسی"), Some("fa"));
+        assert_eq!(language_code("English"), Some("en"));
+        assert_eq!(language_code("cmn"), Some("zh"));
+        assert_eq!(language_code("unknown"), None);
+        
+        let builtin = BuiltinStopWords;
+        assert!(builtin.is_stop_word(Some("de"), "und"));
+        assert!(!builtin.is_stop_word(Some("en"), "und"));
+        assert!(builtin.is_stop_word(None, "und"));
+        assert!(builtin.is_stop_word(None, "برای"));
+        
+        // Every allowed language in the default config has a list
+        for lang in AppConfig::default().analysis.allowed_languages {
+            assert!(BUILTIN.contains_key(lang.as_str()), "no stop words for {}", lang);
+        }
+    }
+}
\ No newline at end of file
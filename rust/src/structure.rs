@@ -0,0 +1,205 @@
+use std::ops::Range;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Layout of a message written in Markdown or Telegram's MarkdownV2: headings,
+// lists, quotes, code and emphasis, counted from the markup rather than from
+// message entities, so it works on text that has not been sent yet. Code
+// matters most here: a pasted stack trace or config file is full of long
+// "words" and symbols that wreck readability scores, turn up as keywords and
+// trip sentiment words like "error" or "fatal". Blanking code replaces it
+// with spaces of the same byte length, so the remaining text keeps its
+// offsets.
+
+lazy_static! {
+    static ref HEADING_PATTERN: Regex = Regex::new(r"^ {0,3}#{1,6}\s+\S").unwrap();
+    // Telegram has no headings; posts put a title on a line of its own in bold
+    static ref BOLD_LINE_PATTERN: Regex = Regex::new(r"^\*{1,2}[^*\s][^*]*\*{1,2}$").unwrap();
+    static ref LIST_ITEM_PATTERN: Regex = Regex::new(r"^\s*(?:[-*+•▪◦–]|[0-9۰-۹]{1,3}[.)])\s+\S").unwrap();
+    // Also MarkdownV2's expandable quotes, **>
+    static ref QUOTE_PATTERN: Regex = Regex::new(r"^\s*(?:\*\*)?>").unwrap();
+    static ref INLINE_CODE_PATTERN: Regex = Regex::new(r"`[^`\n]+`").unwrap();
+    static ref BOLD_PATTERN: Regex = Regex::new(r"\*\*[^*\n]+\*\*|\*[^*\s][^*\n]*\*").unwrap();
+    // Underscores inside words are snake_case, not emphasis
+    static ref ITALIC_PATTERN: Regex = Regex::new(r"(?:^|[^\w_])_{1,2}[^_\s][^_\n]*_{1,2}(?:[^\w_]|$)").unwrap();
+    static ref LINK_PATTERN: Regex = Regex::new(r"\[[^\]\n]+\]\([^)\s]+\)").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&HEADING_PATTERN);
+    lazy_static::initialize(&BOLD_LINE_PATTERN);
+    lazy_static::initialize(&LIST_ITEM_PATTERN);
+    lazy_static::initialize(&QUOTE_PATTERN);
+    lazy_static::initialize(&INLINE_CODE_PATTERN);
+    lazy_static::initialize(&BOLD_PATTERN);
+    lazy_static::initialize(&ITALIC_PATTERN);
+    lazy_static::initialize(&LINK_PATTERN);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructureStats {
+    // Markdown headings and lines that are bold on their own
+    pub headings: usize,
+    // Runs of consecutive list items
+    pub lists: usize,
+    pub list_items: usize,
+    // Runs of consecutive quoted lines
+    pub quotes: usize,
+    pub quoted_lines: usize,
+    pub code_blocks: usize,
+    // Inside the fences
+    pub code_lines: usize,
+    // As named after opening fences, once each in order
+    pub code_languages: Vec<String>,
+    pub inline_code: usize,
+    pub bold: usize,
+    pub italic: usize,
+    pub links: usize,
+    // Share of the characters in code blocks and inline code
+    pub code_ratio: f64,
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+// Byte ranges of fenced code blocks, fences included, and whether the text
+// ends inside one; in_block says it starts inside one, for text that
+// continues an earlier part. An unclosed fence runs to the end.
+fn code_blocks(text: &str, in_block: bool) -> (Vec<Range<usize>>, bool) {
+    let mut blocks = Vec::new();
+    let mut open = if in_block { Some(0) } else { None };
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        if is_fence(line) {
+            match open.take() {
+                Some(from) => blocks.push(from..start + line.len()),
+                None => open = Some(start),
+            }
+        }
+        start += line.len();
+    }
+    if let Some(from) = open {
+        blocks.push(from..text.len());
+    }
+    (blocks, open.is_some())
+}
+
+// Fenced blocks, then inline code outside them, in order
+fn code_spans(text: &str, in_block: bool) -> (Vec<Range<usize>>, bool) {
+    let (blocks, open) = code_blocks(text, in_block);
+    let mut spans = blocks.clone();
+    let mut from = 0;
+    for block in blocks.iter().cloned().chain(std::iter::once(text.len()..text.len())) {
+        spans.extend(INLINE_CODE_PATTERN.find_iter(&text[from..block.start]).map(|m| from + m.start()..from + m.end()));
+        from = block.end;
+    }
+    spans.sort_by_key(|span| span.start);
+    (spans, open)
+}
+
+fn blank(text: &str, spans: &[Range<usize>]) -> String {
+    let mut blanked = String::with_capacity(text.len());
+    let mut from = 0;
+    for span in spans {
+        blanked.push_str(&text[from..span.start]);
+        // Byte for byte, keeping line breaks so lines stay lines
+        blanked.extend(text[span.clone()].bytes().map(|b| if b == b'\n' { '\n' } else { ' ' }));
+        from = span.end;
+    }
+    blanked.push_str(&text[from..]);
+    blanked
+}
+
+// Code blocks and inline code replaced by spaces, byte offsets unchanged
+pub fn blank_code(text: &str) -> String {
+    blank_code_continuing(text, &mut false)
+}
+
+// For text that arrives in parts: in_block carries an open fence from one
+// part to the next
+pub fn blank_code_continuing(text: &str, in_block: &mut bool) -> String {
+    let (spans, open) = code_spans(text, *in_block);
+    *in_block = open;
+    blank(text, &spans)
+}
+
+pub fn analyze(text: &str) -> StructureStats {
+    let mut stats = StructureStats::default();
+    let (blocks, _) = code_blocks(text, false);
+    for block in &blocks {
+        let lines: Vec<&str> = text[block.clone()].lines().collect();
+        let closed = lines.len() > 1 && lines.last().is_some_and(|line| is_fence(line));
+        stats.code_blocks += 1;
+        stats.code_lines += lines.len() - 1 - usize::from(closed);
+        let language = lines[0].trim_start().trim_start_matches('`').trim().to_lowercase();
+        if !language.is_empty() && !stats.code_languages.contains(&language) {
+            stats.code_languages.push(language);
+        }
+    }
+    let (spans, _) = code_spans(text, false);
+    stats.inline_code = spans.len() - blocks.len();
+    let total = text.chars().count();
+    if total > 0 {
+        let code: usize = spans.iter().map(|span| text[span.clone()].chars().count()).sum();
+        stats.code_ratio = code as f64 / total as f64;
+    }
+    
+    // The rest is read with the code blanked, so a "# comment" or "- x" in a
+    // code block is not a heading or list item
+    let prose = blank(text, &spans);
+    let (mut in_list, mut in_quote) = (false, false);
+    for line in prose.lines() {
+        if HEADING_PATTERN.is_match(line) || BOLD_LINE_PATTERN.is_match(line.trim()) {
+            stats.headings += 1;
+        }
+        let item = LIST_ITEM_PATTERN.is_match(line) && !BOLD_LINE_PATTERN.is_match(line.trim());
+        if item {
+            stats.list_items += 1;
+            stats.lists += usize::from(!in_list);
+        }
+        let quoted = QUOTE_PATTERN.is_match(line);
+        if quoted {
+            stats.quoted_lines += 1;
+            stats.quotes += usize::from(!in_quote);
+        }
+        (in_list, in_quote) = (item, quoted);
+    }
+    stats.bold = BOLD_PATTERN.find_iter(&prose).count();
+    stats.italic = ITALIC_PATTERN.find_iter(&prose).count();
+    stats.links = LINK_PATTERN.find_iter(&prose).count();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_structure() {
+        let text = "*Release notes*\n\nWe fixed the crash in `parse_config`:\n\n```rust\n// error: fatal\nfn main() {}\n```\n\n- faster startup\n- fewer __warnings__\n\n1. update\n2. restart\n\n> it works now\n> thanks\n\nSee [the docs](https://example.com), snake_case_names stay.";
+        let stats = analyze(text);
+        assert_eq!(stats.headings, 1);
+        assert_eq!((stats.lists, stats.list_items), (2, 4));
+        assert_eq!((stats.quotes, stats.quoted_lines), (1, 2));
+        assert_eq!((stats.code_blocks, stats.code_lines, stats.inline_code), (1, 2, 1));
+        assert_eq!(stats.code_languages, vec!["rust"]);
+        assert_eq!((stats.bold, stats.italic, stats.links), (1, 1, 1));
+        assert!(stats.code_ratio > 0.1 && stats.code_ratio < 0.3);
+        
+        // Blanked code keeps the offsets of the text around it
+        let blanked = blank_code(text);
+        assert_eq!(blanked.len(), text.len());
+        assert!(!blanked.contains("fatal") && !blanked.contains("parse_config"));
+        assert_eq!(blanked.find("faster"), text.find("faster"));
+        
+        // A fence left open in one part closes in the next
+        let mut in_block = false;
+        assert_eq!(blank_code_continuing("see\n```\nlet x", &mut in_block), "see\n   \n     ");
+        assert!(in_block);
+        assert_eq!(blank_code_continuing(" = 1;\n```\nok", &mut in_block), "     \n   \nok");
+        assert!(!in_block);
+    }
+}
\ No newline at end of file
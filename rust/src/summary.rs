@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::{keywords, language};
+use crate::language::LanguageSpan;
+use crate::truncation::{truncate_for_telegram, TextEntity, TruncateOptions};
+
+// Extractive summaries ranked with TextRank: sentences are nodes, edges are
+// weighted by how many content words two sentences share (normalized by
+// their lengths), and PageRank over that graph favours sentences that many
+// others overlap with. The picked sentences keep their original order.
+
+const DAMPING: f64 = 0.85;
+const MAX_ITERATIONS: usize = 50;
+const CONVERGENCE: f64 = 1e-6;
+// Telegram counts message length in UTF-16 code units
+pub const TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+const ELLIPSIS: char = '…';
+
+lazy_static! {
+    // Spans a truncated summary must keep whole or leave out: links,
+    // addresses, mentions, hashtags, bot commands and capitalized names
+    static ref ENTITY_PATTERN: Regex = Regex::new(
+        r"(?:https?://|www\.|t\.me/)\S+|[\w.+-]+@[\w-]+(?:\.[\w-]+)+|[@#/]\w+|\p{Lu}\p{Ll}+(?:\s+\p{Lu}\p{Ll}+)+"
+    ).unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&ENTITY_PATTERN);
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryOptions {
+    // 0 returns an empty summary
+    pub max_sentences: usize,
+    // In UTF-16 code units like Telegram; 0 for no budget
+    pub max_chars: usize,
+    // Never cut a link, mention, hashtag or name when the best sentence has
+    // to be truncated
+    pub preserve_entities: bool,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            max_sentences: 2,
+            max_chars: TELEGRAM_MESSAGE_LIMIT,
+            preserve_entities: true,
+        }
+    }
+}
+
+impl SummaryOptions {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid summary options: {}", e))
+    }
+}
+
+fn length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+// Sentences end at terminal punctuation followed by whitespace, or at a line
+// break; the punctuation stays with its sentence
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => Some(i),
+            '.' | '!' | '?' | '…' | '؟' | '。' | '！' | '？' => {
+                while let Some(&(_, next)) = chars.peek() {
+                    if matches!(next, '.' | '!' | '?' | '…' | '؟' | '"' | '\'' | ')' | '»' | '”') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match chars.peek() {
+                    Some(&(j, next)) if next.is_whitespace() => Some(j),
+                    None => Some(text.len()),
+                    // Full-width stops need no space after them
+                    Some(&(j, _)) if matches!(c, '。' | '！' | '？') => Some(j),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(end) = end {
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.len() < 2 && b.len() < 2 {
+        // ln(1) + ln(1) would divide by zero
+        return if !a.is_empty() && a == b { 1.0 } else { 0.0 };
+    }
+    let shared = a.intersection(b).count() as f64;
+    shared / ((a.len().max(1) as f64).ln() + (b.len().max(1) as f64).ln())
+}
+
+// TextRank score per sentence, in sentence order
+pub fn rank_sentences(sentences: &[&str], language: &str) -> Vec<f64> {
+    rank(&sentences.iter().map(|s| keywords::terms(s, language).into_iter().collect()).collect::<Vec<_>>())
+}
+
+fn rank(terms: &[HashSet<String>]) -> Vec<f64> {
+    let n = terms.len();
+    let mut weights = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let weight = similarity(&terms[i], &terms[j]);
+            weights[i][j] = weight;
+            weights[j][i] = weight;
+        }
+    }
+    let out_weight: Vec<f64> = weights.iter().map(|row| row.iter().sum()).collect();
+    
+    let mut scores = vec![1.0 / n.max(1) as f64; n];
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<f64> = (0..n).map(|i| {
+            let incoming: f64 = (0..n)
+                .filter(|&j| out_weight[j] > 0.0)
+                .map(|j| weights[j][i] / out_weight[j] * scores[j])
+                .sum();
+            (1.0 - DAMPING) / n as f64 + DAMPING * incoming
+        }).collect();
+        let delta: f64 = next.iter().zip(&scores).map(|(a, b)| (a - b).abs()).sum();
+        scores = next;
+        if delta < CONVERGENCE {
+            break;
+        }
+    }
+    scores
+}
+
+// Cuts a sentence that cannot fit whole at the last word boundary within
+// max_chars, leaving room for an ellipsis. With preserve_entities the cut
+// moves before any entity it would split; without, a single word longer than
+// the budget is cut mid-word as a last resort.
+pub fn truncate(sentence: &str, max_chars: usize, preserve_entities: bool) -> String {
+    if length(sentence) <= max_chars {
+        return sentence.to_string();
+    }
+    if max_chars <= ELLIPSIS.len_utf16() {
+        return String::new();
+    }
+    
+    // truncate_for_telegram keeps atomic entities whole; the type only has
+    // to be one of those
+    let entities: Vec<TextEntity> = if preserve_entities {
+        ENTITY_PATTERN.find_iter(sentence)
+            .map(|m| TextEntity {
+                entity_type: "url".to_string(),
+                offset: length(&sentence[..m.start()]),
+                length: length(m.as_str()),
+                extra: serde_json::Map::new(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let options = TruncateOptions { ellipsis: ELLIPSIS.to_string(), word_boundary: true };
+    let truncated = match truncate_for_telegram(sentence, &entities, max_chars, &options) {
+        Ok(truncated) => truncated.text,
+        Err(_) => return String::new(),
+    };
+    let kept = truncated.strip_suffix(ELLIPSIS).unwrap_or(&truncated);
+    
+    // Without a space or an entity after the cut it fell mid-word
+    let at_boundary = sentence[kept.len()..].starts_with(char::is_whitespace)
+        || entities.iter().any(|entity| entity.offset == length(kept));
+    if preserve_entities && !at_boundary {
+        return String::new();
+    }
+    let kept = kept.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | ':' | '-' | '—'));
+    if kept.is_empty() {
+        return String::new();
+    }
+    format!("{}{}", kept, ELLIPSIS)
+}
+
+// Up to max_sentences of the best sentences whose joined length stays within
+// max_chars. A sentence too long for what is left of the budget is skipped
+// for a shorter, lower ranked one; when not even one fits, the best sentence
+// is truncated. An "unknown" language is detected per segment, so a Persian
+// sentence in an English message still loses its Persian stop words.
+pub fn summarize(text: &str, language: &str, options: &SummaryOptions) -> String {
+    let spans = if language == "unknown" {
+        language::detect_languages_segmented(text)
+    } else {
+        language::whole(text, language, 1.0)
+    };
+    summarize_in(text, &spans, options)
+}
+
+pub(crate) fn summarize_in(text: &str, spans: &[LanguageSpan], options: &SummaryOptions) -> String {
+    let sentences = split_sentences(text);
+    if options.max_sentences == 0 || sentences.is_empty() {
+        return String::new();
+    }
+    let fits = |chars: usize| options.max_chars == 0 || chars <= options.max_chars;
+    if sentences.len() <= options.max_sentences {
+        let whole = sentences.join(" ");
+        if fits(length(&whole)) {
+            return whole;
+        }
+    }
+    
+    // split_sentences returns slices of text, so each one's offset in it
+    // finds its spans
+    let terms: Vec<HashSet<String>> = sentences.iter().map(|sentence| {
+        let start = sentence.as_ptr() as usize - text.as_ptr() as usize;
+        let sentence_spans = language::slice(spans, start, start + sentence.len());
+        keywords::tagged_terms(sentence, &sentence_spans).into_iter().map(|(term, _)| term).collect()
+    }).collect();
+    let scores = rank(&terms);
+    let mut order: Vec<usize> = (0..sentences.len()).collect();
+    // Ties go to the earlier sentence, which usually introduces the topic
+    order.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b)));
+    
+    let mut picked = Vec::new();
+    let mut used = 0;
+    for &i in &order {
+        if picked.len() == options.max_sentences {
+            break;
+        }
+        let cost = length(sentences[i]) + if picked.is_empty() { 0 } else { 1 };
+        if fits(used + cost) {
+            picked.push(i);
+            used += cost;
+        }
+    }
+    if picked.is_empty() {
+        return truncate(sentences[order[0]], options.max_chars, options.preserve_entities);
+    }
+    picked.sort_unstable();
+    picked.iter().map(|&i| sentences[i]).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_sentence_splitting() {
+        assert_eq!(split_sentences("Version 2.1 is out! Update now... Thanks"), vec!["Version 2.1 is out!", "Update now...", "Thanks"]);
+        assert_eq!(split_sentences("سلام. حالت چطوره؟ خوبم"), vec!["سلام.", "حالت چطوره؟", "خوبم"]);
+        assert_eq!(split_sentences("line one\nline two"), vec!["line one", "line two"]);
+        assert!(split_sentences("  ").is_empty());
+    }
+    
+    #[test]
+    fn test_textrank_picks_central_sentences_in_order() {
+        let text = "The release adds offline sync for chat history. \
+            Offline sync stores chat history on the device. \
+            Our office cat enjoyed the launch party. \
+            Chat history sync also works for shared media. \
+            Weather was nice.";
+        let options = |max_sentences, max_chars| SummaryOptions { max_sentences, max_chars, preserve_entities: true };
+        let summary = summarize(text, "en", &options(2, 0));
+        assert_eq!(summary, "The release adds offline sync for chat history. Offline sync stores chat history on the device.");
+        
+        // The budget skips long sentences for ones that still fit
+        let short = summarize(text, "en", &options(2, 60));
+        assert!(short.chars().count() <= 60);
+        assert!(!short.is_empty());
+        assert_eq!(summarize("Short text. Stays whole.", "en", &options(3, 0)), "Short text. Stays whole.");
+        assert!(summarize(text, "en", &options(0, 0)).is_empty());
+    }
+    
+    #[test]
+    fn test_truncation_keeps_entities_whole() {
+        let sentence = "Read the full changelog at https://example.com/releases/2024 before upgrading";
+        assert_eq!(truncate(sentence, 40, true), "Read the full changelog at…");
+        assert_eq!(truncate(sentence, 40, false), "Read the full changelog at…");
+        assert_eq!(truncate(sentence, 50, true), "Read the full changelog at…");
+        assert_eq!(truncate(sentence, 50, false), "Read the full changelog at…");
+        assert_eq!(truncate("Thanks to Alice Johnson for the fix", 17, true), "Thanks to…");
+        assert_eq!(truncate("Thanks to Alice Johnson for the fix", 17, false), "Thanks to Alice…");
+        assert_eq!(truncate("supercalifragilistic", 8, false), "superca…");
+        assert_eq!(truncate("supercalifragilistic", 8, true), "");
+        // Emoji take two UTF-16 units
+        assert!(length(&truncate(&"🎉 ".repeat(10), 9, true)) <= 9);
+        
+        let long = "word ".repeat(2000);
+        let summary = summarize(&long, "en", &SummaryOptions::default());
+        assert!(length(&summary) <= TELEGRAM_MESSAGE_LIMIT);
+        assert!(summary.ends_with("word…"));
+    }
+}
\ No newline at end of file
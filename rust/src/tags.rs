@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Telegram / Twitter style #hashtags, @mentions and $CASHTAGS of a text,
+// counted after case folding: hashtags and mentions in lower case, since
+// Telegram matches both case-insensitively, and cashtags in upper case like
+// ticker symbols. A sigil only starts a tag after whitespace or punctuation,
+// so e-mail addresses, URL anchors and amounts such as $100 are skipped.
+
+lazy_static! {
+    static ref HASHTAG_PATTERN: Regex = Regex::new(r"(?:^|[^\w&/#])#(\w{1,64})").unwrap();
+    // Usernames are 3-32 Latin letters, digits and underscores
+    static ref MENTION_PATTERN: Regex = Regex::new(r"(?:^|[^\w@./])@([A-Za-z][A-Za-z0-9_]{2,31})\b").unwrap();
+    // Tickers, optionally with a class or exchange suffix: $BTC, $BRK.B
+    static ref CASHTAG_PATTERN: Regex = Regex::new(r"(?:^|[^\w$])\$([A-Za-z]{1,6}(?:\.[A-Za-z]{1,2})?)\b").unwrap();
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&HASHTAG_PATTERN);
+    lazy_static::initialize(&MENTION_PATTERN);
+    lazy_static::initialize(&CASHTAG_PATTERN);
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagCount {
+    // Normalized, with its sigil: "#rust", "@durov", "$BTC"
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagStats {
+    // Every tag of the three kinds
+    pub total: usize,
+    // Most used first
+    pub hashtags: Vec<TagCount>,
+    pub mentions: Vec<TagCount>,
+    pub cashtags: Vec<TagCount>,
+}
+
+impl TagStats {
+    // Totals over several texts, e.g. a chat history
+    pub fn combine<'a>(stats: impl IntoIterator<Item = &'a TagStats>) -> Self {
+        let mut hashtags = HashMap::new();
+        let mut mentions = HashMap::new();
+        let mut cashtags = HashMap::new();
+        for stats in stats {
+            for (counts, tags) in [(&mut hashtags, &stats.hashtags), (&mut mentions, &stats.mentions), (&mut cashtags, &stats.cashtags)] {
+                for tag in tags {
+                    let first = counts.len();
+                    counts.entry(tag.tag.clone()).or_insert((0, first)).0 += tag.count;
+                }
+            }
+        }
+        Self::from_counts(hashtags, mentions, cashtags)
+    }
+    
+    fn from_counts(hashtags: Counts, mentions: Counts, cashtags: Counts) -> Self {
+        let total = [&hashtags, &mentions, &cashtags].iter().flat_map(|counts| counts.values()).map(|(count, _)| count).sum();
+        Self { total, hashtags: ranked(hashtags), mentions: ranked(mentions), cashtags: ranked(cashtags) }
+    }
+}
+
+// Count and order of first use per tag
+type Counts = HashMap<String, (usize, usize)>;
+
+fn ranked(counts: Counts) -> Vec<TagCount> {
+    let mut counts: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    // Ties keep the order of first use
+    counts.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.1.1.cmp(&b.1.1)));
+    counts.into_iter().map(|(tag, (count, _))| TagCount { tag, count }).collect()
+}
+
+fn count(text: &str, pattern: &Regex, normalize: impl Fn(&str) -> Option<String>) -> Counts {
+    let mut counts = Counts::new();
+    for cap in pattern.captures_iter(text) {
+        if let Some(tag) = normalize(&cap[1]) {
+            let first = counts.len();
+            counts.entry(tag).or_insert((0, first)).0 += 1;
+        }
+    }
+    counts
+}
+
+pub fn stats(text: &str) -> TagStats {
+    // "#1" numbers a list item rather than tagging it
+    let hashtags = count(text, &HASHTAG_PATTERN, |tag| tag.chars().any(char::is_alphabetic).then(|| format!("#{}", tag.to_lowercase())));
+    let mentions = count(text, &MENTION_PATTERN, |name| Some(format!("@{}", name.to_lowercase())));
+    let cashtags = count(text, &CASHTAG_PATTERN, |ticker| Some(format!("${}", ticker.to_uppercase())));
+    TagStats::from_counts(hashtags, mentions, cashtags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_tags_are_counted_and_normalized() {
+        let found = stats("#Rust release! Thanks @Durov and @durov, #rust #Release #1 #خبر_فوری $btc up, $ETH down, $100 lost; mail me at ops@example.com, see https://x.io/#anchor");
+        let tags = |tags: &[TagCount]| tags.iter().map(|t| (t.tag.clone(), t.count)).collect::<Vec<_>>();
+        let pair = |tag: &str, count: usize| (tag.to_string(), count);
+        assert_eq!(tags(&found.hashtags), vec![pair("#rust", 2), pair("#release", 1), pair("#خبر_فوری", 1)]);
+        assert_eq!(tags(&found.mentions), vec![pair("@durov", 2)]);
+        assert_eq!(tags(&found.cashtags), vec![pair("$BTC", 1), pair("$ETH", 1)]);
+        assert_eq!(found.total, 8);
+        
+        let combined = TagStats::combine([&found, &stats("more #release news")]);
+        assert_eq!(tags(&combined.hashtags)[..2], [pair("#rust", 2), pair("#release", 2)]);
+        assert_eq!(combined.total, 9);
+        assert_eq!(stats("nothing tagged here"), TagStats::default());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,305 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use serde_json::{json, Value};
+use crate::config::TelemetryConfig;
+use crate::logging::{LogEntry, LogLevel, LogSink};
+use crate::performance::{self, PerformanceProfile};
+
+const MAX_BATCH_SIZE: usize = 512;
+const QUEUE_CAPACITY: usize = 10_000;
+const REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+// OTLP aggregation temporality for running totals
+const AGGREGATION_TEMPORALITY_CUMULATIVE: i32 = 2;
+
+// Log sink that hands entries to a background exporter thread, which posts
+// them in batches to the collector together with the metrics rollups
+pub struct OtlpLogSink {
+    sender: Option<Sender<LogEntry>>,
+    exporter: Option<thread::JoinHandle<()>>,
+}
+
+impl OtlpLogSink {
+    pub fn start(config: &TelemetryConfig) -> Option<Self> {
+        let exporter = OtlpExporter {
+            endpoint: config.otlp_endpoint.clone()?,
+            headers: config.otlp_headers.iter().map(|(k, v)| (k.clone(), v.expose().to_string())).collect(),
+            service_name: config.service_name.clone(),
+            interval: Duration::from_secs(config.export_interval_seconds.max(1)),
+            export_metrics: config.export_metrics,
+            start_time: Utc::now(),
+        };
+        
+        let (sender, receiver) = bounded(QUEUE_CAPACITY);
+        let handle = thread::Builder::new()
+            .name("aiogram-otlp".to_string())
+            .spawn(move || exporter.run(receiver))
+            .ok()?;
+        
+        Some(Self { sender: Some(sender), exporter: Some(handle) })
+    }
+}
+
+impl LogSink for OtlpLogSink {
+    fn write(&mut self, entry: &LogEntry) {
+        // Entries are dropped instead of blocking the logger when the
+        // collector falls behind
+        if let Some(sender) = &self.sender {
+            let _ = sender.try_send(entry.clone());
+        }
+    }
+    
+    // Disconnecting the queue makes the exporter post its last batch; wait
+    // for it so nothing is lost when the host unloads the library
+    fn close(&mut self) {
+        self.sender = None;
+        if let Some(handle) = self.exporter.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct OtlpExporter {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    service_name: String,
+    interval: Duration,
+    export_metrics: bool,
+    start_time: DateTime<Utc>,
+}
+
+impl OtlpExporter {
+    // Runs until the sink is dropped (e.g. on config reload), then flushes
+    // whatever is still buffered
+    fn run(self, receiver: Receiver<LogEntry>) {
+        let mut batch = Vec::new();
+        let mut last_export = Instant::now();
+        
+        loop {
+            let timeout = self.interval.saturating_sub(last_export.elapsed());
+            match receiver.recv_timeout(timeout) {
+                Ok(entry) => {
+                    batch.push(entry);
+                    if batch.len() < MAX_BATCH_SIZE {
+                        continue;
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush(&mut batch, true);
+                    return;
+                }
+            }
+            
+            let interval_elapsed = last_export.elapsed() >= self.interval;
+            self.flush(&mut batch, interval_elapsed);
+            if interval_elapsed {
+                last_export = Instant::now();
+            }
+        }
+    }
+    
+    fn flush(&self, batch: &mut Vec<LogEntry>, include_metrics: bool) {
+        if !batch.is_empty() {
+            self.post("/v1/logs", &logs_payload(&self.service_name, batch));
+            batch.clear();
+        }
+        
+        if include_metrics && self.export_metrics {
+            let profiles = performance::get_performance_profiles();
+            if !profiles.is_empty() {
+                self.post("/v1/metrics", &metrics_payload(&self.service_name, &profiles, self.start_time, Utc::now()));
+            }
+        }
+    }
+    
+    fn post(&self, path: &str, body: &Value) {
+        let url = format!("{}{}", self.endpoint.trim_end_matches('/'), path);
+        let mut request = ureq::post(&url)
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+            .set("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        
+        // Export is best effort; failures must not feed back into the log
+        // pipeline they are exporting
+        let _ = request.send_string(&body.to_string());
+    }
+}
+
+fn unix_nanos(timestamp: DateTime<Utc>) -> String {
+    (timestamp.timestamp() as i128 * 1_000_000_000 + timestamp.timestamp_subsec_nanos() as i128).to_string()
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn resource(service_name: &str) -> Value {
+    json!({ "attributes": [string_attribute("service.name", service_name)] })
+}
+
+fn scope() -> Value {
+    json!({ "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn severity_number(level: &LogLevel) -> i32 {
+    match level {
+        LogLevel::DEBUG => 5,
+        LogLevel::INFO => 9,
+        LogLevel::WARN => 13,
+        LogLevel::ERROR => 17,
+        LogLevel::CRITICAL => 21,
+    }
+}
+
+// OTLP/HTTP JSON encoding of a batch of log entries
+fn logs_payload(service_name: &str, entries: &[LogEntry]) -> Value {
+    let records: Vec<Value> = entries.iter()
+        .map(|entry| {
+            let mut attributes = vec![
+                string_attribute("code.namespace", &entry.module),
+                string_attribute("code.function", &entry.function),
+                json!({ "key": "code.lineno", "value": { "intValue": entry.line.to_string() } }),
+            ];
+            if let Some(ref request_id) = entry.request_id {
+                attributes.push(string_attribute("request_id", request_id));
+            }
+            if let Some(ref user_id) = entry.user_id {
+                attributes.push(string_attribute("user_id", user_id));
+            }
+            for (key, value) in &entry.metadata {
+                attributes.push(string_attribute(key, value));
+            }
+            
+            json!({
+                "timeUnixNano": unix_nanos(entry.timestamp),
+                "severityNumber": severity_number(&entry.level),
+                "severityText": format!("{:?}", entry.level),
+                "body": { "stringValue": entry.message },
+                "attributes": attributes,
+            })
+        })
+        .collect();
+    
+    json!({
+        "resourceLogs": [{
+            "resource": resource(service_name),
+            "scopeLogs": [{ "scope": scope(), "logRecords": records }]
+        }]
+    })
+}
+
+// OTLP/HTTP JSON encoding of the per-operation performance rollups
+fn metrics_payload(service_name: &str, profiles: &[PerformanceProfile], start_time: DateTime<Utc>, now: DateTime<Utc>) -> Value {
+    let start = unix_nanos(start_time);
+    let time = unix_nanos(now);
+    
+    let sum = |name: &str, unit: &str, value: fn(&PerformanceProfile) -> u64| {
+        let points: Vec<Value> = profiles.iter()
+            .map(|profile| json!({
+                "attributes": [string_attribute("operation", &profile.operation_name)],
+                "startTimeUnixNano": start,
+                "timeUnixNano": time,
+                "asInt": value(profile).to_string(),
+            }))
+            .collect();
+        json!({
+            "name": name,
+            "unit": unit,
+            "sum": {
+                "aggregationTemporality": AGGREGATION_TEMPORALITY_CUMULATIVE,
+                "isMonotonic": true,
+                "dataPoints": points,
+            }
+        })
+    };
+    
+    let gauge = |name: &str, unit: &str, value: fn(&PerformanceProfile) -> f64| {
+        let points: Vec<Value> = profiles.iter()
+            .map(|profile| json!({
+                "attributes": [string_attribute("operation", &profile.operation_name)],
+                "timeUnixNano": time,
+                "asDouble": value(profile),
+            }))
+            .collect();
+        json!({ "name": name, "unit": unit, "gauge": { "dataPoints": points } })
+    };
+    
+    let metrics = vec![
+        sum("aiogram.operation.calls", "1", |p| p.total_calls),
+        sum("aiogram.operation.duration", "ms", |p| p.total_duration_ms),
+        gauge("aiogram.operation.duration.avg", "ms", |p| p.avg_duration_ms),
+        gauge("aiogram.operation.error_rate", "1", |p| p.error_rate),
+        gauge("aiogram.operation.cache_hit_rate", "1", |p| p.cache_hit_rate),
+    ];
+    
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(service_name),
+            "scopeMetrics": [{ "scope": scope(), "metrics": metrics }]
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    
+    #[test]
+    fn test_logs_payload() {
+        let entry = LogEntry {
+            timestamp: DateTime::from_timestamp(1_700_000_000, 5).unwrap(),
+            level: LogLevel::WARN,
+            message: "Slow analysis".to_string(),
+            module: "analysis".to_string(),
+            function: "analyze_text".to_string(),
+            line: 42,
+            user_id: None,
+            request_id: Some("req_1".to_string()),
+            duration_ms: None,
+            metadata: HashMap::new(),
+        };
+        
+        let payload = logs_payload("bot", &[entry]);
+        let resource_logs = &payload["resourceLogs"][0];
+        assert_eq!(resource_logs["resource"]["attributes"][0]["value"]["stringValue"], "bot");
+        
+        let record = &resource_logs["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["timeUnixNano"], "1700000000000000005");
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["body"]["stringValue"], "Slow analysis");
+        assert!(record["attributes"].as_array().unwrap().iter().any(|a| a["key"] == "request_id"));
+    }
+    
+    #[test]
+    fn test_metrics_payload() {
+        let profile = PerformanceProfile {
+            operation_name: "text_analysis".to_string(),
+            total_calls: 4,
+            total_duration_ms: 100,
+            avg_duration_ms: 25.0,
+            min_duration_ms: 10,
+            max_duration_ms: 40,
+            memory_usage_mb: 0.0,
+            cpu_usage_percent: 0.0,
+            cache_hit_rate: 0.5,
+            error_rate: 0.0,
+        };
+        
+        let now = Utc::now();
+        let payload = metrics_payload("bot", &[profile], now, now);
+        let metrics = payload["resourceMetrics"][0]["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        
+        let calls = metrics.iter().find(|m| m["name"] == "aiogram.operation.calls").unwrap();
+        assert_eq!(calls["sum"]["dataPoints"][0]["asInt"], "4");
+        assert_eq!(calls["sum"]["aggregationTemporality"], AGGREGATION_TEMPORALITY_CUMULATIVE);
+        
+        let avg = metrics.iter().find(|m| m["name"] == "aiogram.operation.duration.avg").unwrap();
+        assert_eq!(avg["gauge"]["dataPoints"][0]["asDouble"], 25.0);
+    }
+}
\ No newline at end of file
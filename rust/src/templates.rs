@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use crate::i18n::{self, PluralCategory};
+
+// Reply templates in a small handlebars-like syntax:
+//
+//   {{user.name}}                        interpolation
+//   {{#if premium}}...{{else}}...{{/if}}  conditionals ({{#unless}} negates)
+//   {{plural count one="# file" other="# files"}}
+//   {{! comment }}
+//
+// Output is Telegram MarkdownV2. Every interpolated value, including the
+// count substituted for '#', is escaped, so user-controlled data can never
+// inject formatting. The template text itself is passed through as written.
+
+// Blocks are rendered recursively
+const MAX_BLOCK_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        path: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Plural {
+        path: String,
+        forms: HashMap<PluralCategory, String>,
+    },
+}
+
+struct Block {
+    path: String,
+    negate: bool,
+    then: Vec<Node>,
+    otherwise: Option<Vec<Node>>,
+}
+
+impl Block {
+    fn nodes(&mut self) -> &mut Vec<Node> {
+        match self.otherwise {
+            Some(ref mut otherwise) => otherwise,
+            None => &mut self.then,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+fn is_valid_path(path: &str) -> bool {
+    !path.is_empty() && path.split('.').all(|segment| {
+        !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+fn parse_path(path: &str) -> Result<String, String> {
+    if is_valid_path(path) {
+        Ok(path.to_string())
+    } else {
+        Err(format!("Invalid variable name: {:?}", path))
+    }
+}
+
+// `count one="# file" other="# files"`; quotes inside a form are written \"
+fn parse_plural(args: &str) -> Result<Node, String> {
+    let args = args.trim_start();
+    let (path, mut rest) = args.split_at(args.find(char::is_whitespace).unwrap_or(args.len()));
+    let path = parse_path(path)?;
+    let mut forms = HashMap::new();
+    
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let eq = rest.find('=').ok_or_else(|| format!("Expected category=\"form\" in plural, found {:?}", rest))?;
+        let name = rest[..eq].trim();
+        let category = PluralCategory::parse(name).ok_or_else(|| format!("Unknown plural category: {:?}", name))?;
+        
+        let mut chars = rest[eq + 1..].char_indices();
+        if chars.next().map(|(_, c)| c) != Some('"') {
+            return Err(format!("Plural form for {} must be quoted", name));
+        }
+        let mut form = String::new();
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in chars {
+            if escaped {
+                form.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                end = Some(i);
+                break;
+            } else {
+                form.push(c);
+            }
+        }
+        let end = end.ok_or_else(|| format!("Unterminated plural form for {}", name))?;
+        forms.insert(category, form);
+        rest = &rest[eq + 1 + end + 1..];
+    }
+    
+    if !forms.contains_key(&PluralCategory::OTHER) {
+        return Err(format!("plural {} needs an \"other\" form", path));
+    }
+    Ok(Node::Plural { path, forms })
+}
+
+impl Template {
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let mut root = Vec::new();
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut rest = source;
+        
+        while let Some(start) = rest.find("{{") {
+            let text = &rest[..start];
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| "Unclosed {{ tag".to_string())?;
+            let tag = after[..end].trim();
+            rest = &after[end + 2..];
+            
+            let target = match blocks.last_mut() {
+                Some(block) => block.nodes(),
+                None => &mut root,
+            };
+            if !text.is_empty() {
+                target.push(Node::Text(text.to_string()));
+            }
+            
+            if tag.starts_with('!') {
+                continue;
+            } else if let Some(path) = tag.strip_prefix("#if ") {
+                blocks.push(Block { path: parse_path(path.trim())?, negate: false, then: Vec::new(), otherwise: None });
+            } else if let Some(path) = tag.strip_prefix("#unless ") {
+                blocks.push(Block { path: parse_path(path.trim())?, negate: true, then: Vec::new(), otherwise: None });
+            } else if tag == "else" {
+                match blocks.last_mut() {
+                    Some(block) if block.otherwise.is_none() => block.otherwise = Some(Vec::new()),
+                    Some(_) => return Err("Duplicate {{else}}".to_string()),
+                    None => return Err("{{else}} outside of a block".to_string()),
+                }
+            } else if tag == "/if" || tag == "/unless" {
+                let block = blocks.pop().ok_or_else(|| format!("Unexpected {{{{{}}}}}", tag))?;
+                if block.negate != (tag == "/unless") {
+                    return Err(format!("Mismatched {{{{{}}}}} for block on {}", tag, block.path));
+                }
+                let node = Node::If {
+                    path: block.path,
+                    negate: block.negate,
+                    then: block.then,
+                    otherwise: block.otherwise.unwrap_or_default(),
+                };
+                match blocks.last_mut() {
+                    Some(parent) => parent.nodes().push(node),
+                    None => root.push(node),
+                }
+            } else if let Some(args) = tag.strip_prefix("plural ") {
+                target.push(parse_plural(args)?);
+            } else {
+                target.push(Node::Var(parse_path(tag)?));
+            }
+            
+            if blocks.len() > MAX_BLOCK_DEPTH {
+                return Err(format!("Blocks nested deeper than {} levels", MAX_BLOCK_DEPTH));
+            }
+        }
+        
+        if let Some(block) = blocks.last() {
+            return Err(format!("Unclosed block on {}", block.path));
+        }
+        if !rest.is_empty() {
+            root.push(Node::Text(rest.to_string()));
+        }
+        Ok(Template { nodes: root })
+    }
+    
+    // Interpolating a variable missing from `vars` is an error so a typo in a
+    // template cannot silently send a half-empty reply; conditionals treat
+    // missing variables as false
+    pub fn render(&self, vars: &Value, lang: &str) -> Result<String, String> {
+        let mut output = String::new();
+        render_nodes(&self.nodes, vars, lang, &mut output)?;
+        Ok(output)
+    }
+}
+
+fn lookup<'a>(vars: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(vars, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64().map(|n| n != 0.0).unwrap_or(false),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Object(map)) => !map.is_empty(),
+    }
+}
+
+fn format_count(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &Value, lang: &str, output: &mut String) -> Result<(), String> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Var(path) => {
+                let text = match lookup(vars, path) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Number(n)) => n.to_string(),
+                    Some(Value::Bool(b)) => b.to_string(),
+                    Some(Value::Null) => String::new(),
+                    Some(_) => return Err(format!("Variable {} is not a scalar", path)),
+                    None => return Err(format!("Missing template variable: {}", path)),
+                };
+                output.push_str(&escape_markdown_v2(&text));
+            }
+            Node::If { path, negate, then, otherwise } => {
+                let branch = if is_truthy(lookup(vars, path)) != *negate { then } else { otherwise };
+                render_nodes(branch, vars, lang, output)?;
+            }
+            Node::Plural { path, forms } => {
+                let count = match lookup(vars, path) {
+                    Some(Value::Number(n)) => n.as_f64().unwrap_or(0.0),
+                    Some(Value::String(s)) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+                        .ok_or_else(|| format!("Variable {} is not a number", path))?,
+                    Some(_) => return Err(format!("Variable {} is not a number", path)),
+                    None => return Err(format!("Missing template variable: {}", path)),
+                };
+                let category = i18n::plural_category(lang, count);
+                let form = forms.get(&category).or_else(|| forms.get(&PluralCategory::OTHER)).map(String::as_str).unwrap_or("");
+                output.push_str(&form.replace('#', &escape_markdown_v2(&format_count(count))));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Escapes every character MarkdownV2 treats as markup, and the backslash
+pub fn escape_markdown_v2(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|' | '{' | '}' | '.' | '!' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn render(source: &str, vars: &Value, lang: &str) -> Result<String, String> {
+    Template::compile(source)?.render(vars, lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_render_escapes_and_pluralizes() {
+        let template = "Hi *{{user.name}}*{{#if user.premium}} ⭐{{else}}, upgrade?{{/if}} {{plural count one=\"# new message\" other=\"# new messages\"}}";
+        let vars = json!({ "user": { "name": "_bob_ [x](http://e.vil)", "premium": false }, "count": 1 });
+        assert_eq!(
+            render(template, &vars, "en").unwrap(),
+            "Hi *\\_bob\\_ \\[x\\]\\(http://e\\.vil\\)*, upgrade? 1 new message"
+        );
+        
+        let plural = "{{plural n one=\"# файл\" few=\"# файла\" many=\"# файлов\" other=\"# файла\"}}";
+        assert_eq!(render(plural, &json!({ "n": 22 }), "ru").unwrap(), "22 файла");
+        assert_eq!(render(plural, &json!({ "n": 11 }), "ru").unwrap(), "11 файлов");
+        assert_eq!(render(plural, &json!({ "n": -1.5 }), "ru").unwrap(), "\\-1\\.5 файла");
+        
+        let nested = "{{#unless banned}}{{#if items.0}}first: {{items.0}}{{/if}}{{/unless}}{{! note }}";
+        assert_eq!(render(nested, &json!({ "items": ["a.b"] }), "en").unwrap(), "first: a\\.b");
+    }
+    
+    #[test]
+    fn test_compile_errors() {
+        assert!(Template::compile("{{#if a}}open").is_err());
+        assert!(Template::compile("{{#if a}}x{{/unless}}").is_err());
+        assert!(Template::compile("{{/if}}").is_err());
+        assert!(Template::compile("{{name").is_err());
+        assert!(Template::compile("{{bad name}}").is_err());
+        assert!(Template::compile("{{plural n one=\"#\"}}").is_err());
+        assert!(Template::compile(&"{{#if a}}".repeat(MAX_BLOCK_DEPTH + 1)).is_err());
+        
+        assert!(render("{{missing}}", &json!({}), "en").is_err());
+        assert!(render("{{obj}}", &json!({ "obj": {} }), "en").is_err());
+    }
+}
\ No newline at end of file
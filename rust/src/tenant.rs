@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::{costs, security};
+
+// Which bot (tenant) and which of its users a call is made for. One library
+// instance can serve several bots; the context scopes cache keys, rate-limit
+// buckets, log entries and per-tenant metrics. Calls without a tenant behave
+// exactly as before.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TenantContext {
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+    // Only breaks down the tenant's costs; no key is scoped by it
+    #[serde(default)]
+    pub chat_id: Option<String>,
+}
+
+impl TenantContext {
+    // Empty strings count as absent so C hosts can pass "" instead of NULL
+    pub fn new(tenant_id: Option<&str>, user_id: Option<&str>) -> Self {
+        let non_empty = |s: Option<&str>| s.filter(|s| !s.is_empty()).map(str::to_string);
+        Self {
+            tenant_id: non_empty(tenant_id),
+            user_id: non_empty(user_id),
+            chat_id: None,
+        }
+    }
+    
+    pub fn with_chat(mut self, chat_id: Option<&str>) -> Self {
+        self.chat_id = chat_id.filter(|s| !s.is_empty()).map(str::to_string);
+        self
+    }
+    
+    // Length-prefixed so a tenant id containing ':' cannot collide with
+    // another tenant's keys
+    pub fn scope_key(&self, key: &str) -> String {
+        match self.tenant_id {
+            Some(ref tenant_id) => format!("tenant:{}:{}:{}", tenant_id.len(), tenant_id, key),
+            None => key.to_string(),
+        }
+    }
+    
+    // Quotas apply per user within a tenant, or to the whole tenant when no
+    // user is given
+    pub fn rate_limit_key(&self) -> Option<String> {
+        let tenant_id = self.tenant_id.as_ref()?;
+        Some(match self.user_id {
+            Some(ref user_id) => format!("tenant:{}:{}:user:{}", tenant_id.len(), tenant_id, user_id),
+            None => format!("tenant:{}:{}", tenant_id.len(), tenant_id),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TenantMetrics {
+    pub requests: u64,
+    pub rate_limited: u64,
+    // Refused for having spent the cost quota; see costs.rs
+    pub cost_limited: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+thread_local! {
+    static CURRENT_TENANT: RefCell<TenantContext> = RefCell::new(TenantContext::default());
+    // Set by C hosts with set_current_tenant; every FFI call on the thread
+    // starts in it
+    static THREAD_DEFAULT: RefCell<TenantContext> = RefCell::new(TenantContext::default());
+}
+
+lazy_static! {
+    static ref TENANT_METRICS: Mutex<HashMap<String, TenantMetrics>> = Mutex::new(HashMap::new());
+}
+
+fn metrics_table() -> MutexGuard<'static, HashMap<String, TenantMetrics>> {
+    TENANT_METRICS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn current() -> TenantContext {
+    CURRENT_TENANT.with(|current| current.borrow().clone())
+}
+
+// Restores the outer context even when the body panics, so a caught panic
+// at the FFI boundary cannot leak one tenant's context into the next call
+struct RestoreTenant(TenantContext);
+
+impl Drop for RestoreTenant {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.0);
+        CURRENT_TENANT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+pub fn thread_default() -> TenantContext {
+    THREAD_DEFAULT.with(|default| default.borrow().clone())
+}
+
+pub fn set_thread_default(context: TenantContext) {
+    THREAD_DEFAULT.with(|default| *default.borrow_mut() = context);
+}
+
+pub fn with_tenant<R, F: FnOnce() -> R>(context: TenantContext, body: F) -> R {
+    let previous = CURRENT_TENANT.with(|current| current.replace(context));
+    let _restore = RestoreTenant(previous);
+    body()
+}
+
+fn update_metrics<F: FnOnce(&mut TenantMetrics)>(update: F) {
+    if let Some(tenant_id) = current().tenant_id {
+        update(metrics_table().entry(tenant_id).or_default());
+    }
+}
+
+// Counts the request against the current tenant and checks its quota
+pub fn admit() -> Result<(), String> {
+    let context = current();
+    let key = match context.rate_limit_key() {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    
+    update_metrics(|metrics| metrics.requests += 1);
+    if let Some(retry_after) = costs::exhausted(&context) {
+        update_metrics(|metrics| metrics.cost_limited += 1);
+        return Err(format!("Cost quota exceeded for tenant {}, retry in {} s", context.tenant_id.unwrap_or_default(), retry_after));
+    }
+    if !security::check_rate_limit(&key) {
+        update_metrics(|metrics| metrics.rate_limited += 1);
+        return Err(format!("Rate limit exceeded for tenant {}", context.tenant_id.unwrap_or_default()));
+    }
+    Ok(())
+}
+
+// What admit would decide for the current tenant, without counting the
+// request; None when the call has no tenant and so no quota
+pub fn dry_run_admit() -> Option<security::RateLimitDecision> {
+    let context = current();
+    let decision = security::dry_run_rate_limit(&context.rate_limit_key()?);
+    Some(match costs::exhausted(&context) {
+        Some(retry_after_seconds) => security::RateLimitDecision { allowed: false, remaining: 0, retry_after_seconds: retry_after_seconds.max(decision.retry_after_seconds) },
+        None => decision,
+    })
+}
+
+pub fn record_cache_lookup(hit: bool) {
+    update_metrics(|metrics| {
+        if hit {
+            metrics.cache_hits += 1;
+        } else {
+            metrics.cache_misses += 1;
+        }
+    });
+}
+
+pub fn scope_key(key: &str) -> String {
+    current().scope_key(key)
+}
+
+pub fn metrics(tenant_id: &str) -> Option<TenantMetrics> {
+    metrics_table().get(tenant_id).cloned()
+}
+
+pub fn all_metrics() -> HashMap<String, TenantMetrics> {
+    metrics_table().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_tenant_scoping() {
+        let bot_a = TenantContext::new(Some("bot_a"), Some("42"));
+        let bot_b = TenantContext::new(Some("bot_b"), Some(""));
+        assert_eq!(bot_b.user_id, None);
+        
+        assert_ne!(bot_a.scope_key("k"), bot_b.scope_key("k"));
+        assert_eq!(TenantContext::default().scope_key("k"), "k");
+        assert_ne!(TenantContext::new(Some("a:1"), None).scope_key("k"), TenantContext::new(Some("a"), None).scope_key("1:k"));
+        
+        with_tenant(bot_a.clone(), || {
+            assert_eq!(current(), bot_a);
+            with_tenant(bot_b.clone(), || assert_eq!(current(), bot_b));
+            assert_eq!(current(), bot_a);
+        });
+        assert_eq!(current(), TenantContext::default());
+    }
+    
+    #[test]
+    fn test_admit_counts_requests() {
+        let context = TenantContext::new(Some("tenant_metrics_test"), None);
+        with_tenant(context, || {
+            assert!(admit().is_ok());
+            record_cache_lookup(true);
+            record_cache_lookup(false);
+        });
+        
+        let metrics = metrics("tenant_metrics_test").unwrap();
+        assert_eq!(metrics.requests, 1);
+        assert_eq!(metrics.cache_hits, 1);
+        assert_eq!(metrics.cache_misses, 1);
+        
+        // Calls without a tenant are never counted or limited
+        assert!(admit().is_ok());
+    }
+}
\ No newline at end of file
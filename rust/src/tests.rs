@@ -32,7 +32,7 @@ mod integration_tests {
         assert!(!result.keywords.is_empty());
         assert!(!result.entities.is_empty());
         assert!(!result.summary.is_empty());
-        assert!(result.readability_score >= 0.0 && result.readability_score <= 100.0);
+        assert!(result.readability_score.score >= 0.0 && result.readability_score.score <= 100.0);
         assert!(!result.topics.is_empty());
         assert!(result.plagiarism_score >= 0.0 && result.plagiarism_score <= 1.0);
         assert!(result.processing_time > 0);
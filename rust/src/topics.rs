@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::RwLock;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::analysis::Topic;
+use crate::paths;
+use crate::{keywords, stemming};
+
+// Topic model over a sliding window of recent messages: non-negative matrix
+// factorization of the TF-IDF message-term matrix (V ≈ WH) with Lee-Seung
+// multiplicative updates. Each of the K rows of H is a topic, described by
+// its heaviest terms; a new message is folded in against the fixed H to get
+// its topic mixture. NMF rather than LDA because chat messages are too
+// short for LDA to sample reliable topic assignments from.
+
+const EPSILON: f64 = 1e-9;
+const INFERENCE_ITERATIONS: usize = 50;
+// A topic has to carry this share of a message to be reported for it
+const MIN_TOPIC_SHARE: f64 = 0.15;
+const MAX_REPORTED_TOPICS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopicModelConfig {
+    // K
+    pub topics: usize,
+    // Messages kept for training; the oldest drop out first
+    pub window: usize,
+    pub keywords_per_topic: usize,
+    // Retrains after this many new messages; 0 trains only on request
+    pub retrain_every: usize,
+    pub iterations: usize,
+    // Terms seen in fewer messages stay out of the vocabulary
+    pub min_document_frequency: usize,
+    pub max_vocabulary: usize,
+    // Stop words and stemming; None applies every stop word list
+    pub language: Option<String>,
+    // Same seed and messages give the same topics
+    pub seed: u64,
+}
+
+impl Default for TopicModelConfig {
+    fn default() -> Self {
+        Self {
+            topics: 5,
+            window: 1000,
+            keywords_per_topic: 8,
+            retrain_every: 100,
+            iterations: 200,
+            min_document_frequency: 2,
+            max_vocabulary: 5000,
+            language: None,
+            seed: 42,
+        }
+    }
+}
+
+impl TopicModelConfig {
+    pub fn from_json(config_json: &str) -> Result<Self, String> {
+        if config_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(config_json).map_err(|e| format!("Invalid topic model config: {}", e))
+    }
+    
+    fn validate(&self) -> Result<(), String> {
+        if self.topics == 0 || self.keywords_per_topic == 0 || self.iterations == 0 {
+            return Err("topics, keywords_per_topic and iterations must be at least 1".to_string());
+        }
+        if self.window < self.topics || self.max_vocabulary < self.topics {
+            return Err(format!("window and max_vocabulary must be at least topics ({})", self.topics));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopicKeyword {
+    pub term: String,
+    // Share of the topic's weight, so a topic's keywords sum to at most 1
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelTopic {
+    pub id: usize,
+    pub keywords: Vec<TopicKeyword>,
+}
+
+// Serializable with its window, so a restarted process picks up the same
+// topics and keeps sliding from where it stopped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicModel {
+    config: TopicModelConfig,
+    // Term keys (stems) of each message in the window
+    documents: VecDeque<Vec<String>>,
+    // First surface form seen for each key, for display
+    surface: HashMap<String, String>,
+    vocabulary: Vec<String>,
+    idf: Vec<f64>,
+    // H, topics x vocabulary; empty until trained
+    components: Vec<Vec<f64>>,
+    added_since_training: usize,
+}
+
+fn gram(components: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    components.iter()
+        .map(|a| components.iter().map(|b| a.iter().zip(b).map(|(x, y)| x * y).sum()).collect())
+        .collect()
+}
+
+impl TopicModel {
+    pub fn new(config: TopicModelConfig) -> Result<Self, String> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            documents: VecDeque::new(),
+            surface: HashMap::new(),
+            vocabulary: Vec::new(),
+            idf: Vec::new(),
+            components: Vec::new(),
+            added_since_training: 0,
+        })
+    }
+    
+    pub fn config(&self) -> &TopicModelConfig {
+        &self.config
+    }
+    
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+    
+    pub fn is_trained(&self) -> bool {
+        !self.components.is_empty()
+    }
+    
+    // (key, surface form) of the terms worth modeling. The language is fixed
+    // by the config rather than detected per message, so a word always maps
+    // to the same key.
+    fn terms(&self, text: &str) -> Vec<(String, String)> {
+        let language = self.config.language.as_deref().unwrap_or("unknown");
+        keywords::terms(text, language)
+            .into_iter()
+            .map(|term| (stemming::stem(&term, language), term))
+            .collect()
+    }
+    
+    // Adds a message to the window, retraining once retrain_every messages
+    // have arrived since the last training
+    pub fn add_document(&mut self, text: &str) {
+        let terms = self.terms(text);
+        if terms.is_empty() {
+            return;
+        }
+        let mut keys = Vec::with_capacity(terms.len());
+        for (key, term) in terms {
+            self.surface.entry(key.clone()).or_insert(term);
+            keys.push(key);
+        }
+        self.documents.push_back(keys);
+        while self.documents.len() > self.config.window {
+            self.documents.pop_front();
+        }
+        self.added_since_training += 1;
+        
+        if self.config.retrain_every > 0 && self.added_since_training >= self.config.retrain_every {
+            // Too few messages or terms yet; the next batch tries again
+            let _ = self.train();
+        }
+    }
+    
+    // Sparse TF-IDF row with unit length, over the current vocabulary
+    fn vectorize(&self, keys: &[String], index: &HashMap<&str, usize>) -> Vec<(usize, f64)> {
+        let mut counts: HashMap<usize, f64> = HashMap::new();
+        for key in keys {
+            if let Some(&j) = index.get(key.as_str()) {
+                *counts.entry(j).or_insert(0.0) += 1.0;
+            }
+        }
+        let mut row: Vec<(usize, f64)> = counts.into_iter().map(|(j, count)| (j, count * self.idf[j])).collect();
+        row.sort_by_key(|&(j, _)| j);
+        let norm = row.iter().map(|(_, v)| v * v).sum::<f64>().sqrt();
+        row.iter_mut().for_each(|(_, v)| *v /= norm.max(EPSILON));
+        row
+    }
+    
+    fn index(&self) -> HashMap<&str, usize> {
+        self.vocabulary.iter().enumerate().map(|(j, key)| (key.as_str(), j)).collect()
+    }
+    
+    pub fn train(&mut self) -> Result<(), String> {
+        let k = self.config.topics;
+        let n = self.documents.len();
+        if n < k {
+            return Err(format!("Need at least {} messages to train {} topics, have {}", k, k, n));
+        }
+        
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        for document in &self.documents {
+            let unique: HashSet<&str> = document.iter().map(String::as_str).collect();
+            for key in unique {
+                *document_frequency.entry(key).or_insert(0) += 1;
+            }
+        }
+        let mut vocabulary: Vec<(String, usize)> = document_frequency.into_iter()
+            .filter(|&(_, df)| df >= self.config.min_document_frequency)
+            .map(|(key, df)| (key.to_string(), df))
+            .collect();
+        vocabulary.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        vocabulary.truncate(self.config.max_vocabulary);
+        if vocabulary.len() < k {
+            return Err(format!("Need at least {} recurring terms to train {} topics, have {}", k, k, vocabulary.len()));
+        }
+        
+        let m = vocabulary.len();
+        let idf: Vec<f64> = vocabulary.iter().map(|&(_, df)| ((1.0 + n as f64) / (1.0 + df as f64)).ln() + 1.0).collect();
+        self.vocabulary = vocabulary.into_iter().map(|(key, _)| key).collect();
+        self.idf = idf;
+        let index = self.index();
+        let rows: Vec<Vec<(usize, f64)>> = self.documents.iter()
+            .map(|document| self.vectorize(document, &index))
+            .filter(|row| !row.is_empty())
+            .collect();
+        
+        // Random start scaled to the data, as sklearn's "random" init
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        let mean = rows.iter().flatten().map(|(_, v)| v).sum::<f64>() / (rows.len() * m) as f64;
+        let scale = (mean / k as f64).sqrt().max(EPSILON);
+        let mut w: Vec<Vec<f64>> = (0..rows.len()).map(|_| (0..k).map(|_| rng.gen::<f64>() * scale).collect()).collect();
+        let mut h: Vec<Vec<f64>> = (0..k).map(|_| (0..m).map(|_| rng.gen::<f64>() * scale).collect()).collect();
+        
+        for _ in 0..self.config.iterations {
+            // H <- H * (WᵀV) / (WᵀWH)
+            let mut wtv = vec![vec![0.0; m]; k];
+            for (row, weights) in rows.iter().zip(&w) {
+                for &(j, v) in row {
+                    for t in 0..k {
+                        wtv[t][j] += weights[t] * v;
+                    }
+                }
+            }
+            let wtw: Vec<Vec<f64>> = (0..k)
+                .map(|a| (0..k).map(|b| w.iter().map(|weights| weights[a] * weights[b]).sum()).collect())
+                .collect();
+            let previous = h.clone();
+            for t in 0..k {
+                for j in 0..m {
+                    let denominator: f64 = (0..k).map(|s| wtw[t][s] * previous[s][j]).sum();
+                    h[t][j] *= wtv[t][j] / (denominator + EPSILON);
+                }
+            }
+            
+            // W <- W * (VHᵀ) / (WHHᵀ)
+            let hht = gram(&h);
+            for (row, weights) in rows.iter().zip(w.iter_mut()) {
+                let vht: Vec<f64> = (0..k).map(|t| row.iter().map(|&(j, v)| v * h[t][j]).sum()).collect();
+                let previous = weights.clone();
+                for t in 0..k {
+                    let denominator: f64 = (0..k).map(|s| previous[s] * hht[s][t]).sum();
+                    weights[t] *= vht[t] / (denominator + EPSILON);
+                }
+            }
+        }
+        
+        // Surface forms of keys that left the window go with them
+        let live: HashSet<&String> = self.documents.iter().flatten().collect();
+        self.surface.retain(|key, _| live.contains(key));
+        self.components = h;
+        self.added_since_training = 0;
+        Ok(())
+    }
+    
+    fn display(&self, key: &str) -> String {
+        self.surface.get(key).cloned().unwrap_or_else(|| key.to_string())
+    }
+    
+    // Heaviest terms of each topic; empty until trained
+    pub fn topics(&self) -> Vec<ModelTopic> {
+        self.components.iter().enumerate().map(|(id, component)| {
+            let total = component.iter().sum::<f64>().max(EPSILON);
+            let mut terms: Vec<(usize, f64)> = component.iter().copied().enumerate().filter(|&(_, v)| v > EPSILON).collect();
+            terms.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            terms.truncate(self.config.keywords_per_topic);
+            let keywords = terms.into_iter()
+                .map(|(j, v)| TopicKeyword { term: self.display(&self.vocabulary[j]), weight: v / total })
+                .collect();
+            ModelTopic { id, keywords }
+        }).collect()
+    }
+    
+    // Topics of text, heaviest first, weighted by their share of it. None
+    // when untrained or when text shares no term with the vocabulary.
+    pub fn infer(&self, text: &str) -> Option<Vec<Topic>> {
+        if !self.is_trained() {
+            return None;
+        }
+        let keys: Vec<String> = self.terms(text).into_iter().map(|(key, _)| key).collect();
+        let row = self.vectorize(&keys, &self.index());
+        if row.is_empty() {
+            return None;
+        }
+        
+        // Same W update as training, with H fixed
+        let k = self.components.len();
+        let hht = gram(&self.components);
+        let vht: Vec<f64> = self.components.iter().map(|component| row.iter().map(|&(j, v)| v * component[j]).sum()).collect();
+        let mut weights = vec![1.0 / k as f64; k];
+        for _ in 0..INFERENCE_ITERATIONS {
+            let previous = weights.clone();
+            for t in 0..k {
+                let denominator: f64 = (0..k).map(|s| previous[s] * hht[s][t]).sum();
+                weights[t] *= vht[t] / (denominator + EPSILON);
+            }
+        }
+        
+        let total = weights.iter().sum::<f64>();
+        if total <= EPSILON {
+            return None;
+        }
+        let topics = self.topics();
+        let mut shares: Vec<(usize, f64)> = weights.iter().map(|w| w / total).enumerate()
+            .filter(|&(_, share)| share >= MIN_TOPIC_SHARE)
+            .collect();
+        shares.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        shares.truncate(MAX_REPORTED_TOPICS);
+        Some(shares.into_iter().map(|(t, share)| {
+            let keywords: Vec<String> = topics[t].keywords.iter().map(|keyword| keyword.term.clone()).collect();
+            Topic { name: keywords.first().cloned().unwrap_or_default(), weight: share, keywords }
+        }).collect())
+    }
+}
+
+lazy_static! {
+    static ref MODEL: RwLock<Option<TopicModel>> = RwLock::new(None);
+}
+
+// Starts a model with config, keeping the messages of the current window
+// and retraining on them when there are enough
+pub fn configure(config: TopicModelConfig) -> Result<(), String> {
+    let mut model = TopicModel::new(config)?;
+    let mut current = MODEL.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(previous) = current.take() {
+        let skip = previous.documents.len().saturating_sub(model.config.window);
+        model.documents = previous.documents.into_iter().skip(skip).collect();
+        model.surface = previous.surface;
+        let _ = model.train();
+    }
+    *current = Some(model);
+    Ok(())
+}
+
+// Adds to the pipeline's window, starting a default model if none is set
+pub fn add_document(text: &str) {
+    let mut model = MODEL.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    model.get_or_insert_with(|| TopicModel::new(TopicModelConfig::default()).expect("default config is valid"))
+        .add_document(text);
+}
+
+pub fn train() -> Result<Vec<ModelTopic>, String> {
+    let mut model = MODEL.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let model = model.as_mut().ok_or("No topic model; add messages first")?;
+    model.train()?;
+    Ok(model.topics())
+}
+
+pub fn topics() -> Vec<ModelTopic> {
+    MODEL.read().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref().map(TopicModel::topics).unwrap_or_default()
+}
+
+// For the text pipeline; None falls back to frequency topics
+pub fn infer(text: &str) -> Option<Vec<Topic>> {
+    MODEL.read().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref()?.infer(text)
+}
+
+pub fn clear() {
+    *MODEL.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
+
+// Writes the model and its window to path (relative paths resolve under the
+// data directory); returns how many messages the window holds
+pub fn save(path: &str) -> Result<usize, String> {
+    let (json, count) = {
+        let model = MODEL.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let model = model.as_ref().ok_or("No topic model to save")?;
+        (serde_json::to_vec(model).map_err(|e| e.to_string())?, model.document_count())
+    };
+    paths::atomic_write(path, "topic model", &json)?;
+    Ok(count)
+}
+
+// Replaces the current model with one written by save
+pub fn load(path: &str) -> Result<usize, String> {
+    let contents = paths::read_data(path, "topic model")?;
+    let model: TopicModel = serde_json::from_slice(&contents).map_err(|e| format!("Invalid topic model: {}", e))?;
+    model.config.validate()?;
+    let count = model.document_count();
+    *MODEL.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(model);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn trained() -> TopicModel {
+        let config = TopicModelConfig { topics: 2, retrain_every: 0, language: Some("en".to_string()), ..Default::default() };
+        let mut model = TopicModel::new(config).unwrap();
+        for _ in 0..3 {
+            model.add_document("the team scored a late goal and won the match");
+            model.add_document("great match, the keeper saved a goal for the team");
+            model.add_document("which team plays the next match? the goal was offside");
+            model.add_document("bake the bread in the oven with flour and sugar");
+            model.add_document("this recipe needs more sugar and less flour");
+            model.add_document("preheat the oven before you bake the recipe");
+        }
+        model.train().unwrap();
+        model
+    }
+    
+    #[test]
+    fn test_separates_topics() {
+        let model = trained();
+        let topics = model.topics();
+        assert_eq!(topics.len(), 2);
+        let terms = |topic: &ModelTopic| topic.keywords.iter().take(3).map(|keyword| keyword.term.clone()).collect::<Vec<_>>();
+        let football = topics.iter().find(|topic| terms(topic).contains(&"team".to_string())).unwrap();
+        let baking = topics.iter().find(|topic| terms(topic).contains(&"oven".to_string())).unwrap();
+        assert_ne!(football.id, baking.id);
+        
+        let inferred = model.infer("What a goal! The match was great").unwrap();
+        assert_eq!(inferred[0].keywords, football.keywords.iter().map(|keyword| keyword.term.clone()).collect::<Vec<_>>());
+        assert!(inferred[0].weight > 0.8);
+        assert!(model.infer("quantum entanglement").is_none());
+        
+        let restored: TopicModel = serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+        assert_eq!(restored.topics(), topics);
+        assert_eq!(restored.document_count(), 18);
+    }
+    
+    #[test]
+    fn test_window_and_config() {
+        let config = TopicModelConfig { topics: 1, window: 2, retrain_every: 2, min_document_frequency: 1, ..Default::default() };
+        let mut model = TopicModel::new(config).unwrap();
+        model.add_document("deploy failed again");
+        assert!(!model.is_trained());
+        model.add_document("deploy succeeded");
+        model.add_document("deploy rolled back");
+        assert!(model.is_trained());
+        assert_eq!(model.document_count(), 2);
+        
+        assert!(TopicModel::new(TopicModelConfig { topics: 0, ..Default::default() }).is_err());
+        assert!(TopicModelConfig::from_json(r#"{"topics": 8, "window": 500}"#).is_ok());
+        assert!(TopicModelConfig::from_json(r#"{"k": 8}"#).is_err());
+    }
+}
\ No newline at end of file
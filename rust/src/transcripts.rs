@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::analysis::{self, AnalysisOptions, TextAnalysisResult};
+use crate::sentiment::{self, SentimentScore};
+
+// Voice notes the host has already transcribed: the words, joined into text,
+// get the usual text analysis, and their timestamps and speaker labels add
+// how the message was spoken. Speaking rates leave out pauses, so a long
+// silence in the middle of a note does not drag the rate down; silence is
+// reported on its own as the share of the recording without speech.
+
+// A gap between two words at least this long is a pause
+const PAUSE_SECONDS: f64 = 0.5;
+// Words in one transcript, about an hour of speech
+pub const MAX_TRANSCRIPT_WORDS: usize = 10_000;
+// Label of words the transcriber did not attribute
+const UNKNOWN_SPEAKER: &str = "unknown";
+
+// As speech-to-text services emit them; other fields (confidence and the
+// like) are ignored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptWord {
+    pub word: String,
+    // Seconds from the start of the recording
+    pub start: f64,
+    pub end: f64,
+    #[serde(default)]
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub words: Vec<TranscriptWord>,
+    // Length of the recording in seconds; the end of the last word when
+    // missing, which leaves out trailing silence
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+impl Transcript {
+    pub fn from_json(transcript_json: &str) -> Result<Self, String> {
+        let transcript: Transcript = serde_json::from_str(transcript_json).map_err(|e| format!("Invalid transcript: {}", e))?;
+        transcript.validate()?;
+        Ok(transcript)
+    }
+    
+    fn validate(&self) -> Result<(), String> {
+        if self.words.len() > MAX_TRANSCRIPT_WORDS {
+            return Err(format!("Transcript of {} words exceeds the limit of {}", self.words.len(), MAX_TRANSCRIPT_WORDS));
+        }
+        for (i, word) in self.words.iter().enumerate() {
+            if !word.start.is_finite() || !word.end.is_finite() || word.start < 0.0 || word.end < word.start {
+                return Err(format!("Word {} has an invalid time range {}-{}", i, word.start, word.end));
+            }
+        }
+        match self.duration {
+            Some(duration) if !duration.is_finite() || duration < 0.0 => Err(format!("Invalid transcript duration {}", duration)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechStats {
+    pub duration_seconds: f64,
+    // Time covered by at least one word
+    pub speech_seconds: f64,
+    // 1 - speech_seconds / duration_seconds
+    pub silence_ratio: f64,
+    // Over the time from the first word to the last, pauses left out
+    pub words_per_minute: f64,
+    // Gaps between words of at least half a second
+    pub pauses: usize,
+    pub longest_pause_seconds: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerStats {
+    // "unknown" for words without a label
+    pub speaker: String,
+    pub words: usize,
+    // Runs of consecutive words by this speaker
+    pub turns: usize,
+    // Sum of the speaker's word durations
+    pub speaking_seconds: f64,
+    // Of all words
+    pub share: f64,
+    // Within the speaker's turns, pauses left out
+    pub words_per_minute: f64,
+    pub sentiment: SentimentScore,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscriptAnalysis {
+    // The words in time order, a line per turn
+    pub text: String,
+    pub analysis: TextAnalysisResult,
+    pub speech: SpeechStats,
+    // Most words first
+    pub speakers: Vec<SpeakerStats>,
+}
+
+// Seconds of a run of words without its pauses, and the pauses themselves
+fn active_seconds(words: &[&TranscriptWord]) -> (f64, Vec<f64>) {
+    let (first, last) = match (words.first(), words.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return (0.0, Vec::new()),
+    };
+    let pauses: Vec<f64> = words.windows(2)
+        .map(|pair| pair[1].start - pair[0].end)
+        .filter(|gap| *gap >= PAUSE_SECONDS)
+        .collect();
+    let span = words.iter().map(|word| word.end).fold(last.end, f64::max) - first.start;
+    ((span - pauses.iter().sum::<f64>()).max(0.0), pauses)
+}
+
+fn per_minute(words: usize, seconds: f64) -> f64 {
+    if seconds > 0.0 { words as f64 * 60.0 / seconds } else { 0.0 }
+}
+
+fn speaker_of(word: &TranscriptWord) -> &str {
+    word.speaker.as_deref().map(str::trim).filter(|speaker| !speaker.is_empty()).unwrap_or(UNKNOWN_SPEAKER)
+}
+
+pub fn analyze(transcript: &Transcript, options: &AnalysisOptions) -> TranscriptAnalysis {
+    let mut words: Vec<&TranscriptWord> = transcript.words.iter().filter(|word| !word.word.trim().is_empty()).collect();
+    // Stable, so words the transcriber gave the same start keep their order
+    words.sort_by(|a, b| a.start.total_cmp(&b.start));
+    
+    // Turns: runs of words by one speaker
+    let mut turns: Vec<(&str, Vec<&TranscriptWord>)> = Vec::new();
+    for &word in &words {
+        match turns.last_mut() {
+            Some((speaker, turn)) if *speaker == speaker_of(word) => turn.push(word),
+            _ => turns.push((speaker_of(word), vec![word])),
+        }
+    }
+    let text = turns.iter()
+        .map(|(_, turn)| turn.iter().map(|word| word.word.trim()).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    
+    let mut speech_seconds = 0.0;
+    let mut covered_until = f64::NEG_INFINITY;
+    for word in &words {
+        let from = word.start.max(covered_until);
+        if word.end > from {
+            speech_seconds += word.end - from;
+        }
+        covered_until = covered_until.max(word.end);
+    }
+    let last_end = words.iter().map(|word| word.end).fold(0.0, f64::max);
+    let duration_seconds = transcript.duration.unwrap_or(last_end).max(last_end);
+    let (active, pauses) = active_seconds(&words);
+    let speech = SpeechStats {
+        duration_seconds,
+        speech_seconds,
+        silence_ratio: if duration_seconds > 0.0 { (1.0 - speech_seconds / duration_seconds).max(0.0) } else { 0.0 },
+        words_per_minute: per_minute(words.len(), active),
+        pauses: pauses.len(),
+        longest_pause_seconds: pauses.iter().copied().fold(0.0, f64::max),
+    };
+    
+    // Speaker -> (words, turns, speaking seconds, active seconds, text), in
+    // order of first appearance
+    let mut order: Vec<&str> = Vec::new();
+    let mut by_speaker: HashMap<&str, (usize, usize, f64, f64, Vec<&str>)> = HashMap::new();
+    for (speaker, turn) in &turns {
+        let entry = by_speaker.entry(speaker).or_insert_with(|| {
+            order.push(speaker);
+            (0, 0, 0.0, 0.0, Vec::new())
+        });
+        entry.0 += turn.len();
+        entry.1 += 1;
+        entry.2 += turn.iter().map(|word| word.end - word.start).sum::<f64>();
+        entry.3 += active_seconds(turn).0;
+        entry.4.extend(turn.iter().map(|word| word.word.trim()));
+    }
+    let lexicon = sentiment::active();
+    let mut speakers: Vec<SpeakerStats> = order.into_iter().map(|speaker| {
+        let (count, turns, speaking_seconds, active, spoken) = &by_speaker[speaker];
+        SpeakerStats {
+            speaker: speaker.to_string(),
+            words: *count,
+            turns: *turns,
+            speaking_seconds: *speaking_seconds,
+            share: *count as f64 / words.len() as f64,
+            words_per_minute: per_minute(*count, *active),
+            sentiment: lexicon.analyze(&spoken.join(" ")),
+        }
+    }).collect();
+    speakers.sort_by_key(|speaker| std::cmp::Reverse(speaker.words));
+    
+    TranscriptAnalysis {
+        analysis: analysis::analyze_text_with_options(&text, options),
+        text,
+        speech,
+        speakers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn word(word: &str, start: f64, end: f64, speaker: &str) -> TranscriptWord {
+        TranscriptWord { word: word.to_string(), start, end, speaker: Some(speaker.to_string()) }
+    }
+    
+    #[test]
+    fn test_transcript_analysis() {
+        let transcript = Transcript {
+            words: vec![
+                word("I", 0.0, 0.2, "A"),
+                word("love", 0.2, 0.6, "A"),
+                word("this", 0.6, 1.0, "A"),
+                // A two second pause
+                word("great", 3.0, 3.5, "A"),
+                word("this", 4.0, 4.5, "B"),
+                word("is", 4.5, 4.7, "B"),
+                word("terrible", 4.7, 5.4, "B"),
+            ],
+            duration: Some(10.0),
+        };
+        let result = analyze(&transcript, &AnalysisOptions::default());
+        assert_eq!(result.text, "I love this great\nthis is terrible");
+        assert_eq!(result.analysis.word_count, 7);
+        
+        let speech = &result.speech;
+        assert!((speech.speech_seconds - 2.9).abs() < 1e-9);
+        assert!((speech.silence_ratio - 0.71).abs() < 1e-9);
+        // 5.4 s from first to last word, less the pauses of 2 and 0.5 s
+        assert_eq!((speech.pauses, speech.longest_pause_seconds), (2, 2.0));
+        assert!((speech.words_per_minute - 7.0 * 60.0 / 2.9).abs() < 1e-9);
+        
+        let speakers: Vec<(&str, usize, usize)> = result.speakers.iter().map(|s| (s.speaker.as_str(), s.words, s.turns)).collect();
+        assert_eq!(speakers, vec![("A", 4, 1), ("B", 3, 1)]);
+        assert!(result.speakers[0].sentiment.compound > 0.0);
+        assert!(result.speakers[1].sentiment.compound < 0.0);
+        
+        assert!(Transcript::from_json(r#"{"words": [{"word": "hi", "start": 1.0, "end": 0.5}]}"#).is_err());
+        let unlabeled = Transcript::from_json(r#"{"words": [{"word": "hi", "start": 0, "end": 0.5, "confidence": 0.9}]}"#).unwrap();
+        assert_eq!(analyze(&unlabeled, &AnalysisOptions::default()).speakers[0].speaker, "unknown");
+    }
+}
\ No newline at end of file
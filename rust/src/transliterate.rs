@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+
+// Finglish, Persian typed in Latin letters ("salam khoobi"), is common in
+// chats typed on phones without a Persian keyboard. Common chat words come
+// from a table; other words are spelled letter by letter, which cannot
+// recover the short vowels Persian script leaves out, so results are meant
+// for analysis and search rather than display. Links, mentions, hashtags,
+// commands and e-mail addresses are left alone.
+
+const ZWNJ: char = '\u{200C}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransliterationDirection {
+    // Finglish to Persian script
+    #[serde(rename = "TO_PERSIAN")]
+    ToPersian,
+    // Persian or Arabic script to Finglish
+    #[serde(rename = "TO_LATIN")]
+    ToLatin,
+}
+
+impl TransliterationDirection {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('-', "_").as_str() {
+            "to_persian" | "persian" | "fa" => Some(TransliterationDirection::ToPersian),
+            "to_latin" | "latin" | "finglish" => Some(TransliterationDirection::ToLatin),
+            _ => None,
+        }
+    }
+}
+
+// Finglish spellings and the Persian word; the first spelling of a word is
+// the one ToLatin writes
+const WORDS: &[(&str, &str)] = &[
+    ("salam", "سلام"), ("khoobi", "خوبی"), ("khubi", "خوبی"), ("khoob", "خوب"), ("khub", "خوب"),
+    ("chetori", "چطوری"), ("chetor", "چطور"), ("merci", "مرسی"), ("mersi", "مرسی"),
+    ("mamnoon", "ممنون"), ("mamnun", "ممنون"), ("mamnoonam", "ممنونم"), ("lotfan", "لطفا"),
+    ("khodahafez", "خداحافظ"), ("khodafez", "خداحافظ"), ("bashe", "باشه"), ("basheh", "باشه"),
+    ("are", "آره"), ("areh", "آره"), ("bale", "بله"), ("baleh", "بله"), ("na", "نه"), ("nah", "نه"),
+    ("man", "من"), ("to", "تو"), ("ma", "ما"), ("shoma", "شما"), ("oon", "اون"), ("un", "اون"),
+    ("in", "این"), ("ino", "اینو"), ("ina", "اینا"), ("ye", "یه"), ("yek", "یک"), ("chi", "چی"),
+    ("che", "چه"), ("chera", "چرا"), ("koja", "کجا"), ("kei", "کی"), ("key", "کی"), ("ki", "کی"),
+    ("kheili", "خیلی"), ("kheyli", "خیلی"), ("khaili", "خیلی"), ("alan", "الان"), ("emrooz", "امروز"),
+    ("emruz", "امروز"), ("farda", "فردا"), ("dirooz", "دیروز"), ("diruz", "دیروز"), ("hast", "هست"),
+    ("nist", "نیست"), ("dige", "دیگه"), ("ham", "هم"), ("va", "و"), ("ba", "با"), ("az", "از"),
+    ("be", "به"), ("ke", "که"), ("dar", "در"), ("ro", "رو"), ("age", "اگه"), ("agar", "اگر"),
+    ("vali", "ولی"), ("amma", "اما"), ("chon", "چون"), ("ghashang", "قشنگ"), ("khoshgel", "خوشگل"),
+    ("doost", "دوست"), ("dust", "دوست"), ("azizam", "عزیزم"), ("aziz", "عزیز"), ("joon", "جون"),
+    ("jan", "جان"), ("khabar", "خبر"), ("hal", "حال"), ("halet", "حالت"), ("sobh", "صبح"),
+    ("sob", "صبح"), ("shab", "شب"), ("bekheir", "بخیر"), ("bekheyr", "بخیر"), ("ghorbanet", "قربونت"),
+    ("eyval", "ایول"), ("mikham", "میخوام"), ("mikhay", "میخوای"), ("midoonam", "میدونم"),
+    ("nemidoonam", "نمیدونم"), ("daram", "دارم"), ("dari", "داری"), ("dare", "داره"), ("kar", "کار"),
+    ("bad", "بد"), ("saat", "ساعت"), ("vaght", "وقت"), ("vaqt", "وقت"), ("ghaza", "غذا"),
+];
+
+lazy_static! {
+    static ref TO_PERSIAN_WORDS: HashMap<&'static str, &'static str> = WORDS.iter().copied().collect();
+    static ref TO_LATIN_WORDS: HashMap<&'static str, &'static str> = {
+        let mut words = HashMap::new();
+        for &(latin, persian) in WORDS {
+            words.entry(persian).or_insert(latin);
+        }
+        words
+    };
+}
+
+// Tokens that are not words of the message
+fn is_verbatim(token: &str) -> bool {
+    token.contains("://") || token.starts_with("www.") || token.contains('@') || token.starts_with(['#', '/'])
+}
+
+fn persian_word(word: &str) -> String {
+    let word = word.to_ascii_lowercase();
+    if let Some(persian) = TO_PERSIAN_WORDS.get(word.as_str()) {
+        return persian.to_string();
+    }
+    
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let first = i == 0;
+        let digraph = match (chars[i], chars.get(i + 1).copied().unwrap_or(' ')) {
+            ('k', 'h') => Some("خ"),
+            ('s', 'h') => Some("ش"),
+            ('c', 'h') => Some("چ"),
+            ('z', 'h') => Some("ژ"),
+            ('g', 'h') => Some("ق"),
+            ('a', 'a') => Some(if first { "آ" } else { "ا" }),
+            ('o', 'o') | ('o', 'u') => Some(if first { "او" } else { "و" }),
+            ('e', 'e') | ('e', 'i') | ('e', 'y') => Some(if first { "ای" } else { "ی" }),
+            _ => None,
+        };
+        if let Some(letters) = digraph {
+            out.push_str(letters);
+            i += 2;
+            continue;
+        }
+        
+        let last = i == chars.len() - 1;
+        let letters = match chars[i] {
+            'a' => "ا",
+            // Short e and o are not written inside a word
+            'e' if first => "ا",
+            'e' if last => "ه",
+            'e' => "",
+            'o' if first => "ا",
+            'o' if last => "و",
+            'o' => "",
+            'i' if first => "ای",
+            'i' | 'y' => "ی",
+            'u' if first => "او",
+            'u' | 'v' | 'w' => "و",
+            'b' => "ب",
+            'p' => "پ",
+            't' => "ت",
+            's' => "س",
+            'j' => "ج",
+            'h' => "ه",
+            'd' => "د",
+            'r' => "ر",
+            'z' => "ز",
+            'f' => "ف",
+            'q' => "ق",
+            'k' | 'c' => "ک",
+            'g' => "گ",
+            'l' => "ل",
+            'm' => "م",
+            'n' => "ن",
+            'x' => "کس",
+            // Digits standing in for letters, as in "3ali" or "7al"
+            '2' if first => "ا",
+            '2' => "ء",
+            '3' | '\'' => "ع",
+            '7' => "ح",
+            '8' => "ق",
+            c => {
+                out.push(c);
+                ""
+            }
+        };
+        out.push_str(letters);
+        i += 1;
+    }
+    out
+}
+
+fn unify(c: char) -> char {
+    match c {
+        'ي' | 'ى' => 'ی',
+        'ك' => 'ک',
+        'ة' => 'ه',
+        'أ' | 'إ' | 'ٱ' => 'ا',
+        c => c,
+    }
+}
+
+fn is_vowel_letter(c: char) -> bool {
+    matches!(c, 'ا' | 'آ' | 'و' | 'ی')
+}
+
+fn latin_word(word: &str) -> String {
+    let unified: String = word.chars().filter(|&c| c != ZWNJ).map(unify).collect();
+    if let Some(latin) = TO_LATIN_WORDS.get(unified.as_str()) {
+        return latin.to_string();
+    }
+    
+    let chars: Vec<char> = unified.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let first = i == 0;
+        let last = i == chars.len() - 1;
+        let before_vowel = chars.get(i + 1).map(|&next| is_vowel_letter(next)).unwrap_or(false);
+        let letters = match c {
+            'آ' => "a",
+            // Carrier of an initial long vowel: "این", "اون"
+            'ا' if first && matches!(chars.get(1), Some('و') | Some('ی')) => "",
+            'ا' => "a",
+            'و' if first || before_vowel => "v",
+            'و' if last => "o",
+            'و' => "oo",
+            'ی' if first || before_vowel => "y",
+            'ی' | 'ئ' => "i",
+            'ه' if last && !first => "e",
+            'ه' | 'ح' => "h",
+            'ب' => "b",
+            'پ' => "p",
+            'ت' | 'ط' => "t",
+            'ث' | 'س' | 'ص' => "s",
+            'ج' => "j",
+            'چ' => "ch",
+            'خ' => "kh",
+            'د' => "d",
+            'ذ' | 'ز' | 'ض' | 'ظ' => "z",
+            'ر' => "r",
+            'ژ' => "zh",
+            'ش' => "sh",
+            'غ' | 'ق' => "gh",
+            'ف' => "f",
+            'ک' => "k",
+            'گ' => "g",
+            'ل' => "l",
+            'م' => "m",
+            'ن' => "n",
+            'ؤ' => "o",
+            // Harakat, when written
+            '\u{064E}' => "a",
+            '\u{0650}' => "e",
+            '\u{064F}' => "o",
+            '\u{064B}' => "an",
+            'ع' | 'ء' | '\u{0651}' | '\u{0652}' | 'ـ' => "",
+            c => {
+                out.push(c);
+                ""
+            }
+        };
+        out.push_str(letters);
+    }
+    out
+}
+
+fn is_latin_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '\''
+}
+
+fn is_persian_word_char(c: char) -> bool {
+    c == ZWNJ || (('\u{0600}'..='\u{06FF}').contains(&c) && (c.is_alphabetic() || ('\u{064B}'..='\u{0652}').contains(&c)))
+}
+
+fn to_persian(token: &str, out: &mut String) {
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if word.chars().any(|c| c.is_ascii_alphabetic()) {
+            out.push_str(&persian_word(word));
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for c in token.chars() {
+        if is_latin_word_char(c) {
+            word.push(c);
+            continue;
+        }
+        flush(&mut word, out);
+        out.push(match c {
+            ',' => '،',
+            '?' => '؟',
+            ';' => '؛',
+            c => c,
+        });
+    }
+    flush(&mut word, out);
+}
+
+fn to_latin(token: &str, out: &mut String) {
+    let mut word = String::new();
+    for c in token.chars() {
+        if is_persian_word_char(c) {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            out.push_str(&latin_word(&word));
+            word.clear();
+        }
+        out.push(match c {
+            '۰'..='۹' => char::from(b'0' + (c as u32 - '۰' as u32) as u8),
+            '٠'..='٩' => char::from(b'0' + (c as u32 - '٠' as u32) as u8),
+            '،' => ',',
+            '؟' => '?',
+            '؛' => ';',
+            '«' | '»' => '"',
+            c => c,
+        });
+    }
+    if !word.is_empty() {
+        out.push_str(&latin_word(&word));
+    }
+}
+
+// Whitespace, punctuation other than , ? ; and text in other scripts pass
+// through unchanged
+pub fn transliterate(text: &str, direction: TransliterationDirection) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    for chunk in text.split_inclusive(char::is_whitespace) {
+        let token = chunk.trim_end();
+        if is_verbatim(token) {
+            out.push_str(token);
+        } else {
+            match direction {
+                TransliterationDirection::ToPersian => to_persian(token, &mut out),
+                TransliterationDirection::ToLatin => to_latin(token, &mut out),
+            }
+        }
+        out.push_str(&chunk[token.len()..]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_finglish_round_trip() {
+        let persian = transliterate("Salam khoobi? merci, man alan khoone-am", TransliterationDirection::ToPersian);
+        assert_eq!(persian, "سلام خوبی؟ مرسی، من الان خونه-ام");
+        // Spelled letter by letter: short vowels drop, digits stand in for letters
+        assert_eq!(transliterate("dorost 3ali 7al", TransliterationDirection::ToPersian), "درست عالی حال");
+        assert_eq!(transliterate("see @ali_bot #news https://t.me/x 2024", TransliterationDirection::ToPersian), "سی @ali_bot #news https://t.me/x 2024");
+        
+        let latin = transliterate("سلام، خوبی؟ امروز كتاب‌ها رو خریدم ۱۲ تا", TransliterationDirection::ToLatin);
+        // Unwritten short vowels stay missing outside the word table
+        assert_eq!(latin, "salam, khoobi? emrooz ktabha ro khridm 12 ta");
+        assert_eq!(transliterate("این خونه", TransliterationDirection::ToLatin), "in khoone");
+        assert_eq!(TransliterationDirection::from_name("to-latin"), Some(TransliterationDirection::ToLatin));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,182 @@
+use serde::{Serialize, Deserialize};
+use unicode_segmentation::UnicodeSegmentation;
+pub use crate::summary::TELEGRAM_MESSAGE_LIMIT;
+
+// Fits outgoing text into a message or caption. Telegram measures both, and
+// the offsets of message entities, in UTF-16 code units, so cutting by bytes
+// or chars can split an emoji or a Persian letter with its combining marks
+// and leave bold or link entities pointing past the end. The cut here falls
+// between grapheme clusters, preferably at a space, never inside a mention,
+// link or other entity that only works whole, and formatting entities that
+// cross it are shortened to end there. Formatting is expected as entities,
+// not MarkdownV2 markup, which would need reparsing after every cut.
+
+pub const TELEGRAM_CAPTION_LIMIT: usize = 1024;
+
+// Entities that mean nothing cut short; one crossing the cut is left out
+// along with its text
+const ATOMIC_ENTITY_TYPES: &[&str] = &["mention", "hashtag", "cashtag", "bot_command", "url", "email", "phone_number", "custom_emoji"];
+
+// As in the Bot API, with offset and length in UTF-16 code units; fields
+// other than these (url, user, language, custom_emoji_id) are kept as they are
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextEntity {
+    #[serde(rename = "type")]
+    pub entity_type: String,
+    pub offset: usize,
+    pub length: usize,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TextEntity {
+    fn end(&self) -> usize {
+        self.offset + self.length
+    }
+    
+    fn is_atomic(&self) -> bool {
+        ATOMIC_ENTITY_TYPES.contains(&self.entity_type.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TruncateOptions {
+    // Appended after a cut and counted toward the limit; may be empty
+    pub ellipsis: String,
+    // Cuts at the last space that fits rather than mid-word, unless the
+    // text before it has no space at all
+    pub word_boundary: bool,
+}
+
+impl Default for TruncateOptions {
+    fn default() -> Self {
+        Self { ellipsis: "…".to_string(), word_boundary: true }
+    }
+}
+
+impl TruncateOptions {
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid truncate options: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TruncatedText {
+    pub text: String,
+    // Those that survive the cut, shortened where they cross it
+    pub entities: Vec<TextEntity>,
+    pub truncated: bool,
+}
+
+fn length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+// (byte index, UTF-16 offset) of every grapheme boundary, start and end included
+fn boundaries(text: &str) -> Vec<(usize, usize)> {
+    let mut boundaries = vec![(0, 0)];
+    let mut offset = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        offset += length(grapheme);
+        boundaries.push((i + grapheme.len(), offset));
+    }
+    boundaries
+}
+
+pub fn truncate_for_telegram(text: &str, entities: &[TextEntity], limit: usize, options: &TruncateOptions) -> Result<TruncatedText, String> {
+    let total = length(text);
+    if let Some(entity) = entities.iter().find(|entity| entity.end() > total) {
+        return Err(format!("Entity '{}' at {}+{} runs past the end of the text", entity.entity_type, entity.offset, entity.length));
+    }
+    if total <= limit {
+        return Ok(TruncatedText { text: text.to_string(), entities: entities.to_vec(), truncated: false });
+    }
+    
+    // An ellipsis that does not fit is left out rather than cut
+    let ellipsis = if length(&options.ellipsis) <= limit { options.ellipsis.as_str() } else { "" };
+    let budget = limit - length(ellipsis);
+    let boundaries = boundaries(text);
+    // Index into boundaries of the cut
+    let mut cut = boundaries.iter().rposition(|&(_, offset)| offset <= budget).unwrap_or(0);
+    
+    if options.word_boundary {
+        let (byte, _) = boundaries[cut];
+        let at_space = text[byte..].starts_with(char::is_whitespace);
+        if !at_space {
+            if let Some(space) = text[..byte].rfind(char::is_whitespace) {
+                cut = boundaries.iter().rposition(|&(b, _)| b <= space).unwrap_or(0);
+            }
+        }
+    }
+    // Back out of atomic entities until none crosses the cut; each step
+    // moves the cut earlier, so this ends
+    while let Some(entity) = entities.iter().find(|entity| entity.is_atomic() && entity.offset < boundaries[cut].1 && entity.end() > boundaries[cut].1) {
+        cut = boundaries.iter().rposition(|&(_, offset)| offset <= entity.offset).unwrap_or(0);
+    }
+    // No trailing spaces before the ellipsis
+    while cut > 0 && text[boundaries[cut - 1].0..boundaries[cut].0].chars().all(char::is_whitespace) {
+        cut -= 1;
+    }
+    
+    let (byte, offset) = boundaries[cut];
+    let kept = entities.iter()
+        .filter(|entity| entity.offset < offset)
+        .map(|entity| TextEntity { length: entity.end().min(offset) - entity.offset, ..entity.clone() })
+        .collect();
+    Ok(TruncatedText {
+        text: format!("{}{}", &text[..byte], ellipsis),
+        entities: kept,
+        truncated: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn entity(entity_type: &str, offset: usize, length: usize) -> TextEntity {
+        TextEntity { entity_type: entity_type.to_string(), offset, length, extra: serde_json::Map::new() }
+    }
+    
+    #[test]
+    fn test_truncation() {
+        let options = TruncateOptions::default();
+        let short = truncate_for_telegram("hello", &[entity("bold", 0, 5)], 10, &options).unwrap();
+        assert!(!short.truncated);
+        assert_eq!(short.text, "hello");
+        
+        // Bold "hello world" is shortened, the mention after the cut dropped
+        let entities = [entity("bold", 0, 11), entity("mention", 12, 4)];
+        let result = truncate_for_telegram("hello world @bob", &entities, 14, &options).unwrap();
+        assert_eq!(result.text, "hello world…");
+        assert_eq!(result.entities, vec![entity("bold", 0, 11)]);
+        
+        // A mention crossing the cut goes as a whole
+        let result = truncate_for_telegram("hi @alexander there", &[entity("mention", 3, 10)], 12, &TruncateOptions { word_boundary: false, ..Default::default() }).unwrap();
+        assert_eq!(result.text, "hi…");
+        assert!(result.entities.is_empty());
+        
+        // Emoji and Persian letters with marks are not split, and the limit
+        // counts UTF-16 units
+        let result = truncate_for_telegram("👍👍👍", &[], 5, &TruncateOptions { word_boundary: false, ..Default::default() }).unwrap();
+        assert_eq!(result.text, "👍👍…");
+        let persian = "سلامِ دوستانِ عزیز";
+        let result = truncate_for_telegram(persian, &[entity("italic", 6, 7)], 12, &options).unwrap();
+        assert_eq!(result.text, "سلامِ…");
+        assert!(result.entities.is_empty());
+        // The kasra stays with its letter, out of the text
+        let result = truncate_for_telegram(persian, &[], 12, &TruncateOptions { ellipsis: String::new(), word_boundary: false }).unwrap();
+        assert_eq!(result.text, "سلامِ دوستا");
+        
+        let text_link = TextEntity { extra: serde_json::json!({"url": "https://example.com"}).as_object().unwrap().clone(), ..entity("text_link", 0, 9) };
+        let result = truncate_for_telegram("read more here", &[text_link], 10, &TruncateOptions { ellipsis: "...".to_string(), word_boundary: false }).unwrap();
+        assert_eq!(result.text, "read mo...");
+        assert_eq!((result.entities[0].length, &result.entities[0].extra["url"]), (7, &serde_json::json!("https://example.com")));
+        
+        assert!(truncate_for_telegram("hi", &[entity("bold", 1, 5)], 10, &options).is_err());
+    }
+}
\ No newline at end of file
@@ -1,7 +1,9 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::sync::RwLock;
 use regex::Regex;
 use lazy_static::lazy_static;
+use crate::parsers;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationRule {
@@ -93,6 +95,35 @@ impl DataValidator {
         self.rules.insert(schema_name.to_string(), rules);
     }
     
+    // Built-in schemas ("email", "numeric", "json") take precedence over
+    // registered rule sets
+    pub fn validate_with_schema(&self, data: &str, schema_name: &str) -> ValidationResult {
+        match schema_name {
+            "email" => self.validate_email(data),
+            "numeric" => self.validate_numeric_data(data),
+            "json" => self.validate_json(data),
+            name => match self.rules.get(name) {
+                Some(rules) => self.validate_text(data, rules),
+                None => {
+                    let errors = vec![ValidationError {
+                        field: "schema".to_string(),
+                        message: format!("Unknown validation schema: {}", name),
+                        severity: ValidationSeverity::HIGH,
+                        code: "UNKNOWN_SCHEMA".to_string(),
+                    }];
+                    let quality_score = self.calculate_quality_score(&errors, &[]);
+                    
+                    ValidationResult {
+                        is_valid: false,
+                        errors,
+                        warnings: Vec::new(),
+                        quality_score,
+                    }
+                }
+            },
+        }
+    }
+    
     pub fn validate_text(&self, text: &str, rules: &[ValidationRule]) -> ValidationResult {
         let mut errors = Vec::new();
         let mut warnings = Vec::new();
@@ -300,58 +331,14 @@ impl DataValidator {
                 severity: ValidationSeverity::HIGH,
                 code: "EMPTY_JSON".to_string(),
             });
-        } else {
-            // Check for balanced braces/brackets
-            let mut brace_count = 0;
-            let mut bracket_count = 0;
-            let mut in_string = false;
-            let mut escaped = false;
-            
-            for ch in json_str.chars() {
-                if escaped {
-                    escaped = false;
-                    continue;
-                }
-                
-                if ch == '\\' {
-                    escaped = true;
-                    continue;
-                }
-                
-                if ch == '"' && !escaped {
-                    in_string = !in_string;
-                    continue;
-                }
-                
-                if !in_string {
-                    match ch {
-                        '{' => brace_count += 1,
-                        '}' => brace_count -= 1,
-                        '[' => bracket_count += 1,
-                        ']' => bracket_count -= 1,
-                        _ => {}
-                    }
-                }
-                
-                if brace_count < 0 || bracket_count < 0 {
-                    errors.push(ValidationError {
-                        field: "json".to_string(),
-                        message: "Unbalanced JSON structure".to_string(),
-                        severity: ValidationSeverity::HIGH,
-                        code: "UNBALANCED_JSON".to_string(),
-                    });
-                    break;
-                }
-            }
-            
-            if brace_count != 0 || bracket_count != 0 {
-                errors.push(ValidationError {
-                    field: "json".to_string(),
-                    message: "Unbalanced JSON structure".to_string(),
-                    severity: ValidationSeverity::HIGH,
-                    code: "UNBALANCED_JSON".to_string(),
-                });
-            }
+        } else if let Err(e) = parsers::check_json_structure(json_str) {
+            // Balanced, string-aware and depth-limited
+            errors.push(ValidationError {
+                field: "json".to_string(),
+                message: e.to_string(),
+                severity: ValidationSeverity::HIGH,
+                code: e.code().to_string(),
+            });
         }
         
         let quality_score = self.calculate_quality_score(&errors, &warnings);
@@ -436,10 +423,8 @@ impl DataValidator {
     }
 }
 
-// Public validation functions
-pub fn validate_text_input(text: &str) -> ValidationResult {
-    let validator = DataValidator::new();
-    let rules = vec![
+fn default_text_rules() -> Vec<ValidationRule> {
+    vec![
         ValidationRule {
             field_name: "text".to_string(),
             rule_type: ValidationRuleType::TEXT,
@@ -452,9 +437,31 @@ pub fn validate_text_input(text: &str) -> ValidationResult {
             allowed_values: None,
             custom_validator: None,
         }
-    ];
-    
-    validator.validate_text(text, &rules)
+    ]
+}
+
+// Global validator holding the named schemas
+lazy_static! {
+    static ref VALIDATOR: RwLock<DataValidator> = {
+        let mut validator = DataValidator::new();
+        validator.add_schema("text", default_text_rules());
+        RwLock::new(validator)
+    };
+}
+
+pub fn initialize() -> Result<(), String> {
+    lazy_static::initialize(&EMAIL_REGEX);
+    lazy_static::initialize(&URL_REGEX);
+    lazy_static::initialize(&PHONE_REGEX);
+    lazy_static::initialize(&DATE_REGEX);
+    lazy_static::initialize(&VALIDATOR);
+    Ok(())
+}
+
+// Public validation functions
+pub fn validate_text_input(text: &str) -> ValidationResult {
+    let validator = DataValidator::new();
+    validator.validate_text(text, &default_text_rules())
 }
 
 pub fn validate_email_input(email: &str) -> ValidationResult {
@@ -477,6 +484,19 @@ pub fn get_data_quality_metrics(data: &str, data_type: &str) -> DataQualityMetri
     validator.calculate_data_quality_metrics(data, data_type)
 }
 
+pub fn register_schema(schema_name: &str, rules: Vec<ValidationRule>) {
+    if let Ok(mut validator) = VALIDATOR.write() {
+        validator.add_schema(schema_name, rules);
+    }
+}
+
+pub fn validate_with_schema(data: &str, schema_name: &str) -> ValidationResult {
+    match VALIDATOR.read() {
+        Ok(validator) => validator.validate_with_schema(data, schema_name),
+        Err(_) => DataValidator::new().validate_with_schema(data, schema_name),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -522,6 +542,26 @@ mod tests {
         assert!(result.errors.iter().any(|e| e.code == "UNBALANCED_JSON"));
     }
     
+    #[test]
+    fn test_schema_validation() {
+        assert!(validate_with_schema("Hello world", "text").is_valid);
+        assert!(!validate_with_schema("invalid-email", "email").is_valid);
+        assert!(validate_with_schema("1,2,3", "numeric").is_valid);
+        
+        let result = validate_with_schema("anything", "no_such_schema");
+        assert!(!result.is_valid);
+        assert!(result.errors.iter().any(|e| e.code == "UNKNOWN_SCHEMA"));
+        
+        let mut rules = default_text_rules();
+        rules[0].field_name = "command".to_string();
+        rules[0].allowed_values = Some(vec!["/start".to_string(), "/help".to_string()]);
+        register_schema("bot_command", rules);
+        
+        assert!(validate_with_schema("/start", "bot_command").is_valid);
+        let result = validate_with_schema("/stop", "bot_command");
+        assert!(result.errors.iter().any(|e| e.field == "command" && e.code == "INVALID_VALUE"));
+    }
+    
     #[test]
     fn test_data_quality_metrics() {
         let metrics = get_data_quality_metrics("1,2,3,4,5", "numeric");
@@ -0,0 +1,19 @@
+use wasm_bindgen::prelude::*;
+#[cfg(any(feature = "nlp", feature = "stats"))]
+use crate::api;
+
+// Browser bindings for the analysis engine. Results are returned as JSON
+// strings with the same shape as the native analysis structs.
+#[cfg(feature = "nlp")]
+#[wasm_bindgen(js_name = analyzeText)]
+pub fn analyze_text(text: &str) -> Result<String, JsValue> {
+    let result = api::analyze_text(text);
+    serde_json::to_string(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(feature = "stats")]
+#[wasm_bindgen(js_name = analyzeData)]
+pub fn analyze_data(data: &str) -> Result<String, JsValue> {
+    let result = api::analyze_data(data);
+    serde_json::to_string(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+}
\ No newline at end of file
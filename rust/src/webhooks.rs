@@ -0,0 +1,502 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Once, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde::{Serialize, Deserialize};
+use lazy_static::lazy_static;
+use crate::alerts::{self, AlertEvent};
+use crate::config::{AppConfig, WebhookEndpoint, WebhooksConfig};
+use crate::logging;
+
+const REQUEST_TIMEOUT_SECONDS: u64 = 10;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WebhookPriority {
+    LOW,
+    NORMAL,
+    HIGH,
+    CRITICAL,
+}
+
+impl WebhookPriority {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "low" => Some(WebhookPriority::LOW),
+            "normal" => Some(WebhookPriority::NORMAL),
+            "high" => Some(WebhookPriority::HIGH),
+            "critical" => Some(WebhookPriority::CRITICAL),
+            _ => None,
+        }
+    }
+    
+    // Security alerts carry a severity label, log rule alerts the rule's level
+    pub fn of_alert(event: &AlertEvent) -> Self {
+        let level = event.labels.get("severity")
+            .or_else(|| event.labels.get("level"))
+            .map(|level| level.to_lowercase());
+        match level.as_deref() {
+            Some("critical") => WebhookPriority::CRITICAL,
+            Some("high") | Some("error") => WebhookPriority::HIGH,
+            Some("low") | Some("debug") | Some("info") => WebhookPriority::LOW,
+            _ => WebhookPriority::NORMAL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookStats {
+    pub queued: usize,
+    pub delivered: u64,
+    pub retried: u64,
+    pub failed: u64,
+    pub dropped: u64,
+    pub rate_limited: u64,
+}
+
+// Sends one signed request; the HTTP transport in production, a recorder
+// in tests
+pub trait WebhookTransport: Send + Sync {
+    fn post(&self, url: &str, headers: &[(String, String)], body: &str) -> Result<(), String>;
+}
+
+struct HttpTransport;
+
+impl WebhookTransport for HttpTransport {
+    fn post(&self, url: &str, headers: &[(String, String)], body: &str) -> Result<(), String> {
+        let mut request = ureq::post(url)
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+            .set("Content-Type", "application/json");
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        
+        // Non-2xx responses come back as errors and are retried
+        request.send_string(body).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+// Everything needed to send, resolved from the endpoint config when the
+// delivery is queued so a config reload does not affect queued deliveries
+#[derive(Debug, Clone)]
+struct Delivery {
+    endpoint: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    secret: Option<String>,
+    max_per_minute: u32,
+    priority: WebhookPriority,
+    body: String,
+    attempt: u32,
+    due: Instant,
+    seq: u64,
+}
+
+// Ready deliveries pop highest priority first, oldest first within a priority
+impl Ord for Delivery {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority.cmp(&other.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Delivery {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Delivery {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for Delivery {}
+
+#[derive(Default)]
+struct DeliveryQueue {
+    ready: BinaryHeap<Delivery>,
+    // Waiting out a retry backoff or an endpoint's rate limit window
+    delayed: Vec<Delivery>,
+}
+
+impl DeliveryQueue {
+    fn len(&self) -> usize {
+        self.ready.len() + self.delayed.len()
+    }
+    
+    // Makes room by dropping the lowest-priority, newest delivery
+    fn drop_lowest(&mut self) {
+        let mut all: Vec<Delivery> = self.ready.drain().chain(self.delayed.drain(..)).collect();
+        if let Some(index) = all.iter().enumerate().min_by(|(_, a), (_, b)| a.cmp(b)).map(|(index, _)| index) {
+            all.remove(index);
+        }
+        let now = Instant::now();
+        for delivery in all {
+            if delivery.due <= now {
+                self.ready.push(delivery);
+            } else {
+                self.delayed.push(delivery);
+            }
+        }
+    }
+}
+
+struct RateWindow {
+    started: Instant,
+    sent: u32,
+}
+
+pub struct WebhookDispatcher {
+    config: RwLock<WebhooksConfig>,
+    queue: Mutex<DeliveryQueue>,
+    wakeup: Condvar,
+    windows: Mutex<HashMap<String, RateWindow>>,
+    stats: Mutex<WebhookStats>,
+    transport: Box<dyn WebhookTransport>,
+    next_seq: AtomicU64,
+    stopped: AtomicBool,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: WebhooksConfig, transport: Box<dyn WebhookTransport>) -> Self {
+        Self {
+            config: RwLock::new(config),
+            queue: Mutex::new(DeliveryQueue::default()),
+            wakeup: Condvar::new(),
+            windows: Mutex::new(HashMap::new()),
+            stats: Mutex::new(WebhookStats::default()),
+            transport,
+            next_seq: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        }
+    }
+    
+    pub fn set_config(&self, config: WebhooksConfig) {
+        if let Ok(mut current) = self.config.write() {
+            *current = config;
+        }
+    }
+    
+    fn settings(&self) -> WebhooksConfig {
+        self.config.read().map(|config| config.clone()).unwrap_or_default()
+    }
+    
+    // Queues the payload for every endpoint subscribed to the source at this
+    // priority; returns how many deliveries were queued
+    pub fn dispatch(&self, source: &str, priority: WebhookPriority, payload: &serde_json::Value) -> usize {
+        let settings = self.settings();
+        let body = serde_json::json!({
+            "source": source,
+            "priority": priority,
+            "payload": payload,
+        }).to_string();
+        
+        let deliveries: Vec<Delivery> = settings.endpoints.iter()
+            .filter(|endpoint| accepts(endpoint, source, priority))
+            .map(|endpoint| Delivery {
+                endpoint: endpoint.name.clone(),
+                url: endpoint.url.clone(),
+                headers: endpoint.headers.iter().map(|(name, value)| (name.clone(), value.expose().to_string())).collect(),
+                secret: endpoint.secret.as_ref().map(|secret| secret.expose().to_string()),
+                max_per_minute: endpoint.max_per_minute,
+                priority,
+                body: body.clone(),
+                attempt: 0,
+                due: Instant::now(),
+                seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            })
+            .collect();
+        
+        let queued = deliveries.len();
+        if queued == 0 {
+            return 0;
+        }
+        
+        let mut dropped = 0;
+        {
+            let mut queue = lock(&self.queue);
+            for delivery in deliveries {
+                queue.ready.push(delivery);
+            }
+            while queue.len() > settings.queue_capacity.max(1) {
+                queue.drop_lowest();
+                dropped += 1;
+            }
+        }
+        lock(&self.stats).dropped += dropped;
+        self.wakeup.notify_one();
+        queued
+    }
+    
+    // Sends every delivery that is due at `now` and returns when the next
+    // delayed one comes due
+    pub fn run_pending(&self, now: Instant) -> Option<Instant> {
+        {
+            let mut queue = lock(&self.queue);
+            let (due, waiting): (Vec<Delivery>, Vec<Delivery>) = queue.delayed.drain(..).partition(|delivery| delivery.due <= now);
+            queue.delayed = waiting;
+            queue.ready.extend(due);
+        }
+        
+        loop {
+            let next = lock(&self.queue).ready.pop();
+            match next {
+                Some(delivery) => self.attempt(delivery, now),
+                None => break,
+            }
+        }
+        
+        lock(&self.queue).delayed.iter().map(|delivery| delivery.due).min()
+    }
+    
+    fn attempt(&self, mut delivery: Delivery, now: Instant) {
+        if let Some(window_end) = self.take_rate_slot(&delivery, now) {
+            lock(&self.stats).rate_limited += 1;
+            delivery.due = window_end;
+            lock(&self.queue).delayed.push(delivery);
+            return;
+        }
+        
+        let headers = signed_headers(&delivery);
+        match self.transport.post(&delivery.url, &headers, &delivery.body) {
+            Ok(()) => lock(&self.stats).delivered += 1,
+            Err(error) => {
+                let settings = self.settings();
+                delivery.attempt += 1;
+                if delivery.attempt >= settings.max_attempts {
+                    lock(&self.stats).failed += 1;
+                    logging::warn(
+                        &format!("Webhook {} failed after {} attempts: {}", delivery.endpoint, delivery.attempt, error),
+                        "webhooks", "attempt", line!(),
+                    );
+                    return;
+                }
+                
+                lock(&self.stats).retried += 1;
+                delivery.due = now + backoff(&settings, delivery.attempt);
+                lock(&self.queue).delayed.push(delivery);
+            }
+        }
+    }
+    
+    // Returns when the endpoint's window reopens if it is already full
+    fn take_rate_slot(&self, delivery: &Delivery, now: Instant) -> Option<Instant> {
+        let mut windows = lock(&self.windows);
+        let window = windows.entry(delivery.endpoint.clone()).or_insert(RateWindow { started: now, sent: 0 });
+        if now.saturating_duration_since(window.started) >= RATE_WINDOW {
+            window.started = now;
+            window.sent = 0;
+        }
+        
+        if window.sent >= delivery.max_per_minute {
+            return Some(window.started + RATE_WINDOW);
+        }
+        window.sent += 1;
+        None
+    }
+    
+    pub fn stats(&self) -> WebhookStats {
+        let mut stats = lock(&self.stats).clone();
+        stats.queued = lock(&self.queue).len();
+        stats
+    }
+    
+    fn run(&self) {
+        while !self.stopped.load(Ordering::SeqCst) {
+            let next_due = self.run_pending(Instant::now());
+            
+            let queue = lock(&self.queue);
+            if queue.ready.is_empty() && !self.stopped.load(Ordering::SeqCst) {
+                let wait = next_due
+                    .map(|due| due.saturating_duration_since(Instant::now()))
+                    .unwrap_or(IDLE_WAIT);
+                let _ = self.wakeup.wait_timeout(queue, wait);
+            }
+        }
+    }
+}
+
+fn accepts(endpoint: &WebhookEndpoint, source: &str, priority: WebhookPriority) -> bool {
+    let min_priority = WebhookPriority::parse(&endpoint.min_priority).unwrap_or(WebhookPriority::LOW);
+    priority >= min_priority && (endpoint.sources.is_empty() || endpoint.sources.iter().any(|s| s == source))
+}
+
+fn backoff(settings: &WebhooksConfig, attempt: u32) -> Duration {
+    let factor = 1u64 << (attempt - 1).min(20);
+    Duration::from_millis(settings.initial_backoff_ms.saturating_mul(factor).min(settings.max_backoff_ms))
+}
+
+// Receivers verify X-Aiogram-Signature = "sha256=" + hex(HMAC-SHA256(secret,
+// timestamp + "." + body)) and reject stale timestamps to stop replays
+fn signed_headers(delivery: &Delivery) -> Vec<(String, String)> {
+    let mut headers = delivery.headers.clone();
+    let secret = match delivery.secret {
+        Some(ref secret) => secret,
+        None => return headers,
+    };
+    
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    headers.push(("X-Aiogram-Timestamp".to_string(), timestamp.to_string()));
+    headers.push(("X-Aiogram-Signature".to_string(), format!("sha256={}", sign(secret, timestamp, &delivery.body))));
+    headers
+}
+
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+// Global dispatcher, fed by the alert manager
+lazy_static! {
+    static ref DISPATCHER: Arc<WebhookDispatcher> = Arc::new(WebhookDispatcher::new(
+        AppConfig::get().webhooks,
+        Box::new(HttpTransport),
+    ));
+    static ref WORKER: Mutex<Option<thread::JoinHandle<()>>> = Mutex::new(None);
+}
+
+static SUBSCRIBE: Once = Once::new();
+
+pub fn initialize() -> Result<(), String> {
+    SUBSCRIBE.call_once(|| alerts::subscribe(Arc::new(dispatch_alert)));
+    
+    let mut worker = lock(&WORKER);
+    if worker.is_none() {
+        DISPATCHER.stopped.store(false, Ordering::SeqCst);
+        let dispatcher = Arc::clone(&DISPATCHER);
+        let handle = thread::Builder::new()
+            .name("aiogram-webhooks".to_string())
+            .spawn(move || dispatcher.run())
+            .map_err(|e| format!("Failed to start webhook dispatcher: {}", e))?;
+        *worker = Some(handle);
+    }
+    Ok(())
+}
+
+// Deliveries still queued are kept and sent after the next initialize
+pub fn shutdown() {
+    if let Some(handle) = lock(&WORKER).take() {
+        DISPATCHER.stopped.store(true, Ordering::SeqCst);
+        DISPATCHER.wakeup.notify_all();
+        let _ = handle.join();
+    }
+}
+
+fn dispatch_alert(event: &AlertEvent) {
+    if let Ok(payload) = serde_json::to_value(event) {
+        DISPATCHER.dispatch(&event.source, WebhookPriority::of_alert(event), &payload);
+    }
+}
+
+// Public webhook functions
+pub fn dispatch(source: &str, priority: WebhookPriority, payload: &serde_json::Value) -> usize {
+    DISPATCHER.dispatch(source, priority, payload)
+}
+
+pub fn get_stats() -> WebhookStats {
+    DISPATCHER.stats()
+}
+
+pub fn reload_config() {
+    DISPATCHER.set_config(AppConfig::get().webhooks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::Secret;
+    
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent: Arc<Mutex<Vec<(Vec<(String, String)>, String)>>>,
+        failures: Arc<AtomicU64>,
+    }
+    
+    impl WebhookTransport for RecordingTransport {
+        fn post(&self, _url: &str, headers: &[(String, String)], body: &str) -> Result<(), String> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err("503 Service Unavailable".to_string());
+            }
+            self.sent.lock().unwrap().push((headers.to_vec(), body.to_string()));
+            Ok(())
+        }
+    }
+    
+    fn endpoint(max_per_minute: u32) -> WebhookEndpoint {
+        WebhookEndpoint {
+            name: "ops".to_string(),
+            url: "http://localhost/hook".to_string(),
+            secret: Some(Secret::new("s3cret")),
+            sources: vec!["security".to_string()],
+            min_priority: "normal".to_string(),
+            max_per_minute,
+            headers: HashMap::new(),
+        }
+    }
+    
+    fn dispatcher(max_per_minute: u32) -> (WebhookDispatcher, RecordingTransport) {
+        let transport = RecordingTransport::default();
+        let recorder = RecordingTransport { sent: Arc::clone(&transport.sent), failures: Arc::clone(&transport.failures) };
+        let config = WebhooksConfig { endpoints: vec![endpoint(max_per_minute)], max_attempts: 3, initial_backoff_ms: 1000, ..Default::default() };
+        (WebhookDispatcher::new(config, Box::new(transport)), recorder)
+    }
+    
+    #[test]
+    fn test_priority_order_and_signing() {
+        let (dispatcher, recorder) = dispatcher(60);
+        assert_eq!(dispatcher.dispatch("security", WebhookPriority::NORMAL, &serde_json::json!({ "n": 1 })), 1);
+        assert_eq!(dispatcher.dispatch("security", WebhookPriority::CRITICAL, &serde_json::json!({ "n": 2 })), 1);
+        // Filtered by source and by min_priority
+        assert_eq!(dispatcher.dispatch("log", WebhookPriority::CRITICAL, &serde_json::json!({})), 0);
+        assert_eq!(dispatcher.dispatch("security", WebhookPriority::LOW, &serde_json::json!({})), 0);
+        
+        dispatcher.run_pending(Instant::now());
+        let sent = recorder.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].1.contains("\"n\":2"));
+        
+        let (headers, body) = &sent[0];
+        let header = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone()).unwrap();
+        let timestamp: u64 = header("X-Aiogram-Timestamp").parse().unwrap();
+        assert_eq!(header("X-Aiogram-Signature"), format!("sha256={}", sign("s3cret", timestamp, body)));
+    }
+    
+    #[test]
+    fn test_retries_and_rate_limits() {
+        let (dispatcher, recorder) = dispatcher(1);
+        let start = Instant::now();
+        recorder.failures.store(1, Ordering::SeqCst);
+        dispatcher.dispatch("security", WebhookPriority::HIGH, &serde_json::json!({}));
+        
+        // First attempt fails and is retried after the initial backoff
+        let next = dispatcher.run_pending(start).unwrap();
+        assert_eq!(next, start + Duration::from_millis(1000));
+        assert_eq!(dispatcher.stats().retried, 1);
+        
+        // The failed attempt used the endpoint's only slot this minute
+        dispatcher.dispatch("security", WebhookPriority::HIGH, &serde_json::json!({}));
+        dispatcher.run_pending(next);
+        assert_eq!(dispatcher.stats().rate_limited, 2);
+        assert!(recorder.sent.lock().unwrap().is_empty());
+        
+        dispatcher.run_pending(start + RATE_WINDOW);
+        let stats = dispatcher.stats();
+        assert_eq!(stats.delivered, 1);
+        assert_eq!(stats.queued, 1);
+    }
+}
\ No newline at end of file